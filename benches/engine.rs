@@ -0,0 +1,54 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lemon_chess_api::game::{
+    color::Color,
+    render::{self, theme::Theme},
+    state::GameState,
+};
+
+/// Kiwipete, a middlegame with castling, pins and en passant for both sides
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+fn legal_moves(c: &mut Criterion) {
+    let state = GameState::from_fen(KIWIPETE).unwrap();
+    c.bench_function("legal moves", |b| {
+        b.iter(|| black_box(&state).get_legal_moves(Color::WHITE).unwrap())
+    });
+    c.bench_function("perft 3", |b| {
+        b.iter(|| black_box(&state).perft(3).unwrap())
+    });
+}
+
+fn check_detection(c: &mut Criterion) {
+    let state = GameState::from_fen(KIWIPETE).unwrap();
+    let board = &state.chess_board;
+    c.bench_function("checkers", |b| {
+        b.iter(|| black_box(board).get_checkers(Color::WHITE))
+    });
+    c.bench_function("pinned mask", |b| {
+        b.iter(|| black_box(board).get_pinned_mask(Color::WHITE))
+    });
+}
+
+fn fen_roundtrip(c: &mut Criterion) {
+    c.bench_function("fen roundtrip", |b| {
+        b.iter(|| GameState::from_fen(black_box(KIWIPETE)).unwrap().to_fen())
+    });
+}
+
+fn full_render(c: &mut Criterion) {
+    render::preload_sprites().unwrap();
+    let state = GameState::from_fen(KIWIPETE).unwrap();
+    let theme = Theme::find("classic").unwrap();
+    c.bench_function("render", |b| {
+        b.iter(|| render::render(black_box(&state), Color::WHITE, &theme).unwrap())
+    });
+}
+
+criterion_group!(
+    benches,
+    legal_moves,
+    check_detection,
+    fen_roundtrip,
+    full_render
+);
+criterion_main!(benches);
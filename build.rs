@@ -0,0 +1,9 @@
+fn main() {
+    built::write_built_file().expect("Failed to collect build information.");
+
+    // The vendored protoc keeps the build from depending on a system installation
+    let protoc = protoc_bin_vendored::protoc_bin_path().expect("Failed to find protoc.");
+    std::env::set_var("PROTOC", protoc);
+    tonic_prost_build::compile_protos("proto/lemon_chess.proto")
+        .expect("Failed to compile the protobuf definitions.");
+}
@@ -19,17 +19,14 @@ impl BitBoard {
         (self.0 & (1 << index)) != 0
     }
 
-    /// Returns the indizes of all 1's
-    pub fn get_bits(&self) -> Vec<u8> {
-        let mut result: Vec<u8> = Vec::new();
-
-        for i in 0..64 {
-            if (self.0 & (1 << i)) != 0 {
-                result.push(i);
-            }
-        }
+    /// Iterates over the indizes of all 1's in ascending order
+    pub fn iter(&self) -> BitIter {
+        BitIter(self.0)
+    }
 
-        result
+    /// Amount of 1's
+    pub fn count(&self) -> u32 {
+        self.0.count_ones()
     }
 
     pub fn set_bit(&mut self, index: u8) {
@@ -86,14 +83,14 @@ impl BitBoard {
     }
 
     pub fn populate_left(&mut self, index: u8, steps: u8, block_mask: BitBoard) {
-        if index % 8 == 0 {
+        if index.is_multiple_of(8) {
             return;
         }
 
         let mut current_index = index.wrapping_sub(1);
         for _ in 0..steps {
             self.set_bit(current_index);
-            if block_mask.get_bit(current_index) || current_index % 8 == 0 {
+            if block_mask.get_bit(current_index) || current_index.is_multiple_of(8) {
                 break;
             }
             current_index = current_index.wrapping_sub(1);
@@ -105,13 +102,11 @@ impl BitBoard {
             return;
         }
 
-        let mut current_index = index + 1;
-        for _ in 0..steps {
+        for current_index in (index + 1..64).take(steps as usize) {
             self.set_bit(current_index);
             if block_mask.get_bit(current_index) || current_index % 8 == 7 {
                 break;
             }
-            current_index += 1;
         }
     }
 
@@ -134,7 +129,7 @@ impl BitBoard {
     }
 
     pub fn populate_up_left(&mut self, index: u8, steps: u8, block_mask: BitBoard) {
-        if index % 8 == 0 {
+        if index.is_multiple_of(8) {
             return;
         }
 
@@ -144,7 +139,7 @@ impl BitBoard {
                 break;
             }
             self.set_bit(current_index);
-            if block_mask.get_bit(current_index) || current_index % 8 == 0 {
+            if block_mask.get_bit(current_index) || current_index.is_multiple_of(8) {
                 break;
             }
             current_index += 7;
@@ -170,7 +165,7 @@ impl BitBoard {
     }
 
     pub fn populate_down_left(&mut self, index: u8, steps: u8, block_mask: BitBoard) {
-        if index % 8 == 0 || index < 8 {
+        if index.is_multiple_of(8) || index < 8 {
             return;
         }
 
@@ -180,7 +175,7 @@ impl BitBoard {
                 break;
             }
             self.set_bit(current_index);
-            if block_mask.get_bit(current_index) || current_index % 8 == 0 {
+            if block_mask.get_bit(current_index) || current_index.is_multiple_of(8) {
                 break;
             }
             current_index = current_index.wrapping_sub(9);
@@ -223,6 +218,38 @@ impl BitBoard {
     }
 }
 
+/// Pops the lowest 1 on every step, so it only takes as many steps as there are bits set
+pub struct BitIter(u64);
+
+impl Iterator for BitIter {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0 == 0 {
+            return None;
+        }
+        let index = self.0.trailing_zeros() as u8;
+        self.0 &= self.0 - 1;
+        Some(index)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.0.count_ones() as usize;
+        (count, Some(count))
+    }
+}
+
+impl ExactSizeIterator for BitIter {}
+
+impl IntoIterator for BitBoard {
+    type Item = u8;
+    type IntoIter = BitIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
 impl From<Vec<u8>> for BitBoard {
     fn from(indices: Vec<u8>) -> Self {
         let bits: u64 = indices
@@ -372,6 +399,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_iter() {
+        let board = BitBoard(0b1000000000000000000000000000000000000000000000000010000000000101);
+        assert_eq!(board.iter().collect::<Vec<u8>>(), vec![0, 2, 13, 63]);
+        assert_eq!(board.iter().len(), 4);
+        assert_eq!(board.count(), 4);
+        assert_eq!(BitBoard(0).iter().next(), None);
+        assert_eq!(BitBoard(0).count(), 0);
+    }
+
     #[test]
     fn test_flip_bit() {
         let mut board = BitBoard(u64::MAX);
@@ -0,0 +1,1356 @@
+use crate::{
+    bit_board::BitBoard,
+    color::Color,
+    error::GameError,
+    piece::Piece,
+    rays::{
+        diagonal_rays, orthogonal_rays, BETWEEN, KING_ATTACKS, KNIGHT_ATTACKS, LINE, PAWN_ATTACKS,
+    },
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+
+use super::position::{Move, MoveKind, Position, Square};
+
+/// Cells where file + rank is odd, A1 being a dark cell
+const LIGHT_CELLS: BitBoard = BitBoard(0x55AA_55AA_55AA_55AA);
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+/// INFERENCES:
+/// - Board is always 8x8
+/// - Only 1 King
+/// - Rooks always on either side of the king
+/// - White is always at the bottom of the board (at least internally, board can be rotated for visualization)
+pub struct ChessBoard {
+    pub colors: [BitBoard; 2],
+    pub pieces: [BitBoard; 6],
+}
+
+// Representation can later be individualized by rotating the board
+// Index starts in the bottom left
+impl Default for ChessBoard {
+    fn default() -> Self {
+        Self {
+            colors: [
+                BitBoard(0b0000000000000000000000000000000000000000000000001111111111111111),
+                BitBoard(0b1111111111111111000000000000000000000000000000000000000000000000),
+            ],
+            pieces: [
+                BitBoard(0b0000000011111111000000000000000000000000000000001111111100000000), // Pawn
+                BitBoard(0b0010010000000000000000000000000000000000000000000000000000100100), // Bishop
+                BitBoard(0b0100001000000000000000000000000000000000000000000000000001000010), // Knight
+                BitBoard(0b1000000100000000000000000000000000000000000000000000000010000001), // Rook
+                BitBoard(0b0000100000000000000000000000000000000000000000000000000000001000), // Queen
+                BitBoard(0b0001000000000000000000000000000000000000000000000000000000010000), // King
+            ],
+        }
+    }
+}
+
+/// Everything needed to take back a move made with apply_move
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoveUndo {
+    pub from: u8,
+    pub to: u8,
+    pub piece: Piece,
+    pub color: Color,
+    /// The captured piece and its cell, which differs from the target cell on en passant
+    pub captured: Option<(Piece, u8)>,
+    pub promotion: bool,
+    pub en_passant_indices: [u8; 2],
+    pub kingside_castling_rights: [bool; 2],
+    pub queenside_castling_rights: [bool; 2],
+}
+
+#[derive(PartialEq, Eq, Clone, Debug, Default, Hash)]
+/// Describes all available moves, castling excluded
+pub struct AvailableMoves(pub Vec<Move>);
+
+impl AvailableMoves {
+    pub fn get_moves(&self) -> &[Move] {
+        &self.0
+    }
+
+    pub fn get_moves_in_notation(&self) -> Vec<String> {
+        self.0.iter().copied().map(String::from).collect()
+    }
+
+    /// The move going from and to the given cells, if it is available
+    pub fn find_move(&self, from: Square, to: Square) -> Option<Move> {
+        self.0
+            .iter()
+            .copied()
+            .find(|m| m.from == from && m.to == to)
+    }
+
+    pub fn has_move(&self, from: Square, to: Square) -> bool {
+        self.find_move(from, to).is_some()
+    }
+
+    pub fn has_moves(&self) -> bool {
+        !self.0.is_empty()
+    }
+}
+
+/// Why a move can't be played, so clients can explain rejected moves
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum IllegalMoveReason {
+    NotYourTurn,
+    /// The starting cell is empty
+    PieceMissing,
+    /// The piece on the starting cell belongs to the opponent
+    OpponentPiece,
+    /// One of your own pieces stands on the destination cell
+    OwnPieceOnTarget,
+    /// The piece could reach the destination cell if another piece wasn't in the way
+    BlockedPath,
+    /// The piece doesn't move like that
+    UnreachableCell,
+    /// The move would leave or put your own king in check
+    LeavesKingInCheck,
+    /// A promotion was asked for, but the move doesn't bring a pawn to the last rank
+    WrongPromotionRank,
+    /// The king or the rook moved already
+    CastlingRightsLost,
+    /// Pieces stand between king and rook or on their destination cells
+    CastlingBlocked,
+    /// The king is in check or would pass or end on an attacked cell
+    CastlingThroughCheck,
+}
+
+impl IllegalMoveReason {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Self::NotYourTurn => "It's not your turn.",
+            Self::PieceMissing => "There is no piece on the starting cell.",
+            Self::OpponentPiece => "The piece on the starting cell belongs to your opponent.",
+            Self::OwnPieceOnTarget => "One of your own pieces stands on the destination cell.",
+            Self::BlockedPath => "Another piece blocks the way to the destination cell.",
+            Self::UnreachableCell => "The piece can't move to the destination cell.",
+            Self::LeavesKingInCheck => "The move would leave your king in check.",
+            Self::WrongPromotionRank => "Only pawns reaching the last rank can be promoted.",
+            Self::CastlingRightsLost => "The king or the rook moved already.",
+            Self::CastlingBlocked => "Pieces stand between the king and the rook.",
+            Self::CastlingThroughCheck => "The king can't castle out of, through or into check.",
+        }
+    }
+}
+
+impl ChessBoard {
+    pub const ENCODED_LENGTH: usize = 64;
+
+    pub fn new_empty() -> Self {
+        Self {
+            colors: [BitBoard(0), BitBoard(0)],
+            pieces: [
+                BitBoard(0),
+                BitBoard(0),
+                BitBoard(0),
+                BitBoard(0),
+                BitBoard(0),
+                BitBoard(0),
+            ],
+        }
+    }
+
+    pub fn validate_index(index: u8) -> Result<(), GameError> {
+        if index >= 64 {
+            return Err(GameError::ValidationError(
+                "Board address out of range.".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn to_fen_positions(&self) -> String {
+        let mut result = String::new();
+        let mut row_string = String::new();
+        let mut empty_cells: u8 = 0;
+        for i in (0..64).rev() {
+            let is_end_of_row = i % 8 == 0;
+            let (piece, color) = self.piece_and_color_at_cell(i).unwrap();
+            let has_piece = piece != Piece::NONE && color != Color::NONE;
+
+            if !has_piece {
+                empty_cells += 1;
+                if !is_end_of_row {
+                    continue;
+                }
+            }
+
+            if empty_cells > 0 {
+                row_string.push_str(&empty_cells.to_string());
+                empty_cells = 0;
+            }
+
+            if has_piece {
+                row_string.push_str(&piece.get_fen_letter(color));
+            }
+
+            if is_end_of_row {
+                let reversed_row_string = row_string.chars().rev().collect::<String>();
+                result.push_str(&reversed_row_string);
+                row_string = String::new();
+
+                // Prevents trailing / at the end
+                if i != 0 {
+                    result.push('/');
+                }
+            }
+        }
+
+        result
+    }
+
+    pub fn from_fen_positions(fen: &str) -> Result<Self, GameError> {
+        let mut board = Self::new_empty();
+
+        let fen_rows: Vec<&str> = fen.split('/').collect();
+        if fen_rows.len() != 8 {
+            return Err(GameError::ValidationError(format!(
+                "FEN-String needs 8 rows, found {}",
+                fen_rows.len()
+            )));
+        }
+
+        for (row_index, fen_row) in fen_rows.into_iter().enumerate() {
+            let row = 7 - row_index;
+            let mut column: u32 = 0;
+            for fen_char in fen_row.chars() {
+                if let Some(digit) = fen_char.to_digit(10) {
+                    if digit == 0 {
+                        return Err(GameError::ValidationError(format!(
+                            "Invalid digit in FEN-String in row {}",
+                            row_index + 1
+                        )));
+                    }
+                    column += digit;
+                    continue;
+                }
+
+                if column >= 8 {
+                    return Err(GameError::ValidationError(format!(
+                        "Row {} of the FEN-String is longer than 8 cells",
+                        row_index + 1
+                    )));
+                }
+
+                let (piece, color) = Piece::from_fen_letter(fen_char);
+                if piece == Piece::NONE {
+                    return Err(GameError::ValidationError(format!(
+                        "Found invalid character in FEN-String: {}",
+                        fen_char
+                    )));
+                }
+                let index = ((row * 8) + column as usize) as u8;
+                board.place_piece(index, piece, color)?;
+                column += 1;
+            }
+
+            if column != 8 {
+                return Err(GameError::ValidationError(format!(
+                    "Row {} of the FEN-String covers {} instead of 8 cells",
+                    row_index + 1,
+                    column
+                )));
+            }
+        }
+
+        board.validate()?;
+        Ok(board)
+    }
+
+    pub fn to_base64(&self) -> Result<String, GameError> {
+        Ok(STANDARD.encode(self.to_bytes()))
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self, GameError> {
+        let decoded = STANDARD.decode(encoded)?;
+        Self::from_bytes(&decoded)
+    }
+
+    /// The color boards followed by the piece boards, 8 big endian bytes each
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bit_vec = Vec::with_capacity(Self::ENCODED_LENGTH);
+
+        for color_board in &self.colors {
+            bit_vec.extend_from_slice(&color_board.0.to_be_bytes());
+        }
+        for piece_board in &self.pieces {
+            bit_vec.extend_from_slice(&piece_board.0.to_be_bytes());
+        }
+
+        bit_vec
+    }
+
+    pub fn from_bytes(decoded: &[u8]) -> Result<Self, GameError> {
+        if decoded.len() != Self::ENCODED_LENGTH {
+            return Err(GameError::EncodingError("Invalid length.".to_string()));
+        }
+
+        let mut colors = [BitBoard(0); 2];
+        let mut pieces = [BitBoard(0); 6];
+
+        for (i, color_board) in colors.iter_mut().enumerate() {
+            let start = i * 8;
+            let bytes = &decoded[start..start + 8];
+            color_board.0 = u64::from_be_bytes(bytes.try_into().unwrap());
+        }
+
+        for (i, piece_board) in pieces.iter_mut().enumerate() {
+            let start = (2 + i) * 8;
+            let bytes = &decoded[start..start + 8];
+            piece_board.0 = u64::from_be_bytes(bytes.try_into().unwrap());
+        }
+
+        let board = ChessBoard { colors, pieces };
+        board.validate()?;
+        Ok(board)
+    }
+
+    /// Makes sure the board is a position the engine can work with,
+    /// e.g. a missing king would otherwise silently be looked up on cell 0
+    pub fn validate(&self) -> Result<(), GameError> {
+        if (self.colors[0] & self.colors[1]).0 != 0 {
+            return Err(GameError::ValidationError(
+                "Cells are occupied by both colors.".to_string(),
+            ));
+        }
+
+        let mut occupied = BitBoard::default();
+        for piece_board in self.pieces {
+            if (occupied & piece_board).0 != 0 {
+                return Err(GameError::ValidationError(
+                    "Cells are occupied by multiple pieces.".to_string(),
+                ));
+            }
+            occupied = occupied | piece_board;
+        }
+        if occupied != self.colors[0] | self.colors[1] {
+            return Err(GameError::ValidationError(
+                "Pieces and colors don't occupy the same cells.".to_string(),
+            ));
+        }
+
+        for (color, name) in [(Color::WHITE, "white"), (Color::BLACK, "black")] {
+            let kings = self.mask_by_piece_and_color(Piece::KING, color).count();
+            if kings != 1 {
+                return Err(GameError::ValidationError(format!(
+                    "Expected exactly one {} king, found {}.",
+                    name, kings
+                )));
+            }
+        }
+
+        let back_ranks = BitBoard(0xFF000000000000FF);
+        if (self.pieces[Piece::PAWN as usize] & back_ranks).0 != 0 {
+            return Err(GameError::ValidationError(
+                "Pawns can't stand on the first or last rank.".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn is_cell_occupied(&self, index: u8) -> Result<bool, GameError> {
+        Self::validate_index(index)?;
+        let occupied_by_white = self.colors[0].get_bit(index);
+        let occupied_by_black = self.colors[1].get_bit(index);
+        Ok(occupied_by_black || occupied_by_white)
+    }
+
+    pub fn piece_at_cell(&self, index: u8) -> Result<Piece, GameError> {
+        Self::validate_index(index)?;
+        for piece_id in 0..6 {
+            if self.pieces[piece_id].get_bit(index) {
+                return Ok(Piece::from(piece_id));
+            }
+        }
+
+        Ok(Piece::NONE)
+    }
+
+    pub fn color_at_cell(&self, index: u8) -> Result<Color, GameError> {
+        Self::validate_index(index)?;
+        for color_id in 0..2 {
+            if self.colors[color_id].get_bit(index) {
+                return Ok(Color::from(color_id));
+            }
+        }
+
+        Ok(Color::NONE)
+    }
+
+    pub fn piece_and_color_at_cell(&self, index: u8) -> Result<(Piece, Color), GameError> {
+        let piece = Self::piece_at_cell(self, index)?;
+        let color = Self::color_at_cell(self, index)?;
+        Ok((piece, color))
+    }
+
+    pub fn place_piece(&mut self, index: u8, piece: Piece, color: Color) -> Result<(), GameError> {
+        Self::validate_index(index)?;
+        if piece == Piece::NONE || color == Color::NONE {
+            return Err(GameError::ValidationError(
+                "Unable to place undefined piece or piece with undefined color".to_string(),
+            ));
+        }
+
+        self.pieces[piece as usize].set_bit(index);
+        self.colors[color as usize].set_bit(index);
+
+        Ok(())
+    }
+
+    pub fn mask_by_piece_and_color(&self, piece: Piece, color: Color) -> BitBoard {
+        self.pieces[piece as usize] & self.colors[color as usize]
+    }
+
+    pub fn relocate_piece(&mut self, from: u8, to: u8) -> Result<(), GameError> {
+        Self::validate_index(to)?;
+        let (piece, color) = self.piece_and_color_at_cell(from)?;
+        self.pieces[piece as usize].clear_bit(from);
+        self.pieces[piece as usize].set_bit(to);
+        self.colors[color as usize].clear_bit(from);
+        self.colors[color as usize].set_bit(to);
+        Ok(())
+    }
+
+    /// Returns a tuple of (success, was_capture_or_pawn_move, san_move)
+    pub fn make_move(
+        &mut self,
+        from: u8,
+        to: u8,
+        en_passant_indices: &mut [u8; 2],
+        kingside_castling_rights: &mut [bool; 2],
+        queenside_castling_rights: &mut [bool; 2],
+    ) -> Result<(bool, bool, String), GameError> {
+        let undo = match self.apply_move(
+            from,
+            to,
+            en_passant_indices,
+            kingside_castling_rights,
+            queenside_castling_rights,
+        )? {
+            Some(undo) => undo,
+            None => return Ok((false, false, String::new())),
+        };
+
+        let pawn_move = undo.piece == Piece::PAWN;
+        let capture_move = undo.captured.is_some();
+        let did_en_passant = undo.captured.is_some_and(|(_, index)| index != to);
+
+        let from_str = Position::try_from(from)?.as_str().to_lowercase();
+        let to_str = Position::try_from(to)?.as_str().to_lowercase();
+
+        // SAN / Standard Algebraic Notation
+        let san_move = if pawn_move && !capture_move {
+            if undo.promotion {
+                format!("{}Q", to_str)
+            } else {
+                to_str
+            }
+        } else if pawn_move && capture_move {
+            if undo.promotion {
+                format!("{}x{}Q", from_str, to_str)
+            } else if did_en_passant {
+                format!("{}x{} e.p.", from_str, to_str)
+            } else {
+                format!("{}x{}", from_str, to_str)
+            }
+        } else {
+            format!("{}{}x{}", undo.piece.get_letter(), from_str, to_str)
+        };
+
+        Ok((true, capture_move || pawn_move, san_move))
+    }
+
+    /// Moves the piece and updates en passant and castling rights, without any notation
+    /// Returns None if there is no piece to move or the target is occupied by the same color
+    pub fn apply_move(
+        &mut self,
+        from: u8,
+        to: u8,
+        en_passant_indices: &mut [u8; 2],
+        kingside_castling_rights: &mut [bool; 2],
+        queenside_castling_rights: &mut [bool; 2],
+    ) -> Result<Option<MoveUndo>, GameError> {
+        Self::validate_index(from)?;
+        Self::validate_index(to)?;
+
+        let (source_piece, source_color) = Self::piece_and_color_at_cell(self, from)?;
+        let (target_piece, target_color) = Self::piece_and_color_at_cell(self, to)?;
+        if source_piece == Piece::NONE || target_color == source_color {
+            return Ok(None);
+        }
+
+        let mut undo = MoveUndo {
+            from,
+            to,
+            piece: source_piece,
+            color: source_color,
+            captured: None,
+            promotion: false,
+            en_passant_indices: *en_passant_indices,
+            kingside_castling_rights: *kingside_castling_rights,
+            queenside_castling_rights: *queenside_castling_rights,
+        };
+
+        // Capture piece
+        if target_piece != Piece::NONE {
+            self.pieces[target_piece as usize].clear_bit(to);
+            self.colors[target_color as usize].clear_bit(to);
+            undo.captured = Some((target_piece, to));
+        }
+
+        // Update piece
+        let piece_index = source_piece as usize;
+        self.pieces[piece_index].clear_bit(from);
+        self.pieces[piece_index].set_bit(to);
+
+        // Update color
+        let color_index = source_color as usize;
+        self.colors[color_index].clear_bit(from);
+        self.colors[color_index].set_bit(to);
+
+        // Capture en-passant
+        let opponent_color = source_color.opponent_color();
+        if source_piece == Piece::PAWN && to == en_passant_indices[opponent_color as usize] {
+            let captured_pawn_index = match opponent_color {
+                Color::BLACK => to - 8,
+                Color::WHITE => to + 8,
+                Color::NONE => to + 8,
+            };
+            self.pieces[Piece::PAWN as usize].clear_bit(captured_pawn_index);
+            self.colors[opponent_color as usize].clear_bit(captured_pawn_index);
+            en_passant_indices[opponent_color as usize] = 64;
+            undo.captured = Some((Piece::PAWN, captured_pawn_index));
+        }
+
+        // Update en-passant
+        if source_piece == Piece::PAWN && to.abs_diff(from) == 16 {
+            en_passant_indices[source_color as usize] = match source_color {
+                Color::BLACK => to + 8,
+                Color::WHITE => to - 8,
+                Color::NONE => to - 8,
+            }
+        } else {
+            en_passant_indices[source_color as usize] = 64;
+        }
+
+        // King has moved, castling rights removed
+        if source_piece == Piece::KING {
+            kingside_castling_rights[color_index] = false;
+            queenside_castling_rights[color_index] = false;
+        }
+
+        // Update kingside castling rights
+        if kingside_castling_rights[color_index] && source_piece == Piece::ROOK {
+            let king_index = self.get_king_position_by_color(source_color);
+            // rook is kingside
+            if from > king_index {
+                kingside_castling_rights[color_index] = false;
+            }
+        }
+
+        // Update queenside castling rights
+        if queenside_castling_rights[color_index] && source_piece == Piece::ROOK {
+            let king_index = self.get_king_position_by_color(source_color);
+            if from < king_index {
+                queenside_castling_rights[color_index] = false;
+            }
+        }
+
+        // Check for queen promotion
+        if source_piece == Piece::PAWN && !(8..=55).contains(&to) {
+            self.pieces[Piece::PAWN as usize].clear_bit(to);
+            self.pieces[Piece::QUEEN as usize].set_bit(to);
+            undo.promotion = true;
+        }
+
+        Ok(Some(undo))
+    }
+
+    /// Takes back a move made with apply_move, restoring the board, en passant and castling rights
+    pub fn undo_move(
+        &mut self,
+        undo: &MoveUndo,
+        en_passant_indices: &mut [u8; 2],
+        kingside_castling_rights: &mut [bool; 2],
+        queenside_castling_rights: &mut [bool; 2],
+    ) {
+        let placed_piece = if undo.promotion {
+            Piece::QUEEN
+        } else {
+            undo.piece
+        };
+        let color_index = undo.color as usize;
+        self.pieces[placed_piece as usize].clear_bit(undo.to);
+        self.colors[color_index].clear_bit(undo.to);
+        self.pieces[undo.piece as usize].set_bit(undo.from);
+        self.colors[color_index].set_bit(undo.from);
+
+        if let Some((piece, index)) = undo.captured {
+            self.pieces[piece as usize].set_bit(index);
+            self.colors[undo.color.opponent_color() as usize].set_bit(index);
+        }
+
+        *en_passant_indices = undo.en_passant_indices;
+        *kingside_castling_rights = undo.kingside_castling_rights;
+        *queenside_castling_rights = undo.queenside_castling_rights;
+    }
+
+    pub fn castle_kingside(&mut self, king_index: u8, rook_index: u8) -> Result<(), GameError> {
+        Self::validate_index(king_index)?;
+        Self::validate_index(rook_index)?;
+
+        let color = self.color_at_cell(king_index)?;
+        let new_king_index = match color {
+            Color::WHITE => 6,
+            Color::BLACK => 62,
+            Color::NONE => 1,
+        };
+        let new_rook_index = new_king_index - 1;
+
+        self.relocate_piece(king_index, new_king_index)?;
+        self.relocate_piece(rook_index, new_rook_index)?;
+
+        Ok(())
+    }
+
+    pub fn castle_queenside(&mut self, king_index: u8, rook_index: u8) -> Result<(), GameError> {
+        Self::validate_index(king_index)?;
+        Self::validate_index(rook_index)?;
+
+        let color = self.color_at_cell(king_index)?;
+        let new_king_index = match color {
+            Color::WHITE => 2,
+            Color::BLACK => 58,
+            Color::NONE => 1,
+        };
+        let new_rook_index = new_king_index + 1;
+
+        self.relocate_piece(king_index, new_king_index)?;
+        self.relocate_piece(rook_index, new_rook_index)?;
+
+        Ok(())
+    }
+
+    pub fn generate_legal_moves(
+        &mut self,
+        color: Color,
+        initial_pawn_mask: BitBoard,
+        en_passant_indices: &[u8; 2],
+        kingside_castling_rights: &[bool; 2],
+        queenside_castling_rights: &[bool; 2],
+        checkers: BitBoard,
+    ) -> Result<AvailableMoves, GameError> {
+        let piece_indices = self.colors[color as usize];
+        let mut moves: Vec<Move> = Vec::new();
+
+        let king_index = self.get_king_position_by_color(color);
+        let pinned_mask = self.get_pinned_mask(color);
+        let evasion_mask = Self::get_evasion_mask(king_index, checkers);
+        let opponent_color = color.opponent_color();
+        let en_passant_index = en_passant_indices[opponent_color as usize];
+        // The king doesn't shield the cells behind it from sliders once it moves away
+        let occupied_without_king =
+            (self.colors[0] | self.colors[1]) & !(BitBoard::default() + king_index);
+
+        for index in piece_indices {
+            let piece = self.piece_at_cell(index)?;
+            let action_mask = piece.get_action_mask(
+                index,
+                color,
+                initial_pawn_mask,
+                self.colors,
+                en_passant_indices,
+            );
+
+            for target_index in action_mask {
+                let is_en_passant = piece == Piece::PAWN && target_index == en_passant_index;
+                let kind = if is_en_passant {
+                    MoveKind::EnPassant
+                } else if piece == Piece::PAWN && !(8..=55).contains(&target_index) {
+                    MoveKind::Promotion(Piece::QUEEN)
+                } else {
+                    MoveKind::Normal
+                };
+                let chess_move = Move::new(Square(index), Square(target_index), kind);
+
+                if piece == Piece::KING {
+                    let attackers = self.get_attackers_through(
+                        target_index,
+                        opponent_color,
+                        occupied_without_king,
+                    );
+                    if attackers.0 == 0 {
+                        moves.push(chess_move)
+                    }
+                    continue;
+                }
+
+                // En passant can expose the king in ways the ray tables don't cover
+                if !is_en_passant {
+                    let pin_mask = if pinned_mask.get_bit(index) {
+                        LINE[king_index as usize][index as usize]
+                    } else {
+                        BitBoard(u64::MAX)
+                    };
+                    if (evasion_mask & pin_mask).get_bit(target_index) {
+                        moves.push(chess_move)
+                    }
+                    continue;
+                }
+
+                if !self.does_move_lead_to_check(
+                    color,
+                    index,
+                    target_index,
+                    en_passant_indices,
+                    kingside_castling_rights,
+                    queenside_castling_rights,
+                ) {
+                    moves.push(chess_move)
+                }
+            }
+        }
+
+        Ok(AvailableMoves(moves))
+    }
+
+    /// If a move leads to your own king being in check
+    /// The move is made and taken back on this board, so it is left unchanged
+    pub fn does_move_lead_to_check(
+        &mut self,
+        color: Color,
+        from: u8,
+        to: u8,
+        en_passant_indices: &[u8; 2],
+        kingside_castling_rights: &[bool; 2],
+        queenside_castling_rights: &[bool; 2],
+    ) -> bool {
+        let mut future_en_passant_indices = *en_passant_indices;
+        let mut future_kingside_castling_rights = *kingside_castling_rights;
+        let mut future_queenside_castling_rights = *queenside_castling_rights;
+        let Ok(Some(undo)) = self.apply_move(
+            from,
+            to,
+            &mut future_en_passant_indices,
+            &mut future_kingside_castling_rights,
+            &mut future_queenside_castling_rights,
+        ) else {
+            return false;
+        };
+
+        let check = self.is_king_check(color);
+        self.undo_move(
+            &undo,
+            &mut future_en_passant_indices,
+            &mut future_kingside_castling_rights,
+            &mut future_queenside_castling_rights,
+        );
+        check
+    }
+
+    pub fn get_king_position_by_color(&self, color: Color) -> u8 {
+        let king_board = Self::mask_by_piece_and_color(self, Piece::KING, color);
+        king_board.iter().next().unwrap_or(0) // 0 is undefined behavior
+    }
+
+    pub fn is_king_check(&self, color: Color) -> bool {
+        self.is_square_attacked(
+            self.get_king_position_by_color(color),
+            color.opponent_color(),
+        )
+    }
+
+    /// If any piece of the given color attacks the cell
+    pub fn is_square_attacked(&self, index: u8, by_color: Color) -> bool {
+        self.get_attackers(index, by_color).0 != 0
+    }
+
+    /// Returns all pieces of the given color attacking the cell
+    pub fn get_attackers(&self, index: u8, by_color: Color) -> BitBoard {
+        self.get_attackers_through(index, by_color, self.colors[0] | self.colors[1])
+    }
+
+    /// Like get_attackers, but sliders are only blocked by the given occupied cells
+    fn get_attackers_through(&self, index: u8, by_color: Color, occupied: BitBoard) -> BitBoard {
+        let cell = index as usize;
+        let queens = self.pieces[Piece::QUEEN as usize];
+        // A pawn attacks the cell if a pawn of the other color on the cell would attack the pawn
+        let mut attackers = (PAWN_ATTACKS[by_color.opponent_color() as usize][cell]
+            & self.pieces[Piece::PAWN as usize])
+            | (KNIGHT_ATTACKS[cell] & self.pieces[Piece::KNIGHT as usize])
+            | (KING_ATTACKS[cell] & self.pieces[Piece::KING as usize]);
+
+        let sliders = (((self.pieces[Piece::ROOK as usize] | queens) & orthogonal_rays(index))
+            | ((self.pieces[Piece::BISHOP as usize] | queens) & diagonal_rays(index)))
+            & self.colors[by_color as usize];
+        for slider_index in sliders {
+            if (BETWEEN[cell][slider_index as usize] & occupied).0 == 0 {
+                attackers.set_bit(slider_index);
+            }
+        }
+
+        attackers & self.colors[by_color as usize]
+    }
+
+    /// If no sequence of legal moves can lead to a checkmate, which is a draw under FIDE rules.
+    /// Covers insufficient material and locked pawn walls, as long as every bishop stands on the same cell color:
+    /// - no pawn can move or capture, so the structure never changes
+    /// - bishops can't capture pawns or be captured by them, pawns only attack their own cell color
+    /// - neither king can get next to an enemy pawn
+    /// - a king checked by a bishop always has two of its orthogonal neighbors (of the other cell color) to escape to,
+    ///   no bishop can ever cover them and the enemy king can't cover both without standing next to the king
+    pub fn is_dead_position(&self) -> bool {
+        let pawns = self.pieces[Piece::PAWN as usize];
+        let bishops = self.pieces[Piece::BISHOP as usize];
+        let knights = self.pieces[Piece::KNIGHT as usize];
+        let heavy_pieces = self.pieces[Piece::ROOK as usize] | self.pieces[Piece::QUEEN as usize];
+        if heavy_pieces.0 != 0 {
+            return false;
+        }
+        if knights.0 != 0 {
+            // A lone knight can't mate
+            return knights.count() == 1 && (pawns | bishops).0 == 0;
+        }
+
+        let bishop_cells = if (bishops & LIGHT_CELLS).0 == 0 {
+            !LIGHT_CELLS
+        } else if (bishops & !LIGHT_CELLS).0 == 0 {
+            LIGHT_CELLS
+        } else {
+            return false;
+        };
+
+        for color in [Color::WHITE, Color::BLACK] {
+            let opponent_color = color.opponent_color();
+            let own_pawns = pawns & self.colors[color as usize];
+            let opponent_pawns = pawns & self.colors[opponent_color as usize];
+            let opponent_capturable =
+                self.colors[opponent_color as usize] & !self.pieces[Piece::KING as usize];
+
+            let pawn_blocked = own_pawns.iter().all(|index| {
+                let front = if color == Color::WHITE {
+                    index + 8
+                } else {
+                    index - 8
+                };
+                pawns.get_bit(front)
+                    && (PAWN_ATTACKS[color as usize][index as usize] & opponent_capturable).0 == 0
+            });
+            if !pawn_blocked {
+                return false;
+            }
+
+            if (bishops & self.colors[color as usize]).0 != 0
+                && (opponent_pawns & bishop_cells).0 != 0
+            {
+                return false;
+            }
+
+            // Every cell the king can ever walk to
+            let mut opponent_pawn_attacks = BitBoard::default();
+            for index in opponent_pawns {
+                opponent_pawn_attacks =
+                    opponent_pawn_attacks | PAWN_ATTACKS[opponent_color as usize][index as usize];
+            }
+            let walkable = !(pawns | opponent_pawn_attacks);
+            let mut region = BitBoard::default() + self.get_king_position_by_color(color);
+            loop {
+                let mut grown = region;
+                for index in region {
+                    grown = grown | (KING_ATTACKS[index as usize] & walkable);
+                }
+                if grown == region {
+                    break;
+                }
+                region = grown;
+            }
+
+            let mut reach = BitBoard::default();
+            for index in region {
+                reach = reach | KING_ATTACKS[index as usize];
+            }
+            if (reach & opponent_pawns).0 != 0 {
+                return false;
+            }
+
+            if (bishops & self.colors[opponent_color as usize]).0 != 0
+                && (region & bishop_cells).iter().any(|index| {
+                    (KING_ATTACKS[index as usize] & !bishop_cells & region).count() < 2
+                })
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Returns all pieces of the given color which are pinned to their own king
+    pub fn get_pinned_mask(&self, color: Color) -> BitBoard {
+        let king_index = self.get_king_position_by_color(color);
+        let block_mask = self.colors[0] | self.colors[1];
+        let opponent_mask = self.colors[color.opponent_color() as usize];
+        let queens = self.pieces[Piece::QUEEN as usize];
+
+        let orthogonal_pinners = (self.pieces[Piece::ROOK as usize] | queens)
+            & opponent_mask
+            & orthogonal_rays(king_index);
+        let diagonal_pinners = (self.pieces[Piece::BISHOP as usize] | queens)
+            & opponent_mask
+            & diagonal_rays(king_index);
+
+        let mut pinned_mask = BitBoard::default();
+        for pinner_index in orthogonal_pinners | diagonal_pinners {
+            let blockers = BETWEEN[king_index as usize][pinner_index as usize] & block_mask;
+            if blockers.count() == 1 {
+                pinned_mask = pinned_mask | (blockers & self.colors[color as usize]);
+            }
+        }
+        pinned_mask
+    }
+
+    /// Returns all opponent pieces giving check to the king of the given color
+    pub fn get_checkers(&self, color: Color) -> BitBoard {
+        self.get_attackers(
+            self.get_king_position_by_color(color),
+            color.opponent_color(),
+        )
+    }
+
+    /// Returns the opponent pieces checking the king of the given color after the opponent moved,
+    /// only looking at pieces which arrived on a cell and sliders uncovered by a cell being vacated
+    pub fn get_checkers_after_move(&self, color: Color, previous: &ChessBoard) -> BitBoard {
+        let opponent_color = color.opponent_color();
+        let opponent_mask = self.colors[opponent_color as usize];
+        let block_mask = self.colors[0] | self.colors[1];
+        let vacated_mask = (previous.colors[0] | previous.colors[1]) & !block_mask;
+        let arrived_mask = opponent_mask & !previous.colors[opponent_color as usize];
+
+        let king_index = self.get_king_position_by_color(color);
+        let mut checkers = self.get_attackers(king_index, opponent_color) & arrived_mask;
+
+        let queens = self.pieces[Piece::QUEEN as usize];
+        let sliders = (((self.pieces[Piece::ROOK as usize] | queens)
+            & orthogonal_rays(king_index))
+            | ((self.pieces[Piece::BISHOP as usize] | queens) & diagonal_rays(king_index)))
+            & opponent_mask
+            & !arrived_mask;
+        for slider_index in sliders {
+            let between = BETWEEN[king_index as usize][slider_index as usize];
+            if (between & vacated_mask).0 != 0 && (between & block_mask).0 == 0 {
+                checkers.set_bit(slider_index);
+            }
+        }
+
+        checkers
+    }
+
+    /// Returns the cells a non-king piece may move to while its king is in check
+    /// Everything if not in check, capturing or blocking a single checker or nothing on a double check
+    pub fn get_evasion_mask(king_index: u8, checkers: BitBoard) -> BitBoard {
+        match checkers.count() {
+            0 => BitBoard(u64::MAX),
+            1 => BETWEEN[king_index as usize][checkers.0.trailing_zeros() as usize] | checkers,
+            _ => BitBoard::default(),
+        }
+    }
+
+    /// Returns king and kingside rook index
+    pub fn get_kingside_rook(&self, color: Color) -> Option<(u8, u8)> {
+        let king_index = self.get_king_position_by_color(color);
+        let rank_mask = BitBoard(0xFF << (king_index - king_index % 8));
+        let rook_board =
+            self.pieces[Piece::ROOK as usize] & self.colors[color as usize] & rank_mask;
+        match rook_board.iter().last() {
+            Some(rook_index) => {
+                if king_index > rook_index {
+                    None
+                } else {
+                    Some((king_index, rook_index))
+                }
+            }
+            None => None,
+        }
+    }
+
+    /// Returns king and queenside rook index
+    pub fn get_queenside_rook(&self, color: Color) -> Option<(u8, u8)> {
+        let king_index = self.get_king_position_by_color(color);
+        let rank_mask = BitBoard(0xFF << (king_index - king_index % 8));
+        let rook_board =
+            self.pieces[Piece::ROOK as usize] & self.colors[color as usize] & rank_mask;
+        match rook_board.iter().next() {
+            Some(rook_index) => {
+                if king_index < rook_index {
+                    None
+                } else {
+                    Some((king_index, rook_index))
+                }
+            }
+            None => None,
+        }
+    }
+
+    pub fn can_castle_kingside(&self, color: Color) -> bool {
+        self.get_castling_obstacle(color, true).is_none()
+    }
+
+    pub fn can_castle_queenside(&self, color: Color) -> bool {
+        self.get_castling_obstacle(color, false).is_none()
+    }
+
+    /// What keeps the color from castling to the given side on this board, castling rights aside
+    pub fn get_castling_obstacle(&self, color: Color, kingside: bool) -> Option<IllegalMoveReason> {
+        let rook = match kingside {
+            true => self.get_kingside_rook(color),
+            false => self.get_queenside_rook(color),
+        };
+        let Some((king_index, rook_index)) = rook else {
+            return Some(IllegalMoveReason::CastlingRightsLost);
+        };
+        let back_rank = king_index - king_index % 8;
+        let (king_target, rook_target) = match kingside {
+            true => (back_rank + 6, back_rank + 5),
+            false => (back_rank + 2, back_rank + 3),
+        };
+        self.get_castling_obstacle_common(color, king_index, rook_index, king_target, rook_target)
+    }
+
+    /// Both paths have to be free (except for king and rook themselves)
+    /// and the king may not start, pass or end on an attacked cell
+    pub fn get_castling_obstacle_common(
+        &self,
+        color: Color,
+        king_index: u8,
+        rook_index: u8,
+        king_target: u8,
+        rook_target: u8,
+    ) -> Option<IllegalMoveReason> {
+        let king_path = BETWEEN[king_index as usize][king_target as usize] + king_target;
+        let rook_path = BETWEEN[rook_index as usize][rook_target as usize] + rook_target;
+        let travel_mask = BETWEEN[king_index as usize][rook_index as usize] | king_path | rook_path;
+
+        let block_mask =
+            (self.colors[0] | self.colors[1]) & !(BitBoard::default() + king_index + rook_index);
+        if (travel_mask & block_mask).0 != 0 {
+            return Some(IllegalMoveReason::CastlingBlocked);
+        }
+
+        let opponent_color = color.opponent_color();
+        (king_path + king_index)
+            .iter()
+            .any(|index| self.is_square_attacked(index, opponent_color))
+            .then_some(IllegalMoveReason::CastlingThroughCheck)
+    }
+
+    /// Why the regular move of the color from and to the given cells isn't legal
+    /// Only meaningful for moves missing from the legal moves, since it doesn't look for checks itself
+    pub fn diagnose_move(
+        &self,
+        color: Color,
+        from: u8,
+        to: u8,
+        initial_pawn_mask: BitBoard,
+        en_passant_indices: &[u8; 2],
+    ) -> Result<IllegalMoveReason, GameError> {
+        Self::validate_index(from)?;
+        Self::validate_index(to)?;
+        let (piece, piece_color) = self.piece_and_color_at_cell(from)?;
+        if piece == Piece::NONE {
+            return Ok(IllegalMoveReason::PieceMissing);
+        }
+        if piece_color != color {
+            return Ok(IllegalMoveReason::OpponentPiece);
+        }
+        if self.colors[color as usize].get_bit(to) {
+            return Ok(IllegalMoveReason::OwnPieceOnTarget);
+        }
+
+        let action_mask = piece.get_action_mask(
+            from,
+            color,
+            initial_pawn_mask,
+            self.colors,
+            en_passant_indices,
+        );
+        if action_mask.get_bit(to) {
+            // The piece can get there, so only the safety of the king is left
+            return Ok(IllegalMoveReason::LeavesKingInCheck);
+        }
+
+        // On a board without other pieces nothing could be in the way
+        let mut alone = [BitBoard::default(); 2];
+        alone[color as usize] = BitBoard::default() + from;
+        let free_mask =
+            piece.get_action_mask(from, color, initial_pawn_mask, alone, en_passant_indices);
+        match free_mask.get_bit(to) {
+            true => Ok(IllegalMoveReason::BlockedPath),
+            false => Ok(IllegalMoveReason::UnreachableCell),
+        }
+    }
+
+    pub fn rotate(&self) -> Self {
+        Self {
+            colors: [self.colors[0].rotate(), self.colors[1].rotate()],
+            pieces: [
+                self.pieces[0].rotate(),
+                self.pieces[1].rotate(),
+                self.pieces[2].rotate(),
+                self.pieces[3].rotate(),
+                self.pieces[4].rotate(),
+                self.pieces[5].rotate(),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::position::Position as Pos;
+
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        let board = ChessBoard::default();
+        let base64_string = board.to_base64().unwrap();
+        let decoded_board = ChessBoard::from_base64(&base64_string).unwrap();
+        assert_eq!(board, decoded_board);
+    }
+
+    #[test]
+    fn test_dead_position() {
+        let is_dead = |fen: &str| {
+            ChessBoard::from_fen_positions(fen)
+                .unwrap()
+                .is_dead_position()
+        };
+
+        // Insufficient material
+        assert!(is_dead("8/8/4k3/8/8/3K4/8/8"));
+        assert!(is_dead("8/8/4k3/8/8/3KN3/8/8"));
+        assert!(is_dead("8/8/4kb2/8/8/3KB3/8/8"));
+        assert!(!is_dead("8/8/4k3/8/8/3KNN2/8/8"));
+        assert!(!is_dead("8/8/4kb2/8/8/3K1B2/8/8"));
+        assert!(!is_dead("8/8/4k3/8/8/3KBB2/8/8"));
+        assert!(!is_dead("8/8/4k3/8/8/3K4/4P3/8"));
+
+        // Locked pawn walls
+        assert!(is_dead("8/8/4k3/p1p1p1p1/P1P1P1P1/8/8/4K3"));
+        // The bishop stands on the other cell color than the pawns it could attack
+        assert!(is_dead("8/8/4k3/p1p1p1p1/P1P1P1P1/8/2B5/4K3"));
+        assert!(!is_dead("8/8/4k3/p1p1p1p1/P1P1P1P1/8/3B4/4K3"));
+        // The kings can walk around the wall
+        assert!(!is_dead("8/8/4k3/p1p1p3/P1P1P3/8/8/4K3"));
+        // A pawn can still capture
+        assert!(!is_dead("8/8/4k3/p1p1p1p1/P1P1PP2/8/8/4K3"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(ChessBoard::default().validate().is_ok());
+        assert!(ChessBoard::from_fen_positions("4k3/8/8/8/8/8/8/8").is_err());
+        assert!(ChessBoard::from_fen_positions("4k3/8/8/8/8/8/8/3KK3").is_err());
+        assert!(ChessBoard::from_fen_positions("4k2P/8/8/8/8/8/8/4K3").is_err());
+
+        let mut board = ChessBoard::default();
+        board.colors[1].set_bit(0);
+        assert!(board.validate().is_err());
+
+        let mut board = ChessBoard::default();
+        board.pieces[Piece::QUEEN as usize].set_bit(0);
+        assert!(board.validate().is_err());
+
+        let mut board = ChessBoard::default();
+        board.colors[0].set_bit(20);
+        assert!(ChessBoard::from_bytes(&board.to_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_is_cell_occupied() {
+        let board = ChessBoard::default();
+        assert!(board.is_cell_occupied(Pos::A1.into()).unwrap());
+        assert!(board.is_cell_occupied(Pos::H2.into()).unwrap());
+        assert!(board.is_cell_occupied(Pos::A7.into()).unwrap());
+        assert!(board.is_cell_occupied(Pos::H8.into()).unwrap());
+        assert!(!board.is_cell_occupied(Pos::A3.into()).unwrap());
+        assert!(!board.is_cell_occupied(Pos::H6.into()).unwrap());
+        assert!(board.is_cell_occupied(64).is_err());
+    }
+
+    #[test]
+    fn test_piece_at_cell() {
+        let board = ChessBoard::default();
+        assert_eq!(board.piece_at_cell(Pos::A1.into()).unwrap(), Piece::ROOK);
+        assert_eq!(board.piece_at_cell(Pos::B1.into()).unwrap(), Piece::KNIGHT);
+        assert_eq!(board.piece_at_cell(Pos::C1.into()).unwrap(), Piece::BISHOP);
+        assert_eq!(board.piece_at_cell(Pos::D1.into()).unwrap(), Piece::QUEEN);
+        assert_eq!(board.piece_at_cell(Pos::E1.into()).unwrap(), Piece::KING);
+        assert_eq!(board.piece_at_cell(Pos::F1.into()).unwrap(), Piece::BISHOP);
+        assert_eq!(board.piece_at_cell(Pos::G1.into()).unwrap(), Piece::KNIGHT);
+        assert_eq!(board.piece_at_cell(Pos::H1.into()).unwrap(), Piece::ROOK);
+        assert_eq!(board.piece_at_cell(Pos::A2.into()).unwrap(), Piece::PAWN);
+        assert_eq!(board.piece_at_cell(Pos::B2.into()).unwrap(), Piece::PAWN);
+        assert_eq!(board.piece_at_cell(Pos::A3.into()).unwrap(), Piece::NONE);
+        assert!(board.piece_at_cell(64).is_err());
+    }
+
+    #[test]
+    fn test_color_at_cell() {
+        let board = ChessBoard::default();
+        assert_eq!(board.color_at_cell(Pos::A1.into()).unwrap(), Color::WHITE);
+        assert_eq!(board.color_at_cell(Pos::H2.into()).unwrap(), Color::WHITE);
+        assert_eq!(board.color_at_cell(Pos::A3.into()).unwrap(), Color::NONE);
+        assert_eq!(board.color_at_cell(Pos::A7.into()).unwrap(), Color::BLACK);
+        assert_eq!(board.color_at_cell(Pos::H8.into()).unwrap(), Color::BLACK);
+        assert!(board.color_at_cell(64).is_err());
+    }
+
+    #[test]
+    fn test_make_move() {
+        let mut board = ChessBoard::default();
+        assert_eq!(
+            board
+                .make_move(
+                    Pos::H2.into(),
+                    Pos::H3.into(),
+                    &mut [64, 64],
+                    &mut [true, true],
+                    &mut [true, true]
+                )
+                .unwrap(),
+            (true, true, "h3".to_string())
+        );
+        assert_ne!(
+            board
+                .make_move(
+                    Pos::H2.into(),
+                    Pos::H3.into(),
+                    &mut [64, 64],
+                    &mut [true, true],
+                    &mut [true, true]
+                )
+                .unwrap(),
+            (true, true, String::new())
+        );
+        assert_eq!(board.piece_at_cell(Pos::H2.into()).unwrap(), Piece::NONE);
+        assert_eq!(board.piece_at_cell(Pos::H3.into()).unwrap(), Piece::PAWN);
+    }
+
+    #[test]
+    fn test_undo_move() {
+        // En passant, capturing promotion and a rook move losing castling rights
+        let moves = [
+            (Pos::E5, Pos::D6, [64, Pos::D6 as u8]),
+            (Pos::B7, Pos::A8, [64, 64]),
+            (Pos::H1, Pos::H8, [64, 64]),
+        ];
+        for (from, to, en_passant_indices) in moves {
+            let mut board = ChessBoard::from_fen_positions("r3k2r/1P6/8/3pP3/8/8/8/4K2R").unwrap();
+            let previous_board = board.clone();
+            let mut en_passant = en_passant_indices;
+            let mut kingside = [true, true];
+            let mut queenside = [true, true];
+
+            let undo = board
+                .apply_move(
+                    from.into(),
+                    to.into(),
+                    &mut en_passant,
+                    &mut kingside,
+                    &mut queenside,
+                )
+                .unwrap()
+                .unwrap();
+            assert!(undo.captured.is_some());
+            assert_ne!(board, previous_board);
+
+            board.undo_move(&undo, &mut en_passant, &mut kingside, &mut queenside);
+            assert_eq!(board, previous_board);
+            assert_eq!(en_passant, en_passant_indices);
+            assert_eq!(kingside, [true, true]);
+            assert_eq!(queenside, [true, true]);
+        }
+    }
+
+    #[test]
+    fn test_get_pinned_mask() {
+        let board = ChessBoard::from_fen_positions("k3r3/8/8/8/1b6/8/3N4/4R1K1").unwrap();
+        assert_eq!(board.get_pinned_mask(Color::WHITE), BitBoard(0));
+
+        // Two pieces between king and attacker, nothing is pinned
+        let board = ChessBoard::from_fen_positions("k5r1/8/8/8/3b4/4P3/5N2/6K1").unwrap();
+        assert_eq!(board.get_pinned_mask(Color::WHITE), BitBoard(0));
+
+        let board = ChessBoard::from_fen_positions("k5r1/8/8/8/3b4/6P1/5N2/6K1").unwrap();
+        assert_eq!(
+            board.get_pinned_mask(Color::WHITE),
+            BitBoard::from(vec![Pos::F2.into(), Pos::G3.into()])
+        );
+    }
+
+    #[test]
+    fn test_can_castle() {
+        // Attacked b1 doesn't matter, attacked d1 does
+        let board = ChessBoard::from_fen_positions("1r2k3/8/8/8/8/8/8/R3K2R").unwrap();
+        assert!(board.can_castle_queenside(Color::WHITE));
+        assert!(board.can_castle_kingside(Color::WHITE));
+
+        let board = ChessBoard::from_fen_positions("3rk3/8/8/8/8/8/8/R3K2R").unwrap();
+        assert!(!board.can_castle_queenside(Color::WHITE));
+
+        let board = ChessBoard::from_fen_positions("4k3/8/8/8/8/8/8/R3KN1R").unwrap();
+        assert!(!board.can_castle_kingside(Color::WHITE));
+    }
+
+    #[test]
+    fn test_get_attackers() {
+        let board = ChessBoard::from_fen_positions("4k3/8/2n5/3p4/4K2r/8/8/4Q3").unwrap();
+        assert_eq!(
+            board.get_attackers(Pos::E4.into(), Color::BLACK),
+            BitBoard::from(vec![Pos::D5.into(), Pos::H4.into()])
+        );
+        assert_eq!(
+            board.get_attackers(Pos::E3.into(), Color::WHITE),
+            BitBoard::from(vec![Pos::E4.into(), Pos::E1.into()])
+        );
+        // The white king blocks the queen from reaching e5
+        assert!(!board
+            .get_attackers(Pos::E5.into(), Color::WHITE)
+            .get_bit(Pos::E1.into()));
+        assert!(board.is_square_attacked(Pos::B4.into(), Color::BLACK));
+        assert!(!board.is_square_attacked(Pos::A1.into(), Color::BLACK));
+        assert!(board.is_king_check(Color::WHITE));
+        assert!(!board.is_king_check(Color::BLACK));
+    }
+
+    #[test]
+    fn test_get_checkers_after_move() {
+        // Bishop leaves the e-file and uncovers the rook, giving a double check
+        let mut board = ChessBoard::from_fen_positions("4k3/8/8/8/4B3/8/8/4R1K1").unwrap();
+        let previous = board.clone();
+        board
+            .make_move(
+                Pos::E4.into(),
+                Pos::B5.into(),
+                &mut [64, 64],
+                &mut [false, false],
+                &mut [false, false],
+            )
+            .unwrap();
+        assert_eq!(
+            board.get_checkers_after_move(Color::BLACK, &previous),
+            BitBoard::from(vec![Pos::E1.into(), Pos::B5.into()])
+        );
+        assert_eq!(
+            board.get_checkers_after_move(Color::BLACK, &previous),
+            board.get_checkers(Color::BLACK)
+        );
+    }
+}
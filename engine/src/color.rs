@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub enum Color {
+    WHITE = 0,
+    BLACK = 1,
+    NONE = 2,
+}
+
+impl From<usize> for Color {
+    fn from(number: usize) -> Self {
+        match number {
+            0 => Color::WHITE,
+            1 => Color::BLACK,
+            _ => Color::NONE,
+        }
+    }
+}
+
+impl Color {
+    /// Index into per color arrays and the bitboards of the chess board, white is always 0
+    pub fn as_index(self) -> usize {
+        self as usize
+    }
+
+    pub fn opponent_color(&self) -> Color {
+        if self == &Color::WHITE {
+            Color::BLACK
+        } else {
+            Color::WHITE
+        }
+    }
+
+    pub fn get_fen_letter(&self) -> char {
+        match self {
+            Color::WHITE => 'w',
+            Color::BLACK => 'b',
+            Color::NONE => '-',
+        }
+    }
+
+    pub fn from_fen_letter(letter: char) -> Self {
+        match letter.to_ascii_lowercase() {
+            'w' => Color::WHITE,
+            'b' => Color::BLACK,
+            _ => Color::NONE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_conversion() {
+        for color in [Color::WHITE, Color::BLACK, Color::NONE] {
+            assert_eq!(Color::from(color.as_index()), color);
+            assert_eq!(Color::from_fen_letter(color.get_fen_letter()), color);
+        }
+        assert_eq!(Color::WHITE.as_index(), 0);
+        assert_eq!(Color::BLACK.as_index(), 1);
+        assert_eq!(Color::from(7), Color::NONE);
+        assert_eq!(Color::WHITE.opponent_color(), Color::BLACK);
+        assert_eq!(Color::BLACK.opponent_color(), Color::WHITE);
+    }
+}
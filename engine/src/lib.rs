@@ -0,0 +1,17 @@
+//! Chess rules of lemon-chess: bitboards, move generation, FEN, PGN and SAN, game states and engine evaluation
+//! Doesn't depend on the API, so bots and tools can use it on their own
+
+pub mod bit_board;
+pub mod chess_board;
+pub mod color;
+pub mod error;
+pub mod opening;
+pub mod pgn;
+pub mod phase;
+pub mod piece;
+pub mod position;
+pub mod rays;
+pub mod review;
+pub mod state;
+pub mod termination;
+pub mod transposition;
@@ -0,0 +1,229 @@
+use super::position::Move;
+
+/// A named opening of the ECO (Encyclopaedia of Chess Openings) classification
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Opening {
+    pub eco: &'static str,
+    pub name: &'static str,
+    /// The moves defining the opening in coordinate notation, e.g. "e2e4 e7e5"
+    moves: &'static str,
+}
+
+const fn opening(eco: &'static str, name: &'static str, moves: &'static str) -> Opening {
+    Opening { eco, name, moves }
+}
+
+/// The most common openings, more specific lines win over the ones they start with
+pub static OPENINGS: &[Opening] = &[
+    opening("A00", "Polish Opening", "b2b4"),
+    opening("A01", "Nimzo-Larsen Attack", "b2b3"),
+    opening("A02", "Bird's Opening", "f2f4"),
+    opening("A04", "Réti Opening", "g1f3"),
+    opening("A10", "English Opening", "c2c4"),
+    opening("A15", "English Opening: Anglo-Indian Defense", "c2c4 g8f6"),
+    opening(
+        "A20",
+        "English Opening: King's English Variation",
+        "c2c4 e7e5",
+    ),
+    opening("A30", "English Opening: Symmetrical Variation", "c2c4 c7c5"),
+    opening("A40", "Queen's Pawn Game", "d2d4"),
+    opening("A45", "Indian Defense", "d2d4 g8f6"),
+    opening("A50", "Indian Defense: Normal Variation", "d2d4 g8f6 c2c4"),
+    opening("A56", "Benoni Defense", "d2d4 g8f6 c2c4 c7c5"),
+    opening("A57", "Benko Gambit", "d2d4 g8f6 c2c4 c7c5 d4d5 b7b5"),
+    opening("A80", "Dutch Defense", "d2d4 f7f5"),
+    opening("B00", "King's Pawn Game", "e2e4"),
+    opening("B00", "Nimzowitsch Defense", "e2e4 b8c6"),
+    opening("B01", "Scandinavian Defense", "e2e4 d7d5"),
+    opening("B02", "Alekhine's Defense", "e2e4 g8f6"),
+    opening("B06", "Modern Defense", "e2e4 g7g6"),
+    opening("B07", "Pirc Defense", "e2e4 d7d6 d2d4 g8f6"),
+    opening("B10", "Caro-Kann Defense", "e2e4 c7c6"),
+    opening(
+        "B12",
+        "Caro-Kann Defense: Advance Variation",
+        "e2e4 c7c6 d2d4 d7d5 e4e5",
+    ),
+    opening("B20", "Sicilian Defense", "e2e4 c7c5"),
+    opening(
+        "B21",
+        "Sicilian Defense: Smith-Morra Gambit",
+        "e2e4 c7c5 d2d4 c5d4 c2c3",
+    ),
+    opening(
+        "B22",
+        "Sicilian Defense: Alapin Variation",
+        "e2e4 c7c5 c2c3",
+    ),
+    opening("B23", "Sicilian Defense: Closed", "e2e4 c7c5 b1c3"),
+    opening("B27", "Sicilian Defense", "e2e4 c7c5 g1f3"),
+    opening(
+        "B30",
+        "Sicilian Defense: Old Sicilian",
+        "e2e4 c7c5 g1f3 b8c6",
+    ),
+    opening(
+        "B40",
+        "Sicilian Defense: French Variation",
+        "e2e4 c7c5 g1f3 e7e6",
+    ),
+    opening("B50", "Sicilian Defense", "e2e4 c7c5 g1f3 d7d6"),
+    opening(
+        "B70",
+        "Sicilian Defense: Dragon Variation",
+        "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 g7g6",
+    ),
+    opening(
+        "B90",
+        "Sicilian Defense: Najdorf Variation",
+        "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 a7a6",
+    ),
+    opening("C00", "French Defense", "e2e4 e7e6"),
+    opening(
+        "C02",
+        "French Defense: Advance Variation",
+        "e2e4 e7e6 d2d4 d7d5 e4e5",
+    ),
+    opening(
+        "C03",
+        "French Defense: Tarrasch Variation",
+        "e2e4 e7e6 d2d4 d7d5 b1d2",
+    ),
+    opening("C20", "King's Pawn Game", "e2e4 e7e5"),
+    opening("C21", "Center Game", "e2e4 e7e5 d2d4 e5d4"),
+    opening("C21", "Danish Gambit", "e2e4 e7e5 d2d4 e5d4 c2c3"),
+    opening("C23", "Bishop's Opening", "e2e4 e7e5 f1c4"),
+    opening("C25", "Vienna Game", "e2e4 e7e5 b1c3"),
+    opening("C30", "King's Gambit", "e2e4 e7e5 f2f4"),
+    opening("C33", "King's Gambit Accepted", "e2e4 e7e5 f2f4 e5f4"),
+    opening("C40", "King's Knight Opening", "e2e4 e7e5 g1f3"),
+    opening("C41", "Philidor Defense", "e2e4 e7e5 g1f3 d7d6"),
+    opening("C42", "Petrov's Defense", "e2e4 e7e5 g1f3 g8f6"),
+    opening(
+        "C44",
+        "King's Knight Opening: Normal Variation",
+        "e2e4 e7e5 g1f3 b8c6",
+    ),
+    opening("C44", "Ponziani Opening", "e2e4 e7e5 g1f3 b8c6 c2c3"),
+    opening("C44", "Scotch Game", "e2e4 e7e5 g1f3 b8c6 d2d4"),
+    opening("C45", "Scotch Game", "e2e4 e7e5 g1f3 b8c6 d2d4 e5d4 f3d4"),
+    opening("C46", "Three Knights Opening", "e2e4 e7e5 g1f3 b8c6 b1c3"),
+    opening("C47", "Four Knights Game", "e2e4 e7e5 g1f3 b8c6 b1c3 g8f6"),
+    opening("C50", "Italian Game", "e2e4 e7e5 g1f3 b8c6 f1c4"),
+    opening(
+        "C50",
+        "Italian Game: Giuoco Piano",
+        "e2e4 e7e5 g1f3 b8c6 f1c4 f8c5",
+    ),
+    opening(
+        "C51",
+        "Italian Game: Evans Gambit",
+        "e2e4 e7e5 g1f3 b8c6 f1c4 f8c5 b2b4",
+    ),
+    opening(
+        "C55",
+        "Italian Game: Two Knights Defense",
+        "e2e4 e7e5 g1f3 b8c6 f1c4 g8f6",
+    ),
+    opening("C60", "Ruy Lopez", "e2e4 e7e5 g1f3 b8c6 f1b5"),
+    opening(
+        "C65",
+        "Ruy Lopez: Berlin Defense",
+        "e2e4 e7e5 g1f3 b8c6 f1b5 g8f6",
+    ),
+    opening(
+        "C68",
+        "Ruy Lopez: Exchange Variation",
+        "e2e4 e7e5 g1f3 b8c6 f1b5 a7a6 b5c6",
+    ),
+    opening(
+        "C70",
+        "Ruy Lopez: Morphy Defense",
+        "e2e4 e7e5 g1f3 b8c6 f1b5 a7a6",
+    ),
+    opening("D00", "Queen's Pawn Game", "d2d4 d7d5"),
+    opening("D00", "Queen's Pawn Game: London System", "d2d4 d7d5 c1f4"),
+    opening("D06", "Queen's Gambit", "d2d4 d7d5 c2c4"),
+    opening("D10", "Slav Defense", "d2d4 d7d5 c2c4 c7c6"),
+    opening("D20", "Queen's Gambit Accepted", "d2d4 d7d5 c2c4 d5c4"),
+    opening("D30", "Queen's Gambit Declined", "d2d4 d7d5 c2c4 e7e6"),
+    opening("D80", "Grünfeld Defense", "d2d4 g8f6 c2c4 g7g6 b1c3 d7d5"),
+    opening("E01", "Catalan Opening", "d2d4 g8f6 c2c4 e7e6 g2g3"),
+    opening(
+        "E12",
+        "Queen's Indian Defense",
+        "d2d4 g8f6 c2c4 e7e6 g1f3 b7b6",
+    ),
+    opening(
+        "E20",
+        "Nimzo-Indian Defense",
+        "d2d4 g8f6 c2c4 e7e6 b1c3 f8b4",
+    ),
+    opening("E60", "King's Indian Defense", "d2d4 g8f6 c2c4 g7g6"),
+    opening(
+        "E61",
+        "King's Indian Defense",
+        "d2d4 g8f6 c2c4 g7g6 b1c3 f8g7",
+    ),
+];
+
+impl Opening {
+    pub fn get_moves(&self) -> impl Iterator<Item = &'static str> {
+        self.moves.split_whitespace()
+    }
+
+    /// The most specific opening the game started with, None if it left the known lines right away
+    pub fn classify(move_log: &[Move]) -> Option<&'static Opening> {
+        let played: Vec<String> = move_log.iter().map(|m| m.to_coordinates()).collect();
+        OPENINGS
+            .iter()
+            .filter(|opening| {
+                let moves: Vec<&str> = opening.get_moves().collect();
+                moves.len() <= played.len() && moves.iter().zip(&played).all(|(a, b)| *a == b)
+            })
+            .max_by_key(|opening| opening.get_moves().count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{position::Square, state::GameState};
+
+    use super::*;
+
+    fn play(moves: &str) -> GameState {
+        let mut state = GameState::new().unwrap();
+        for coordinates in moves.split_whitespace() {
+            let from = Square::try_from(coordinates[0..2].to_string()).unwrap();
+            let to = Square::try_from(coordinates[2..4].to_string()).unwrap();
+            assert!(
+                state.make_move(from, to).unwrap(),
+                "{} can't be played",
+                coordinates
+            );
+        }
+        state
+    }
+
+    #[test]
+    fn test_openings_are_playable() {
+        for opening in OPENINGS {
+            let state = play(opening.moves);
+            assert_eq!(Opening::classify(&state.move_log), Some(opening));
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(Opening::classify(&[]), None);
+
+        let state = play("e2e4 e7e5 g1f3 b8c6 f1c4 f8c5 c2c3");
+        let opening = Opening::classify(&state.move_log).unwrap();
+        assert_eq!(opening.eco, "C50");
+        assert_eq!(opening.name, "Italian Game: Giuoco Piano");
+
+        let state = play("a2a3");
+        assert_eq!(Opening::classify(&state.move_log), None);
+    }
+}
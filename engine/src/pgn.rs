@@ -0,0 +1,116 @@
+use super::{error::GameError, state::GameState};
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+/// The value of a tag pair like [White "Alice"], None if the tag is missing
+pub fn get_tag(pgn: &str, name: &str) -> Option<String> {
+    let prefix = format!("[{} \"", name);
+    pgn.lines()
+        .map(str::trim)
+        .find_map(|line| line.strip_prefix(&prefix)?.strip_suffix("\"]"))
+        .map(|value| value.replace("\\\"", "\""))
+}
+
+/// The moves of the movetext in SAN, without move numbers, comments, variations, glyphs and the result
+pub fn parse_movetext(pgn: &str) -> Vec<String> {
+    let mut movetext = String::new();
+    let mut comment_depth = 0;
+    let mut variation_depth = 0;
+    for line in pgn
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('['))
+    {
+        for character in line.chars() {
+            match character {
+                '{' => comment_depth += 1,
+                '}' if comment_depth > 0 => comment_depth -= 1,
+                ';' if comment_depth == 0 => break,
+                '(' if comment_depth == 0 => variation_depth += 1,
+                ')' if comment_depth == 0 && variation_depth > 0 => variation_depth -= 1,
+                _ if comment_depth == 0 && variation_depth == 0 => movetext.push(character),
+                _ => {}
+            }
+        }
+        movetext.push(' ');
+    }
+
+    movetext
+        .split_whitespace()
+        .filter(|token| !RESULTS.contains(token))
+        .map(strip_move_number)
+        .filter(|token| !token.is_empty() && !token.starts_with('$'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Move numbers may be glued to the move, like 12.Nf3 or 12...Nf6
+fn strip_move_number(token: &str) -> &str {
+    let digits = token.trim_start_matches(|c: char| c.is_ascii_digit());
+    match digits.starts_with('.') {
+        true => digits.trim_start_matches('.'),
+        false => token,
+    }
+}
+
+/// Plays all moves of a game which started from the regular starting position
+pub fn replay_pgn(pgn: &str) -> Result<GameState, GameError> {
+    if get_tag(pgn, "FEN").is_some() {
+        return Err(GameError::ValidationError(
+            "Games starting from a custom position are not supported".to_string(),
+        ));
+    }
+
+    let mut state = GameState::new()?;
+    for san in parse_movetext(pgn) {
+        let chess_move = state.find_san_move(&san)?;
+        if !state.replay_move(chess_move)? {
+            return Err(GameError::ValidationError(format!(
+                "{} can't be played",
+                san
+            )));
+        }
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::termination::Termination;
+
+    const PGN: &str = r#"[Event "Live Chess"]
+[White "alice"]
+[Black "bob \"the rook\""]
+[Result "1-0"]
+
+1. e4 {[%clk 0:02:59.9]} 1... e5 {[%clk 0:02:58.1]} 2. Nf3 Nc6 (2... d6 3. d4) 3. Bc4 Nd4?! $6
+4. Nxe5 Qg5 5. Nxf7 Qxg2 6. Rf1 Qxe4+ 7. Be2 Nf3# 0-1"#;
+
+    #[test]
+    fn test_get_tag() {
+        assert_eq!(get_tag(PGN, "White").as_deref(), Some("alice"));
+        assert_eq!(get_tag(PGN, "Black").as_deref(), Some("bob \"the rook\""));
+        assert_eq!(get_tag(PGN, "FEN"), None);
+    }
+
+    #[test]
+    fn test_replay_pgn() {
+        let moves = parse_movetext(PGN);
+        assert_eq!(moves.len(), 14);
+        assert_eq!(moves[5], "Nd4?!");
+
+        let state = replay_pgn(PGN).unwrap();
+        assert_eq!(state.move_log.len(), 14);
+        assert_eq!(state.termination, Some(Termination::Checkmate));
+        assert_eq!(state.winner, 1);
+
+        let castles = replay_pgn("1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. O-O Nf6 *").unwrap();
+        assert!(castles
+            .to_fen()
+            .starts_with("r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1"));
+
+        assert!(replay_pgn("1. e4 e5 2. Ke3 *").is_err());
+        assert!(replay_pgn("1. e4 e5 2. Nc3 Nc6 3. Ne2 *").is_err());
+        assert!(replay_pgn("1.e4 e5 2.Nc3 Nc6 3.Nge2 *").is_ok());
+    }
+}
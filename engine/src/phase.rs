@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub enum GamePhase {
+    OPENING,
+    MIDDLEGAME,
+    ENDGAME,
+}
+
+impl GamePhase {
+    /// Non-pawn material of both colors in pawns at the start of a game
+    pub const STARTING_PIECE_MATERIAL: i32 = 62;
+    /// At most this much non-pawn material left on the board counts as an endgame, e.g. a rook and a minor piece each
+    const ENDGAME_PIECE_MATERIAL: i32 = 26;
+    /// The opening is over once more than a pair of minor pieces was traded
+    const OPENING_PIECE_MATERIAL: i32 = 56;
+    /// The opening is over after this full move at the latest
+    const OPENING_MOVES: u16 = 10;
+
+    /// Heuristic by the non-pawn material of both colors and the current full move
+    pub fn from_material(piece_material: i32, full_move: u16) -> Self {
+        if piece_material <= Self::ENDGAME_PIECE_MATERIAL {
+            GamePhase::ENDGAME
+        } else if piece_material >= Self::OPENING_PIECE_MATERIAL && full_move <= Self::OPENING_MOVES
+        {
+            GamePhase::OPENING
+        } else {
+            GamePhase::MIDDLEGAME
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_material() {
+        let start = GamePhase::STARTING_PIECE_MATERIAL;
+        assert_eq!(GamePhase::from_material(start, 1), GamePhase::OPENING);
+        assert_eq!(GamePhase::from_material(start - 6, 10), GamePhase::OPENING);
+        assert_eq!(GamePhase::from_material(start, 11), GamePhase::MIDDLEGAME);
+        assert_eq!(
+            GamePhase::from_material(start - 9, 5),
+            GamePhase::MIDDLEGAME
+        );
+        assert_eq!(GamePhase::from_material(16, 5), GamePhase::ENDGAME);
+    }
+}
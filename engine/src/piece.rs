@@ -1,6 +1,6 @@
 use super::{bit_board::BitBoard, color::Color};
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Piece {
     PAWN = 0,
     BISHOP = 1,
@@ -26,6 +26,11 @@ impl From<usize> for Piece {
 }
 
 impl Piece {
+    /// Index into the piece bitboards of the chess board and the capture log, NONE is 6
+    pub fn as_index(self) -> usize {
+        self as usize
+    }
+
     /// The reach mask will include the cell that the piece is blocked by
     /// That way you can just subtract the current players color mask from the reach mask to get the move mask
     /// Or AND the opponent color mask with the reach mask to get the attack mask (except pawns)
@@ -145,32 +150,6 @@ impl Piece {
         }
     }
 
-    // If a respective opponent piece is at a 1 position, it means the king is check
-    pub fn get_king_threat_masks(
-        index: u8,
-        current_color: Color,
-        block_mask: BitBoard,
-    ) -> [BitBoard; 6] {
-        let mut masks: [BitBoard; 6] = [BitBoard::default(); 6];
-
-        for piece_id in 0..6 {
-            let piece = Piece::from(piece_id);
-            let reach_mask =
-                piece.get_reach_mask(index, current_color, block_mask, BitBoard::default());
-            let threat_mask = Piece::get_attack_mask(
-                index,
-                reach_mask,
-                &piece,
-                current_color,
-                BitBoard(u64::MAX),
-                64,
-            );
-            masks[piece as usize] = threat_mask;
-        }
-
-        masks
-    }
-
     pub fn get_name(&self) -> String {
         match self {
             Piece::PAWN => "Pawn".to_string(),
@@ -183,6 +162,17 @@ impl Piece {
         }
     }
 
+    /// Material value in pawns
+    pub fn get_value(&self) -> i32 {
+        match self {
+            Piece::PAWN => 1,
+            Piece::BISHOP | Piece::KNIGHT => 3,
+            Piece::ROOK => 5,
+            Piece::QUEEN => 9,
+            Piece::KING | Piece::NONE => 0,
+        }
+    }
+
     pub fn get_image_name(&self, color: Color) -> String {
         let name = self.get_name();
         if color == Color::WHITE {
@@ -231,3 +221,24 @@ impl Piece {
         (piece, color)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_conversion() {
+        for index in 0..=6 {
+            assert_eq!(Piece::from(index).as_index(), index);
+        }
+        assert_eq!(Piece::from(9), Piece::NONE);
+        assert_eq!(Piece::PAWN.as_index(), 0);
+        assert_eq!(Piece::KING.as_index(), 5);
+
+        for letter in ['P', 'b', 'N', 'r', 'Q', 'k'] {
+            let (piece, color) = Piece::from_fen_letter(letter);
+            assert_eq!(piece.get_fen_letter(color), letter.to_string());
+            assert_eq!(color.as_index(), letter.is_ascii_lowercase() as usize);
+        }
+    }
+}
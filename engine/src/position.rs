@@ -0,0 +1,261 @@
+use super::{error::GameError, piece::Piece};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+// Prioritizing speed, its faster to just map all 64 coordinates to the respective index
+pub enum Position {
+    A1,
+    B1,
+    C1,
+    D1,
+    E1,
+    F1,
+    G1,
+    H1,
+    A2,
+    B2,
+    C2,
+    D2,
+    E2,
+    F2,
+    G2,
+    H2,
+    A3,
+    B3,
+    C3,
+    D3,
+    E3,
+    F3,
+    G3,
+    H3,
+    A4,
+    B4,
+    C4,
+    D4,
+    E4,
+    F4,
+    G4,
+    H4,
+    A5,
+    B5,
+    C5,
+    D5,
+    E5,
+    F5,
+    G5,
+    H5,
+    A6,
+    B6,
+    C6,
+    D6,
+    E6,
+    F6,
+    G6,
+    H6,
+    A7,
+    B7,
+    C7,
+    D7,
+    E7,
+    F7,
+    G7,
+    H7,
+    A8,
+    B8,
+    C8,
+    D8,
+    E8,
+    F8,
+    G8,
+    H8,
+}
+
+impl Position {
+    pub fn as_str(&self) -> String {
+        let index = *self as u8;
+        let file = b'A' + (index % 8);
+        let rank = 1 + (index / 8);
+        format!("{}{}", file as char, rank)
+    }
+}
+
+impl std::convert::TryFrom<String> for Position {
+    type Error = GameError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if value.len() != 2 {
+            return Err(GameError::DecodingError(format!(
+                "Invalid position '{}'. Position must be 2 characters long.",
+                value
+            )));
+        }
+
+        let mut chars = value.chars();
+        let file_char = chars.nth(0).unwrap_or_default();
+        let rank_char = chars.nth(0).unwrap_or_default();
+
+        if !file_char.is_ascii_alphabetic() || !rank_char.is_ascii_digit() {
+            return Err(GameError::DecodingError(format!(
+                "Invalid position format: '{}'.",
+                value
+            )));
+        }
+
+        let file = file_char.to_ascii_uppercase() as u8 - b'A';
+        let rank = (rank_char.to_digit(10).unwrap_or_default() as u8).wrapping_sub(1);
+
+        if file > 7 || rank > 7 {
+            return Err(GameError::DecodingError(format!(
+                "Position out of bounds: '{}'.",
+                value
+            )));
+        }
+
+        let index = rank * 8 + file;
+        Self::try_from(index)
+            .map_err(|_| GameError::DecodingError(format!("Invalid position index: '{}'.", index)))
+    }
+}
+
+impl From<Position> for u8 {
+    fn from(val: Position) -> Self {
+        val as u8
+    }
+}
+
+impl std::convert::TryFrom<u8> for Position {
+    type Error = GameError;
+
+    fn try_from(index: u8) -> Result<Self, Self::Error> {
+        if index < 64 {
+            Ok(unsafe { std::mem::transmute::<u8, Position>(index) })
+        } else {
+            Err(GameError::DecodingError(format!(
+                "Can't create position from index {}. Index has to be between 0 and 63.",
+                index
+            )))
+        }
+    }
+}
+
+/// A cell index on the board, 0 = A1 up to 63 = H8
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Square(pub u8);
+
+impl Square {
+    pub fn index(self) -> u8 {
+        self.0
+    }
+
+    pub fn as_str(&self) -> String {
+        let file = b'A' + (self.0 % 8);
+        let rank = 1 + (self.0 / 8);
+        format!("{}{}", file as char, rank)
+    }
+}
+
+impl From<Position> for Square {
+    fn from(position: Position) -> Self {
+        Self(position as u8)
+    }
+}
+
+impl From<Square> for u8 {
+    fn from(square: Square) -> Self {
+        square.0
+    }
+}
+
+impl std::convert::TryFrom<u8> for Square {
+    type Error = GameError;
+
+    fn try_from(index: u8) -> Result<Self, Self::Error> {
+        Position::try_from(index).map(Self::from)
+    }
+}
+
+impl std::convert::TryFrom<String> for Square {
+    type Error = GameError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Position::try_from(value).map(Self::from)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MoveKind {
+    Normal,
+    /// The king moves two cells towards the rook, which jumps over it
+    Castle,
+    EnPassant,
+    Promotion(Piece),
+}
+
+/// A single move, castling goes from the king's cell to the cell the king ends up on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub from: Square,
+    pub to: Square,
+    pub kind: MoveKind,
+}
+
+impl Move {
+    pub fn new(from: Square, to: Square, kind: MoveKind) -> Self {
+        Self { from, to, kind }
+    }
+
+    pub fn normal(from: Square, to: Square) -> Self {
+        Self::new(from, to, MoveKind::Normal)
+    }
+
+    pub fn is_kingside_castle(&self) -> bool {
+        self.kind == MoveKind::Castle && self.to > self.from
+    }
+
+    pub fn is_queenside_castle(&self) -> bool {
+        self.kind == MoveKind::Castle && self.to < self.from
+    }
+
+    /// Lowercase from and to cells, e.g. "e2e4", castling being the king's move
+    pub fn to_coordinates(&self) -> String {
+        format!("{}{}", self.from.as_str(), self.to.as_str()).to_lowercase()
+    }
+
+    /// One byte per field, the kind byte being 0 = normal, 1 = castle, 2 = en passant and 3 + piece for promotions
+    pub fn to_bytes(self) -> [u8; 3] {
+        let kind = match self.kind {
+            MoveKind::Normal => 0,
+            MoveKind::Castle => 1,
+            MoveKind::EnPassant => 2,
+            MoveKind::Promotion(piece) => 3 + piece as u8,
+        };
+        [self.from.0, self.to.0, kind]
+    }
+
+    pub fn from_bytes(bytes: [u8; 3]) -> Result<Self, GameError> {
+        let [from, to, kind] = bytes;
+        let kind = match kind {
+            0 => MoveKind::Normal,
+            1 => MoveKind::Castle,
+            2 => MoveKind::EnPassant,
+            // Bishop to queen
+            4..=7 => MoveKind::Promotion(Piece::from((kind - 3) as usize)),
+            _ => {
+                return Err(GameError::DecodingError(format!(
+                    "Invalid move kind {}",
+                    kind
+                )))
+            }
+        };
+        Ok(Self::new(
+            Square::try_from(from)?,
+            Square::try_from(to)?,
+            kind,
+        ))
+    }
+}
+
+impl From<Move> for String {
+    fn from(m: Move) -> Self {
+        format!("{}->{}", m.from.as_str(), m.to.as_str())
+    }
+}
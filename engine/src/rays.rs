@@ -0,0 +1,204 @@
+use super::bit_board::BitBoard;
+
+/// Squares strictly between two cells on a shared rank, file or diagonal, empty if they aren't aligned
+pub static BETWEEN: [[BitBoard; 64]; 64] = generate_between();
+
+/// The full edge-to-edge line through two cells on a shared rank, file or diagonal, empty if they aren't aligned
+pub static LINE: [[BitBoard; 64]; 64] = generate_line();
+
+/// Cells a knight on the given cell jumps to
+pub static KNIGHT_ATTACKS: [BitBoard; 64] = generate_steps(&KNIGHT_JUMPS);
+
+/// Cells next to the given cell, which a king on it attacks
+pub static KING_ATTACKS: [BitBoard; 64] = generate_steps(&DIRECTIONS);
+
+/// Cells a pawn of the given color on the given cell attacks, 0 = white, 1 = black
+pub static PAWN_ATTACKS: [[BitBoard; 64]; 2] = [
+    generate_steps(&[(1, 1), (1, -1)]),
+    generate_steps(&[(-1, 1), (-1, -1)]),
+];
+
+const KNIGHT_JUMPS: [(i8, i8); 8] = [
+    (2, 1),
+    (1, 2),
+    (2, -1),
+    (1, -2),
+    (-2, 1),
+    (-1, 2),
+    (-2, -1),
+    (-1, -2),
+];
+
+const DIRECTIONS: [(i8, i8); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+
+/// Returns the (row, column) step leading from one cell towards the other, if they are aligned
+const fn direction(from: u8, to: u8) -> Option<(i8, i8)> {
+    if from == to {
+        return None;
+    }
+
+    let row_diff = (to / 8) as i8 - (from / 8) as i8;
+    let col_diff = (to % 8) as i8 - (from % 8) as i8;
+
+    if row_diff == 0 || col_diff == 0 || row_diff.abs() == col_diff.abs() {
+        Some((row_diff.signum(), col_diff.signum()))
+    } else {
+        None
+    }
+}
+
+/// Walks from the given cell in the given direction, collecting every cell until the edge of the board
+const fn ray(index: u8, row_step: i8, col_step: i8) -> u64 {
+    let mut mask = 0;
+    let mut row = (index / 8) as i8 + row_step;
+    let mut col = (index % 8) as i8 + col_step;
+    while row >= 0 && row < 8 && col >= 0 && col < 8 {
+        mask |= 1 << (row * 8 + col);
+        row += row_step;
+        col += col_step;
+    }
+    mask
+}
+
+/// Every cell reachable by a single (row, column) step from the given cell, steps leaving the board are dropped
+const fn generate_steps(steps: &[(i8, i8)]) -> [BitBoard; 64] {
+    let mut table = [BitBoard(0); 64];
+    let mut index = 0;
+    while index < 64 {
+        let mut mask = 0;
+        let mut i = 0;
+        while i < steps.len() {
+            let row = (index / 8) as i8 + steps[i].0;
+            let col = (index % 8) as i8 + steps[i].1;
+            if row >= 0 && row < 8 && col >= 0 && col < 8 {
+                mask |= 1 << (row * 8 + col);
+            }
+            i += 1;
+        }
+        table[index as usize] = BitBoard(mask);
+        index += 1;
+    }
+    table
+}
+
+const fn generate_between() -> [[BitBoard; 64]; 64] {
+    let mut table = [[BitBoard(0); 64]; 64];
+    let mut from = 0;
+    while from < 64 {
+        let mut to = 0;
+        while to < 64 {
+            if let Some((row_step, col_step)) = direction(from, to) {
+                // Cells after 'from' towards 'to', minus 'to' and everything behind it
+                let beyond_to = ray(to, row_step, col_step) | (1 << to);
+                table[from as usize][to as usize] =
+                    BitBoard(ray(from, row_step, col_step) & !beyond_to);
+            }
+            to += 1;
+        }
+        from += 1;
+    }
+    table
+}
+
+const fn generate_line() -> [[BitBoard; 64]; 64] {
+    let mut table = [[BitBoard(0); 64]; 64];
+    let mut from = 0;
+    while from < 64 {
+        let mut to = 0;
+        while to < 64 {
+            if let Some((row_step, col_step)) = direction(from, to) {
+                table[from as usize][to as usize] = BitBoard(
+                    ray(from, row_step, col_step) | ray(from, -row_step, -col_step) | (1 << from),
+                );
+            }
+            to += 1;
+        }
+        from += 1;
+    }
+    table
+}
+
+/// Cells reachable from the given cell by a rook (on an empty board)
+pub const fn orthogonal_rays(index: u8) -> BitBoard {
+    let mut mask = 0;
+    let mut i = 0;
+    while i < 4 {
+        mask |= ray(index, DIRECTIONS[i].0, DIRECTIONS[i].1);
+        i += 1;
+    }
+    BitBoard(mask)
+}
+
+/// Cells reachable from the given cell by a bishop (on an empty board)
+pub const fn diagonal_rays(index: u8) -> BitBoard {
+    let mut mask = 0;
+    let mut i = 4;
+    while i < 8 {
+        mask |= ray(index, DIRECTIONS[i].0, DIRECTIONS[i].1);
+        i += 1;
+    }
+    BitBoard(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::position::Position as Pos;
+
+    use super::*;
+
+    #[test]
+    fn test_between() {
+        let between = BETWEEN[Pos::E1 as usize][Pos::H1 as usize];
+        assert_eq!(
+            between,
+            BitBoard::from(vec![Pos::F1.into(), Pos::G1.into()])
+        );
+
+        let between = BETWEEN[Pos::H8 as usize][Pos::A1 as usize];
+        assert_eq!(between.count(), 6);
+        assert!(between.get_bit(Pos::D4.into()));
+
+        assert_eq!(BETWEEN[Pos::A1 as usize][Pos::B3 as usize], BitBoard(0));
+        assert_eq!(BETWEEN[Pos::A1 as usize][Pos::A2 as usize], BitBoard(0));
+        assert_eq!(BETWEEN[Pos::A1 as usize][Pos::A1 as usize], BitBoard(0));
+    }
+
+    #[test]
+    fn test_line() {
+        let line = LINE[Pos::B2 as usize][Pos::C3 as usize];
+        assert_eq!(line.count(), 8);
+        assert!(line.get_bit(Pos::A1.into()));
+        assert!(line.get_bit(Pos::H8.into()));
+
+        assert_eq!(LINE[Pos::E1 as usize][Pos::E4 as usize].count(), 8);
+        assert_eq!(LINE[Pos::A1 as usize][Pos::B3 as usize], BitBoard(0));
+    }
+
+    #[test]
+    fn test_step_attacks() {
+        assert_eq!(
+            KNIGHT_ATTACKS[Pos::A1 as usize],
+            BitBoard::from(vec![Pos::B3.into(), Pos::C2.into()])
+        );
+        assert_eq!(KNIGHT_ATTACKS[Pos::D4 as usize].count(), 8);
+        assert_eq!(KING_ATTACKS[Pos::H8 as usize].count(), 3);
+        assert_eq!(KING_ATTACKS[Pos::E4 as usize].count(), 8);
+        assert_eq!(
+            PAWN_ATTACKS[0][Pos::A2 as usize],
+            BitBoard::from(vec![Pos::B3.into()])
+        );
+        assert_eq!(
+            PAWN_ATTACKS[1][Pos::E7 as usize],
+            BitBoard::from(vec![Pos::D6.into(), Pos::F6.into()])
+        );
+    }
+}
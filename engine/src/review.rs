@@ -0,0 +1,747 @@
+use pleco::{
+    core::{CastleType, GenTypes, PieceType, Player},
+    helper::prelude::{king_moves, pawn_attacks_from},
+    tools::eval::{Eval, PAWN_POS},
+    BitBoard, BitMove, Board,
+};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use super::{
+    color::Color,
+    error::GameError,
+    state::GameState,
+    transposition::{Bound, TranspositionTable},
+};
+
+/// How many plies the engine looks ahead when evaluating a position
+const REVIEW_DEPTH: u16 = 2;
+
+/// How many captures are followed up at the end of the search, so a hanging piece doesn't flip the evaluation every ply
+const QUIESCENCE_DEPTH: u16 = 4;
+
+/// Transposition table slots shared by all positions of a reviewed game
+const REVIEW_TABLE_SIZE: usize = 1 << 16;
+
+/// Transposition table slots for evaluating a single position
+const EVALUATE_TABLE_SIZE: usize = 1 << 12;
+
+/// Evaluations are capped to this, a forced mate scores the full amount
+pub const MATE_SCORE: i32 = 10_000;
+
+/// Evaluations beyond this are drawn at the edge of eval graphs
+pub const GRAPH_CAP: i32 = 1000;
+
+/// Captures followed in a row when looking for hanging material
+const HANGING_DEPTH: u16 = 4;
+
+/// Losing less isn't worth a blunder warning, so giving up a pawn for activity doesn't trigger one
+const MIN_BLUNDER_MATERIAL: i32 = 200;
+
+/// Evaluation losses (from the perspective of the moving color) at which a move gets annotated
+const ANNOTATIONS: [(i32, &str); 3] = [(500, "??"), (250, "?"), (100, "?!")];
+
+/// A single reviewed ply, evaluations are from white's perspective (positive favors white)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewedPly {
+    pub ply: usize,
+    pub color: Color,
+    pub san: String,
+    /// Position after the ply was played
+    pub fen: String,
+    pub eval: i32,
+    /// How much worse the position got for the moving color
+    pub eval_loss: i32,
+    /// ?! for inaccuracies, ? for mistakes and ?? for blunders
+    pub annotation: Option<String>,
+}
+
+/// How well a player played over a whole game, like the post-game numbers of Lichess
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct PlayerAccuracy {
+    /// Mean accuracy of all moves from 0 to 100, derived from the lost winning chances
+    pub accuracy: f64,
+    /// Average centipawn loss per move, evaluations are capped at 10 pawns
+    pub acpl: u32,
+}
+
+/// Winning chances from 0 to 100 for an evaluation from the perspective of the moving color
+fn win_percent(eval: i32) -> f64 {
+    let eval = eval.clamp(-GRAPH_CAP, GRAPH_CAP) as f64;
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * eval).exp()) - 1.0)
+}
+
+/// Accuracy of a single move from 0 to 100 by the winning chances before and after it
+fn move_accuracy(win_before: f64, win_after: f64) -> f64 {
+    let loss = (win_before - win_after).max(0.0);
+    (103.1668 * (-0.04354 * loss).exp() - 3.1669).clamp(0.0, 100.0)
+}
+
+/// Engine evaluation of every position of a game
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameReview {
+    /// Evaluation of the starting position
+    pub initial_eval: i32,
+    pub plies: Vec<ReviewedPly>,
+}
+
+impl GameReview {
+    /// Replays the whole game and evaluates the position after every ply
+    #[instrument(name = "review", skip_all)]
+    pub fn new(game_state: &GameState) -> Result<Self, GameError> {
+        let mut state = GameState::new()?;
+        let mut table = TranspositionTable::new(REVIEW_TABLE_SIZE);
+        let initial_eval = evaluate_with(&state, &mut table)?;
+
+        let mut previous_eval = initial_eval;
+        let mut plies = Vec::with_capacity(game_state.move_log.len());
+        for (ply, chess_move) in game_state.move_log.iter().enumerate() {
+            let color = Color::from(state.next_to_move as usize);
+            state.replay_move(*chess_move)?;
+
+            let eval = evaluate_with(&state, &mut table)?;
+            let eval_loss = match color {
+                Color::BLACK => eval - previous_eval,
+                _ => previous_eval - eval,
+            }
+            .max(0);
+            let annotation = ANNOTATIONS
+                .iter()
+                .find(|(threshold, _)| eval_loss >= *threshold)
+                .map(|(_, annotation)| annotation.to_string());
+
+            plies.push(ReviewedPly {
+                ply: ply + 1,
+                color,
+                san: game_state.san_log.get(ply).cloned().unwrap_or_default(),
+                fen: state.to_fen(),
+                eval,
+                eval_loss,
+                annotation,
+            });
+            previous_eval = eval;
+        }
+
+        Ok(Self {
+            initial_eval,
+            plies,
+        })
+    }
+
+    /// The evaluation of every position, starting with the initial one
+    pub fn get_evals(&self) -> Vec<i32> {
+        let mut evals = vec![self.initial_eval];
+        evals.extend(self.plies.iter().map(|ply| ply.eval));
+        evals
+    }
+
+    /// Accuracy and average centipawn loss of the given color, None if it didn't move
+    pub fn get_accuracy(&self, color: Color) -> Option<PlayerAccuracy> {
+        let sign = if color == Color::BLACK { -1 } else { 1 };
+        let evals = self.get_evals();
+        let moves: Vec<(i32, i32)> = evals
+            .windows(2)
+            .zip(&self.plies)
+            .filter(|(_, ply)| ply.color == color)
+            .map(|(evals, _)| {
+                (
+                    sign * evals[0].clamp(-GRAPH_CAP, GRAPH_CAP),
+                    sign * evals[1].clamp(-GRAPH_CAP, GRAPH_CAP),
+                )
+            })
+            .collect();
+        if moves.is_empty() {
+            return None;
+        }
+
+        let count = moves.len() as f64;
+        let accuracy = moves
+            .iter()
+            .map(|(before, after)| move_accuracy(win_percent(*before), win_percent(*after)))
+            .sum::<f64>()
+            / count;
+        let centipawn_loss: i32 = moves
+            .iter()
+            .map(|(before, after)| (before - after).max(0))
+            .sum();
+
+        Some(PlayerAccuracy {
+            accuracy: (accuracy * 10.0).round() / 10.0,
+            acpl: (centipawn_loss as f64 / count).round() as u32,
+        })
+    }
+
+    /// The plies with the biggest evaluation losses in the order they were played
+    pub fn get_key_plies(&self, count: usize) -> Vec<&ReviewedPly> {
+        let mut key_plies: Vec<&ReviewedPly> = self
+            .plies
+            .iter()
+            .filter(|ply| ply.annotation.is_some())
+            .collect();
+        key_plies.sort_by_key(|ply| -ply.eval_loss);
+        key_plies.truncate(count);
+        key_plies.sort_by_key(|ply| ply.ply);
+        key_plies
+    }
+}
+
+/// Evaluates a position from white's perspective (positive favors white)
+pub fn evaluate(state: &GameState) -> Result<i32, GameError> {
+    evaluate_with(state, &mut TranspositionTable::new(EVALUATE_TABLE_SIZE))
+}
+
+/// Like evaluate, but reuses the results of earlier searches in the table
+pub fn evaluate_with(state: &GameState, table: &mut TranspositionTable) -> Result<i32, GameError> {
+    let mut board = Board::from_fen(&state.to_fen())?;
+    let score = search(&mut board, table, -MATE_SCORE, MATE_SCORE, REVIEW_DEPTH);
+
+    match Color::from(state.next_to_move as usize) {
+        Color::BLACK => Ok(-score),
+        _ => Ok(score),
+    }
+}
+
+/// The terms of the static evaluation of one side, in centipawns except for the mobility
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EvalTerms {
+    /// Pawns and pieces on the board, including the bonus for the bishop pair
+    pub material: i32,
+    /// Castling rights, own pieces around the king and pins, minus a penalty for being in check
+    pub king_safety: i32,
+    /// Advanced and connected pawns, minus penalties for doubled and isolated ones
+    pub pawn_structure: i32,
+    /// Squares the knights, bishops, rooks and queens can move to, the engine doesn't score it
+    pub mobility: u32,
+}
+
+impl EvalTerms {
+    /// Mirrors the static evaluation the search uses at its leaves, split into named terms
+    fn new(board: &Board, player: Player) -> Self {
+        let own_pieces = board.get_occupied_player(player);
+
+        let bishop_pair = if board.count_piece(player, PieceType::B) > 1 {
+            19
+        } else {
+            0
+        };
+        let material = board.count_piece(player, PieceType::P) as i32 * 100
+            + board.non_pawn_material(player)
+            + bishop_pair;
+
+        let castling_rights = [CastleType::KingSide, CastleType::QueenSide]
+            .into_iter()
+            .filter(|castle_type| board.can_castle(player, *castle_type))
+            .count() as i32;
+        let check = if board.in_check() && board.turn() == player {
+            14
+        } else {
+            0
+        };
+        let shelter = (king_moves(board.king_sq(player)) & own_pieces).count_bits() as i32;
+        let pinned = board.all_pinned_pieces(player);
+        let own_pinned = (pinned & own_pieces).count_bits() as i32;
+        let other_pinned = (pinned & board.get_occupied_player(!player)).count_bits() as i32;
+        let king_safety =
+            7 * castling_rights - check + 9 * shelter + 18 * own_pinned + 6 * other_pinned;
+
+        let mobility = [PieceType::N, PieceType::B, PieceType::R, PieceType::Q]
+            .into_iter()
+            .flat_map(|piece| {
+                board
+                    .piece_bb(player, piece)
+                    .map(move |square| (piece, square))
+            })
+            .map(|(piece, square)| {
+                (board.attacks_from(piece, square, player) & !own_pieces).count_bits() as u32
+            })
+            .sum();
+
+        Self {
+            material,
+            king_safety,
+            pawn_structure: pawn_structure(board, player),
+            mobility,
+        }
+    }
+
+    /// The centipawns of the terms the engine scores
+    pub fn total(&self) -> i32 {
+        self.material + self.king_safety + self.pawn_structure
+    }
+}
+
+fn pawn_structure(board: &Board, player: Player) -> i32 {
+    let pawns = board.piece_bb(player, PieceType::P);
+    let mut score = 0;
+    let mut file_counts = [0i32; 8];
+    let mut defended = BitBoard(0);
+    for square in pawns {
+        defended |= pawn_attacks_from(square, player);
+        file_counts[(square.0 % 8) as usize] += 1;
+        score += PAWN_POS[player as usize][square.0 as usize];
+    }
+
+    // Squares covered by pawns, pawns covering other pawns count extra
+    score += defended.count_bits() as i32 + 3 * (defended & pawns).count_bits() as i32;
+
+    for file in 0..8 {
+        if file_counts[file] > 1 {
+            score -= 3 * file_counts[file];
+        }
+        if file == 0 || file == 7 || file_counts[file] == 0 {
+            continue;
+        }
+        score += match (file_counts[file - 1] > 0, file_counts[file + 1] > 0) {
+            (true, true) => 7,
+            (false, false) => -4,
+            _ => 3,
+        };
+    }
+    score
+}
+
+/// Evaluation of a position broken down into the terms of both sides
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EvalExplanation {
+    /// Evaluation of the search like in game reviews, from white's perspective (positive favors white)
+    pub eval: i32,
+    /// Evaluation of the position without looking ahead, the total of white's terms minus black's
+    pub static_eval: i32,
+    pub white: EvalTerms,
+    pub black: EvalTerms,
+    /// One sentence per term saying which side it favors
+    pub summary: Vec<String>,
+}
+
+/// Breaks the evaluation of a position down into material, king safety, pawn structure and mobility
+pub fn explain(state: &GameState) -> Result<EvalExplanation, GameError> {
+    let board = Board::from_fen(&state.to_fen())?;
+    let white = EvalTerms::new(&board, Player::White);
+    let black = EvalTerms::new(&board, Player::Black);
+
+    let terms = [
+        ("Material", white.material - black.material),
+        ("King safety", white.king_safety - black.king_safety),
+        (
+            "Pawn structure",
+            white.pawn_structure - black.pawn_structure,
+        ),
+    ];
+    let mut summary: Vec<String> = terms
+        .into_iter()
+        .map(|(name, difference)| {
+            let pawns = format!("{:.2} pawns", difference.abs() as f64 / 100.0);
+            describe_term(name, difference, &pawns)
+        })
+        .collect();
+    let mobility = white.mobility as i32 - black.mobility as i32;
+    let squares = format!("{} squares", mobility.abs());
+    summary.push(describe_term("Mobility", mobility, &squares));
+
+    Ok(EvalExplanation {
+        eval: evaluate(state)?,
+        static_eval: white.total() - black.total(),
+        white,
+        black,
+        summary,
+    })
+}
+
+/// e.g. "Material favors white by 3.00 pawns" or "Mobility is equal"
+fn describe_term(name: &str, difference: i32, amount: &str) -> String {
+    match difference.signum() {
+        1 => format!("{} favors white by {}", name, amount),
+        -1 => format!("{} favors black by {}", name, amount),
+        _ => format!("{} is equal", name),
+    }
+}
+
+/// A legal move and its score from the perspective of the moving color
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RankedMove {
+    pub bit_move: BitMove,
+    pub score: i32,
+}
+
+/// Scores every legal move with a search of the given depth, best first, like the multi-PV mode of other engines
+/// Pawns only promote to queens in this game, so the other promotions are left out
+pub fn rank_moves(state: &GameState, depth: u16) -> Result<Vec<RankedMove>, GameError> {
+    let mut board = Board::from_fen(&state.to_fen())?;
+    let mut table = TranspositionTable::new(EVALUATE_TABLE_SIZE);
+    let moves = board.generate_moves();
+
+    let mut ranked = Vec::with_capacity(moves.len());
+    for bit_move in moves.iter().copied() {
+        if bit_move.is_promo() && bit_move.promo_piece() != PieceType::Q {
+            continue;
+        }
+        board.apply_move(bit_move);
+        // A full window for every move, so the scores of worse moves are exact as well
+        let score = -search(
+            &mut board,
+            &mut table,
+            -MATE_SCORE,
+            MATE_SCORE,
+            depth.saturating_sub(1),
+        );
+        board.undo_move();
+        ranked.push(RankedMove { bit_move, score });
+    }
+    ranked.sort_by_key(|ranked_move| -ranked_move.score);
+    Ok(ranked)
+}
+
+/// Material a move left hanging, which the opponent can win right away with a series of captures
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct HangingMaterial {
+    /// Cell of the piece starting the captures of the opponent
+    pub from: String,
+    /// Cell of the first piece the opponent captures
+    pub to: String,
+    /// Centipawns lost, counting pawns 100, knights and bishops 300, rooks 500 and queens 900
+    pub material: i32,
+}
+
+/// Checks if the move from `before` to `after` left at least two pawns worth of material hanging
+/// Material the move captured itself is subtracted, so starting an even trade isn't a blunder
+pub fn find_blunder(
+    before: &GameState,
+    after: &GameState,
+) -> Result<Option<HangingMaterial>, GameError> {
+    let color = Color::from(before.next_to_move as usize);
+    let [before_own, before_other] = relative_material(before, color);
+    let [after_own, after_other] = relative_material(after, color);
+    let move_gain = 100 * ((before_other - after_other) + (after_own - before_own));
+
+    let mut board = Board::from_fen(&after.to_fen())?;
+    let mut blunder: Option<HangingMaterial> = None;
+    for bit_move in generate_captures(&board) {
+        let captured = captured_value(&board, bit_move);
+        board.apply_move(bit_move);
+        let material = captured - capture_gain(&mut board, HANGING_DEPTH - 1) - move_gain;
+        board.undo_move();
+
+        let best = blunder
+            .as_ref()
+            .map_or(MIN_BLUNDER_MATERIAL - 1, |blunder| blunder.material);
+        if material > best {
+            blunder = Some(HangingMaterial {
+                from: bit_move.get_src().to_string(),
+                to: bit_move.get_dest().to_string(),
+                material,
+            });
+        }
+    }
+    Ok(blunder)
+}
+
+/// Material in pawns of the given color first and of its opponent second
+fn relative_material(state: &GameState, color: Color) -> [i32; 2] {
+    let [white, black] = state.material();
+    match color {
+        Color::BLACK => [black, white],
+        _ => [white, black],
+    }
+}
+
+/// The most material the player to move wins with a series of captures, 0 if capturing doesn't pay off
+fn capture_gain(board: &mut Board, depth: u16) -> i32 {
+    if depth == 0 {
+        return 0;
+    }
+
+    let mut best = 0;
+    for bit_move in generate_captures(board) {
+        let captured = captured_value(board, bit_move);
+        // Even if the opponent can't take anything back, this capture can't beat the best one
+        if captured <= best {
+            continue;
+        }
+        board.apply_move(bit_move);
+        best = best.max(captured - capture_gain(board, depth - 1));
+        board.undo_move();
+    }
+    best
+}
+
+/// Legal captures of the player to move, pleco only generates captures on their own when not in check
+fn generate_captures(board: &Board) -> Vec<BitMove> {
+    if board.in_check() {
+        board
+            .generate_moves()
+            .iter()
+            .copied()
+            .filter(|bit_move| board.is_capture(*bit_move))
+            .collect()
+    } else {
+        board
+            .generate_moves_of_type(GenTypes::Captures)
+            .iter()
+            .copied()
+            .collect()
+    }
+}
+
+fn captured_value(board: &Board, bit_move: BitMove) -> i32 {
+    if bit_move.is_en_passant() {
+        return 100;
+    }
+    match board.piece_at_sq(bit_move.get_dest()).type_of() {
+        PieceType::P => 100,
+        PieceType::N | PieceType::B => 300,
+        PieceType::R => 500,
+        PieceType::Q => 900,
+        _ => 0,
+    }
+}
+
+/// Negamax with alpha-beta pruning, scores are from the perspective of the color to move
+fn search(
+    board: &mut Board,
+    table: &mut TranspositionTable,
+    mut alpha: i32,
+    beta: i32,
+    depth: u16,
+) -> i32 {
+    if depth == 0 {
+        return quiescence(board, alpha, beta, QUIESCENCE_DEPTH);
+    }
+
+    let hash = board.zobrist();
+    if let Some(score) = table.probe(hash, depth, alpha, beta) {
+        return score;
+    }
+
+    let moves = board.generate_moves();
+    if moves.is_empty() {
+        let score = if board.in_check() { -MATE_SCORE } else { 0 };
+        table.store(hash, depth, score, Bound::Exact);
+        return score;
+    }
+
+    let original_alpha = alpha;
+    for bit_move in moves.iter() {
+        board.apply_move(*bit_move);
+        let score = -search(board, table, -beta, -alpha, depth - 1);
+        board.undo_move();
+        if score >= beta {
+            table.store(hash, depth, beta, Bound::Lower);
+            return beta;
+        }
+        alpha = alpha.max(score);
+    }
+
+    let bound = if alpha > original_alpha {
+        Bound::Exact
+    } else {
+        Bound::Upper
+    };
+    table.store(hash, depth, alpha, bound);
+    alpha
+}
+
+/// Only looks at captures (or evasions when in check) until the position is quiet
+fn quiescence(board: &mut Board, mut alpha: i32, beta: i32, depth: u16) -> i32 {
+    let stand_pat = Eval::eval_low(board).clamp(-MATE_SCORE + 1, MATE_SCORE - 1);
+    if depth == 0 {
+        return stand_pat;
+    }
+
+    let in_check = board.in_check();
+    let moves = if in_check {
+        board.generate_moves()
+    } else {
+        board.generate_moves_of_type(GenTypes::Captures)
+    };
+    if in_check && moves.is_empty() {
+        return -MATE_SCORE;
+    }
+
+    if !in_check {
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+    }
+
+    for bit_move in moves.iter() {
+        board.apply_move(*bit_move);
+        let score = -quiescence(board, -beta, -alpha, depth - 1);
+        board.undo_move();
+        if score >= beta {
+            return beta;
+        }
+        alpha = alpha.max(score);
+    }
+    alpha
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::position::Position as Pos;
+
+    use super::*;
+
+    #[test]
+    fn test_review() {
+        let mut state = GameState::new().unwrap();
+        // Fool's mate
+        for (from, to) in [
+            (Pos::F2, Pos::F3),
+            (Pos::E7, Pos::E5),
+            (Pos::G2, Pos::G4),
+            (Pos::D8, Pos::H4),
+        ] {
+            state.make_move(from.into(), to.into()).unwrap();
+        }
+
+        let review = GameReview::new(&state).unwrap();
+        assert_eq!(review.plies.len(), 4);
+        assert_eq!(review.get_evals().len(), 5);
+
+        let last = review.plies.last().unwrap();
+        assert_eq!(last.eval, -MATE_SCORE);
+        assert_eq!(last.color, Color::BLACK);
+
+        // g4 allows the mate
+        let blunder = &review.plies[2];
+        assert_eq!(blunder.annotation.as_deref(), Some("??"));
+        assert_eq!(review.get_key_plies(1)[0].ply, 3);
+    }
+
+    #[test]
+    fn test_accuracy() {
+        let ply = |ply: usize, color: Color, eval: i32| ReviewedPly {
+            ply,
+            color,
+            san: String::new(),
+            fen: String::new(),
+            eval,
+            eval_loss: 0,
+            annotation: None,
+        };
+        let mut review = GameReview {
+            initial_eval: 0,
+            plies: Vec::new(),
+        };
+        assert_eq!(review.get_accuracy(Color::WHITE), None);
+
+        // White's second move loses everything, capped at 10 pawns, black never loses anything
+        review.plies = vec![
+            ply(1, Color::WHITE, 0),
+            ply(2, Color::BLACK, 0),
+            ply(3, Color::WHITE, -MATE_SCORE),
+            ply(4, Color::BLACK, -MATE_SCORE),
+        ];
+        let white = review.get_accuracy(Color::WHITE).unwrap();
+        assert_eq!(white.acpl, 500);
+        assert!(white.accuracy > 50.0 && white.accuracy < 60.0);
+
+        let black = review.get_accuracy(Color::BLACK).unwrap();
+        assert_eq!(black.acpl, 0);
+        assert_eq!(black.accuracy, 100.0);
+    }
+
+    #[test]
+    fn test_explain() {
+        let explanation = explain(&GameState::new().unwrap()).unwrap();
+        assert_eq!(explanation.white, explanation.black);
+        assert_eq!(explanation.static_eval, 0);
+        assert_eq!(explanation.white.mobility, 4);
+        assert!(explanation
+            .summary
+            .iter()
+            .all(|line| line.ends_with("is equal")));
+
+        // The terms add up to the static evaluation of the engine
+        for fen in [
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+            "r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R b KQkq - 0 5",
+            "4k3/8/3p4/1p2P3/1P6/8/5PPP/3R2K1 w - - 0 30",
+            "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3",
+        ] {
+            let state = GameState::from_fen(fen).unwrap();
+            let explanation = explain(&state).unwrap();
+            let board = Board::from_fen(fen).unwrap();
+            let static_eval = match board.turn() {
+                Player::White => Eval::eval_low(&board),
+                Player::Black => -Eval::eval_low(&board),
+            };
+            assert_eq!(explanation.static_eval, static_eval, "{}", fen);
+        }
+
+        let state = GameState::from_fen("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap();
+        let explanation = explain(&state).unwrap();
+        assert!(explanation.static_eval > 500);
+        assert_eq!(explanation.black.mobility, 0);
+        assert_eq!(
+            explanation.summary[0],
+            format!(
+                "Material favors white by {:.2} pawns",
+                (explanation.white.material - explanation.black.material) as f64 / 100.0
+            )
+        );
+    }
+
+    #[test]
+    fn test_find_blunder() {
+        let play = |moves: &[(Pos, Pos)]| {
+            let mut states = vec![GameState::new().unwrap()];
+            for (from, to) in moves {
+                let mut state = states.last().unwrap().clone();
+                state.make_move((*from).into(), (*to).into()).unwrap();
+                states.push(state);
+            }
+            states
+        };
+
+        // Ba6 hangs the bishop to the pawn on b7
+        let states = play(&[(Pos::E2, Pos::E4), (Pos::E7, Pos::E5), (Pos::F1, Pos::A6)]);
+        assert_eq!(find_blunder(&states[1], &states[2]).unwrap(), None);
+        let blunder = find_blunder(&states[2], &states[3]).unwrap().unwrap();
+        assert_eq!((blunder.from.as_str(), blunder.to.as_str()), ("b7", "a6"));
+        assert_eq!(blunder.material, 300);
+
+        // Qxe5+ wins a pawn for the queen
+        let states = play(&[
+            (Pos::E2, Pos::E4),
+            (Pos::E7, Pos::E5),
+            (Pos::D1, Pos::H5),
+            (Pos::B8, Pos::C6),
+            (Pos::H5, Pos::E5),
+        ]);
+        let blunder = find_blunder(&states[4], &states[5]).unwrap().unwrap();
+        assert_eq!((blunder.from.as_str(), blunder.to.as_str()), ("c6", "e5"));
+        assert_eq!(blunder.material, 800);
+
+        // Bxc6 starts an even trade
+        let states = play(&[
+            (Pos::E2, Pos::E4),
+            (Pos::E7, Pos::E5),
+            (Pos::G1, Pos::F3),
+            (Pos::B8, Pos::C6),
+            (Pos::F1, Pos::B5),
+            (Pos::A7, Pos::A6),
+            (Pos::B5, Pos::C6),
+        ]);
+        assert_eq!(find_blunder(&states[6], &states[7]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_rank_moves() {
+        let state = GameState::new().unwrap();
+        let ranked = rank_moves(&state, 2).unwrap();
+        assert_eq!(ranked.len(), 20);
+        assert!(ranked.windows(2).all(|pair| pair[0].score >= pair[1].score));
+
+        // Taking the free queen beats everything else
+        let state = GameState::from_fen("4k3/8/8/3q4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let ranked = rank_moves(&state, 2).unwrap();
+        assert_eq!(ranked[0].bit_move.get_dest().to_string(), "d5");
+        assert!(ranked[0].score - ranked[1].score > 500);
+    }
+}
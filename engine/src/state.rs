@@ -0,0 +1,1660 @@
+use crate::{bit_board::BitBoard, chess_board::ChessBoard};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+use super::{
+    chess_board::{AvailableMoves, IllegalMoveReason},
+    color::Color,
+    error::GameError,
+    phase::GamePhase,
+    piece::Piece,
+    position::{Move, MoveKind, Position, Square},
+    termination::Termination,
+};
+
+/// Version of the binary encoding written by GameState::to_bytes
+/// Version 1 stored the move counters and the tick as single bytes,
+/// versions 1 and 2 stored moves as (from, to) pairs with 64 and 65 standing for castling,
+/// versions 1 to 3 stored a flag per ending instead of the termination
+const ENCODING_VERSION: u8 = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameState {
+    pub chess_board: ChessBoard,
+    /// Next to move, 0 = white, 1 = black
+    pub next_to_move: u8,
+    half_move_counter: u16,
+    full_move_counter: u16,
+    #[serde(default)]
+    tick: u16,
+    /// Initial pawn locations by color, 0 = white, 1 = black
+    initial_pawn_masks: [BitBoard; 2],
+    /// All available moves by color, computed on demand and dropped whenever the board changes
+    #[serde(skip)]
+    available_moves: [OnceLock<AvailableMoves>; 2],
+    /// Check state by color
+    check_states: [bool; 2],
+    /// All pieces giving check to the color to move
+    #[serde(default)]
+    pub checkers: BitBoard,
+    /// The fields of en passant by color, 64 being the NONE state
+    en_passant_indices: [u8; 2],
+    /// Castle rights by color
+    kingside_castling_rights: [bool; 2],
+    queenside_castling_rights: [bool; 2],
+    /// Castle abilities by color
+    pub can_castle_kingside: [bool; 2],
+    pub can_castle_queenside: [bool; 2],
+    /// Rook & king positions by color
+    king_indices: [u8; 2],
+    kingside_rook_indices: [u8; 2],
+    queenside_rook_indices: [u8; 2],
+    /// The winner color, 0 = white, 1 = black, 2 = none, set once a player is checkmate
+    pub winner: u8,
+    /// How the game ended, None while it is running
+    #[serde(default)]
+    pub termination: Option<Termination>,
+    #[serde(default, with = "move_log_serde")]
+    pub move_log: Vec<Move>,
+    /// Piece captured by each move of the move log, 6 (NONE) if it didn't capture anything
+    #[serde(default)]
+    pub capture_log: Vec<u8>,
+    #[serde(default)]
+    pub san_log: Vec<String>,
+}
+
+impl GameState {
+    pub fn new() -> Result<Self, GameError> {
+        let board = ChessBoard::default();
+        let initial_pawn_masks = [
+            board.mask_by_piece_and_color(Piece::PAWN, Color::WHITE),
+            board.mask_by_piece_and_color(Piece::PAWN, Color::BLACK),
+        ];
+
+        let mut game_state = Self {
+            initial_pawn_masks,
+            chess_board: board,
+            next_to_move: 0,
+            half_move_counter: 0,
+            full_move_counter: 1,
+            tick: 0,
+            available_moves: Default::default(),
+            check_states: [false, false],
+            checkers: BitBoard::default(),
+            en_passant_indices: [64, 64],
+            kingside_castling_rights: [true, true],
+            queenside_castling_rights: [true, true],
+            can_castle_kingside: [false, false],
+            can_castle_queenside: [false, false],
+            // Uses default board, therefore positions are predictable
+            king_indices: [4, 60],
+            kingside_rook_indices: [7, 63],
+            queenside_rook_indices: [0, 56],
+            winner: 2,
+            termination: None,
+            move_log: Vec::new(),
+            capture_log: Vec::new(),
+            san_log: Vec::new(),
+        };
+
+        game_state.update()?;
+
+        Ok(game_state)
+    }
+
+    pub fn to_fen(&self) -> String {
+        let fen_positions = self.chess_board.to_fen_positions();
+
+        let color_to_move = Color::from(self.next_to_move as usize);
+        let active_color = color_to_move.get_fen_letter();
+
+        let mut castling_rights = String::new();
+        if self.kingside_castling_rights[Color::WHITE as usize] {
+            castling_rights.push('K');
+        }
+        if self.queenside_castling_rights[Color::WHITE as usize] {
+            castling_rights.push('Q');
+        }
+        if self.kingside_castling_rights[Color::BLACK as usize] {
+            castling_rights.push('k');
+        }
+        if self.queenside_castling_rights[Color::BLACK as usize] {
+            castling_rights.push('q');
+        }
+
+        if castling_rights.is_empty() {
+            castling_rights = "-".to_string();
+        }
+
+        let en_passant_cell = self.en_passant_indices[color_to_move.opponent_color() as usize];
+        let en_passant = if en_passant_cell == 64 {
+            "-".to_string()
+        } else {
+            Position::try_from(en_passant_cell)
+                .unwrap()
+                .as_str()
+                .to_lowercase()
+        };
+
+        format!(
+            "{} {} {} {} {} {}",
+            fen_positions,
+            active_color,
+            castling_rights,
+            en_passant,
+            self.half_move_counter,
+            self.full_move_counter
+        )
+    }
+
+    pub fn get_san(&self) -> String {
+        let mut result = String::new();
+        let moves = &self.san_log;
+
+        for i in 0..(moves.len() / 2) {
+            let move_number = i + 1;
+            let white_move = &moves[2 * i];
+            let black_move = &moves[2 * i + 1];
+
+            result.push_str(&format!("{}. {} {} ", move_number, white_move, black_move));
+        }
+
+        if !moves.len().is_multiple_of(2) {
+            let last_move_number = moves.len() / 2 + 1;
+            let last_move = &moves[moves.len() - 1];
+
+            result.push_str(&format!("{}. {} ", last_move_number, last_move));
+        }
+
+        result.trim_end().to_string()
+    }
+
+    pub fn from_fen(fen: &str) -> Result<Self, GameError> {
+        let parts: Vec<&str> = fen.split(' ').collect();
+        if parts.len() != 6 {
+            return Err(GameError::ValidationError(format!(
+                "FEN-String needs 6 components, found {}",
+                parts.len()
+            )));
+        };
+
+        let chess_board = ChessBoard::from_fen_positions(parts[0])?;
+
+        let active_color = match parts[1] {
+            "w" => Color::WHITE,
+            "b" => Color::BLACK,
+            _ => {
+                return Err(GameError::ValidationError(format!(
+                    "Invalid active color '{}', expected w or b",
+                    parts[1]
+                )))
+            }
+        };
+        if chess_board.is_king_check(active_color.opponent_color()) {
+            return Err(GameError::ValidationError(
+                "The color which just moved can't be in check".to_string(),
+            ));
+        }
+
+        let kingside_castling_rights = [parts[2].contains('K'), parts[2].contains('k')];
+        let queenside_castling_rights = [parts[2].contains('Q'), parts[2].contains('q')];
+        validate_castling_rights(
+            &chess_board,
+            parts[2],
+            kingside_castling_rights,
+            queenside_castling_rights,
+        )?;
+
+        let en_passant_index = match parts[3] {
+            "-" => 64,
+            cell => validate_en_passant(&chess_board, active_color, cell)?,
+        };
+        let (white_en_passent, black_en_passent) = match active_color {
+            Color::WHITE => (64, en_passant_index),
+            _ => (en_passant_index, 64),
+        };
+
+        let half_move_counter = parts[4].parse::<u16>().map_err(|_| {
+            GameError::ValidationError(format!("Invalid halfmove clock '{}'", parts[4]))
+        })?;
+        let full_move_counter = match parts[5].parse::<u16>() {
+            Ok(counter) if counter >= 1 => counter,
+            _ => {
+                return Err(GameError::ValidationError(format!(
+                    "Invalid fullmove number '{}', it starts at 1",
+                    parts[5]
+                )))
+            }
+        };
+
+        // Since its FEN, the pawns will always be in the same rows
+        let initial_pawn_masks = [
+            BitBoard(0b0000000000000000000000000000000000000000000000001111111100000000),
+            BitBoard(0b0000000011111111000000000000000000000000000000000000000000000000),
+        ];
+
+        // Castling king/rook indices, important to comply with Fischer960 king and rook positions
+        let white_king = if kingside_castling_rights[Color::WHITE as usize]
+            || queenside_castling_rights[Color::WHITE as usize]
+        {
+            chess_board.get_king_position_by_color(Color::WHITE)
+        } else {
+            4
+        };
+
+        let black_king = if kingside_castling_rights[Color::BLACK as usize]
+            || queenside_castling_rights[Color::BLACK as usize]
+        {
+            chess_board.get_king_position_by_color(Color::BLACK)
+        } else {
+            60
+        };
+
+        let white_kingside_rook = if kingside_castling_rights[Color::WHITE as usize] {
+            let (_, rook) = chess_board
+                .get_kingside_rook(Color::WHITE)
+                .unwrap_or((4, 7));
+            rook
+        } else {
+            7
+        };
+
+        let black_kingside_rook = if kingside_castling_rights[Color::BLACK as usize] {
+            let (_, rook) = chess_board
+                .get_kingside_rook(Color::BLACK)
+                .unwrap_or((60, 63));
+            rook
+        } else {
+            63
+        };
+
+        let white_queenside_rook = if queenside_castling_rights[Color::WHITE as usize] {
+            let (_, rook) = chess_board
+                .get_queenside_rook(Color::WHITE)
+                .unwrap_or((4, 0));
+            rook
+        } else {
+            0
+        };
+
+        let black_queenside_rook = if queenside_castling_rights[Color::BLACK as usize] {
+            let (_, rook) = chess_board
+                .get_queenside_rook(Color::BLACK)
+                .unwrap_or((60, 56));
+            rook
+        } else {
+            56
+        };
+
+        let checkers = chess_board.get_checkers(active_color);
+
+        let mut state = Self {
+            initial_pawn_masks,
+            chess_board,
+            next_to_move: active_color as u8,
+            half_move_counter,
+            full_move_counter,
+            tick: 0,
+            available_moves: Default::default(),
+            check_states: [false, false],
+            checkers,
+            en_passant_indices: [white_en_passent, black_en_passent],
+            kingside_castling_rights,
+            queenside_castling_rights,
+            can_castle_kingside: [false, false],
+            can_castle_queenside: [false, false],
+            king_indices: [white_king, black_king],
+            kingside_rook_indices: [white_kingside_rook, black_kingside_rook],
+            queenside_rook_indices: [white_queenside_rook, black_queenside_rook],
+            winner: 2,
+            termination: None,
+            move_log: Vec::new(),
+            capture_log: Vec::new(),
+            san_log: Vec::new(),
+        };
+
+        state.update()?;
+
+        Ok(state)
+    }
+
+    /// Compact binary encoding for storage, starting with the encoding version.
+    /// Only the state which can't be derived from the position is written, the rest is recomputed when decoding.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![ENCODING_VERSION];
+        bytes.extend(self.chess_board.to_bytes());
+        bytes.push(self.next_to_move);
+        for counter in [self.half_move_counter, self.full_move_counter, self.tick] {
+            bytes.extend(counter.to_be_bytes());
+        }
+        for mask in &self.initial_pawn_masks {
+            bytes.extend(mask.0.to_be_bytes());
+        }
+        bytes.extend(self.checkers.0.to_be_bytes());
+        bytes.extend(self.en_passant_indices);
+        bytes.push(pack_flags(&[
+            self.kingside_castling_rights[0],
+            self.kingside_castling_rights[1],
+            self.queenside_castling_rights[0],
+            self.queenside_castling_rights[1],
+        ]));
+        bytes.extend(self.king_indices);
+        bytes.extend(self.kingside_rook_indices);
+        bytes.extend(self.queenside_rook_indices);
+        bytes.push(self.winner);
+        bytes.push(Termination::to_byte(self.termination));
+
+        bytes.extend((self.move_log.len() as u16).to_be_bytes());
+        for chess_move in &self.move_log {
+            bytes.extend(chess_move.to_bytes());
+        }
+        bytes.extend((self.capture_log.len() as u16).to_be_bytes());
+        bytes.extend(&self.capture_log);
+        bytes.extend((self.san_log.len() as u16).to_be_bytes());
+        for san in &self.san_log {
+            bytes.push(san.len() as u8);
+            bytes.extend(san.as_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, GameError> {
+        let mut reader = ByteReader(bytes);
+        let version = reader.read_u8()?;
+        if !(1..=ENCODING_VERSION).contains(&version) {
+            return Err(GameError::DecodingError(format!(
+                "Unknown game state encoding version {}",
+                version
+            )));
+        }
+
+        let chess_board = ChessBoard::from_bytes(reader.read(ChessBoard::ENCODED_LENGTH)?)?;
+        let next_to_move = reader.read_u8()?;
+        let [half_move_counter, full_move_counter, tick] = match version {
+            1 => reader.read_array::<3>()?.map(u16::from),
+            _ => [reader.read_u16()?, reader.read_u16()?, reader.read_u16()?],
+        };
+        let initial_pawn_masks = [reader.read_bit_board()?, reader.read_bit_board()?];
+        let checkers = reader.read_bit_board()?;
+        let en_passant_indices = reader.read_array()?;
+        let [white_kingside, black_kingside, white_queenside, black_queenside] =
+            unpack_flags(reader.read_u8()?);
+        let king_indices = reader.read_array()?;
+        let kingside_rook_indices = reader.read_array()?;
+        let queenside_rook_indices = reader.read_array()?;
+        let winner = reader.read_u8()?;
+        let termination = match version {
+            // Older encodings had a flag per ending and leave the dead position flag unset
+            1..=3 => {
+                let [draw, checkmate, resign, stalemate, remis, dead_position] =
+                    unpack_flags(reader.read_u8()?);
+                Termination::from_flags(draw, checkmate, resign, stalemate, remis, dead_position)
+            }
+            _ => match reader.read_u8()? {
+                0 => None,
+                byte => Some(Termination::from_byte(byte).ok_or(GameError::DecodingError(
+                    format!("Unknown termination {}", byte),
+                ))?),
+            },
+        };
+
+        let move_count = reader.read_u16()?;
+        let mut move_log = Vec::with_capacity(move_count as usize);
+        for _ in 0..move_count {
+            let chess_move = match version {
+                1 | 2 => {
+                    let [from, to] = reader.read_array()?;
+                    move_from_log_pair(from, to)?
+                }
+                _ => Move::from_bytes(reader.read_array()?)?,
+            };
+            move_log.push(chess_move);
+        }
+        let capture_count = reader.read_u16()?;
+        let capture_log = reader.read(capture_count as usize)?.to_vec();
+        let san_count = reader.read_u16()?;
+        let mut san_log = Vec::with_capacity(san_count as usize);
+        for _ in 0..san_count {
+            let length = reader.read_u8()?;
+            let san = std::str::from_utf8(reader.read(length as usize)?)
+                .map_err(|error| GameError::DecodingError(error.to_string()))?;
+            san_log.push(san.to_string());
+        }
+        if !reader.0.is_empty() {
+            return Err(GameError::DecodingError(
+                "Trailing bytes after game state".to_string(),
+            ));
+        }
+
+        // The indices are used without bounds checks later on, corrupt ones have to be rejected here
+        if next_to_move > 1 {
+            return Err(GameError::DecodingError(format!(
+                "Invalid side to move {}",
+                next_to_move
+            )));
+        }
+        let castling_rights = [
+            white_kingside || white_queenside,
+            black_kingside || black_queenside,
+        ];
+        // The en passant field lies behind a pawn which just moved two squares, 64 being the NONE state
+        for (color, fields) in [(Color::WHITE, 16..24), (Color::BLACK, 40..48)] {
+            let en_passant_index = en_passant_indices[color as usize];
+            if en_passant_index != 64 && !fields.contains(&en_passant_index) {
+                return Err(GameError::DecodingError(format!(
+                    "Invalid en passant index {}",
+                    en_passant_index
+                )));
+            }
+
+            // The king index is only kept up to date while the color is able to castle
+            let king_index = king_indices[color as usize];
+            let kings = chess_board.mask_by_piece_and_color(Piece::KING, color);
+            if king_index >= 64 || (castling_rights[color as usize] && !kings.get_bit(king_index)) {
+                return Err(GameError::DecodingError(format!(
+                    "Invalid king index {}",
+                    king_index
+                )));
+            }
+
+            for rook_index in [
+                kingside_rook_indices[color as usize],
+                queenside_rook_indices[color as usize],
+            ] {
+                if rook_index >= 64 {
+                    return Err(GameError::DecodingError(format!(
+                        "Invalid rook index {}",
+                        rook_index
+                    )));
+                }
+            }
+        }
+
+        let mut state = Self {
+            chess_board,
+            next_to_move,
+            half_move_counter,
+            full_move_counter,
+            tick,
+            initial_pawn_masks,
+            available_moves: Default::default(),
+            check_states: [false, false],
+            checkers,
+            en_passant_indices,
+            kingside_castling_rights: [white_kingside, black_kingside],
+            queenside_castling_rights: [white_queenside, black_queenside],
+            can_castle_kingside: [false, false],
+            can_castle_queenside: [false, false],
+            king_indices,
+            kingside_rook_indices,
+            queenside_rook_indices,
+            winner,
+            termination,
+            move_log,
+            capture_log,
+            san_log,
+        };
+
+        if version < 3 {
+            state.restore_move_kinds();
+        }
+
+        // Like update, but the stored end state is kept as is (e.g. a resignation can't be derived)
+        // and legal moves are only generated once they are needed, most loaded sessions are only read
+        state.update_check_states();
+        state.update_castle_ability();
+
+        Ok(state)
+    }
+
+    /// Older encodings only stored (from, to) pairs, the move kinds are recovered from the SAN log
+    pub fn restore_move_kinds(&mut self) {
+        for (chess_move, san) in self.move_log.iter_mut().zip(&self.san_log) {
+            if san.starts_with("O-O") {
+                let color = chess_move.from.index() / 32;
+                chess_move.from = Square(self.king_indices[color as usize]);
+                chess_move.kind = MoveKind::Castle;
+            } else if san.ends_with("e.p.") {
+                chess_move.kind = MoveKind::EnPassant;
+            } else if san.ends_with('Q') {
+                chess_move.kind = MoveKind::Promotion(Piece::QUEEN);
+            }
+        }
+    }
+
+    pub fn make_move(&mut self, from: Square, to: Square) -> Result<bool, GameError> {
+        let (piece, color) = self.chess_board.piece_and_color_at_cell(from.index())?;
+        let kind = if piece != Piece::PAWN {
+            MoveKind::Normal
+        } else if to.index() == self.en_passant_indices[color.opponent_color() as usize] {
+            MoveKind::EnPassant
+        } else if !(8..=55).contains(&to.index()) {
+            MoveKind::Promotion(Piece::QUEEN)
+        } else {
+            MoveKind::Normal
+        };
+
+        let previous_board = self.chess_board.clone();
+        let (success, capture_or_pawn_move, san_move) = self.chess_board.make_move(
+            from.index(),
+            to.index(),
+            &mut self.en_passant_indices,
+            &mut self.kingside_castling_rights,
+            &mut self.queenside_castling_rights,
+        )?;
+        if !success {
+            return Ok(false);
+        }
+
+        // Log the move
+        let captured_piece = self.get_captured_piece(&previous_board);
+        self.move_log.push(Move::new(from, to, kind));
+        self.capture_log.push(captured_piece as u8);
+        self.san_log.push(san_move);
+
+        self.clock(capture_or_pawn_move);
+        self.update_checkers(&previous_board);
+        self.update()?;
+
+        Ok(true)
+    }
+
+    pub fn castle_kingside(&mut self, color: Color) -> Result<bool, GameError> {
+        if !self.can_castle_kingside[color as usize] {
+            return Ok(false);
+        }
+
+        let previous_board = self.chess_board.clone();
+        let castle_move = self.get_castle_move(color, true);
+
+        self.chess_board.castle_kingside(
+            self.king_indices[color as usize],
+            self.kingside_rook_indices[color as usize],
+        )?;
+
+        self.kingside_castling_rights[color as usize] = false;
+        self.queenside_castling_rights[color as usize] = false;
+
+        self.move_log.push(castle_move);
+        self.capture_log.push(Piece::NONE as u8);
+        self.san_log.push("O-O".to_string());
+
+        self.clock(false);
+        self.update_checkers(&previous_board);
+        self.update()?;
+
+        Ok(true)
+    }
+
+    pub fn castle_queenside(&mut self, color: Color) -> Result<bool, GameError> {
+        if !self.can_castle_queenside[color as usize] {
+            return Ok(false);
+        }
+
+        let previous_board = self.chess_board.clone();
+        let castle_move = self.get_castle_move(color, false);
+
+        self.chess_board.castle_queenside(
+            self.king_indices[color as usize],
+            self.queenside_rook_indices[color as usize],
+        )?;
+
+        self.kingside_castling_rights[color as usize] = false;
+        self.queenside_castling_rights[color as usize] = false;
+
+        self.move_log.push(castle_move);
+        self.capture_log.push(Piece::NONE as u8);
+        self.san_log.push("O-O-O".to_string());
+
+        self.clock(false);
+        self.update_checkers(&previous_board);
+        self.update()?;
+
+        Ok(true)
+    }
+
+    /// Castling as a move of the king, from its starting cell to the cell it ends up on
+    fn get_castle_move(&self, color: Color, kingside: bool) -> Move {
+        let king_index = self.king_indices[color as usize];
+        let back_rank = king_index - king_index % 8;
+        let target = if kingside {
+            back_rank + 6
+        } else {
+            back_rank + 2
+        };
+        Move::new(Square(king_index), Square(target), MoveKind::Castle)
+    }
+
+    /// Plays a move the way it is stored in the move log
+    pub fn replay_move(&mut self, chess_move: Move) -> Result<bool, GameError> {
+        if chess_move.kind != MoveKind::Castle {
+            return self.make_move(chess_move.from, chess_move.to);
+        }
+
+        let color = self.chess_board.color_at_cell(chess_move.from.index())?;
+        if chess_move.is_kingside_castle() {
+            self.castle_kingside(color)
+        } else {
+            self.castle_queenside(color)
+        }
+    }
+
+    /// The position after the first up_to_ply moves of the move log were played from the starting position
+    pub fn replay(move_log: &[Move], up_to_ply: usize) -> Result<Self, GameError> {
+        let moves = move_log.get(..up_to_ply).ok_or_else(|| {
+            GameError::ValidationError(format!(
+                "Ply {} is out of range, the game has {} plies",
+                up_to_ply,
+                move_log.len()
+            ))
+        })?;
+
+        let mut state = Self::new()?;
+        for chess_move in moves {
+            if !state.replay_move(*chess_move)? {
+                return Err(GameError::ValidationError(format!(
+                    "Logged move {} can't be played",
+                    String::from(*chess_move)
+                )));
+            }
+        }
+        Ok(state)
+    }
+
+    /// The position of this game after the given amount of plies, ply 0 being the starting position
+    pub fn at_ply(&self, ply: usize) -> Result<Self, GameError> {
+        Self::replay(&self.move_log, ply)
+    }
+
+    /// Counts the positions reachable in exactly the given amount of plies, to verify move generation against known counts
+    /// Pawns only promote to queens, so positions with promotions count fewer nodes than the usual tables
+    pub fn perft(&self, depth: u8) -> Result<u64, GameError> {
+        if depth == 0 {
+            return Ok(1);
+        }
+
+        let color = Color::from(self.next_to_move as usize);
+        let mut moves = self.get_available_moves(color)?.get_moves().to_vec();
+        if self.can_castle_kingside[color as usize] {
+            moves.push(self.get_castle_move(color, true));
+        }
+        if self.can_castle_queenside[color as usize] {
+            moves.push(self.get_castle_move(color, false));
+        }
+        if depth == 1 {
+            return Ok(moves.len() as u64);
+        }
+
+        let mut nodes = 0;
+        for chess_move in moves {
+            let mut state = self.clone();
+            state.replay_move(chess_move)?;
+            nodes += state.perft(depth - 1)?;
+        }
+        Ok(nodes)
+    }
+
+    /// The opponent piece which disappeared from the board with the last move, also covers en passant
+    fn get_captured_piece(&self, previous_board: &ChessBoard) -> Piece {
+        let opponent_color = Color::from(self.next_to_move as usize).opponent_color();
+        (0..6)
+            .map(Piece::from)
+            .find(|piece| {
+                let before = previous_board.mask_by_piece_and_color(*piece, opponent_color);
+                let after = self
+                    .chess_board
+                    .mask_by_piece_and_color(*piece, opponent_color);
+                (before & !after).0 != 0
+            })
+            .unwrap_or(Piece::NONE)
+    }
+
+    /// Pieces captured by white (index 0) and black (index 1), sorted by value
+    pub fn get_captured_pieces(&self) -> Result<[Vec<Piece>; 2], GameError> {
+        // Games stored before captures were logged are replayed to rebuild the capture log
+        if self.capture_log.len() != self.move_log.len() {
+            return self.at_ply(self.move_log.len())?.get_captured_pieces();
+        }
+
+        let mut captured: [Vec<Piece>; 2] = Default::default();
+        // The last move was made by the color which isn't to move, the ones before alternate
+        let mut color = Color::from(self.next_to_move as usize);
+        for piece in self.capture_log.iter().rev() {
+            color = color.opponent_color();
+            if *piece != Piece::NONE as u8 {
+                captured[color as usize].push(Piece::from(*piece as usize));
+            }
+        }
+
+        for pieces in captured.iter_mut() {
+            pieces.sort_by_key(|piece| (piece.get_value(), *piece as u8));
+        }
+        Ok(captured)
+    }
+
+    /// Material on the board in pawns, from white's perspective (positive favors white)
+    pub fn get_material_difference(&self) -> i32 {
+        let [white, black] = self.material();
+        white - black
+    }
+
+    /// Material on the board in pawns by color, 0 = white, 1 = black
+    pub fn material(&self) -> [i32; 2] {
+        [Color::WHITE, Color::BLACK].map(|color| {
+            (0..6)
+                .map(Piece::from)
+                .map(|piece| {
+                    self.chess_board
+                        .mask_by_piece_and_color(piece, color)
+                        .count() as i32
+                        * piece.get_value()
+                })
+                .sum()
+        })
+    }
+
+    /// Opening, middlegame or endgame, judged by the pieces left and the move number
+    pub fn phase(&self) -> GamePhase {
+        let pawns = self.chess_board.pieces[Piece::PAWN as usize].count() as i32;
+        let piece_material = self.material().iter().sum::<i32>() - pawns;
+        GamePhase::from_material(piece_material, self.full_move_counter)
+    }
+
+    /// Handles ticking move counter and switching active player
+    pub fn clock(&mut self, capture_or_pawn_move: bool) {
+        if Color::from(self.next_to_move as usize) == Color::BLACK {
+            self.full_move_counter += 1;
+        }
+
+        if capture_or_pawn_move {
+            self.half_move_counter = 0;
+        } else {
+            self.half_move_counter += 1;
+        }
+
+        if self.next_to_move == 1 {
+            self.next_to_move = 0;
+        } else {
+            self.next_to_move = 1;
+        }
+
+        self.tick += 1;
+    }
+
+    pub fn update(&mut self) -> Result<(), GameError> {
+        self.update_check_states();
+        self.update_legal_moves()?;
+        self.update_castle_ability();
+        self.check_end_condition()
+    }
+
+    /// Derives the checkers from the last move, the color which just moved can't be in check
+    pub fn update_checkers(&mut self, previous_board: &ChessBoard) {
+        let color = Color::from(self.next_to_move as usize);
+        self.checkers = self
+            .chess_board
+            .get_checkers_after_move(color, previous_board);
+    }
+
+    pub fn update_check_states(&mut self) {
+        let color = Color::from(self.next_to_move as usize);
+        self.check_states[color as usize] = self.checkers.0 != 0;
+        self.check_states[color.opponent_color() as usize] = false;
+    }
+
+    pub fn update_castle_ability(&mut self) {
+        for color_index in 0..2 {
+            let color = Color::from(color_index);
+            self.can_castle_kingside[color_index] = self.kingside_castling_rights[color_index]
+                && self.chess_board.can_castle_kingside(color);
+            self.can_castle_queenside[color_index] = self.queenside_castling_rights[color_index]
+                && self.chess_board.can_castle_queenside(color);
+        }
+    }
+
+    /// Generates on a copy of the board, since legality is checked by making the moves on it
+    pub fn get_legal_moves(&self, color: Color) -> Result<AvailableMoves, GameError> {
+        let checkers = if color as u8 == self.next_to_move {
+            self.checkers
+        } else {
+            BitBoard::default()
+        };
+
+        self.chess_board.clone().generate_legal_moves(
+            color,
+            self.initial_pawn_masks[color as usize],
+            &self.en_passant_indices,
+            &self.kingside_castling_rights,
+            &self.queenside_castling_rights,
+            checkers,
+        )
+    }
+
+    /// Only the color to move needs its moves right away, the other color is generated when asked for
+    pub fn update_legal_moves(&mut self) -> Result<(), GameError> {
+        self.available_moves = Default::default();
+        self.get_available_moves(Color::from(self.next_to_move as usize))?;
+        Ok(())
+    }
+
+    /// All legal moves of the given color except castling, only generated once per position
+    pub fn get_available_moves(&self, color: Color) -> Result<&AvailableMoves, GameError> {
+        let cell = &self.available_moves[color as usize];
+        if let Some(available_moves) = cell.get() {
+            return Ok(available_moves);
+        }
+        let available_moves = self.get_legal_moves(color)?;
+        Ok(cell.get_or_init(|| available_moves))
+    }
+
+    /// Why the regular move isn't one of the legal moves of the color
+    pub fn diagnose_move(
+        &self,
+        color: Color,
+        from: Square,
+        to: Square,
+    ) -> Result<IllegalMoveReason, GameError> {
+        self.chess_board.diagnose_move(
+            color,
+            from.index(),
+            to.index(),
+            self.initial_pawn_masks[color as usize],
+            &self.en_passant_indices,
+        )
+    }
+
+    /// Why the color can't castle to the given side
+    pub fn diagnose_castle(&self, color: Color, kingside: bool) -> IllegalMoveReason {
+        let rights = match kingside {
+            true => self.kingside_castling_rights,
+            false => self.queenside_castling_rights,
+        };
+        if !rights[color as usize] {
+            return IllegalMoveReason::CastlingRightsLost;
+        }
+        self.chess_board
+            .get_castling_obstacle(color, kingside)
+            .unwrap_or(IllegalMoveReason::CastlingRightsLost)
+    }
+
+    /// Returns (capture, promotion, check) for a legal move
+    pub fn get_move_flags(
+        &self,
+        from: Square,
+        to: Square,
+    ) -> Result<(bool, bool, bool), GameError> {
+        let (piece, color) = self.chess_board.piece_and_color_at_cell(from.index())?;
+        let opponent_color = color.opponent_color();
+
+        let en_passant = piece == Piece::PAWN && self.get_en_passant_target(color)? == Some(to);
+        let capture = en_passant || self.chess_board.color_at_cell(to.index())? == opponent_color;
+        let promotion = piece == Piece::PAWN && !(8..=55).contains(&to.index());
+
+        let mut chess_board = self.chess_board.clone();
+        chess_board.make_move(
+            from.index(),
+            to.index(),
+            &mut self.en_passant_indices.clone(),
+            &mut self.kingside_castling_rights.clone(),
+            &mut self.queenside_castling_rights.clone(),
+        )?;
+        let check = chess_board.get_checkers(opponent_color).0 != 0;
+
+        Ok((capture, promotion, check))
+    }
+
+    /// The cell a pawn of the given color could capture en passant on, if any of them can
+    pub fn get_en_passant_target(&self, color: Color) -> Result<Option<Square>, GameError> {
+        if self.en_passant_indices[color.opponent_color() as usize] == 64 {
+            return Ok(None);
+        }
+
+        Ok(self
+            .get_available_moves(color)?
+            .get_moves()
+            .iter()
+            .find(|chess_move| chess_move.kind == MoveKind::EnPassant)
+            .map(|chess_move| chess_move.to))
+    }
+
+    /// Returns the cells the king ends up on when castling (kingside, queenside), if possible
+    pub fn get_castle_targets(&self, color: Color) -> (Option<Square>, Option<Square>) {
+        (
+            self.can_castle_kingside[color as usize].then(|| self.get_castle_move(color, true).to),
+            self.can_castle_queenside[color as usize]
+                .then(|| self.get_castle_move(color, false).to),
+        )
+    }
+
+    /// Finds the legal move of the color to move given in Standard Algebraic Notation, e.g. Nbd7, exd6 or O-O-O
+    pub fn find_san_move(&self, san: &str) -> Result<Move, GameError> {
+        let color = Color::from(self.next_to_move as usize);
+        let notation = san.trim_end_matches(['+', '#', '!', '?']).replace('0', "O");
+        if notation == "O-O" || notation == "O-O-O" {
+            let kingside = notation == "O-O";
+            let can_castle = match kingside {
+                true => self.can_castle_kingside[color as usize],
+                false => self.can_castle_queenside[color as usize],
+            };
+            return match can_castle {
+                true => Ok(self.get_castle_move(color, kingside)),
+                false => Err(GameError::ValidationError(format!(
+                    "Castling with {} is not possible",
+                    san
+                ))),
+            };
+        }
+
+        let (notation, promotion) = match notation.split_once('=') {
+            Some((notation, promotion)) => (notation.to_string(), Some(promotion.to_string())),
+            None => (notation, None),
+        };
+        if promotion.is_some_and(|promotion| promotion != "Q") {
+            return Err(GameError::ValidationError(format!(
+                "{} is not supported, pawns can only promote to a queen",
+                san
+            )));
+        }
+
+        let notation = notation.replace('x', "");
+        let piece = match notation.chars().next() {
+            Some(letter @ ('K' | 'Q' | 'R' | 'B' | 'N')) => Piece::from_fen_letter(letter).0,
+            _ => Piece::PAWN,
+        };
+        let notation = notation.trim_start_matches(['K', 'Q', 'R', 'B', 'N']);
+        if notation.len() < 2 || !notation.is_char_boundary(notation.len() - 2) {
+            return Err(GameError::ParseError(format!("Invalid move '{}'", san)));
+        }
+        let (disambiguation, target) = notation.split_at(notation.len() - 2);
+        let target = Square::try_from(target.to_string())?;
+
+        let mut candidates = Vec::new();
+        for chess_move in self.get_available_moves(color)?.get_moves() {
+            let from = chess_move.from.as_str().to_lowercase();
+            if chess_move.to == target
+                && self.chess_board.piece_at_cell(chess_move.from.index())? == piece
+                && disambiguation.chars().all(|part| from.contains(part))
+            {
+                candidates.push(*chess_move);
+            }
+        }
+        match candidates.as_slice() {
+            [chess_move] => Ok(*chess_move),
+            [] => Err(GameError::ValidationError(format!(
+                "{} is not a legal move",
+                san
+            ))),
+            _ => Err(GameError::ValidationError(format!("{} is ambiguous", san))),
+        }
+    }
+
+    pub fn has_no_available_moves(&self, color: Color) -> Result<bool, GameError> {
+        Ok(!self.can_castle_kingside[color as usize]
+            && !self.can_castle_queenside[color as usize]
+            && !self.get_available_moves(color)?.has_moves())
+    }
+
+    pub fn is_stalemate(&self, color: Color) -> Result<bool, GameError> {
+        Ok(!self.check_states[color as usize] && self.has_no_available_moves(color)?)
+    }
+
+    /// Only generates the moves of a color in check, so the color which just moved is never generated
+    pub fn is_checkmate(&self, color: Color) -> Result<bool, GameError> {
+        Ok(self.check_states[color as usize] && self.has_no_available_moves(color)?)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.termination.is_some()
+    }
+
+    pub fn is_draw(&self) -> bool {
+        self.termination
+            .is_some_and(|termination| termination.is_draw())
+    }
+
+    pub fn check_end_condition(&mut self) -> Result<(), GameError> {
+        // Only the color to move can be checkmate or stalemate
+        // A mate on the last move before the fifty-move rule still wins the game
+        let current_color = Color::from(self.next_to_move as usize);
+        if self.is_checkmate(current_color)? {
+            self.winner = current_color.opponent_color() as u8;
+            self.termination = Some(Termination::Checkmate);
+            return Ok(());
+        }
+
+        if self.is_stalemate(current_color)? {
+            self.termination = Some(Termination::Stalemate);
+            return Ok(());
+        }
+
+        // 50 moves of both colors are 100 halfmoves
+        if self.half_move_counter >= 100 {
+            self.termination = Some(Termination::FiftyMove);
+            return Ok(());
+        }
+
+        if self.chess_board.is_dead_position() {
+            self.termination = Some(Termination::InsufficientMaterial);
+        }
+        Ok(())
+    }
+}
+
+/// Castling needs the king and the matching rook on the back rank, e.g. KQkq for a board without rooks is impossible
+fn validate_castling_rights(
+    chess_board: &ChessBoard,
+    field: &str,
+    kingside_castling_rights: [bool; 2],
+    queenside_castling_rights: [bool; 2],
+) -> Result<(), GameError> {
+    if field.is_empty() {
+        return Err(invalid_castling_rights(field));
+    }
+    if field != "-" {
+        let mut seen = String::new();
+        for right in field.chars() {
+            if !"KQkq".contains(right) || seen.contains(right) {
+                return Err(invalid_castling_rights(field));
+            }
+            seen.push(right);
+        }
+    }
+
+    for (color, name, back_rank) in [(Color::WHITE, "White", 0), (Color::BLACK, "Black", 56)] {
+        let king_index = chess_board.get_king_position_by_color(color);
+        let king_on_back_rank = (back_rank..back_rank + 8).contains(&king_index);
+        if kingside_castling_rights[color as usize]
+            && !(king_on_back_rank && chess_board.get_kingside_rook(color).is_some())
+        {
+            return Err(GameError::ValidationError(format!(
+                "{} can't castle kingside without king and rook on the back rank",
+                name
+            )));
+        }
+        if queenside_castling_rights[color as usize]
+            && !(king_on_back_rank && chess_board.get_queenside_rook(color).is_some())
+        {
+            return Err(GameError::ValidationError(format!(
+                "{} can't castle queenside without king and rook on the back rank",
+                name
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn invalid_castling_rights(field: &str) -> GameError {
+    GameError::ValidationError(format!(
+        "Invalid castling rights '{}', expected - or a combination of KQkq",
+        field
+    ))
+}
+
+/// The en passant cell has to be empty, right behind a pawn of the color which just moved a double step
+fn validate_en_passant(
+    chess_board: &ChessBoard,
+    active_color: Color,
+    cell: &str,
+) -> Result<u8, GameError> {
+    let invalid = || GameError::ValidationError(format!("Invalid en passant cell '{}'", cell));
+    let index = Position::try_from(cell.to_string()).map_err(|_| invalid())? as u8;
+
+    let (rank_start, pawn_index, origin_index) = match active_color {
+        Color::WHITE => (40, index.wrapping_sub(8), index + 8),
+        _ => (16, index + 8, index.wrapping_sub(8)),
+    };
+    let pawn_color = active_color.opponent_color();
+    let is_valid = (rank_start..rank_start + 8).contains(&index)
+        && chess_board.piece_and_color_at_cell(pawn_index)? == (Piece::PAWN, pawn_color)
+        && !chess_board.is_cell_occupied(index)?
+        && !chess_board.is_cell_occupied(origin_index)?;
+    if !is_valid {
+        return Err(invalid());
+    }
+    Ok(index)
+}
+
+/// Converts a move stored as (from, to) pair by older versions, castles were stored as (64 | 65, color)
+fn move_from_log_pair(from: u8, to: u8) -> Result<Move, GameError> {
+    let back_rank = if to == Color::WHITE as u8 { 0 } else { 56 };
+    match from {
+        64 => Ok(Move::new(
+            Square(back_rank + 4),
+            Square(back_rank + 6),
+            MoveKind::Castle,
+        )),
+        65 => Ok(Move::new(
+            Square(back_rank + 4),
+            Square(back_rank + 2),
+            MoveKind::Castle,
+        )),
+        _ => Ok(Move::normal(Square::try_from(from)?, Square::try_from(to)?)),
+    }
+}
+
+/// Moves are written as (from, to, kind) triples, sessions stored before the kinds were logged contain (from, to) pairs
+mod move_log_serde {
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{move_from_log_pair, Move};
+
+    pub fn serialize<S: Serializer>(move_log: &[Move], serializer: S) -> Result<S::Ok, S::Error> {
+        let moves: Vec<[u8; 3]> = move_log.iter().map(|m| m.to_bytes()).collect();
+        moves.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Move>, D::Error> {
+        Vec::<Vec<u8>>::deserialize(deserializer)?
+            .into_iter()
+            .map(|fields| match fields[..] {
+                [from, to] => move_from_log_pair(from, to),
+                [from, to, kind] => Move::from_bytes([from, to, kind]),
+                _ => Err(super::GameError::DecodingError(format!(
+                    "Invalid logged move {:?}",
+                    fields
+                ))),
+            })
+            .collect::<Result<_, _>>()
+            .map_err(de::Error::custom)
+    }
+}
+
+fn pack_flags(flags: &[bool]) -> u8 {
+    flags
+        .iter()
+        .enumerate()
+        .fold(0, |packed, (i, flag)| packed | (*flag as u8) << i)
+}
+
+fn unpack_flags<const N: usize>(packed: u8) -> [bool; N] {
+    std::array::from_fn(|i| packed & (1 << i) != 0)
+}
+
+/// Reads the binary game state encoding front to back
+struct ByteReader<'a>(&'a [u8]);
+
+impl<'a> ByteReader<'a> {
+    fn read(&mut self, length: usize) -> Result<&'a [u8], GameError> {
+        if self.0.len() < length {
+            return Err(GameError::DecodingError(
+                "Game state encoding ended unexpectedly".to_string(),
+            ));
+        }
+        let (bytes, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], GameError> {
+        Ok(self.read(N)?.try_into().unwrap())
+    }
+
+    fn read_u8(&mut self) -> Result<u8, GameError> {
+        let [byte] = self.read_array()?;
+        Ok(byte)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, GameError> {
+        Ok(u16::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_bit_board(&mut self) -> Result<BitBoard, GameError> {
+        Ok(BitBoard(u64::from_be_bytes(self.read_array()?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::position::Position as Pos;
+
+    use super::*;
+
+    #[test]
+    fn test_move_flags() {
+        let state =
+            GameState::from_fen("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3")
+                .unwrap();
+
+        assert_eq!(
+            state.get_en_passant_target(Color::WHITE).unwrap(),
+            Some(Pos::D6.into())
+        );
+        assert_eq!(state.get_en_passant_target(Color::BLACK).unwrap(), None);
+
+        let flags = state
+            .get_move_flags(Pos::E5.into(), Pos::D6.into())
+            .unwrap();
+        assert_eq!(flags, (true, false, false));
+
+        let flags = state
+            .get_move_flags(Pos::F1.into(), Pos::B5.into())
+            .unwrap();
+        assert_eq!(flags, (false, false, true));
+
+        let flags = state
+            .get_move_flags(Pos::E5.into(), Pos::E6.into())
+            .unwrap();
+        assert_eq!(flags, (false, false, false));
+
+        assert_eq!(state.get_castle_targets(Color::WHITE), (None, None));
+    }
+
+    #[test]
+    fn test_diagnose_move() {
+        use IllegalMoveReason::*;
+        let diagnose = |fen: &str, from: Pos, to: Pos| {
+            GameState::from_fen(fen)
+                .unwrap()
+                .diagnose_move(Color::WHITE, from.into(), to.into())
+                .unwrap()
+        };
+
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(diagnose(start, Pos::E3, Pos::E4), PieceMissing);
+        assert_eq!(diagnose(start, Pos::E7, Pos::E5), OpponentPiece);
+        assert_eq!(diagnose(start, Pos::D1, Pos::D2), OwnPieceOnTarget);
+        assert_eq!(diagnose(start, Pos::D1, Pos::D4), BlockedPath);
+        assert_eq!(diagnose(start, Pos::B1, Pos::B3), UnreachableCell);
+        assert_eq!(diagnose(start, Pos::E2, Pos::E5), UnreachableCell);
+
+        // Pawns can't capture straight ahead
+        let blocked_pawn = "4k3/8/8/8/8/4p3/4P3/4K3 w - - 0 1";
+        assert_eq!(diagnose(blocked_pawn, Pos::E2, Pos::E3), BlockedPath);
+        assert_eq!(diagnose(blocked_pawn, Pos::E2, Pos::D3), UnreachableCell);
+
+        let pinned = "4k3/4r3/8/8/8/8/4B3/4K3 w - - 0 1";
+        assert_eq!(diagnose(pinned, Pos::E2, Pos::D3), LeavesKingInCheck);
+        assert_eq!(diagnose(pinned, Pos::E1, Pos::E3), UnreachableCell);
+        let exposed = "4k3/8/8/8/8/8/5r2/4K3 w - - 0 1";
+        assert_eq!(diagnose(exposed, Pos::E1, Pos::E2), LeavesKingInCheck);
+
+        let state = GameState::from_fen("r3k2r/8/8/8/8/8/3r4/R3K1NR w KQ - 0 1").unwrap();
+        assert_eq!(state.diagnose_castle(Color::WHITE, true), CastlingBlocked);
+        assert_eq!(
+            state.diagnose_castle(Color::WHITE, false),
+            CastlingThroughCheck
+        );
+        assert_eq!(
+            state.diagnose_castle(Color::BLACK, true),
+            CastlingRightsLost
+        );
+    }
+
+    #[test]
+    fn test_castle_targets() {
+        let state = GameState::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+        assert_eq!(
+            state.get_castle_targets(Color::BLACK),
+            (Some(Pos::G8.into()), Some(Pos::C8.into()))
+        );
+        assert_eq!(
+            state.get_castle_targets(Color::WHITE),
+            (Some(Pos::G1.into()), Some(Pos::C1.into()))
+        );
+    }
+
+    #[test]
+    fn test_captured_pieces() {
+        let mut state = GameState::new().unwrap();
+        // 1. e4 d5 2. exd5 Qxd5 3. Nc3 Qe5+ 4. Be2 Qxe2+
+        for (from, to) in [
+            (Pos::E2, Pos::E4),
+            (Pos::D7, Pos::D5),
+            (Pos::E4, Pos::D5),
+            (Pos::D8, Pos::D5),
+            (Pos::B1, Pos::C3),
+            (Pos::D5, Pos::E5),
+            (Pos::F1, Pos::E2),
+            (Pos::E5, Pos::E2),
+        ] {
+            assert!(state.make_move(from.into(), to.into()).unwrap());
+        }
+
+        let captured = state.get_captured_pieces().unwrap();
+        assert_eq!(captured[Color::WHITE as usize], vec![Piece::PAWN]);
+        assert_eq!(
+            captured[Color::BLACK as usize],
+            vec![Piece::PAWN, Piece::BISHOP]
+        );
+        assert_eq!(state.get_material_difference(), -3);
+        assert_eq!(state.material(), [35, 38]);
+
+        // Sessions stored without a capture log get it rebuilt
+        state.capture_log.clear();
+        assert_eq!(state.get_captured_pieces().unwrap(), captured);
+    }
+
+    #[test]
+    fn test_captured_en_passant() {
+        let mut state =
+            GameState::from_fen("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3")
+                .unwrap();
+        assert!(state.make_move(Pos::E5.into(), Pos::D6.into()).unwrap());
+        assert_eq!(state.capture_log, vec![Piece::PAWN as u8]);
+        assert_eq!(
+            state.get_captured_pieces().unwrap()[Color::WHITE as usize],
+            vec![Piece::PAWN]
+        );
+    }
+
+    #[test]
+    fn test_fen_validation() {
+        let invalid = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1BNR w kq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkqK - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN1 w KQkq - 0 1",
+            "r3k2r/8/8/8/8/8/8/4K3 w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e3 0 1",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e6 0 1",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e0 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - -1 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 0",
+            "4k3/8/8/8/8/8/8/4Q1K1 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4K3 w - - 0",
+        ];
+        for fen in invalid {
+            assert!(
+                matches!(GameState::from_fen(fen), Err(GameError::ValidationError(_))),
+                "{}",
+                fen
+            );
+        }
+
+        let valid = [
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R w Kq - 12 40",
+            "4k3/8/8/8/8/8/8/4Q1K1 b - - 0 1",
+        ];
+        for fen in valid {
+            assert!(GameState::from_fen(fen).is_ok(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_perft() {
+        let positions: [(&str, &[u64]); 4] = [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                &[20, 400, 8902, 197281],
+            ),
+            // Kiwipete, castling through and out of check
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                &[48, 2039, 97862],
+            ),
+            // En passant discovering a check along the rank
+            (
+                "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+                &[14, 191, 2812, 43238],
+            ),
+            // Promotion to a queen only, which checks the king (9 and 40 with underpromotions)
+            ("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", &[6, 28]),
+        ];
+        for (fen, counts) in positions {
+            let state = GameState::from_fen(fen).unwrap();
+            for (depth, count) in counts.iter().enumerate() {
+                assert_eq!(
+                    state.perft(depth as u8 + 1).unwrap(),
+                    *count,
+                    "{} at depth {}",
+                    fen,
+                    depth + 1
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_lazy_legal_moves() {
+        let mut state = GameState::new().unwrap();
+        assert!(state.make_move(Pos::E2.into(), Pos::E4.into()).unwrap());
+        assert!(state.available_moves[Color::WHITE as usize].get().is_none());
+        assert!(state.available_moves[Color::BLACK as usize].get().is_some());
+
+        let decoded = GameState::from_bytes(&state.to_bytes()).unwrap();
+        assert!(decoded
+            .available_moves
+            .iter()
+            .all(|moves| moves.get().is_none()));
+        assert_eq!(
+            decoded
+                .get_available_moves(Color::WHITE)
+                .unwrap()
+                .get_moves()
+                .len(),
+            30
+        );
+    }
+
+    #[test]
+    fn test_bytes_roundtrip() {
+        let mut state = GameState::new().unwrap();
+        assert!(state.make_move(Pos::E2.into(), Pos::E4.into()).unwrap());
+        assert!(state.make_move(Pos::D7.into(), Pos::D5.into()).unwrap());
+        assert!(state.make_move(Pos::E4.into(), Pos::D5.into()).unwrap());
+        state.winner = Color::WHITE as u8;
+        state.termination = Some(Termination::Resignation);
+
+        let decoded = GameState::from_bytes(&state.to_bytes()).unwrap();
+        assert_eq!(decoded.to_fen(), state.to_fen());
+        assert_eq!(decoded.move_log, state.move_log);
+        assert_eq!(decoded.capture_log, state.capture_log);
+        assert_eq!(decoded.san_log, state.san_log);
+        for color in [Color::WHITE, Color::BLACK] {
+            assert_eq!(
+                decoded.get_available_moves(color).unwrap(),
+                state.get_available_moves(color).unwrap()
+            );
+        }
+        assert_eq!(decoded.winner, Color::WHITE as u8);
+        assert_eq!(decoded.termination, Some(Termination::Resignation));
+        assert!(!decoded.is_draw());
+
+        // Version 3 stored the endings as flags, the resign flag is the third one
+        let mut legacy = state.to_bytes();
+        let flags_index = 2 + ChessBoard::ENCODED_LENGTH + 6 + 34;
+        legacy[0] = 3;
+        legacy[flags_index] = 0b100;
+        let decoded = GameState::from_bytes(&legacy).unwrap();
+        assert_eq!(decoded.termination, Some(Termination::Resignation));
+        legacy[flags_index] = 0b1001;
+        let decoded = GameState::from_bytes(&legacy).unwrap();
+        assert_eq!(decoded.termination, Some(Termination::Stalemate));
+        assert!(decoded.is_draw());
+    }
+
+    #[test]
+    fn test_long_game_counters() {
+        let fen = "4k3/8/8/8/8/8/8/4K3 w - - 99 300";
+        let state = GameState::from_fen(fen).unwrap();
+        assert_eq!(state.to_fen(), fen);
+
+        let decoded = GameState::from_bytes(&state.to_bytes()).unwrap();
+        assert_eq!(decoded.to_fen(), fen);
+    }
+
+    #[test]
+    fn test_bytes_version_1() {
+        let mut state = GameState::new().unwrap();
+        assert!(state.make_move(Pos::E2.into(), Pos::E4.into()).unwrap());
+
+        // Version 1 stored the counters and tick as single bytes right after the side to move
+        // and the moves without their kind byte, which follows the 35 bytes of state and the move count
+        let bytes = state.to_bytes();
+        let counters_start = 2 + ChessBoard::ENCODED_LENGTH;
+        let kind_byte = counters_start + 6 + 35 + 2 + 2;
+        let mut legacy = vec![1];
+        legacy.extend(&bytes[1..counters_start]);
+        legacy.extend([0, 1, 1]);
+        legacy.extend(&bytes[counters_start + 6..kind_byte]);
+        legacy.extend(&bytes[kind_byte + 1..]);
+
+        let decoded = GameState::from_bytes(&legacy).unwrap();
+        assert_eq!(decoded.to_fen(), state.to_fen());
+        assert_eq!(decoded.tick, 1);
+        assert_eq!(decoded.move_log, state.move_log);
+    }
+
+    #[test]
+    fn test_at_ply() {
+        let mut state = GameState::new().unwrap();
+        for (from, to) in [
+            (Pos::E2, Pos::E4),
+            (Pos::E7, Pos::E5),
+            (Pos::G1, Pos::F3),
+            (Pos::B8, Pos::C6),
+        ] {
+            assert!(state.make_move(from.into(), to.into()).unwrap());
+        }
+
+        assert_eq!(
+            state.at_ply(0).unwrap().to_fen(),
+            GameState::new().unwrap().to_fen()
+        );
+        assert_eq!(
+            state.at_ply(2).unwrap().to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2"
+        );
+        assert_eq!(state.at_ply(4).unwrap().to_fen(), state.to_fen());
+        assert!(state.at_ply(5).is_err());
+
+        let replayed = GameState::replay(&state.move_log, 3).unwrap();
+        assert_eq!(replayed.move_log, state.move_log[..3]);
+        assert_eq!(replayed.next_to_move, Color::BLACK as u8);
+    }
+
+    #[test]
+    fn test_dead_position_draw() {
+        let mut state = GameState::from_fen("8/8/4k3/8/8/3KN3/8/8 w - - 0 60").unwrap();
+        assert!(state.is_draw());
+        assert_eq!(state.termination, Some(Termination::InsufficientMaterial));
+        let decoded = GameState::from_bytes(&state.to_bytes()).unwrap();
+        assert_eq!(decoded.termination, Some(Termination::InsufficientMaterial));
+
+        // Capturing the last pawn ends the game
+        state = GameState::from_fen("8/8/4k3/4p3/8/3K4/8/8 b - - 0 60").unwrap();
+        assert!(!state.is_finished());
+        for (from, to) in [
+            (Pos::E6, Pos::D7),
+            (Pos::D3, Pos::E4),
+            (Pos::D7, Pos::C7),
+            (Pos::E4, Pos::E5),
+        ] {
+            let color = Color::from(state.next_to_move as usize);
+            assert!(state
+                .get_available_moves(color)
+                .unwrap()
+                .has_move(from.into(), to.into()));
+            assert!(state.make_move(from.into(), to.into()).unwrap());
+        }
+        assert_eq!(state.termination, Some(Termination::InsufficientMaterial));
+    }
+
+    #[test]
+    fn test_fifty_move_rule() {
+        let mut state = GameState::from_fen("4k3/8/8/8/8/8/R7/4K3 w - - 98 80").unwrap();
+        assert!(state.make_move(Pos::A2.into(), Pos::A3.into()).unwrap());
+        assert!(!state.is_finished());
+        assert!(state.make_move(Pos::E8.into(), Pos::D8.into()).unwrap());
+        assert_eq!(state.termination, Some(Termination::FiftyMove));
+        assert!(state.is_draw());
+
+        // A mate on the 100th halfmove wins
+        let mut state = GameState::from_fen("6k1/5ppp/8/8/8/8/8/R3K3 w - - 99 80").unwrap();
+        assert!(state.make_move(Pos::A1.into(), Pos::A8.into()).unwrap());
+        assert_eq!(state.termination, Some(Termination::Checkmate));
+        assert_eq!(state.winner, Color::WHITE as u8);
+    }
+
+    #[test]
+    fn test_move_kinds() {
+        let fen = "4k3/1P6/8/8/3p4/8/4P3/4K2R w K - 0 1";
+        let mut state = GameState::from_fen(fen).unwrap();
+        assert!(state.make_move(Pos::E2.into(), Pos::E4.into()).unwrap());
+        assert!(state.make_move(Pos::D4.into(), Pos::E3.into()).unwrap());
+        assert!(state.castle_kingside(Color::WHITE).unwrap());
+        assert!(state.make_move(Pos::E8.into(), Pos::D7.into()).unwrap());
+        assert!(state.make_move(Pos::B7.into(), Pos::B8.into()).unwrap());
+
+        let kinds: Vec<MoveKind> = state.move_log.iter().map(|m| m.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                MoveKind::Normal,
+                MoveKind::EnPassant,
+                MoveKind::Castle,
+                MoveKind::Normal,
+                MoveKind::Promotion(Piece::QUEEN),
+            ]
+        );
+        assert_eq!(
+            state.move_log[2],
+            Move::new(Pos::E1.into(), Pos::G1.into(), MoveKind::Castle)
+        );
+
+        let mut replayed = GameState::from_fen(fen).unwrap();
+        for chess_move in &state.move_log {
+            assert!(replayed.replay_move(*chess_move).unwrap());
+        }
+        assert_eq!(replayed.to_fen(), state.to_fen());
+
+        // Logs of (from, to) pairs get their kinds back from the SAN log
+        let mut legacy = state.clone();
+        legacy.move_log = state
+            .move_log
+            .iter()
+            .map(|m| match m.kind {
+                MoveKind::Castle => move_from_log_pair(64, Color::WHITE as u8).unwrap(),
+                _ => move_from_log_pair(m.from.index(), m.to.index()).unwrap(),
+            })
+            .collect();
+        assert_ne!(legacy.move_log, state.move_log);
+        legacy.restore_move_kinds();
+        assert_eq!(legacy.move_log, state.move_log);
+    }
+
+    #[test]
+    fn test_bytes_invalid() {
+        let bytes = GameState::new().unwrap().to_bytes();
+        assert!(GameState::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut unknown_version = bytes.clone();
+        unknown_version[0] = 0;
+        assert!(GameState::from_bytes(&unknown_version).is_err());
+
+        let next_to_move_index = 1 + ChessBoard::ENCODED_LENGTH;
+        let en_passant_index = next_to_move_index + 1 + 6 + 24;
+        let king_index = en_passant_index + 3;
+        for (index, value) in [
+            (next_to_move_index, 2),
+            (en_passant_index, 65),
+            (en_passant_index, 40),
+            (en_passant_index + 1, 20),
+            (king_index, 64),
+            (king_index, 3),
+            (king_index + 2, 64),
+            (king_index + 5, 200),
+        ] {
+            let mut corrupt = bytes.clone();
+            corrupt[index] = value;
+            assert!(
+                matches!(
+                    GameState::from_bytes(&corrupt),
+                    Err(GameError::DecodingError(_))
+                ),
+                "byte {} set to {} was accepted",
+                index,
+                value
+            );
+        }
+
+        // A white pawn which just moved from e2 to e4 leaves e3 behind
+        let mut en_passant = bytes.clone();
+        en_passant[en_passant_index] = 20;
+        assert!(GameState::from_bytes(&en_passant).is_ok());
+    }
+}
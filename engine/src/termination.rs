@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+
+/// How a game ended
+/// The discriminants are the bytes of the binary game state encoding, they must never change
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Termination {
+    Checkmate = 1,
+    Resignation = 2,
+    /// The player to move ran out of time
+    Timeout = 3,
+    Stalemate = 4,
+    /// 50 moves of both colors without a capture or pawn move
+    FiftyMove = 5,
+    /// The same position occurred three times, only set by imported games
+    Repetition = 6,
+    /// No sequence of legal moves could lead to a checkmate anymore
+    InsufficientMaterial = 7,
+    /// Both players agreed to a draw
+    Agreement = 8,
+    /// A player left the game, only set by imported games
+    Abandonment = 9,
+    /// A player aborted the game before both sides moved, it has no winner and isn't a draw
+    Aborted = 10,
+}
+
+impl Termination {
+    pub const ALL: [Self; 10] = [
+        Self::Checkmate,
+        Self::Resignation,
+        Self::Timeout,
+        Self::Stalemate,
+        Self::FiftyMove,
+        Self::Repetition,
+        Self::InsufficientMaterial,
+        Self::Agreement,
+        Self::Abandonment,
+        Self::Aborted,
+    ];
+
+    /// Neither color won, aborted games aren't draws either
+    pub fn is_draw(&self) -> bool {
+        matches!(
+            self,
+            Self::Stalemate
+                | Self::FiftyMove
+                | Self::Repetition
+                | Self::InsufficientMaterial
+                | Self::Agreement
+        )
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Self::Checkmate => "checkmate",
+            Self::Resignation => "resignation",
+            Self::Timeout => "timeout",
+            Self::Stalemate => "stalemate",
+            Self::FiftyMove => "fifty-move rule",
+            Self::Repetition => "repetition",
+            Self::InsufficientMaterial => "insufficient material",
+            Self::Agreement => "agreement",
+            Self::Abandonment => "abandonment",
+            Self::Aborted => "abort",
+        }
+    }
+
+    /// Value of the PGN Termination tag, everything decided by the rules or the players is a normal ending
+    pub fn pgn_tag(&self) -> &'static str {
+        match self {
+            Self::Timeout => "time forfeit",
+            Self::Abandonment => "abandoned",
+            Self::Aborted => "unterminated",
+            _ => "normal",
+        }
+    }
+
+    /// Single byte of the binary game state encoding, 0 is kept for running games
+    pub fn to_byte(termination: Option<Self>) -> u8 {
+        termination.map_or(0, |termination| termination as u8)
+    }
+
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|termination| *termination as u8 == byte)
+    }
+
+    /// Game states encoded before the termination existed had one flag per ending
+    /// A draw without any other flag was agreed on, e.g. in imported games
+    pub fn from_flags(
+        draw: bool,
+        checkmate: bool,
+        resign: bool,
+        stalemate: bool,
+        remis: bool,
+        dead_position: bool,
+    ) -> Option<Self> {
+        let termination = if checkmate {
+            Self::Checkmate
+        } else if resign {
+            Self::Resignation
+        } else if stalemate {
+            Self::Stalemate
+        } else if remis {
+            Self::FiftyMove
+        } else if dead_position {
+            Self::InsufficientMaterial
+        } else if draw {
+            Self::Agreement
+        } else {
+            return None;
+        };
+        Some(termination)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_round_trip() {
+        assert_eq!(Termination::from_byte(Termination::to_byte(None)), None);
+        for termination in Termination::ALL {
+            let byte = Termination::to_byte(Some(termination));
+            assert_eq!(Termination::from_byte(byte), Some(termination));
+        }
+        assert_eq!(Termination::from_byte(11), None);
+    }
+
+    #[test]
+    fn test_bytes_are_stable() {
+        let expected = [
+            (Termination::Checkmate, 1),
+            (Termination::Resignation, 2),
+            (Termination::Timeout, 3),
+            (Termination::Stalemate, 4),
+            (Termination::FiftyMove, 5),
+            (Termination::Repetition, 6),
+            (Termination::InsufficientMaterial, 7),
+            (Termination::Agreement, 8),
+            (Termination::Abandonment, 9),
+            (Termination::Aborted, 10),
+        ];
+        assert_eq!(expected.len(), Termination::ALL.len());
+        for (termination, byte) in expected {
+            assert_eq!(Termination::to_byte(Some(termination)), byte);
+            assert_eq!(Termination::from_byte(byte), Some(termination));
+        }
+        assert_eq!(Termination::to_byte(None), 0);
+    }
+
+    #[test]
+    fn test_from_flags() {
+        assert_eq!(
+            Termination::from_flags(false, false, false, false, false, false),
+            None
+        );
+        assert_eq!(
+            Termination::from_flags(true, false, false, true, false, false),
+            Some(Termination::Stalemate)
+        );
+        assert_eq!(
+            Termination::from_flags(true, false, false, false, false, true),
+            Some(Termination::InsufficientMaterial)
+        );
+        assert_eq!(
+            Termination::from_flags(true, false, false, false, false, false),
+            Some(Termination::Agreement)
+        );
+    }
+}
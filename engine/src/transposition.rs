@@ -0,0 +1,109 @@
+/// What the stored score says about the real score of the position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound {
+    Exact,
+    /// The search was cut off, the real score is at least this
+    Lower,
+    /// No move raised alpha, the real score is at most this
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    hash: u64,
+    depth: u16,
+    score: i32,
+    bound: Bound,
+}
+
+/// Fixed-size table of already searched positions keyed by their Zobrist hash
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+}
+
+impl TranspositionTable {
+    /// The size is rounded up to a power of two, so the slot is just the lower bits of the hash
+    pub fn new(size: usize) -> Self {
+        Self {
+            entries: vec![None; size.next_power_of_two()],
+        }
+    }
+
+    fn slot(&self, hash: u64) -> usize {
+        (hash as usize) & (self.entries.len() - 1)
+    }
+
+    /// The score if the position was searched at least this deep and the bound settles it for the window
+    pub fn probe(&self, hash: u64, depth: u16, alpha: i32, beta: i32) -> Option<i32> {
+        let entry = self.entries[self.slot(hash)]?;
+        if entry.hash != hash || entry.depth < depth {
+            return None;
+        }
+
+        match entry.bound {
+            Bound::Exact => Some(entry.score),
+            Bound::Lower if entry.score >= beta => Some(beta),
+            Bound::Upper if entry.score <= alpha => Some(alpha),
+            _ => None,
+        }
+    }
+
+    /// Deeper searches win the slot over shallower ones of other positions, the same position is always updated
+    pub fn store(&mut self, hash: u64, depth: u16, score: i32, bound: Bound) {
+        let slot = self.slot(hash);
+        let replace = match self.entries[slot] {
+            Some(entry) => entry.hash == hash || entry.depth <= depth,
+            None => true,
+        };
+        if replace {
+            self.entries[slot] = Some(Entry {
+                hash,
+                depth,
+                score,
+                bound,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe() {
+        let mut table = TranspositionTable::new(1000);
+        assert_eq!(table.entries.len(), 1024);
+
+        table.store(5, 2, 30, Bound::Exact);
+        assert_eq!(table.probe(5, 2, -100, 100), Some(30));
+        assert_eq!(table.probe(5, 3, -100, 100), None);
+        assert_eq!(table.probe(6, 1, -100, 100), None);
+
+        table.store(7, 2, 50, Bound::Lower);
+        assert_eq!(table.probe(7, 2, -100, 40), Some(40));
+        assert_eq!(table.probe(7, 2, -100, 100), None);
+
+        table.store(8, 2, -50, Bound::Upper);
+        assert_eq!(table.probe(8, 2, -40, 100), Some(-40));
+        assert_eq!(table.probe(8, 2, -100, 100), None);
+    }
+
+    #[test]
+    fn test_replacement() {
+        let mut table = TranspositionTable::new(4);
+
+        table.store(1, 3, 10, Bound::Exact);
+        // Same slot, shallower search of another position
+        table.store(5, 1, 20, Bound::Exact);
+        assert_eq!(table.probe(1, 3, -100, 100), Some(10));
+        assert_eq!(table.probe(5, 1, -100, 100), None);
+
+        table.store(5, 3, 20, Bound::Exact);
+        assert_eq!(table.probe(5, 3, -100, 100), Some(20));
+
+        // The same position is updated even with a shallower search
+        table.store(5, 1, 25, Bound::Exact);
+        assert_eq!(table.probe(5, 1, -100, 100), Some(25));
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::HashSet;
+
+use serde::Deserialize;
+
+use crate::{
+    entities::{session::Session, user::User},
+    error::ApiError,
+    game::{color::Color, pgn::replay_pgn, state::GameState, termination::Termination},
+    models::session_models::{ImportSummary, ImportedGame, TimeControl},
+    storage::SessionFilter,
+    utils::http::get_json,
+    AppState,
+};
+
+const API_URL: &str = "https://api.chess.com/pub/player";
+
+#[derive(Deserialize)]
+struct Archives {
+    /// Links to the monthly archives, oldest first
+    archives: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ArchiveGames {
+    games: Vec<ArchivedGame>,
+}
+
+#[derive(Deserialize)]
+struct ArchivedGame {
+    url: String,
+    /// Missing for some aborted games
+    #[serde(default)]
+    pgn: String,
+    /// Seconds with an optional increment like 180+2, daily games look like 1/86400
+    #[serde(default)]
+    time_control: String,
+    /// UNIX timestamp in seconds
+    end_time: u64,
+    /// chess for regular games, variants have other rules
+    rules: String,
+    white: ArchivedPlayer,
+    black: ArchivedPlayer,
+}
+
+#[derive(Deserialize)]
+struct ArchivedPlayer {
+    username: String,
+    /// win, checkmated, resigned, timeout, agreed, repetition, stalemate, insufficient, 50move...
+    result: String,
+}
+
+/// Imports the finished games of the latest months of a chess.com account as sessions of the user
+/// Games imported before are skipped, so importing again only adds new games
+pub async fn import_games(
+    state: &AppState,
+    user: &User,
+    username: &str,
+    months: usize,
+) -> Result<ImportSummary, ApiError> {
+    let archives: Archives = get_json(&format!("{}/{}/games/archives", API_URL, username)).await?;
+    let (existing, _) = state
+        .storage
+        .find_sessions_by_key(
+            &user.key,
+            &SessionFilter {
+                include_archived: true,
+                ..Default::default()
+            },
+            0,
+            i64::MAX as u64,
+        )
+        .await?;
+    let mut imported_urls: HashSet<String> = existing
+        .into_iter()
+        .filter_map(|session| session.imported.map(|imported| imported.url))
+        .collect();
+
+    let mut summary = ImportSummary::default();
+    let latest = archives.archives.len().saturating_sub(months);
+    for archive_url in &archives.archives[latest..] {
+        let archive: ArchiveGames = get_json(archive_url).await?;
+        for game in archive.games {
+            if imported_urls.contains(&game.url) {
+                summary.skipped += 1;
+                continue;
+            }
+
+            match to_session(&game, username, &user.key) {
+                Some(session) => {
+                    session.save(&state.storage, &state.tasks).await?;
+                    imported_urls.insert(game.url);
+                    summary.imported += 1;
+                }
+                None => summary.unsupported += 1,
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// None if the game can't be replayed, like variants or underpromotions
+fn to_session(game: &ArchivedGame, username: &str, key: &str) -> Option<Session> {
+    if game.rules != "chess" {
+        return None;
+    }
+    let (color, opponent) = if game.white.username.eq_ignore_ascii_case(username) {
+        (Color::WHITE, &game.black)
+    } else if game.black.username.eq_ignore_ascii_case(username) {
+        (Color::BLACK, &game.white)
+    } else {
+        return None;
+    };
+
+    let mut game_state = replay_pgn(&game.pgn).ok()?;
+    apply_result(&mut game_state, &game.white, &game.black);
+
+    let name = format!(
+        "chess.com: {} vs {}",
+        game.white.username, game.black.username
+    );
+    let imported = ImportedGame {
+        url: game.url.clone(),
+        opponent: opponent.username.clone(),
+    };
+    let mut session = Session::new_imported(
+        name,
+        key.to_string(),
+        color,
+        game_state,
+        game.end_time * 1_000_000_000,
+        imported,
+    );
+    session.time_control = parse_time_control(&game.time_control);
+    Some(session)
+}
+
+/// Games decided on the board keep their result, everything else ends the way it did on chess.com
+/// The player who didn't win holds the reason, e.g. resigned, timeout, abandoned, agreed or repetition
+fn apply_result(state: &mut GameState, white: &ArchivedPlayer, black: &ArchivedPlayer) {
+    if matches!(
+        state.termination,
+        Some(Termination::Checkmate | Termination::Stalemate)
+    ) {
+        return;
+    }
+
+    let (winner, reason) = match (white.result == "win", black.result == "win") {
+        (true, _) => (Color::WHITE as u8, &black.result),
+        (_, true) => (Color::BLACK as u8, &white.result),
+        _ => (2, &white.result),
+    };
+    // The replay may have ended the game on the fifty-move rule while it went on
+    state.winner = winner;
+    state.termination = Some(match reason.as_str() {
+        "checkmated" => Termination::Checkmate,
+        "timeout" => Termination::Timeout,
+        "abandoned" => Termination::Abandonment,
+        "stalemate" => Termination::Stalemate,
+        "50move" => Termination::FiftyMove,
+        "repetition" => Termination::Repetition,
+        "insufficient" | "timevsinsufficient" => Termination::InsufficientMaterial,
+        _ if winner == 2 => Termination::Agreement,
+        _ => Termination::Resignation,
+    });
+}
+
+/// None for daily games, they have a time per move instead of clocks
+fn parse_time_control(time_control: &str) -> Option<TimeControl> {
+    let (initial, increment) = time_control.split_once('+').unwrap_or((time_control, "0"));
+    TimeControl::new(initial.parse().ok()?, increment.parse().ok()?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(username: &str, result: &str) -> ArchivedPlayer {
+        ArchivedPlayer {
+            username: username.to_string(),
+            result: result.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_to_session() {
+        let mut game = ArchivedGame {
+            url: "https://www.chess.com/game/live/1".to_string(),
+            pgn: "[Event \"Live Chess\"]\n\n1. e4 {[%clk 0:02:59.9]} 1... e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0".to_string(),
+            time_control: "180+2".to_string(),
+            end_time: 1_700_000_000,
+            rules: "chess".to_string(),
+            white: player("Alice", "win"),
+            black: player("bob", "checkmated"),
+        };
+
+        let session = to_session(&game, "alice", "key").unwrap();
+        assert_eq!(session.keys, ["key".to_string(), "IMPORTED".to_string()]);
+        assert_eq!(session.get_names_of(&[None, None])[1], "bob");
+        assert_eq!(
+            session.time_control,
+            Some(TimeControl::new(180, 2).unwrap())
+        );
+        assert_eq!(session.created_stamp, 1_700_000_000_000_000_000);
+        assert_eq!(session.game_state.termination, Some(Termination::Checkmate));
+        assert_eq!(session.get_result_notation(), "1-0");
+
+        // Ended by resignation before the mate
+        game.pgn = "1. e4 e5 2. Qh5 Nc6 0-1".to_string();
+        game.white.result = "resigned".to_string();
+        game.black.result = "win".to_string();
+        let session = to_session(&game, "bob", "key").unwrap();
+        assert_eq!(session.keys[1], "key");
+        assert_eq!(
+            session.game_state.termination,
+            Some(Termination::Resignation)
+        );
+        assert_eq!(session.get_result_notation(), "0-1");
+        game.white.result = "timeout".to_string();
+        let session = to_session(&game, "bob", "key").unwrap();
+        assert_eq!(session.get_termination(), Some(Termination::Timeout));
+        game.white.result = "abandoned".to_string();
+        let session = to_session(&game, "bob", "key").unwrap();
+        assert_eq!(session.get_termination(), Some(Termination::Abandonment));
+        assert_eq!(session.get_result_notation(), "0-1");
+
+        game.black.result = "agreed".to_string();
+        game.white.result = "agreed".to_string();
+        game.time_control = "1/86400".to_string();
+        let session = to_session(&game, "bob", "key").unwrap();
+        assert_eq!(session.get_result_notation(), "1/2-1/2");
+        assert_eq!(session.get_termination(), Some(Termination::Agreement));
+        assert_eq!(session.time_control, None);
+        game.black.result = "repetition".to_string();
+        game.white.result = "repetition".to_string();
+        let session = to_session(&game, "bob", "key").unwrap();
+        assert_eq!(session.get_termination(), Some(Termination::Repetition));
+        assert_eq!(session.get_result_notation(), "1/2-1/2");
+
+        assert!(to_session(&game, "carol", "key").is_none());
+        game.rules = "chess960".to_string();
+        assert!(to_session(&game, "bob", "key").is_none());
+    }
+}
@@ -0,0 +1,165 @@
+use std::{fs, io, path::PathBuf, time::Instant};
+
+use clap::{Parser, Subcommand};
+
+use crate::{
+    database,
+    entities::session::Session,
+    game::{
+        ai::get_next_move,
+        color::Color,
+        render::{render_board_png, theme::Theme},
+        state::GameState,
+    },
+    models::enums::AiDifficulty,
+    utils::random::GameRng,
+};
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Chess API with an AI opponent, board rendering and game reviews"
+)]
+pub struct Cli {
+    /// Serves the API if left out
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Serve the API
+    Serve,
+    /// Count the positions reachable in the given amount of plies, to verify the move generation
+    Perft {
+        depth: u8,
+        /// Starting position if left out
+        fen: Option<String>,
+    },
+    /// Render a position as PNG
+    Render {
+        fen: String,
+        out: PathBuf,
+        /// classic, pixel, brown, green, blue or gameboy
+        #[arg(long, default_value = "classic")]
+        theme: String,
+        /// Render from black's perspective
+        #[arg(long)]
+        black: bool,
+    },
+    /// Let the AI play against itself and print the moves
+    Selfplay {
+        /// Starting position if left out
+        #[arg(long)]
+        fen: Option<String>,
+        /// Stops the game after this many plies if it didn't end before
+        #[arg(long, default_value_t = 200)]
+        max_plies: usize,
+    },
+    /// Run the storage migrations and exit
+    Migrate,
+}
+
+fn other_error(error: impl ToString) -> io::Error {
+    io::Error::other(error.to_string())
+}
+
+fn load_state(fen: Option<&str>) -> io::Result<GameState> {
+    match fen {
+        Some(fen) => GameState::from_fen(fen),
+        None => GameState::new(),
+    }
+    .map_err(other_error)
+}
+
+/// Entry point of the binary
+pub async fn run() -> io::Result<()> {
+    match Cli::parse().command.unwrap_or(Command::Serve) {
+        Command::Serve => crate::run().await,
+        Command::Perft { depth, fen } => {
+            let state = load_state(fen.as_deref())?;
+            let start = Instant::now();
+            let nodes = state.perft(depth).map_err(other_error)?;
+            println!("perft({}) = {} in {:?}", depth, nodes, start.elapsed());
+            Ok(())
+        }
+        Command::Render {
+            fen,
+            out,
+            theme,
+            black,
+        } => {
+            let state = load_state(Some(&fen))?;
+            let theme = Theme::find(&theme).map_err(other_error)?;
+            let color = if black { Color::BLACK } else { Color::WHITE };
+            let png = render_board_png(&state, color, &theme).map_err(other_error)?;
+            fs::write(&out, png)?;
+            println!("Rendered to {}", out.display());
+            Ok(())
+        }
+        Command::Selfplay { fen, max_plies } => selfplay(fen.as_deref(), max_plies),
+        Command::Migrate => {
+            let storage = database::setup().await.map_err(other_error)?;
+            storage.migrate().await.map_err(other_error)?;
+            println!("Migrations finished");
+            Ok(())
+        }
+    }
+}
+
+fn selfplay(fen: Option<&str>, max_plies: usize) -> io::Result<()> {
+    let keys = ["white".to_string(), "black".to_string()];
+    let mut session = Session::new("Self-play".to_string(), keys, load_state(fen)?);
+    let rng = GameRng::from_env();
+    for _ in 0..max_plies {
+        if session.is_finished() {
+            break;
+        }
+        let start = Instant::now();
+        let next_move =
+            get_next_move(&session.game_state, AiDifficulty::HARD, &rng).map_err(other_error)?;
+        let key = session.keys[session.game_state.next_to_move as usize].clone();
+        session.do_move(&key, &next_move).map_err(other_error)?;
+        println!(
+            "{} ({:?})",
+            session
+                .game_state
+                .san_log
+                .last()
+                .cloned()
+                .unwrap_or_default(),
+            start.elapsed()
+        );
+    }
+
+    println!("{}", session.game_state.get_san());
+    match session.get_end_reason() {
+        Some(reason) => println!("{} by {}", session.get_result_notation(), reason),
+        None => println!("Stopped after {} plies", max_plies),
+    }
+    println!("{}", session.game_state.to_fen());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+        let cli = Cli::try_parse_from(["lemon-chess-api", "perft", "3"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Perft {
+                depth: 3,
+                fen: None
+            })
+        ));
+        assert!(Cli::try_parse_from(["lemon-chess-api"])
+            .unwrap()
+            .command
+            .is_none());
+    }
+}
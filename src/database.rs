@@ -1,27 +1,40 @@
-use crate::entities::{room::Room, session::Session, user::User};
+use crate::{
+    error::ApiError,
+    storage::{memory::MemoryStorage, mongo::MongoStorage, sqlite::SqliteStorage, Storage},
+};
 use dotenvy::dotenv;
-use mongodb::{error::Result, options::ClientOptions, Client, Collection};
-use std::env;
+use redis::aio::MultiplexedConnection;
+use std::{env, sync::Arc};
 
-#[derive(Clone)]
-pub struct DB {
-    pub client: Client,
-    pub session_collection: Collection<Session>,
-    pub user_collection: Collection<User>,
-    pub room_collection: Collection<Room>,
-}
-
-pub async fn setup() -> Result<DB> {
+/// MongoDB unless STORAGE is set to sqlite, then everything is stored in the file at SQLITE_PATH
+/// STORAGE=memory keeps everything in memory, nothing survives a restart
+pub async fn setup() -> Result<Arc<dyn Storage>, ApiError> {
     dotenv().expect("Failed to load .env");
-    let mongo_url = env::var("DB_URL").expect("DB URL not set.");
-    let client_options = ClientOptions::parse(mongo_url).await?;
-    let client = Client::with_options(client_options)?;
-    let db = client.database("LemonChess");
+    let storage: Arc<dyn Storage> = match env::var("STORAGE").as_deref() {
+        Ok("sqlite") => {
+            let path = env::var("SQLITE_PATH").unwrap_or("lemon-chess.sqlite".to_string());
+            Arc::new(SqliteStorage::open(&path)?)
+        }
+        Ok("memory") => Arc::new(MemoryStorage::new()),
+        Ok("mongodb") | Err(_) => {
+            let mongo_url = env::var("DB_URL").expect("DB URL not set.");
+            Arc::new(MongoStorage::connect(&mongo_url).await?)
+        }
+        Ok(other) => panic!(
+            "Unknown STORAGE '{}', expected mongodb, sqlite or memory.",
+            other
+        ),
+    };
+    Ok(storage)
+}
 
-    Ok(DB {
-        client,
-        session_collection: db.collection("sessions"),
-        user_collection: db.collection("users"),
-        room_collection: db.collection("rooms"),
-    })
+/// Shared by everything that has to be consistent across API replicas, None if REDIS_URL is not set
+pub async fn setup_redis() -> Option<MultiplexedConnection> {
+    let url = env::var("REDIS_URL").ok()?;
+    let client = redis::Client::open(url).expect("Invalid REDIS_URL.");
+    let connection = client
+        .get_multiplexed_tokio_connection()
+        .await
+        .expect("Failed to connect to Redis.");
+    Some(connection)
 }
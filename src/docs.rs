@@ -1,10 +1,37 @@
 use crate::{
-    game::{color::Color, render::RenderStyle},
+    game::{
+        chess_board::IllegalMoveReason,
+        color::Color,
+        phase::GamePhase,
+        render::RenderStyle,
+        report::ReportFormat,
+        review::{EvalExplanation, EvalTerms, HangingMaterial, PlayerAccuracy},
+        termination::Termination,
+    },
     models::{
-        move_models::LegalMoves,
-        response_models::{MessageResponse, Pagination, UserApiKey},
+        audit_models::{AuditAction, AuditEntryInfo, AuditLog},
+        enums::{
+            AiDifficulty, ColorPreference, GameOutcome, KeyScope, PermissionLevel, Platform,
+            RoomSort, SessionSort,
+        },
+        friend_models::{FriendInfo, FriendList, FriendRequestInfo},
+        move_models::{IllegalMoveResponse, LegalMove, LegalMoves, MoveSubmission},
+        notification_models::{NotificationInfo, NotificationKind, NotificationList},
+        render_job_models::{RenderJobInfo, RenderJobStatus},
+        response_models::{HealthReport, MessageResponse, Pagination, UserApiKey, VersionInfo},
+        review_models::{PlyEval, ReviewEvals},
         room_models::{RoomInfo, RoomList},
-        session_models::{SessionInfo, SessionList},
+        scheduler_models::{ScheduledTaskInfo, SchedulerInfo},
+        session_models::{
+            AiError, AiRetryReport, Annotation, ImportSummary, ImportedGame, OpeningInfo,
+            ResignConfirmation, SessionBatchRequest, SessionEvent, SessionInfo, SessionList,
+            SessionPosition, SessionResult, TimeControl,
+        },
+        user_models::{
+            ActivityInfo, ApiKeyInfo, CooldownState, DailyActivity, Title, UsageInfo, UsageSummary,
+            UserAdminInfo, UserInfo, UserList,
+        },
+        webhook_models::{GameFinishedEvent, WebhookInfo},
     },
     resources,
 };
@@ -17,35 +44,92 @@ use utoipa::{
 #[openapi(
     info(
         title="Lemon Chess",
-        description="A chess web service handling multiplayer, sessions and all game logic.\n\nAll available docs: Rapidoc (/docs), Swagger (/swagger) and Redoc (/redoc).\n\nIf you find bugs or have feedback please create an issue here: https://github.com/Zitronenjoghurt/lemon-chess/issues"
+        description="A chess web service handling multiplayer, sessions and all game logic.\n\nAll available docs: Rapidoc (/docs), Swagger (/swagger) and Redoc (/redoc).\n\nAll endpoints are rate limited per user, all API keys of a user share the limits. The current state is returned in the X-RateLimit-Limit, X-RateLimit-Remaining and X-RateLimit-Reset (seconds) headers.\n\nIf you find bugs or have feedback please create an issue here: https://github.com/Zitronenjoghurt/lemon-chess/issues"
     ),
     paths(
+        resources::notification::get_notifications,
+        resources::notification::post_notifications_read,
         resources::ping::get_ping,
+        resources::presence::post_presence,
+        resources::health::get_health_live,
+        resources::health::get_health_ready,
+        resources::version::get_version,
         resources::room::post_room,
         resources::room::delete_room,
+        resources::room::patch_room,
         resources::room::post_room_join,
+        resources::room::post_room_refresh,
         resources::room::get_rooms,
+        resources::room::get_rooms_invites,
         resources::room::get_rooms_public,
         resources::session::get_session,
         resources::session::post_session,
         resources::session::get_session_pgn,
+        resources::session::get_session_fen,
+        resources::session::get_session_eval_explain,
+        resources::session::post_session_annotations,
         resources::session::delete_session,
+        resources::session::post_session_abort,
+        resources::session::post_session_pause,
+        resources::session::post_session_resume,
+        resources::session::patch_session,
+        resources::session::patch_session_mark,
         resources::session::get_sessions,
+        resources::session::post_sessions_batch,
         resources::session::get_session_render,
         resources::session::get_session_render_history,
+        resources::session::post_session_render_history,
+        resources::session::get_session_render_history_job,
+        resources::session::get_session_render_signed,
+        resources::session::get_session_result,
+        resources::session::get_session_review_evals,
+        resources::session::get_session_review_export,
+        resources::session::get_session_review_pgn,
         resources::session::get_session_move,
         resources::session::post_session_move,
+        resources::session::get_session_events,
         resources::user::post_user_discord,
+        resources::user::post_user_bot,
+        resources::user::post_user_link,
+        resources::user::delete_user_link,
+        resources::user::patch_user,
+        resources::user::get_user_me,
+        resources::user::get_user_activity,
+        resources::user::get_user_usage,
+        resources::user::post_user_webhook,
+        resources::user::get_user_webhook,
+        resources::user::delete_user_webhook,
+        resources::user::post_user_import_chesscom,
+        resources::user::get_user_keys,
+        resources::user::post_user_keys,
+        resources::user::delete_user_keys,
+        resources::friend::get_friends,
+        resources::friend::post_friends_request,
+        resources::friend::post_friends_accept,
+        resources::friend::delete_friends,
+        resources::admin::get_admin_users,
+        resources::admin::get_admin_usage,
+        resources::admin::patch_admin_user_permission,
+        resources::admin::post_admin_user_ban,
+        resources::admin::post_admin_user_title,
+        resources::admin::delete_admin_user_title,
+        resources::admin::delete_admin_user,
+        resources::admin::get_admin_audit,
+        resources::admin::get_admin_scheduler,
+        resources::admin::post_admin_ai_retry,
     ),
     tags(
         (name = "Misc", description = "Miscellaneous endpoints"),
         (name = "User", description = "User endpoints"),
         (name = "Room", description = "Room endpoints"),
         (name = "Session", description = "Session endpoints"),
+        (name = "Friends", description = "Friend endpoints"),
+        (name = "Notifications", description = "Notification endpoints"),
+        (name = "Admin", description = "User management, audit log and background tasks, only for admins"),
     ),
     modifiers(&SecurityAddon),
     components(
-        schemas(MessageResponse, UserApiKey, SessionInfo, Color, LegalMoves, SessionList, Pagination, RoomInfo, RoomList, RenderStyle),
+        schemas(MessageResponse, HealthReport, VersionInfo, UserApiKey, SessionInfo, SessionBatchRequest, ResignConfirmation, SessionResult, Termination, Color, IllegalMoveResponse, IllegalMoveReason, LegalMove, LegalMoves, MoveSubmission, SessionList, Pagination, RoomInfo, RoomList, RenderStyle, ReportFormat, PlyEval, ReviewEvals, PlayerAccuracy, EvalExplanation, EvalTerms, HangingMaterial, RenderJobInfo, RenderJobStatus, ColorPreference, AiDifficulty, RoomSort, SessionSort, GameOutcome, TimeControl, SessionEvent, SessionPosition, Annotation, ImportedGame, ImportSummary, AiError, AiRetryReport, OpeningInfo, GamePhase, PermissionLevel, UserAdminInfo, UserList, AuditAction, AuditEntryInfo, AuditLog, ScheduledTaskInfo, SchedulerInfo, KeyScope, ApiKeyInfo, UserInfo, FriendInfo, FriendList, FriendRequestInfo, NotificationKind, NotificationInfo, NotificationList, CooldownState, UsageInfo, UsageSummary, Title, DailyActivity, ActivityInfo, WebhookInfo, GameFinishedEvent, Platform),
     )
 )]
 pub struct ApiDoc;
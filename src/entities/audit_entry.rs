@@ -0,0 +1,81 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    entities::user::User,
+    error::ApiError,
+    models::{
+        audit_models::{AuditAction, AuditEntryInfo, AuditLog},
+        query_models::AuditLogQuery,
+        response_models::Pagination,
+    },
+    storage::Storage,
+    utils::time_operations::timestamp_now_nanos,
+    AppState,
+};
+
+/// A security relevant event, entries are never changed once recorded
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub action: AuditAction,
+    /// Name of the user who did it
+    pub actor: String,
+    pub target: Option<String>,
+    pub details: Option<String>,
+    pub namespace: String,
+    pub created_stamp: u64,
+}
+
+impl AuditEntry {
+    pub fn new(action: AuditAction, actor: &User) -> Self {
+        Self {
+            id: None,
+            action,
+            actor: actor.name.clone(),
+            target: None,
+            details: None,
+            namespace: actor.namespace.clone(),
+            created_stamp: timestamp_now_nanos(),
+        }
+    }
+
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    pub fn details(mut self, details: impl Into<String>) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+
+    /// The action already happened at this point, a failed write shouldn't fail the request
+    pub async fn record(self, storage: &dyn Storage) {
+        if let Err(error) = storage.insert_audit_entry(&self).await {
+            tracing::warn!(action = ?self.action, actor = self.actor, %error, "Failed to record audit entry");
+        }
+    }
+}
+
+pub async fn find_audit_entries_with_pagination(
+    state: &AppState,
+    query: &AuditLogQuery,
+    page: u32,
+    page_size: u32,
+) -> Result<AuditLog, ApiError> {
+    let offset = Pagination::get_offset(page, page_size) as u64;
+    let (entries, total) = state
+        .storage
+        .find_audit_entries(query, offset, page_size as u64)
+        .await?;
+
+    let entries_info: Vec<AuditEntryInfo> = entries.into_iter().map(AuditEntryInfo::from).collect();
+    let results = entries_info.len() as u32;
+
+    Ok(AuditLog {
+        entries: entries_info,
+        pagination: Pagination::generate(results, total as u32, page, page_size),
+    })
+}
@@ -0,0 +1,114 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    entities::user::{User, UserDirectory},
+    error::ApiError,
+    models::friend_models::{FriendInfo, FriendList, FriendRequestInfo},
+    storage::Storage,
+    utils::time_operations::timestamp_now_nanos,
+};
+
+/// A friend request, once accepted both users are friends
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Friendship {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    /// API key of the user who sent the request
+    pub requester: String,
+    /// API key of the user who received the request
+    pub addressee: String,
+    pub accepted: bool,
+    pub created_stamp: u64,
+    pub accepted_stamp: Option<u64>,
+}
+
+impl Friendship {
+    pub fn new(requester: String, addressee: String) -> Self {
+        Self {
+            id: None,
+            requester,
+            addressee,
+            accepted: false,
+            created_stamp: timestamp_now_nanos(),
+            accepted_stamp: None,
+        }
+    }
+
+    pub fn accept(&mut self) {
+        self.accepted = true;
+        self.accepted_stamp = Some(timestamp_now_nanos());
+    }
+
+    /// The key of the other user
+    pub fn other_key(&self, key: &str) -> &str {
+        if self.requester == key {
+            &self.addressee
+        } else {
+            &self.requester
+        }
+    }
+}
+
+/// The lock which has to be held while changing the friendship of two users, the same for both directions
+pub fn get_lock_key(key: &str, other_key: &str) -> String {
+    let (first, second) = match key < other_key {
+        true => (key, other_key),
+        false => (other_key, key),
+    };
+    format!("friendship:{}:{}", first, second)
+}
+
+/// Friends with their online status and the running sessions against them, plus pending requests
+pub async fn get_friend_list(storage: &dyn Storage, user: &User) -> Result<FriendList, ApiError> {
+    let friendships = storage.find_friendships_by_key(&user.key).await?;
+    let active_sessions = storage
+        .find_sessions_by_key_and_finished(&user.key, false)
+        .await?;
+    let other_keys = friendships
+        .iter()
+        .map(|friendship| friendship.other_key(&user.key).to_string())
+        .collect();
+    let users = UserDirectory::load(storage, other_keys).await?;
+
+    let mut friend_list = FriendList {
+        friends: Vec::new(),
+        incoming: Vec::new(),
+        outgoing: Vec::new(),
+    };
+    for friendship in friendships {
+        let other_key = friendship.other_key(&user.key);
+        // The other user might have been deleted
+        let Some(other) = users.get(other_key) else {
+            continue;
+        };
+
+        if friendship.accepted {
+            let active_sessions = active_sessions
+                .iter()
+                .filter(|session| session.keys.iter().any(|key| key == other_key))
+                .filter_map(|session| session.id.map(|id| id.to_hex()))
+                .collect();
+            friend_list.friends.push(FriendInfo {
+                online: other.is_online(),
+                name: other.name.clone(),
+                display_name: other.display_name.clone(),
+                friends_since: friendship
+                    .accepted_stamp
+                    .unwrap_or(friendship.created_stamp),
+                active_sessions,
+            });
+        } else {
+            let request = FriendRequestInfo {
+                name: other.name.clone(),
+                display_name: other.display_name.clone(),
+                created_stamp: friendship.created_stamp,
+            };
+            match friendship.requester == user.key {
+                true => friend_list.outgoing.push(request),
+                false => friend_list.incoming.push(request),
+            }
+        }
+    }
+    Ok(friend_list)
+}
@@ -0,0 +1,118 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    entities::session::Session,
+    error::ApiError,
+    models::{
+        notification_models::{NotificationInfo, NotificationKind, NotificationList},
+        response_models::Pagination,
+    },
+    storage::Storage,
+    utils::time_operations::timestamp_now_nanos,
+    AppState,
+};
+
+/// An inbox entry, so clients without a live connection still learn about what happened
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Notification {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    /// Key of the user receiving it
+    pub key: String,
+    pub kind: NotificationKind,
+    pub message: String,
+    pub session_id: Option<String>,
+    /// Name of the user who caused it
+    pub from: Option<String>,
+    pub read: bool,
+    pub created_stamp: u64,
+}
+
+impl Notification {
+    pub fn new(key: &str, kind: NotificationKind, message: impl Into<String>) -> Self {
+        Self {
+            id: None,
+            key: key.to_string(),
+            kind,
+            message: message.into(),
+            session_id: None,
+            from: None,
+            read: false,
+            created_stamp: timestamp_now_nanos(),
+        }
+    }
+
+    pub fn session(mut self, session: &Session) -> Self {
+        self.session_id = session.id.map(|id| id.to_hex());
+        self
+    }
+
+    pub fn from(mut self, name: impl Into<String>) -> Self {
+        self.from = Some(name.into());
+        self
+    }
+
+    /// The event already happened at this point, a failed write shouldn't fail the request
+    pub async fn send(self, storage: &dyn Storage) {
+        if self.key == "AI" {
+            return;
+        }
+        if let Err(error) = storage.insert_notification(&self).await {
+            tracing::warn!(kind = ?self.kind, %error, "Failed to send notification");
+        }
+    }
+}
+
+/// Tells the opponent of the player who just changed the session that it is their turn or that the game is over
+pub async fn notify_opponent(storage: &dyn Storage, session: &Session, key: &str, name: &str) {
+    let Some(opponent_key) = session.keys.iter().find(|other| *other != key) else {
+        return;
+    };
+
+    let notification = match session.get_end_reason() {
+        Some(reason) => Notification::new(
+            opponent_key,
+            NotificationKind::GameFinished,
+            format!(
+                "{} ended by {} ({})",
+                session.name,
+                reason,
+                session.get_result_notation()
+            ),
+        ),
+        None => Notification::new(
+            opponent_key,
+            NotificationKind::YourTurn,
+            format!("It's your turn in {}", session.name),
+        ),
+    };
+    notification.session(session).from(name).send(storage).await;
+}
+
+pub async fn find_notifications_with_pagination(
+    state: &AppState,
+    key: &str,
+    unread_only: bool,
+    page: u32,
+    page_size: u32,
+) -> Result<NotificationList, ApiError> {
+    let offset = Pagination::get_offset(page, page_size) as u64;
+    let (notifications, total) = state
+        .storage
+        .find_notifications(key, unread_only, offset, page_size as u64)
+        .await?;
+    let unread = state.storage.count_unread_notifications(key).await?;
+
+    let notifications_info: Vec<NotificationInfo> = notifications
+        .into_iter()
+        .map(NotificationInfo::from)
+        .collect();
+    let results = notifications_info.len() as u32;
+
+    Ok(NotificationList {
+        notifications: notifications_info,
+        unread,
+        pagination: Pagination::generate(results, total as u32, page, page_size),
+    })
+}
@@ -0,0 +1,79 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::ApiError,
+    game::color::Color,
+    models::{
+        query_models::{PlyRangeQuery, RenderStyleQuery},
+        render_job_models::RenderJobStatus,
+    },
+    storage::Storage,
+    utils::time_operations::timestamp_now_nanos,
+};
+
+/// Running jobs which haven't finished after this are assumed to be abandoned (e.g. the replica died) and get picked up again
+pub const STALE_AFTER_NANOS: u64 = 5 * 60 * 1_000_000_000;
+
+/// Jobs and their results are deleted after this
+pub const EXPIRE_AFTER_NANOS: u64 = 60 * 60 * 1_000_000_000;
+
+/// A history GIF which gets rendered by a worker, the result is stored next to the job
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RenderJob {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    /// API key of the user who requested the render
+    pub key: String,
+    pub session_id: ObjectId,
+    pub color: Color,
+    pub style: RenderStyleQuery,
+    pub plies: PlyRangeQuery,
+    pub status: RenderJobStatus,
+    pub error: Option<String>,
+    /// Stored file of the finished GIF
+    pub result_id: Option<ObjectId>,
+    pub created_stamp: u64,
+    pub started_stamp: Option<u64>,
+}
+
+impl RenderJob {
+    pub fn new(
+        key: String,
+        session_id: ObjectId,
+        color: Color,
+        style: RenderStyleQuery,
+        plies: PlyRangeQuery,
+    ) -> Self {
+        Self {
+            id: None,
+            key,
+            session_id,
+            color,
+            style,
+            plies,
+            status: RenderJobStatus::QUEUED,
+            error: None,
+            result_id: None,
+            created_stamp: timestamp_now_nanos(),
+            started_stamp: None,
+        }
+    }
+
+    /// Inserts the job and sets its id
+    pub async fn insert(&mut self, storage: &dyn Storage) -> Result<(), ApiError> {
+        storage.insert_render_job(self).await
+    }
+
+    pub async fn complete(
+        &self,
+        storage: &dyn Storage,
+        result_id: ObjectId,
+    ) -> Result<(), ApiError> {
+        storage.complete_render_job(self, result_id).await
+    }
+
+    pub async fn fail(&self, storage: &dyn Storage, error: &str) -> Result<(), ApiError> {
+        storage.fail_render_job(self, error).await
+    }
+}
@@ -1,23 +1,32 @@
-use futures::{stream, StreamExt, TryStreamExt};
-use mongodb::{
-    bson::{self, doc, oid::ObjectId},
-    options::{FindOptions, InsertOneOptions, UpdateOptions},
-    Collection,
-};
+use std::sync::Arc;
+
+use mongodb::bson::{oid::ObjectId, DateTime};
 use serde::{Deserialize, Serialize};
+use tokio_util::task::TaskTracker;
 
 use crate::{
+    entities::session::Session,
     error::ApiError,
     models::{
+        enums::ColorPreference,
+        query_models::RoomFilterQuery,
         response_models::Pagination,
         room_models::{RoomInfo, RoomList},
+        session_models::TimeControl,
     },
-    utils::{random::generate_user_friendly_code, time_operations::timestamp_now_nanos},
+    storage::{RoomSelection, Storage},
+    utils::{random::GameRng, time_operations::timestamp_now_nanos},
     AppState,
 };
 
+/// Rooms nobody joined are closed after this, unless the owner refreshes them
+pub const ROOM_LIFETIME_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// How often a new room code is drawn if the previous one is taken
+const ROOM_CODE_ATTEMPTS: usize = 5;
+
 /// A user will create a room, if another person joins the room will be deleted and a session will be started
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Room {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
@@ -26,56 +35,90 @@ pub struct Room {
     pub name: String,
     pub created_stamp: u64,
     pub public: bool,
+    #[serde(default)]
+    /// Namespace of the creator, only users of the same namespace can see and join the room
+    pub namespace: String,
+    /// The color the creator will play
+    #[serde(default)]
+    pub color: ColorPreference,
+    /// Carried onto the session once someone joins
+    #[serde(default)]
+    pub time_control: Option<TimeControl>,
+    /// The room is deleted once this has passed
+    #[serde(default)]
+    pub expires_at: Option<DateTime>,
+    /// Only this user can join the room if set
+    #[serde(default)]
+    pub invited_key: Option<String>,
+    /// If bot accounts may join the room
+    #[serde(default)]
+    pub allow_bots: bool,
 }
 
 impl Room {
-    pub async fn new(
-        collection: &Collection<Room>,
+    /// The code has to be unused, see generate_room_code
+    pub fn new(
+        code: String,
         key: String,
+        namespace: String,
         name: String,
         public: bool,
-    ) -> Result<Self, ApiError> {
-        let code = generate_user_friendly_code(6);
-
-        let code_available = room_code_available(collection, &code).await?;
-        if !code_available {
-            return Err(ApiError::BadRequest("Room code collision".to_string()));
-        }
-
-        let room = Self {
+        color: ColorPreference,
+        time_control: Option<TimeControl>,
+    ) -> Self {
+        Self {
             id: None,
             key,
             code,
             name,
             created_stamp: timestamp_now_nanos(),
             public,
-        };
+            namespace,
+            color,
+            time_control,
+            expires_at: Some(get_room_expiry()),
+            invited_key: None,
+            allow_bots: false,
+        }
+    }
 
-        Ok(room)
+    /// Restricts the room to a single user, invites are never publicly listed
+    pub fn invite(&mut self, key: String) {
+        self.invited_key = Some(key);
+        self.public = false;
     }
 
-    pub async fn save(&self, collection: &Collection<Room>) -> Result<(), ApiError> {
-        if let Some(id) = &self.id {
-            let filter = doc! { "_id": id };
-            let update = doc! { "$set": bson::to_bson(self)? };
-            let options = UpdateOptions::builder().upsert(true).build();
-            collection.update_one(filter, update, Some(options)).await?;
-        } else {
-            let options = InsertOneOptions::builder().build();
-            collection.insert_one(self, Some(options)).await?;
-        }
-        Ok(())
+    pub fn can_join(&self, key: &str) -> bool {
+        self.invited_key
+            .as_ref()
+            .is_none_or(|invited_key| invited_key == key)
+    }
+
+    /// Bots can join rooms which allow bots and rooms they were invited to
+    pub fn allows_bot(&self, key: &str) -> bool {
+        self.allow_bots || self.invited_key.as_deref() == Some(key)
+    }
+
+    /// Restarts the lifetime of the room
+    pub fn refresh(&mut self) {
+        self.expires_at = Some(get_room_expiry());
+    }
+
+    /// Seconds until the room expires
+    pub fn get_expires_in(&self) -> Option<u64> {
+        self.expires_at.map(|expires_at| {
+            let remaining_ms = expires_at.timestamp_millis() - DateTime::now().timestamp_millis();
+            remaining_ms.max(0) as u64 / 1000
+        })
+    }
+
+    pub async fn save(&self, storage: &dyn Storage) -> Result<(), ApiError> {
+        storage.save_room(self).await
     }
 }
 
-pub async fn find_rooms_by_key(
-    collection: &Collection<Room>,
-    key: &str,
-) -> Result<Vec<Room>, ApiError> {
-    let filter = doc! { "key": key};
-    let cursor = collection.find(filter, None).await?;
-    let rooms: Vec<Room> = cursor.try_collect().await?;
-    Ok(rooms)
+pub fn get_room_expiry() -> DateTime {
+    DateTime::from_millis(DateTime::now().timestamp_millis() + ROOM_LIFETIME_MS)
 }
 
 pub async fn find_rooms_by_key_with_pagination(
@@ -84,83 +127,81 @@ pub async fn find_rooms_by_key_with_pagination(
     page: u32,
     page_size: u32,
 ) -> Result<RoomList, ApiError> {
-    let collection = &state.database.room_collection;
-
-    let offset = Pagination::get_offset(page, page_size);
-    let find_options = FindOptions::builder()
-        .skip(offset as u64)
-        .limit(page_size as i64)
-        .build();
-    let filter = doc! { "key": key };
-
-    let total = collection.count_documents(filter.clone(), None).await? as u32;
-
-    let cursor = collection.find(filter, find_options).await?;
-    let rooms: Vec<Room> = cursor.try_collect().await?;
-    let rooms_info: Vec<RoomInfo> = stream::iter(rooms)
-        .then(|room| RoomInfo::from_room(state, room))
-        .try_collect()
-        .await?;
-    let results = rooms_info.len() as u32;
+    find_rooms_with_pagination(state, RoomSelection::Owner(key), page, page_size).await
+}
 
-    Ok(RoomList {
-        rooms: rooms_info,
-        pagination: Pagination::generate(results, total, page, page_size),
-    })
+/// Rooms other users have invited the given user to
+pub async fn find_room_invites_with_pagination(
+    state: &AppState,
+    key: &str,
+    page: u32,
+    page_size: u32,
+) -> Result<RoomList, ApiError> {
+    find_rooms_with_pagination(state, RoomSelection::Invited(key), page, page_size).await
 }
 
+/// Without a namespace, public rooms of all namespaces are returned
 pub async fn find_public_rooms_with_pagination(
     state: &AppState,
+    namespace: Option<&str>,
+    filter: &RoomFilterQuery,
     page: u32,
     page_size: u32,
 ) -> Result<RoomList, ApiError> {
-    let collection = &state.database.room_collection;
-
-    let offset = Pagination::get_offset(page, page_size);
-    let find_options = FindOptions::builder()
-        .skip(offset as u64)
-        .limit(page_size as i64)
-        .build();
-    let filter = doc! { "public": true };
-
-    let total = collection.count_documents(filter.clone(), None).await? as u32;
-
-    let cursor = collection.find(filter, find_options).await?;
-    let rooms: Vec<Room> = cursor.try_collect().await?;
-    let rooms_info: Vec<RoomInfo> = stream::iter(rooms)
-        .then(|room| RoomInfo::from_room(state, room))
-        .try_collect()
+    let selection = RoomSelection::Public { namespace, filter };
+    find_rooms_with_pagination(state, selection, page, page_size).await
+}
+
+async fn find_rooms_with_pagination(
+    state: &AppState,
+    selection: RoomSelection<'_>,
+    page: u32,
+    page_size: u32,
+) -> Result<RoomList, ApiError> {
+    let offset = Pagination::get_offset(page, page_size) as u64;
+    let (rooms, total) = state
+        .storage
+        .find_rooms(selection, offset, page_size as u64)
         .await?;
+
+    let rooms_info = RoomInfo::from_rooms(state, rooms).await?;
     let results = rooms_info.len() as u32;
 
     Ok(RoomList {
         rooms: rooms_info,
-        pagination: Pagination::generate(results, total, page, page_size),
+        pagination: Pagination::generate(results, total as u32, page, page_size),
     })
 }
 
-pub async fn find_room_by_code(
-    collection: &Collection<Room>,
+/// Deletes the room and inserts the session started from it, either both happen or neither.
+/// Runs detached from the request, so a client disconnect can't interrupt it halfway.
+pub async fn start_room_session(
+    storage: &Arc<dyn Storage>,
+    tasks: &TaskTracker,
     code: &str,
-) -> Result<Option<Room>, ApiError> {
-    let filter = doc! { "code": code.to_uppercase() };
-    let room = collection.find_one(Some(filter), None).await?;
-    Ok(room)
+    session: Session,
+) -> Result<(), ApiError> {
+    let storage = storage.clone();
+    let code = code.to_string();
+
+    tasks
+        .spawn(async move { storage.start_room_session(&code, &session).await })
+        .await
+        .map_err(|err| ApiError::ServerError(err.to_string()))?
 }
 
-pub async fn room_code_available(
-    collection: &Collection<Room>,
-    code: &str,
-) -> Result<bool, ApiError> {
-    let room = find_room_by_code(collection, code).await?;
-    Ok(room.is_none())
+/// Codes already taken are drawn again a few times before giving up
+pub async fn generate_room_code(storage: &dyn Storage, rng: &GameRng) -> Result<String, ApiError> {
+    for _ in 0..ROOM_CODE_ATTEMPTS {
+        let code = rng.generate_code(6);
+        if room_code_available(storage, &code).await? {
+            return Ok(code);
+        }
+    }
+    Err(ApiError::BadRequest("Room code collision".to_string()))
 }
 
-pub async fn delete_room_by_code(
-    collection: &Collection<Room>,
-    code: &str,
-) -> Result<(), ApiError> {
-    let filter = doc! { "code": code };
-    collection.delete_one(filter, None).await?;
-    Ok(())
+pub async fn room_code_available(storage: &dyn Storage, code: &str) -> Result<bool, ApiError> {
+    let room = storage.find_room_by_code(code).await?;
+    Ok(room.is_none())
 }
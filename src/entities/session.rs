@@ -1,94 +1,290 @@
+use std::sync::Arc;
+
 use chrono_tz::UTC;
-use futures::{stream, StreamExt, TryStreamExt};
-use mongodb::{
-    bson::{self, doc, oid::ObjectId},
-    options::{FindOptions, InsertOneOptions, UpdateOptions},
-    Collection,
-};
-use rand::Rng;
+use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
+use tokio_util::task::TaskTracker;
+use tracing::Instrument;
 
 use crate::{
+    entities::{
+        session_mark::SessionMark,
+        user::{User, UserDirectory},
+    },
     error::ApiError,
-    game::{ai::get_next_move, color::Color, state::GameState},
+    game::{
+        ai::{get_next_move, ponder, take_pondered_move},
+        chess_board::IllegalMoveReason,
+        color::Color,
+        opening::Opening,
+        position::Square,
+        report::GameReport,
+        review::{find_blunder, GameReview, HangingMaterial, PlayerAccuracy},
+        state::GameState,
+        termination::Termination,
+    },
     models::{
-        move_models::{LegalMoves, MoveQuery},
+        enums::{AiDifficulty, GameOutcome},
+        move_models::{LegalMove, LegalMoves, MoveQuery, PlayerMove},
         response_models::Pagination,
-        session_models::{SessionInfo, SessionList},
+        session_models::{
+            AiError, Annotation, ImportedGame, SessionInfo, SessionList, TimeControl,
+            ANNOTATION_GLYPHS,
+        },
+    },
+    storage::{SessionFilter, Storage},
+    utils::{
+        etag,
+        random::{generate_user_friendly_code, GameRng},
+        time_operations::{nanos_to_date, timestamp_now_nanos},
     },
-    utils::time_operations::{nanos_to_date, timestamp_now_nanos},
     AppState,
 };
 
-use super::user::find_user_by_key;
-
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Session {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     pub name: String,
     pub keys: [String; 2],
     pub created_stamp: u64,
+    #[serde(with = "stored_game_state")]
     pub game_state: GameState,
+    /// None if the game is played without clocks
+    #[serde(default)]
+    pub time_control: Option<TimeControl>,
+    /// Accuracy of white and black, stored once the finished game was reviewed
+    #[serde(default)]
+    pub accuracy: Option<[PlayerAccuracy; 2]>,
+    /// Glyphs and comments the players attached to plies, at most one per player and ply
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+    /// Set if the game was played on another site, the opponent then has the IMPORTED_KEY
+    #[serde(default)]
+    pub imported: Option<ImportedGame>,
+    /// UNIX timestamp in nanoseconds of the last move or resignation
+    /// 0 for sessions from before it was tracked, see get_activity_stamp
+    #[serde(default)]
+    pub updated_stamp: u64,
+    /// Set if a player aborted the game before both sides moved, the game then has no result
+    #[serde(default)]
+    pub aborted: bool,
+    /// Resignation a player asked to confirm, see request_resignation
+    #[serde(default)]
+    pub pending_resignation: Option<PendingResignation>,
+    /// Set while the game is paused by agreement of both players, nobody can move until both resume it
+    #[serde(default)]
+    pub paused: bool,
+    /// Key of the player who asked to pause or resume the game, see request_pause
+    #[serde(default)]
+    pub pause_request: Option<String>,
+    /// Set if the player to move lost because they abandoned a timed game, see is_abandoned
+    #[serde(default)]
+    pub timed_out: bool,
+    /// Warns players in the response to their move if it left material hanging, see PATCH /session
+    #[serde(default)]
+    pub blunder_alerts: bool,
+    /// How strong the AI plays, only used if one of the keys is the AI
+    #[serde(default)]
+    pub ai_difficulty: AiDifficulty,
+    /// Set while the AI fails to play its move, see apply_ai_move
+    #[serde(default)]
+    pub ai_error: Option<AiError>,
+}
+
+/// The AI stops retrying a failed move after this many attempts, only admins can retry it then
+pub const MAX_AI_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry of a failed AI move, it doubles with every further attempt
+const AI_RETRY_DELAY_S: u64 = 10;
+
+/// How long a player has to confirm their resignation
+pub const RESIGN_CONFIRMATION_S: u64 = 30;
+
+/// A resignation which only happens once the player sends the token again
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PendingResignation {
+    pub key: String,
+    pub token: String,
+    /// UNIX timestamp in nanoseconds
+    pub expires_stamp: u64,
+}
+
+/// Key of the opponent in imported games, it doesn't belong to any user
+pub const IMPORTED_KEY: &str = "IMPORTED";
+
+/// The game state is stored in its binary encoding, winner and draw are kept next to it for queries and indexes
+mod stored_game_state {
+    use mongodb::bson::{self, doc, spec::BinarySubtype, Binary, Document};
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::game::{color::Color, state::GameState, termination::Termination};
+
+    pub fn serialize<S: Serializer>(state: &GameState, serializer: S) -> Result<S::Ok, S::Error> {
+        let data = Binary {
+            subtype: BinarySubtype::Generic,
+            bytes: state.to_bytes(),
+        };
+        doc! { "data": data, "winner": state.winner as i32, "draw": state.is_draw() }
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<GameState, D::Error> {
+        let document = Document::deserialize(deserializer)?;
+        match document.get_binary_generic("data") {
+            Ok(bytes) => GameState::from_bytes(bytes).map_err(de::Error::custom),
+            // Sessions saved before the binary encoding contain every field of the game state
+            Err(_) => bson::from_document::<GameState>(document.clone())
+                .map_err(de::Error::custom)
+                .and_then(|mut state| {
+                    state.chess_board.validate().map_err(de::Error::custom)?;
+                    state.restore_move_kinds();
+                    // And no checkers, without them the check states and legal moves are wrong
+                    let color = Color::from(state.next_to_move as usize);
+                    state.checkers = state.chess_board.get_checkers(color);
+                    state.update_check_states();
+                    // They also had a flag per ending instead of the termination
+                    let flag = |name| document.get_bool(name).unwrap_or(false);
+                    state.termination = state.termination.or(Termination::from_flags(
+                        flag("draw"),
+                        flag("checkmate"),
+                        flag("resign"),
+                        flag("stalemate"),
+                        flag("remis"),
+                        flag("dead_position"),
+                    ));
+                    Ok(state)
+                }),
+        }
+    }
 }
 
 impl Session {
     pub fn new(name: String, keys: [String; 2], game_state: GameState) -> Self {
+        let created_stamp = timestamp_now_nanos();
         Self {
             id: None,
             name,
             keys,
-            created_stamp: timestamp_now_nanos(),
+            created_stamp,
             game_state,
+            time_control: None,
+            accuracy: None,
+            annotations: Vec::new(),
+            imported: None,
+            updated_stamp: created_stamp,
+            aborted: false,
+            pending_resignation: None,
+            paused: false,
+            pause_request: None,
+            timed_out: false,
+            blunder_alerts: false,
+            ai_difficulty: AiDifficulty::default(),
+            ai_error: None,
         }
     }
 
-    pub fn new_ai(name: String, key: String, game_state: GameState) -> Self {
-        let mut rng = rand::thread_rng();
+    pub fn new_ai(name: String, key: String, game_state: GameState, rng: &GameRng) -> Self {
         let keys = match rng.gen_bool(0.5) {
             true => ["AI".to_string(), key],
             _ => [key, "AI".to_string()],
         };
 
+        let created_stamp = timestamp_now_nanos();
         Self {
             id: None,
             name,
             keys,
-            created_stamp: timestamp_now_nanos(),
+            created_stamp,
             game_state,
+            time_control: None,
+            accuracy: None,
+            annotations: Vec::new(),
+            imported: None,
+            updated_stamp: created_stamp,
+            aborted: false,
+            pending_resignation: None,
+            paused: false,
+            pause_request: None,
+            timed_out: false,
+            blunder_alerts: false,
+            ai_difficulty: AiDifficulty::default(),
+            ai_error: None,
         }
     }
 
-    pub fn do_move(&mut self, key: &str, chess_move: &MoveQuery) -> Result<(), ApiError> {
-        if !self.can_move(key.to_string()) {
-            return Err(ApiError::BadRequest(
-                "You can't move in this game.".to_string(),
-            ));
+    /// A finished game of another site, played by the owner of the key with the given color
+    pub fn new_imported(
+        name: String,
+        key: String,
+        color: Color,
+        game_state: GameState,
+        created_stamp: u64,
+        imported: ImportedGame,
+    ) -> Self {
+        let keys = match color {
+            Color::WHITE => [key, IMPORTED_KEY.to_string()],
+            _ => [IMPORTED_KEY.to_string(), key],
+        };
+
+        Self {
+            id: None,
+            name,
+            keys,
+            created_stamp,
+            game_state,
+            time_control: None,
+            accuracy: None,
+            annotations: Vec::new(),
+            imported: Some(imported),
+            updated_stamp: created_stamp,
+            aborted: false,
+            pending_resignation: None,
+            paused: false,
+            pause_request: None,
+            timed_out: false,
+            blunder_alerts: false,
+            ai_difficulty: AiDifficulty::default(),
+            ai_error: None,
         }
+    }
 
-        if !self.is_move_possible(key, chess_move)? {
+    /// Returns the material the move left hanging if blunder alerts are on
+    /// The check happens before the AI replies, which could take the material right away
+    /// The AI reply isn't part of the move, see session_service::run_ai_move
+    pub fn do_move(
+        &mut self,
+        key: &str,
+        chess_move: &MoveQuery,
+    ) -> Result<Option<HangingMaterial>, ApiError> {
+        if self.paused {
             return Err(ApiError::BadRequest(
-                "The given move is impossible.".to_string(),
+                "The game is paused, both players have to resume it first.".to_string(),
             ));
         }
-
         let color = match self.get_color_from_key(key) {
-            Some(color) => color,
-            None => {
+            Some(color) if !self.is_finished() => color,
+            _ => {
                 return Err(ApiError::BadRequest(
                     "You can't move in this game.".to_string(),
                 ))
             }
         };
+        if !self.can_move(key.to_string()) {
+            return Err(ApiError::IllegalMove(IllegalMoveReason::NotYourTurn));
+        }
+
+        let player_move = chess_move.convert_to_move()?;
+        if let Some(reason) = self.diagnose_move(color, player_move, chess_move)? {
+            return Err(ApiError::IllegalMove(reason));
+        }
 
-        let (from, to, kingside_castle, queenside_castle) = chess_move.convert_to_move()?;
+        let before = (self.blunder_alerts && key != "AI").then(|| self.game_state.clone());
 
-        let success = if kingside_castle {
-            self.game_state.castle_kingside(color)
-        } else if queenside_castle {
-            self.game_state.castle_queenside(color)
-        } else {
-            self.game_state.make_move(from, to)
+        let success = match player_move {
+            PlayerMove::Regular { from, to } => self.game_state.make_move(from, to),
+            PlayerMove::CastleKingside => self.game_state.castle_kingside(color),
+            PlayerMove::CastleQueenside => self.game_state.castle_queenside(color),
         }?;
 
         if !success {
@@ -97,22 +293,85 @@ impl Session {
             ));
         }
 
-        // Do AI move if possible
-        self.do_ai_move().map_err(|err| {
-            ApiError::ServerError(format!("An error occured while playing the AI: {}", err))
-        })?;
+        let blunder = match before {
+            Some(before) => find_blunder(&before, &self.game_state)?,
+            None => None,
+        };
 
-        Ok(())
+        self.updated_stamp = timestamp_now_nanos();
+        Ok(blunder)
+    }
+
+    pub fn needs_ai_move(&self) -> bool {
+        self.can_move("AI".to_string()) && !self.is_finished()
     }
 
-    pub fn do_ai_move(&mut self) -> Result<(), ApiError> {
-        if !self.can_move("AI".to_string()) || self.is_finished() {
-            return Ok(());
+    /// The move the AI plays next, the reply pondered on the opponent's time if it still fits
+    /// The search can take seconds, so it shouldn't run on the async runtime or while holding the session lock
+    pub fn find_ai_move(&self, rng: &GameRng) -> Result<MoveQuery, ApiError> {
+        let pondered_move = self
+            .id
+            .and_then(|id| take_pondered_move(&id.to_hex(), &self.game_state, self.ai_difficulty));
+        match pondered_move {
+            Some(pondered_move) => Ok(pondered_move),
+            None => Ok(get_next_move(&self.game_state, self.ai_difficulty, rng)?),
         }
+    }
 
-        let next_move = get_next_move(&self.game_state)?;
-        self.do_move("AI", &next_move)?;
-        Ok(())
+    /// Plays the move found by find_ai_move, a failure is recorded in ai_error and retried later
+    /// Returns if the AI doesn't have to move anymore
+    pub fn apply_ai_move(&mut self, next_move: Result<MoveQuery, ApiError>) -> bool {
+        if !self.needs_ai_move() {
+            return true;
+        }
+        let result = next_move.and_then(|next_move| self.do_move("AI", &next_move));
+        let error = match result {
+            Ok(_) => {
+                self.ai_error = None;
+                return true;
+            }
+            Err(error) => error,
+        };
+
+        let attempts = self.ai_error.as_ref().map_or(0, |error| error.attempts) + 1;
+        let failed_stamp = timestamp_now_nanos();
+        let retry_stamp = (attempts < MAX_AI_ATTEMPTS)
+            .then(|| failed_stamp + (AI_RETRY_DELAY_S << (attempts - 1)) * 1_000_000_000);
+        let session_id = self.id.map(|id| id.to_hex()).unwrap_or_default();
+        tracing::warn!(session_id, attempts, %error, "AI failed to move");
+        self.ai_error = Some(AiError {
+            message: error.to_string(),
+            attempts,
+            failed_stamp,
+            retry_stamp,
+        });
+        false
+    }
+
+    /// If the AI should try to move now, failed moves are only retried once their delay passed
+    pub fn is_ai_move_due(&self, now: u64) -> bool {
+        self.needs_ai_move()
+            && self.ai_error.as_ref().is_none_or(|error| {
+                error
+                    .retry_stamp
+                    .is_some_and(|retry_stamp| retry_stamp <= now)
+            })
+    }
+
+    /// Lets the AI think about its next reply while its opponent is to move
+    pub fn ponder_ai_move(&self, rng: &GameRng) {
+        let Some(id) = self.id else {
+            return;
+        };
+        if self.is_finished() || !self.keys.iter().any(|key| key == "AI") || self.needs_ai_move() {
+            return;
+        }
+        ponder(
+            id.to_hex(),
+            self.game_state.clone(),
+            self.ai_difficulty,
+            rng.clone(),
+        );
     }
 
     pub fn get_color_from_key(&self, key: &str) -> Option<Color> {
@@ -131,29 +390,45 @@ impl Session {
             None => return Ok(false),
         };
 
-        let (from, to, kingside_castle, queenside_castle) = chess_move.convert_to_move()?;
-
-        if kingside_castle && queenside_castle {
-            return Ok(false);
-        }
-
-        if kingside_castle && self.game_state.can_castle_kingside[color as usize] {
-            return Ok(true);
-        }
-
-        if queenside_castle && self.game_state.can_castle_queenside[color as usize] {
-            return Ok(true);
-        }
-
-        if self.game_state.available_moves[color as usize].has_move(from, to) {
-            return Ok(true);
-        }
+        let player_move = chess_move.convert_to_move()?;
+        Ok(self
+            .diagnose_move(color, player_move, chess_move)?
+            .is_none())
+    }
 
-        Ok(false)
+    /// Why the move of the color can't be played, None if it's legal
+    fn diagnose_move(
+        &self,
+        color: Color,
+        player_move: PlayerMove,
+        chess_move: &MoveQuery,
+    ) -> Result<Option<IllegalMoveReason>, ApiError> {
+        let reason = match player_move {
+            PlayerMove::Regular { from, to } => {
+                if !self
+                    .game_state
+                    .get_available_moves(color)?
+                    .has_move(from, to)
+                {
+                    Some(self.game_state.diagnose_move(color, from, to)?)
+                } else if chess_move.promotion.is_some()
+                    && !self.game_state.get_move_flags(from, to)?.1
+                {
+                    Some(IllegalMoveReason::WrongPromotionRank)
+                } else {
+                    None
+                }
+            }
+            PlayerMove::CastleKingside => (!self.game_state.can_castle_kingside[color as usize])
+                .then(|| self.game_state.diagnose_castle(color, true)),
+            PlayerMove::CastleQueenside => (!self.game_state.can_castle_queenside[color as usize])
+                .then(|| self.game_state.diagnose_castle(color, false)),
+        };
+        Ok(reason)
     }
 
     pub fn can_move(&self, key: String) -> bool {
-        if self.is_finished() || !self.keys.contains(&key) {
+        if self.is_finished() || self.paused || !self.keys.contains(&key) {
             return false;
         }
 
@@ -166,27 +441,134 @@ impl Session {
     }
 
     pub fn get_legal_moves(&self, color: Color) -> Result<LegalMoves, ApiError> {
-        let available_moves = &self.game_state.available_moves[color as usize];
-        let moves = available_moves.get_moves()?;
+        let moves = self.game_state.get_available_moves(color)?.get_moves();
 
         let mut move_pairs: Vec<(String, String)> = Vec::new();
+        let mut flagged_moves: Vec<LegalMove> = Vec::new();
         for m in moves {
-            move_pairs.push((m.0.as_str(), m.1.as_str()));
+            let (capture, promotion, check) = self.game_state.get_move_flags(m.from, m.to)?;
+            flagged_moves.push(LegalMove {
+                from: m.from.as_str(),
+                to: m.to.as_str(),
+                capture,
+                promotion,
+                check,
+            });
+            move_pairs.push((m.from.as_str(), m.to.as_str()));
         }
 
+        let (kingside_target, queenside_target) = self.game_state.get_castle_targets(color);
+        let en_passant = self.game_state.get_en_passant_target(color)?;
+        let to_cell = |square: Option<Square>| square.map(|square| square.as_str());
+
         let legal_moves = LegalMoves {
             color,
             cells: move_pairs,
+            moves: flagged_moves,
             current_turn: color as u8 == self.game_state.next_to_move,
             castle_kingside: self.game_state.can_castle_kingside[color as usize],
             castle_queenside: self.game_state.can_castle_queenside[color as usize],
+            castle_kingside_target: to_cell(kingside_target),
+            castle_queenside_target: to_cell(queenside_target),
+            en_passant: to_cell(en_passant),
         };
 
         Ok(legal_moves)
     }
 
+    /// Changes whenever the session itself changes for the given key, see SessionInfo::get_etag for the rest of the info
+    pub fn get_etag(&self, key: &str) -> String {
+        let end_reason = self.get_end_reason().unwrap_or_default();
+        let pause_state = format!("{}{:?}", self.paused, self.get_pause_requester());
+        let blunder_alerts = self.blunder_alerts.to_string();
+        let ai_error = format!("{:?}", self.ai_error);
+        etag::generate(&[
+            &self.name,
+            &pause_state,
+            &blunder_alerts,
+            &ai_error,
+            &self.game_state.move_log.len().to_string(),
+            &self.game_state.to_fen(),
+            &end_reason,
+            key,
+        ])
+    }
+
     pub fn is_finished(&self) -> bool {
-        self.game_state.winner != 2 || self.game_state.draw
+        self.game_state.winner != 2 || self.get_termination().is_some()
+    }
+
+    /// How the game ended, None while it's running
+    /// Sessions saved before the termination existed only have the aborted and timed out flags
+    pub fn get_termination(&self) -> Option<Termination> {
+        if self.aborted {
+            return Some(Termination::Aborted);
+        }
+        if self.timed_out {
+            return Some(Termination::Timeout);
+        }
+        self.game_state.termination
+    }
+
+    /// Why the game ended, None while it's running
+    pub fn get_end_reason(&self) -> Option<String> {
+        self.get_termination()
+            .map(|termination| termination.describe().to_string())
+    }
+
+    /// If the player to move of a timed game hasn't moved for longer than their clock could show
+    /// Their clock never had more than the initial time plus the increments of their own moves
+    pub fn is_abandoned(&self, now: u64) -> bool {
+        let Some(time_control) = self.time_control else {
+            return false;
+        };
+        if self.is_finished() || self.paused || self.imported.is_some() {
+            return false;
+        }
+
+        // The player to move made exactly half of the plies, rounded down
+        let own_moves = (self.game_state.move_log.len() / 2) as u64;
+        let max_clock_s =
+            time_control.initial_seconds as u64 + time_control.increment_seconds as u64 * own_moves;
+        now.saturating_sub(self.get_activity_stamp()) > max_clock_s * 1_000_000_000
+    }
+
+    /// The player to move loses on time, returns their color
+    pub fn time_out(&mut self) -> Result<Color, ApiError> {
+        if self.is_finished() {
+            return Err(ApiError::BadRequest("Game is already finished".to_string()));
+        }
+
+        let color = Color::from(self.game_state.next_to_move as usize);
+        self.game_state.winner = color.opponent_color() as u8;
+        self.game_state.termination = Some(Termination::Timeout);
+        self.timed_out = true;
+        self.pause_request = None;
+        self.pending_resignation = None;
+        self.updated_stamp = timestamp_now_nanos();
+        Ok(color)
+    }
+
+    /// Games can be aborted until both sides made their first move
+    pub fn can_abort(&self) -> bool {
+        !self.is_finished() && self.imported.is_none() && self.game_state.move_log.len() < 2
+    }
+
+    /// Ends the game without a winner, neither player wins or loses
+    pub fn abort(&mut self) -> Result<(), ApiError> {
+        if self.is_finished() {
+            return Err(ApiError::BadRequest("Game is already finished".to_string()));
+        }
+        if !self.can_abort() {
+            return Err(ApiError::BadRequest(
+                "Games can only be aborted before both sides made a move".to_string(),
+            ));
+        }
+
+        self.aborted = true;
+        self.game_state.termination = Some(Termination::Aborted);
+        self.updated_stamp = timestamp_now_nanos();
+        Ok(())
     }
 
     pub fn resign(&mut self, color: Color) -> Result<(), ApiError> {
@@ -195,40 +577,162 @@ impl Session {
         }
 
         self.game_state.winner = color.opponent_color() as u8;
-        self.game_state.resign = true;
+        self.game_state.termination = Some(Termination::Resignation);
+        self.pending_resignation = None;
+        self.updated_stamp = timestamp_now_nanos();
         Ok(())
     }
 
-    pub async fn save(&self, collection: &Collection<Session>) -> Result<(), ApiError> {
-        if let Some(id) = &self.id {
-            let filter = doc! { "_id": id };
-            let update = doc! { "$set": bson::to_bson(self)? };
-            let options = UpdateOptions::builder().upsert(true).build();
-            collection.update_one(filter, update, Some(options)).await?;
+    /// Asks to pause or resume the game, it only changes once the other player asks for the same
+    /// The AI always agrees right away, returns if the game was paused or resumed
+    pub fn request_pause(&mut self, key: &str, pause: bool) -> Result<bool, ApiError> {
+        if self.is_finished() {
+            return Err(ApiError::BadRequest("Game is already finished".to_string()));
+        }
+        if self.paused == pause {
+            let state = if pause { "paused" } else { "running" };
+            return Err(ApiError::BadRequest(format!(
+                "The game is already {}",
+                state
+            )));
+        }
+
+        let agreed = self.keys.iter().any(|other| other == "AI")
+            || self
+                .pause_request
+                .as_ref()
+                .is_some_and(|requester| requester != key);
+        if agreed {
+            self.paused = pause;
+            self.pause_request = None;
         } else {
-            let options = InsertOneOptions::builder().build();
-            collection.insert_one(self, Some(options)).await?;
+            self.pause_request = Some(key.to_string());
+        }
+        Ok(agreed)
+    }
+
+    /// Color of the player who asked to pause or resume the game
+    pub fn get_pause_requester(&self) -> Option<Color> {
+        self.pause_request
+            .as_ref()
+            .and_then(|key| self.get_color_from_key(key))
+    }
+
+    /// First step of a confirmed resignation, replaces a previous request of the player
+    pub fn request_resignation(&mut self, key: &str) -> Result<PendingResignation, ApiError> {
+        if self.is_finished() {
+            return Err(ApiError::BadRequest("Game is already finished".to_string()));
+        }
+
+        let pending = PendingResignation {
+            key: key.to_string(),
+            token: generate_user_friendly_code(12),
+            expires_stamp: timestamp_now_nanos() + RESIGN_CONFIRMATION_S * 1_000_000_000,
+        };
+        self.pending_resignation = Some(pending.clone());
+        Ok(pending)
+    }
+
+    /// Second step of a confirmed resignation, the token can only be used once
+    pub fn confirm_resignation(&mut self, key: &str, token: &str) -> Result<(), ApiError> {
+        let valid = self.pending_resignation.as_ref().is_some_and(|pending| {
+            pending.key == key
+                && pending.token == token
+                && pending.expires_stamp > timestamp_now_nanos()
+        });
+        if !valid {
+            return Err(ApiError::BadRequest(
+                "Invalid or expired confirmation token, request a new one with confirm=true"
+                    .to_string(),
+            ));
         }
+        self.pending_resignation = None;
         Ok(())
     }
 
-    pub async fn to_pgn(&self, state: &AppState) -> Result<String, ApiError> {
-        let white_player =
-            match find_user_by_key(&state.database.user_collection, &self.keys[0]).await? {
-                Some(user) => user.display_name,
-                None => "Unknown".to_string(),
-            };
+    /// When something last happened in the game, falls back to the creation for old sessions
+    pub fn get_activity_stamp(&self) -> u64 {
+        self.updated_stamp.max(self.created_stamp)
+    }
+
+    /// The result from the perspective of the given player, None while the game is running
+    pub fn get_outcome(&self, key: &str) -> Option<GameOutcome> {
+        let color = self.get_color_from_key(key)?;
+        if self.aborted {
+            None
+        } else if self.game_state.is_draw() {
+            Some(GameOutcome::Draw)
+        } else if self.game_state.winner == 2 {
+            None
+        } else if self.game_state.winner == color as u8 {
+            Some(GameOutcome::Win)
+        } else {
+            Some(GameOutcome::Loss)
+        }
+    }
+
+    /// The write runs as a tracked task, so it still completes if the request gets dropped
+    /// and shutdown can wait for it
+    pub async fn save(
+        &self,
+        storage: &Arc<dyn Storage>,
+        tasks: &TaskTracker,
+    ) -> Result<(), ApiError> {
+        let storage = storage.clone();
+        let session = self.clone();
+
+        tasks
+            .spawn(async move { storage.save_session(&session).await }.in_current_span())
+            .await
+            .map_err(|err| ApiError::ServerError(err.to_string()))?
+    }
 
-        let black_player =
-            match find_user_by_key(&state.database.user_collection, &self.keys[1]).await? {
-                Some(user) => user.display_name,
+    /// The users behind both keys, None for the AI, imported opponents and deleted users
+    pub async fn get_players(&self, storage: &dyn Storage) -> Result<[Option<User>; 2], ApiError> {
+        let users = UserDirectory::load(storage, self.get_user_keys().collect()).await?;
+        Ok(self.get_players_from(&users))
+    }
+
+    /// Like get_players, with users which were already looked up
+    pub fn get_players_from(&self, users: &UserDirectory) -> [Option<User>; 2] {
+        self.keys.clone().map(|key| users.get(&key).cloned())
+    }
+
+    /// The keys which can belong to users, without the AI and imported opponents
+    pub fn get_user_keys(&self) -> impl Iterator<Item = String> + '_ {
+        self.keys
+            .iter()
+            .filter(|key| *key != "AI" && *key != IMPORTED_KEY)
+            .cloned()
+    }
+
+    /// Display names of the players returned by get_players
+    pub fn get_names_of(&self, players: &[Option<User>; 2]) -> [String; 2] {
+        let mut names = [String::new(), String::new()];
+        for ((name, key), player) in names.iter_mut().zip(self.keys.iter()).zip(players) {
+            *name = match player {
+                Some(user) => user.display_name.clone(),
+                None if key == "AI" => "AI".to_string(),
+                None if key == IMPORTED_KEY => self
+                    .imported
+                    .as_ref()
+                    .map(|imported| imported.opponent.clone())
+                    .unwrap_or("Unknown".to_string()),
                 None => "Unknown".to_string(),
             };
+        }
+        names
+    }
 
-        let event = format!("LemonChess Online Game: '{}'", self.name);
-        let date = nanos_to_date(self.created_stamp, &UTC);
+    /// Returns the display names of the white and black player
+    pub async fn get_player_names(&self, storage: &dyn Storage) -> Result<[String; 2], ApiError> {
+        let players = self.get_players(storage).await?;
+        Ok(self.get_names_of(&players))
+    }
 
-        let result = if !self.is_finished() {
+    /// The result in PGN notation: 1-0, 0-1, 1/2-1/2 or * while still running
+    pub fn get_result_notation(&self) -> String {
+        let result = if !self.is_finished() || self.aborted {
             "*"
         } else if self.game_state.winner != 2 {
             if self.game_state.winner == 0 {
@@ -239,8 +743,118 @@ impl Session {
         } else {
             "1/2-1/2"
         };
+        result.to_string()
+    }
+
+    /// Replaces the annotation of the player at the given ply, without glyph and comment it gets removed
+    pub fn annotate(
+        &mut self,
+        key: &str,
+        ply: usize,
+        glyph: Option<String>,
+        comment: Option<String>,
+    ) -> Result<(), ApiError> {
+        let author = self.get_color_from_key(key).ok_or(ApiError::BadRequest(
+            "You're not part of this session.".to_string(),
+        ))?;
+        let ply_count = self.game_state.move_log.len();
+        if ply == 0 || ply > ply_count {
+            return Err(ApiError::BadRequest(format!(
+                "ply has to be between 1 and the {} plies of the game",
+                ply_count
+            )));
+        }
+        if let Some(glyph) = &glyph {
+            if !ANNOTATION_GLYPHS.contains(&glyph.as_str()) {
+                return Err(ApiError::BadRequest(format!(
+                    "glyph has to be one of {}",
+                    ANNOTATION_GLYPHS.join(", ")
+                )));
+            }
+        }
+
+        self.annotations
+            .retain(|annotation| annotation.ply != ply || annotation.author != author);
+        if glyph.is_some() || comment.is_some() {
+            self.annotations.push(Annotation {
+                ply,
+                author,
+                glyph,
+                comment,
+            });
+            self.annotations.sort_by_key(|annotation| annotation.ply);
+        }
+        Ok(())
+    }
+
+    pub fn get_annotations(&self, ply: usize) -> Vec<Annotation> {
+        self.annotations
+            .iter()
+            .filter(|annotation| annotation.ply == ply)
+            .cloned()
+            .collect()
+    }
+
+    /// Player annotations are always included, with a review every move also gets a %eval comment
+    /// A glyph of the players takes precedence over the one of the review
+    pub async fn to_pgn(
+        &self,
+        state: &AppState,
+        review: Option<&GameReview>,
+    ) -> Result<String, ApiError> {
+        let [white_player, black_player] = self.get_player_names(&*state.storage).await?;
 
-        let movetext = self.game_state.get_san();
+        let event = format!("LemonChess Online Game: '{}'", self.name);
+        let date = nanos_to_date(self.created_stamp, &UTC);
+        let result = self.get_result_notation();
+
+        let mut plies: Vec<PgnPly> = self
+            .game_state
+            .san_log
+            .iter()
+            .map(|san| PgnPly {
+                san,
+                glyph: None,
+                comments: Vec::new(),
+            })
+            .collect();
+        for annotation in &self.annotations {
+            if let Some(ply) = plies.get_mut(annotation.ply - 1) {
+                if ply.glyph.is_none() {
+                    ply.glyph = annotation.glyph.as_deref();
+                }
+                ply.comments.extend(annotation.comment.clone());
+            }
+        }
+        for reviewed in review
+            .map(|review| review.plies.as_slice())
+            .unwrap_or_default()
+        {
+            if let Some(ply) = plies.get_mut(reviewed.ply - 1) {
+                if ply.glyph.is_none() {
+                    ply.glyph = reviewed.annotation.as_deref();
+                }
+                ply.comments
+                    .push(format!("[%eval {:.2}]", reviewed.eval as f64 / 100.0));
+            }
+        }
+        let movetext = format_movetext(&plies);
+        let mut optional_tags = String::new();
+        if let Some(termination) = self.get_termination() {
+            optional_tags.push_str(&format!("[Termination \"{}\"]\n", termination.pgn_tag()));
+        }
+        if let Some(time_control) = self.time_control {
+            optional_tags.push_str(&format!(
+                "[TimeControl \"{}+{}\"]\n",
+                time_control.initial_seconds, time_control.increment_seconds
+            ));
+        }
+        if let Some(opening) = Opening::classify(&self.game_state.move_log) {
+            optional_tags.push_str(&format!(
+                "[ECO \"{}\"]\n[Opening \"{}\"]\n",
+                opening.eco, opening.name
+            ));
+        }
 
         let pgn = format!(
             r#"[Event "{}"]
@@ -249,78 +863,134 @@ impl Session {
 [White "{}"]
 [Black "{}"]
 [Result "{}"]
-[Annotator "chess.lemon.industries"]
+{}[Annotator "chess.lemon.industries"]
 {}"#,
-            event, date, white_player, black_player, result, movetext
+            event, date, white_player, black_player, result, optional_tags, movetext
         );
 
         Ok(pgn)
     }
-}
 
-pub async fn find_session_by_keys(
-    collection: &Collection<Session>,
-    keys: Vec<String>,
-) -> Result<Option<Session>, ApiError> {
-    let filter = doc! { "keys": { "$all": keys.clone() }};
-    let session = collection.find_one(filter, None).await?;
-    Ok(session)
-}
+    /// Reviews the whole game, this is expensive since every position gets evaluated
+    /// The accuracy of both players is stored the first time a finished game is reviewed
+    /// Only that field is written, the session itself is read without a lock and may be outdated
+    pub async fn get_review(&mut self, state: &AppState) -> Result<GameReview, ApiError> {
+        // Reviewing searches every position of the game, which would block the async runtime
+        let game_state = self.game_state.clone();
+        let review = tokio::task::spawn_blocking(move || GameReview::new(&game_state))
+            .await
+            .map_err(|error| ApiError::ServerError(error.to_string()))??;
+        if self.is_finished() && self.accuracy.is_none() {
+            if let (Some(id), Some(white), Some(black)) = (
+                self.id,
+                review.get_accuracy(Color::WHITE),
+                review.get_accuracy(Color::BLACK),
+            ) {
+                let accuracy = [white, black];
+                state
+                    .storage
+                    .set_session_accuracy(&id.to_hex(), &accuracy)
+                    .await?;
+                self.accuracy = Some(accuracy);
+            }
+        }
+        Ok(review)
+    }
 
-pub async fn find_active_session_by_keys(
-    collection: &Collection<Session>,
-    keys: Vec<String>,
-) -> Result<Option<Session>, ApiError> {
-    let filter =
-        doc! { "keys": { "$all": keys.clone() }, "game_state.winner": 2, "game_state.draw": false};
-    let session = collection.find_one(filter, None).await?;
-    Ok(session)
+    pub async fn get_report(
+        &self,
+        storage: &dyn Storage,
+        review: GameReview,
+    ) -> Result<GameReport, ApiError> {
+        let [white_player, black_player] = self.get_player_names(storage).await?;
+
+        Ok(GameReport {
+            title: format!("LemonChess Online Game: '{}'", self.name),
+            white_player,
+            black_player,
+            date: nanos_to_date(self.created_stamp, &UTC),
+            result: self.get_result_notation(),
+            review,
+        })
+    }
 }
 
-pub async fn find_sessions_by_key(
-    collection: &Collection<Session>,
-    key: &str,
-) -> Result<Vec<Session>, ApiError> {
-    let filter = doc! { "keys": key};
-    let cursor = collection.find(filter, None).await?;
-    let sessions: Vec<Session> = cursor.try_collect().await?;
-    Ok(sessions)
+/// A move of the PGN movetext with everything attached to it
+struct PgnPly<'a> {
+    san: &'a str,
+    glyph: Option<&'a str>,
+    comments: Vec<String>,
 }
 
-pub async fn find_sessions_by_key_and_finished(
-    collection: &Collection<Session>,
-    key: &str,
-    finished: bool,
-) -> Result<Vec<Session>, ApiError> {
-    let filter = doc! { "keys": key, "finished": finished};
-    let cursor = collection.find(filter, None).await?;
-    let sessions: Vec<Session> = cursor.try_collect().await?;
-    Ok(sessions)
+/// Black's moves get their own move number if a comment stands between them and white's move
+fn format_movetext(plies: &[PgnPly]) -> String {
+    let mut tokens = Vec::new();
+    for (index, ply) in plies.iter().enumerate() {
+        let move_number = index / 2 + 1;
+        if index.is_multiple_of(2) {
+            tokens.push(format!("{}.", move_number));
+        } else if !plies[index - 1].comments.is_empty() {
+            tokens.push(format!("{}...", move_number));
+        }
+        tokens.push(format!("{}{}", ply.san, ply.glyph.unwrap_or_default()));
+        for comment in &ply.comments {
+            tokens.push(format!("{{ {} }}", comment));
+        }
+    }
+    tokens.join(" ")
 }
 
+/// Without a sort, archived sessions are listed after the running and recently finished ones
 pub async fn find_sessions_by_key_with_pagination(
     state: &AppState,
     key: String,
+    filter: &SessionFilter,
     page: u32,
     page_size: u32,
 ) -> Result<SessionList, ApiError> {
-    let collection = &state.database.session_collection;
+    let offset = Pagination::get_offset(page, page_size) as u64;
+    let (sessions, total) = state
+        .storage
+        .find_sessions_by_key(&key, filter, offset, page_size as u64)
+        .await?;
+
+    let sessions_info = SessionInfo::from_sessions(state, sessions, key).await?;
+    let results = sessions_info.len() as u32;
 
+    Ok(SessionList {
+        sessions: sessions_info,
+        pagination: Pagination::generate(results, total as u32, page, page_size),
+    })
+}
+
+/// Sessions the user tagged with the given tag and/or marked as favorite, archived ones included
+/// Ordered like the marks, the most recently marked sessions first
+pub async fn find_marked_sessions_with_pagination(
+    state: &AppState,
+    key: String,
+    tag: Option<&str>,
+    favorite_only: bool,
+    page: u32,
+    page_size: u32,
+) -> Result<SessionList, ApiError> {
     let offset = Pagination::get_offset(page, page_size);
-    let find_options = FindOptions::builder()
-        .skip(offset as u64)
-        .limit(page_size as i64)
-        .build();
-    let filter = doc! { "keys": &key };
-
-    let total = collection.count_documents(filter.clone(), None).await? as u32;
-
-    let cursor = collection.find(filter, find_options).await?;
-    let sessions: Vec<Session> = cursor.try_collect().await?;
-    let sessions_info: Vec<SessionInfo> = stream::iter(sessions)
-        .then(|session| SessionInfo::from_session(state, session, key.clone()))
-        .try_collect()
-        .await?;
+    let marks: Vec<SessionMark> = state
+        .storage
+        .find_session_marks_by_key(&key)
+        .await?
+        .into_iter()
+        .filter(|mark| tag.is_none_or(|tag| mark.has_tag(tag)))
+        .filter(|mark| !favorite_only || mark.favorite)
+        .collect();
+    let total = marks.len() as u32;
+
+    let session_ids: Vec<String> = marks
+        .into_iter()
+        .skip(offset as usize)
+        .take(page_size as usize)
+        .map(|mark| mark.session_id)
+        .collect();
+    let sessions_info = find_sessions_info_by_ids(state, &session_ids, key).await?;
     let results = sessions_info.len() as u32;
 
     Ok(SessionList {
@@ -329,12 +999,148 @@ pub async fn find_sessions_by_key_with_pagination(
     })
 }
 
-pub async fn find_session_by_id(
-    collection: &Collection<Session>,
+/// Running and archived sessions in the order of the ids, unknown ids are skipped
+/// The sessions are loaded with one query and their players with another
+pub async fn find_sessions_info_by_ids(
+    state: &AppState,
+    ids: &[String],
+    key: String,
+) -> Result<Vec<SessionInfo>, ApiError> {
+    let mut found = state.storage.find_sessions_by_ids(ids).await?;
+    let mut sessions = Vec::with_capacity(found.len());
+    for id in ids {
+        let id = ObjectId::parse_str(id)?;
+        if let Some(index) = found.iter().position(|session| session.id == Some(id)) {
+            sessions.push(found.swap_remove(index));
+        }
+    }
+    SessionInfo::from_sessions(state, sessions, key).await
+}
+
+/// The lock which has to be held while changing and saving the session
+pub fn get_lock_key(session_id: &str) -> String {
+    format!("session:{}", session_id)
+}
+
+/// Also looks in the archive, for read access to old games
+pub async fn find_session_or_archived_by_id(
+    storage: &dyn Storage,
     id: &str,
 ) -> Result<Option<Session>, ApiError> {
-    let oid = ObjectId::parse_str(id)?;
-    let filter = doc! { "_id": oid };
-    let session = collection.find_one(Some(filter), None).await?;
-    Ok(session)
+    if let Some(session) = storage.find_session_by_id(id).await? {
+        return Ok(Some(session));
+    }
+    storage.find_archived_session_by_id(id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson;
+
+    use super::*;
+
+    #[test]
+    fn test_stored_game_state() {
+        let mut game_state = GameState::new().unwrap();
+        game_state.make_move(Square(12), Square(28)).unwrap();
+        let fen = game_state.to_fen();
+        let session = Session::new("Test".to_string(), ["a".into(), "b".into()], game_state);
+
+        let document = bson::to_document(&session).unwrap();
+        let stored = document.get_document("game_state").unwrap();
+        assert!(stored.get_binary_generic("data").is_ok());
+        assert_eq!(stored.get_i32("winner").unwrap(), 2);
+        let decoded: Session = bson::from_document(document).unwrap();
+        assert_eq!(decoded.game_state.to_fen(), fen);
+
+        // Sessions from before the binary encoding are still readable
+        let mut legacy = bson::to_document(&session).unwrap();
+        legacy.insert(
+            "game_state",
+            bson::to_document(&session.game_state).unwrap(),
+        );
+        let decoded: Session = bson::from_document(legacy).unwrap();
+        assert_eq!(decoded.game_state.to_fen(), fen);
+    }
+
+    #[test]
+    fn test_annotations() {
+        let mut game_state = GameState::new().unwrap();
+        game_state.make_move(Square(12), Square(28)).unwrap();
+        game_state.make_move(Square(52), Square(36)).unwrap();
+        game_state.make_move(Square(6), Square(21)).unwrap();
+        let mut session = Session::new("Test".to_string(), ["a".into(), "b".into()], game_state);
+
+        assert!(session.annotate("c", 1, None, None).is_err());
+        assert!(session.annotate("a", 0, None, None).is_err());
+        assert!(session.annotate("a", 4, None, None).is_err());
+        assert!(session
+            .annotate("a", 1, Some("?!!".to_string()), None)
+            .is_err());
+
+        session
+            .annotate("a", 1, Some("!".to_string()), Some("Best by test".into()))
+            .unwrap();
+        session
+            .annotate("b", 1, Some("?!".to_string()), None)
+            .unwrap();
+        assert_eq!(session.get_annotations(1).len(), 2);
+        session.annotate("b", 1, None, None).unwrap();
+        assert_eq!(session.get_annotations(1).len(), 1);
+
+        let plies: Vec<PgnPly> = session
+            .game_state
+            .san_log
+            .iter()
+            .enumerate()
+            .map(|(index, san)| {
+                let annotation = session.get_annotations(index + 1).pop();
+                PgnPly {
+                    san,
+                    glyph: None,
+                    comments: annotation.and_then(|a| a.comment).into_iter().collect(),
+                }
+            })
+            .collect();
+        assert_eq!(
+            format_movetext(&plies),
+            format!(
+                "1. {} {{ Best by test }} 1... {} 2. {}",
+                plies[0].san, plies[1].san, plies[2].san
+            )
+        );
+
+        let plain: Vec<PgnPly> = session
+            .game_state
+            .san_log
+            .iter()
+            .map(|san| PgnPly {
+                san,
+                glyph: None,
+                comments: Vec::new(),
+            })
+            .collect();
+        assert_eq!(format_movetext(&plain), session.game_state.get_san());
+    }
+
+    #[test]
+    fn test_legacy_game_state_in_check() {
+        let state = GameState::from_fen("4k3/8/8/8/8/8/4r3/4K3 w - - 0 1").unwrap();
+        assert_ne!(state.checkers.0, 0);
+        let session = Session::new(
+            "Legacy".to_string(),
+            ["lemon".to_string(), "lime".to_string()],
+            state.clone(),
+        );
+
+        // Sessions saved before the binary encoding have the game state fields but no checkers
+        let mut legacy_state = bson::to_document(&state).unwrap();
+        legacy_state.remove("checkers");
+        let mut document = bson::to_document(&session).unwrap();
+        document.insert("game_state", legacy_state);
+
+        let decoded: Session = bson::from_document(document).unwrap();
+        assert_eq!(decoded.game_state.checkers.0, state.checkers.0);
+        assert_eq!(decoded.game_state.to_bytes(), state.to_bytes());
+    }
 }
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{error::ApiError, utils::time_operations::timestamp_now_nanos};
+
+/// A user can't put more tags than this on one session
+pub const MAX_TAGS: usize = 10;
+
+/// Tags and favorite flag one user gave a session, kept apart from the session shared by both players
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SessionMark {
+    /// Key of the user who marked the session
+    pub key: String,
+    pub session_id: String,
+    /// Lowercase and unique
+    pub tags: Vec<String>,
+    pub favorite: bool,
+    pub updated_stamp: u64,
+}
+
+impl SessionMark {
+    pub fn new(key: &str, session_id: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            session_id: session_id.to_string(),
+            tags: Vec::new(),
+            favorite: false,
+            updated_stamp: timestamp_now_nanos(),
+        }
+    }
+
+    /// Replaces the tags, duplicates are dropped
+    pub fn set_tags(&mut self, tags: Vec<String>) -> Result<(), ApiError> {
+        let mut unique: Vec<String> = Vec::new();
+        for tag in tags {
+            if !unique.contains(&tag) {
+                unique.push(tag);
+            }
+        }
+        if unique.len() > MAX_TAGS {
+            return Err(ApiError::BadRequest(format!(
+                "A session can't have more than {} tags",
+                MAX_TAGS
+            )));
+        }
+        self.tags = unique;
+        Ok(())
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|own| own == tag)
+    }
+
+    /// Marks without tags that aren't favorites don't need to be stored
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && !self.favorite
+    }
+}
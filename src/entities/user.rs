@@ -1,20 +1,38 @@
 use std::collections::HashMap;
 
-use futures::future::try_join_all;
-use mongodb::{
-    bson::{self, doc},
-    options::UpdateOptions,
-    Collection,
-};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
-    error::ApiError, models::enums::PermissionLevel, utils::time_operations::timestamp_now_nanos,
+    error::ApiError,
+    models::{
+        enums::{KeyScope, PermissionLevel, Platform},
+        response_models::Pagination,
+        user_models::{Title, UsageSummary, UserAdminInfo, UserList},
+    },
+    storage::Storage,
+    utils::time_operations::timestamp_now_nanos,
+    AppState,
 };
 
-#[derive(Serialize, Deserialize)]
+/// A user can't have more secondary keys than this
+pub const MAX_SECONDARY_KEYS: usize = 10;
+
+/// Users who made a request within this time count as online
+const ONLINE_WINDOW_NANOS: u64 = 5 * 60 * 1_000_000_000;
+
+/// A user can't hold more titles than this
+pub const MAX_TITLES: usize = 20;
+
+/// Users loaded at once while adding up the usage of everyone
+const USAGE_SUMMARY_BATCH: u64 = 100;
+
+/// API keys of bot accounts start with this, so they can be recognized before the user is loaded
+pub const BOT_KEY_PREFIX: &str = "bot_";
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "UserDocument")]
 pub struct User {
     pub key: String,
     pub name: String,
@@ -26,38 +44,118 @@ pub struct User {
     #[serde(default)]
     pub endpoint_usage: HashMap<String, u64>,
     #[serde(default)]
-    /// If user was added through a negotiator via discord, this is the discord user id
-    pub discord_id: String,
+    /// The user ids on chat platforms the user is linked with, unique per platform and namespace
+    pub platform_links: HashMap<Platform, String>,
+    #[serde(default)]
+    /// The tenant this user belongs to, users registered by a negotiator inherit its namespace
+    /// Empty for the default namespace
+    pub namespace: String,
+    #[serde(default)]
+    /// Banned users can't use their API key anymore
+    pub banned: bool,
+    #[serde(default)]
+    /// Additional keys with limited scope, e.g. for bots
+    pub secondary_keys: Vec<SecondaryKey>,
+    #[serde(default)]
+    /// Engine accounts registered through POST /user/bot, they can only join rooms which allow bots
+    pub bot: bool,
+    #[serde(default)]
+    /// Name of the user who registered the bot account
+    pub operator: Option<String>,
+    #[serde(default)]
+    /// Titles and badges awarded by admins, oldest first
+    pub titles: Vec<Title>,
+}
+
+/// Users as they are stored, documents from before platform links existed only have a discord id
+#[derive(Deserialize)]
+struct UserDocument {
+    key: String,
+    name: String,
+    display_name: String,
+    created_stamp: u64,
+    permission: PermissionLevel,
+    #[serde(default)]
+    last_access_stamp: u64,
     #[serde(default)]
-    pub rate_limiting: HashMap<String, u64>,
+    endpoint_usage: HashMap<String, u64>,
+    #[serde(default)]
+    discord_id: String,
+    #[serde(default)]
+    platform_links: HashMap<Platform, String>,
+    #[serde(default)]
+    namespace: String,
+    #[serde(default)]
+    banned: bool,
+    #[serde(default)]
+    secondary_keys: Vec<SecondaryKey>,
+    #[serde(default)]
+    bot: bool,
+    #[serde(default)]
+    operator: Option<String>,
+    #[serde(default)]
+    titles: Vec<Title>,
+}
+
+impl From<UserDocument> for User {
+    fn from(document: UserDocument) -> Self {
+        let mut platform_links = document.platform_links;
+        if !document.discord_id.is_empty() {
+            platform_links
+                .entry(Platform::DISCORD)
+                .or_insert(document.discord_id);
+        }
+
+        Self {
+            key: document.key,
+            name: document.name,
+            display_name: document.display_name,
+            created_stamp: document.created_stamp,
+            permission: document.permission,
+            last_access_stamp: document.last_access_stamp,
+            endpoint_usage: document.endpoint_usage,
+            platform_links,
+            namespace: document.namespace,
+            banned: document.banned,
+            secondary_keys: document.secondary_keys,
+            bot: document.bot,
+            operator: document.operator,
+            titles: document.titles,
+        }
+    }
+}
+
+/// An additional API key of a user, requests made with it act as the user within its scope
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SecondaryKey {
+    pub key: String,
+    /// Unique per user, used to tell the keys apart
+    pub name: String,
+    pub scope: KeyScope,
+    pub created_stamp: u64,
 }
 
 impl User {
-    /// Creates a new discord user
-    pub async fn new_from_discord(
-        collection: &Collection<User>,
+    /// Creates a new user linked with the given chat platform user id
+    pub async fn new_from_platform(
+        storage: &dyn Storage,
+        namespace: &str,
+        platform: Platform,
         name: &str,
         display_name: &str,
         id: &str,
     ) -> Result<Self, ApiError> {
-        if find_user_by_discord_id(collection, id).await?.is_some() {
-            return Err(ApiError::BadRequest(
-                "User with the given user id already exists.".to_string(),
-            ));
-        };
-
-        // Name already exists so it generates a random number added behind the name
-        let user_name = if find_user_by_name(collection, &name.to_lowercase())
+        if storage
+            .find_user_by_platform_id(namespace, platform, id)
             .await?
             .is_some()
         {
-            let mut rng = rand::thread_rng();
-            let random_number = rng.gen_range(100000000..1000000000);
-            format!("{}-{}", name, random_number).to_lowercase()
-        } else {
-            name.to_string().to_lowercase()
+            return Err(ApiError::BadRequest(
+                "User with the given user id already exists.".to_string(),
+            ));
         };
 
+        let user_name = get_free_name(storage, name).await?;
         let key = Uuid::new_v4().simple().to_string();
         let current_stamp = timestamp_now_nanos();
 
@@ -69,87 +167,296 @@ impl User {
             permission: PermissionLevel::User,
             last_access_stamp: current_stamp,
             endpoint_usage: HashMap::new(),
-            discord_id: id.to_string(),
-            rate_limiting: HashMap::new(),
+            platform_links: HashMap::from([(platform, id.to_string())]),
+            namespace: namespace.to_string(),
+            banned: false,
+            secondary_keys: Vec::new(),
+            bot: false,
+            operator: None,
+            titles: Vec::new(),
         };
 
-        user.save(collection).await?;
+        storage.insert_user(&user).await?;
 
         Ok(user)
     }
 
-    pub async fn rate_limit(
-        &mut self,
-        collection: &Collection<User>,
-        id: &str,
-        cooldown_s: u64,
-    ) -> Result<(), ApiError> {
+    /// Creates a bot account in the namespace of its operator
+    pub async fn new_bot(
+        storage: &dyn Storage,
+        operator: &User,
+        name: &str,
+        display_name: &str,
+    ) -> Result<Self, ApiError> {
+        if operator.bot {
+            return Err(ApiError::BadRequest(
+                "Bots can't register other bots.".to_string(),
+            ));
+        }
+
+        let user_name = get_free_name(storage, name).await?;
+        let key = format!("{}{}", BOT_KEY_PREFIX, Uuid::new_v4().simple());
         let current_stamp = timestamp_now_nanos();
-        let cooldown = cooldown_s * 1000000000;
-        match self.rate_limiting.get(id) {
-            Some(&last_access_stamp) if current_stamp < last_access_stamp + cooldown => Err(
-                ApiError::RateLimited(last_access_stamp + cooldown - current_stamp),
-            ),
-            _ => {
-                self.rate_limiting.insert(id.to_string(), current_stamp);
-                self.save(collection).await?;
-                Ok(())
-            }
+
+        let user = Self {
+            key,
+            name: user_name,
+            display_name: display_name.to_string(),
+            created_stamp: current_stamp,
+            permission: PermissionLevel::User,
+            last_access_stamp: current_stamp,
+            endpoint_usage: HashMap::new(),
+            platform_links: HashMap::new(),
+            namespace: operator.namespace.clone(),
+            banned: false,
+            secondary_keys: Vec::new(),
+            bot: true,
+            operator: Some(operator.name.clone()),
+            titles: Vec::new(),
+        };
+
+        storage.insert_user(&user).await?;
+
+        Ok(user)
+    }
+
+    /// Writes the whole user, changes of single fields go through the targeted storage updates instead
+    pub async fn save(&self, storage: &dyn Storage) -> Result<(), ApiError> {
+        storage.save_user(self).await
+    }
+
+    pub fn rename(&mut self, name: Option<&str>, display_name: Option<&str>) {
+        if let Some(name) = name {
+            self.name = name.to_string();
+        }
+        if let Some(display_name) = display_name {
+            self.display_name = display_name.to_string();
         }
     }
 
-    pub async fn save(&self, collection: &Collection<User>) -> Result<(), ApiError> {
-        let filter = doc! { "key": &self.key };
-        let update = doc! { "$set": bson::to_bson(self)? };
-        let options = UpdateOptions::builder().upsert(true).build();
+    fn check_new_secondary_key(&self, name: &str) -> Result<(), ApiError> {
+        if self.secondary_keys.len() >= MAX_SECONDARY_KEYS {
+            return Err(ApiError::BadRequest(format!(
+                "Maximum key limit of {} reached.",
+                MAX_SECONDARY_KEYS
+            )));
+        }
+        if self.secondary_keys.iter().any(|key| key.name == name) {
+            return Err(ApiError::Conflict(
+                "A key with this name already exists.".to_string(),
+            ));
+        }
+        Ok(())
+    }
 
-        collection.update_one(filter, update, Some(options)).await?;
+    /// Generates a key the user could add, it isn't added yet (see Storage::push_secondary_key)
+    pub fn new_secondary_key(
+        &self,
+        name: String,
+        scope: KeyScope,
+    ) -> Result<SecondaryKey, ApiError> {
+        self.check_new_secondary_key(&name)?;
+        Ok(SecondaryKey {
+            key: Uuid::new_v4().simple().to_string(),
+            name,
+            scope,
+            created_stamp: timestamp_now_nanos(),
+        })
+    }
+
+    pub fn add_secondary_key(&mut self, secondary_key: SecondaryKey) -> Result<(), ApiError> {
+        self.check_new_secondary_key(&secondary_key.name)?;
+        self.secondary_keys.push(secondary_key);
+        Ok(())
+    }
+
+    pub fn remove_secondary_key(&mut self, name: &str) -> Result<SecondaryKey, ApiError> {
+        let index = self
+            .secondary_keys
+            .iter()
+            .position(|key| key.name == name)
+            .ok_or(ApiError::NotFound("Key not found".to_string()))?;
+        Ok(self.secondary_keys.remove(index))
+    }
+
+    fn check_new_title(&self, name: &str) -> Result<(), ApiError> {
+        if self.titles.iter().any(|title| title.name == name) {
+            return Err(ApiError::Conflict(format!(
+                "The user already holds the title {}",
+                name
+            )));
+        }
+        if self.titles.len() >= MAX_TITLES {
+            return Err(ApiError::BadRequest(format!(
+                "A user can't hold more than {} titles",
+                MAX_TITLES
+            )));
+        }
         Ok(())
     }
 
-    pub fn use_endpoint(&mut self, method: &str, path: &str) {
+    /// A title the user could be awarded, it isn't added yet (see Storage::push_user_title)
+    pub fn new_title(&self, name: &str) -> Result<Title, ApiError> {
+        self.check_new_title(name)?;
+        Ok(Title {
+            name: name.to_string(),
+            awarded_stamp: timestamp_now_nanos(),
+        })
+    }
+
+    pub fn award_title(&mut self, title: Title) -> Result<(), ApiError> {
+        self.check_new_title(&title.name)?;
+        self.titles.push(title);
+        Ok(())
+    }
+
+    /// Removes the given title or all titles if none is given, returns how many were removed
+    pub fn reset_titles(&mut self, name: Option<&str>) -> Result<usize, ApiError> {
+        let Some(name) = name else {
+            return Ok(std::mem::take(&mut self.titles).len());
+        };
+
+        let index = self
+            .titles
+            .iter()
+            .position(|title| title.name == name)
+            .ok_or(ApiError::NotFound(format!(
+                "The user doesn't hold the title {}",
+                name
+            )))?;
+        self.titles.remove(index);
+        Ok(1)
+    }
+
+    /// None for the main key, which can do everything
+    pub fn get_key_scope(&self, key: &str) -> Option<KeyScope> {
+        self.secondary_keys
+            .iter()
+            .find(|secondary_key| secondary_key.key == key)
+            .map(|secondary_key| secondary_key.scope)
+    }
+
+    pub fn is_online(&self) -> bool {
+        timestamp_now_nanos().saturating_sub(self.last_access_stamp) < ONLINE_WINDOW_NANOS
+    }
+
+    /// Returns the name the use is counted under in the endpoint usage
+    pub fn use_endpoint(&mut self, method: &str, path: &str) -> String {
         self.last_access_stamp = timestamp_now_nanos();
-        *self
-            .endpoint_usage
-            .entry(format!("{method} {path}"))
-            .or_insert(0) += 1;
+        let endpoint = format!("{method} {path}");
+        *self.endpoint_usage.entry(endpoint.clone()).or_insert(0) += 1;
+        endpoint
     }
 }
 
-pub async fn find_user_by_key(
-    collection: &Collection<User>,
-    key: &str,
-) -> Result<Option<User>, ApiError> {
-    let filter = doc! { "key": key };
-    let user = collection.find_one(Some(filter), None).await?;
-    Ok(user)
+/// Held while a unique name is checked and taken, by registrations and renames alike
+pub fn get_name_lock_key(name: &str) -> String {
+    format!("user_name:{}", name.to_lowercase())
 }
 
-pub async fn find_users_by_keys(
-    collection: &Collection<User>,
-    keys: Vec<&str>,
-) -> Result<Vec<Option<User>>, ApiError> {
-    let futures = keys
-        .into_iter()
-        .map(|key| find_user_by_key(collection, key))
-        .collect::<Vec<_>>();
-    try_join_all(futures).await
+/// The lowercase name, if it already exists a random number is added behind it
+async fn get_free_name(storage: &dyn Storage, name: &str) -> Result<String, ApiError> {
+    if storage
+        .find_user_by_name(&name.to_lowercase())
+        .await?
+        .is_none()
+    {
+        return Ok(name.to_lowercase());
+    }
+
+    let mut rng = rand::thread_rng();
+    let random_number = rng.gen_range(100000000..1000000000);
+    Ok(format!("{}-{}", name, random_number).to_lowercase())
 }
 
-pub async fn find_user_by_name(
-    collection: &Collection<User>,
-    name: &str,
-) -> Result<Option<User>, ApiError> {
-    let filter = doc! { "name": name.to_lowercase() };
-    let user = collection.find_one(Some(filter), None).await?;
-    Ok(user)
+/// The users behind many keys, looked up with one query while building lists
+/// Only lives as long as the request, so changed display names show up right away
+#[derive(Default)]
+pub struct UserDirectory {
+    users: HashMap<String, User>,
 }
 
-pub async fn find_user_by_discord_id(
-    collection: &Collection<User>,
-    discord_id: &str,
-) -> Result<Option<User>, ApiError> {
-    let filter = doc! { "discord_id": discord_id };
-    let user = collection.find_one(Some(filter), None).await?;
-    Ok(user)
+impl UserDirectory {
+    pub async fn load(storage: &dyn Storage, mut keys: Vec<String>) -> Result<Self, ApiError> {
+        keys.sort();
+        keys.dedup();
+        if keys.is_empty() {
+            return Ok(Self::default());
+        }
+        let users = storage
+            .find_users_by_keys(&keys)
+            .await?
+            .into_iter()
+            .map(|user| (user.key.clone(), user))
+            .collect();
+        Ok(Self { users })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&User> {
+        self.users.get(key)
+    }
+
+    /// Unknown for keys of deleted users
+    pub fn get_display_name(&self, key: &str) -> String {
+        self.get(key)
+            .map(|user| user.display_name.clone())
+            .unwrap_or("Unknown".to_string())
+    }
+}
+
+/// Without a namespace, users of all namespaces are returned
+pub async fn find_users_with_pagination(
+    state: &AppState,
+    namespace: Option<&str>,
+    page: u32,
+    page_size: u32,
+) -> Result<UserList, ApiError> {
+    let offset = Pagination::get_offset(page, page_size) as u64;
+    let (users, total) = state
+        .storage
+        .find_users(namespace, offset, page_size as u64)
+        .await?;
+
+    let users_info: Vec<UserAdminInfo> = users.into_iter().map(UserAdminInfo::from).collect();
+    let results = users_info.len() as u32;
+
+    Ok(UserList {
+        users: users_info,
+        pagination: Pagination::generate(results, total as u32, page, page_size),
+    })
+}
+
+/// Adds up the usage of all users, without a namespace over all namespaces
+pub async fn get_usage_summary(
+    storage: &dyn Storage,
+    namespace: Option<&str>,
+) -> Result<UsageSummary, ApiError> {
+    let mut summary = UsageSummary {
+        users: 0,
+        online_users: 0,
+        total_requests: 0,
+        endpoint_usage: HashMap::new(),
+        panics: 0,
+    };
+
+    loop {
+        let (users, _) = storage
+            .find_users(namespace, summary.users, USAGE_SUMMARY_BATCH)
+            .await?;
+        for user in &users {
+            summary.users += 1;
+            if user.is_online() {
+                summary.online_users += 1;
+            }
+            for (endpoint, count) in &user.endpoint_usage {
+                summary.total_requests += count;
+                *summary.endpoint_usage.entry(endpoint.clone()).or_insert(0) += count;
+            }
+        }
+        if (users.len() as u64) < USAGE_SUMMARY_BATCH {
+            break;
+        }
+    }
+
+    Ok(summary)
 }
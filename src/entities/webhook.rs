@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    entities::{session::Session, user::User},
+    error::ApiError,
+    models::{
+        enums::Platform,
+        session_models::SessionResult,
+        webhook_models::{GameFinishedEvent, WebhookInfo},
+    },
+    utils::{http::post_json, signing::sign_with, time_operations::timestamp_now_nanos},
+    AppState,
+};
+
+/// Header carrying the hex encoded HMAC-SHA256 signature of the body, made with the secret of the webhook
+pub const SIGNATURE_HEADER: &str = "X-Lemon-Signature";
+
+/// How often a call is tried before the event is dropped for that webhook
+const MAX_DELIVERY_ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry, doubled for every further one
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// A callback URL of a negotiator, called when a game of a Discord user of its namespace finished
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    /// API key of the negotiator who registered it, a negotiator has at most one webhook
+    pub key: String,
+    pub namespace: String,
+    pub url: String,
+    /// Shared with the negotiator once, so it can verify that calls come from us
+    pub secret: String,
+    pub created_stamp: u64,
+}
+
+impl Webhook {
+    pub fn new(negotiator: &User, url: String) -> Self {
+        let secret = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        Self {
+            key: negotiator.key.clone(),
+            namespace: negotiator.namespace.clone(),
+            url,
+            secret,
+            created_stamp: timestamp_now_nanos(),
+        }
+    }
+
+    /// The secret is only included right after the webhook was registered
+    pub fn to_info(&self, include_secret: bool) -> WebhookInfo {
+        WebhookInfo {
+            url: self.url.clone(),
+            namespace: self.namespace.clone(),
+            created_stamp: self.created_stamp,
+            secret: include_secret.then(|| self.secret.clone()),
+        }
+    }
+}
+
+/// Calls the webhooks of the namespaces of the players who are linked with Discord
+/// Runs in the background after the game finished, delivery is best-effort:
+/// failed calls are retried a few times and then only logged, nothing is persisted for later
+pub async fn deliver_game_finished(state: AppState, session: Session) {
+    if let Err(error) = try_deliver_game_finished(&state, &session).await {
+        tracing::error!(%error, "Failed to deliver game result webhooks");
+    }
+}
+
+async fn try_deliver_game_finished(state: &AppState, session: &Session) -> Result<(), ApiError> {
+    let players = session.get_players(&*state.storage).await?;
+    let mut namespaces: Vec<&str> = players
+        .iter()
+        .flatten()
+        .filter(|user| user.platform_links.contains_key(&Platform::DISCORD))
+        .map(|user| user.namespace.as_str())
+        .collect();
+    namespaces.dedup();
+    if namespaces.is_empty() {
+        return Ok(());
+    }
+
+    let result = SessionResult::from_session(state, session).await?;
+    for namespace in namespaces {
+        let webhooks = state.storage.find_webhooks_by_namespace(namespace).await?;
+        if webhooks.is_empty() {
+            continue;
+        }
+
+        // Negotiators only learn the Discord ids of users of their own namespace
+        let [white_discord_id, black_discord_id] = players.clone().map(|player| {
+            player
+                .filter(|user| user.namespace == namespace)
+                .and_then(|user| user.platform_links.get(&Platform::DISCORD).cloned())
+        });
+        let event = GameFinishedEvent {
+            white_discord_id,
+            black_discord_id,
+            result: result.clone(),
+        };
+        let body = serde_json::to_string(&event)
+            .map_err(|err| ApiError::SerializationError(err.to_string()))?;
+
+        for webhook in webhooks {
+            let signature = sign_with(&webhook.secret, &body)?;
+            let headers = [(SIGNATURE_HEADER, signature.as_str())];
+            call_webhook(&webhook.url, &body, &headers).await;
+        }
+    }
+    Ok(())
+}
+
+/// Calls a webhook, retrying with a growing delay until it succeeds or the attempts are used up
+async fn call_webhook(url: &str, body: &str, headers: &[(&str, &str)]) {
+    let mut delay = RETRY_DELAY;
+    for attempt in 1..=MAX_DELIVERY_ATTEMPTS {
+        let Err(error) = post_json(url, body, headers).await else {
+            return;
+        };
+        tracing::warn!(url, attempt, %error, "Failed to call the webhook");
+        if attempt < MAX_DELIVERY_ATTEMPTS {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+}
@@ -1,19 +1,28 @@
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
+    Json,
 };
 use std::fmt;
 
-use crate::game::error::GameError;
+use crate::{
+    error_reporting,
+    game::{chess_board::IllegalMoveReason, error::GameError},
+    models::move_models::IllegalMoveResponse,
+};
 
 #[derive(Debug)]
 pub enum ApiError {
     AuthorizationError(String),
     BadRequest(String),
+    Conflict(String),
     DatabaseError(String),
+    HeadersTooLarge(String),
+    IllegalMove(IllegalMoveReason),
     NoPermission(String),
     NotFound(String),
     ParseError(String),
+    PayloadTooLarge(String),
     RateLimited(u64),
     SerializationError(String),
     ServerError(String),
@@ -31,12 +40,24 @@ impl From<mongodb::error::Error> for ApiError {
     }
 }
 
+impl From<rusqlite::Error> for ApiError {
+    fn from(error: rusqlite::Error) -> Self {
+        ApiError::DatabaseError(error.to_string())
+    }
+}
+
 impl From<mongodb::bson::ser::Error> for ApiError {
     fn from(error: mongodb::bson::ser::Error) -> Self {
         ApiError::SerializationError(error.to_string())
     }
 }
 
+impl From<mongodb::bson::de::Error> for ApiError {
+    fn from(error: mongodb::bson::de::Error) -> Self {
+        ApiError::SerializationError(error.to_string())
+    }
+}
+
 impl From<mongodb::bson::oid::Error> for ApiError {
     fn from(error: mongodb::bson::oid::Error) -> Self {
         ApiError::SerializationError(error.to_string())
@@ -75,6 +96,7 @@ impl From<GameError> for ApiError {
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
+        error_reporting::capture(&self);
         let (status, error_message) = match self {
             ApiError::DatabaseError(message) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -89,9 +111,21 @@ impl IntoResponse for ApiError {
                 format!("An authorization error occured: {}", message),
             ),
             ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            ApiError::Conflict(message) => (StatusCode::CONFLICT, message),
+            ApiError::IllegalMove(reason) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(IllegalMoveResponse::new(reason)),
+                )
+                    .into_response()
+            }
             ApiError::NoPermission(message) => (StatusCode::FORBIDDEN, message),
             ApiError::NotFound(message) => (StatusCode::NOT_FOUND, message),
             ApiError::ParseError(message) => (StatusCode::BAD_REQUEST, message),
+            ApiError::PayloadTooLarge(message) => (StatusCode::PAYLOAD_TOO_LARGE, message),
+            ApiError::HeadersTooLarge(message) => {
+                (StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE, message)
+            }
             ApiError::RateLimited(time_left_nanos) => (
                 StatusCode::TOO_MANY_REQUESTS,
                 format!("Cooldown: {}nanos", time_left_nanos),
@@ -0,0 +1,64 @@
+use axum::{extract::Request, middleware::Next, response::Response};
+
+use crate::error::ApiError;
+
+/// Reports server errors and panics to Sentry if SENTRY_DSN is set and the sentry feature is enabled
+/// The returned guard has to be kept until shutdown, dropping it flushes the pending events
+#[cfg(feature = "sentry")]
+pub fn setup() -> Option<sentry::ClientInitGuard> {
+    let dsn = std::env::var("SENTRY_DSN").ok()?;
+    let guard = sentry::init((
+        dsn,
+        sentry::ClientOptions {
+            release: sentry::release_name!(),
+            ..Default::default()
+        },
+    ));
+    println!("Reporting errors to Sentry");
+    Some(guard)
+}
+
+#[cfg(not(feature = "sentry"))]
+pub fn setup() -> Option<()> {
+    None
+}
+
+/// Only errors which are our fault are reported, not the ones caused by bad requests
+pub fn capture(error: &ApiError) {
+    #[cfg(feature = "sentry")]
+    if let ApiError::ServerError(_) | ApiError::DatabaseError(_) = error {
+        sentry::capture_message(&error.to_string(), sentry::Level::Error);
+    }
+    #[cfg(not(feature = "sentry"))]
+    let _ = error;
+}
+
+/// Runs every request with its own Sentry scope, tagged with the hash of the API key and the session id
+/// Errors and panics of the request carry these tags
+pub async fn report_errors(request: Request, next: Next) -> Response {
+    #[cfg(feature = "sentry")]
+    {
+        use sentry::{Hub, SentryFutureExt};
+        use sha2::{Digest, Sha256};
+
+        let hub = Hub::new_from_top(Hub::current());
+        let headers = request.headers();
+        hub.configure_scope(|scope| {
+            scope.set_tag(
+                "route",
+                format!("{} {}", request.method(), request.uri().path()),
+            );
+            // Keys are secrets, the hash still tells which user ran into the error
+            if let Some(key) = headers.get("x-api-key") {
+                let hash = hex::encode(Sha256::digest(key.as_bytes()));
+                scope.set_tag("user_key_hash", &hash[..16]);
+            }
+            if let Some(session_id) = headers.get("session-id").and_then(|id| id.to_str().ok()) {
+                scope.set_tag("session_id", session_id);
+            }
+        });
+        next.run(request).bind_hub(hub).await
+    }
+    #[cfg(not(feature = "sentry"))]
+    next.run(request).await
+}
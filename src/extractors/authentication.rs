@@ -1,7 +1,6 @@
 use crate::{
-    entities::user::{find_user_by_key, User},
-    error::ApiError,
-    AppState,
+    entities::user::User, error::ApiError, middleware::rate_limit::ResolvedUser,
+    services::user_service, AppState,
 };
 use axum::{
     async_trait,
@@ -33,18 +32,41 @@ impl FromRequestParts<AppState> for ExtractUser {
                 )
             })?;
 
-        let mut user = find_user_by_key(&state.database.user_collection, api_key)
-            .await?
-            .ok_or(ApiError::AuthorizationError(
-                "Invalid API key, check /docs for more information".to_string(),
-            ))?;
+        // The rate limiter already looked up the user of the key
+        let user = match parts.extensions.remove::<ResolvedUser>() {
+            Some(resolved) if resolved.api_key == api_key => resolved.user,
+            _ => user_service::find_user_by_api_key(state, api_key).await?,
+        };
+        let user = user_service::authorize(
+            state,
+            user,
+            api_key,
+            parts.method.as_str(),
+            parts.uri.path(),
+        )
+        .await?;
+        Ok(ExtractUser(user))
+    }
+}
 
-        let method = parts.method.as_str();
-        let path = parts.uri.path();
-        user.use_endpoint(method, path);
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
 
-        user.save(&state.database.user_collection).await?;
+    use crate::test_utils::*;
 
-        Ok(ExtractUser(user))
+    #[tokio::test]
+    async fn test_authentication() {
+        let state = test_state();
+        let key = create_user(&state, "lemon").await;
+
+        let (status, _) = send(&state, Method::GET, "/", "invalid").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = send(&state, Method::GET, "/", &key).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, me) = send(&state, Method::GET, "/user/me", &key).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(me["name"], "lemon");
+        assert_eq!(me["active_sessions"], 0);
     }
 }
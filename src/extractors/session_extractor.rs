@@ -1,7 +1,5 @@
 use crate::{
-    entities::session::{find_session_by_id, Session},
-    error::ApiError,
-    AppState,
+    entities::session::Session, error::ApiError, locks::Lock, services::session_service, AppState,
 };
 use axum::{
     async_trait,
@@ -11,6 +9,25 @@ use axum::{
 
 pub struct ExtractSession(pub Session);
 
+/// The session together with its lock, for handlers which change and save it
+pub struct ExtractLockedSession(pub Session, pub Lock);
+
+/// The session if a session-id header was sent, for handlers which also work without one
+pub struct ExtractOptionalSession(pub Option<Session>);
+
+fn get_session_id(parts: &Parts) -> Result<String, ApiError> {
+    let session_key_header = HeaderName::from_static("session-id");
+    let session_id = parts
+        .headers
+        .get(&session_key_header)
+        .ok_or(ApiError::BadRequest(
+            "session-id header is missing".to_string(),
+        ))?
+        .to_str()
+        .map_err(|_| ApiError::BadRequest("Invalid session-id format".to_string()))?;
+    Ok(session_id.to_string())
+}
+
 #[async_trait]
 impl FromRequestParts<AppState> for ExtractSession {
     type Rejection = ApiError;
@@ -19,20 +36,38 @@ impl FromRequestParts<AppState> for ExtractSession {
         parts: &mut Parts,
         state: &AppState,
     ) -> Result<Self, Self::Rejection> {
-        let session_key_header = HeaderName::from_static("session-id");
-        let session_id = parts
-            .headers
-            .get(&session_key_header)
-            .ok_or(ApiError::BadRequest(
-                "session-id header is missing".to_string(),
-            ))?
-            .to_str()
-            .map_err(|_| ApiError::BadRequest("Invalid session-id format".to_string()))?;
-
-        let session = find_session_by_id(&state.database.session_collection, session_id)
-            .await?
-            .ok_or(ApiError::NotFound("Session not found".to_string()))?;
-
+        let session_id = get_session_id(parts)?;
+        let session = session_service::find_session(state, &session_id).await?;
         Ok(ExtractSession(session))
     }
 }
+
+#[async_trait]
+impl FromRequestParts<AppState> for ExtractOptionalSession {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        if !parts.headers.contains_key("session-id") {
+            return Ok(ExtractOptionalSession(None));
+        }
+        let ExtractSession(session) = ExtractSession::from_request_parts(parts, state).await?;
+        Ok(ExtractOptionalSession(Some(session)))
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for ExtractLockedSession {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let session_id = get_session_id(parts)?;
+        let (session, lock) = session_service::lock_session(state, &session_id).await?;
+        Ok(ExtractLockedSession(session, lock))
+    }
+}
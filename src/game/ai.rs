@@ -1,34 +1,190 @@
-use pleco::{bots::IterativeSearcher, tools::Searcher, Board};
+use std::{num::NonZeroUsize, sync::Mutex};
 
-use crate::models::move_models::MoveQuery;
+use lazy_static::lazy_static;
+use lru::LruCache;
+use pleco::{bots::IterativeSearcher, tools::Searcher, BitMove, Board};
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+use tracing::instrument;
 
-use super::{error::GameError, position::Position, state::GameState};
+use crate::{
+    models::{enums::AiDifficulty, move_models::MoveQuery},
+    utils::random::GameRng,
+};
 
-pub fn get_next_move(state: &GameState) -> Result<MoveQuery, GameError> {
-    let fen = state.to_fen();
-    let board = Board::from_fen(&fen)?;
-    let best_move = IterativeSearcher::best_move(board, 6);
+use super::{
+    color::Color,
+    error::GameError,
+    position::Position,
+    review::{rank_moves, RankedMove},
+    state::GameState,
+};
 
-    let move_query = if best_move.is_king_castle() {
+lazy_static! {
+    /// Replies the AI prepared on the opponent's time, by session id
+    static ref PONDERED_REPLIES: Mutex<LruCache<String, PonderedReply>> =
+        Mutex::new(LruCache::new(NonZeroUsize::new(PONDER_CAPACITY).unwrap()));
+}
+
+/// Sessions whose pondered reply is kept, the least recently pondered ones are dropped first
+const PONDER_CAPACITY: usize = 1024;
+
+/// Depth the move of the opponent is predicted with
+const PREDICTION_DEPTH: u16 = 2;
+
+/// The reply of the AI to the move it expects from its opponent
+struct PonderedReply {
+    /// FEN of the position after the predicted move
+    fen: String,
+    difficulty: AiDifficulty,
+    reply: MoveQuery,
+}
+
+/// Depth of the full search of the hardest level
+const HARD_DEPTH: u16 = 6;
+
+/// Depth the moves are ranked with on the easier levels, the weakness comes from the sampling
+const SAMPLING_DEPTH: u16 = 2;
+
+impl AiDifficulty {
+    /// How many of the best moves are considered and the temperature in centipawns they are sampled with
+    /// A higher temperature makes worse moves more likely, None always plays the best move
+    fn get_sampling(&self) -> Option<(usize, f64)> {
+        match self {
+            AiDifficulty::BEGINNER => Some((6, 200.0)),
+            AiDifficulty::EASY => Some((4, 100.0)),
+            AiDifficulty::MEDIUM => Some((3, 40.0)),
+            AiDifficulty::HARD => None,
+        }
+    }
+}
+
+/// Easier levels sample with a generator forked from the position, so seeded games always get the same moves
+#[instrument(name = "ai_search", skip_all)]
+pub fn get_next_move(
+    state: &GameState,
+    difficulty: AiDifficulty,
+    rng: &GameRng,
+) -> Result<MoveQuery, GameError> {
+    let best_move = match difficulty.get_sampling() {
+        Some((top_k, temperature)) => {
+            let ranked = rank_moves(state, SAMPLING_DEPTH)?;
+            sample_move(&ranked, top_k, temperature, &mut rng.fork(&state.to_fen()))
+                .ok_or(GameError::AiError("No legal move left".to_string()))?
+        }
+        None => {
+            let board = Board::from_fen(&state.to_fen())?;
+            IterativeSearcher::best_move(board, HARD_DEPTH)
+        }
+    };
+    to_move_query(best_move)
+}
+
+/// Thinks about the reply to the most likely move of the opponent in the background
+/// If the opponent plays it, take_pondered_move returns the reply right away
+pub fn ponder(session_id: String, state: GameState, difficulty: AiDifficulty, rng: GameRng) {
+    tokio::task::spawn_blocking(move || {
+        match predict_reply(&state, difficulty, &rng) {
+            Ok(Some(pondered)) => {
+                lock_pondered_replies().put(session_id, pondered);
+            }
+            Ok(None) => {}
+            Err(error) => tracing::warn!(session_id, %error, "Failed to ponder"),
+        };
+    });
+}
+
+/// The reply pondered for this session if the opponent played the predicted move
+pub fn take_pondered_move(
+    session_id: &str,
+    state: &GameState,
+    difficulty: AiDifficulty,
+) -> Option<MoveQuery> {
+    let pondered = lock_pondered_replies().pop(session_id)?;
+    (pondered.fen == state.to_fen() && pondered.difficulty == difficulty).then_some(pondered.reply)
+}
+
+fn lock_pondered_replies() -> std::sync::MutexGuard<'static, LruCache<String, PonderedReply>> {
+    PONDERED_REPLIES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[instrument(name = "ai_ponder", skip_all)]
+fn predict_reply(
+    state: &GameState,
+    difficulty: AiDifficulty,
+    rng: &GameRng,
+) -> Result<Option<PonderedReply>, GameError> {
+    let Some(predicted) = rank_moves(state, PREDICTION_DEPTH)?.first().copied() else {
+        return Ok(None);
+    };
+    let mut state = state.clone();
+    if !play_move(&mut state, predicted.bit_move)? || state.is_finished() {
+        return Ok(None);
+    }
+
+    let reply = get_next_move(&state, difficulty, rng)?;
+    Ok(Some(PonderedReply {
+        fen: state.to_fen(),
+        difficulty,
+        reply,
+    }))
+}
+
+/// Plays a move found by pleco on the game state the same way moves of players are played
+fn play_move(state: &mut GameState, bit_move: BitMove) -> Result<bool, GameError> {
+    let color = Color::from(state.next_to_move as usize);
+    if bit_move.is_king_castle() {
+        state.castle_kingside(color)
+    } else if bit_move.is_queen_castle() {
+        state.castle_queenside(color)
+    } else {
+        let from = Position::try_from(bit_move.get_src_u8())?;
+        let to = Position::try_from(bit_move.get_dest_u8())?;
+        state.make_move(from.into(), to.into())
+    }
+}
+
+/// Picks one of the top_k moves, with a chance falling off exponentially with the score lost against the best move
+fn sample_move(
+    ranked: &[RankedMove],
+    top_k: usize,
+    temperature: f64,
+    rng: &mut impl Rng,
+) -> Option<BitMove> {
+    let candidates = &ranked[..ranked.len().min(top_k)];
+    let best_score = candidates.first()?.score;
+    let weights = candidates
+        .iter()
+        .map(|candidate| (-(best_score - candidate.score) as f64 / temperature).exp());
+    let index = WeightedIndex::new(weights).ok()?.sample(rng);
+    Some(candidates[index].bit_move)
+}
+
+fn to_move_query(bit_move: BitMove) -> Result<MoveQuery, GameError> {
+    let move_query = if bit_move.is_king_castle() {
         MoveQuery {
             from: None,
             to: None,
+            promotion: None,
             castle_kingside: Some(true),
             castle_queenside: None,
         }
-    } else if best_move.is_queen_castle() {
+    } else if bit_move.is_queen_castle() {
         MoveQuery {
             from: None,
             to: None,
+            promotion: None,
             castle_kingside: None,
             castle_queenside: Some(true),
         }
     } else {
-        let from = Position::try_from(best_move.get_src_u8())?;
-        let to = Position::try_from(best_move.get_dest_u8())?;
+        let from = Position::try_from(bit_move.get_src_u8())?;
+        let to = Position::try_from(bit_move.get_dest_u8())?;
         MoveQuery {
             from: Some(from.as_str()),
             to: Some(to.as_str()),
+            promotion: None,
             castle_kingside: None,
             castle_queenside: None,
         }
@@ -36,3 +192,73 @@ pub fn get_next_move(state: &GameState) -> Result<MoveQuery, GameError> {
 
     Ok(move_query)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_sample_move() {
+        let state = GameState::from_fen("4k3/8/8/3q4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let ranked = rank_moves(&state, SAMPLING_DEPTH).unwrap();
+        let mut rng = StdRng::seed_from_u64(7);
+
+        // Hanging the chance to win the queen is far too costly, even for beginners
+        for _ in 0..20 {
+            let bit_move = sample_move(&ranked, 6, 200.0, &mut rng).unwrap();
+            assert_eq!(bit_move, ranked[0].bit_move);
+        }
+
+        // Among even moves every candidate gets played
+        let state = GameState::new().unwrap();
+        let ranked = rank_moves(&state, SAMPLING_DEPTH).unwrap();
+        let picked: Vec<BitMove> = (0..200)
+            .filter_map(|_| sample_move(&ranked, 3, 1_000_000.0, &mut rng))
+            .collect();
+        for candidate in &ranked[..3] {
+            assert!(picked.contains(&candidate.bit_move));
+        }
+        assert!(picked.iter().all(|bit_move| ranked[..3]
+            .iter()
+            .any(|ranked| ranked.bit_move == *bit_move)));
+        assert_eq!(sample_move(&[], 3, 100.0, &mut rng), None);
+    }
+
+    #[test]
+    fn test_ponder() {
+        let state = GameState::from_fen("4k3/8/8/3q4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let pondered = predict_reply(&state, AiDifficulty::EASY, &GameRng::seeded(0))
+            .unwrap()
+            .unwrap();
+        let mut expected = state.clone();
+        expected
+            .make_move(Position::E4.into(), Position::D5.into())
+            .unwrap();
+        assert_eq!(pondered.fen, expected.to_fen());
+
+        lock_pondered_replies().put("ponder".to_string(), pondered);
+        // Other difficulties need a fresh search, the stale reply is dropped either way
+        assert!(take_pondered_move("ponder", &expected, AiDifficulty::HARD).is_none());
+        assert!(take_pondered_move("ponder", &expected, AiDifficulty::EASY).is_none());
+
+        let reply = |fen: String| PonderedReply {
+            fen,
+            difficulty: AiDifficulty::EASY,
+            reply: MoveQuery {
+                from: Some("e8".to_string()),
+                to: Some("e7".to_string()),
+                ..Default::default()
+            },
+        };
+        // The opponent played another move than predicted
+        lock_pondered_replies().put("ponder".to_string(), reply(expected.to_fen()));
+        assert!(take_pondered_move("ponder", &state, AiDifficulty::EASY).is_none());
+
+        lock_pondered_replies().put("ponder".to_string(), reply(expected.to_fen()));
+        let taken = take_pondered_move("ponder", &expected, AiDifficulty::EASY).unwrap();
+        assert_eq!(taken.from.as_deref(), Some("e8"));
+        assert_eq!(taken.to.as_deref(), Some("e7"));
+        assert!(take_pondered_move("ponder", &expected, AiDifficulty::EASY).is_none());
+    }
+}
@@ -1,19 +1,41 @@
 use gif::{DisposalMethod, Encoder, Frame, Repeat};
-use image::{DynamicImage, ImageBuffer, Rgba};
+use image::{DynamicImage, ImageBuffer, Rgba, RgbaImage};
+use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use std::io::Cursor;
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    sync::{Arc, RwLock},
+};
+use tracing::instrument;
 use utoipa::ToSchema;
 
-use crate::error::ApiError;
+use crate::{error::ApiError, utils::pdf::JpegImage};
 
-use super::{color::Color, piece::Piece, state::GameState};
+use super::{color::Color, piece::Piece, review::GRAPH_CAP, state::GameState};
 
-#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, ToSchema)]
+use cache::RenderKey;
+use sidebar::Sidebar;
+use theme::Theme;
+
+pub mod cache;
+pub mod sidebar;
+pub mod theme;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize, ToSchema)]
 pub enum RenderStyle {
     PIXEL,
     MODERN,
 }
 
+lazy_static! {
+    /// Decoded sprites by path, pieces are already resized to the size of their style
+    static ref SPRITES: RwLock<HashMap<String, Arc<RgbaImage>>> = RwLock::new(HashMap::new());
+}
+
+/// Width / Height
+const EVAL_GRAPH_SIZE: (u32, u32) = (600, 150);
+
 struct StyleConfig {
     asset_path: String,
     top_left_x: i64,
@@ -23,6 +45,10 @@ struct StyleConfig {
     /// Width / Height
     board_size: (u16, u16),
     piece_size: (u16, u16),
+    /// Part of the board asset covered by squares (x, y, width, height), used for recoloring
+    square_area: (u32, u32, u32, u32),
+    /// Scale of the pieces in the capture tray relative to their sprite
+    tray_scale: f32,
     filter: image::imageops::FilterType,
 }
 
@@ -37,6 +63,8 @@ impl StyleConfig {
                 step_y: 12,
                 board_size: (568, 568),
                 piece_size: (16, 32),
+                square_area: (6, 19, 130, 98),
+                tray_scale: 2.0,
                 filter: image::imageops::FilterType::Nearest,
             },
             RenderStyle::MODERN => Self {
@@ -47,16 +75,60 @@ impl StyleConfig {
                 step_y: 102,
                 board_size: (1024, 1024),
                 piece_size: (85, 85),
+                square_area: (107, 107, 810, 810),
+                tray_scale: 0.5,
                 filter: image::imageops::FilterType::CatmullRom,
             },
         }
     }
 }
 
-pub fn render(state: &GameState, color: Color, style: &RenderStyle) -> Result<Vec<u8>, ApiError> {
-    let config = StyleConfig::new(style);
+/// Returns the paths of all asset files needed for rendering
+pub fn get_required_assets() -> Vec<String> {
+    let mut assets = vec![sidebar::FONT_PATH.to_string()];
+    for style in [RenderStyle::PIXEL, RenderStyle::MODERN] {
+        let config = StyleConfig::new(&style);
+        for board in ["board_white.png", "board_black.png"] {
+            assets.push(format!("{}{}", config.asset_path, board));
+        }
+        for piece_id in 0..6 {
+            for color in [Color::WHITE, Color::BLACK] {
+                let image_name = Piece::from(piece_id).get_image_name(color);
+                assets.push(format!("{}{}", config.asset_path, image_name));
+            }
+        }
+    }
+    assets
+}
 
-    let mut board = image::open(format!(
+/// Loads an image once and keeps it decoded (and resized) in memory
+fn load_sprite(
+    path: &str,
+    size: Option<(u16, u16)>,
+    filter: image::imageops::FilterType,
+) -> Result<Arc<RgbaImage>, ApiError> {
+    if let Some(sprite) = SPRITES
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(path)
+    {
+        return Ok(sprite.clone());
+    }
+
+    let mut image = image::open(path)?;
+    if let Some((width, height)) = size {
+        image = image.resize(width as u32, height as u32, filter);
+    }
+    let sprite = Arc::new(image.to_rgba8());
+    SPRITES
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(path.to_string(), sprite.clone());
+    Ok(sprite)
+}
+
+fn load_board(config: &StyleConfig, color: Color) -> Result<Arc<RgbaImage>, ApiError> {
+    let path = format!(
         "{}board_{}.png",
         config.asset_path,
         if color == Color::WHITE {
@@ -64,8 +136,43 @@ pub fn render(state: &GameState, color: Color, style: &RenderStyle) -> Result<Ve
         } else {
             "black"
         }
-    ))?
-    .to_rgba8();
+    );
+    load_sprite(&path, None, config.filter)
+}
+
+fn load_piece(
+    config: &StyleConfig,
+    piece: Piece,
+    color: Color,
+) -> Result<Arc<RgbaImage>, ApiError> {
+    let path = format!("{}{}", config.asset_path, piece.get_image_name(color));
+    load_sprite(&path, Some(config.piece_size), config.filter)
+}
+
+/// Loads all boards and pieces of every style and the sidebar font, returns the amount of sprites
+pub fn preload_sprites() -> Result<usize, ApiError> {
+    sidebar::load_font()?;
+    let mut count = 0;
+    for style in [RenderStyle::PIXEL, RenderStyle::MODERN] {
+        let config = StyleConfig::new(&style);
+        for color in [Color::WHITE, Color::BLACK] {
+            load_board(&config, color)?;
+            for piece_id in 0..6 {
+                load_piece(&config, Piece::from(piece_id), color)?;
+                count += 1;
+            }
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[instrument(skip_all)]
+pub fn render(state: &GameState, color: Color, theme: &Theme) -> Result<Vec<u8>, ApiError> {
+    let config = StyleConfig::new(&theme.style);
+
+    let mut board = (*load_board(&config, color)?).clone();
+    theme.paint_squares(&mut board, config.square_area);
     let chess_board = if color == Color::WHITE {
         state.chess_board.clone()
     } else {
@@ -78,15 +185,8 @@ pub fn render(state: &GameState, color: Color, style: &RenderStyle) -> Result<Ve
             continue;
         }
         let (x, y) = calculate_coordinates(index, &config);
-        let path = format!("{}{}", config.asset_path, piece.get_image_name(piece_color));
-        let piece_image = image::open(path)?
-            .resize(
-                config.piece_size.0 as u32,
-                config.piece_size.1 as u32,
-                config.filter,
-            )
-            .to_rgba8();
-        image::imageops::overlay(&mut board, &piece_image, x, y);
+        let piece_image = load_piece(&config, piece, piece_color)?;
+        image::imageops::overlay(&mut board, piece_image.as_ref(), x, y);
     }
 
     let upscaled_image = image::imageops::resize(
@@ -99,14 +199,14 @@ pub fn render(state: &GameState, color: Color, style: &RenderStyle) -> Result<Ve
     Ok(upscaled_image.into_raw())
 }
 
-pub fn render_board_png(
+fn render_board_image(
     game_state: &GameState,
     color: Color,
-    style: &RenderStyle,
-) -> Result<Vec<u8>, ApiError> {
-    let config = StyleConfig::new(style);
+    theme: &Theme,
+) -> Result<DynamicImage, ApiError> {
+    let config = StyleConfig::new(&theme.style);
 
-    let raw_data = render(game_state, color, style)?;
+    let raw_data = render(game_state, color, theme)?;
     let buffer = ImageBuffer::<Rgba<u8>, _>::from_raw(
         config.board_size.0 as u32,
         config.board_size.1 as u32,
@@ -115,19 +215,108 @@ pub fn render_board_png(
     .ok_or(ApiError::ServerError(
         "Failed to create image buffer.".to_string(),
     ))?;
-    let dynamic_image = DynamicImage::ImageRgba8(buffer);
+    Ok(DynamicImage::ImageRgba8(buffer))
+}
+
+pub fn render_board_png(
+    game_state: &GameState,
+    color: Color,
+    theme: &Theme,
+) -> Result<Vec<u8>, ApiError> {
+    render_board_png_with_sidebar(game_state, color, theme, None)
+}
+
+pub fn render_board_png_with_sidebar(
+    game_state: &GameState,
+    color: Color,
+    theme: &Theme,
+    sidebar: Option<&Sidebar>,
+) -> Result<Vec<u8>, ApiError> {
+    let key = RenderKey::new(&game_state.to_fen(), color, theme).with_sidebar(sidebar);
+    let png_bytes = cache::get_or_render(key, || {
+        let mut dynamic_image = render_board_image(game_state, color, theme)?;
+        if let Some(sidebar) = sidebar {
+            dynamic_image = DynamicImage::ImageRgba8(sidebar.draw(
+                &dynamic_image.to_rgba8(),
+                color,
+                &theme.style,
+            )?);
+        }
+        let mut png_bytes = Vec::new();
+        let mut cursor = Cursor::new(&mut png_bytes);
+        dynamic_image.write_to(&mut cursor, image::ImageFormat::Png)?;
+        Ok::<_, ApiError>(png_bytes)
+    })?;
+    Ok(png_bytes.to_vec())
+}
+
+/// JPEG has no alpha channel, but unlike PNG it can be embedded into PDFs as is
+pub fn render_board_jpeg(
+    game_state: &GameState,
+    color: Color,
+    theme: &Theme,
+) -> Result<JpegImage, ApiError> {
+    let rgb_image =
+        DynamicImage::ImageRgb8(render_board_image(game_state, color, theme)?.to_rgb8());
+    let mut jpeg_bytes = Vec::new();
+    let mut cursor = Cursor::new(&mut jpeg_bytes);
+    rgb_image.write_to(&mut cursor, image::ImageFormat::Jpeg)?;
+    Ok(JpegImage {
+        width: rgb_image.width(),
+        height: rgb_image.height(),
+        data: jpeg_bytes,
+    })
+}
+
+/// Renders evaluations as an area chart, white's advantage upwards in white and black's downwards in black
+#[instrument(skip_all)]
+pub fn render_eval_graph_png(evals: &[i32]) -> Result<Vec<u8>, ApiError> {
+    let (width, height) = EVAL_GRAPH_SIZE;
+    let center = height as f32 / 2.0;
+    let mut graph = ImageBuffer::from_pixel(width, height, Rgba([128, 128, 128, 255]));
+
+    for x in 0..width {
+        // Linear interpolation between the two closest evaluations
+        let position = x as f32 / (width - 1) as f32 * (evals.len().max(1) - 1) as f32;
+        let left = evals
+            .get(position.floor() as usize)
+            .copied()
+            .unwrap_or_default();
+        let right = evals.get(position.ceil() as usize).copied().unwrap_or(left);
+        let eval = left as f32 + (right - left) as f32 * position.fract();
+
+        let offset = eval.clamp(-GRAPH_CAP as f32, GRAPH_CAP as f32) / GRAPH_CAP as f32 * center;
+        let (from, to, pixel) = if offset >= 0.0 {
+            (center - offset, center, Rgba([255, 255, 255, 255]))
+        } else {
+            (center, center - offset, Rgba([0, 0, 0, 255]))
+        };
+        for y in (from as u32)..(to as u32).min(height) {
+            graph.put_pixel(x, y, pixel);
+        }
+    }
+
     let mut png_bytes = Vec::new();
     let mut cursor = Cursor::new(&mut png_bytes);
-    dynamic_image.write_to(&mut cursor, image::ImageFormat::Png)?;
+    DynamicImage::ImageRgba8(graph).write_to(&mut cursor, image::ImageFormat::Png)?;
     Ok(png_bytes)
 }
 
+/// Animates the positions from after from_ply to after to_ply plies, ply 0 being the starting position
+#[instrument(skip_all)]
 pub fn render_history_gif(
     game_state: &GameState,
     color: Color,
-    style: &RenderStyle,
+    theme: &Theme,
+    (from_ply, to_ply): (usize, usize),
 ) -> Result<Vec<u8>, ApiError> {
-    let config = StyleConfig::new(style);
+    let config = StyleConfig::new(&theme.style);
+    if from_ply > to_ply || to_ply > game_state.move_log.len() {
+        return Err(ApiError::BadRequest(format!(
+            "Invalid ply range, the game has {} plies",
+            game_state.move_log.len()
+        )));
+    }
 
     let mut buffer = Vec::new();
     let mut cursor = Cursor::new(&mut buffer);
@@ -136,28 +325,13 @@ pub fn render_history_gif(
         let mut encoder = Encoder::new(&mut cursor, config.board_size.0, config.board_size.1, &[])?;
         encoder.set_repeat(Repeat::Infinite)?;
 
-        let mut state = GameState::new()?;
-        let mut initial_image = render(&state, color, style)?;
-        let mut initial_frame = Frame::from_rgba_speed(
-            config.board_size.0,
-            config.board_size.1,
-            &mut initial_image,
-            10,
-        );
-        initial_frame.dispose = DisposalMethod::Background;
-        initial_frame.delay = 100;
-        encoder.write_frame(&initial_frame)?;
-
-        for (i, (from, to)) in game_state.move_log.iter().enumerate() {
-            if *from == 64 {
-                state.castle_kingside(Color::from(*to as usize))?;
-            } else if *from == 65 {
-                state.castle_queenside(Color::from(*to as usize))?;
-            } else {
-                state.make_move(*from, *to)?;
+        let mut state = game_state.at_ply(from_ply)?;
+        for ply in from_ply..=to_ply {
+            if ply > from_ply {
+                state.replay_move(game_state.move_log[ply - 1])?;
             }
 
-            let mut frame_image = render(&state, color, style)?;
+            let mut frame_image = render(&state, color, theme)?;
             let mut frame = Frame::from_rgba_speed(
                 config.board_size.0,
                 config.board_size.1,
@@ -166,11 +340,7 @@ pub fn render_history_gif(
             );
             frame.dispose = DisposalMethod::Background;
 
-            frame.delay = if i < game_state.move_log.len() - 1 {
-                100
-            } else {
-                500
-            };
+            frame.delay = if ply < to_ply { 100 } else { 500 };
             encoder.write_frame(&frame)?;
         }
     }
@@ -185,3 +355,58 @@ fn calculate_coordinates(index: u8, config: &StyleConfig) -> (i64, i64) {
     let y = config.top_left_y + (7 - current_row) * config.step_y;
     (x, y)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::game::position::Square;
+
+    use super::*;
+
+    #[test]
+    fn test_preload_sprites() {
+        let image_count = get_required_assets()
+            .iter()
+            .filter(|path| path.ends_with(".png"))
+            .count();
+        assert_eq!(preload_sprites().unwrap(), image_count);
+    }
+
+    #[test]
+    fn test_render_eval_graph_png() {
+        let png_bytes = render_eval_graph_png(&[0, 300, -5000, 0]).unwrap();
+        let graph = image::load_from_memory(&png_bytes).unwrap().to_rgba8();
+        assert_eq!(graph.dimensions(), EVAL_GRAPH_SIZE);
+
+        // Black's decisive advantage fills the lower half
+        let x = EVAL_GRAPH_SIZE.0 * 2 / 3;
+        assert_eq!(
+            graph.get_pixel(x, EVAL_GRAPH_SIZE.1 - 1),
+            &Rgba([0, 0, 0, 255])
+        );
+        assert_eq!(graph.get_pixel(x, 0), &Rgba([128, 128, 128, 255]));
+
+        assert!(render_eval_graph_png(&[]).is_ok());
+    }
+
+    #[test]
+    fn test_render_history_gif_range() {
+        let mut state = GameState::new().unwrap();
+        for (from, to) in [(12, 28), (52, 36), (6, 21), (57, 42)] {
+            state.make_move(Square(from), Square(to)).unwrap();
+        }
+        let theme = Theme::new(RenderStyle::PIXEL);
+
+        let gif_bytes = render_history_gif(&state, Color::WHITE, &theme, (1, 3)).unwrap();
+        let mut decoder = gif::DecodeOptions::new()
+            .read_info(Cursor::new(gif_bytes))
+            .unwrap();
+        let mut frames = 0;
+        while decoder.read_next_frame().unwrap().is_some() {
+            frames += 1;
+        }
+        assert_eq!(frames, 3);
+
+        assert!(render_history_gif(&state, Color::WHITE, &theme, (3, 1)).is_err());
+        assert!(render_history_gif(&state, Color::WHITE, &theme, (0, 5)).is_err());
+    }
+}
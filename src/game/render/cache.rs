@@ -0,0 +1,147 @@
+use std::{
+    env,
+    sync::{Arc, Mutex},
+};
+
+use lazy_static::lazy_static;
+use lru::LruCache;
+
+use crate::game::color::Color;
+
+use super::{sidebar::Sidebar, theme::Theme};
+
+lazy_static! {
+    /// Rendered board PNGs, bounded by RENDER_CACHE_MB (defaults to 64)
+    static ref RENDER_CACHE: Mutex<RenderCache> = Mutex::new(RenderCache::new(
+        env::var("RENDER_CACHE_MB")
+            .ok()
+            .map(|size| size.parse::<usize>().expect("RENDER_CACHE_MB has to be a number."))
+            .unwrap_or(64)
+            * 1024
+            * 1024
+    ));
+}
+
+/// Everything a rendered board depends on
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RenderKey {
+    /// Piece placement part of the FEN, the rest doesn't show up on the board
+    pub placement: String,
+    pub color: Color,
+    pub theme: Theme,
+    pub sidebar: Option<Sidebar>,
+}
+
+impl RenderKey {
+    pub fn new(fen: &str, color: Color, theme: &Theme) -> Self {
+        Self {
+            placement: fen.split(' ').next().unwrap_or_default().to_string(),
+            color,
+            theme: *theme,
+            sidebar: None,
+        }
+    }
+
+    pub fn with_sidebar(mut self, sidebar: Option<&Sidebar>) -> Self {
+        self.sidebar = sidebar.cloned();
+        self
+    }
+}
+
+/// Least recently used images are evicted once their total size exceeds the limit
+pub struct RenderCache {
+    entries: LruCache<RenderKey, Arc<Vec<u8>>>,
+    size: usize,
+    max_size: usize,
+}
+
+impl RenderCache {
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            entries: LruCache::unbounded(),
+            size: 0,
+            max_size,
+        }
+    }
+
+    pub fn get(&mut self, key: &RenderKey) -> Option<Arc<Vec<u8>>> {
+        self.entries.get(key).cloned()
+    }
+
+    pub fn insert(&mut self, key: RenderKey, image: Arc<Vec<u8>>) {
+        if image.len() > self.max_size {
+            return;
+        }
+
+        self.size += image.len();
+        if let Some((_, replaced)) = self.entries.push(key, image) {
+            self.size -= replaced.len();
+        }
+        while self.size > self.max_size {
+            match self.entries.pop_lru() {
+                Some((_, evicted)) => self.size -= evicted.len(),
+                None => break,
+            }
+        }
+    }
+}
+
+/// Returns the cached image or renders and caches it
+pub fn get_or_render<F, E>(key: RenderKey, render: F) -> Result<Arc<Vec<u8>>, E>
+where
+    F: FnOnce() -> Result<Vec<u8>, E>,
+{
+    let cached = RENDER_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(&key);
+    if let Some(image) = cached {
+        return Ok(image);
+    }
+
+    // Rendering happens without holding the lock, so a miss doesn't block other renders
+    let image = Arc::new(render()?);
+    RENDER_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(key, image.clone());
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::render::RenderStyle;
+
+    use super::*;
+
+    fn key(placement: &str) -> RenderKey {
+        RenderKey::new(placement, Color::WHITE, &Theme::new(RenderStyle::MODERN))
+    }
+
+    #[test]
+    fn test_render_cache() {
+        let mut cache = RenderCache::new(10);
+        cache.insert(key("a"), Arc::new(vec![0; 4]));
+        cache.insert(key("b"), Arc::new(vec![0; 4]));
+        // Makes b the least recently used one
+        assert!(cache.get(&key("a")).is_some());
+
+        cache.insert(key("c"), Arc::new(vec![0; 4]));
+        assert!(cache.get(&key("b")).is_none());
+        assert!(cache.get(&key("a")).is_some());
+        assert_eq!(cache.size, 8);
+
+        // Too large to be cached at all
+        cache.insert(key("d"), Arc::new(vec![0; 11]));
+        assert!(cache.get(&key("d")).is_none());
+        assert_eq!(cache.size, 8);
+    }
+
+    #[test]
+    fn test_render_key_ignores_counters() {
+        assert_eq!(
+            key("8/8/8/8/8/8/8/K6k w - - 0 1"),
+            key("8/8/8/8/8/8/8/K6k w - - 12 40")
+        );
+    }
+}
@@ -0,0 +1,372 @@
+use std::fs;
+
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use image::{Rgba, RgbaImage};
+use lazy_static::lazy_static;
+
+use crate::{
+    error::ApiError,
+    game::{
+        color::Color, error::GameError, piece::Piece, report::format_eval, review::GRAPH_CAP,
+        state::GameState,
+    },
+};
+
+use super::{load_piece, RenderStyle, StyleConfig};
+
+pub const FONT_PATH: &str = "src/assets/fonts/DejaVuSans.ttf";
+
+lazy_static! {
+    static ref FONT: Option<FontVec> = fs::read(FONT_PATH)
+        .ok()
+        .and_then(|bytes| FontVec::try_from_vec(bytes).ok());
+}
+
+/// Fails if the font file is missing or isn't a valid font
+pub fn load_font() -> Result<&'static FontVec, ApiError> {
+    FONT.as_ref().ok_or(ApiError::ServerError(
+        "Failed to load the sidebar font.".to_string(),
+    ))
+}
+
+const BACKGROUND: Rgba<u8> = Rgba([48, 46, 43, 255]);
+const TEXT: Rgba<u8> = Rgba([230, 230, 230, 255]);
+const EVAL_WHITE: Rgba<u8> = Rgba([235, 235, 235, 255]);
+const EVAL_BLACK: Rgba<u8> = Rgba([25, 25, 25, 255]);
+
+/// What is shown next to the board, makes shared images self-describing
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Sidebar {
+    /// Display names of the white and black player
+    pub names: Option<[String; 2]>,
+    /// Evaluation from white's perspective (positive favors white)
+    pub eval: Option<i32>,
+    pub captures: Option<CaptureTray>,
+}
+
+/// Pieces captured by each color, shown next to the player who captured them
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CaptureTray {
+    /// Captured by white (index 0) and black (index 1)
+    pub pieces: [Vec<Piece>; 2],
+    /// Material on the board in pawns by color, 0 = white, 1 = black
+    pub material: [i32; 2],
+}
+
+impl CaptureTray {
+    pub fn from_state(state: &GameState) -> Result<Self, GameError> {
+        Ok(Self {
+            pieces: state.get_captured_pieces()?,
+            material: state.material(),
+        })
+    }
+
+    /// Material advantage of the given color, if it's ahead
+    fn get_advantage(&self, color: Color) -> Option<i32> {
+        let advantage =
+            self.material[color as usize] - self.material[color.opponent_color() as usize];
+        (advantage > 0).then_some(advantage)
+    }
+}
+
+impl Sidebar {
+    /// Places the board on a wider canvas with the sidebar to its right.
+    /// The perspective color is at the bottom, just like on the board.
+    pub fn draw(
+        &self,
+        board: &RgbaImage,
+        color: Color,
+        style: &RenderStyle,
+    ) -> Result<RgbaImage, ApiError> {
+        let font = load_font()?;
+
+        let (board_width, height) = board.dimensions();
+        let width = board_width + height * 3 / 8;
+        let margin = height / 32;
+        let font_size = (height / 20) as f32;
+
+        let mut canvas = RgbaImage::from_pixel(width, height, BACKGROUND);
+        image::imageops::overlay(&mut canvas, board, 0, 0);
+
+        let mut text_x = board_width + margin;
+        if let Some(eval) = self.eval {
+            let bar_width = height / 16;
+            let bar_height = height - 2 * margin;
+            let white_share =
+                (eval.clamp(-GRAPH_CAP, GRAPH_CAP) + GRAPH_CAP) as f32 / (2 * GRAPH_CAP) as f32;
+            let white_height = (white_share * bar_height as f32) as u32;
+
+            for y in 0..bar_height {
+                // White's share grows from white's side of the board
+                let from_white_side = if color == Color::BLACK {
+                    y
+                } else {
+                    bar_height - 1 - y
+                };
+                let pixel = if from_white_side < white_height {
+                    EVAL_WHITE
+                } else {
+                    EVAL_BLACK
+                };
+                for x in 0..bar_width {
+                    canvas.put_pixel(text_x + x, margin + y, pixel);
+                }
+            }
+
+            draw_text(
+                &mut canvas,
+                font,
+                &format_eval(eval),
+                (text_x + bar_width + margin) as f32,
+                (height as f32 - font_size) / 2.0,
+                font_size,
+            );
+            text_x += bar_width + margin;
+        }
+
+        let column_width = width - text_x - margin;
+        let colors = if color == Color::BLACK {
+            [Color::WHITE, Color::BLACK]
+        } else {
+            [Color::BLACK, Color::WHITE]
+        };
+
+        // The opponent's section grows downwards from the top, the own one upwards from the bottom
+        let mut top = margin;
+        let mut bottom = height - margin;
+        if let Some(names) = &self.names {
+            let [top_name, bottom_name] = colors.map(|color| &names[color as usize]);
+            let font_height = font_size as u32;
+            for (name, y) in [(top_name, top), (bottom_name, bottom - font_height)] {
+                let name = fit_text(font, name, font_size, column_width as f32);
+                draw_text(&mut canvas, font, &name, text_x as f32, y as f32, font_size);
+            }
+            top += font_height + margin / 2;
+            bottom -= font_height + margin / 2;
+        }
+
+        if let Some(captures) = &self.captures {
+            let config = StyleConfig::new(style);
+            let text_size = font_size * 0.8;
+            for (tray_color, at_top) in [(colors[0], true), (colors[1], false)] {
+                let pieces = &captures.pieces[tray_color as usize];
+                let mut items = Vec::new();
+                for (index, piece) in pieces.iter().enumerate() {
+                    let icon = load_tray_icon(&config, *piece, tray_color.opponent_color())?;
+                    // Pieces of the same kind overlap, like in most chess GUIs
+                    let advance = if pieces.get(index + 1) == Some(piece) {
+                        icon.width() / 2
+                    } else {
+                        icon.width() * 9 / 8
+                    };
+                    items.push((TrayItem::Icon(icon), advance));
+                }
+                if let Some(advantage) = captures.get_advantage(tray_color) {
+                    let text = format!("+{}", advantage);
+                    let advance = text_width(font, &text, text_size).ceil() as u32;
+                    items.push((TrayItem::Text(text), advance));
+                }
+
+                let row_height = items
+                    .iter()
+                    .map(|(item, _)| match item {
+                        TrayItem::Icon(icon) => icon.height(),
+                        TrayItem::Text(_) => text_size as u32,
+                    })
+                    .max()
+                    .unwrap_or(0);
+                let layout = layout_tray(&items, column_width);
+                let rows = layout.iter().map(|(_, row)| row + 1).max().unwrap_or(0);
+                let first_row_y = if at_top {
+                    top
+                } else {
+                    bottom.saturating_sub(rows * row_height)
+                };
+
+                // Everything is aligned to the bottom of its row
+                for ((item, _), (x, row)) in items.iter().zip(layout) {
+                    let x = text_x + x;
+                    let row_bottom = first_row_y + (row + 1) * row_height;
+                    match item {
+                        TrayItem::Icon(icon) => image::imageops::overlay(
+                            &mut canvas,
+                            icon,
+                            x as i64,
+                            (row_bottom - icon.height()) as i64,
+                        ),
+                        TrayItem::Text(text) => draw_text(
+                            &mut canvas,
+                            font,
+                            text,
+                            x as f32,
+                            row_bottom as f32 - text_size,
+                            text_size,
+                        ),
+                    }
+                }
+            }
+        }
+
+        Ok(canvas)
+    }
+}
+
+enum TrayItem {
+    Icon(RgbaImage),
+    Text(String),
+}
+
+/// A piece sprite without its transparent border, scaled for the capture tray
+fn load_tray_icon(config: &StyleConfig, piece: Piece, color: Color) -> Result<RgbaImage, ApiError> {
+    let sprite = load_piece(config, piece, color)?;
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in sprite.enumerate_pixels() {
+        if pixel.0[3] > 0 {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+    }
+    if min_x > max_x {
+        return Ok(RgbaImage::new(1, 1));
+    }
+
+    let trimmed = image::imageops::crop_imm(
+        sprite.as_ref(),
+        min_x,
+        min_y,
+        max_x - min_x + 1,
+        max_y - min_y + 1,
+    )
+    .to_image();
+    let (width, height) = trimmed.dimensions();
+    Ok(image::imageops::resize(
+        &trimmed,
+        ((width as f32 * config.tray_scale) as u32).max(1),
+        ((height as f32 * config.tray_scale) as u32).max(1),
+        config.filter,
+    ))
+}
+
+/// Places the items (and how far each advances) left to right, wrapping into new rows.
+/// Returns the x offset and row of each item.
+fn layout_tray(items: &[(TrayItem, u32)], max_width: u32) -> Vec<(u32, u32)> {
+    let mut positions = Vec::new();
+    let (mut x, mut row) = (0, 0);
+    for (item, advance) in items {
+        let width = match item {
+            TrayItem::Icon(icon) => icon.width(),
+            TrayItem::Text(_) => *advance,
+        };
+        if x > 0 && x + width > max_width {
+            x = 0;
+            row += 1;
+        }
+        positions.push((x, row));
+        x += advance;
+    }
+    positions
+}
+
+fn text_width(font: &FontVec, text: &str, size: f32) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(size));
+    text.chars()
+        .map(|character| scaled.h_advance(font.glyph_id(character)))
+        .sum()
+}
+
+/// Shortens the text with an ellipsis until it fits into the given width
+fn fit_text(font: &FontVec, text: &str, size: f32, max_width: f32) -> String {
+    if text_width(font, text, size) <= max_width {
+        return text.to_string();
+    }
+
+    let mut characters: Vec<char> = text.chars().collect();
+    while !characters.is_empty() {
+        characters.pop();
+        let shortened = format!("{}...", characters.iter().collect::<String>());
+        if text_width(font, &shortened, size) <= max_width {
+            return shortened;
+        }
+    }
+    String::new()
+}
+
+/// Draws a single line of text, (x, y) being its top left corner
+fn draw_text(canvas: &mut RgbaImage, font: &FontVec, text: &str, x: f32, y: f32, size: f32) {
+    let scale = PxScale::from(size);
+    let scaled = font.as_scaled(scale);
+    let mut caret = x;
+
+    for character in text.chars() {
+        let glyph_id = font.glyph_id(character);
+        let glyph = glyph_id.with_scale_and_position(scale, point(caret, y + scaled.ascent()));
+        caret += scaled.h_advance(glyph_id);
+
+        let Some(outline) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|glyph_x, glyph_y, coverage| {
+            let pixel_x = bounds.min.x as i64 + glyph_x as i64;
+            let pixel_y = bounds.min.y as i64 + glyph_y as i64;
+            if pixel_x < 0
+                || pixel_y < 0
+                || pixel_x >= canvas.width() as i64
+                || pixel_y >= canvas.height() as i64
+            {
+                return;
+            }
+
+            let pixel = canvas.get_pixel_mut(pixel_x as u32, pixel_y as u32);
+            for channel in 0..3 {
+                let background = pixel.0[channel] as f32;
+                let foreground = TEXT.0[channel] as f32;
+                pixel.0[channel] = (background + (foreground - background) * coverage) as u8;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_sidebar() {
+        let board = RgbaImage::from_pixel(320, 320, Rgba([255, 0, 0, 255]));
+        let sidebar = Sidebar {
+            names: Some(["Lemon".to_string(), "A very long display name".to_string()]),
+            eval: Some(GRAPH_CAP),
+            captures: None,
+        };
+
+        let canvas = sidebar
+            .draw(&board, Color::WHITE, &RenderStyle::MODERN)
+            .unwrap();
+        assert_eq!(canvas.dimensions(), (440, 320));
+        assert_eq!(canvas.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+        // A decisive advantage for white fills the whole bar
+        assert_eq!(canvas.get_pixel(335, 20), &EVAL_WHITE);
+
+        let font = FONT.as_ref().unwrap();
+        let name = fit_text(font, "A very long display name", 16.0, 80.0);
+        assert!(name.ends_with("...") && text_width(font, &name, 16.0) <= 80.0);
+    }
+
+    #[test]
+    fn test_layout_tray() {
+        let icon = || (TrayItem::Icon(RgbaImage::new(10, 10)), 10);
+        let items = [
+            icon(),
+            icon(),
+            icon(),
+            (TrayItem::Text("+3".to_string()), 8),
+        ];
+        assert_eq!(
+            layout_tray(&items, 25),
+            vec![(0, 0), (10, 0), (0, 1), (10, 1)]
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use image::{Rgb, RgbaImage};
+
+use crate::error::ApiError;
+
+use super::RenderStyle;
+
+/// Pixels of the board asset brighter than this belong to light squares
+const LIGHT_THRESHOLD: u32 = 128;
+
+/// How a board is rendered, the style decides the board layout and piece set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Theme {
+    pub style: RenderStyle,
+    /// None keeps the colors of the board asset
+    pub light: Option<Rgb<u8>>,
+    pub dark: Option<Rgb<u8>>,
+}
+
+pub const THEMES: [(&str, Theme); 6] = [
+    ("classic", Theme::new(RenderStyle::MODERN)),
+    ("pixel", Theme::new(RenderStyle::PIXEL)),
+    (
+        "brown",
+        Theme::with_colors(RenderStyle::MODERN, [240, 217, 181], [181, 136, 99]),
+    ),
+    (
+        "green",
+        Theme::with_colors(RenderStyle::MODERN, [238, 238, 210], [118, 150, 86]),
+    ),
+    (
+        "blue",
+        Theme::with_colors(RenderStyle::MODERN, [222, 227, 230], [140, 162, 173]),
+    ),
+    (
+        "gameboy",
+        Theme::with_colors(RenderStyle::PIXEL, [155, 188, 15], [48, 98, 48]),
+    ),
+];
+
+impl Theme {
+    pub const fn new(style: RenderStyle) -> Self {
+        Self {
+            style,
+            light: None,
+            dark: None,
+        }
+    }
+
+    const fn with_colors(style: RenderStyle, light: [u8; 3], dark: [u8; 3]) -> Self {
+        Self {
+            style,
+            light: Some(Rgb(light)),
+            dark: Some(Rgb(dark)),
+        }
+    }
+
+    pub fn find(name: &str) -> Result<Self, ApiError> {
+        THEMES
+            .iter()
+            .find(|(theme_name, _)| theme_name.eq_ignore_ascii_case(name))
+            .map(|(_, theme)| *theme)
+            .ok_or_else(|| {
+                let names: Vec<&str> = THEMES.iter().map(|(name, _)| *name).collect();
+                ApiError::BadRequest(format!(
+                    "Unknown theme, available themes: {}",
+                    names.join(", ")
+                ))
+            })
+    }
+
+    /// Recolors the squares within the given area (x, y, width, height) of a board asset.
+    /// Shading is kept by scaling the new color with each pixel's brightness relative to its square color.
+    pub fn paint_squares(&self, board: &mut RgbaImage, area: (u32, u32, u32, u32)) {
+        if self.light.is_none() && self.dark.is_none() {
+            return;
+        }
+
+        let (x, y, width, height) = area;
+        let luminance = |pixel: &[u8]| (pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32) / 3;
+
+        // Average brightness of light and dark squares
+        let mut sums = [(0u64, 0u64); 2];
+        for row in y..y + height {
+            for column in x..x + width {
+                let value = luminance(&board.get_pixel(column, row).0);
+                let sum = &mut sums[(value > LIGHT_THRESHOLD) as usize];
+                sum.0 += value as u64;
+                sum.1 += 1;
+            }
+        }
+        let averages = sums.map(|(sum, count)| (sum / count.max(1)).max(1) as u32);
+
+        for row in y..y + height {
+            for column in x..x + width {
+                let pixel = board.get_pixel_mut(column, row);
+                let value = luminance(&pixel.0);
+                let is_light = value > LIGHT_THRESHOLD;
+                let Some(Rgb(color)) = (if is_light { self.light } else { self.dark }) else {
+                    continue;
+                };
+                let average = averages[is_light as usize];
+                for (channel, target) in pixel.0.iter_mut().zip(color) {
+                    *channel = (target as u32 * value / average).min(255) as u8;
+                }
+            }
+        }
+    }
+}
+
+impl From<RenderStyle> for Theme {
+    fn from(style: RenderStyle) -> Self {
+        Self::new(style)
+    }
+}
+
+/// Parses colors like #F0D9B5 or F0D9B5
+pub fn parse_hex_color(input: &str) -> Result<Rgb<u8>, ApiError> {
+    let hex = input.trim_start_matches('#');
+    let invalid = || ApiError::BadRequest(format!("Invalid color {}, expected #RRGGBB", input));
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+
+    let mut color = [0; 3];
+    for (index, channel) in color.iter_mut().enumerate() {
+        *channel = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(Rgb(color))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_hex_color() {
+        assert_eq!(parse_hex_color("#F0D9B5").unwrap(), Rgb([240, 217, 181]));
+        assert_eq!(parse_hex_color("769656").unwrap(), Rgb([118, 150, 86]));
+        assert!(parse_hex_color("#F0D9B").is_err());
+        assert!(parse_hex_color("#GGGGGG").is_err());
+    }
+
+    #[test]
+    fn test_paint_squares() {
+        let mut board = RgbaImage::from_pixel(4, 2, Rgba([200, 200, 200, 255]));
+        board.put_pixel(1, 0, Rgba([50, 50, 50, 255]));
+        board.put_pixel(3, 1, Rgba([0, 0, 0, 255]));
+
+        Theme::find("green")
+            .unwrap()
+            .paint_squares(&mut board, (0, 0, 2, 2));
+        assert_eq!(board.get_pixel(0, 0), &Rgba([238, 238, 210, 255]));
+        assert_eq!(board.get_pixel(1, 0), &Rgba([118, 150, 86, 255]));
+        // Outside of the area
+        assert_eq!(board.get_pixel(3, 1), &Rgba([0, 0, 0, 255]));
+        assert!(Theme::find("unknown").is_err());
+    }
+}
@@ -0,0 +1,304 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+use crate::{
+    error::ApiError,
+    utils::pdf::{PdfDocument, PdfPage, PAGE_HEIGHT, PAGE_WIDTH},
+};
+
+use super::{
+    color::Color,
+    render::{render_board_jpeg, render_board_png, theme::Theme},
+    review::{GameReview, PlayerAccuracy, ReviewedPly, GRAPH_CAP, MATE_SCORE},
+    state::GameState,
+};
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, ToSchema)]
+pub enum ReportFormat {
+    MARKDOWN,
+    PDF,
+}
+
+/// How many of the worst moves get a diagram
+const KEY_DIAGRAMS: usize = 3;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+const MARGIN: f32 = 50.0;
+const LINE_HEIGHT: f32 = 14.0;
+/// Helvetica at 10pt fits roughly this many characters between the margins
+const LINE_CHARACTERS: usize = 90;
+
+/// A shareable summary of a reviewed game
+pub struct GameReport {
+    pub title: String,
+    pub white_player: String,
+    pub black_player: String,
+    pub date: String,
+    /// 1-0, 0-1, 1/2-1/2 or *
+    pub result: String,
+    pub review: GameReview,
+}
+
+impl GameReport {
+    #[instrument(skip_all)]
+    pub fn to_markdown(&self, color: Color, theme: &Theme) -> Result<String, ApiError> {
+        let mut markdown = format!(
+            "# {}\n\n**White:** {}  \n**Black:** {}  \n**Date:** {}  \n**Result:** {}\n\n",
+            self.title, self.white_player, self.black_player, self.date, self.result
+        );
+
+        markdown.push_str("## Evaluation\n\n");
+        for (color, name) in [
+            (Color::WHITE, &self.white_player),
+            (Color::BLACK, &self.black_player),
+        ] {
+            if let Some(accuracy) = self.review.get_accuracy(color) {
+                markdown.push_str(&format!("**{}:** {}  \n", name, format_accuracy(accuracy)));
+            }
+        }
+        markdown.push('\n');
+        markdown.push_str(&format!(
+            "`{}`\n\n",
+            get_sparkline(&self.review.get_evals())
+        ));
+
+        markdown
+            .push_str("## Moves\n\n| # | White | Eval | Black | Eval |\n|---|---|---|---|---|\n");
+        for (number, plies) in self.get_full_moves() {
+            let cells: Vec<String> = plies
+                .iter()
+                .map(|ply| match ply {
+                    Some(ply) => format!("{} | {}", get_annotated_san(ply), format_eval(ply.eval)),
+                    None => " | ".to_string(),
+                })
+                .collect();
+            markdown.push_str(&format!("| {} | {} |\n", number, cells.join(" | ")));
+        }
+
+        let key_plies = self.review.get_key_plies(KEY_DIAGRAMS);
+        if !key_plies.is_empty() {
+            markdown.push_str("\n## Key moments\n");
+        }
+        for ply in key_plies {
+            let state = GameState::from_fen(&ply.fen)?;
+            let image = STANDARD.encode(render_board_png(&state, color, theme)?);
+            markdown.push_str(&format!(
+                "\n### {} ({})\n\n![Position after {}](data:image/png;base64,{})\n",
+                get_numbered_san(ply),
+                format_eval(ply.eval),
+                get_numbered_san(ply),
+                image
+            ));
+        }
+
+        Ok(markdown)
+    }
+
+    #[instrument(skip_all)]
+    pub fn to_pdf(&self, color: Color, theme: &Theme) -> Result<Vec<u8>, ApiError> {
+        let mut document = PdfDocument::default();
+        let mut page = PdfPage::default();
+
+        let mut y = PAGE_HEIGHT - MARGIN;
+        page.text(MARGIN, y, 18.0, &self.title);
+        y -= 2.0 * LINE_HEIGHT;
+        for line in [
+            format!("White: {}", self.white_player),
+            format!("Black: {}", self.black_player),
+            format!("Date: {}", self.date),
+            format!("Result: {}", self.result),
+        ] {
+            page.text(MARGIN, y, 11.0, &line);
+            y -= LINE_HEIGHT;
+        }
+        for (color, name) in [
+            (Color::WHITE, &self.white_player),
+            (Color::BLACK, &self.black_player),
+        ] {
+            if let Some(accuracy) = self.review.get_accuracy(color) {
+                let line = format!("{}: {}", name, format_accuracy(accuracy));
+                page.text(MARGIN, y, 11.0, &line);
+                y -= LINE_HEIGHT;
+            }
+        }
+
+        // Eval graph, white's advantage upwards
+        let graph_height = 120.0;
+        let graph_width = PAGE_WIDTH - 2.0 * MARGIN;
+        y -= LINE_HEIGHT + graph_height;
+        page.rectangle(MARGIN, y, graph_width, graph_height);
+        let center = y + graph_height / 2.0;
+        page.polyline(&[(MARGIN, center), (MARGIN + graph_width, center)], 0.25);
+        let evals = self.review.get_evals();
+        let step = graph_width / (evals.len().max(2) - 1) as f32;
+        let points: Vec<(f32, f32)> = evals
+            .iter()
+            .enumerate()
+            .map(|(index, eval)| {
+                let value = eval.clamp(&-GRAPH_CAP, &GRAPH_CAP);
+                (
+                    MARGIN + index as f32 * step,
+                    center + *value as f32 / GRAPH_CAP as f32 * graph_height / 2.0,
+                )
+            })
+            .collect();
+        page.polyline(&points, 1.0);
+        y -= 2.0 * LINE_HEIGHT;
+
+        for line in wrap(&self.get_movetext(), LINE_CHARACTERS) {
+            if y < MARGIN {
+                document.add_page(page);
+                page = PdfPage::default();
+                y = PAGE_HEIGHT - MARGIN;
+            }
+            page.text(MARGIN, y, 10.0, &line);
+            y -= LINE_HEIGHT;
+        }
+        document.add_page(page);
+
+        let diagram_size = PAGE_WIDTH - 2.0 * MARGIN;
+        for ply in self.review.get_key_plies(KEY_DIAGRAMS) {
+            let mut page = PdfPage::default();
+            let state = GameState::from_fen(&ply.fen)?;
+            page.text(
+                MARGIN,
+                PAGE_HEIGHT - MARGIN,
+                14.0,
+                &format!("{} ({})", get_numbered_san(ply), format_eval(ply.eval)),
+            );
+            page.image(
+                MARGIN,
+                PAGE_HEIGHT - 2.0 * MARGIN - diagram_size,
+                diagram_size,
+                diagram_size,
+                render_board_jpeg(&state, color, theme)?,
+            );
+            document.add_page(page);
+        }
+
+        Ok(document.to_bytes())
+    }
+
+    /// Move number with the white and black ply, black's is missing if the game ended after white's
+    fn get_full_moves(&self) -> Vec<(usize, [Option<&ReviewedPly>; 2])> {
+        self.review
+            .plies
+            .chunks(2)
+            .enumerate()
+            .map(|(index, plies)| (index + 1, [plies.first(), plies.get(1)]))
+            .collect()
+    }
+
+    fn get_movetext(&self) -> String {
+        let mut moves: Vec<String> = self
+            .get_full_moves()
+            .into_iter()
+            .map(|(number, plies)| {
+                let sans: Vec<String> = plies
+                    .iter()
+                    .flatten()
+                    .map(|ply| get_annotated_san(ply))
+                    .collect();
+                format!("{}. {}", number, sans.join(" "))
+            })
+            .collect();
+        moves.push(self.result.clone());
+        moves.join(" ")
+    }
+}
+
+fn get_annotated_san(ply: &ReviewedPly) -> String {
+    format!(
+        "{}{}",
+        ply.san,
+        ply.annotation.as_deref().unwrap_or_default()
+    )
+}
+
+/// For example 12. Nf3 or 12... Nf6
+fn get_numbered_san(ply: &ReviewedPly) -> String {
+    let dots = if ply.color == Color::BLACK {
+        "..."
+    } else {
+        "."
+    };
+    format!("{}{} {}", ply.ply.div_ceil(2), dots, get_annotated_san(ply))
+}
+
+/// Evaluations in pawns, forced mates as #
+pub fn format_eval(eval: i32) -> String {
+    match eval {
+        MATE_SCORE => "+#".to_string(),
+        eval if eval == -MATE_SCORE => "-#".to_string(),
+        eval => format!("{:+.2}", eval as f32 / 100.0),
+    }
+}
+
+/// For example 87.3% accuracy, ACPL 24
+pub fn format_accuracy(accuracy: PlayerAccuracy) -> String {
+    format!("{:.1}% accuracy, ACPL {}", accuracy.accuracy, accuracy.acpl)
+}
+
+/// A one line graph of all evaluations, a full block meaning a decisive advantage for white
+pub fn get_sparkline(evals: &[i32]) -> String {
+    evals
+        .iter()
+        .map(|eval| {
+            let value = (eval.clamp(&-GRAPH_CAP, &GRAPH_CAP) + GRAPH_CAP) as usize;
+            SPARKS[value * (SPARKS.len() - 1) / (2 * GRAPH_CAP as usize)]
+        })
+        .collect()
+}
+
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + word.len() + 1 > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(get_sparkline(&[-5000, 0, 1000]), "▁▄█");
+    }
+
+    #[test]
+    fn test_format_eval() {
+        assert_eq!(format_eval(35), "+0.35");
+        assert_eq!(format_eval(-120), "-1.20");
+        assert_eq!(format_eval(-MATE_SCORE), "-#");
+        assert_eq!(
+            format_accuracy(PlayerAccuracy {
+                accuracy: 87.26,
+                acpl: 24
+            }),
+            "87.3% accuracy, ACPL 24"
+        );
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(
+            wrap("1. e4 e5 2. Nf3 Nc6", 9),
+            vec!["1. e4 e5", "2. Nf3", "Nc6"]
+        );
+    }
+}
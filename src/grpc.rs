@@ -0,0 +1,348 @@
+use std::net::SocketAddr;
+
+use axum::http::Method;
+use tokio_util::sync::CancellationToken;
+use tonic::{transport::Server, Code, Request, Response, Status};
+
+use crate::{
+    entities::user::User,
+    error::ApiError,
+    error_reporting,
+    game::color::Color,
+    middleware::rate_limit::bucket_for_request,
+    models::{
+        move_models::{LegalMoves, MoveQuery},
+        session_models::SessionInfo,
+    },
+    services::{session_service, user_service},
+    AppState,
+};
+
+pub mod proto {
+    tonic::include_proto!("lemon_chess");
+}
+
+use proto::sessions_server::{Sessions, SessionsServer};
+
+/// Serves the gRPC API on its own port until shutdown is requested
+pub async fn serve(
+    state: AppState,
+    port: u16,
+    shutdown: CancellationToken,
+) -> Result<(), tonic::transport::Error> {
+    let address = SocketAddr::from(([0, 0, 0, 0], port));
+    println!("Serving gRPC on {}", address);
+    Server::builder()
+        .add_service(SessionsServer::new(GrpcSessions::new(state)))
+        .serve_with_shutdown(address, shutdown.cancelled_owned())
+        .await
+}
+
+/// The session endpoints over gRPC, sharing the session service with the HTTP handlers
+pub struct GrpcSessions {
+    state: AppState,
+}
+
+impl GrpcSessions {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// Authenticates and rate limits a call the same way as the HTTP route it mirrors
+    async fn authenticate<T>(
+        &self,
+        request: &Request<T>,
+        method: Method,
+        path: &str,
+    ) -> Result<User, ApiError> {
+        let api_key = request
+            .metadata()
+            .get("x-api-key")
+            .ok_or(ApiError::AuthorizationError(
+                "API key metadata is missing, check /docs for more information".to_string(),
+            ))?
+            .to_str()
+            .map_err(|_| {
+                ApiError::AuthorizationError(
+                    "Invalid API key format, check /docs for more information".to_string(),
+                )
+            })?;
+
+        let user = user_service::find_user_by_api_key(&self.state, api_key).await?;
+        let address = request.remote_addr().map(|address| address.ip());
+        let bucket = bucket_for_request(user.as_ref(), address, &method, path);
+        if let Some((key, config)) = bucket {
+            match self.state.rate_limiter.take(&key, &config).await {
+                Ok(outcome) if !outcome.allowed => {
+                    return Err(ApiError::RateLimited(outcome.retry_after_ms * 1_000_000))
+                }
+                Ok(_) => {}
+                // A broken rate limiting store shouldn't take the whole API down with it
+                Err(error) => tracing::warn!(%error, "Rate limiting failed"),
+            }
+        }
+
+        user_service::authorize(&self.state, user, api_key, method.as_str(), path).await
+    }
+}
+
+#[tonic::async_trait]
+impl Sessions for GrpcSessions {
+    async fn get_session(
+        &self,
+        request: Request<proto::SessionRequest>,
+    ) -> Result<Response<proto::SessionInfo>, Status> {
+        let user = self.authenticate(&request, Method::GET, "/session").await?;
+        let session =
+            session_service::find_session(&self.state, &request.get_ref().session_id).await?;
+        let session = session_service::catch_up_ai_move(&self.state, session).await?;
+        let info = SessionInfo::from_session(&self.state, session, user.key).await?;
+        Ok(Response::new(info.into()))
+    }
+
+    async fn start_ai_session(
+        &self,
+        request: Request<proto::StartAiSessionRequest>,
+    ) -> Result<Response<proto::SessionInfo>, Status> {
+        let user = self
+            .authenticate(&request, Method::POST, "/session")
+            .await?;
+        let session =
+            session_service::start_ai_session(&self.state, &user, Default::default()).await?;
+        let info = SessionInfo::from_session(&self.state, session, user.key).await?;
+        Ok(Response::new(info.into()))
+    }
+
+    async fn get_legal_moves(
+        &self,
+        request: Request<proto::SessionRequest>,
+    ) -> Result<Response<proto::LegalMoves>, Status> {
+        let user = self
+            .authenticate(&request, Method::GET, "/session/move")
+            .await?;
+        let session =
+            session_service::find_session(&self.state, &request.get_ref().session_id).await?;
+        let legal_moves = session_service::get_legal_moves(&session, &user)?;
+        Ok(Response::new(legal_moves.into()))
+    }
+
+    async fn play_move(
+        &self,
+        request: Request<proto::MoveRequest>,
+    ) -> Result<Response<proto::SessionInfo>, Status> {
+        let user = self
+            .authenticate(&request, Method::POST, "/session/move")
+            .await?;
+        let move_request = request.into_inner();
+        let (session, lock) =
+            session_service::lock_session(&self.state, &move_request.session_id).await?;
+        let chess_move = MoveQuery {
+            from: move_request.from,
+            to: move_request.to,
+            promotion: move_request.promotion,
+            castle_kingside: Some(move_request.castle_kingside),
+            castle_queenside: Some(move_request.castle_queenside),
+        };
+        let info =
+            session_service::play_move(&self.state, user, session, lock, &chess_move).await?;
+        Ok(Response::new(info.into()))
+    }
+}
+
+impl From<ApiError> for Status {
+    fn from(error: ApiError) -> Self {
+        error_reporting::capture(&error);
+        let (code, message) = match error {
+            ApiError::AuthorizationError(message) => (Code::Unauthenticated, message),
+            ApiError::BadRequest(message) => (Code::InvalidArgument, message),
+            ApiError::Conflict(message) => (Code::Aborted, message),
+            ApiError::DatabaseError(message) => (
+                Code::Internal,
+                format!("A database error occurred: {}", message),
+            ),
+            ApiError::HeadersTooLarge(message) => (Code::ResourceExhausted, message),
+            ApiError::IllegalMove(reason) => {
+                let mut status = Status::new(Code::InvalidArgument, reason.describe());
+                let code = serde_json::to_value(reason).ok();
+                if let Some(Ok(value)) =
+                    code.as_ref().and_then(|code| code.as_str()).map(str::parse)
+                {
+                    status.metadata_mut().insert("illegal-move-reason", value);
+                }
+                return status;
+            }
+            ApiError::NoPermission(message) => (Code::PermissionDenied, message),
+            ApiError::NotFound(message) => (Code::NotFound, message),
+            ApiError::ParseError(message) => (Code::InvalidArgument, message),
+            ApiError::PayloadTooLarge(message) => (Code::ResourceExhausted, message),
+            ApiError::RateLimited(time_left_nanos) => (
+                Code::ResourceExhausted,
+                format!("Cooldown: {}nanos", time_left_nanos),
+            ),
+            ApiError::SerializationError(message) => (
+                Code::Internal,
+                format!("A serialization error occured: {}", message),
+            ),
+            ApiError::ServerError(message) => (Code::Internal, message),
+        };
+        Status::new(code, message)
+    }
+}
+
+impl From<Color> for proto::Color {
+    fn from(color: Color) -> Self {
+        match color {
+            Color::WHITE => Self::White,
+            Color::BLACK => Self::Black,
+            Color::NONE => Self::None,
+        }
+    }
+}
+
+impl From<SessionInfo> for proto::SessionInfo {
+    fn from(info: SessionInfo) -> Self {
+        Self {
+            id: info.id,
+            name: info.name,
+            white_player: info.white_player,
+            black_player: info.black_player,
+            fen: info.fen,
+            san: info.san,
+            color_to_move: proto::Color::from(info.color_to_move).into(),
+            checkers: info.checkers,
+            your_turn: info.your_turn,
+            finished: info.finished,
+            winner: proto::Color::from(info.winner).into(),
+            draw: info.draw,
+            checkmate: info.checkmate,
+            resign: info.resign,
+            stalemate: info.stalemate,
+            remis: info.remis,
+            dead_position: info.dead_position,
+            aborted: info.aborted,
+            timeout: info.timeout,
+            paused: info.paused,
+        }
+    }
+}
+
+impl From<LegalMoves> for proto::LegalMoves {
+    fn from(legal_moves: LegalMoves) -> Self {
+        Self {
+            color: proto::Color::from(legal_moves.color).into(),
+            current_turn: legal_moves.current_turn,
+            moves: legal_moves
+                .moves
+                .into_iter()
+                .map(|legal_move| proto::LegalMove {
+                    from: legal_move.from,
+                    to: legal_move.to,
+                    capture: legal_move.capture,
+                    promotion: legal_move.promotion,
+                    check: legal_move.check,
+                })
+                .collect(),
+            castle_kingside: legal_moves.castle_kingside,
+            castle_queenside: legal_moves.castle_queenside,
+            castle_kingside_target: legal_moves.castle_kingside_target,
+            castle_queenside_target: legal_moves.castle_queenside_target,
+            en_passant: legal_moves.en_passant,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use mongodb::bson::oid::ObjectId;
+
+    use super::*;
+    use crate::test_utils::*;
+
+    #[tokio::test]
+    async fn test_grpc_sessions() {
+        use proto::{sessions_server::Sessions, MoveRequest, SessionRequest};
+        use tonic::{Code, Request};
+
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let sessions = GrpcSessions::new(state.clone());
+        fn with_key<T>(message: T, key: &str) -> Request<T> {
+            let mut request = Request::new(message);
+            request
+                .metadata_mut()
+                .insert("x-api-key", key.parse().unwrap());
+            request
+        }
+
+        let status = sessions
+            .start_ai_session(Request::new(Default::default()))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        let info = sessions
+            .start_ai_session(with_key(Default::default(), &lemon))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(info.your_turn);
+        let status = sessions
+            .start_ai_session(with_key(Default::default(), &lemon))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        let session_request = SessionRequest {
+            session_id: info.id.clone(),
+        };
+        let legal_moves = sessions
+            .get_legal_moves(with_key(session_request.clone(), &lemon))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(legal_moves.current_turn);
+        assert_eq!(legal_moves.moves.len(), 20);
+
+        let legal_move = &legal_moves.moves[0];
+        let played = sessions
+            .play_move(with_key(
+                MoveRequest {
+                    session_id: info.id.clone(),
+                    from: Some(legal_move.from.clone()),
+                    to: Some(legal_move.to.clone()),
+                    ..Default::default()
+                },
+                &lemon,
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_ne!(played.fen, info.fen);
+
+        // Both transports share the sessions
+        let uri = "/session";
+        let headers = [("session-id", info.id.as_str())];
+        let (status, rest_info) =
+            send_with_headers(&state, Method::GET, uri, &lemon, &headers).await;
+        assert_eq!(status, StatusCode::OK);
+        let grpc_info = sessions
+            .get_session(with_key(session_request, &lemon))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(rest_info["fen"], grpc_info.fen);
+        assert!(grpc_info.your_turn);
+
+        let status = sessions
+            .get_session(with_key(
+                SessionRequest {
+                    session_id: ObjectId::new().to_hex(),
+                },
+                &lemon,
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+    }
+}
@@ -0,0 +1,258 @@
+use axum::{
+    extract::DefaultBodyLimit,
+    middleware::{from_fn, from_fn_with_state},
+    Router,
+};
+use locks::LockManager;
+use middleware::{
+    catch_panic::PanicHandler,
+    rate_limit::{rate_limit, RateLimiter},
+    request_limits::{limit_request_size, MAX_BODY_BYTES},
+};
+use notifications::Notifier;
+use presence::PresenceTracker;
+use scheduler::SchedulerMetrics;
+use server::ServerConfig;
+use std::{io, sync::Arc};
+use storage::Storage;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tower_http::catch_panic::CatchPanicLayer;
+use utils::random::GameRng;
+use utoipa::OpenApi;
+use utoipa_rapidoc::RapiDoc;
+use utoipa_redoc::{Redoc, Servable};
+use utoipa_swagger_ui::SwaggerUi;
+
+mod chess_com;
+pub mod cli;
+mod database;
+mod docs;
+pub mod error;
+mod error_reporting;
+pub mod grpc;
+mod locks;
+mod notifications;
+mod presence;
+mod render_worker;
+mod scheduler;
+mod self_check;
+mod server;
+mod shutdown;
+mod storage;
+mod telemetry;
+#[cfg(test)]
+mod test_utils;
+
+pub mod entities {
+    pub mod audit_entry;
+    pub mod friendship;
+    pub mod notification;
+    pub mod render_job;
+    pub mod room;
+    pub mod session;
+    pub mod session_mark;
+    pub mod user;
+    pub mod webhook;
+}
+
+pub mod extractors {
+    pub mod authentication;
+    pub mod session_extractor;
+}
+
+pub mod game {
+    pub use lemon_chess_engine::{
+        bit_board, chess_board, color, error, opening, pgn, phase, piece, position, rays, review,
+        state, termination, transposition,
+    };
+
+    pub mod ai;
+    pub mod render;
+    pub mod report;
+}
+
+pub mod middleware {
+    pub mod catch_panic;
+    pub mod rate_limit;
+    pub mod request_limits;
+}
+
+pub mod models {
+    pub mod audit_models;
+    pub mod enums;
+    pub mod friend_models;
+    pub mod move_models;
+    pub mod notification_models;
+    pub mod query_models;
+    pub mod render_job_models;
+    pub mod response_models;
+    pub mod review_models;
+    pub mod room_models;
+    pub mod scheduler_models;
+    pub mod session_models;
+    pub mod user_models;
+    pub mod webhook_models;
+}
+
+pub mod resources {
+    pub mod admin;
+    pub mod friend;
+    pub mod health;
+    pub mod notification;
+    pub mod ping;
+    pub mod presence;
+    pub mod room;
+    pub mod session;
+    pub mod user;
+    pub mod version;
+}
+
+pub mod services {
+    pub mod session_service;
+    pub mod user_service;
+}
+
+pub mod utils {
+    pub mod build_info;
+    pub mod etag;
+    pub mod http;
+    pub mod pdf;
+    pub mod random;
+    pub mod sanitize;
+    pub mod signing;
+    pub mod time_operations;
+}
+
+#[derive(Clone)]
+pub struct AppState {
+    storage: Arc<dyn Storage>,
+    rate_limiter: RateLimiter,
+    /// Keeps replicas from changing the same session or room at once
+    locks: LockManager,
+    /// Work which has to be finished before shutting down
+    tasks: TaskTracker,
+    /// Live session updates for the subscribers connected to this instance
+    notifier: Notifier,
+    /// Who is currently viewing which session
+    presence: PresenceTracker,
+    /// Outcome of the background maintenance tasks of this instance
+    scheduler: SchedulerMetrics,
+    /// Turns panicking requests into 500s and counts them
+    panics: PanicHandler,
+    /// Randomness of game setup and AI play, seeded with RNG_SEED
+    rng: GameRng,
+}
+
+/// All routes and the API docs, separate from main so tests can send requests without a server
+pub fn app(app_state: AppState) -> Router {
+    Router::<AppState>::new()
+        .nest("/", resources::admin::router())
+        .nest("/", resources::friend::router())
+        .nest("/", resources::health::router())
+        .nest("/", resources::notification::router())
+        .nest("/", resources::ping::router())
+        .nest("/", resources::presence::router())
+        .nest("/", resources::room::router())
+        .nest("/", resources::session::router())
+        .nest("/", resources::user::router())
+        .nest("/", resources::version::router())
+        .merge(SwaggerUi::new("/swagger").url("/api-docs/openapi.json", docs::ApiDoc::openapi()))
+        .merge(Redoc::with_url("/redoc", docs::ApiDoc::openapi()))
+        .merge(RapiDoc::new("/api-docs/openapi.json").path("/docs"))
+        .layer(from_fn_with_state(app_state.clone(), rate_limit))
+        .layer(from_fn(limit_request_size))
+        .layer(DefaultBodyLimit::max(MAX_BODY_BYTES))
+        // Inside the panic layer, so panics are reported with the scope of their request
+        .layer(from_fn(error_reporting::report_errors))
+        .layer(CatchPanicLayer::custom(app_state.panics.clone()))
+        .layer(from_fn(telemetry::trace_request))
+        .with_state(app_state)
+}
+
+/// Sets up storage and background workers and serves the API until a shutdown signal arrives
+pub async fn run() -> io::Result<()> {
+    let storage = database::setup()
+        .await
+        .expect("Failed to set up the storage.");
+    let server_config = ServerConfig::from_env();
+    let tracer_provider = telemetry::setup();
+    let _error_reporting = error_reporting::setup();
+
+    let redis = database::setup_redis().await;
+    let rate_limiter = RateLimiter::setup(redis.clone());
+    let locks = LockManager::setup(redis.clone());
+    let presence = PresenceTracker::setup(redis);
+
+    let app_state = AppState {
+        storage,
+        rate_limiter,
+        locks,
+        tasks: TaskTracker::new(),
+        notifier: Notifier::new(),
+        presence,
+        scheduler: SchedulerMetrics::new(),
+        panics: PanicHandler::new(),
+        rng: GameRng::from_env(),
+    };
+
+    let problems = self_check::run(&app_state).await;
+    if !problems.is_empty() {
+        panic!("Startup self-check failed:\n- {}", problems.join("\n- "));
+    }
+
+    let worker_shutdown = CancellationToken::new();
+    app_state.tasks.spawn(render_worker::run(
+        app_state.clone(),
+        worker_shutdown.clone(),
+    ));
+    let migration_storage = app_state.storage.clone();
+    app_state.tasks.spawn(async move {
+        if let Err(error) = migration_storage.migrate().await {
+            tracing::error!(%error, "Failed to migrate the storage");
+        }
+    });
+    app_state
+        .tasks
+        .spawn(scheduler::run(app_state.clone(), worker_shutdown.clone()));
+    app_state.tasks.spawn(notifications::run(
+        app_state.clone(),
+        worker_shutdown.clone(),
+    ));
+
+    let app = app(app_state.clone());
+
+    let server_shutdown = CancellationToken::new();
+    tokio::spawn({
+        let notifier = app_state.notifier.clone();
+        let server_shutdown = server_shutdown.clone();
+        async move {
+            shutdown::signal().await;
+            notifier.close();
+            server_shutdown.cancel();
+        }
+    });
+    let grpc = server_config.grpc_port.map(|port| {
+        tokio::spawn(grpc::serve(
+            app_state.clone(),
+            port,
+            server_shutdown.clone(),
+        ))
+    });
+    server::serve(app, &server_config, server_shutdown).await?;
+    if let Some(grpc) = grpc {
+        grpc.await?.map_err(io::Error::other)?;
+    }
+
+    println!("Waiting for pending tasks...");
+    worker_shutdown.cancel();
+    app_state.tasks.close();
+    app_state.tasks.wait().await;
+    if let Some(tracer_provider) = tracer_provider {
+        if let Err(error) = tracer_provider.shutdown() {
+            tracing::warn!(%error, "Failed to send the remaining traces");
+        }
+    }
+    println!("Shut down gracefully");
+
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use redis::{aio::MultiplexedConnection, Script};
+use uuid::Uuid;
+
+use crate::{error::ApiError, utils::time_operations::timestamp_now_nanos};
+
+/// A lock expires on its own after this, in case its holder died
+const LOCK_TTL_MS: u64 = 10_000;
+const ACQUIRE_TIMEOUT_MS: u64 = 5_000;
+const RETRY_INTERVAL_MS: u64 = 25;
+
+/// Only deletes the lock if it is still held with the given token
+const REDIS_RELEASE: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+#[derive(Clone)]
+enum Backend {
+    /// Token and expiry timestamp in milliseconds by key
+    Memory(Arc<Mutex<HashMap<String, (String, u64)>>>),
+    Redis(MultiplexedConnection),
+}
+
+/// Mutual exclusion across all API replicas if backed by Redis, otherwise only within this process
+#[derive(Clone)]
+pub struct LockManager {
+    backend: Backend,
+}
+
+impl LockManager {
+    pub fn new_in_memory() -> Self {
+        Self {
+            backend: Backend::Memory(Arc::new(Mutex::new(HashMap::new()))),
+        }
+    }
+
+    pub fn new_redis(connection: MultiplexedConnection) -> Self {
+        Self {
+            backend: Backend::Redis(connection),
+        }
+    }
+
+    pub fn setup(redis: Option<MultiplexedConnection>) -> Self {
+        match redis {
+            Some(connection) => Self::new_redis(connection),
+            None => Self::new_in_memory(),
+        }
+    }
+
+    /// Waits until the lock is free, the lock is released once the returned guard is dropped
+    pub async fn acquire(&self, key: &str) -> Result<Lock, ApiError> {
+        let key = format!("lock:{}", key);
+        let token = Uuid::new_v4().simple().to_string();
+
+        let mut waited_ms = 0;
+        while !self.try_acquire(&key, &token).await? {
+            if waited_ms >= ACQUIRE_TIMEOUT_MS {
+                return Err(ApiError::Conflict(
+                    "The resource is busy, try again later.".to_string(),
+                ));
+            }
+            tokio::time::sleep(Duration::from_millis(RETRY_INTERVAL_MS)).await;
+            waited_ms += RETRY_INTERVAL_MS;
+        }
+
+        Ok(Lock {
+            manager: self.clone(),
+            key,
+            token,
+            released: false,
+        })
+    }
+
+    async fn try_acquire(&self, key: &str, token: &str) -> Result<bool, ApiError> {
+        match &self.backend {
+            Backend::Memory(locks) => {
+                let now_ms = timestamp_now_nanos() / 1_000_000;
+                let mut locks = locks
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                locks.retain(|_, (_, expires_ms)| *expires_ms > now_ms);
+                if locks.contains_key(key) {
+                    return Ok(false);
+                }
+                locks.insert(key.to_string(), (token.to_string(), now_ms + LOCK_TTL_MS));
+                Ok(true)
+            }
+            Backend::Redis(connection) => {
+                let result: Option<String> = redis::cmd("SET")
+                    .arg(key)
+                    .arg(token)
+                    .arg("NX")
+                    .arg("PX")
+                    .arg(LOCK_TTL_MS)
+                    .query_async(&mut connection.clone())
+                    .await
+                    .map_err(|err| ApiError::ServerError(err.to_string()))?;
+                Ok(result.is_some())
+            }
+        }
+    }
+
+    async fn release(&self, key: &str, token: &str) -> Result<(), ApiError> {
+        match &self.backend {
+            Backend::Memory(locks) => {
+                let mut locks = locks
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                if locks
+                    .get(key)
+                    .is_some_and(|(held_token, _)| held_token == token)
+                {
+                    locks.remove(key);
+                }
+                Ok(())
+            }
+            Backend::Redis(connection) => {
+                let _: u8 = Script::new(REDIS_RELEASE)
+                    .key(key)
+                    .arg(token)
+                    .invoke_async(&mut connection.clone())
+                    .await
+                    .map_err(|err| ApiError::ServerError(err.to_string()))?;
+                Ok(())
+            }
+        }
+    }
+}
+
+pub struct Lock {
+    manager: LockManager,
+    key: String,
+    token: String,
+    released: bool,
+}
+
+impl Lock {
+    pub async fn release(mut self) {
+        self.released = true;
+        if let Err(error) = self.manager.release(&self.key, &self.token).await {
+            tracing::warn!(key = self.key, %error, "Failed to release lock");
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+
+        // Early returns (e.g. errors) end up here, the release can't be awaited in drop
+        let manager = self.manager.clone();
+        let key = std::mem::take(&mut self.key);
+        let token = std::mem::take(&mut self.token);
+        tokio::spawn(async move {
+            if let Err(error) = manager.release(&key, &token).await {
+                tracing::warn!(key, %error, "Failed to release lock");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_lock() {
+        let locks = LockManager::new_in_memory();
+
+        let lock = locks.acquire("session").await.unwrap();
+        assert!(!locks.try_acquire("lock:session", "other").await.unwrap());
+        assert!(locks.try_acquire("lock:room", "other").await.unwrap());
+
+        lock.release().await;
+        assert!(locks.try_acquire("lock:session", "other").await.unwrap());
+
+        // Releasing with a stale token doesn't free someone else's lock
+        locks.release("lock:session", "stale").await.unwrap();
+        assert!(!locks.try_acquire("lock:session", "third").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_lock_released_on_drop() {
+        let locks = LockManager::new_in_memory();
+        drop(locks.acquire("session").await.unwrap());
+
+        let lock = locks.acquire("session").await;
+        assert!(lock.is_ok());
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    any::Any,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use axum::{
+    body::Body,
+    http::HeaderValue,
+    response::{IntoResponse, Response},
+};
+use tower_http::catch_panic::ResponseForPanic;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+
+/// Lets clients quote the failed request, the same id is logged with the panic message
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Answers requests whose handler panicked with a 500 instead of dropping the connection
+/// Also counts the panics of this instance
+#[derive(Clone, Default)]
+pub struct PanicHandler {
+    panics: Arc<AtomicU64>,
+}
+
+impl PanicHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_panic_count(&self) -> u64 {
+        self.panics.load(Ordering::Relaxed)
+    }
+}
+
+impl ResponseForPanic for PanicHandler {
+    type ResponseBody = Body;
+
+    fn response_for_panic(&mut self, error: Box<dyn Any + Send + 'static>) -> Response<Body> {
+        self.panics.fetch_add(1, Ordering::Relaxed);
+        let message = if let Some(message) = error.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = error.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "Unknown panic".to_string()
+        };
+        let request_id = Uuid::new_v4().to_string();
+        tracing::error!(request_id, message, "Request panicked");
+
+        let mut response = ApiError::ServerError(format!(
+            "An unexpected error occurred, request id: {}",
+            request_id
+        ))
+        .into_response();
+        if let Ok(value) = HeaderValue::from_str(&request_id) {
+            response.headers_mut().insert(REQUEST_ID_HEADER, value);
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        http::{Request, StatusCode},
+        routing::get,
+        Router,
+    };
+    use tower::ServiceExt;
+    use tower_http::catch_panic::CatchPanicLayer;
+
+    async fn panicking_handler() -> &'static str {
+        panic!("index out of bounds")
+    }
+
+    #[tokio::test]
+    async fn test_catch_panic() {
+        let handler = PanicHandler::new();
+        let app = Router::new()
+            .route("/", get(panicking_handler))
+            .layer(CatchPanicLayer::custom(handler.clone()));
+
+        let request = Request::builder().uri("/").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(response.headers().contains_key(REQUEST_ID_HEADER));
+        assert_eq!(handler.get_panic_count(), 1);
+    }
+}
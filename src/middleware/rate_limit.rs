@@ -0,0 +1,721 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use redis::{aio::MultiplexedConnection, Script};
+
+use crate::{
+    entities::user::{User, BOT_KEY_PREFIX},
+    error::ApiError,
+    models::enums::PermissionLevel,
+    services::user_service,
+    utils::time_operations::timestamp_now_nanos,
+    AppState,
+};
+
+/// Requests without a known API key and without a peer address share the buckets of this client
+const UNKNOWN_CLIENT: &str = "unknown";
+
+const REDIS_TOKEN_BUCKET: &str = r#"
+local capacity = tonumber(ARGV[1])
+local interval = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'stamp')
+local tokens = tonumber(bucket[1]) or capacity
+local stamp = tonumber(bucket[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - stamp) / interval)
+local allowed = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'stamp', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil((capacity - tokens) * interval) + 1000)
+return {allowed, tostring(tokens)}
+"#;
+
+/// A token bucket holding up to `capacity` requests, regaining one every `refill_interval_ms`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BucketConfig {
+    pub capacity: u32,
+    pub refill_interval_ms: u64,
+}
+
+impl BucketConfig {
+    /// Fallback bucket shared by every route without a dedicated one
+    pub const DEFAULT: Self = Self {
+        capacity: 60,
+        refill_interval_ms: 1000,
+    };
+
+    /// A single request per cooldown
+    pub const fn cooldown(seconds: u64) -> Self {
+        Self {
+            capacity: 1,
+            refill_interval_ms: seconds * 1000,
+        }
+    }
+}
+
+/// Routes that are expensive or guessable get their own, stricter bucket
+const ROUTE_BUCKETS: [(Method, &str, &str, BucketConfig); 10] = [
+    // Repeated renders of the same position are served from the render cache
+    (
+        Method::GET,
+        "/session/render",
+        "render",
+        BucketConfig::cooldown(2),
+    ),
+    (
+        Method::GET,
+        "/session/render/history",
+        "render_gif",
+        BucketConfig::cooldown(30),
+    ),
+    (
+        Method::POST,
+        "/session/render/history",
+        "render_gif",
+        BucketConfig::cooldown(30),
+    ),
+    // All review routes evaluate every position of the game, so they share a bucket
+    (
+        Method::GET,
+        "/session/review/evals",
+        "review",
+        BucketConfig::cooldown(30),
+    ),
+    (
+        Method::GET,
+        "/session/review/export",
+        "review",
+        BucketConfig::cooldown(30),
+    ),
+    (
+        Method::GET,
+        "/session/review/pgn",
+        "review",
+        BucketConfig::cooldown(30),
+    ),
+    // With a 10s delay it takes >400 years to traverse all room codes
+    (
+        Method::POST,
+        "/room/join",
+        "join_room",
+        BucketConfig::cooldown(10),
+    ),
+    // Every import fetches up to 13 pages from chess.com
+    (
+        Method::POST,
+        "/user/import/chesscom",
+        "import",
+        BucketConfig::cooldown(60),
+    ),
+    (
+        Method::POST,
+        "/user/bot",
+        "register_bot",
+        BucketConfig::cooldown(60),
+    ),
+    // Keeps names from being squatted in bulk or changed in rapid succession
+    (
+        Method::PATCH,
+        "/user",
+        "rename_user",
+        BucketConfig::cooldown(60),
+    ),
+];
+
+/// Engines play far more moves than people, bot keys get these buckets instead of the regular ones
+const BOT_ROUTE_BUCKETS: [(Method, &str, &str, BucketConfig); 1] = [(
+    Method::POST,
+    "/session/move",
+    "bot_move",
+    BucketConfig {
+        capacity: 300,
+        refill_interval_ms: 100,
+    },
+)];
+
+/// Trusted keys like Discord bots proxying many people get larger budgets on the cooldowns by permission level
+/// A missing config exempts the level from the bucket, levels without an entry keep the regular bucket
+const TIER_BUCKETS: [(PermissionLevel, &str, Option<BucketConfig>); 6] = [
+    (
+        PermissionLevel::Negotiator,
+        "render",
+        Some(BucketConfig {
+            capacity: 20,
+            refill_interval_ms: 250,
+        }),
+    ),
+    (
+        PermissionLevel::Negotiator,
+        "render_gif",
+        Some(BucketConfig {
+            capacity: 5,
+            refill_interval_ms: 6000,
+        }),
+    ),
+    (
+        PermissionLevel::Negotiator,
+        "join_room",
+        Some(BucketConfig {
+            capacity: 10,
+            refill_interval_ms: 1000,
+        }),
+    ),
+    (PermissionLevel::Admin, "render", None),
+    (PermissionLevel::Admin, "render_gif", None),
+    (PermissionLevel::Admin, "join_room", None),
+];
+
+/// The config of the bucket for the given permission level, None if the level is exempt from it
+pub fn tier_bucket(
+    permission: &PermissionLevel,
+    bucket_id: &str,
+    config: BucketConfig,
+) -> Option<BucketConfig> {
+    TIER_BUCKETS
+        .iter()
+        .find(|(level, id, _)| level == permission && *id == bucket_id)
+        .map_or(Some(config), |(_, _, tier_config)| *tier_config)
+}
+
+/// Every bucket once together with the routes using it, the default bucket comes last
+pub fn list_buckets() -> Vec<(&'static str, BucketConfig, Vec<String>)> {
+    let mut buckets: Vec<(&'static str, BucketConfig, Vec<String>)> = Vec::new();
+    for (method, path, id, config) in ROUTE_BUCKETS.iter() {
+        let route = format!("{} {}", method, path);
+        match buckets.iter_mut().find(|(bucket_id, _, _)| bucket_id == id) {
+            Some((_, _, routes)) => routes.push(route),
+            None => buckets.push((id, *config, vec![route])),
+        }
+    }
+    buckets.push(("default", BucketConfig::DEFAULT, Vec::new()));
+    buckets
+}
+
+/// Buckets are kept per client, which is the user of the API key or the peer address without a known key
+pub fn bucket_key(client: &str, bucket_id: &str) -> String {
+    format!("{}:{}", client, bucket_id)
+}
+
+/// Like bucket_for_route, but bot keys get the relaxed bot buckets where there are some
+pub fn bucket_for_client(
+    client: &str,
+    method: &Method,
+    path: &str,
+) -> (&'static str, BucketConfig) {
+    let bot_bucket = BOT_ROUTE_BUCKETS
+        .iter()
+        .find(|(route_method, route_path, _, _)| route_method == method && *route_path == path)
+        .map(|(_, _, id, config)| (*id, *config));
+    match bot_bucket {
+        Some(bucket) if client.starts_with(BOT_KEY_PREFIX) => bucket,
+        _ => bucket_for_route(method, path),
+    }
+}
+
+pub fn bucket_for_route(method: &Method, path: &str) -> (&'static str, BucketConfig) {
+    ROUTE_BUCKETS
+        .iter()
+        .find(|(route_method, route_path, _, _)| route_method == method && *route_path == path)
+        .map(|(_, _, id, config)| (*id, *config))
+        .unwrap_or(("default", BucketConfig::DEFAULT))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    stamp_ms: u64,
+}
+
+impl Bucket {
+    fn refill(&mut self, config: &BucketConfig, now_ms: u64) {
+        let elapsed = now_ms.saturating_sub(self.stamp_ms) as f64;
+        self.tokens =
+            (self.tokens + elapsed / config.refill_interval_ms as f64).min(config.capacity as f64);
+        self.stamp_ms = now_ms;
+    }
+
+    fn is_full(&self, config: &BucketConfig, now_ms: u64) -> bool {
+        let mut bucket = *self;
+        bucket.refill(config, now_ms);
+        bucket.tokens >= config.capacity as f64
+    }
+}
+
+/// The result of taking a token from a bucket
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitOutcome {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Milliseconds until the next token is available, 0 if there is one right now
+    pub retry_after_ms: u64,
+    /// Milliseconds until the bucket is completely refilled
+    pub reset_after_ms: u64,
+}
+
+impl RateLimitOutcome {
+    fn new(allowed: bool, tokens: f64, config: &BucketConfig) -> Self {
+        let interval = config.refill_interval_ms as f64;
+        let retry_after_ms = if tokens >= 1.0 {
+            0
+        } else {
+            ((1.0 - tokens) * interval).ceil() as u64
+        };
+        let reset_after_ms = ((config.capacity as f64 - tokens).max(0.0) * interval).ceil() as u64;
+
+        Self {
+            allowed,
+            limit: config.capacity,
+            remaining: tokens.floor() as u32,
+            retry_after_ms,
+            reset_after_ms,
+        }
+    }
+
+    fn apply_headers(&self, headers: &mut HeaderMap) {
+        let values = [
+            ("x-ratelimit-limit", self.limit as u64),
+            ("x-ratelimit-remaining", self.remaining as u64),
+            ("x-ratelimit-reset", self.reset_after_ms.div_ceil(1000)),
+        ];
+        for (name, value) in values {
+            headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+        }
+
+        if !self.allowed {
+            headers.insert(
+                HeaderName::from_static("retry-after"),
+                HeaderValue::from(self.retry_after_ms.div_ceil(1000)),
+            );
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Backend {
+    Memory(Arc<Mutex<HashMap<String, (Bucket, BucketConfig)>>>),
+    Redis(MultiplexedConnection),
+}
+
+/// Token buckets per API key, kept in memory or in Redis if REDIS_URL is set
+#[derive(Clone)]
+pub struct RateLimiter {
+    backend: Backend,
+}
+
+impl RateLimiter {
+    pub fn new_in_memory() -> Self {
+        Self {
+            backend: Backend::Memory(Arc::new(Mutex::new(HashMap::new()))),
+        }
+    }
+
+    pub fn new_redis(connection: MultiplexedConnection) -> Self {
+        Self {
+            backend: Backend::Redis(connection),
+        }
+    }
+
+    pub fn setup(redis: Option<MultiplexedConnection>) -> Self {
+        match redis {
+            Some(connection) => Self::new_redis(connection),
+            None => Self::new_in_memory(),
+        }
+    }
+
+    pub async fn take(
+        &self,
+        key: &str,
+        config: &BucketConfig,
+    ) -> Result<RateLimitOutcome, ApiError> {
+        let now_ms = timestamp_now_nanos() / 1_000_000;
+        match &self.backend {
+            Backend::Memory(buckets) => Ok(Self::take_in_memory(buckets, key, config, now_ms)),
+            Backend::Redis(connection) => {
+                let (allowed, tokens): (u8, String) = Script::new(REDIS_TOKEN_BUCKET)
+                    .key(format!("rate_limit:{}", key))
+                    .arg(config.capacity)
+                    .arg(config.refill_interval_ms)
+                    .arg(now_ms)
+                    .invoke_async(&mut connection.clone())
+                    .await
+                    .map_err(|err| ApiError::ServerError(err.to_string()))?;
+                let tokens = tokens.parse::<f64>().unwrap_or_default();
+                Ok(RateLimitOutcome::new(allowed == 1, tokens, config))
+            }
+        }
+    }
+
+    /// The current state of a bucket, without taking a token
+    pub async fn peek(
+        &self,
+        key: &str,
+        config: &BucketConfig,
+    ) -> Result<RateLimitOutcome, ApiError> {
+        let now_ms = timestamp_now_nanos() / 1_000_000;
+        let mut bucket = match &self.backend {
+            Backend::Memory(buckets) => {
+                let buckets = buckets
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                buckets.get(key).map(|(bucket, _)| *bucket)
+            }
+            Backend::Redis(connection) => {
+                let (tokens, stamp_ms): (Option<String>, Option<u64>) = redis::cmd("HMGET")
+                    .arg(format!("rate_limit:{}", key))
+                    .arg("tokens")
+                    .arg("stamp")
+                    .query_async(&mut connection.clone())
+                    .await
+                    .map_err(|err| ApiError::ServerError(err.to_string()))?;
+                tokens
+                    .and_then(|tokens| tokens.parse::<f64>().ok())
+                    .zip(stamp_ms)
+                    .map(|(tokens, stamp_ms)| Bucket { tokens, stamp_ms })
+            }
+        }
+        .unwrap_or(Bucket {
+            tokens: config.capacity as f64,
+            stamp_ms: now_ms,
+        });
+        bucket.refill(config, now_ms);
+        Ok(RateLimitOutcome::new(
+            bucket.tokens >= 1.0,
+            bucket.tokens,
+            config,
+        ))
+    }
+
+    /// Drops the in-memory buckets which are full again, returns how many were dropped
+    /// Redis expires them by itself
+    pub fn cleanup(&self) -> u64 {
+        let Backend::Memory(buckets) = &self.backend else {
+            return 0;
+        };
+        let now_ms = timestamp_now_nanos() / 1_000_000;
+        let mut buckets = buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let before = buckets.len();
+        buckets.retain(|_, (bucket, config)| !bucket.is_full(config, now_ms));
+        (before - buckets.len()) as u64
+    }
+
+    fn take_in_memory(
+        buckets: &Mutex<HashMap<String, (Bucket, BucketConfig)>>,
+        key: &str,
+        config: &BucketConfig,
+        now_ms: u64,
+    ) -> RateLimitOutcome {
+        let mut buckets = buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let (bucket, _) = buckets.entry(key.to_string()).or_insert((
+            Bucket {
+                tokens: config.capacity as f64,
+                stamp_ms: now_ms,
+            },
+            *config,
+        ));
+        bucket.refill(config, now_ms);
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        RateLimitOutcome::new(allowed, bucket.tokens, config)
+    }
+}
+
+/// The user of the API key as the rate limiter found it, so ExtractUser doesn't look it up again
+#[derive(Clone)]
+pub struct ResolvedUser {
+    pub api_key: String,
+    pub user: Option<User>,
+}
+
+/// Limits every route per user, falling back to the peer address for requests without a known API key
+pub async fn rate_limit(
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let api_key = request
+        .headers()
+        .get("x-api-key")
+        .and_then(|api_key| api_key.to_str().ok())
+        .map(str::to_string);
+    // A failed lookup is limited like an unknown key, ExtractUser looks the user up again and reports the error
+    let resolved = match api_key {
+        Some(api_key) => user_service::find_user_by_api_key(&state, &api_key)
+            .await
+            .ok()
+            .map(|user| ResolvedUser { api_key, user }),
+        None => None,
+    };
+    let address = connect_info.map(|ConnectInfo(address)| address.ip());
+    let bucket = bucket_for_request(
+        resolved
+            .as_ref()
+            .and_then(|resolved| resolved.user.as_ref()),
+        address,
+        request.method(),
+        request.uri().path(),
+    );
+    if let Some(resolved) = resolved {
+        request.extensions_mut().insert(resolved);
+    }
+    let Some((key, config)) = bucket else {
+        return next.run(request).await;
+    };
+
+    let outcome = match state.rate_limiter.take(&key, &config).await {
+        Ok(outcome) => outcome,
+        // A broken rate limiting store shouldn't take the whole API down with it
+        Err(error) => {
+            tracing::warn!(%error, "Rate limiting failed");
+            return next.run(request).await;
+        }
+    };
+
+    let mut response = if outcome.allowed {
+        next.run(request).await
+    } else {
+        ApiError::RateLimited(outcome.retry_after_ms * 1_000_000).into_response()
+    };
+    outcome.apply_headers(response.headers_mut());
+    response
+}
+
+/// Key and config of the bucket a request takes its token from, None if the user is exempt from the bucket
+/// Shared by the HTTP middleware and the gRPC calls, which are limited like the routes they mirror
+/// The user is the one of the API key, None without a key or for an unknown key
+pub fn bucket_for_request(
+    user: Option<&User>,
+    address: Option<IpAddr>,
+    method: &Method,
+    path: &str,
+) -> Option<(String, BucketConfig)> {
+    let (client, permission) = find_client(user, address);
+    let (bucket_id, config) = bucket_for_client(&client, method, path);
+    tier_bucket(&permission, bucket_id, config)
+        .map(|config| (bucket_key(&client, bucket_id), config))
+}
+
+/// Who a request is limited as and their permission level, banned users are regular users
+/// Known keys are limited as their user, so secondary keys share the budget of the primary key
+/// Unknown keys are limited like requests without one, otherwise every made up key would get fresh buckets
+fn find_client(user: Option<&User>, address: Option<IpAddr>) -> (String, PermissionLevel) {
+    match user {
+        Some(user) if user.banned => (user.key.clone(), PermissionLevel::User),
+        Some(user) => (user.key.clone(), user.permission.clone()),
+        None => (
+            address.map_or(UNKNOWN_CLIENT.to_string(), |address| address.to_string()),
+            PermissionLevel::User,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+
+    use super::*;
+    use crate::{models::enums::PermissionLevel, test_utils::*};
+
+    #[test]
+    fn test_token_bucket() {
+        let buckets = Mutex::new(HashMap::new());
+        let config = BucketConfig {
+            capacity: 2,
+            refill_interval_ms: 1000,
+        };
+
+        let outcome = RateLimiter::take_in_memory(&buckets, "key", &config, 0);
+        assert!(outcome.allowed);
+        assert_eq!(outcome.remaining, 1);
+
+        let outcome = RateLimiter::take_in_memory(&buckets, "key", &config, 0);
+        assert!(outcome.allowed);
+        assert_eq!(outcome.remaining, 0);
+        assert_eq!(outcome.reset_after_ms, 2000);
+
+        let outcome = RateLimiter::take_in_memory(&buckets, "key", &config, 500);
+        assert!(!outcome.allowed);
+        assert_eq!(outcome.retry_after_ms, 500);
+
+        let outcome = RateLimiter::take_in_memory(&buckets, "other", &config, 500);
+        assert!(outcome.allowed);
+
+        let outcome = RateLimiter::take_in_memory(&buckets, "key", &config, 1000);
+        assert!(outcome.allowed);
+        assert_eq!(outcome.remaining, 0);
+    }
+
+    #[tokio::test]
+    async fn test_peek() {
+        let limiter = RateLimiter::new_in_memory();
+        let config = BucketConfig::cooldown(10);
+
+        let outcome = limiter.peek("key", &config).await.unwrap();
+        assert_eq!(outcome.remaining, 1);
+        limiter.take("key", &config).await.unwrap();
+        let outcome = limiter.peek("key", &config).await.unwrap();
+        assert!(!outcome.allowed);
+        assert_eq!(outcome.remaining, 0);
+        assert!(outcome.retry_after_ms > 9000);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup() {
+        let limiter = RateLimiter::new_in_memory();
+        let Backend::Memory(buckets) = &limiter.backend else {
+            unreachable!()
+        };
+        let config = BucketConfig::cooldown(10);
+        // Taken long ago, so the bucket is full again
+        RateLimiter::take_in_memory(buckets, "old", &config, 0);
+        limiter.take("recent", &config).await.unwrap();
+
+        assert_eq!(limiter.cleanup(), 1);
+        assert!(!limiter.peek("recent", &config).await.unwrap().allowed);
+        assert_eq!(limiter.cleanup(), 0);
+    }
+
+    #[test]
+    fn test_list_buckets() {
+        let buckets = list_buckets();
+        let (_, config, routes) = buckets
+            .iter()
+            .find(|(id, _, _)| *id == "render_gif")
+            .unwrap();
+        assert_eq!(*config, BucketConfig::cooldown(30));
+        assert_eq!(routes.len(), 2);
+        assert_eq!(buckets.last().unwrap().0, "default");
+    }
+
+    #[test]
+    fn test_bucket_for_client() {
+        assert_eq!(
+            bucket_for_client("bot_abc", &Method::POST, "/session/move").0,
+            "bot_move"
+        );
+        assert_eq!(
+            bucket_for_client("abc", &Method::POST, "/session/move").0,
+            "default"
+        );
+        assert_eq!(
+            bucket_for_client("bot_abc", &Method::GET, "/session/render").0,
+            "render"
+        );
+    }
+
+    #[test]
+    fn test_tier_bucket() {
+        let (id, config) = bucket_for_route(&Method::GET, "/session/render");
+        assert_eq!(
+            tier_bucket(&PermissionLevel::User, id, config),
+            Some(config)
+        );
+        assert_eq!(
+            tier_bucket(&PermissionLevel::Negotiator, id, config).map(|tier| tier.capacity),
+            Some(20)
+        );
+        assert_eq!(tier_bucket(&PermissionLevel::Admin, id, config), None);
+        assert_eq!(
+            tier_bucket(
+                &PermissionLevel::Admin,
+                "review",
+                BucketConfig::cooldown(30)
+            ),
+            Some(BucketConfig::cooldown(30))
+        );
+    }
+
+    #[test]
+    fn test_bucket_for_route() {
+        assert_eq!(
+            bucket_for_route(&Method::GET, "/session/render"),
+            ("render", BucketConfig::cooldown(2))
+        );
+        assert_eq!(
+            bucket_for_route(&Method::POST, "/session/render"),
+            ("default", BucketConfig::DEFAULT)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_tiers() {
+        let state = test_state();
+        let mut requests_until_limited = Vec::new();
+        for (name, permission) in [
+            ("lemon", PermissionLevel::User),
+            ("negotiator", PermissionLevel::Negotiator),
+            ("admin", PermissionLevel::Admin),
+        ] {
+            let key = create_user(&state, name).await;
+            set_permission(&state, &key, permission).await;
+
+            let mut requests = 0;
+            while requests < 20 {
+                let uri = "/room/join?code=NOPE";
+                let (status, _) = send(&state, Method::POST, uri, &key).await;
+                if status == StatusCode::TOO_MANY_REQUESTS {
+                    break;
+                }
+                requests += 1;
+            }
+            requests_until_limited.push(requests);
+        }
+        assert_eq!(requests_until_limited, [1, 10, 20]);
+
+        // Exempt buckets are left out of the usage, the review cooldown applies to every level
+        let admin = create_user(&state, "other").await;
+        set_permission(&state, &admin, PermissionLevel::Admin).await;
+        let (_, usage) = send(&state, Method::GET, "/user/usage", &admin).await;
+        let buckets: Vec<&str> = usage["cooldowns"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|cooldown| cooldown["bucket"].as_str().unwrap())
+            .collect();
+        assert!(buckets.contains(&"review"));
+        assert!(!buckets.contains(&"join_room"));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_clients() {
+        let state = test_state();
+        let key = create_user(&state, "lemon").await;
+        let uri = "/user/keys?name=bot&scope=PLAY_MOVES";
+        let (_, created) = send(&state, Method::POST, uri, &key).await;
+        let secondary_key = created["api_key"].as_str().unwrap();
+
+        // Secondary keys share the budget of the primary key
+        let uri = "/room/join?code=NOPE";
+        let (status, _) = send(&state, Method::POST, uri, &key).await;
+        assert_ne!(status, StatusCode::TOO_MANY_REQUESTS);
+        let (status, _) = send(&state, Method::POST, uri, secondary_key).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+        // Made up keys don't get buckets of their own
+        let (status, _) = send(&state, Method::POST, uri, "made-up").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = send(&state, Method::POST, uri, "also-made-up").await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    }
+}
@@ -0,0 +1,85 @@
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::error::ApiError;
+
+/// Bodies are only used for small JSON documents, bodies without a Content-Length are cut off at this size while being read
+pub const MAX_BODY_BYTES: usize = 64 * 1024;
+pub const MAX_HEADER_COUNT: usize = 64;
+/// Names and values of all headers together
+pub const MAX_HEADER_BYTES: usize = 16 * 1024;
+
+/// Rejects oversized requests before their body is read
+pub async fn limit_request_size(request: Request, next: Next) -> Result<Response, ApiError> {
+    check_headers(request.headers())?;
+    if let Some(length) = get_content_length(request.headers()) {
+        if length > MAX_BODY_BYTES {
+            return Err(ApiError::PayloadTooLarge(format!(
+                "The request body can't be larger than {} bytes",
+                MAX_BODY_BYTES
+            )));
+        }
+    }
+    Ok(next.run(request).await)
+}
+
+fn check_headers(headers: &HeaderMap) -> Result<(), ApiError> {
+    if headers.len() > MAX_HEADER_COUNT {
+        return Err(ApiError::HeadersTooLarge(format!(
+            "A request can't have more than {} headers",
+            MAX_HEADER_COUNT
+        )));
+    }
+    let header_bytes: usize = headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len())
+        .sum();
+    if header_bytes > MAX_HEADER_BYTES {
+        return Err(ApiError::HeadersTooLarge(format!(
+            "The request headers can't be larger than {} bytes",
+            MAX_HEADER_BYTES
+        )));
+    }
+    Ok(())
+}
+
+fn get_content_length(headers: &HeaderMap) -> Option<usize> {
+    headers
+        .get(header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use mongodb::bson::oid::ObjectId;
+
+    use super::*;
+    use crate::test_utils::*;
+
+    #[tokio::test]
+    async fn test_request_size_limits() {
+        let state = test_state();
+        let key = create_user(&state, "lemon").await;
+        let ids = vec![ObjectId::new().to_hex(); MAX_BODY_BYTES / 24];
+        let body = serde_json::json!({ "ids": ids });
+        let (status, _) = send_json(&state, Method::POST, "/sessions/batch", &key, body).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+        let names: Vec<String> = (0..100)
+            .map(|index| format!("x-filler-{}", index))
+            .collect();
+        let headers: Vec<(&str, &str)> = names.iter().map(|name| (name.as_str(), "1")).collect();
+        let (status, _) = send_with_headers(&state, Method::GET, "/", &key, &headers).await;
+        assert_eq!(status, StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+        let (status, _) = send_with_headers(&state, Method::GET, "/", &key, &headers[..10]).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::entities::audit_entry::AuditEntry;
+
+use super::response_models::Pagination;
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AuditAction {
+    UserCreated,
+    DiscordLinked,
+    PermissionChanged,
+    UserBanned,
+    UserUnbanned,
+    UserDeleted,
+    SessionResigned,
+    SessionAborted,
+    KeyCreated,
+    KeyRevoked,
+    UserRenamed,
+    PlatformLinked,
+    PlatformUnlinked,
+    TitleAwarded,
+    TitlesReset,
+    AiMovesRetried,
+}
+
+/// A security relevant event
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AuditEntryInfo {
+    pub action: AuditAction,
+    /// Name of the user who did it
+    pub actor: String,
+    /// Name of the affected user or ID of the affected session, if it wasn't the actor itself
+    pub target: Option<String>,
+    /// What exactly changed
+    pub details: Option<String>,
+    /// The namespace of the actor, empty for the default namespace
+    pub namespace: String,
+    /// UNIX timestamp in nanoseconds when it happened
+    pub created_stamp: u64,
+}
+
+impl From<AuditEntry> for AuditEntryInfo {
+    fn from(entry: AuditEntry) -> Self {
+        Self {
+            action: entry.action,
+            actor: entry.actor,
+            target: entry.target,
+            details: entry.details,
+            namespace: entry.namespace,
+            created_stamp: entry.created_stamp,
+        }
+    }
+}
+
+/// A page of the audit log, newest entries first
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AuditLog {
+    pub entries: Vec<AuditEntryInfo>,
+    pub pagination: Pagination,
+}
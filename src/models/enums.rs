@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-use crate::error::ApiError;
+use crate::{error::ApiError, game::color::Color, utils::random::GameRng};
 
-#[derive(Serialize, Deserialize, Clone, Default, PartialEq, PartialOrd)]
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Default, PartialEq, PartialOrd)]
 pub enum PermissionLevel {
     #[default]
     User = 0,
@@ -19,3 +20,110 @@ impl PermissionLevel {
         }
     }
 }
+
+/// What a secondary API key can be used for
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum KeyScope {
+    /// Only GET endpoints, except the key management
+    ReadOnly,
+    /// Everything read only keys can do plus moving
+    PlayMoves,
+    /// Everything the main key can do
+    Admin,
+}
+
+impl KeyScope {
+    pub fn allows(&self, method: &str, path: &str) -> bool {
+        let read = method == "GET" && !path.starts_with("/user/keys");
+        match self {
+            KeyScope::ReadOnly => read,
+            KeyScope::PlayMoves => read || path == "/session/move",
+            KeyScope::Admin => true,
+        }
+    }
+}
+
+/// Chat platforms whose bots can act for users through a negotiator
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Platform {
+    #[serde(alias = "discord")]
+    DISCORD,
+    #[serde(alias = "telegram")]
+    TELEGRAM,
+    #[serde(alias = "slack")]
+    SLACK,
+}
+
+impl Platform {
+    pub const ALL: [Self; 3] = [Self::DISCORD, Self::TELEGRAM, Self::SLACK];
+}
+
+/// The color the creator of a room wants to play
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, Default, PartialEq)]
+pub enum ColorPreference {
+    #[serde(alias = "white")]
+    WHITE,
+    #[serde(alias = "black")]
+    BLACK,
+    #[default]
+    #[serde(alias = "random")]
+    RANDOM,
+}
+
+impl ColorPreference {
+    /// Picks a color, random ones are a coin flip
+    pub fn resolve(&self, rng: &GameRng) -> Color {
+        match self {
+            ColorPreference::WHITE => Color::WHITE,
+            ColorPreference::BLACK => Color::BLACK,
+            ColorPreference::RANDOM => match rng.gen_bool(0.5) {
+                true => Color::WHITE,
+                false => Color::BLACK,
+            },
+        }
+    }
+}
+
+/// How strong the AI plays, the easier levels pick among the better moves by chance instead of always playing the best one
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, Default, PartialEq)]
+pub enum AiDifficulty {
+    #[serde(alias = "beginner")]
+    BEGINNER,
+    #[serde(alias = "easy")]
+    EASY,
+    #[serde(alias = "medium")]
+    MEDIUM,
+    #[default]
+    #[serde(alias = "hard")]
+    HARD,
+}
+
+/// Order of room listings
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, Default, PartialEq)]
+pub enum RoomSort {
+    NEWEST,
+    #[default]
+    OLDEST,
+}
+
+/// Order of session listings, archived sessions are sorted together with the others
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SessionSort {
+    /// Newest sessions first
+    Newest,
+    /// Oldest sessions first
+    Oldest,
+    /// Sessions with the latest move or resignation first
+    RecentActivity,
+}
+
+/// How a finished game ended for one of its players
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GameOutcome {
+    Win,
+    Loss,
+    Draw,
+}
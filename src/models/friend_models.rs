@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A user you are friends with
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct FriendInfo {
+    /// The unique name of the friend
+    pub name: String,
+    /// The name other people see
+    pub display_name: String,
+    /// If the friend made a request within the last 5 minutes
+    pub online: bool,
+    /// UNIX timestamp in nanoseconds since when you are friends
+    pub friends_since: u64,
+    /// IDs of your running sessions against this friend
+    pub active_sessions: Vec<String>,
+}
+
+/// A friend request which wasn't accepted yet
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct FriendRequestInfo {
+    /// The unique name of the other user
+    pub name: String,
+    /// The name other people see
+    pub display_name: String,
+    /// UNIX timestamp in nanoseconds when the request was sent
+    pub created_stamp: u64,
+}
+
+/// Your friends and pending friend requests
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct FriendList {
+    pub friends: Vec<FriendInfo>,
+    /// Requests other users sent you
+    pub incoming: Vec<FriendRequestInfo>,
+    /// Requests you sent
+    pub outgoing: Vec<FriendRequestInfo>,
+}
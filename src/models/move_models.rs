@@ -3,7 +3,7 @@ use utoipa::{IntoParams, ToSchema};
 
 use crate::{
     error::ApiError,
-    game::{color::Color, position::Position},
+    game::{chess_board::IllegalMoveReason, color::Color, position::Square},
 };
 
 #[derive(Deserialize, IntoParams, Default)]
@@ -11,42 +11,131 @@ use crate::{
 pub struct MoveQuery {
     pub from: Option<String>,
     pub to: Option<String>,
+    /// Piece a pawn gets promoted to (q), pawns are always promoted to a queen
+    pub promotion: Option<String>,
     pub castle_kingside: Option<bool>,
     pub castle_queenside: Option<bool>,
 }
 
-impl MoveQuery {
-    pub fn convert_to_move(&self) -> Result<(u8, u8, bool, bool), ApiError> {
-        if self.castle_kingside == Some(true) {
-            return Ok((0, 0, true, false));
-        }
+/// A move sent as JSON body, for example {"from": "e7", "to": "e8", "promotion": "q"}
+#[derive(Deserialize, ToSchema, Default)]
+pub struct MoveSubmission {
+    #[schema(example = "e2")]
+    pub from: Option<String>,
+    #[schema(example = "e4")]
+    pub to: Option<String>,
+    /// Piece a pawn gets promoted to (q), pawns are always promoted to a queen
+    #[schema(example = "q")]
+    pub promotion: Option<String>,
+    pub castle_kingside: Option<bool>,
+    pub castle_queenside: Option<bool>,
+}
 
-        if self.castle_queenside == Some(true) {
-            return Ok((0, 0, false, true));
+impl From<MoveSubmission> for MoveQuery {
+    fn from(submission: MoveSubmission) -> Self {
+        Self {
+            from: submission.from,
+            to: submission.to,
+            promotion: submission.promotion,
+            castle_kingside: submission.castle_kingside,
+            castle_queenside: submission.castle_queenside,
         }
+    }
+}
 
-        let from = match self.from.clone() {
-            Some(from_str) => Position::try_from(from_str)? as u8,
-            None => {
+/// A move of a player after validating the query, see MoveQuery::convert_to_move
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayerMove {
+    /// Includes promotions, pawns are always promoted to a queen
+    Regular {
+        from: Square,
+        to: Square,
+    },
+    CastleKingside,
+    CastleQueenside,
+}
+
+impl MoveQuery {
+    /// Rejects contradictory queries like castling to both sides or castling with cells
+    pub fn convert_to_move(&self) -> Result<PlayerMove, ApiError> {
+        if let Some(promotion) = &self.promotion {
+            if !matches!(promotion.to_lowercase().as_str(), "q" | "queen") {
                 return Err(ApiError::BadRequest(
-                    "Move needs a specified starting cell".to_string(),
+                    "Pawns can only be promoted to a queen".to_string(),
                 ));
             }
-        };
+        }
 
-        let to = match self.to.clone() {
-            Some(to_str) => Position::try_from(to_str)? as u8,
-            None => {
+        let kingside = self.castle_kingside == Some(true);
+        let queenside = self.castle_queenside == Some(true);
+        if kingside && queenside {
+            return Err(ApiError::BadRequest(
+                "A move can't castle kingside and queenside at once".to_string(),
+            ));
+        }
+        if kingside || queenside {
+            if self.from.is_some() || self.to.is_some() || self.promotion.is_some() {
                 return Err(ApiError::BadRequest(
-                    "Move needs a specified destination cell".to_string(),
+                    "Castling moves can't have cells or a promotion".to_string(),
                 ));
             }
-        };
+            return Ok(match kingside {
+                true => PlayerMove::CastleKingside,
+                false => PlayerMove::CastleQueenside,
+            });
+        }
+
+        let from = parse_cell(self.from.as_deref(), "starting")?;
+        let to = parse_cell(self.to.as_deref(), "destination")?;
+        if from == to {
+            return Err(ApiError::BadRequest(
+                "Starting and destination cell can't be the same".to_string(),
+            ));
+        }
 
-        Ok((from, to, false, false))
+        Ok(PlayerMove::Regular { from, to })
     }
 }
 
+fn parse_cell(cell: Option<&str>, kind: &str) -> Result<Square, ApiError> {
+    let cell = cell.ok_or(ApiError::BadRequest(format!(
+        "Move needs a specified {} cell",
+        kind
+    )))?;
+    Square::try_from(cell.to_string())
+        .map_err(|_| ApiError::BadRequest(format!("Unknown {} cell: {}", kind, cell)))
+}
+
+/// Returned with status 400 if a move was rejected for breaking the rules
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct IllegalMoveResponse {
+    #[schema(example = "Another piece blocks the way to the destination cell.")]
+    pub message: String,
+    pub reason: IllegalMoveReason,
+}
+
+impl IllegalMoveResponse {
+    pub fn new(reason: IllegalMoveReason) -> Self {
+        Self {
+            message: reason.describe().to_string(),
+            reason,
+        }
+    }
+}
+
+/// A single legal move and what it would result in
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct LegalMove {
+    pub from: String,
+    pub to: String,
+    /// If a piece gets captured, including en passant
+    pub capture: bool,
+    /// If a pawn gets promoted to a queen
+    pub promotion: bool,
+    /// If the move gives check to the opponent
+    pub check: bool,
+}
+
 /// All legal moves for a given color
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct LegalMoves {
@@ -56,8 +145,104 @@ pub struct LegalMoves {
     pub current_turn: bool,
     /// Move pairs (from, to) chess cells
     pub cells: Vec<(String, String)>,
+    /// The same moves as cells, with additional flags
+    pub moves: Vec<LegalMove>,
     /// If the player can castle kingside
     pub castle_kingside: bool,
     /// If the player can castle queenside
     pub castle_queenside: bool,
+    /// The cell the king ends up on when castling kingside, if possible
+    pub castle_kingside_target: Option<String>,
+    /// The cell the king ends up on when castling queenside, if possible
+    pub castle_queenside_target: Option<String>,
+    /// The cell a pawn can capture en passant on, if possible
+    pub en_passant: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::position::Position;
+
+    use super::*;
+
+    #[test]
+    fn test_move_submission() {
+        let submission: MoveSubmission =
+            serde_json::from_str(r#"{"from":"e7","to":"e8","promotion":"q"}"#).unwrap();
+        let query = MoveQuery::from(submission);
+        assert_eq!(
+            query.convert_to_move().unwrap(),
+            PlayerMove::Regular {
+                from: Position::E7.into(),
+                to: Position::E8.into()
+            }
+        );
+
+        let query = MoveQuery {
+            promotion: Some("n".to_string()),
+            ..query
+        };
+        assert!(query.convert_to_move().is_err());
+    }
+
+    #[test]
+    fn test_convert_to_move() {
+        let error = |query: MoveQuery| match query.convert_to_move() {
+            Err(ApiError::BadRequest(message)) => message,
+            _ => panic!("The move should be rejected"),
+        };
+        let cells = |from: &str, to: &str| MoveQuery {
+            from: Some(from.to_string()),
+            to: Some(to.to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(error(cells("e2", "i9")), "Unknown destination cell: i9");
+        assert_eq!(error(cells("", "e4")), "Unknown starting cell: ");
+        assert_eq!(
+            error(cells("e2", "e2")),
+            "Starting and destination cell can't be the same"
+        );
+        assert_eq!(
+            error(MoveQuery {
+                to: None,
+                ..cells("e2", "e4")
+            }),
+            "Move needs a specified destination cell"
+        );
+
+        // Castling used to silently win over everything else
+        let castle = |kingside, queenside| MoveQuery {
+            castle_kingside: Some(kingside),
+            castle_queenside: Some(queenside),
+            ..Default::default()
+        };
+        assert_eq!(
+            castle(true, false).convert_to_move().unwrap(),
+            PlayerMove::CastleKingside
+        );
+        assert_eq!(
+            castle(false, true).convert_to_move().unwrap(),
+            PlayerMove::CastleQueenside
+        );
+        assert_eq!(
+            error(castle(true, true)),
+            "A move can't castle kingside and queenside at once"
+        );
+        assert_eq!(
+            error(MoveQuery {
+                from: Some("e1".to_string()),
+                ..castle(true, false)
+            }),
+            "Castling moves can't have cells or a promotion"
+        );
+        // Explicitly not castling is fine for regular moves
+        assert!(MoveQuery {
+            castle_kingside: Some(false),
+            castle_queenside: Some(false),
+            ..cells("e2", "e4")
+        }
+        .convert_to_move()
+        .is_ok());
+    }
 }
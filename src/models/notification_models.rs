@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::entities::notification::Notification;
+
+use super::response_models::Pagination;
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum NotificationKind {
+    YourTurn,
+    GameStarted,
+    GameFinished,
+    FriendRequest,
+    FriendAccepted,
+    PauseRequested,
+}
+
+/// An entry of your notification inbox
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct NotificationInfo {
+    pub id: String,
+    pub kind: NotificationKind,
+    pub message: String,
+    /// ID of the session it is about, if any
+    pub session_id: Option<String>,
+    /// Name of the user who caused it, if any
+    pub from: Option<String>,
+    pub read: bool,
+    /// UNIX timestamp in nanoseconds when it was sent
+    pub created_stamp: u64,
+}
+
+impl From<Notification> for NotificationInfo {
+    fn from(notification: Notification) -> Self {
+        Self {
+            id: notification.id.map(|id| id.to_hex()).unwrap_or_default(),
+            kind: notification.kind,
+            message: notification.message,
+            session_id: notification.session_id,
+            from: notification.from,
+            read: notification.read,
+            created_stamp: notification.created_stamp,
+        }
+    }
+}
+
+/// A page of your notifications, newest first
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct NotificationList {
+    pub notifications: Vec<NotificationInfo>,
+    /// Amount of unread notifications in total
+    pub unread: u64,
+    pub pagination: Pagination,
+}
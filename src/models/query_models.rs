@@ -1,7 +1,35 @@
-use serde::Deserialize;
+use std::net::IpAddr;
+
+use chrono::{Days, NaiveDate};
+use serde::{Deserialize, Serialize};
 use utoipa::IntoParams;
 
-use crate::{game::render::RenderStyle, utils::sanitize};
+use crate::{
+    entities::user::User,
+    error::ApiError,
+    game::{
+        color::Color,
+        render::{
+            theme::{parse_hex_color, Theme},
+            RenderStyle,
+        },
+        report::ReportFormat,
+    },
+    models::{
+        audit_models::AuditAction,
+        enums::{
+            AiDifficulty, ColorPreference, GameOutcome, KeyScope, PermissionLevel, Platform,
+            RoomSort, SessionSort,
+        },
+        session_models::TimeControl,
+    },
+    utils::{
+        http::split_https_url,
+        sanitize::{Sanitize, SanitizePolicy},
+        signing,
+        time_operations::{day_to_nanos, timestamp_now_nanos, DAY_FORMAT},
+    },
+};
 
 #[derive(Deserialize, IntoParams)]
 #[into_params(parameter_in = Query)]
@@ -16,6 +44,58 @@ pub struct DiscordUserCreation {
     pub api_key: Option<String>,
 }
 
+impl Sanitize for DiscordUserCreation {
+    fn sanitize(&self, policy: &SanitizePolicy) -> Result<Self, ApiError> {
+        let name = policy.clean(&self.name, policy.max_name_length);
+        if name.is_empty() {
+            return Err(ApiError::BadRequest("Invalid user name.".to_string()));
+        }
+
+        let display_name = policy.clean_public(&self.display_name, policy.max_name_length)?;
+        if display_name.is_empty() {
+            return Err(ApiError::BadRequest("Invalid display name.".to_string()));
+        }
+
+        Ok(Self {
+            id: self.id.clone(),
+            name,
+            display_name,
+            api_key: self.api_key.clone(),
+        })
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BotCreation {
+    /// The unique name of the bot
+    pub name: String,
+    /// The name other people will see | defaults to the name
+    pub display_name: Option<String>,
+}
+
+impl Sanitize for BotCreation {
+    fn sanitize(&self, policy: &SanitizePolicy) -> Result<Self, ApiError> {
+        let name = policy.clean(&self.name, policy.max_name_length);
+        if name.is_empty() {
+            return Err(ApiError::BadRequest("Invalid user name.".to_string()));
+        }
+
+        let display_name = policy.clean_public(
+            self.display_name.as_deref().unwrap_or(&name),
+            policy.max_name_length,
+        )?;
+        if display_name.is_empty() {
+            return Err(ApiError::BadRequest("Invalid display name.".to_string()));
+        }
+
+        Ok(Self {
+            name,
+            display_name: Some(display_name),
+        })
+    }
+}
+
 #[derive(Deserialize, IntoParams)]
 #[into_params(parameter_in = Query)]
 pub struct PaginationQuery {
@@ -48,22 +128,326 @@ pub struct RoomCreation {
     pub name: Option<String>,
     /// If the room is supposed to be public or not | defaults to true
     pub public: Option<bool>,
+    /// The color you want to play | defaults to RANDOM
+    pub color: Option<ColorPreference>,
+    /// Starting time on each clock in seconds (at most 3 hours) | the game has no clocks if not given
+    pub time_initial: Option<u32>,
+    /// Seconds added to a clock after each move (at most 3 minutes) | defaults to 0
+    pub time_increment: Option<u32>,
+    /// Name of the only user who can join, invites are never public
+    pub invite_name: Option<String>,
+    /// Discord id of the only user who can join, invites are never public
+    pub invite_discord_id: Option<String>,
+    /// If bot accounts may join the room, an invited bot can always join | defaults to false
+    pub allow_bots: Option<bool>,
 }
 
 impl RoomCreation {
-    pub fn sanitize(&self) -> Self {
+    pub fn get_time_control(&self) -> Result<Option<TimeControl>, ApiError> {
+        let Some(initial_seconds) = self.time_initial else {
+            if self.time_increment.is_some() {
+                return Err(ApiError::BadRequest(
+                    "time_increment requires time_initial".to_string(),
+                ));
+            }
+            return Ok(None);
+        };
+        TimeControl::new(initial_seconds, self.time_increment.unwrap_or(0)).map(Some)
+    }
+}
+
+impl Sanitize for RoomCreation {
+    fn sanitize(&self, policy: &SanitizePolicy) -> Result<Self, ApiError> {
+        let name = self
+            .name
+            .as_ref()
+            .map(|name| policy.clean_public(name, policy.max_room_name_length))
+            .transpose()?
+            .filter(|name| !name.is_empty());
+
+        Ok(Self {
+            name,
+            public: self.public,
+            color: self.color,
+            time_initial: self.time_initial,
+            time_increment: self.time_increment,
+            invite_name: self.invite_name.clone(),
+            invite_discord_id: self.invite_discord_id.clone(),
+            allow_bots: self.allow_bots,
+        })
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RoomUpdate {
+    /// The code of the room
+    pub code: String,
+    /// The new name of the room
+    pub name: Option<String>,
+    /// If the room is supposed to be public or not
+    pub public: Option<bool>,
+    /// The color you want to play
+    pub color: Option<ColorPreference>,
+    /// Starting time on each clock in seconds (at most 3 hours), 0 removes the time control
+    pub time_initial: Option<u32>,
+    /// Seconds added to a clock after each move (at most 3 minutes) | defaults to 0 if time_initial is given
+    pub time_increment: Option<u32>,
+    /// If bot accounts may join the room
+    pub allow_bots: Option<bool>,
+}
+
+impl RoomUpdate {
+    /// None keeps the current time control, Some(None) removes it
+    pub fn get_time_control(&self) -> Result<Option<Option<TimeControl>>, ApiError> {
+        match self.time_initial {
+            None if self.time_increment.is_some() => Err(ApiError::BadRequest(
+                "time_increment requires time_initial".to_string(),
+            )),
+            None => Ok(None),
+            Some(0) => Ok(Some(None)),
+            Some(initial_seconds) => {
+                TimeControl::new(initial_seconds, self.time_increment.unwrap_or(0))
+                    .map(|time_control| Some(Some(time_control)))
+            }
+        }
+    }
+}
+
+impl Sanitize for RoomUpdate {
+    fn sanitize(&self, policy: &SanitizePolicy) -> Result<Self, ApiError> {
         let name = self
             .name
             .as_ref()
-            .map(|name| sanitize::limit_string(&sanitize::profanity(name), 64));
+            .map(|name| policy.clean_public(name, policy.max_room_name_length))
+            .transpose()?;
+        if name.as_ref().is_some_and(|name| name.is_empty()) {
+            return Err(ApiError::BadRequest("Invalid room name.".to_string()));
+        }
 
-        Self {
+        Ok(Self {
+            code: self.code.clone(),
             name,
             public: self.public,
+            color: self.color,
+            time_initial: self.time_initial,
+            time_increment: self.time_increment,
+            allow_bots: self.allow_bots,
+        })
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RoomFilterQuery {
+    /// Only rooms whose name contains this, case insensitive
+    pub search: Option<String>,
+    /// Only rooms created after this UNIX timestamp in nanoseconds
+    pub created_after: Option<u64>,
+    /// NEWEST or OLDEST first | defaults to OLDEST
+    pub sort: Option<RoomSort>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NamespaceQuery {
+    /// ADMIN ONLY! The namespace to look at, all namespaces if not given
+    pub namespace: Option<String>,
+}
+
+impl NamespaceQuery {
+    /// Everyone except admins is limited to their own namespace
+    pub fn retrieve(&self, user: &User) -> Result<Option<String>, ApiError> {
+        if user.permission.authenticate(PermissionLevel::Admin).is_ok() {
+            return Ok(self.namespace.clone());
+        }
+
+        match &self.namespace {
+            Some(namespace) if namespace != &user.namespace => Err(ApiError::NoPermission(
+                "Can't view other namespaces.".to_string(),
+            )),
+            _ => Ok(Some(user.namespace.clone())),
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserUpdate {
+    /// The new unique name, it has to be free and may only contain letters, digits, dashes and underscores
+    pub name: Option<String>,
+    /// The new name other people will see
+    pub display_name: Option<String>,
+}
+
+impl Sanitize for UserUpdate {
+    fn sanitize(&self, policy: &SanitizePolicy) -> Result<Self, ApiError> {
+        let name = self
+            .name
+            .as_ref()
+            .map(|name| policy.unique_name(name))
+            .transpose()?;
+
+        let display_name = self
+            .display_name
+            .as_ref()
+            .map(|display_name| policy.clean_public(display_name, policy.max_name_length))
+            .transpose()?;
+        if display_name
+            .as_ref()
+            .is_some_and(|display_name| display_name.is_empty())
+        {
+            return Err(ApiError::BadRequest("Invalid display name.".to_string()));
+        }
+
+        Ok(Self { name, display_name })
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ApiKeyCreation {
+    /// Unique name to tell your keys apart
+    pub name: String,
+    /// READ_ONLY, PLAY_MOVES or ADMIN
+    pub scope: KeyScope,
+}
+
+impl Sanitize for ApiKeyCreation {
+    fn sanitize(&self, policy: &SanitizePolicy) -> Result<Self, ApiError> {
+        let name = policy.clean(&self.name, policy.max_name_length);
+        if name.is_empty() {
+            return Err(ApiError::BadRequest("Invalid key name.".to_string()));
+        }
+
+        Ok(Self {
+            name,
+            scope: self.scope,
+        })
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ApiKeyName {
+    /// The name of the key
+    pub name: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AiRetryQuery {
+    /// Only retry this session | defaults to all sessions in which the AI failed to move
+    pub session_id: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserName {
+    /// The unique name of the user
+    pub name: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PlatformLink {
+    /// The unique name of the user
+    pub name: String,
+    /// DISCORD, TELEGRAM or SLACK
+    pub platform: Platform,
+    /// The user id on the platform
+    pub id: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PlatformUnlink {
+    /// The unique name of the user
+    pub name: String,
+    /// DISCORD, TELEGRAM or SLACK
+    pub platform: Platform,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserListQuery {
+    /// Only users of this namespace, leave it empty for the default namespace | defaults to all namespaces
+    pub namespace: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PermissionChange {
+    /// The unique name of the user
+    pub name: String,
+    /// User, Negotiator or Admin
+    pub permission: PermissionLevel,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserBan {
+    /// The unique name of the user
+    pub name: String,
+    /// False lifts the ban again | defaults to true
+    pub banned: Option<bool>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TitleAward {
+    /// The unique name of the user
+    pub name: String,
+    /// The title shown on the profile of the user
+    pub title: String,
+}
+
+impl Sanitize for TitleAward {
+    fn sanitize(&self, policy: &SanitizePolicy) -> Result<Self, ApiError> {
+        let title = policy.clean_public(&self.title, policy.max_name_length)?;
+        if title.is_empty() {
+            return Err(ApiError::BadRequest("Invalid title.".to_string()));
         }
+
+        Ok(Self {
+            name: self.name.clone(),
+            title,
+        })
     }
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TitleReset {
+    /// The unique name of the user
+    pub name: String,
+    /// Only remove this title | defaults to removing all titles
+    pub title: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditLogQuery {
+    /// Only entries of this action
+    pub action: Option<AuditAction>,
+    /// Only entries where this user name is the actor or the target
+    pub user: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NotificationQuery {
+    /// Only notifications which weren't read yet | defaults to false
+    pub unread: Option<bool>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NotificationId {
+    /// The ID of the notification | defaults to all of your notifications
+    pub id: Option<String>,
+}
+
 #[derive(Deserialize, IntoParams)]
 #[into_params(parameter_in = Query)]
 pub struct RoomCode {
@@ -71,15 +455,670 @@ pub struct RoomCode {
     pub code: String,
 }
 
-#[derive(Deserialize, IntoParams)]
+#[derive(Clone, Serialize, Deserialize, IntoParams)]
 #[into_params(parameter_in = Query)]
 pub struct RenderStyleQuery {
-    /// The style that should be used for rendering
+    /// The board layout and piece set, overrides the one of the theme
     pub style: Option<RenderStyle>,
+    /// classic, pixel, brown, green, blue or gameboy | defaults to classic
+    pub theme: Option<String>,
+    /// Color of the light squares as #RRGGBB, overrides the one of the theme
+    pub light: Option<String>,
+    /// Color of the dark squares as #RRGGBB, overrides the one of the theme
+    pub dark: Option<String>,
 }
 
 impl RenderStyleQuery {
-    pub fn retrieve(&self) -> RenderStyle {
-        self.style.unwrap_or(RenderStyle::MODERN)
+    pub fn retrieve(&self) -> Result<Theme, ApiError> {
+        let mut theme = match &self.theme {
+            Some(name) => Theme::find(name)?,
+            None => Theme::new(RenderStyle::MODERN),
+        };
+        if let Some(style) = self.style {
+            theme.style = style;
+        }
+        if let Some(light) = &self.light {
+            theme.light = Some(parse_hex_color(light)?);
+        }
+        if let Some(dark) = &self.dark {
+            theme.dark = Some(parse_hex_color(dark)?);
+        }
+        Ok(theme)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PlyRangeQuery {
+    /// First ply to show, 0 being the starting position | defaults to 0
+    pub from_ply: Option<usize>,
+    /// Last ply to show | defaults to the last ply of the game
+    pub to_ply: Option<usize>,
+}
+
+impl PlyRangeQuery {
+    pub fn retrieve(&self, ply_count: usize) -> Result<(usize, usize), ApiError> {
+        let from_ply = self.from_ply.unwrap_or(0);
+        let to_ply = self.to_ply.unwrap_or(ply_count);
+        if to_ply > ply_count {
+            return Err(ApiError::BadRequest(format!(
+                "to_ply can't exceed the {} plies of the game",
+                ply_count
+            )));
+        }
+        if from_ply > to_ply {
+            return Err(ApiError::BadRequest(
+                "from_ply can't be after to_ply".to_string(),
+            ));
+        }
+        Ok((from_ply, to_ply))
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PlyQuery {
+    /// Amount of plies played, 0 being the starting position | defaults to the last ply of the game
+    pub ply: Option<usize>,
+}
+
+impl PlyQuery {
+    pub fn retrieve(&self, ply_count: usize) -> Result<usize, ApiError> {
+        match self.ply {
+            Some(ply) if ply > ply_count => Err(ApiError::BadRequest(format!(
+                "ply can't exceed the {} plies of the game",
+                ply_count
+            ))),
+            Some(ply) => Ok(ply),
+            None => Ok(ply_count),
+        }
+    }
+}
+
+/// Most months of games which can be imported at once
+const MAX_IMPORT_MONTHS: u32 = 12;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ChessComImportQuery {
+    /// Your chess.com username
+    pub username: String,
+    /// How many of the latest months with games to import (at most 12) | defaults to 1
+    pub months: Option<u32>,
+}
+
+impl ChessComImportQuery {
+    pub fn retrieve(&self) -> Result<(String, usize), ApiError> {
+        let username = self.username.trim().to_lowercase();
+        let valid = (3..=25).contains(&username.len())
+            && username
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return Err(ApiError::BadRequest(
+                "Invalid chess.com username.".to_string(),
+            ));
+        }
+
+        let months = self.months.unwrap_or(1);
+        if months == 0 || months > MAX_IMPORT_MONTHS {
+            return Err(ApiError::BadRequest(format!(
+                "months has to be between 1 and {}",
+                MAX_IMPORT_MONTHS
+            )));
+        }
+        Ok((username, months as usize))
+    }
+}
+
+/// Most days of activity which can be retrieved at once, a leap year
+const MAX_ACTIVITY_DAYS: u64 = 366;
+
+/// A UTC day like 2024-05-31
+fn parse_day(day: &str) -> Result<NaiveDate, ApiError> {
+    NaiveDate::parse_from_str(day, DAY_FORMAT)
+        .map_err(|_| ApiError::BadRequest(format!("Invalid day {}, use YYYY-MM-DD", day)))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ActivityQuery {
+    /// First UTC day like 2024-05-31 | defaults to a year before the last day
+    pub from: Option<String>,
+    /// Last UTC day like 2024-05-31, at most today | defaults to today
+    pub to: Option<String>,
+}
+
+impl ActivityQuery {
+    /// The first and the last day, given today as UTC day
+    pub fn retrieve(&self, today: NaiveDate) -> Result<(NaiveDate, NaiveDate), ApiError> {
+        let to = match &self.to {
+            Some(to) => parse_day(to)?.min(today),
+            None => today,
+        };
+        let from = match &self.from {
+            Some(from) => parse_day(from)?,
+            None => to
+                .checked_sub_days(Days::new(MAX_ACTIVITY_DAYS - 1))
+                .unwrap_or(NaiveDate::MIN),
+        };
+
+        if from > to {
+            return Err(ApiError::BadRequest("from has to be before to".to_string()));
+        }
+        if (to - from).num_days() as u64 >= MAX_ACTIVITY_DAYS {
+            return Err(ApiError::BadRequest(format!(
+                "At most {} days can be retrieved at once",
+                MAX_ACTIVITY_DAYS
+            )));
+        }
+        Ok((from, to))
+    }
+}
+
+/// Longest URL a webhook can be registered with
+const MAX_WEBHOOK_URL_LENGTH: usize = 500;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WebhookRegistration {
+    /// https URL on the default port, called whenever a game of a Discord user of your namespace finished
+    pub url: String,
+}
+
+impl WebhookRegistration {
+    /// Webhooks can only call hosts by name, not IP addresses, ports or credentials
+    /// Whether the name resolves to a public address is checked on every call, see http::post_json
+    pub fn retrieve(&self) -> Result<String, ApiError> {
+        let url = self.url.trim();
+        let valid_host = |host: &str| {
+            host.contains('.')
+                && host.parse::<IpAddr>().is_err()
+                && !host.contains([':', '@', '[', ']'])
+        };
+        let valid = url.len() <= MAX_WEBHOOK_URL_LENGTH
+            && !url.contains(|c: char| c.is_whitespace() || c.is_control())
+            && split_https_url(url).is_some_and(|(host, _)| valid_host(host));
+        if !valid {
+            return Err(ApiError::BadRequest(
+                "Invalid webhook URL, it has to be a https URL with a host name".to_string(),
+            ));
+        }
+        Ok(url.to_string())
+    }
+}
+
+/// Longest comment a move can be annotated with
+const MAX_ANNOTATION_LENGTH: usize = 500;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AnnotationQuery {
+    /// The ply to annotate, 1 being the first move of white
+    pub ply: usize,
+    /// !!, !, !?, ?!, ? or ??
+    pub glyph: Option<String>,
+    /// A comment about the move (at most 500 characters)
+    pub comment: Option<String>,
+}
+
+impl Sanitize for AnnotationQuery {
+    fn sanitize(&self, policy: &SanitizePolicy) -> Result<Self, ApiError> {
+        // Braces would end the comment early in PGN exports
+        let comment = self
+            .comment
+            .as_ref()
+            .map(|comment| {
+                policy.clean_public(&comment.replace(['{', '}'], ""), MAX_ANNOTATION_LENGTH)
+            })
+            .transpose()?
+            .filter(|comment| !comment.is_empty());
+
+        Ok(Self {
+            ply: self.ply,
+            glyph: self.glyph.clone(),
+            comment,
+        })
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ReportQuery {
+    /// The file format of the report | defaults to MARKDOWN
+    pub format: Option<ReportFormat>,
+    /// The style that should be used for rendering diagrams
+    pub style: Option<RenderStyle>,
+}
+
+impl ReportQuery {
+    pub fn retrieve(&self) -> (ReportFormat, Theme) {
+        (
+            self.format.unwrap_or(ReportFormat::MARKDOWN),
+            Theme::from(self.style.unwrap_or(RenderStyle::MODERN)),
+        )
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AiSessionQuery {
+    /// How strong the AI plays | defaults to HARD
+    pub difficulty: Option<AiDifficulty>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EvalsQuery {
+    /// Return the evaluations rendered as a PNG graph instead of JSON | defaults to false
+    pub image: Option<bool>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ResignQuery {
+    /// Only request a confirmation token instead of resigning right away | defaults to false
+    pub confirm: Option<bool>,
+    /// Token from the previous request with confirm=true, resigns if it's still valid
+    pub token: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SessionListQuery {
+    /// Also list finished sessions which were moved to the archive | defaults to false
+    pub include_archived: Option<bool>,
+    /// Only sessions you tagged with this, archived ones included
+    pub tag: Option<String>,
+    /// Only sessions you marked as favorite, archived ones included
+    pub favorite: Option<bool>,
+    /// Only finished sessions if true, only running ones if false
+    pub finished: Option<bool>,
+    /// Only games against the AI if true, only games against people if false
+    pub vs_ai: Option<bool>,
+    /// Only finished games which ended like this for you
+    pub result: Option<GameOutcome>,
+    /// Only games against the user with this name or imported games against a player with it
+    pub opponent: Option<String>,
+    /// Only sessions created on or after this UTC day like 2024-05-31
+    pub from: Option<String>,
+    /// Only sessions created on or before this UTC day like 2024-05-31
+    pub to: Option<String>,
+    /// Order of the sessions | defaults to running sessions before archived ones, oldest first
+    pub sort: Option<SessionSort>,
+}
+
+impl SessionListQuery {
+    /// Filters by your own marks, None if all sessions are listed
+    pub fn get_mark_filter(&self) -> Option<(Option<String>, bool)> {
+        let tag = self
+            .tag
+            .as_deref()
+            .map(|tag| tag.trim().to_lowercase())
+            .filter(|tag| !tag.is_empty());
+        let favorite = self.favorite.unwrap_or(false);
+        (tag.is_some() || favorite).then_some((tag, favorite))
+    }
+
+    pub fn get_opponent(&self) -> Option<String> {
+        self.opponent
+            .as_deref()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+    }
+
+    /// Range of creation timestamps in nanoseconds, the end is exclusive
+    pub fn get_created_range(&self) -> Result<Option<(u64, u64)>, ApiError> {
+        if self.from.is_none() && self.to.is_none() {
+            return Ok(None);
+        }
+        let from = match &self.from {
+            Some(from) => day_to_nanos(parse_day(from)?),
+            None => 0,
+        };
+        let to = match &self.to {
+            Some(to) => parse_day(to)?
+                .succ_opt()
+                .map(day_to_nanos)
+                .unwrap_or(u64::MAX),
+            None => u64::MAX,
+        };
+        if from >= to {
+            return Err(ApiError::BadRequest("from has to be before to".to_string()));
+        }
+        Ok(Some((from, to)))
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SessionUpdate {
+    /// The new name of the session, visible to both players
+    pub name: Option<String>,
+    /// Warn both players in the response to their move if it leaves material hanging
+    pub blunder_alerts: Option<bool>,
+}
+
+impl Sanitize for SessionUpdate {
+    fn sanitize(&self, policy: &SanitizePolicy) -> Result<Self, ApiError> {
+        let name = match &self.name {
+            // Sessions inherit the name of their room, so the same limit applies
+            Some(name) => Some(policy.clean_public(name, policy.max_room_name_length)?),
+            None => None,
+        };
+        if name.as_ref().is_some_and(|name| name.is_empty()) {
+            return Err(ApiError::BadRequest("Invalid session name.".to_string()));
+        }
+        Ok(Self {
+            name,
+            blunder_alerts: self.blunder_alerts,
+        })
+    }
+}
+
+/// Longest tag a session can be tagged with
+const MAX_TAG_LENGTH: usize = 30;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SessionMarkUpdate {
+    /// Comma separated tags like study later,brilliancy, replaces your previous tags, empty removes them
+    pub tags: Option<String>,
+    /// Mark the session as one of your favorites
+    pub favorite: Option<bool>,
+}
+
+impl SessionMarkUpdate {
+    /// The cleaned tags, None if they aren't changed
+    pub fn get_tags(&self, policy: &SanitizePolicy) -> Option<Vec<String>> {
+        let tags = self.tags.as_ref()?;
+        Some(
+            tags.split(',')
+                .map(|tag| policy.clean(&tag.to_lowercase(), MAX_TAG_LENGTH))
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+        )
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SidebarQuery {
+    /// Show the display names of both players next to the board | defaults to false
+    pub names: Option<bool>,
+    /// Show an engine evaluation bar next to the board, only for finished games | defaults to false
+    pub eval_bar: Option<bool>,
+    /// Show the captured pieces and material difference next to the board | defaults to false
+    pub captured: Option<bool>,
+}
+
+impl SidebarQuery {
+    pub fn is_enabled(&self) -> bool {
+        self.names.unwrap_or(false)
+            || self.eval_bar.unwrap_or(false)
+            || self.captured.unwrap_or(false)
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SignedRenderQuery {
+    /// ID of the session
+    pub session_id: String,
+    /// The perspective the board is rendered from
+    pub color: Color,
+    /// The style that should be used for rendering
+    pub style: RenderStyle,
+    /// UNIX timestamp in seconds until which the link is valid
+    pub expires: u64,
+    /// Signature over all other parameters
+    pub signature: String,
+}
+
+impl SignedRenderQuery {
+    pub fn new(
+        session_id: String,
+        color: Color,
+        style: RenderStyle,
+        valid_for_s: u64,
+    ) -> Result<Self, ApiError> {
+        let expires = timestamp_now_nanos() / 1_000_000_000 + valid_for_s;
+        let message = Self::get_message(&session_id, color, style, expires);
+        Ok(Self {
+            session_id,
+            color,
+            style,
+            expires,
+            signature: signing::sign(&message)?,
+        })
+    }
+
+    fn get_message(session_id: &str, color: Color, style: RenderStyle, expires: u64) -> String {
+        format!("{}:{:?}:{:?}:{}", session_id, color, style, expires)
+    }
+
+    pub fn verify(&self) -> Result<(), ApiError> {
+        if self.expires < timestamp_now_nanos() / 1_000_000_000 {
+            return Err(ApiError::NoPermission("Render link expired.".to_string()));
+        }
+
+        let message = Self::get_message(&self.session_id, self.color, self.style, self.expires);
+        if !signing::verify(&message, &self.signature)? {
+            return Err(ApiError::NoPermission("Invalid signature.".to_string()));
+        }
+
+        Ok(())
+    }
+
+    pub fn to_url(&self, base_url: &str) -> String {
+        format!(
+            "{}/session/render/signed?session_id={}&color={:?}&style={:?}&expires={}&signature={}",
+            base_url, self.session_id, self.color, self.style, self.expires, self.signature
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn room_creation(time_initial: Option<u32>, time_increment: Option<u32>) -> RoomCreation {
+        RoomCreation {
+            name: None,
+            public: None,
+            color: None,
+            time_initial,
+            time_increment,
+            invite_name: None,
+            invite_discord_id: None,
+            allow_bots: None,
+        }
+    }
+
+    #[test]
+    fn test_room_time_control() {
+        assert_eq!(room_creation(None, None).get_time_control().unwrap(), None);
+        assert_eq!(
+            room_creation(Some(300), None).get_time_control().unwrap(),
+            Some(TimeControl {
+                initial_seconds: 300,
+                increment_seconds: 0
+            })
+        );
+        assert!(room_creation(None, Some(2)).get_time_control().is_err());
+        assert!(room_creation(Some(0), None).get_time_control().is_err());
+        assert!(room_creation(Some(300), Some(181))
+            .get_time_control()
+            .is_err());
+    }
+
+    #[test]
+    fn test_activity_query() {
+        let date = |day| NaiveDate::parse_from_str(day, DAY_FORMAT).unwrap();
+        let query = |from: Option<&str>, to: Option<&str>| ActivityQuery {
+            from: from.map(str::to_string),
+            to: to.map(str::to_string),
+        };
+        let today = date("2024-06-15");
+
+        assert_eq!(
+            query(None, None).retrieve(today).unwrap(),
+            (date("2023-06-16"), today)
+        );
+        assert_eq!(
+            query(Some("2024-06-01"), Some("2024-12-31"))
+                .retrieve(today)
+                .unwrap(),
+            (date("2024-06-01"), today)
+        );
+        assert!(query(Some("2024-06-02"), Some("2024-06-01"))
+            .retrieve(today)
+            .is_err());
+        assert!(query(Some("2023-01-01"), None).retrieve(today).is_err());
+        assert!(query(Some("01.06.2024"), None).retrieve(today).is_err());
+    }
+
+    #[test]
+    fn test_webhook_registration() {
+        let retrieve = |url: &str| {
+            WebhookRegistration {
+                url: url.to_string(),
+            }
+            .retrieve()
+        };
+
+        assert_eq!(
+            retrieve(" https://bot.example.com/hooks/chess ").unwrap(),
+            "https://bot.example.com/hooks/chess"
+        );
+        assert!(retrieve("https://example.com").is_ok());
+        for url in [
+            "http://bot.example.com/hooks",
+            "https://127.0.0.1/hooks",
+            "https://[::1]/hooks",
+            "https://localhost/hooks",
+            "https://bot.example.com:8080/hooks",
+            "https://user@bot.example.com/hooks",
+            "https://bot.example.com/a b",
+        ] {
+            assert!(retrieve(url).is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_discord_user_creation() {
+        let policy = SanitizePolicy::default();
+        let creation = |display_name: &str| DiscordUserCreation {
+            id: "1".to_string(),
+            name: "lemon".to_string(),
+            display_name: display_name.to_string(),
+            api_key: None,
+        };
+
+        let display_name = "Zitrönenjoghurt 🍋".repeat(10);
+        let sanitized = creation(&display_name).sanitize(&policy).unwrap();
+        assert_eq!(
+            sanitized.display_name,
+            format!("{}...", display_name.chars().take(61).collect::<String>())
+        );
+        let sanitized = creation(&"👨\u{200D}👩\u{200D}👧".repeat(70))
+            .sanitize(&policy)
+            .unwrap();
+        assert_eq!(
+            sanitized.display_name,
+            format!("{}...", "👨\u{200D}👩\u{200D}👧".repeat(61))
+        );
+    }
+
+    #[test]
+    fn test_session_mark_update() {
+        let policy = SanitizePolicy::for_namespace("");
+        let update = |tags: Option<&str>| SessionMarkUpdate {
+            tags: tags.map(str::to_string),
+            favorite: None,
+        };
+
+        assert_eq!(update(None).get_tags(policy), None);
+        assert_eq!(update(Some("")).get_tags(policy), Some(Vec::new()));
+        assert_eq!(
+            update(Some(" Study Later,brilliancy,, ")).get_tags(policy),
+            Some(vec!["study later".to_string(), "brilliancy".to_string()])
+        );
+
+        let list = |tag: Option<&str>, favorite| SessionListQuery {
+            include_archived: None,
+            tag: tag.map(str::to_string),
+            favorite,
+            finished: None,
+            vs_ai: None,
+            result: None,
+            opponent: None,
+            from: None,
+            to: None,
+            sort: None,
+        };
+        assert_eq!(list(None, Some(false)).get_mark_filter(), None);
+        assert_eq!(list(Some(" "), None).get_mark_filter(), None);
+        assert_eq!(
+            list(Some("Brilliancy"), Some(true)).get_mark_filter(),
+            Some((Some("brilliancy".to_string()), true))
+        );
+    }
+
+    #[test]
+    fn test_session_list_created_range() {
+        let list = |from: Option<&str>, to: Option<&str>| SessionListQuery {
+            include_archived: None,
+            tag: None,
+            favorite: None,
+            finished: None,
+            vs_ai: None,
+            result: None,
+            opponent: None,
+            from: from.map(str::to_string),
+            to: to.map(str::to_string),
+            sort: None,
+        };
+        let day = 24 * 60 * 60 * 1_000_000_000;
+
+        assert_eq!(list(None, None).get_created_range().unwrap(), None);
+        assert_eq!(
+            list(Some("1970-01-02"), Some("1970-01-02"))
+                .get_created_range()
+                .unwrap(),
+            Some((day, 2 * day))
+        );
+        assert_eq!(
+            list(None, Some("1970-01-01")).get_created_range().unwrap(),
+            Some((0, day))
+        );
+        assert!(list(Some("1970-01-03"), Some("1970-01-02"))
+            .get_created_range()
+            .is_err());
+        assert!(list(Some("yesterday"), None).get_created_range().is_err());
+    }
+
+    #[test]
+    fn test_room_update_time_control() {
+        let update = |time_initial, time_increment| RoomUpdate {
+            code: "ABCDEF".to_string(),
+            name: None,
+            public: None,
+            color: None,
+            time_initial,
+            time_increment,
+            allow_bots: None,
+        };
+        assert_eq!(update(None, None).get_time_control().unwrap(), None);
+        assert_eq!(
+            update(Some(0), None).get_time_control().unwrap(),
+            Some(None)
+        );
+        assert!(update(Some(60), Some(1))
+            .get_time_control()
+            .unwrap()
+            .is_some_and(|time_control| time_control.is_some()));
+        assert!(update(None, Some(1)).get_time_control().is_err());
     }
 }
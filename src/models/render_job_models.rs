@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::entities::render_job::RenderJob;
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, ToSchema)]
+pub enum RenderJobStatus {
+    QUEUED,
+    RUNNING,
+    DONE,
+    FAILED,
+}
+
+/// The state of a background render
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct RenderJobInfo {
+    /// ID of the job, used to retrieve the result
+    pub job_id: String,
+    pub status: RenderJobStatus,
+    /// Why the job failed, only set if it did
+    pub error: Option<String>,
+    /// UNIX timestamp in nanoseconds when the job was created
+    pub created_stamp: u64,
+}
+
+impl From<&RenderJob> for RenderJobInfo {
+    fn from(job: &RenderJob) -> Self {
+        Self {
+            job_id: job.id.map(|id| id.to_hex()).unwrap_or_default(),
+            status: job.status,
+            error: job.error.clone(),
+            created_stamp: job.created_stamp,
+        }
+    }
+}
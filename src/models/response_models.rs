@@ -1,6 +1,9 @@
+use chrono::DateTime;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+use crate::utils::build_info;
+
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct MessageResponse {
     pub message: String,
@@ -11,6 +14,56 @@ pub struct UserApiKey {
     pub api_key: String,
 }
 
+/// Status of the services the API depends on
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct HealthReport {
+    /// If the API is ready to receive traffic
+    pub ready: bool,
+    /// If the storage backend responded to a ping
+    pub database: bool,
+    /// If all render assets are available
+    pub assets: bool,
+}
+
+/// Which build of the API answered the request
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct VersionInfo {
+    pub version: String,
+    /// Commit the server was built from, None if it wasn't built from a git checkout
+    pub git_hash: Option<String>,
+    /// If the checkout had uncommitted changes
+    pub git_dirty: Option<bool>,
+    /// UNIX timestamp in nanoseconds when the server was built
+    pub build_stamp: u64,
+    /// Cargo features the server was compiled with
+    pub features: Vec<String>,
+    /// debug or release
+    pub profile: String,
+    pub rustc_version: String,
+}
+
+impl VersionInfo {
+    pub fn current() -> Self {
+        let build_stamp = DateTime::parse_from_rfc2822(build_info::BUILT_TIME_UTC)
+            .ok()
+            .and_then(|time| time.timestamp_nanos_opt())
+            .unwrap_or_default() as u64;
+        Self {
+            version: build_info::PKG_VERSION.to_string(),
+            git_hash: build_info::GIT_COMMIT_HASH.map(str::to_string),
+            git_dirty: build_info::GIT_DIRTY,
+            build_stamp,
+            features: build_info::FEATURES_LOWERCASE
+                .iter()
+                .filter(|feature| !feature.is_empty())
+                .map(|feature| feature.to_string())
+                .collect(),
+            profile: build_info::PROFILE.to_string(),
+            rustc_version: build_info::RUSTC_VERSION.to_string(),
+        }
+    }
+}
+
 /// Pagination information for the request results
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct Pagination {
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::game::{
+    color::Color,
+    review::{GameReview, PlayerAccuracy},
+};
+
+/// The evaluation after a single ply
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PlyEval {
+    pub ply: usize,
+    /// The color which played this ply
+    pub color: Color,
+    /// Standard Algebraic Notation
+    pub san: String,
+    /// Engine evaluation from white's perspective, 100 per pawn, +-10000 for a forced mate
+    pub eval: i32,
+    /// ?! for inaccuracies, ? for mistakes and ?? for blunders
+    pub annotation: Option<String>,
+}
+
+/// The evaluation series of a whole game
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ReviewEvals {
+    /// Evaluation of the starting position
+    pub initial_eval: i32,
+    pub plies: Vec<PlyEval>,
+    /// None if white didn't move yet
+    pub white_accuracy: Option<PlayerAccuracy>,
+    /// None if black didn't move yet
+    pub black_accuracy: Option<PlayerAccuracy>,
+}
+
+impl From<GameReview> for ReviewEvals {
+    fn from(review: GameReview) -> Self {
+        let white_accuracy = review.get_accuracy(Color::WHITE);
+        let black_accuracy = review.get_accuracy(Color::BLACK);
+        let plies = review
+            .plies
+            .into_iter()
+            .map(|ply| PlyEval {
+                ply: ply.ply,
+                color: ply.color,
+                san: ply.san,
+                eval: ply.eval,
+                annotation: ply.annotation,
+            })
+            .collect();
+
+        Self {
+            initial_eval: review.initial_eval,
+            plies,
+            white_accuracy,
+            black_accuracy,
+        }
+    }
+}
@@ -2,12 +2,12 @@ use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 use crate::{
-    entities::{room::Room, user::find_user_by_key},
+    entities::{room::Room, user::UserDirectory},
     error::ApiError,
     AppState,
 };
 
-use super::response_models::Pagination;
+use super::{enums::ColorPreference, response_models::Pagination, session_models::TimeControl};
 
 /// Basic room information
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -22,26 +22,57 @@ pub struct RoomInfo {
     pub created_stamp: u64,
     /// If the room is publicly visible or not
     pub public: bool,
+    /// The namespace the room belongs to, empty for the default namespace
+    pub namespace: String,
+    /// The color the creator of the room will play
+    pub color: ColorPreference,
+    /// None if the game will be played without clocks
+    pub time_control: Option<TimeControl>,
+    /// Seconds until the room is closed automatically, POST /room/refresh restarts its lifetime
+    pub expires_in: Option<u64>,
+    /// Display name of the only user who can join the room, if it's an invite
+    pub invited_user: Option<String>,
+    /// If bot accounts may join the room
+    pub allow_bots: bool,
 }
 
 impl RoomInfo {
     pub async fn from_room(state: &AppState, room: Room) -> Result<Self, ApiError> {
-        let user = find_user_by_key(&state.database.user_collection, &room.key).await?;
-
-        let user_name = match user {
-            Some(user) => user.display_name,
-            None => "Unknown".to_string(),
-        };
+        let mut infos = Self::from_rooms(state, vec![room]).await?;
+        infos
+            .pop()
+            .ok_or(ApiError::ServerError("Room info missing".to_string()))
+    }
 
-        let info = Self {
-            name: room.name,
-            user_name,
-            code: room.code,
-            created_stamp: room.created_stamp,
-            public: room.public,
-        };
+    /// The owners and invited users of all rooms are looked up at once
+    pub async fn from_rooms(state: &AppState, rooms: Vec<Room>) -> Result<Vec<Self>, ApiError> {
+        let keys = rooms
+            .iter()
+            .flat_map(|room| [Some(room.key.clone()), room.invited_key.clone()])
+            .flatten()
+            .collect();
+        let users = UserDirectory::load(&*state.storage, keys).await?;
 
-        Ok(info)
+        let infos = rooms
+            .into_iter()
+            .map(|room| Self {
+                expires_in: room.get_expires_in(),
+                invited_user: room
+                    .invited_key
+                    .as_deref()
+                    .map(|key| users.get_display_name(key)),
+                user_name: users.get_display_name(&room.key),
+                name: room.name,
+                code: room.code,
+                created_stamp: room.created_stamp,
+                public: room.public,
+                namespace: room.namespace,
+                color: room.color,
+                time_control: room.time_control,
+                allow_bots: room.allow_bots,
+            })
+            .collect();
+        Ok(infos)
     }
 }
 
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Statistics of one background maintenance task since this instance started
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ScheduledTaskInfo {
+    pub name: String,
+    /// Seconds between two runs, configurable with SCHEDULER_<NAME>_INTERVAL
+    pub interval_seconds: u64,
+    pub runs: u64,
+    pub failures: u64,
+    /// Amount of rooms or sessions the task changed over all runs
+    pub total_affected: u64,
+    pub last_affected: u64,
+    /// UNIX timestamp in nanoseconds when the last run started, None if it didn't run yet
+    pub last_run_stamp: Option<u64>,
+    pub last_duration_ms: Option<u64>,
+    /// Error of the last run, None if it succeeded
+    pub last_error: Option<String>,
+}
+
+/// Background maintenance tasks of the instance which answered the request
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SchedulerInfo {
+    pub tasks: Vec<ScheduledTaskInfo>,
+}
@@ -1,14 +1,139 @@
 use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, env};
 use utoipa::ToSchema;
 
 use crate::{
-    entities::{session::Session, user::find_user_by_key},
+    entities::{
+        session::Session,
+        session_mark::SessionMark,
+        user::{User, UserDirectory},
+    },
     error::ApiError,
-    game::color::Color,
+    game::{
+        color::Color, opening::Opening, phase::GamePhase, piece::Piece, position::Position,
+        render::RenderStyle, review::HangingMaterial, termination::Termination,
+    },
+    utils::etag,
     AppState,
 };
 
-use super::response_models::Pagination;
+use super::{enums::AiDifficulty, query_models::SignedRenderQuery, response_models::Pagination};
+
+/// Longest starting time of a time control
+const MAX_INITIAL_SECONDS: u32 = 3 * 60 * 60;
+const MAX_INCREMENT_SECONDS: u32 = 3 * 60;
+
+/// How long the render link of a finished session stays valid
+const RESULT_RENDER_VALID_FOR_S: u64 = 7 * 24 * 60 * 60;
+
+/// Time each player has for the whole game
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq)]
+pub struct TimeControl {
+    /// Starting time on each clock in seconds
+    pub initial_seconds: u32,
+    /// Seconds added to the clock of a player after each of their moves
+    pub increment_seconds: u32,
+}
+
+impl TimeControl {
+    pub fn new(initial_seconds: u32, increment_seconds: u32) -> Result<Self, ApiError> {
+        if initial_seconds == 0 || initial_seconds > MAX_INITIAL_SECONDS {
+            return Err(ApiError::BadRequest(format!(
+                "time_initial has to be between 1 and {} seconds",
+                MAX_INITIAL_SECONDS
+            )));
+        }
+        if increment_seconds > MAX_INCREMENT_SECONDS {
+            return Err(ApiError::BadRequest(format!(
+                "time_increment can't exceed {} seconds",
+                MAX_INCREMENT_SECONDS
+            )));
+        }
+
+        Ok(Self {
+            initial_seconds,
+            increment_seconds,
+        })
+    }
+}
+
+/// An opening of the ECO (Encyclopaedia of Chess Openings) classification
+#[derive(Serialize, Deserialize, ToSchema, Clone, PartialEq, Debug)]
+pub struct OpeningInfo {
+    #[schema(example = "C50")]
+    pub eco: String,
+    #[schema(example = "Italian Game: Giuoco Piano")]
+    pub name: String,
+}
+
+impl From<&Opening> for OpeningInfo {
+    fn from(opening: &Opening) -> Self {
+        Self {
+            eco: opening.eco.to_string(),
+            name: opening.name.to_string(),
+        }
+    }
+}
+
+/// Glyphs a move can be annotated with, a subset of the Numeric Annotation Glyphs of PGN
+pub const ANNOTATION_GLYPHS: [&str; 6] = ["!!", "!", "!?", "?!", "?", "??"];
+
+/// A glyph and/or comment a player attached to a ply of their game
+#[derive(Serialize, Deserialize, ToSchema, Clone, PartialEq, Debug)]
+pub struct Annotation {
+    /// The annotated ply, 1 being the first move of white
+    pub ply: usize,
+    /// The player who wrote the annotation
+    pub author: Color,
+    /// !!, !, !?, ?!, ? or ??
+    #[schema(example = "!?")]
+    pub glyph: Option<String>,
+    pub comment: Option<String>,
+}
+
+/// A game played on another site which was imported as a finished session
+#[derive(Serialize, Deserialize, ToSchema, Clone, PartialEq, Debug)]
+pub struct ImportedGame {
+    /// Link to the game on the other site
+    #[schema(example = "https://www.chess.com/game/live/123456789")]
+    pub url: String,
+    /// Name of the opponent on the other site
+    pub opponent: String,
+}
+
+/// The last failure of the AI to play its move, it is retried with growing delays
+#[derive(Serialize, Deserialize, ToSchema, Clone, PartialEq, Debug)]
+pub struct AiError {
+    pub message: String,
+    /// How often the AI failed to move in a row
+    pub attempts: u32,
+    /// UNIX timestamp in nanoseconds of the last failure
+    pub failed_stamp: u64,
+    /// UNIX timestamp in nanoseconds of the next retry, None once the AI gave up until an admin retries it
+    pub retry_stamp: Option<u64>,
+}
+
+/// Outcome of retrying the moves of stuck AI sessions
+#[derive(Serialize, Deserialize, ToSchema, Default, Debug)]
+pub struct AiRetryReport {
+    /// Sessions whose AI move was attempted again
+    pub retried: u32,
+    /// Sessions in which the AI moved this time
+    pub recovered: u32,
+    /// IDs of the sessions in which the AI failed again
+    pub failed: Vec<String>,
+}
+
+/// What happened to the games found while importing
+#[derive(Serialize, Deserialize, ToSchema, Default, Debug, PartialEq)]
+pub struct ImportSummary {
+    /// Games which were added as finished sessions
+    pub imported: u32,
+    /// Games which were imported before
+    pub skipped: u32,
+    /// Games which can't be represented, like variants or promotions to other pieces than a queen
+    pub unsupported: u32,
+}
 
 /// Basic session information
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -22,44 +147,86 @@ pub struct SessionInfo {
     /// Standard Algebraic Notation
     pub san: String,
     pub color_to_move: Color,
+    /// The opening the game started with, None until the first moves match a known opening
+    pub opening: Option<OpeningInfo>,
+    /// Cells of all pieces giving check to the color to move
+    pub checkers: Vec<String>,
+    /// FEN letters of the pieces white captured, sorted by value
+    pub captured_by_white: Vec<String>,
+    /// FEN letters of the pieces black captured, sorted by value
+    pub captured_by_black: Vec<String>,
+    /// Material on the board in pawns from white's perspective (positive favors white)
+    pub material_difference: i32,
+    /// Material on the board in pawns of white and black
+    pub material: [i32; 2],
+    /// Opening, middlegame or endgame, judged by the pieces left and the move number
+    pub phase: GamePhase,
     pub your_turn: bool,
     pub finished: bool,
     pub winner: Color,
+    /// How the game ended, None while it's running. The flags below are kept for older clients.
+    pub termination: Option<Termination>,
     pub draw: bool,
     pub checkmate: bool,
     pub resign: bool,
     pub stalemate: bool,
     pub remis: bool,
+    /// No sequence of legal moves could lead to a checkmate anymore, e.g. insufficient material
+    pub dead_position: bool,
+    /// A player aborted the game before both sides moved, it has no winner and isn't a draw
+    pub aborted: bool,
+    /// The player to move abandoned the timed game and lost on time
+    pub timeout: bool,
+    /// Both players agreed to pause the game, nobody can move until both resume it
+    pub paused: bool,
+    /// The player who asked to pause or resume the game, waiting for the other to agree
+    pub pause_requested_by: Option<Color>,
+    /// None if the game is played without clocks
+    pub time_control: Option<TimeControl>,
+    /// Set if the game was played on another site
+    pub imported: Option<ImportedGame>,
+    /// If your opponent used the API recently, None if you're not a player or play against the AI
+    pub opponent_online: Option<bool>,
+    /// If your opponent is currently viewing this game, see POST /presence
+    pub opponent_viewing: Option<bool>,
+    /// Your own tags of this session, see PATCH /session/mark
+    pub tags: Vec<String>,
+    /// If you marked this session as favorite
+    pub favorite: bool,
+    /// If players get warned when their move leaves material hanging, see PATCH /session
+    pub blunder_alerts: bool,
+    /// How strong the AI plays, None if nobody plays against the AI
+    pub ai_difficulty: Option<AiDifficulty>,
+    /// Set while the AI fails to play its move, it is retried automatically until it gives up
+    pub ai_error: Option<AiError>,
+    /// Only in the response to your own move with blunder alerts on: the capture that wins the material your move left hanging
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blunder_warning: Option<HangingMaterial>,
 }
 
 impl SessionInfo {
-    pub async fn from_session(
-        state: &AppState,
-        session: Session,
-        key: String,
-    ) -> Result<Self, ApiError> {
+    /// Builds the info from a session whose player names are already known
+    pub fn new(session: Session, player_names: [String; 2], key: String) -> Result<Self, ApiError> {
         let id = session.id.unwrap_or_default();
         let finished = session.is_finished();
+        let termination = session.get_termination();
         let your_turn = session.can_move(key);
+        let pause_requested_by = session.get_pause_requester();
         let san = session.game_state.get_san();
-
-        let white_player = if &session.keys[0] == "AI" {
-            "AI".to_string()
-        } else {
-            match find_user_by_key(&state.database.user_collection, &session.keys[0]).await? {
-                Some(user) => user.display_name,
-                None => "Unknown".to_string(),
-            }
-        };
-
-        let black_player = if &session.keys[1] == "AI" {
-            "AI".to_string()
-        } else {
-            match find_user_by_key(&state.database.user_collection, &session.keys[1]).await? {
-                Some(user) => user.display_name,
-                None => "Unknown".to_string(),
-            }
+        let checkers = session
+            .game_state
+            .checkers
+            .iter()
+            .map(|index| Position::try_from(index).map(|position| position.as_str()))
+            .collect::<Result<Vec<String>, _>>()?;
+        let [captured_by_white, captured_by_black] = session.game_state.get_captured_pieces()?;
+        let to_letters = |pieces: Vec<Piece>, color: Color| -> Vec<String> {
+            pieces
+                .iter()
+                .map(|piece| piece.get_fen_letter(color))
+                .collect()
         };
+        let [white_player, black_player] = player_names;
 
         let info = Self {
             id: id.to_string(),
@@ -69,18 +236,199 @@ impl SessionInfo {
             fen: session.game_state.to_fen(),
             san,
             color_to_move: Color::from(session.game_state.next_to_move as usize),
+            opening: Opening::classify(&session.game_state.move_log).map(OpeningInfo::from),
+            checkers,
+            captured_by_white: to_letters(captured_by_white, Color::BLACK),
+            captured_by_black: to_letters(captured_by_black, Color::WHITE),
+            material_difference: session.game_state.get_material_difference(),
+            material: session.game_state.material(),
+            phase: session.game_state.phase(),
             your_turn,
             finished,
             winner: Color::from(session.game_state.winner as usize),
-            draw: session.game_state.draw,
-            checkmate: session.game_state.checkmate,
-            resign: session.game_state.resign,
-            stalemate: session.game_state.stalemate,
-            remis: session.game_state.remis,
+            termination,
+            draw: session.game_state.is_draw(),
+            checkmate: termination == Some(Termination::Checkmate),
+            resign: termination == Some(Termination::Resignation),
+            stalemate: termination == Some(Termination::Stalemate),
+            remis: termination == Some(Termination::FiftyMove),
+            dead_position: termination == Some(Termination::InsufficientMaterial),
+            aborted: session.aborted,
+            timeout: session.timed_out,
+            paused: session.paused,
+            pause_requested_by,
+            time_control: session.time_control,
+            imported: session.imported,
+            opponent_online: None,
+            opponent_viewing: None,
+            tags: Vec::new(),
+            favorite: false,
+            blunder_alerts: session.blunder_alerts,
+            ai_difficulty: session
+                .keys
+                .contains(&"AI".to_string())
+                .then_some(session.ai_difficulty),
+            ai_error: session.ai_error,
+            blunder_warning: None,
+        };
+
+        Ok(info)
+    }
+
+    /// Looks up the players and the presence of the opponent and builds the info
+    pub async fn from_session(
+        state: &AppState,
+        session: Session,
+        key: String,
+    ) -> Result<Self, ApiError> {
+        let players = session.get_players(&*state.storage).await?;
+        let session_id = session.id.map(|id| id.to_hex()).unwrap_or_default();
+        let mark = state.storage.find_session_mark(&key, &session_id).await?;
+        Self::from_players(state, session, players, mark, key).await
+    }
+
+    /// Like from_session, but the players and marks of all sessions are looked up at once
+    pub async fn from_sessions(
+        state: &AppState,
+        sessions: Vec<Session>,
+        key: String,
+    ) -> Result<Vec<Self>, ApiError> {
+        if sessions.is_empty() {
+            return Ok(Vec::new());
+        }
+        let player_keys = sessions
+            .iter()
+            .flat_map(|session| session.get_user_keys())
+            .collect();
+        let users = UserDirectory::load(&*state.storage, player_keys).await?;
+        let mut marks: HashMap<String, SessionMark> = state
+            .storage
+            .find_session_marks_by_key(&key)
+            .await?
+            .into_iter()
+            .map(|mark| (mark.session_id.clone(), mark))
+            .collect();
+
+        let mut infos = Vec::with_capacity(sessions.len());
+        for session in sessions {
+            let players = session.get_players_from(&users);
+            let session_id = session.id.map(|id| id.to_hex()).unwrap_or_default();
+            let mark = marks.remove(&session_id);
+            infos.push(Self::from_players(state, session, players, mark, key.clone()).await?);
+        }
+        Ok(infos)
+    }
+
+    async fn from_players(
+        state: &AppState,
+        session: Session,
+        players: [Option<User>; 2],
+        mark: Option<SessionMark>,
+        key: String,
+    ) -> Result<Self, ApiError> {
+        let player_names = session.get_names_of(&players);
+        let session_id = session.id.map(|id| id.to_hex()).unwrap_or_default();
+
+        let opponent = session
+            .get_color_from_key(&key)
+            .and_then(|color| players[1 - color as usize].as_ref());
+        let (opponent_online, opponent_viewing) = match opponent {
+            Some(opponent) => {
+                let viewing = state
+                    .presence
+                    .is_viewing(&session_id, &opponent.key)
+                    .await?;
+                (Some(opponent.is_online()), Some(viewing))
+            }
+            None => (None, None),
         };
 
+        let mut info = Self::new(session, player_names, key)?;
+        info.opponent_online = opponent_online;
+        info.opponent_viewing = opponent_viewing;
+        if let Some(mark) = mark {
+            info.tags = mark.tags;
+            info.favorite = mark.favorite;
+        }
         Ok(info)
     }
+
+    /// Extends the ETag of the session with what the info looks up besides it, like the names and the presence
+    pub fn get_etag(&self, session_etag: &str) -> String {
+        let presence = format!("{:?}{:?}", self.opponent_online, self.opponent_viewing);
+        let mark = format!("{:?}{}", self.tags, self.favorite);
+        etag::generate(&[
+            session_etag,
+            &self.white_player,
+            &self.black_player,
+            &presence,
+            &mark,
+        ])
+    }
+}
+
+/// Send the token back within the time to resign
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ResignConfirmation {
+    pub token: String,
+    /// Seconds until the token expires
+    pub expires_in: u64,
+}
+
+/// Session ids sent as JSON body, for example {"ids": ["65f0c0ffee0000000000beef"]}
+#[derive(Deserialize, ToSchema)]
+pub struct SessionBatchRequest {
+    pub ids: Vec<String>,
+}
+
+/// The position of a session after a given amount of plies
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SessionPosition {
+    /// Amount of plies played, 0 being the starting position
+    pub ply: usize,
+    /// Forsyth-Edwards Notation of the position
+    pub fen: String,
+    /// Standard Algebraic Notation of the move leading to the position, None for the starting position
+    pub last_move: Option<String>,
+    pub color_to_move: Color,
+    /// What the players annotated the last move with
+    pub annotations: Vec<Annotation>,
+}
+
+/// Sent to subscribers of a session whenever it changes
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
+pub struct SessionEvent {
+    pub id: String,
+    /// Amount of moves played so far
+    pub move_count: usize,
+    /// Standard Algebraic Notation of the last move, None before the first move
+    pub last_move: Option<String>,
+    /// Forsyth-Edwards Notation of the current game state
+    pub fen: String,
+    pub color_to_move: Color,
+    pub finished: bool,
+    pub winner: Color,
+    pub draw: bool,
+    pub aborted: bool,
+    pub paused: bool,
+}
+
+impl From<&Session> for SessionEvent {
+    fn from(session: &Session) -> Self {
+        let game_state = &session.game_state;
+        Self {
+            id: session.id.map(|id| id.to_hex()).unwrap_or_default(),
+            move_count: game_state.move_log.len(),
+            last_move: game_state.san_log.last().cloned(),
+            fen: game_state.to_fen(),
+            color_to_move: Color::from(game_state.next_to_move as usize),
+            finished: session.is_finished(),
+            winner: Color::from(game_state.winner as usize),
+            draw: game_state.is_draw(),
+            aborted: session.aborted,
+            paused: session.paused,
+        }
+    }
 }
 
 /// Your current available sessions
@@ -89,3 +437,89 @@ pub struct SessionList {
     pub sessions: Vec<SessionInfo>,
     pub pagination: Pagination,
 }
+
+/// Final artifacts of a finished session
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct SessionResult {
+    pub id: String,
+    pub name: String,
+    pub white_player: String,
+    pub black_player: String,
+    pub winner: Color,
+    /// The result in PGN notation: 1-0, 0-1 or 1/2-1/2
+    pub result: String,
+    /// Why the game ended, e.g. checkmate, resignation, stalemate or timeout
+    pub reason: String,
+    /// Forsyth-Edwards Notation of the final position
+    pub fen: String,
+    /// Portable Game Notation of the whole game
+    pub pgn: String,
+    /// Link to a render of the final position from white's perspective, valid for 7 days without an API key
+    pub render_url: String,
+}
+
+impl SessionResult {
+    pub async fn from_session(state: &AppState, session: &Session) -> Result<Self, ApiError> {
+        let reason = session.get_end_reason().ok_or(ApiError::BadRequest(
+            "Game is not finished yet.".to_string(),
+        ))?;
+
+        let id = session.id.unwrap_or_default().to_string();
+        let [white_player, black_player] = session.get_player_names(&*state.storage).await?;
+
+        let base_url =
+            env::var("PUBLIC_URL").unwrap_or("https://chess.lemon.industries".to_string());
+        let render_url = SignedRenderQuery::new(
+            id.clone(),
+            Color::WHITE,
+            RenderStyle::MODERN,
+            RESULT_RENDER_VALID_FOR_S,
+        )?
+        .to_url(&base_url);
+
+        Ok(Self {
+            id,
+            name: session.name.clone(),
+            white_player,
+            black_player,
+            winner: Color::from(session.game_state.winner as usize),
+            result: session.get_result_notation(),
+            reason,
+            fen: session.game_state.to_fen(),
+            pgn: session.to_pgn(state, None).await?,
+            render_url,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::state::GameState;
+
+    use super::*;
+
+    #[test]
+    fn test_session_info_new() {
+        let session = Session::new(
+            "Test".to_string(),
+            ["white".to_string(), "black".to_string()],
+            GameState::new().unwrap(),
+        );
+        let info = SessionInfo::new(
+            session,
+            ["Alice".to_string(), "Bob".to_string()],
+            "white".to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(info.white_player, "Alice");
+        assert_eq!(info.black_player, "Bob");
+        assert!(info.your_turn);
+        assert!(!info.finished);
+        assert!(info.checkers.is_empty());
+        assert!(info.captured_by_white.is_empty());
+        assert_eq!(info.material_difference, 0);
+        assert_eq!(info.material, [39, 39]);
+        assert_eq!(info.phase, GamePhase::OPENING);
+    }
+}
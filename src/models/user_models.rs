@@ -0,0 +1,373 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Days, NaiveDate};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    entities::{
+        session::Session,
+        user::{SecondaryKey, User},
+    },
+    error::ApiError,
+    game::review::PlayerAccuracy,
+    middleware::rate_limit::{bucket_key, list_buckets, tier_bucket, RateLimiter},
+    models::enums::{KeyScope, PermissionLevel, Platform},
+    storage::Storage,
+    utils::time_operations::{nanos_to_day, DAY_FORMAT},
+};
+
+use super::response_models::Pagination;
+
+/// Your own profile
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UserInfo {
+    /// The unique name of the user
+    pub name: String,
+    /// The name other people see
+    pub display_name: String,
+    /// The namespace the user belongs to, empty for the default namespace
+    pub namespace: String,
+    pub permission: PermissionLevel,
+    /// UNIX timestamp in nanoseconds when the user was created
+    pub created_stamp: u64,
+    /// Your user ids on the chat platforms you are linked with
+    pub platform_links: HashMap<Platform, String>,
+    /// If this is an engine account
+    pub bot: bool,
+    /// Name of the user who registered the bot account
+    pub operator: Option<String>,
+    /// Titles and badges awarded by admins, oldest first
+    pub titles: Vec<Title>,
+    /// Amount of sessions which aren't finished yet
+    pub active_sessions: u32,
+    /// Amount of rooms waiting for someone to join
+    pub open_rooms: u32,
+    /// Amount of your finished games which were reviewed
+    pub reviewed_games: u32,
+    /// Your average over all reviewed games, None if none were reviewed yet
+    pub average_accuracy: Option<PlayerAccuracy>,
+}
+
+impl UserInfo {
+    pub async fn from_user(storage: &dyn Storage, user: User) -> Result<Self, ApiError> {
+        let active_sessions = storage
+            .find_sessions_by_key_and_finished(&user.key, false)
+            .await?
+            .len() as u32;
+        let open_rooms = storage.find_rooms_by_key(&user.key).await?.len() as u32;
+
+        let accuracies: Vec<PlayerAccuracy> = storage
+            .find_sessions_by_key_and_finished(&user.key, true)
+            .await?
+            .iter()
+            .filter_map(|session| {
+                let color = session.get_color_from_key(&user.key)?;
+                session.accuracy.map(|accuracy| accuracy[color as usize])
+            })
+            .collect();
+        let reviewed_games = accuracies.len() as u32;
+        let average_accuracy = (reviewed_games > 0).then(|| {
+            let count = reviewed_games as f64;
+            PlayerAccuracy {
+                accuracy: (accuracies.iter().map(|a| a.accuracy).sum::<f64>() / count * 10.0)
+                    .round()
+                    / 10.0,
+                acpl: (accuracies.iter().map(|a| a.acpl as f64).sum::<f64>() / count).round()
+                    as u32,
+            }
+        });
+
+        Ok(Self {
+            name: user.name,
+            display_name: user.display_name,
+            namespace: user.namespace,
+            permission: user.permission,
+            created_stamp: user.created_stamp,
+            platform_links: user.platform_links,
+            bot: user.bot,
+            operator: user.operator,
+            titles: user.titles,
+            active_sessions,
+            open_rooms,
+            reviewed_games,
+            average_accuracy,
+        })
+    }
+}
+
+/// A title or badge awarded by an admin, e.g. for winning a tournament
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct Title {
+    pub name: String,
+    /// UNIX timestamp in nanoseconds when it was awarded
+    pub awarded_stamp: u64,
+}
+
+/// User information for administrators, the API key is never included
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UserAdminInfo {
+    /// The unique name of the user
+    pub name: String,
+    /// The name other people see
+    pub display_name: String,
+    /// The namespace the user belongs to, empty for the default namespace
+    pub namespace: String,
+    pub permission: PermissionLevel,
+    /// If the API key of the user has been revoked
+    pub banned: bool,
+    /// If this is an engine account
+    pub bot: bool,
+    pub titles: Vec<Title>,
+    /// The user ids on the chat platforms the user is linked with
+    pub platform_links: HashMap<Platform, String>,
+    /// UNIX timestamp in nanoseconds when the user was created
+    pub created_stamp: u64,
+    /// UNIX timestamp in nanoseconds of the last request
+    pub last_access_stamp: u64,
+    /// Amount of requests over all endpoints
+    pub total_requests: u64,
+    /// Amount of requests by "METHOD /path"
+    pub endpoint_usage: HashMap<String, u64>,
+}
+
+impl From<User> for UserAdminInfo {
+    fn from(user: User) -> Self {
+        Self {
+            name: user.name,
+            display_name: user.display_name,
+            namespace: user.namespace,
+            permission: user.permission,
+            banned: user.banned,
+            bot: user.bot,
+            titles: user.titles,
+            platform_links: user.platform_links,
+            created_stamp: user.created_stamp,
+            last_access_stamp: user.last_access_stamp,
+            total_requests: user.endpoint_usage.values().sum(),
+            endpoint_usage: user.endpoint_usage,
+        }
+    }
+}
+
+/// A list of users
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UserList {
+    pub users: Vec<UserAdminInfo>,
+    pub pagination: Pagination,
+}
+
+/// The rate limit state of one bucket
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct CooldownState {
+    pub bucket: String,
+    /// Routes sharing this bucket as "METHOD /path", empty for the default bucket of all other routes
+    pub routes: Vec<String>,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Milliseconds until the next request is allowed, 0 if it is allowed right now
+    pub retry_after_ms: u64,
+    /// Milliseconds until the bucket is completely refilled
+    pub reset_after_ms: u64,
+}
+
+/// Your API usage
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UsageInfo {
+    /// UNIX timestamp in nanoseconds of the last request
+    pub last_access_stamp: u64,
+    /// Amount of requests over all endpoints
+    pub total_requests: u64,
+    /// Amount of requests by "METHOD /path"
+    pub endpoint_usage: HashMap<String, u64>,
+    /// Rate limits of your API keys, buckets your permission level is exempt from are left out
+    pub cooldowns: Vec<CooldownState>,
+}
+
+impl UsageInfo {
+    /// The cooldowns are shared by the primary and all secondary keys of the user
+    pub async fn from_user(rate_limiter: &RateLimiter, user: User) -> Result<Self, ApiError> {
+        let mut cooldowns = Vec::new();
+        for (bucket, config, routes) in list_buckets() {
+            let Some(config) = tier_bucket(&user.permission, bucket, config) else {
+                continue;
+            };
+            let outcome = rate_limiter
+                .peek(&bucket_key(&user.key, bucket), &config)
+                .await?;
+            cooldowns.push(CooldownState {
+                bucket: bucket.to_string(),
+                routes,
+                limit: outcome.limit,
+                remaining: outcome.remaining,
+                retry_after_ms: outcome.retry_after_ms,
+                reset_after_ms: outcome.reset_after_ms,
+            });
+        }
+
+        Ok(Self {
+            last_access_stamp: user.last_access_stamp,
+            total_requests: user.endpoint_usage.values().sum(),
+            endpoint_usage: user.endpoint_usage,
+            cooldowns,
+        })
+    }
+}
+
+/// API usage of all users together
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UsageSummary {
+    pub users: u64,
+    /// Users who sent a request in the last 5 minutes
+    pub online_users: u64,
+    /// Amount of requests over all endpoints
+    pub total_requests: u64,
+    /// Amount of requests by "METHOD /path"
+    pub endpoint_usage: HashMap<String, u64>,
+    /// Requests which panicked on the answering instance since it started, of all namespaces
+    pub panics: u64,
+}
+
+/// A secondary API key
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ApiKeyInfo {
+    pub name: String,
+    pub scope: KeyScope,
+    /// UNIX timestamp in nanoseconds when the key was created
+    pub created_stamp: u64,
+    /// The key itself, only included right after it was created
+    pub api_key: Option<String>,
+}
+
+impl From<&SecondaryKey> for ApiKeyInfo {
+    fn from(secondary_key: &SecondaryKey) -> Self {
+        Self {
+            name: secondary_key.name.clone(),
+            scope: secondary_key.scope,
+            created_stamp: secondary_key.created_stamp,
+            api_key: None,
+        }
+    }
+}
+
+/// Your finished games of one day, games count on the day they were started
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DailyActivity {
+    /// UTC day like 2024-05-31
+    pub date: String,
+    pub games: u32,
+    pub wins: u32,
+}
+
+impl DailyActivity {
+    /// Counts the finished games of the user per UTC day, oldest day first, aborted ones aren't counted
+    pub fn aggregate<'a>(key: &str, sessions: impl IntoIterator<Item = &'a Session>) -> Vec<Self> {
+        let mut days: BTreeMap<String, Self> = BTreeMap::new();
+        for session in sessions {
+            let Some(color) = session.get_color_from_key(key) else {
+                continue;
+            };
+            // Aborted games were never played
+            if !session.is_finished() || session.aborted {
+                continue;
+            }
+
+            let date = nanos_to_day(session.created_stamp);
+            let day = days.entry(date.clone()).or_insert(Self {
+                date,
+                games: 0,
+                wins: 0,
+            });
+            day.games += 1;
+            if session.game_state.winner == color as u8 && !session.game_state.is_draw() {
+                day.wins += 1;
+            }
+        }
+        days.into_values().collect()
+    }
+}
+
+/// Your games per day within a range of days, days without games are left out
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ActivityInfo {
+    /// First UTC day of the range
+    pub from: String,
+    /// Last UTC day of the range
+    pub to: String,
+    pub days: Vec<DailyActivity>,
+    /// Days in a row with at least one game up to the last day, a streak isn't broken before the last day is over
+    pub current_streak: u32,
+    /// Most days in a row with at least one game within the range
+    pub longest_streak: u32,
+}
+
+impl ActivityInfo {
+    pub fn new(from: NaiveDate, to: NaiveDate, days: Vec<DailyActivity>) -> Self {
+        let dates: Vec<NaiveDate> = days
+            .iter()
+            .filter_map(|day| NaiveDate::parse_from_str(&day.date, DAY_FORMAT).ok())
+            .collect();
+
+        let mut streak = 0;
+        let mut longest_streak = 0;
+        let mut previous: Option<NaiveDate> = None;
+        for date in &dates {
+            streak = match previous.and_then(|previous| previous.checked_add_days(Days::new(1))) {
+                Some(next) if next == *date => streak + 1,
+                _ => 1,
+            };
+            longest_streak = longest_streak.max(streak);
+            previous = Some(*date);
+        }
+
+        let yesterday = to.checked_sub_days(Days::new(1));
+        let current_streak = match previous {
+            Some(last) if last == to || Some(last) == yesterday => streak,
+            _ => 0,
+        };
+
+        Self {
+            from: from.format(DAY_FORMAT).to_string(),
+            to: to.format(DAY_FORMAT).to_string(),
+            days,
+            current_streak,
+            longest_streak,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(date: &str) -> DailyActivity {
+        DailyActivity {
+            date: date.to_string(),
+            games: 1,
+            wins: 0,
+        }
+    }
+
+    #[test]
+    fn test_activity_streaks() {
+        let date = |date| NaiveDate::parse_from_str(date, DAY_FORMAT).unwrap();
+        let days = vec![
+            day("2024-02-27"),
+            day("2024-02-28"),
+            day("2024-02-29"),
+            day("2024-03-02"),
+            day("2024-03-03"),
+        ];
+
+        let info = ActivityInfo::new(date("2024-02-01"), date("2024-03-04"), days.clone());
+        assert_eq!(info.longest_streak, 3);
+        assert_eq!(info.current_streak, 2);
+        assert_eq!(info.to, "2024-03-04");
+
+        let info = ActivityInfo::new(date("2024-02-01"), date("2024-03-05"), days);
+        assert_eq!(info.current_streak, 0);
+
+        let info = ActivityInfo::new(date("2024-02-01"), date("2024-03-05"), Vec::new());
+        assert_eq!((info.current_streak, info.longest_streak), (0, 0));
+    }
+}
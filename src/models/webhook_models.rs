@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::session_models::SessionResult;
+
+/// Your registered webhook
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct WebhookInfo {
+    pub url: String,
+    /// Games of Discord users of this namespace are reported, empty for the default namespace
+    pub namespace: String,
+    /// UNIX timestamp in nanoseconds when the webhook was registered
+    pub created_stamp: u64,
+    /// Secret of the X-Lemon-Signature header, only included right after the webhook was registered
+    pub secret: Option<String>,
+}
+
+/// Body of the POST request sent to your webhook when a game of one of your users finished
+/// X-Lemon-Signature holds the hex encoded HMAC-SHA256 signature of the body, made with the secret of the webhook
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct GameFinishedEvent {
+    /// Discord id of white, None if white isn't linked with Discord
+    pub white_discord_id: Option<String>,
+    /// Discord id of black, None if black isn't linked with Discord
+    pub black_discord_id: Option<String>,
+    pub result: SessionResult,
+}
@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use futures::{Stream, StreamExt};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_util::sync::CancellationToken;
+
+use crate::{models::session_models::SessionEvent, AppState};
+
+/// How many events a slow subscriber can fall behind before it skips some
+const CHANNEL_CAPACITY: usize = 1024;
+/// How long to wait before watching again after the session stream broke off
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Hands session changes to the subscribers connected to this instance
+#[derive(Clone)]
+pub struct Notifier {
+    sender: broadcast::Sender<SessionEvent>,
+    /// Ends all subscriptions, open streams would otherwise keep the server from shutting down
+    closed: CancellationToken,
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            sender,
+            closed: CancellationToken::new(),
+        }
+    }
+
+    pub fn publish(&self, event: SessionEvent) {
+        // Fails if nobody is subscribed, which is fine
+        let _ = self.sender.send(event);
+    }
+
+    pub fn close(&self) {
+        self.closed.cancel();
+    }
+
+    /// Events of the given session, ends after the session finished
+    pub fn subscribe(&self, session_id: String) -> impl Stream<Item = SessionEvent> {
+        let receiver = self.sender.subscribe();
+        let closed = self.closed.clone();
+        futures::stream::unfold((receiver, false), move |(mut receiver, finished)| {
+            let session_id = session_id.clone();
+            let closed = closed.clone();
+            async move {
+                if finished {
+                    return None;
+                }
+                loop {
+                    let received = tokio::select! {
+                        _ = closed.cancelled() => return None,
+                        received = receiver.recv() => received,
+                    };
+                    match received {
+                        Ok(event) if event.id == session_id => {
+                            let finished = event.finished;
+                            return Some((event, (receiver, finished)));
+                        }
+                        Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            }
+        })
+    }
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Forwards every saved session to the notifier until shutdown is requested
+/// With MongoDB this includes moves made on other replicas
+pub async fn run(state: AppState, shutdown: CancellationToken) {
+    while !shutdown.is_cancelled() {
+        match state.storage.watch_sessions().await {
+            Ok(mut sessions) => loop {
+                let next = tokio::select! {
+                    _ = shutdown.cancelled() => return,
+                    next = sessions.next() => next,
+                };
+                match next {
+                    Some(Ok(session)) => state.notifier.publish(SessionEvent::from(&session)),
+                    Some(Err(error)) => {
+                        tracing::warn!(%error, "Failed to watch sessions");
+                        break;
+                    }
+                    None => break,
+                }
+            },
+            Err(error) => tracing::warn!(%error, "Failed to watch sessions"),
+        }
+
+        tokio::select! {
+            _ = shutdown.cancelled() => {}
+            _ = tokio::time::sleep(RETRY_INTERVAL) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::color::Color;
+
+    fn event(id: &str, move_count: usize, finished: bool) -> SessionEvent {
+        SessionEvent {
+            id: id.to_string(),
+            move_count,
+            last_move: None,
+            fen: String::new(),
+            color_to_move: Color::WHITE,
+            finished,
+            winner: Color::WHITE,
+            draw: false,
+            aborted: false,
+            paused: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscribe() {
+        let notifier = Notifier::new();
+        let events = notifier.subscribe("lemon".to_string());
+        notifier.publish(event("other", 1, false));
+        notifier.publish(event("lemon", 1, false));
+        notifier.publish(event("lemon", 2, true));
+        notifier.publish(event("lemon", 3, false));
+
+        let move_counts: Vec<usize> = events.map(|event| event.move_count).collect().await;
+        assert_eq!(move_counts, vec![1, 2]);
+    }
+}
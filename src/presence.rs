@@ -0,0 +1,115 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use redis::aio::MultiplexedConnection;
+
+use crate::{error::ApiError, utils::time_operations::timestamp_now_nanos};
+
+/// Clients have to send a heartbeat at least this often to keep counting as viewing a game
+pub const VIEWING_TTL_MS: u64 = 60_000;
+
+#[derive(Clone)]
+enum Backend {
+    /// Expiry timestamp in milliseconds by key
+    Memory(Arc<Mutex<HashMap<String, u64>>>),
+    Redis(MultiplexedConnection),
+}
+
+/// Keeps track of who is currently looking at which game, shared across all API replicas if backed by Redis
+#[derive(Clone)]
+pub struct PresenceTracker {
+    backend: Backend,
+}
+
+fn get_viewing_key(session_id: &str, user_key: &str) -> String {
+    format!("presence:{}:{}", session_id, user_key)
+}
+
+impl PresenceTracker {
+    pub fn new_in_memory() -> Self {
+        Self {
+            backend: Backend::Memory(Arc::new(Mutex::new(HashMap::new()))),
+        }
+    }
+
+    pub fn new_redis(connection: MultiplexedConnection) -> Self {
+        Self {
+            backend: Backend::Redis(connection),
+        }
+    }
+
+    pub fn setup(redis: Option<MultiplexedConnection>) -> Self {
+        match redis {
+            Some(connection) => Self::new_redis(connection),
+            None => Self::new_in_memory(),
+        }
+    }
+
+    /// The user counts as viewing the session until the TTL runs out without another heartbeat
+    pub async fn mark_viewing(&self, session_id: &str, user_key: &str) -> Result<(), ApiError> {
+        let key = get_viewing_key(session_id, user_key);
+        match &self.backend {
+            Backend::Memory(entries) => {
+                let now_ms = timestamp_now_nanos() / 1_000_000;
+                let mut entries = entries
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                entries.retain(|_, expires_ms| *expires_ms > now_ms);
+                entries.insert(key, now_ms + VIEWING_TTL_MS);
+                Ok(())
+            }
+            Backend::Redis(connection) => {
+                let _: () = redis::cmd("SET")
+                    .arg(key)
+                    .arg(1)
+                    .arg("PX")
+                    .arg(VIEWING_TTL_MS)
+                    .query_async(&mut connection.clone())
+                    .await
+                    .map_err(|err| ApiError::ServerError(err.to_string()))?;
+                Ok(())
+            }
+        }
+    }
+
+    pub async fn is_viewing(&self, session_id: &str, user_key: &str) -> Result<bool, ApiError> {
+        let key = get_viewing_key(session_id, user_key);
+        match &self.backend {
+            Backend::Memory(entries) => {
+                let now_ms = timestamp_now_nanos() / 1_000_000;
+                let entries = entries
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                Ok(entries
+                    .get(&key)
+                    .is_some_and(|expires_ms| *expires_ms > now_ms))
+            }
+            Backend::Redis(connection) => {
+                let exists: bool = redis::cmd("EXISTS")
+                    .arg(key)
+                    .query_async(&mut connection.clone())
+                    .await
+                    .map_err(|err| ApiError::ServerError(err.to_string()))?;
+                Ok(exists)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_presence() {
+        let presence = PresenceTracker::new_in_memory();
+        assert!(!presence.is_viewing("session", "lemon").await.unwrap());
+
+        presence.mark_viewing("session", "lemon").await.unwrap();
+        assert!(presence.is_viewing("session", "lemon").await.unwrap());
+        assert!(!presence.is_viewing("session", "lime").await.unwrap());
+        assert!(!presence.is_viewing("other", "lemon").await.unwrap());
+    }
+}
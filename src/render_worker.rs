@@ -0,0 +1,93 @@
+use std::time::{Duration, Instant};
+
+use mongodb::bson::oid::ObjectId;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    entities::{render_job::RenderJob, session::find_session_or_archived_by_id},
+    error::ApiError,
+    game::render::render_history_gif,
+    AppState,
+};
+
+/// How long the worker waits before looking for new jobs if the queue was empty
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Works through queued render jobs until shutdown is requested, every replica runs one worker
+pub async fn run(state: AppState, shutdown: CancellationToken) {
+    let mut last_cleanup: Option<Instant> = None;
+
+    while !shutdown.is_cancelled() {
+        if last_cleanup.is_none_or(|stamp| stamp.elapsed() >= CLEANUP_INTERVAL) {
+            cleanup(&state).await;
+            last_cleanup = Some(Instant::now());
+        }
+
+        // Never cancelled midway, a claimed job would otherwise be stuck until it counts as abandoned
+        match state.storage.claim_next_render_job().await {
+            Ok(Some(job)) => {
+                // Tracked, so shutdown waits for the running job instead of abandoning it
+                let job_state = state.clone();
+                state
+                    .tasks
+                    .spawn(async move { process(&job_state, job).await })
+                    .await
+                    .ok();
+            }
+            Ok(None) => {
+                tokio::select! {
+                    _ = shutdown.cancelled() => {}
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                }
+            }
+            Err(error) => {
+                tracing::error!(%error, "Failed to claim render job");
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+async fn cleanup(state: &AppState) {
+    match state.storage.delete_expired_render_jobs().await {
+        Ok(0) => {}
+        Ok(count) => tracing::info!(count, "Deleted expired render jobs"),
+        Err(error) => tracing::error!(%error, "Failed to delete expired render jobs"),
+    }
+}
+
+async fn process(state: &AppState, job: RenderJob) {
+    let storage = &*state.storage;
+    let result = match render(state, &job).await {
+        Ok(result_id) => job.complete(storage, result_id).await,
+        Err(error) => job.fail(storage, &error.to_string()).await,
+    };
+
+    if let Err(error) = result {
+        let job_id = job.id.map(|id| id.to_hex()).unwrap_or_default();
+        tracing::error!(job_id, session_id = %job.session_id, %error, "Failed to update render job");
+    }
+}
+
+/// Renders the GIF and uploads it, returns the id of the uploaded file
+async fn render(state: &AppState, job: &RenderJob) -> Result<ObjectId, ApiError> {
+    let session = find_session_or_archived_by_id(&*state.storage, &job.session_id.to_hex())
+        .await?
+        .ok_or(ApiError::NotFound("Session not found".to_string()))?;
+
+    let theme = job.style.retrieve()?;
+    let plies = job.plies.retrieve(session.game_state.move_log.len())?;
+    let color = job.color;
+    // Rendering takes up to several seconds, which would block the async runtime
+    let gif_bytes = tokio::task::spawn_blocking(move || {
+        render_history_gif(&session.game_state, color, &theme, plies)
+    })
+    .await
+    .map_err(|error| ApiError::ServerError(error.to_string()))??;
+
+    state
+        .storage
+        .store_render_result(&format!("{}.gif", job.session_id.to_hex()), gif_bytes)
+        .await
+}
@@ -0,0 +1,560 @@
+use crate::entities::audit_entry::{find_audit_entries_with_pagination, AuditEntry};
+use crate::entities::user::{find_users_with_pagination, get_usage_summary, User};
+use crate::error::ApiError;
+use crate::extractors::authentication::ExtractUser;
+use crate::models::audit_models::AuditAction;
+use crate::models::enums::PermissionLevel;
+use crate::models::query_models::{
+    AiRetryQuery, AuditLogQuery, PaginationQuery, PermissionChange, TitleAward, TitleReset,
+    UserBan, UserListQuery, UserName,
+};
+use crate::models::response_models::MessageResponse;
+use crate::models::session_models::AiRetryReport;
+use crate::models::user_models::UserAdminInfo;
+use crate::services::session_service;
+use crate::utils::sanitize::{Sanitize, SanitizePolicy};
+use crate::AppState;
+use axum::extract::{Query, State};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, patch, post};
+use axum::{Json, Router};
+
+/// Looks up the user an admin wants to manage, admins can't manage themselves to not lock themselves out
+async fn find_managed_user(state: &AppState, admin: &User, name: &str) -> Result<User, ApiError> {
+    let user = state
+        .storage
+        .find_user_by_name(name)
+        .await?
+        .ok_or(ApiError::NotFound("User not found".to_string()))?;
+    if user.key == admin.key {
+        return Err(ApiError::BadRequest(
+            "You can't manage your own user".to_string(),
+        ));
+    }
+    Ok(user)
+}
+
+/// List users.
+///
+/// ADMIN ONLY! This endpoint lists all users with their permission level and usage statistics, oldest users first.
+#[utoipa::path(
+    get,
+    path = "/admin/users",
+    params(PaginationQuery, UserListQuery),
+    responses(
+        (status = 200, description = "List of users", body = UserList),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "No permission to use this endpoint"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn get_admin_users(
+    ExtractUser(admin): ExtractUser,
+    State(state): State<AppState>,
+    pagination: Query<PaginationQuery>,
+    query: Query<UserListQuery>,
+) -> Result<Response, ApiError> {
+    admin.permission.authenticate(PermissionLevel::Admin)?;
+    let (page, page_size) = pagination.retrieve();
+    let user_list =
+        find_users_with_pagination(&state, query.namespace.as_deref(), page, page_size).await?;
+    Ok(Json(user_list).into_response())
+}
+
+/// Retrieve the API usage of all users.
+///
+/// ADMIN ONLY! This endpoint adds up the endpoint usage of all users, optionally only of one namespace.
+#[utoipa::path(
+    get,
+    path = "/admin/usage",
+    params(UserListQuery),
+    responses(
+        (status = 200, description = "API usage of all users", body = UsageSummary),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "No permission to use this endpoint"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn get_admin_usage(
+    ExtractUser(admin): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<UserListQuery>,
+) -> Result<Response, ApiError> {
+    admin.permission.authenticate(PermissionLevel::Admin)?;
+    let mut summary = get_usage_summary(&*state.storage, query.namespace.as_deref()).await?;
+    summary.panics = state.panics.get_panic_count();
+    Ok(Json(summary).into_response())
+}
+
+/// Change the permission level of a user.
+///
+/// ADMIN ONLY! This endpoint makes a user a regular User, a Negotiator or an Admin.
+#[utoipa::path(
+    patch,
+    path = "/admin/user/permission",
+    params(PermissionChange),
+    responses(
+        (status = 200, description = "Permission level changed", body = UserAdminInfo),
+        (status = 400, description = "Tried to change your own permission level"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "No permission to use this endpoint"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn patch_admin_user_permission(
+    ExtractUser(admin): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<PermissionChange>,
+) -> Result<Response, ApiError> {
+    admin.permission.authenticate(PermissionLevel::Admin)?;
+    let user = find_managed_user(&state, &admin, &query.name).await?;
+    let details = format!("{:?} to {:?}", user.permission, query.permission);
+    // Only the permission is written, other changes made to the user in the meantime are kept
+    let user = state
+        .storage
+        .set_user_permission(&user.key, query.0.permission)
+        .await?
+        .ok_or(ApiError::NotFound("User not found".to_string()))?;
+    AuditEntry::new(AuditAction::PermissionChanged, &admin)
+        .target(&user.name)
+        .details(details)
+        .record(&*state.storage)
+        .await;
+    Ok(Json(UserAdminInfo::from(user)).into_response())
+}
+
+/// Ban a user.
+///
+/// ADMIN ONLY! This endpoint revokes the API key of a user, every request with it is rejected until the ban is lifted.
+/// Sessions and rooms of the user are kept.
+#[utoipa::path(
+    post,
+    path = "/admin/user/ban",
+    params(UserBan),
+    responses(
+        (status = 200, description = "Ban updated", body = UserAdminInfo),
+        (status = 400, description = "Tried to ban yourself"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "No permission to use this endpoint"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn post_admin_user_ban(
+    ExtractUser(admin): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<UserBan>,
+) -> Result<Response, ApiError> {
+    admin.permission.authenticate(PermissionLevel::Admin)?;
+    let user = find_managed_user(&state, &admin, &query.name).await?;
+    let user = state
+        .storage
+        .set_user_banned(&user.key, query.banned.unwrap_or(true))
+        .await?
+        .ok_or(ApiError::NotFound("User not found".to_string()))?;
+    let action = match user.banned {
+        true => AuditAction::UserBanned,
+        false => AuditAction::UserUnbanned,
+    };
+    AuditEntry::new(action, &admin)
+        .target(&user.name)
+        .record(&*state.storage)
+        .await;
+    Ok(Json(UserAdminInfo::from(user)).into_response())
+}
+
+/// Award a title.
+///
+/// ADMIN ONLY! This endpoint adds a title or badge to the profile of a user, e.g. for winning a tournament.
+#[utoipa::path(
+    post,
+    path = "/admin/user/title",
+    params(TitleAward),
+    responses(
+        (status = 200, description = "Title awarded", body = UserAdminInfo),
+        (status = 400, description = "Invalid title, too many titles or tried to award yourself"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "No permission to use this endpoint"),
+        (status = 404, description = "User not found"),
+        (status = 409, description = "The user already holds this title"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn post_admin_user_title(
+    ExtractUser(admin): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<TitleAward>,
+) -> Result<Response, ApiError> {
+    admin.permission.authenticate(PermissionLevel::Admin)?;
+    let user = find_managed_user(&state, &admin, &query.name).await?;
+    let query = query.sanitize(SanitizePolicy::for_namespace(&user.namespace))?;
+    let title = user.new_title(&query.title)?;
+    let user = state
+        .storage
+        .push_user_title(&user.key, &title)
+        .await?
+        .ok_or(ApiError::NotFound("User not found".to_string()))?;
+    AuditEntry::new(AuditAction::TitleAwarded, &admin)
+        .target(&user.name)
+        .details(&query.title)
+        .record(&*state.storage)
+        .await;
+    Ok(Json(UserAdminInfo::from(user)).into_response())
+}
+
+/// Reset titles.
+///
+/// ADMIN ONLY! This endpoint removes one title or all titles from the profile of a user.
+#[utoipa::path(
+    delete,
+    path = "/admin/user/title",
+    params(TitleReset),
+    responses(
+        (status = 200, description = "Titles removed", body = UserAdminInfo),
+        (status = 400, description = "Tried to reset your own titles"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "No permission to use this endpoint"),
+        (status = 404, description = "User not found or the user doesn't hold the title"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn delete_admin_user_title(
+    ExtractUser(admin): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<TitleReset>,
+) -> Result<Response, ApiError> {
+    admin.permission.authenticate(PermissionLevel::Admin)?;
+    let user = find_managed_user(&state, &admin, &query.name).await?;
+    let (user, removed) = state
+        .storage
+        .pull_user_titles(&user.key, query.title.as_deref())
+        .await?
+        .ok_or(ApiError::NotFound("User not found".to_string()))?;
+    let details = match &query.title {
+        Some(title) => title.clone(),
+        None => format!("All {} titles", removed),
+    };
+    AuditEntry::new(AuditAction::TitlesReset, &admin)
+        .target(&user.name)
+        .details(&details)
+        .record(&*state.storage)
+        .await;
+    Ok(Json(UserAdminInfo::from(user)).into_response())
+}
+
+/// Delete a user.
+///
+/// ADMIN ONLY! This endpoint deletes a user together with their open rooms, friendships, notifications, session tags and webhook.
+/// Sessions are kept, so their opponents don't lose their game history.
+#[utoipa::path(
+    delete,
+    path = "/admin/user",
+    params(UserName),
+    responses(
+        (status = 200, description = "User deleted", body = MessageResponse),
+        (status = 400, description = "Tried to delete yourself"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "No permission to use this endpoint"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn delete_admin_user(
+    ExtractUser(admin): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<UserName>,
+) -> Result<Response, ApiError> {
+    admin.permission.authenticate(PermissionLevel::Admin)?;
+    let user = find_managed_user(&state, &admin, &query.name).await?;
+    for room in state.storage.find_rooms_by_key(&user.key).await? {
+        state.storage.delete_room_by_code(&room.code).await?;
+    }
+    for friendship in state.storage.find_friendships_by_key(&user.key).await? {
+        state.storage.delete_friendship(&friendship).await?;
+    }
+    state.storage.delete_notifications_by_key(&user.key).await?;
+    state.storage.delete_webhook_by_key(&user.key).await?;
+    state.storage.delete_session_marks_by_key(&user.key).await?;
+    state.storage.delete_user_by_key(&user.key).await?;
+    AuditEntry::new(AuditAction::UserDeleted, &admin)
+        .target(&user.name)
+        .record(&*state.storage)
+        .await;
+    Ok(Json(MessageResponse {
+        message: format!("User {} deleted", user.name),
+    })
+    .into_response())
+}
+
+/// Retrieve the audit log.
+///
+/// ADMIN ONLY! This endpoint lists security relevant events like user creations, permission changes, bans and resignations, newest first.
+#[utoipa::path(
+    get,
+    path = "/admin/audit",
+    params(PaginationQuery, AuditLogQuery),
+    responses(
+        (status = 200, description = "Audit log entries", body = AuditLog),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "No permission to use this endpoint"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn get_admin_audit(
+    ExtractUser(admin): ExtractUser,
+    State(state): State<AppState>,
+    pagination: Query<PaginationQuery>,
+    query: Query<AuditLogQuery>,
+) -> Result<Response, ApiError> {
+    admin.permission.authenticate(PermissionLevel::Admin)?;
+    let (page, page_size) = pagination.retrieve();
+    let audit_log = find_audit_entries_with_pagination(&state, &query, page, page_size).await?;
+    Ok(Json(audit_log).into_response())
+}
+
+/// Retrieve the background tasks.
+///
+/// ADMIN ONLY! This endpoint shows how often the maintenance tasks which expire rooms, forfeit abandoned timed games, archive old sessions and retry failed AI moves ran on the answering instance and what they changed.
+#[utoipa::path(
+    get,
+    path = "/admin/scheduler",
+    responses(
+        (status = 200, description = "Background tasks of this instance", body = SchedulerInfo),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "No permission to use this endpoint"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn get_admin_scheduler(
+    ExtractUser(admin): ExtractUser,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    admin.permission.authenticate(PermissionLevel::Admin)?;
+    Ok(Json(state.scheduler.get_info()).into_response())
+}
+
+/// Retry stuck AI sessions.
+///
+/// ADMIN ONLY! This endpoint lets the AI try its move again right away in sessions where it failed to move, even if it already gave up retrying.
+#[utoipa::path(
+    post,
+    path = "/admin/ai/retry",
+    params(AiRetryQuery),
+    responses(
+        (status = 200, description = "AI moves retried", body = AiRetryReport),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "No permission to use this endpoint"),
+        (status = 404, description = "Session not found"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn post_admin_ai_retry(
+    ExtractUser(admin): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<AiRetryQuery>,
+) -> Result<Response, ApiError> {
+    admin.permission.authenticate(PermissionLevel::Admin)?;
+    let session_ids = match &query.session_id {
+        Some(session_id) => vec![session_id.clone()],
+        None => state
+            .storage
+            .find_failed_ai_sessions()
+            .await?
+            .into_iter()
+            .filter_map(|session| session.id.map(|id| id.to_hex()))
+            .collect(),
+    };
+
+    let mut report = AiRetryReport::default();
+    for session_id in session_ids {
+        match session_service::retry_ai_move(&state, &session_id, true).await? {
+            Some(true) => report.recovered += 1,
+            Some(false) => report.failed.push(session_id),
+            None => continue,
+        }
+        report.retried += 1;
+    }
+
+    AuditEntry::new(AuditAction::AiMovesRetried, &admin)
+        .details(format!(
+            "{} of {} recovered",
+            report.recovered, report.retried
+        ))
+        .record(&*state.storage)
+        .await;
+    Ok(Json(report).into_response())
+}
+
+pub fn router() -> Router<AppState> {
+    Router::<AppState>::new()
+        .route("/admin/users", get(get_admin_users))
+        .route("/admin/usage", get(get_admin_usage))
+        .route("/admin/user/permission", patch(patch_admin_user_permission))
+        .route("/admin/user/ban", post(post_admin_user_ban))
+        .route(
+            "/admin/user/title",
+            post(post_admin_user_title).delete(delete_admin_user_title),
+        )
+        .route("/admin/user", delete(delete_admin_user))
+        .route("/admin/audit", get(get_admin_audit))
+        .route("/admin/scheduler", get(get_admin_scheduler))
+        .route("/admin/ai/retry", post(post_admin_ai_retry))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+
+    use super::*;
+    use crate::test_utils::*;
+
+    #[tokio::test]
+    async fn test_admin_ban() {
+        let state = test_state();
+        let admin = create_user(&state, "admin").await;
+        let user = create_user(&state, "lemon").await;
+
+        let (status, _) = send(&state, Method::GET, "/admin/users", &admin).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        set_permission(&state, &admin, PermissionLevel::Admin).await;
+
+        let (status, users) = send(&state, Method::GET, "/admin/users", &admin).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(users["pagination"]["total"], 2);
+
+        let (status, _) = send(&state, Method::POST, "/admin/user/ban?name=admin", &admin).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, info) = send(&state, Method::POST, "/admin/user/ban?name=lemon", &admin).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(info["banned"], true);
+
+        let (status, _) = send(&state, Method::GET, "/", &user).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, audit_log) = send(
+            &state,
+            Method::GET,
+            "/admin/audit?action=USER_BANNED",
+            &admin,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(audit_log["pagination"]["total"], 1);
+        assert_eq!(audit_log["entries"][0]["actor"], "admin");
+        assert_eq!(audit_log["entries"][0]["target"], "lemon");
+    }
+
+    #[tokio::test]
+    async fn test_admin_titles() {
+        let state = test_state();
+        let admin = create_user(&state, "admin").await;
+        let user = create_user(&state, "lemon").await;
+
+        let (status, _) = send(
+            &state,
+            Method::POST,
+            "/admin/user/title?name=lemon&title=Champion",
+            &user,
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        set_permission(&state, &admin, PermissionLevel::Admin).await;
+
+        for title in ["Champion", "Puzzle%20Master"] {
+            let uri = format!("/admin/user/title?name=lemon&title={}", title);
+            let (status, _) = send(&state, Method::POST, &uri, &admin).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        let (status, _) = send(
+            &state,
+            Method::POST,
+            "/admin/user/title?name=lemon&title=Champion",
+            &admin,
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (_, info) = send(&state, Method::GET, "/user/me", &user).await;
+        assert_eq!(info["titles"][0]["name"], "Champion");
+        assert_eq!(info["titles"][1]["name"], "Puzzle Master");
+
+        let (status, info) = send(
+            &state,
+            Method::DELETE,
+            "/admin/user/title?name=lemon&title=Champion",
+            &admin,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(info["titles"].as_array().unwrap().len(), 1);
+        let (status, _) = send(
+            &state,
+            Method::DELETE,
+            "/admin/user/title?name=lemon&title=Champion",
+            &admin,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, info) = send(
+            &state,
+            Method::DELETE,
+            "/admin/user/title?name=lemon",
+            &admin,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(info["titles"].as_array().unwrap().is_empty());
+
+        let (_, audit_log) = send(
+            &state,
+            Method::GET,
+            "/admin/audit?action=TITLES_RESET",
+            &admin,
+        )
+        .await;
+        assert_eq!(audit_log["entries"][0]["details"], "All 1 titles");
+    }
+}
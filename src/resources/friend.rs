@@ -0,0 +1,286 @@
+use crate::entities::friendship::{get_friend_list, get_lock_key, Friendship};
+use crate::entities::notification::Notification;
+use crate::entities::user::User;
+use crate::error::ApiError;
+use crate::extractors::authentication::ExtractUser;
+use crate::models::notification_models::NotificationKind;
+use crate::models::query_models::UserName;
+use crate::models::response_models::MessageResponse;
+use crate::AppState;
+use axum::extract::{Query, State};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+
+/// Looks up the other user, only users of the same namespace can be friends
+async fn find_other_user(state: &AppState, user: &User, name: &str) -> Result<User, ApiError> {
+    let other = match state.storage.find_user_by_name(name).await? {
+        Some(other) if other.namespace == user.namespace => other,
+        _ => return Err(ApiError::NotFound("User not found".to_string())),
+    };
+    if other.key == user.key {
+        return Err(ApiError::BadRequest(
+            "You can't be friends with yourself".to_string(),
+        ));
+    }
+    Ok(other)
+}
+
+async fn notify_accepted(state: &AppState, user: &User, requester: &User) {
+    Notification::new(
+        &requester.key,
+        NotificationKind::FriendAccepted,
+        format!("{} accepted your friend request", user.display_name),
+    )
+    .from(&user.name)
+    .send(&*state.storage)
+    .await;
+}
+
+/// Retrieve your friends.
+///
+/// This endpoint lists your friends with their online status and your running sessions against them, as well as pending friend requests.
+#[utoipa::path(
+    get,
+    path = "/friends",
+    responses(
+        (status = 200, description = "Your friends and friend requests", body = FriendList),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Friends"
+)]
+async fn get_friends(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    let friend_list = get_friend_list(&*state.storage, &user).await?;
+    Ok(Json(friend_list).into_response())
+}
+
+/// Send a friend request.
+///
+/// This endpoint sends a friend request to the given user. If they already sent you one, you become friends right away.
+#[utoipa::path(
+    post,
+    path = "/friends/request",
+    params(UserName),
+    responses(
+        (status = 200, description = "Request sent or accepted", body = MessageResponse),
+        (status = 400, description = "Tried to befriend yourself"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "User not found"),
+        (status = 409, description = "Already friends or request already sent"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Friends"
+)]
+async fn post_friends_request(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<UserName>,
+) -> Result<Response, ApiError> {
+    let other = find_other_user(&state, &user, &query.name).await?;
+
+    // Both users sending a request at once must not leave two pending requests
+    let lock = state
+        .locks
+        .acquire(&get_lock_key(&user.key, &other.key))
+        .await?;
+    let message = match state.storage.find_friendship(&user.key, &other.key).await? {
+        Some(friendship) if friendship.accepted => {
+            return Err(ApiError::Conflict("You are already friends".to_string()))
+        }
+        Some(friendship) if friendship.requester == user.key => {
+            return Err(ApiError::Conflict(
+                "You already sent a friend request".to_string(),
+            ))
+        }
+        Some(mut friendship) => {
+            friendship.accept();
+            state.storage.save_friendship(&friendship).await?;
+            notify_accepted(&state, &user, &other).await;
+            format!("You are now friends with {}", other.name)
+        }
+        None => {
+            let friendship = Friendship::new(user.key.clone(), other.key.clone());
+            state.storage.save_friendship(&friendship).await?;
+            Notification::new(
+                &other.key,
+                NotificationKind::FriendRequest,
+                format!("{} sent you a friend request", user.display_name),
+            )
+            .from(&user.name)
+            .send(&*state.storage)
+            .await;
+            format!("Friend request sent to {}", other.name)
+        }
+    };
+    lock.release().await;
+
+    Ok(Json(MessageResponse { message }).into_response())
+}
+
+/// Accept a friend request.
+///
+/// This endpoint accepts the friend request the given user sent you.
+#[utoipa::path(
+    post,
+    path = "/friends/accept",
+    params(UserName),
+    responses(
+        (status = 200, description = "Request accepted", body = MessageResponse),
+        (status = 400, description = "Tried to befriend yourself"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "User or friend request not found"),
+        (status = 409, description = "Already friends"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Friends"
+)]
+async fn post_friends_accept(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<UserName>,
+) -> Result<Response, ApiError> {
+    let other = find_other_user(&state, &user, &query.name).await?;
+
+    let lock = state
+        .locks
+        .acquire(&get_lock_key(&user.key, &other.key))
+        .await?;
+    let mut friendship = match state.storage.find_friendship(&user.key, &other.key).await? {
+        Some(friendship) if friendship.accepted => {
+            return Err(ApiError::Conflict("You are already friends".to_string()))
+        }
+        Some(friendship) if friendship.addressee == user.key => friendship,
+        _ => return Err(ApiError::NotFound("Friend request not found".to_string())),
+    };
+    friendship.accept();
+    state.storage.save_friendship(&friendship).await?;
+    lock.release().await;
+    notify_accepted(&state, &user, &other).await;
+
+    Ok(Json(MessageResponse {
+        message: format!("You are now friends with {}", other.name),
+    })
+    .into_response())
+}
+
+/// Remove a friend.
+///
+/// This endpoint removes the given user from your friends, it also declines or withdraws pending friend requests.
+#[utoipa::path(
+    delete,
+    path = "/friends",
+    params(UserName),
+    responses(
+        (status = 200, description = "Friend or request removed", body = MessageResponse),
+        (status = 400, description = "Tried to remove yourself"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "User or friendship not found"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Friends"
+)]
+async fn delete_friends(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<UserName>,
+) -> Result<Response, ApiError> {
+    let other = find_other_user(&state, &user, &query.name).await?;
+
+    let lock = state
+        .locks
+        .acquire(&get_lock_key(&user.key, &other.key))
+        .await?;
+    let friendship = state
+        .storage
+        .find_friendship(&user.key, &other.key)
+        .await?
+        .ok_or(ApiError::NotFound("Friendship not found".to_string()))?;
+    state.storage.delete_friendship(&friendship).await?;
+    lock.release().await;
+
+    Ok(Json(MessageResponse {
+        message: format!("Removed {}", other.name),
+    })
+    .into_response())
+}
+
+pub fn router() -> Router<AppState> {
+    Router::<AppState>::new()
+        .route("/friends", get(get_friends))
+        .route("/friends", delete(delete_friends))
+        .route("/friends/request", post(post_friends_request))
+        .route("/friends/accept", post(post_friends_accept))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+
+    use crate::test_utils::*;
+
+    #[tokio::test]
+    async fn test_friends() {
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let lime = create_user(&state, "lime").await;
+
+        let (status, _) = send(&state, Method::POST, "/friends/request?name=lime", &lemon).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&state, Method::POST, "/friends/request?name=lime", &lemon).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (status, _) = send(&state, Method::POST, "/friends/accept?name=lime", &lemon).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (_, list) = send(&state, Method::GET, "/friends", &lime).await;
+        assert_eq!(list["incoming"][0]["name"], "lemon");
+
+        let (status, _) = send(&state, Method::POST, "/friends/accept?name=lemon", &lime).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (_, list) = send(&state, Method::GET, "/friends", &lemon).await;
+        assert_eq!(list["friends"][0]["name"], "lime");
+        assert_eq!(list["friends"][0]["online"], true);
+        assert!(list["incoming"].as_array().unwrap().is_empty());
+
+        let (status, _) = send(&state, Method::DELETE, "/friends?name=lemon", &lime).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, list) = send(&state, Method::GET, "/friends", &lemon).await;
+        assert!(list["friends"].as_array().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_crossed_friend_requests() {
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let lime = create_user(&state, "lime").await;
+
+        let (first, second) = tokio::join!(
+            send(&state, Method::POST, "/friends/request?name=lime", &lemon),
+            send(&state, Method::POST, "/friends/request?name=lemon", &lime),
+        );
+        assert_eq!(first.0, StatusCode::OK);
+        assert_eq!(second.0, StatusCode::OK);
+
+        // The later request accepted the earlier one instead of adding a second
+        let (_, list) = send(&state, Method::GET, "/friends", &lemon).await;
+        assert_eq!(list["friends"].as_array().unwrap().len(), 1);
+        assert!(list["incoming"].as_array().unwrap().is_empty());
+        assert!(list["outgoing"].as_array().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,64 @@
+use crate::game::render::get_required_assets;
+use crate::models::response_models::{HealthReport, MessageResponse};
+use crate::AppState;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::{routing::get, Json, Router};
+use std::path::Path;
+
+/// Liveness probe.
+///
+/// This endpoint responds as long as the server is running, it doesn't require an API key.
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    responses(
+        (status = 200, description = "Server is running", body = MessageResponse),
+    ),
+    tag = "Misc"
+)]
+async fn get_health_live() -> Response {
+    let response = MessageResponse {
+        message: "OK".to_string(),
+    };
+    Json(response).into_response()
+}
+
+/// Readiness probe.
+///
+/// This endpoint checks if the storage backend is reachable and all render assets exist, it doesn't require an API key.
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    responses(
+        (status = 200, description = "Ready to receive traffic", body = HealthReport),
+        (status = 503, description = "A dependency is unavailable", body = HealthReport),
+    ),
+    tag = "Misc"
+)]
+async fn get_health_ready(State(state): State<AppState>) -> Response {
+    let database = state.storage.ping().await.is_ok();
+    let assets = get_required_assets()
+        .iter()
+        .all(|asset| Path::new(asset).is_file());
+
+    let report = HealthReport {
+        ready: database && assets,
+        database,
+        assets,
+    };
+
+    let status = if report.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report)).into_response()
+}
+
+pub fn router() -> Router<AppState> {
+    Router::<AppState>::new()
+        .route("/health/live", get(get_health_live))
+        .route("/health/ready", get(get_health_ready))
+}
@@ -0,0 +1,134 @@
+use crate::entities::notification::find_notifications_with_pagination;
+use crate::error::ApiError;
+use crate::extractors::authentication::ExtractUser;
+use crate::models::query_models::{NotificationId, NotificationQuery, PaginationQuery};
+use crate::models::response_models::MessageResponse;
+use crate::AppState;
+use axum::extract::{Query, State};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+
+/// Retrieve your notifications.
+///
+/// This endpoint returns your notification inbox, newest first. It contains turns, started and finished games as well as friend requests, for clients which can't keep a connection open.
+#[utoipa::path(
+    get,
+    path = "/notifications",
+    params(PaginationQuery, NotificationQuery),
+    responses(
+        (status = 200, description = "Your notifications", body = NotificationList),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Notifications"
+)]
+async fn get_notifications(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    pagination: Query<PaginationQuery>,
+    query: Query<NotificationQuery>,
+) -> Result<Response, ApiError> {
+    let (page, page_size) = pagination.retrieve();
+    let unread_only = query.unread.unwrap_or(false);
+    let notifications =
+        find_notifications_with_pagination(&state, &user.key, unread_only, page, page_size).await?;
+    Ok(Json(notifications).into_response())
+}
+
+/// Mark notifications as read.
+///
+/// This endpoint marks the given notification as read, or all of your notifications if no ID is given.
+#[utoipa::path(
+    post,
+    path = "/notifications/read",
+    params(NotificationId),
+    responses(
+        (status = 200, description = "Notifications marked as read", body = MessageResponse),
+        (status = 400, description = "Invalid notification id"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Notification not found or already read"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Notifications"
+)]
+async fn post_notifications_read(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<NotificationId>,
+) -> Result<Response, ApiError> {
+    let count = state
+        .storage
+        .mark_notifications_read(&user.key, query.id.as_deref())
+        .await?;
+    if count == 0 && query.id.is_some() {
+        return Err(ApiError::NotFound(
+            "Notification not found or already read".to_string(),
+        ));
+    }
+
+    Ok(Json(MessageResponse {
+        message: format!("Marked {} notifications as read", count),
+    })
+    .into_response())
+}
+
+pub fn router() -> Router<AppState> {
+    Router::<AppState>::new()
+        .route("/notifications", get(get_notifications))
+        .route("/notifications/read", post(post_notifications_read))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+
+    use crate::test_utils::*;
+
+    #[tokio::test]
+    async fn test_notifications() {
+        let state = test_state();
+        let owner = create_user(&state, "owner").await;
+        let guest = create_user(&state, "guest").await;
+
+        let (_, room) = send(&state, Method::POST, "/room?name=LEMONS", &owner).await;
+        let code = room["code"].as_str().unwrap();
+        send(
+            &state,
+            Method::POST,
+            &format!("/room/join?code={}", code),
+            &guest,
+        )
+        .await;
+        send(&state, Method::POST, "/friends/request?name=owner", &guest).await;
+
+        let (status, inbox) = send(&state, Method::GET, "/notifications", &owner).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(inbox["unread"], 2);
+        assert_eq!(inbox["notifications"][0]["kind"], "FRIEND_REQUEST");
+        assert_eq!(inbox["notifications"][1]["kind"], "GAME_STARTED");
+        assert_eq!(inbox["notifications"][1]["from"], "guest");
+        let (_, sessions) = send(&state, Method::GET, "/sessions", &owner).await;
+        assert_eq!(
+            inbox["notifications"][1]["session_id"],
+            sessions["sessions"][0]["id"]
+        );
+
+        let id = inbox["notifications"][0]["id"].as_str().unwrap();
+        let uri = format!("/notifications/read?id={}", id);
+        let (status, _) = send(&state, Method::POST, &uri, &guest).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&state, Method::POST, &uri, &owner).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (_, inbox) = send(&state, Method::GET, "/notifications?unread=true", &owner).await;
+        assert_eq!(inbox["unread"], 1);
+        assert_eq!(inbox["notifications"][0]["kind"], "GAME_STARTED");
+    }
+}
@@ -0,0 +1,105 @@
+use crate::error::ApiError;
+use crate::extractors::authentication::ExtractUser;
+use crate::extractors::session_extractor::ExtractOptionalSession;
+use crate::models::response_models::MessageResponse;
+use crate::presence::VIEWING_TTL_MS;
+use crate::AppState;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::{routing::post, Json, Router};
+
+/// Send a presence heartbeat.
+///
+/// This endpoint keeps you online for your opponents, like any other authenticated request. With a session id you also count as viewing that game for the next 60 seconds, send it periodically while the game is open.
+#[utoipa::path(
+    post,
+    path = "/presence",
+    responses(
+        (status = 200, description = "Presence updated", body = MessageResponse),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Session not found"),
+        (status = 500, description = "Server error"),
+    ),
+    params(
+        ("session-id" = Option<String>, Header, description = "ID of the session you are viewing"),
+      ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn post_presence(
+    ExtractUser(user): ExtractUser,
+    ExtractOptionalSession(session): ExtractOptionalSession,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    let message = match session {
+        Some(session) => {
+            let session_id = session.id.map(|id| id.to_hex()).unwrap_or_default();
+            state.presence.mark_viewing(&session_id, &user.key).await?;
+            format!(
+                "Viewing session {} for the next {} seconds",
+                session_id,
+                VIEWING_TTL_MS / 1000
+            )
+        }
+        None => "Presence updated".to_string(),
+    };
+    Ok(Json(MessageResponse { message }).into_response())
+}
+
+pub fn router() -> Router<AppState> {
+    Router::<AppState>::new().route("/presence", post(post_presence))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use mongodb::bson::oid::ObjectId;
+
+    use crate::{models::session_models::SessionInfo, test_utils::*};
+
+    #[tokio::test]
+    async fn test_presence() {
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let lime = create_user(&state, "lime").await;
+
+        let session = create_session(&state, [lemon.clone(), lime.clone()]).await;
+        let session_id = session.id.unwrap().to_hex();
+        let headers = [("session-id", session_id.as_str())];
+
+        let (_, info) = send_with_headers(&state, Method::GET, "/session", &lemon, &headers).await;
+        assert_eq!(info["opponent_online"], true);
+        assert_eq!(info["opponent_viewing"], false);
+        let etag = SessionInfo::from_session(&state, session.clone(), lemon.clone())
+            .await
+            .unwrap()
+            .get_etag(&session.get_etag(&lemon));
+        let cached = [
+            ("session-id", session_id.as_str()),
+            ("if-none-match", &etag),
+        ];
+        let (status, _) = send_with_headers(&state, Method::GET, "/session", &lemon, &cached).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+        let (status, _) =
+            send_with_headers(&state, Method::POST, "/presence", &lime, &headers).await;
+        assert_eq!(status, StatusCode::OK);
+        // The presence isn't part of the session, but it still changes the ETag
+        let (status, info) =
+            send_with_headers(&state, Method::GET, "/session", &lemon, &cached).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(info["opponent_viewing"], true);
+
+        let (status, _) = send_with_headers(
+            &state,
+            Method::POST,
+            "/presence",
+            &lime,
+            &[("session-id", &ObjectId::new().to_hex())],
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -1,31 +1,41 @@
+use crate::entities::notification::Notification;
 use crate::entities::room::{
-    delete_room_by_code, find_public_rooms_with_pagination, find_room_by_code, find_rooms_by_key,
-    find_rooms_by_key_with_pagination, Room,
+    find_public_rooms_with_pagination, find_room_invites_with_pagination,
+    find_rooms_by_key_with_pagination, generate_room_code, start_room_session, Room,
 };
-use crate::entities::session::{find_sessions_by_key_and_finished, Session};
+use crate::entities::session::Session;
+use crate::entities::user::User;
 use crate::error::ApiError;
 use crate::extractors::authentication::ExtractUser;
+use crate::game::color::Color;
 use crate::game::state::GameState;
-use crate::models::query_models::{PaginationQuery, RoomCode, RoomCreation};
+use crate::models::enums::Platform;
+use crate::models::notification_models::NotificationKind;
+use crate::models::query_models::{
+    NamespaceQuery, PaginationQuery, RoomCode, RoomCreation, RoomFilterQuery, RoomUpdate,
+};
 use crate::models::room_models::RoomInfo;
+use crate::utils::sanitize::{Sanitize, SanitizePolicy};
 use crate::AppState;
 use axum::extract::{Query, State};
 use axum::response::{IntoResponse, Response};
-use axum::routing::{delete, get, post};
+use axum::routing::{delete, get, patch, post};
 use axum::{Json, Router};
-use rand::Rng;
+use mongodb::bson::oid::ObjectId;
 
 /// Open a new room.
 ///
 /// This endpoint allows you to open a new multiplayer room.
+/// You can choose the color you will play and a time control, which the session takes over once someone joins.
 #[utoipa::path(
     post,
     path = "/room",
     params(RoomCreation),
     responses(
         (status = 200, description = "Room successfully created", body = RoomInfo),
-        (status = 400, description = "Session limit reached"),
+        (status = 400, description = "Session limit reached or invalid time control"),
         (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Invited user not found"),
         (status = 500, description = "Server error"),
     ),
     security(
@@ -38,10 +48,11 @@ async fn post_room(
     State(state): State<AppState>,
     query: Query<RoomCreation>,
 ) -> Result<Response, ApiError> {
-    let rooms = find_rooms_by_key(&state.database.room_collection, &user.key).await?;
-    let sessions =
-        find_sessions_by_key_and_finished(&state.database.session_collection, &user.key, false)
-            .await?;
+    let rooms = state.storage.find_rooms_by_key(&user.key).await?;
+    let sessions = state
+        .storage
+        .find_sessions_by_key_and_finished(&user.key, false)
+        .await?;
 
     let unfinished_count = rooms.len() + sessions.len();
     if unfinished_count > 10 {
@@ -50,13 +61,17 @@ async fn post_room(
         ));
     }
 
-    let finished_sessions =
-        find_sessions_by_key_and_finished(&state.database.session_collection, &user.key, true)
-            .await?;
+    let finished_sessions = state
+        .storage
+        .find_sessions_by_key_and_finished(&user.key, true)
+        .await?;
 
     let total_count = unfinished_count + finished_sessions.len();
 
-    let query = query.sanitize();
+    let query = query.sanitize(SanitizePolicy::for_namespace(&user.namespace))?;
+    let time_control = query.get_time_control()?;
+    let color = query.color.unwrap_or_default();
+    let invited_user = find_invited_user(&state, &user, &query).await?;
     let name = query.name.unwrap_or(format!(
         "{}'s GAME #{}",
         user.display_name.to_uppercase(),
@@ -64,14 +79,61 @@ async fn post_room(
     ));
     let public = query.public.unwrap_or(true);
 
-    let room = Room::new(&state.database.room_collection, user.key, name, public).await?;
-    room.save(&state.database.room_collection).await?;
+    let code = generate_room_code(&*state.storage, &state.rng).await?;
+    let mut room = Room::new(
+        code,
+        user.key,
+        user.namespace,
+        name,
+        public,
+        color,
+        time_control,
+    );
+    if let Some(invited_user) = invited_user {
+        room.invite(invited_user.key);
+    }
+    room.allow_bots = query.allow_bots.unwrap_or(false);
+    room.save(&*state.storage).await?;
 
     let info = RoomInfo::from_room(&state, room).await?;
 
     Ok(Json(info).into_response())
 }
 
+/// Looks up the user a room is restricted to, they have to be in the same namespace
+async fn find_invited_user(
+    state: &AppState,
+    user: &User,
+    query: &RoomCreation,
+) -> Result<Option<User>, ApiError> {
+    let storage = &state.storage;
+    let invited_user = match (&query.invite_name, &query.invite_discord_id) {
+        (None, None) => return Ok(None),
+        (Some(_), Some(_)) => {
+            return Err(ApiError::BadRequest(
+                "Only one of invite_name and invite_discord_id can be given".to_string(),
+            ))
+        }
+        (Some(name), None) => storage
+            .find_user_by_name(name)
+            .await?
+            .filter(|invited_user| invited_user.namespace == user.namespace),
+        (None, Some(discord_id)) => {
+            storage
+                .find_user_by_platform_id(&user.namespace, Platform::DISCORD, discord_id)
+                .await?
+        }
+    };
+
+    match invited_user {
+        Some(invited_user) if invited_user.key == user.key => Err(ApiError::BadRequest(
+            "You can't invite yourself".to_string(),
+        )),
+        Some(invited_user) => Ok(Some(invited_user)),
+        None => Err(ApiError::NotFound("Invited user not found".to_string())),
+    }
+}
+
 /// Close a room.
 ///
 /// This endpoint allows you to close one of your multiplayer rooms.
@@ -96,7 +158,9 @@ async fn delete_room(
     State(state): State<AppState>,
     query: Query<RoomCode>,
 ) -> Result<Response, ApiError> {
-    let room = match find_room_by_code(&state.database.room_collection, &query.code).await? {
+    // A room which is being joined can't be deleted anymore
+    let lock = state.locks.acquire(&format!("room:{}", query.code)).await?;
+    let room = match state.storage.find_room_by_code(&query.code).await? {
         Some(room) => room,
         None => return Err(ApiError::NotFound("Room not found".to_string())),
     };
@@ -105,10 +169,76 @@ async fn delete_room(
         return Err(ApiError::BadRequest("This is not your room".to_string()));
     }
 
-    delete_room_by_code(&state.database.room_collection, &query.code).await?;
+    state.storage.delete_room_by_code(&query.code).await?;
+    lock.release().await;
     Ok(Json("Room closed").into_response())
 }
 
+/// Update a room.
+///
+/// This endpoint allows you to change the name, visibility, color, time control or whether bots may join of one of your rooms before anyone joins.
+/// Parameters which aren't given stay as they are.
+#[utoipa::path(
+    patch,
+    path = "/room",
+    params(RoomUpdate),
+    responses(
+        (status = 200, description = "Room updated", body = RoomInfo),
+        (status = 400, description = "Not your room or invalid settings"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Room not found"),
+        (status = 409, description = "The room is being joined right now"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Room"
+)]
+async fn patch_room(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<RoomUpdate>,
+) -> Result<Response, ApiError> {
+    let update = query.sanitize(SanitizePolicy::for_namespace(&user.namespace))?;
+    let time_control = update.get_time_control()?;
+
+    // Changes must not be saved to a room which was joined in the meantime
+    let lock = state
+        .locks
+        .acquire(&format!("room:{}", update.code))
+        .await?;
+    let mut room = match state.storage.find_room_by_code(&update.code).await? {
+        Some(room) => room,
+        None => return Err(ApiError::NotFound("Room not found".to_string())),
+    };
+
+    if user.key != room.key {
+        return Err(ApiError::BadRequest("This is not your room".to_string()));
+    }
+
+    if let Some(name) = update.name {
+        room.name = name;
+    }
+    if let Some(public) = update.public {
+        room.public = public;
+    }
+    if let Some(color) = update.color {
+        room.color = color;
+    }
+    if let Some(time_control) = time_control {
+        room.time_control = time_control;
+    }
+    if let Some(allow_bots) = update.allow_bots {
+        room.allow_bots = allow_bots;
+    }
+    room.save(&*state.storage).await?;
+    lock.release().await;
+
+    let info = RoomInfo::from_room(&state, room).await?;
+    Ok(Json(info).into_response())
+}
+
 /// Join a room.
 ///
 /// This endpoint allows you to join a multiplayer room, which automatically creates a session.
@@ -120,6 +250,7 @@ async fn delete_room(
         (status = 200, description = "Game started"),
         (status = 400, description = "Unable to join room"),
         (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "The room is reserved for an invited user or doesn't allow bots"),
         (status = 404, description = "Room not found"),
         (status = 429, description = "Rate limited"),
         (status = 500, description = "Server error"),
@@ -130,42 +261,102 @@ async fn delete_room(
     tag = "Room"
 )]
 async fn post_room_join(
-    ExtractUser(mut user): ExtractUser,
+    ExtractUser(user): ExtractUser,
     State(state): State<AppState>,
     query: Query<RoomCode>,
 ) -> Result<Response, ApiError> {
-    // With a 10s delay it takes >400 years to traverse all room codes
-    user.rate_limit(&state.database.user_collection, "join_room", 10)
-        .await?;
-
-    let room = match find_room_by_code(&state.database.room_collection, &query.code).await? {
-        Some(room) => room,
-        None => return Err(ApiError::NotFound("Room not found".to_string())),
+    // Two players joining at once must not both start a game from the same room
+    let lock = state.locks.acquire(&format!("room:{}", query.code)).await?;
+    let room = match state.storage.find_room_by_code(&query.code).await? {
+        Some(room) if room.namespace == user.namespace => room,
+        _ => return Err(ApiError::NotFound("Room not found".to_string())),
     };
 
     if room.key == user.key {
         return Err(ApiError::BadRequest("Can't join your own room".to_string()));
     }
 
-    // Randomly determine color
-    let random_bool = tokio::task::block_in_place(|| {
-        let mut rng = rand::thread_rng();
-        rng.gen_bool(0.5)
-    });
-    let keys = if random_bool {
-        [user.key.clone(), room.key]
-    } else {
-        [room.key, user.key.clone()]
+    if !room.can_join(&user.key) {
+        return Err(ApiError::NoPermission(
+            "This room is reserved for an invited user".to_string(),
+        ));
+    }
+
+    if user.bot && !room.allows_bot(&user.key) {
+        return Err(ApiError::NoPermission(
+            "This room doesn't allow bots".to_string(),
+        ));
+    }
+
+    let keys = match room.color.resolve(&state.rng) {
+        Color::BLACK => [user.key.clone(), room.key.clone()],
+        _ => [room.key.clone(), user.key.clone()],
     };
 
     let game_state = GameState::new()?;
-    let session = Session::new(room.name, keys, game_state);
+    let mut session = Session::new(room.name.clone(), keys, game_state);
+    session.time_control = room.time_control;
+    // Known upfront so the notification can point the room owner to the new session
+    session.id = Some(ObjectId::new());
 
-    delete_room_by_code(&state.database.room_collection, &query.code).await?;
-    session.save(&state.database.session_collection).await?;
+    start_room_session(&state.storage, &state.tasks, &room.code, session.clone()).await?;
+    lock.release().await;
+    Notification::new(
+        &room.key,
+        NotificationKind::GameStarted,
+        format!("{} joined your room {}", user.display_name, room.name),
+    )
+    .session(&session)
+    .from(&user.name)
+    .send(&*state.storage)
+    .await;
     Ok(Json("Game started").into_response())
 }
 
+/// Refresh a room.
+///
+/// This endpoint restarts the lifetime of one of your rooms, rooms nobody joined are closed after 24 hours.
+#[utoipa::path(
+    post,
+    path = "/room/refresh",
+    params(RoomCode),
+    responses(
+        (status = 200, description = "Room refreshed", body = RoomInfo),
+        (status = 400, description = "Not your room"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Room not found"),
+        (status = 409, description = "The room is being joined right now"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Room"
+)]
+async fn post_room_refresh(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<RoomCode>,
+) -> Result<Response, ApiError> {
+    // Saving a room which was joined in the meantime would bring it back
+    let lock = state.locks.acquire(&format!("room:{}", query.code)).await?;
+    let mut room = match state.storage.find_room_by_code(&query.code).await? {
+        Some(room) => room,
+        None => return Err(ApiError::NotFound("Room not found".to_string())),
+    };
+
+    if user.key != room.key {
+        return Err(ApiError::BadRequest("This is not your room".to_string()));
+    }
+
+    room.refresh();
+    room.save(&*state.storage).await?;
+    lock.release().await;
+
+    let info = RoomInfo::from_room(&state, room).await?;
+    Ok(Json(info).into_response())
+}
+
 /// Retrieve your rooms.
 ///
 /// This endpoint retrieves rooms you have created.
@@ -193,16 +384,45 @@ async fn get_rooms(
     Ok(Json(rooms).into_response())
 }
 
+/// Retrieve your invites.
+///
+/// This endpoint retrieves rooms other users have reserved for you, join them like any other room.
+#[utoipa::path(
+    get,
+    path = "/rooms/invites",
+    params(PaginationQuery),
+    responses(
+        (status = 200, description = "Rooms you are invited to", body = RoomList),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Room"
+)]
+async fn get_rooms_invites(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    pagination: Query<PaginationQuery>,
+) -> Result<Response, ApiError> {
+    let (page, page_size) = pagination.retrieve();
+    let rooms = find_room_invites_with_pagination(&state, &user.key, page, page_size).await?;
+    Ok(Json(rooms).into_response())
+}
+
 /// Retrieve public rooms.
 ///
-/// This endpoint retrieves publicly available rooms.
+/// This endpoint retrieves publicly available rooms of your namespace.
+/// They can be searched by name, limited to recently created ones and sorted by age.
 #[utoipa::path(
     get,
     path = "/rooms/public",
-    params(PaginationQuery),
+    params(PaginationQuery, NamespaceQuery, RoomFilterQuery),
     responses(
         (status = 200, description = "Public rooms", body = RoomList),
         (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "No permission to view other namespaces"),
         (status = 500, description = "Server error"),
     ),
     security(
@@ -211,12 +431,22 @@ async fn get_rooms(
     tag = "Room"
 )]
 async fn get_rooms_public(
-    ExtractUser(_): ExtractUser,
+    ExtractUser(user): ExtractUser,
     State(state): State<AppState>,
     pagination: Query<PaginationQuery>,
+    namespace_query: Query<NamespaceQuery>,
+    filter_query: Query<RoomFilterQuery>,
 ) -> Result<Response, ApiError> {
     let (page, page_size) = pagination.retrieve();
-    let rooms = find_public_rooms_with_pagination(&state, page, page_size).await?;
+    let namespace = namespace_query.retrieve(&user)?;
+    let rooms = find_public_rooms_with_pagination(
+        &state,
+        namespace.as_deref(),
+        &filter_query,
+        page,
+        page_size,
+    )
+    .await?;
     Ok(Json(rooms).into_response())
 }
 
@@ -224,7 +454,110 @@ pub fn router() -> Router<AppState> {
     Router::<AppState>::new()
         .route("/room", post(post_room))
         .route("/room", delete(delete_room))
+        .route("/room", patch(patch_room))
         .route("/room/join", post(post_room_join))
+        .route("/room/refresh", post(post_room_refresh))
         .route("/rooms", get(get_rooms))
+        .route("/rooms/invites", get(get_rooms_invites))
         .route("/rooms/public", get(get_rooms_public))
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+
+    use crate::test_utils::*;
+
+    #[tokio::test]
+    async fn test_join_room() {
+        let state = test_state();
+        let owner = create_user(&state, "owner").await;
+        let guest = create_user(&state, "guest").await;
+
+        let (status, room) = send(&state, Method::POST, "/room?name=LEMONS", &owner).await;
+        assert_eq!(status, StatusCode::OK);
+        let code = room["code"].as_str().unwrap();
+
+        let (status, rooms) = send(&state, Method::GET, "/rooms/public", &guest).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(rooms["rooms"][0]["code"], code);
+        assert_eq!(rooms["rooms"][0]["user_name"], "owner");
+
+        let uri = "/room?name=INVITE&invite_name=guest";
+        let (status, _) = send(&state, Method::POST, uri, &owner).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, invites) = send(&state, Method::GET, "/rooms/invites", &guest).await;
+        assert_eq!(invites["rooms"][0]["user_name"], "owner");
+        assert_eq!(invites["rooms"][0]["invited_user"], "guest");
+
+        let (status, _) = send(
+            &state,
+            Method::POST,
+            &format!("/room/join?code={}", code),
+            &owner,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = send(
+            &state,
+            Method::POST,
+            &format!("/room/join?code={}", code),
+            &guest,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, sessions) = send(&state, Method::GET, "/sessions", &guest).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(sessions["sessions"].as_array().unwrap().len(), 1);
+        assert_eq!(sessions["sessions"][0]["name"], "LEMONS");
+
+        let (status, rooms) = send(&state, Method::GET, "/rooms", &owner).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(rooms["rooms"].as_array().unwrap().len(), 1);
+        assert_eq!(rooms["rooms"][0]["name"], "INVITE");
+    }
+
+    #[tokio::test]
+    async fn test_seeded_rng() {
+        use crate::{entities::room::Room, models::enums::ColorPreference};
+
+        let first = test_state();
+        let lemon = create_user(&first, "lemon").await;
+        let (_, room) = send(&first, Method::POST, "/room", &lemon).await;
+        let code = room["code"].as_str().unwrap().to_string();
+        let (_, session) = send(&first, Method::POST, "/session?difficulty=beginner", &lemon).await;
+
+        // Equally seeded instances set up the same games
+        let second = test_state();
+        let lemon = create_user(&second, "lemon").await;
+        let (_, same_room) = send(&second, Method::POST, "/room", &lemon).await;
+        assert_eq!(same_room["code"], code.as_str());
+        let (_, same_session) = send(
+            &second,
+            Method::POST,
+            "/session?difficulty=beginner",
+            &lemon,
+        )
+        .await;
+        assert_eq!(same_session["white_player"], session["white_player"]);
+        assert_eq!(same_session["fen"], session["fen"]);
+
+        // Taken codes are drawn again instead of failing the room creation
+        let second = test_state();
+        let lemon = create_user(&second, "lemon").await;
+        let taken = Room::new(
+            code.clone(),
+            "lime".to_string(),
+            String::new(),
+            "Taken".to_string(),
+            false,
+            ColorPreference::RANDOM,
+            None,
+        );
+        taken.save(&*second.storage).await.unwrap();
+        let (status, room) = send(&second, Method::POST, "/room", &lemon).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_ne!(room["code"], code.as_str());
+    }
+}
@@ -1,31 +1,65 @@
+use crate::entities::audit_entry::AuditEntry;
+use crate::entities::notification::{notify_opponent, Notification};
+use crate::entities::render_job::RenderJob;
 use crate::entities::session::{
-    find_active_session_by_keys, find_sessions_by_key_with_pagination, Session,
+    find_marked_sessions_with_pagination, find_session_or_archived_by_id,
+    find_sessions_by_key_with_pagination, find_sessions_info_by_ids, Session,
+    RESIGN_CONFIRMATION_S,
 };
+use crate::entities::session_mark::SessionMark;
+use crate::entities::user::User;
+use crate::entities::webhook::deliver_game_finished;
 use crate::error::ApiError;
 use crate::extractors::authentication::ExtractUser;
-use crate::extractors::session_extractor::ExtractSession;
+use crate::extractors::session_extractor::{ExtractLockedSession, ExtractSession};
 use crate::game::color::Color;
-use crate::game::render::{render_board_png, render_history_gif};
-use crate::game::state::GameState;
-use crate::models::move_models::MoveQuery;
-use crate::models::query_models::{PaginationQuery, RenderStyleQuery};
-use crate::models::session_models::SessionInfo;
+use crate::game::render::{
+    render_board_png, render_board_png_with_sidebar, render_eval_graph_png, render_history_gif,
+    sidebar::{CaptureTray, Sidebar},
+    theme::Theme,
+};
+use crate::game::report::ReportFormat;
+use crate::game::review::{evaluate, explain};
+use crate::locks::Lock;
+use crate::models::audit_models::AuditAction;
+use crate::models::move_models::{MoveQuery, MoveSubmission};
+use crate::models::notification_models::NotificationKind;
+use crate::models::query_models::{
+    AiSessionQuery, AnnotationQuery, EvalsQuery, PaginationQuery, PlyQuery, PlyRangeQuery,
+    RenderStyleQuery, ReportQuery, ResignQuery, SessionListQuery, SessionMarkUpdate, SessionUpdate,
+    SidebarQuery, SignedRenderQuery,
+};
+use crate::models::render_job_models::{RenderJobInfo, RenderJobStatus};
+use crate::models::review_models::ReviewEvals;
+use crate::models::session_models::{
+    ResignConfirmation, SessionBatchRequest, SessionEvent, SessionInfo, SessionPosition,
+    SessionResult,
+};
+use crate::services::session_service;
+use crate::storage::{OpponentFilter, SessionFilter};
+use crate::utils::etag;
+use crate::utils::sanitize::{Sanitize, SanitizePolicy};
+use crate::utils::time_operations::timestamp_now_nanos;
 use crate::AppState;
-use axum::body::Body;
-use axum::extract::{Query, State};
-use axum::http::StatusCode;
+use axum::body::{Body, Bytes};
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::response::{IntoResponse, Response};
-use axum::routing::{delete, post};
+use axum::routing::{delete, patch, post};
 use axum::{routing::get, Json, Router};
+use futures::{stream, StreamExt};
+use mongodb::bson::oid::ObjectId;
 
 /// Retrieve session information.
 ///
-/// This endpoint returns basic session information.
+/// This endpoint returns basic session information. Send the last ETag in If-None-Match to get a 304 as long as nothing changed.
 #[utoipa::path(
     get,
     path = "/session",
     responses(
         (status = 200, description = "Session information", body = SessionInfo),
+        (status = 304, description = "Session information unchanged"),
         (status = 400, description = "Missing or invalid session id"),
         (status = 401, description = "Invalid API Key"),
         (status = 404, description = "Session not found"),
@@ -33,6 +67,7 @@ use axum::{routing::get, Json, Router};
     ),
     params(
         ("session-id" = String, Header, description = "ID of the session"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of the last retrieved session information"),
       ),
     security(
         ("api_key" = [])
@@ -41,17 +76,33 @@ use axum::{routing::get, Json, Router};
 )]
 async fn get_session(
     ExtractUser(user): ExtractUser,
-    ExtractSession(mut session): ExtractSession,
+    ExtractSession(session): ExtractSession,
     State(state): State<AppState>,
+    headers: HeaderMap,
 ) -> Result<Response, ApiError> {
-    session.do_ai_move()?; // Play AI move if possible, previous errors could have lead to AI not playing
+    let session = session_service::catch_up_ai_move(&state, session).await?;
+    let session_etag = session.get_etag(&user.key);
+    // Names and presence aren't part of the session, so the info has to be built before comparing
     let info = SessionInfo::from_session(&state, session, user.key).await?;
-    Ok(Json(info).into_response())
+    let etag = info.get_etag(&session_etag);
+    if etag::if_none_match(&headers, &etag) {
+        return Ok(Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(header::ETAG, etag)
+            .body(Body::empty())
+            .unwrap());
+    }
+
+    let mut response = Json(info).into_response();
+    response
+        .headers_mut()
+        .insert(header::ETAG, HeaderValue::from_str(&etag).unwrap());
+    Ok(response)
 }
 
 /// Create AI session.
 ///
-/// This endpoint allows you to create an AI session.
+/// This endpoint allows you to create an AI session. On the easier difficulties the AI picks among its best moves by chance, so it makes human-looking mistakes.
 #[utoipa::path(
     post,
     path = "/session",
@@ -62,6 +113,9 @@ async fn get_session(
         (status = 404, description = "Session not found"),
         (status = 500, description = "Server error"),
     ),
+    params(
+        AiSessionQuery,
+      ),
     security(
         ("api_key" = [])
     ),
@@ -70,23 +124,10 @@ async fn get_session(
 async fn post_session(
     ExtractUser(user): ExtractUser,
     State(state): State<AppState>,
+    query: Query<AiSessionQuery>,
 ) -> Result<Response, ApiError> {
-    let session = find_active_session_by_keys(
-        &state.database.session_collection,
-        [user.key.clone(), "AI".to_string()].to_vec(),
-    )
-    .await?;
-
-    if session.is_some() {
-        return Err(ApiError::BadRequest(
-            "You already have an active AI session.".to_string(),
-        ));
-    }
-
-    let game_state = GameState::new()?;
-    let mut new_session = Session::new_ai("AI Game".to_string(), user.key.clone(), game_state);
-    new_session.do_ai_move()?; // Does the AI move if the AI goes first
-    new_session.save(&state.database.session_collection).await?;
+    let difficulty = query.difficulty.unwrap_or_default();
+    session_service::start_ai_session(&state, &user, difficulty).await?;
     Ok(Json("AI game started").into_response())
 }
 
@@ -116,7 +157,7 @@ async fn get_session_pgn(
     ExtractSession(session): ExtractSession,
     State(state): State<AppState>,
 ) -> Result<Response, ApiError> {
-    let pgn = session.to_pgn(&state).await?;
+    let pgn = session.to_pgn(&state, None).await?;
     Ok(Response::builder()
         .status(StatusCode::OK)
         .header("Content-Type", "text/plain")
@@ -124,20 +165,142 @@ async fn get_session_pgn(
         .unwrap())
 }
 
+/// Retrieve the session position at a ply.
+///
+/// This endpoint replays the session and returns the FEN (Forsyth-Edwards Notation) after the given amount of plies.
+#[utoipa::path(
+    get,
+    path = "/session/fen",
+    responses(
+        (status = 200, description = "Session position", body = SessionPosition),
+        (status = 400, description = "Missing/invalid session id or ply"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Session not found"),
+        (status = 500, description = "Server error"),
+    ),
+    params(
+        PlyQuery,
+        ("session-id" = String, Header, description = "ID of the session"),
+      ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Session"
+)]
+async fn get_session_fen(
+    ExtractUser(_): ExtractUser,
+    ExtractSession(session): ExtractSession,
+    query: Query<PlyQuery>,
+) -> Result<Response, ApiError> {
+    let game_state = &session.game_state;
+    let ply = query.retrieve(game_state.move_log.len())?;
+    let state = game_state.at_ply(ply)?;
+    let position = SessionPosition {
+        ply,
+        fen: state.to_fen(),
+        last_move: state.san_log.last().cloned(),
+        color_to_move: Color::from(state.next_to_move as usize),
+        annotations: session.get_annotations(ply),
+    };
+    Ok(Json(position).into_response())
+}
+
+/// Retrieve an explained evaluation of the session position at a ply.
+///
+/// This endpoint breaks the evaluation of the engine down into material, king safety, pawn structure and mobility of both sides, together with a sentence per term saying which side it favors.
+/// It's only available once the game is finished, it would help the players otherwise.
+#[utoipa::path(
+    get,
+    path = "/session/eval/explain",
+    responses(
+        (status = 200, description = "Explained evaluation", body = EvalExplanation),
+        (status = 400, description = "Missing/invalid session id or ply or game not finished yet"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Session not found"),
+        (status = 500, description = "Server error"),
+    ),
+    params(
+        PlyQuery,
+        ("session-id" = String, Header, description = "ID of the session"),
+      ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Session"
+)]
+async fn get_session_eval_explain(
+    ExtractUser(_): ExtractUser,
+    ExtractSession(session): ExtractSession,
+    query: Query<PlyQuery>,
+) -> Result<Response, ApiError> {
+    if !session.is_finished() {
+        return Err(ApiError::BadRequest(
+            "Game is not finished yet.".to_string(),
+        ));
+    }
+
+    let game_state = &session.game_state;
+    let ply = query.retrieve(game_state.move_log.len())?;
+    let explanation = explain(&game_state.at_ply(ply)?)?;
+    Ok(Json(explanation).into_response())
+}
+
+/// Annotate a move of your session.
+///
+/// This endpoint attaches a glyph and/or comment to a ply of a game you play in, replacing your previous annotation of it. Without glyph and comment your annotation gets removed.
+/// Annotations are shown in the PGN export and in GET /session/fen.
+#[utoipa::path(
+    post,
+    path = "/session/annotations",
+    responses(
+        (status = 200, description = "All annotations of the session", body = [Annotation]),
+        (status = 400, description = "Missing/invalid session id, ply or glyph or not a player in this session"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Session not found"),
+        (status = 409, description = "Session is busy"),
+        (status = 500, description = "Server error"),
+    ),
+    params(
+        AnnotationQuery,
+        ("session-id" = String, Header, description = "ID of the session"),
+      ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Session"
+)]
+async fn post_session_annotations(
+    ExtractUser(user): ExtractUser,
+    ExtractLockedSession(mut session, lock): ExtractLockedSession,
+    State(state): State<AppState>,
+    query: Query<AnnotationQuery>,
+) -> Result<Response, ApiError> {
+    let query = query.sanitize(SanitizePolicy::for_namespace(&user.namespace))?;
+    session.annotate(&user.key, query.ply, query.glyph, query.comment)?;
+    session.save(&state.storage, &state.tasks).await?;
+    lock.release().await;
+    Ok(Json(session.annotations).into_response())
+}
+
 /// Resign a session.
 ///
 /// This endpoint allows you to resign a chess game.
+/// With confirm=true you only get a token which is valid for 30 seconds, you resign once you send it back as token.
+/// This keeps bots from resigning by accident.
 #[utoipa::path(
     delete,
     path = "/session",
     responses(
         (status = 200, description = "Session information", body = SessionInfo),
-        (status = 400, description = "Missing/invalid session id or can't resign"),
+        (status = 202, description = "Confirmation token, nothing happened yet", body = ResignConfirmation),
+        (status = 400, description = "Missing/invalid session id, invalid token or can't resign"),
         (status = 401, description = "Invalid API Key"),
         (status = 404, description = "Session not found"),
+        (status = 409, description = "Session is busy"),
         (status = 500, description = "Server error"),
     ),
     params(
+        ResignQuery,
         ("session-id" = String, Header, description = "ID of the session"),
       ),
     security(
@@ -147,8 +310,9 @@ async fn get_session_pgn(
 )]
 async fn delete_session(
     ExtractUser(user): ExtractUser,
-    ExtractSession(mut session): ExtractSession,
+    ExtractLockedSession(mut session, lock): ExtractLockedSession,
     State(state): State<AppState>,
+    query: Query<ResignQuery>,
 ) -> Result<Response, ApiError> {
     let color = match session.get_color_from_key(&user.key) {
         Some(color) => color,
@@ -159,61 +323,100 @@ async fn delete_session(
         }
     };
 
+    if let Some(token) = &query.token {
+        session.confirm_resignation(&user.key, token)?;
+    } else if query.confirm.unwrap_or(false) {
+        let pending = session.request_resignation(&user.key)?;
+        session.save(&state.storage, &state.tasks).await?;
+        lock.release().await;
+        let confirmation = ResignConfirmation {
+            token: pending.token,
+            expires_in: RESIGN_CONFIRMATION_S,
+        };
+        return Ok((StatusCode::ACCEPTED, Json(confirmation)).into_response());
+    }
+
     session.resign(color)?;
-    session.save(&state.database.session_collection).await?;
+    session.save(&state.storage, &state.tasks).await?;
+    lock.release().await;
+    AuditEntry::new(AuditAction::SessionResigned, &user)
+        .target(session.id.map(|id| id.to_hex()).unwrap_or_default())
+        .record(&*state.storage)
+        .await;
+    notify_opponent(&*state.storage, &session, &user.key, &user.name).await;
+    if session.is_finished() {
+        state
+            .tasks
+            .spawn(deliver_game_finished(state.clone(), session.clone()));
+    }
 
     let info = SessionInfo::from_session(&state, session, user.key).await?;
     Ok(Json(info).into_response())
 }
 
-/// Retrieve your current sessions.
+/// Abort a session.
 ///
-/// This endpoint returns all your available sessions.
+/// This endpoint ends a game before both sides made their first move, for example if the opponent never showed up.
+/// An aborted game has no winner and isn't a draw.
 #[utoipa::path(
-    get,
-    path = "/sessions",
+    post,
+    path = "/session/abort",
     responses(
-        (status = 200, description = "Session information", body = SessionList),
+        (status = 200, description = "Session information", body = SessionInfo),
+        (status = 400, description = "Missing/invalid session id or both sides already moved"),
         (status = 401, description = "Invalid API Key"),
         (status = 404, description = "Session not found"),
+        (status = 409, description = "Session is busy"),
         (status = 500, description = "Server error"),
     ),
     params(
-        PaginationQuery
+        ("session-id" = String, Header, description = "ID of the session"),
       ),
     security(
         ("api_key" = [])
     ),
     tag = "Session"
 )]
-async fn get_sessions(
+async fn post_session_abort(
     ExtractUser(user): ExtractUser,
+    ExtractLockedSession(mut session, lock): ExtractLockedSession,
     State(state): State<AppState>,
-    pagination: Query<PaginationQuery>,
 ) -> Result<Response, ApiError> {
-    let (page, page_size) = pagination.retrieve();
-    let session_list =
-        find_sessions_by_key_with_pagination(&state, user.key, page, page_size).await?;
+    if session.get_color_from_key(&user.key).is_none() {
+        return Err(ApiError::BadRequest(
+            "Not a player of this game.".to_string(),
+        ));
+    }
 
-    Ok(Json(session_list).into_response())
+    session.abort()?;
+    session.save(&state.storage, &state.tasks).await?;
+    lock.release().await;
+    AuditEntry::new(AuditAction::SessionAborted, &user)
+        .target(session.id.map(|id| id.to_hex()).unwrap_or_default())
+        .record(&*state.storage)
+        .await;
+    notify_opponent(&*state.storage, &session, &user.key, &user.name).await;
+
+    let info = SessionInfo::from_session(&state, session, user.key).await?;
+    Ok(Json(info).into_response())
 }
 
-/// Retrieve chess board image (10s cooldown).
+/// Pause a session.
 ///
-/// This endpoint renders the chess board and returns an image.
+/// This endpoint asks to pause a game, for example a correspondence game which continues in a few days.
+/// The game is paused once your opponent asks for it as well, nobody can move while it's paused. Games against the AI are paused right away.
 #[utoipa::path(
-    get,
-    path = "/session/render",
+    post,
+    path = "/session/pause",
     responses(
-        (status = 200, description = "Chess board image", content_type = "image/png"),
-        (status = 400, description = "Missing or invalid session id"),
+        (status = 200, description = "Session information", body = SessionInfo),
+        (status = 400, description = "Missing/invalid session id, not a player, finished or already paused"),
         (status = 401, description = "Invalid API Key"),
         (status = 404, description = "Session not found"),
-        (status = 429, description = "Rate limited"),
+        (status = 409, description = "Session is busy"),
         (status = 500, description = "Server error"),
     ),
     params(
-        RenderStyleQuery,
         ("session-id" = String, Header, description = "ID of the session"),
       ),
     security(
@@ -221,47 +424,29 @@ async fn get_sessions(
     ),
     tag = "Session"
 )]
-async fn get_session_render(
-    ExtractUser(mut user): ExtractUser,
-    ExtractSession(session): ExtractSession,
+async fn post_session_pause(
+    ExtractUser(user): ExtractUser,
+    ExtractLockedSession(session, lock): ExtractLockedSession,
     State(state): State<AppState>,
-    query: Query<RenderStyleQuery>,
 ) -> Result<Response, ApiError> {
-    user.rate_limit(&state.database.user_collection, "render", 10)
-        .await?;
-    let player_color = session
-        .get_color_from_key(&user.key)
-        .unwrap_or(Color::WHITE);
-
-    let style = query.retrieve();
-    match render_board_png(&session.game_state, player_color, &style) {
-        Ok(image_bytes) => Ok(Response::builder()
-            .status(StatusCode::OK)
-            .header("Content-Type", "image/png")
-            .body(Body::from(image_bytes))
-            .unwrap()),
-        Err(e) => Err(ApiError::ServerError(format!(
-            "Failed to render image: {}",
-            e
-        ))),
-    }
+    request_pause(state, user, session, lock, true).await
 }
 
-/// Retrieve chess board history (30s cooldown).
+/// Resume a paused session.
 ///
-/// This endpoint renders the chess board history and returns a gif.
+/// This endpoint asks to continue a paused game, it continues once your opponent asks for it as well.
 #[utoipa::path(
-    get,
-    path = "/session/render/history",
+    post,
+    path = "/session/resume",
     responses(
-        (status = 200, description = "Chess board animated GIF", content_type = "image/gif"),
-        (status = 400, description = "Missing/invalid session id or not a player in this session"),
+        (status = 200, description = "Session information", body = SessionInfo),
+        (status = 400, description = "Missing/invalid session id, not a player, finished or not paused"),
         (status = 401, description = "Invalid API Key"),
         (status = 404, description = "Session not found"),
+        (status = 409, description = "Session is busy"),
         (status = 500, description = "Server error"),
     ),
     params(
-        RenderStyleQuery,
         ("session-id" = String, Header, description = "ID of the session"),
       ),
     security(
@@ -269,46 +454,67 @@ async fn get_session_render(
     ),
     tag = "Session"
 )]
-async fn get_session_render_history(
-    ExtractUser(mut user): ExtractUser,
-    ExtractSession(session): ExtractSession,
+async fn post_session_resume(
+    ExtractUser(user): ExtractUser,
+    ExtractLockedSession(session, lock): ExtractLockedSession,
     State(state): State<AppState>,
-    query: Query<RenderStyleQuery>,
 ) -> Result<Response, ApiError> {
-    user.rate_limit(&state.database.user_collection, "render_gif", 30)
-        .await?;
+    request_pause(state, user, session, lock, false).await
+}
 
-    let player_color = session
-        .get_color_from_key(&user.key)
-        .unwrap_or(Color::WHITE);
+async fn request_pause(
+    state: AppState,
+    user: User,
+    mut session: Session,
+    lock: Lock,
+    pause: bool,
+) -> Result<Response, ApiError> {
+    if session.get_color_from_key(&user.key).is_none() {
+        return Err(ApiError::BadRequest(
+            "Not a player of this game.".to_string(),
+        ));
+    }
 
-    let style = query.retrieve();
-    match render_history_gif(&session.game_state, player_color, &style) {
-        Ok(gif_bytes) => Ok(Response::builder()
-            .status(StatusCode::OK)
-            .header("Content-Type", "image/gif")
-            .body(Body::from(gif_bytes))
-            .unwrap()),
-        Err(_) => Err(ApiError::ServerError(
-            "An error occured while rendering the gif".to_string(),
-        )),
+    let changed = session.request_pause(&user.key, pause)?;
+    session.save(&state.storage, &state.tasks).await?;
+    lock.release().await;
+    if !changed {
+        if let Some(opponent_key) = session.keys.iter().find(|key| **key != user.key) {
+            let action = if pause { "pause" } else { "resume" };
+            Notification::new(
+                opponent_key,
+                NotificationKind::PauseRequested,
+                format!("{} asks to {} {}", user.name, action, session.name),
+            )
+            .session(&session)
+            .from(&user.name)
+            .send(&*state.storage)
+            .await;
+        }
     }
+
+    let info = SessionInfo::from_session(&state, session, user.key).await?;
+    Ok(Json(info).into_response())
 }
 
-/// Retrieve legal session moves.
+/// Change the settings of a session.
 ///
-/// This endpoint returns your legal moves in this session.
+/// This endpoint allows both players to change the name of a running game, for example after a typo in the room name.
+/// With blunder alerts on, the response to a move includes a blunder_warning if the move left material hanging, meant for beginners.
+/// Archived sessions can't be changed anymore.
 #[utoipa::path(
-    get,
-    path = "/session/move",
+    patch,
+    path = "/session",
     responses(
-        (status = 200, description = "Legal moves", body = LegalMoves),
-        (status = 400, description = "Missing/invalid session id or not a player in this session"),
+        (status = 200, description = "Updated session information", body = SessionInfo),
+        (status = 400, description = "Missing/invalid session id, invalid name or not a player in this session"),
         (status = 401, description = "Invalid API Key"),
         (status = 404, description = "Session not found"),
+        (status = 409, description = "Session is busy"),
         (status = 500, description = "Server error"),
     ),
     params(
+        SessionUpdate,
         ("session-id" = String, Header, description = "ID of the session"),
       ),
     security(
@@ -316,40 +522,48 @@ async fn get_session_render_history(
     ),
     tag = "Session"
 )]
-async fn get_session_move(
+async fn patch_session(
     ExtractUser(user): ExtractUser,
-    ExtractSession(session): ExtractSession,
+    ExtractLockedSession(mut session, lock): ExtractLockedSession,
+    State(state): State<AppState>,
+    query: Query<SessionUpdate>,
 ) -> Result<Response, ApiError> {
-    let color = match session.get_color_from_key(&user.key) {
-        Some(color) => color,
-        None => {
-            return Ok((
-                StatusCode::BAD_REQUEST,
-                "You're not part of this session.".to_string(),
-            )
-                .into_response())
-        }
-    };
+    if session.get_color_from_key(&user.key).is_none() {
+        return Err(ApiError::BadRequest(
+            "Not a player of this game.".to_string(),
+        ));
+    }
 
-    let legal_moves = session.get_legal_moves(color)?;
-    Ok(Json(legal_moves).into_response())
+    let query = query.sanitize(SanitizePolicy::for_namespace(&user.namespace))?;
+    if let Some(name) = query.name {
+        session.name = name;
+    }
+    if let Some(blunder_alerts) = query.blunder_alerts {
+        session.blunder_alerts = blunder_alerts;
+    }
+    session.save(&state.storage, &state.tasks).await?;
+    lock.release().await;
+
+    let info = SessionInfo::from_session(&state, session, user.key).await?;
+    Ok(Json(info).into_response())
 }
 
-/// Play a move in a chess session.
+/// Tag a session or mark it as favorite.
 ///
-/// This endpoint allows you to move in a chess session.
+/// This endpoint sets your own tags and favorite flag of a game you play in, like "study later" or "brilliancy". Your opponent doesn't see them.
+/// Use the tag and favorite filters of GET /sessions to find the sessions again.
 #[utoipa::path(
-    post,
-    path = "/session/move",
+    patch,
+    path = "/session/mark",
     responses(
         (status = 200, description = "Updated session information", body = SessionInfo),
-        (status = 400, description = "Missing/invalid session id or unable to play the move"),
+        (status = 400, description = "Missing/invalid session id, too many tags or not a player in this session"),
         (status = 401, description = "Invalid API Key"),
         (status = 404, description = "Session not found"),
         (status = 500, description = "Server error"),
     ),
     params(
-        MoveQuery,
+        SessionMarkUpdate,
         ("session-id" = String, Header, description = "ID of the session"),
       ),
     security(
@@ -357,27 +571,1287 @@ async fn get_session_move(
     ),
     tag = "Session"
 )]
-async fn post_session_move(
+async fn patch_session_mark(
     ExtractUser(user): ExtractUser,
-    ExtractSession(mut session): ExtractSession,
+    ExtractSession(session): ExtractSession,
     State(state): State<AppState>,
-    query: Query<MoveQuery>,
+    query: Query<SessionMarkUpdate>,
 ) -> Result<Response, ApiError> {
-    session.do_move(&user.key, &query)?;
-    session.save(&state.database.session_collection).await?;
+    if session.get_color_from_key(&user.key).is_none() {
+        return Err(ApiError::BadRequest(
+            "Not a player of this game.".to_string(),
+        ));
+    }
+
+    let session_id = session.id.map(|id| id.to_hex()).unwrap_or_default();
+    let mut mark = state
+        .storage
+        .find_session_mark(&user.key, &session_id)
+        .await?
+        .unwrap_or_else(|| SessionMark::new(&user.key, &session_id));
+    if let Some(tags) = query.get_tags(SanitizePolicy::for_namespace(&user.namespace)) {
+        mark.set_tags(tags)?;
+    }
+    if let Some(favorite) = query.favorite {
+        mark.favorite = favorite;
+    }
+    mark.updated_stamp = timestamp_now_nanos();
+
+    match mark.is_empty() {
+        true => {
+            state
+                .storage
+                .delete_session_mark(&user.key, &session_id)
+                .await?
+        }
+        false => state.storage.save_session_mark(&mark).await?,
+    }
+
     let info = SessionInfo::from_session(&state, session, user.key).await?;
     Ok(Json(info).into_response())
 }
 
-pub fn router() -> Router<AppState> {
-    Router::<AppState>::new()
-        .route("/session", get(get_session))
-        .route("/session", post(post_session))
-        .route("/session/pgn", get(get_session_pgn))
-        .route("/session", delete(delete_session))
-        .route("/sessions", get(get_sessions))
-        .route("/session/render", get(get_session_render))
-        .route("/session/render/history", get(get_session_render_history))
-        .route("/session/move", get(get_session_move))
-        .route("/session/move", post(post_session_move))
+/// Most sessions which can be retrieved in one batch
+const MAX_BATCH_SESSIONS: usize = 100;
+
+/// Retrieve multiple sessions at once.
+///
+/// This endpoint returns the information of up to 100 sessions, running or archived, in the order of the given ids.
+/// Unknown ids are skipped, duplicates are only returned once.
+#[utoipa::path(
+    post,
+    path = "/sessions/batch",
+    request_body(content = SessionBatchRequest, description = "Ids of the sessions", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Session information", body = Vec<SessionInfo>),
+        (status = 400, description = "Invalid body, invalid session id or too many sessions"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Session"
+)]
+async fn post_sessions_batch(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    let request = serde_json::from_slice::<SessionBatchRequest>(&body)
+        .map_err(|err| ApiError::ParseError(format!("Invalid batch body: {}", err)))?;
+    let mut ids: Vec<String> = Vec::with_capacity(request.ids.len());
+    for id in request.ids {
+        if ObjectId::parse_str(&id).is_err() {
+            return Err(ApiError::BadRequest(format!("Invalid session id {}", id)));
+        }
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    if ids.len() > MAX_BATCH_SESSIONS {
+        return Err(ApiError::BadRequest(format!(
+            "At most {} sessions can be retrieved at once",
+            MAX_BATCH_SESSIONS
+        )));
+    }
+
+    let sessions_info = find_sessions_info_by_ids(&state, &ids, user.key).await?;
+    Ok(Json(sessions_info).into_response())
+}
+
+/// Retrieve your current sessions.
+///
+/// This endpoint returns all your available sessions.
+/// Finished sessions are moved to an archive after a while, use include_archived to list them as well.
+/// The other filters narrow down the list, sort orders it by creation or by the last move or resignation.
+/// With tag or favorite only sessions you marked are listed, the most recently marked first and archived ones included.
+/// The other filters and the sort don't apply to them.
+#[utoipa::path(
+    get,
+    path = "/sessions",
+    responses(
+        (status = 200, description = "Session information", body = SessionList),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Session not found"),
+        (status = 500, description = "Server error"),
+    ),
+    params(
+        PaginationQuery,
+        SessionListQuery
+      ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Session"
+)]
+async fn get_sessions(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    pagination: Query<PaginationQuery>,
+    list_query: Query<SessionListQuery>,
+) -> Result<Response, ApiError> {
+    let (page, page_size) = pagination.retrieve();
+    let session_list = match list_query.get_mark_filter() {
+        Some((tag, favorite_only)) => {
+            find_marked_sessions_with_pagination(
+                &state,
+                user.key,
+                tag.as_deref(),
+                favorite_only,
+                page,
+                page_size,
+            )
+            .await?
+        }
+        None => {
+            let opponent = match list_query.get_opponent() {
+                Some(name) => {
+                    let opponent = state.storage.find_user_by_name(&name).await?;
+                    Some(OpponentFilter {
+                        key: opponent.map(|opponent| opponent.key),
+                        name,
+                    })
+                }
+                None => None,
+            };
+            let filter = SessionFilter {
+                include_archived: list_query.include_archived.unwrap_or(false),
+                finished: list_query.finished,
+                against_ai: list_query.vs_ai,
+                outcome: list_query.result,
+                opponent,
+                created: list_query.get_created_range()?,
+                sort: list_query.sort,
+            };
+            find_sessions_by_key_with_pagination(&state, user.key, &filter, page, page_size).await?
+        }
+    };
+
+    Ok(Json(session_list).into_response())
+}
+
+/// Retrieve chess board image (2s cooldown).
+///
+/// This endpoint renders the chess board and returns an image.
+/// Optionally a sidebar with the player names, an evaluation bar and the captured pieces is added to the right of the board.
+/// The evaluation bar is only available for finished games.
+#[utoipa::path(
+    get,
+    path = "/session/render",
+    responses(
+        (status = 200, description = "Chess board image", content_type = "image/png"),
+        (status = 400, description = "Missing/invalid session id or evaluation bar requested for a running game"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Session not found"),
+        (status = 429, description = "Rate limited"),
+        (status = 500, description = "Server error"),
+    ),
+    params(
+        RenderStyleQuery,
+        SidebarQuery,
+        ("session-id" = String, Header, description = "ID of the session"),
+      ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Session"
+)]
+async fn get_session_render(
+    State(state): State<AppState>,
+    ExtractUser(user): ExtractUser,
+    ExtractSession(session): ExtractSession,
+    query: Query<RenderStyleQuery>,
+    sidebar_query: Query<SidebarQuery>,
+) -> Result<Response, ApiError> {
+    let player_color = session
+        .get_color_from_key(&user.key)
+        .unwrap_or(Color::WHITE);
+
+    let theme = query.retrieve()?;
+    let sidebar = if sidebar_query.is_enabled() {
+        let names = if sidebar_query.names.unwrap_or(false) {
+            Some(session.get_player_names(&*state.storage).await?)
+        } else {
+            None
+        };
+        let eval = if sidebar_query.eval_bar.unwrap_or(false) {
+            // An evaluation of a running game would be a cheating aid
+            if !session.is_finished() {
+                return Err(ApiError::BadRequest(
+                    "The evaluation bar is only available once the game is finished.".to_string(),
+                ));
+            }
+            // Evaluating searches the position, which would block the async runtime
+            let game_state = session.game_state.clone();
+            Some(
+                tokio::task::spawn_blocking(move || evaluate(&game_state))
+                    .await
+                    .map_err(|error| ApiError::ServerError(error.to_string()))??,
+            )
+        } else {
+            None
+        };
+        let captures = if sidebar_query.captured.unwrap_or(false) {
+            Some(CaptureTray::from_state(&session.game_state)?)
+        } else {
+            None
+        };
+        Some(Sidebar {
+            names,
+            eval,
+            captures,
+        })
+    } else {
+        None
+    };
+
+    match render_board_png_with_sidebar(&session.game_state, player_color, &theme, sidebar.as_ref())
+    {
+        Ok(image_bytes) => Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "image/png")
+            .body(Body::from(image_bytes))
+            .unwrap()),
+        Err(e) => Err(ApiError::ServerError(format!(
+            "Failed to render image: {}",
+            e
+        ))),
+    }
+}
+
+/// Retrieve chess board history (30s cooldown).
+///
+/// This endpoint renders the chess board history and returns a gif. Long games can exceed client timeouts, use POST /session/render/history instead.
+#[utoipa::path(
+    get,
+    path = "/session/render/history",
+    responses(
+        (status = 200, description = "Chess board animated GIF", content_type = "image/gif"),
+        (status = 400, description = "Missing/invalid session id, invalid render style or ply range"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Session not found"),
+        (status = 429, description = "Rate limited"),
+        (status = 500, description = "Server error"),
+    ),
+    params(
+        RenderStyleQuery,
+        PlyRangeQuery,
+        ("session-id" = String, Header, description = "ID of the session"),
+      ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Session"
+)]
+async fn get_session_render_history(
+    ExtractUser(user): ExtractUser,
+    ExtractSession(session): ExtractSession,
+    query: Query<RenderStyleQuery>,
+    range_query: Query<PlyRangeQuery>,
+) -> Result<Response, ApiError> {
+    let player_color = session
+        .get_color_from_key(&user.key)
+        .unwrap_or(Color::WHITE);
+
+    let theme = query.retrieve()?;
+    let plies = range_query.retrieve(session.game_state.move_log.len())?;
+    match render_history_gif(&session.game_state, player_color, &theme, plies) {
+        Ok(gif_bytes) => Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "image/gif")
+            .body(Body::from(gif_bytes))
+            .unwrap()),
+        Err(_) => Err(ApiError::ServerError(
+            "An error occured while rendering the gif".to_string(),
+        )),
+    }
+}
+
+/// Queue chess board history rendering (30s cooldown).
+///
+/// This endpoint queues rendering the chess board history as gif, the result can be retrieved with the returned job id.
+#[utoipa::path(
+    post,
+    path = "/session/render/history",
+    responses(
+        (status = 202, description = "Render job queued", body = RenderJobInfo),
+        (status = 400, description = "Missing/invalid session id, invalid render style or ply range"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Session not found"),
+        (status = 429, description = "Rate limited"),
+        (status = 500, description = "Server error"),
+    ),
+    params(
+        RenderStyleQuery,
+        PlyRangeQuery,
+        ("session-id" = String, Header, description = "ID of the session"),
+      ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Session"
+)]
+async fn post_session_render_history(
+    ExtractUser(user): ExtractUser,
+    ExtractSession(session): ExtractSession,
+    State(state): State<AppState>,
+    query: Query<RenderStyleQuery>,
+    range_query: Query<PlyRangeQuery>,
+) -> Result<Response, ApiError> {
+    let player_color = session
+        .get_color_from_key(&user.key)
+        .unwrap_or(Color::WHITE);
+
+    // Invalid styles and ranges are rejected right away instead of failing the job later
+    query.retrieve()?;
+    range_query.retrieve(session.game_state.move_log.len())?;
+
+    let session_id = session
+        .id
+        .ok_or(ApiError::NotFound("Session not found".to_string()))?;
+    let mut job = RenderJob::new(user.key, session_id, player_color, query.0, range_query.0);
+    job.insert(&*state.storage).await?;
+
+    Ok((StatusCode::ACCEPTED, Json(RenderJobInfo::from(&job))).into_response())
+}
+
+/// Retrieve a chess board history render job.
+///
+/// This endpoint returns the status of a render job (202 while it is queued or running) and the gif once it is done. Jobs expire after an hour.
+#[utoipa::path(
+    get,
+    path = "/session/render/history/{job}",
+    responses(
+        (status = 200, description = "Chess board animated GIF", content_type = "image/gif"),
+        (status = 202, description = "Render job still queued or running", body = RenderJobInfo),
+        (status = 400, description = "Invalid job id"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Job not found"),
+        (status = 500, description = "Server error or the render failed"),
+    ),
+    params(
+        ("job" = String, Path, description = "ID of the render job"),
+      ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Session"
+)]
+async fn get_session_render_history_job(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> Result<Response, ApiError> {
+    let job = match state.storage.find_render_job_by_id(&job_id).await? {
+        Some(job) if job.key == user.key => job,
+        _ => return Err(ApiError::NotFound("Job not found".to_string())),
+    };
+
+    match (job.status, job.result_id) {
+        (RenderJobStatus::DONE, Some(result_id)) => {
+            let gif_bytes = state.storage.load_render_result(result_id).await?;
+            Ok(Response::builder()
+                .status(StatusCode::OK)
+                .header("Content-Type", "image/gif")
+                .body(Body::from(gif_bytes))
+                .unwrap())
+        }
+        (RenderJobStatus::FAILED, _) | (RenderJobStatus::DONE, None) => {
+            Err(ApiError::ServerError(format!(
+                "An error occured while rendering the gif: {}",
+                job.error.unwrap_or_default()
+            )))
+        }
+        _ => Ok((StatusCode::ACCEPTED, Json(RenderJobInfo::from(&job))).into_response()),
+    }
+}
+
+/// Retrieve a chess board image through a signed link.
+///
+/// This endpoint renders the chess board without requiring an API key, the link is handed out by other endpoints (e.g. /session/result).
+#[utoipa::path(
+    get,
+    path = "/session/render/signed",
+    responses(
+        (status = 200, description = "Chess board image", content_type = "image/png"),
+        (status = 400, description = "Invalid session id"),
+        (status = 403, description = "Invalid signature or link expired"),
+        (status = 404, description = "Session not found"),
+        (status = 429, description = "Rate limited"),
+        (status = 500, description = "Server error"),
+    ),
+    params(
+        SignedRenderQuery,
+      ),
+    tag = "Session"
+)]
+async fn get_session_render_signed(
+    State(state): State<AppState>,
+    query: Query<SignedRenderQuery>,
+) -> Result<Response, ApiError> {
+    query.verify()?;
+
+    let session = find_session_or_archived_by_id(&*state.storage, &query.session_id)
+        .await?
+        .ok_or(ApiError::NotFound("Session not found".to_string()))?;
+
+    match render_board_png(&session.game_state, query.color, &Theme::from(query.style)) {
+        Ok(image_bytes) => Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "image/png")
+            .body(Body::from(image_bytes))
+            .unwrap()),
+        Err(e) => Err(ApiError::ServerError(format!(
+            "Failed to render image: {}",
+            e
+        ))),
+    }
+}
+
+/// Retrieve the result of a finished session.
+///
+/// This endpoint returns everything needed to announce a finished game: final FEN, PGN, result, reason and a signed render link.
+#[utoipa::path(
+    get,
+    path = "/session/result",
+    responses(
+        (status = 200, description = "Session result", body = SessionResult),
+        (status = 400, description = "Missing/invalid session id or game not finished yet"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Session not found"),
+        (status = 500, description = "Server error"),
+    ),
+    params(
+        ("session-id" = String, Header, description = "ID of the session"),
+      ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Session"
+)]
+async fn get_session_result(
+    ExtractUser(_): ExtractUser,
+    ExtractSession(session): ExtractSession,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    let result = SessionResult::from_session(&state, &session).await?;
+    Ok(Json(result).into_response())
+}
+
+/// Retrieve the evaluation series of a game (30s cooldown).
+///
+/// This endpoint reviews the whole game with the engine and returns the evaluation after every ply, optionally rendered as a graph.
+#[utoipa::path(
+    get,
+    path = "/session/review/evals",
+    responses(
+        (status = 200, description = "Evaluation series, a PNG graph if image is set", body = ReviewEvals),
+        (status = 400, description = "Missing/invalid session id or game not finished yet"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Session not found"),
+        (status = 429, description = "Rate limited"),
+        (status = 500, description = "Server error"),
+    ),
+    params(
+        EvalsQuery,
+        ("session-id" = String, Header, description = "ID of the session"),
+      ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Session"
+)]
+async fn get_session_review_evals(
+    ExtractUser(_): ExtractUser,
+    ExtractSession(mut session): ExtractSession,
+    State(state): State<AppState>,
+    query: Query<EvalsQuery>,
+) -> Result<Response, ApiError> {
+    if !session.is_finished() {
+        return Err(ApiError::BadRequest(
+            "Game is not finished yet.".to_string(),
+        ));
+    }
+
+    let review = session.get_review(&state).await?;
+
+    if query.image.unwrap_or(false) {
+        let image_bytes = render_eval_graph_png(&review.get_evals())?;
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "image/png")
+            .body(Body::from(image_bytes))
+            .unwrap());
+    }
+
+    Ok(Json(ReviewEvals::from(review)).into_response())
+}
+
+/// Retrieve the reviewed session PGN (30s cooldown).
+///
+/// This endpoint reviews the whole game with the engine and returns the PGN with the annotation (?!, ? or ??) behind every move and its evaluation in pawns as a [%eval] comment.
+#[utoipa::path(
+    get,
+    path = "/session/review/pgn",
+    responses(
+        (status = 200, description = "Annotated session PGN", content_type = "text/plain"),
+        (status = 400, description = "Missing/invalid session id or game not finished yet"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Session not found"),
+        (status = 429, description = "Rate limited"),
+        (status = 500, description = "Server error"),
+    ),
+    params(
+        ("session-id" = String, Header, description = "ID of the session"),
+      ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Session"
+)]
+async fn get_session_review_pgn(
+    ExtractUser(_): ExtractUser,
+    ExtractSession(mut session): ExtractSession,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    if !session.is_finished() {
+        return Err(ApiError::BadRequest(
+            "Game is not finished yet.".to_string(),
+        ));
+    }
+
+    let review = session.get_review(&state).await?;
+    let pgn = session.to_pgn(&state, Some(&review)).await?;
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "text/plain")
+        .body(Body::from(pgn))
+        .unwrap())
+}
+
+/// Export a game review (30s cooldown).
+///
+/// This endpoint reviews the whole game with the engine and returns a shareable report with annotated moves, the evaluation graph and diagrams of the key moments, either as Markdown (diagrams embedded as data URIs) or as PDF.
+#[utoipa::path(
+    get,
+    path = "/session/review/export",
+    responses(
+        (status = 200, description = "Game report", content_type = ["text/markdown", "application/pdf"]),
+        (status = 400, description = "Missing/invalid session id or game not finished yet"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Session not found"),
+        (status = 429, description = "Rate limited"),
+        (status = 500, description = "Server error"),
+    ),
+    params(
+        ReportQuery,
+        ("session-id" = String, Header, description = "ID of the session"),
+      ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Session"
+)]
+async fn get_session_review_export(
+    ExtractUser(user): ExtractUser,
+    ExtractSession(mut session): ExtractSession,
+    State(state): State<AppState>,
+    query: Query<ReportQuery>,
+) -> Result<Response, ApiError> {
+    if !session.is_finished() {
+        return Err(ApiError::BadRequest(
+            "Game is not finished yet.".to_string(),
+        ));
+    }
+
+    let player_color = session
+        .get_color_from_key(&user.key)
+        .unwrap_or(Color::WHITE);
+
+    let (format, theme) = query.retrieve();
+    let review = session.get_review(&state).await?;
+    let report = session.get_report(&*state.storage, review).await?;
+
+    let (content_type, body) = match format {
+        ReportFormat::MARKDOWN => (
+            "text/markdown",
+            Body::from(report.to_markdown(player_color, &theme)?),
+        ),
+        ReportFormat::PDF => (
+            "application/pdf",
+            Body::from(report.to_pdf(player_color, &theme)?),
+        ),
+    };
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", content_type)
+        .body(body)
+        .unwrap())
+}
+
+/// Retrieve legal session moves.
+///
+/// This endpoint returns your legal moves in this session.
+#[utoipa::path(
+    get,
+    path = "/session/move",
+    responses(
+        (status = 200, description = "Legal moves", body = LegalMoves),
+        (status = 400, description = "Missing/invalid session id or not a player in this session"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Session not found"),
+        (status = 500, description = "Server error"),
+    ),
+    params(
+        ("session-id" = String, Header, description = "ID of the session"),
+      ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Session"
+)]
+async fn get_session_move(
+    ExtractUser(user): ExtractUser,
+    ExtractSession(session): ExtractSession,
+) -> Result<Response, ApiError> {
+    let legal_moves = session_service::get_legal_moves(&session, &user)?;
+    Ok(Json(legal_moves).into_response())
+}
+
+/// Play a move in a chess session.
+///
+/// This endpoint allows you to move in a chess session. The move can either be given as query parameters or as JSON body, the body takes precedence.
+#[utoipa::path(
+    post,
+    path = "/session/move",
+    request_body(content = Option<MoveSubmission>, description = "The move, instead of the query parameters", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Updated session information", body = SessionInfo),
+        (status = 400, description = "Missing/invalid session id or invalid move, illegal moves come with the reason", body = IllegalMoveResponse),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Session not found"),
+        (status = 409, description = "Session is busy"),
+        (status = 500, description = "Server error"),
+    ),
+    params(
+        MoveQuery,
+        ("session-id" = String, Header, description = "ID of the session"),
+      ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Session"
+)]
+async fn post_session_move(
+    ExtractUser(user): ExtractUser,
+    ExtractLockedSession(session, lock): ExtractLockedSession,
+    State(state): State<AppState>,
+    query: Query<MoveQuery>,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    let chess_move = if body.is_empty() {
+        query.0
+    } else {
+        serde_json::from_slice::<MoveSubmission>(&body)
+            .map_err(|err| ApiError::ParseError(format!("Invalid move body: {}", err)))?
+            .into()
+    };
+
+    let info = session_service::play_move(&state, user, session, lock, &chess_move).await?;
+    Ok(Json(info).into_response())
+}
+
+/// Subscribe to session updates.
+///
+/// This endpoint streams server-sent events of the session, the first one is the current state and another one follows after every move.
+/// The stream ends once the session is finished.
+#[utoipa::path(
+    get,
+    path = "/session/events",
+    responses(
+        (status = 200, description = "Stream of session events", body = SessionEvent, content_type = "text/event-stream"),
+        (status = 400, description = "Missing or invalid session id"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Session not found"),
+        (status = 500, description = "Server error"),
+    ),
+    params(
+        ("session-id" = String, Header, description = "ID of the session"),
+      ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Session"
+)]
+async fn get_session_events(
+    ExtractUser(_): ExtractUser,
+    ExtractSession(session): ExtractSession,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    let current = SessionEvent::from(&session);
+    // Subscribed before anything is sent, so no move can get lost in between
+    let updates = if current.finished {
+        stream::empty().boxed()
+    } else {
+        state.notifier.subscribe(current.id.clone()).boxed()
+    };
+    let events = stream::once(async { current })
+        .chain(updates)
+        .map(|event| Event::default().event("session").json_data(event));
+    Ok(Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response())
+}
+
+pub fn router() -> Router<AppState> {
+    Router::<AppState>::new()
+        .route("/session", get(get_session))
+        .route("/session", post(post_session))
+        .route("/session/pgn", get(get_session_pgn))
+        .route("/session/fen", get(get_session_fen))
+        .route("/session/eval/explain", get(get_session_eval_explain))
+        .route("/session/annotations", post(post_session_annotations))
+        .route("/session", delete(delete_session))
+        .route("/session/abort", post(post_session_abort))
+        .route("/session/pause", post(post_session_pause))
+        .route("/session/resume", post(post_session_resume))
+        .route("/session", patch(patch_session))
+        .route("/session/mark", patch(patch_session_mark))
+        .route("/sessions", get(get_sessions))
+        .route("/sessions/batch", post(post_sessions_batch))
+        .route("/session/render", get(get_session_render))
+        .route("/session/render/history", get(get_session_render_history))
+        .route("/session/render/history", post(post_session_render_history))
+        .route(
+            "/session/render/history/:job",
+            get(get_session_render_history_job),
+        )
+        .route("/session/render/signed", get(get_session_render_signed))
+        .route("/session/result", get(get_session_result))
+        .route("/session/review/evals", get(get_session_review_evals))
+        .route("/session/review/export", get(get_session_review_export))
+        .route("/session/review/pgn", get(get_session_review_pgn))
+        .route("/session/move", get(get_session_move))
+        .route("/session/move", post(post_session_move))
+        .route("/session/events", get(get_session_events))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Method;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{
+        game::{state::GameState, termination::Termination},
+        test_utils::*,
+    };
+
+    #[tokio::test]
+    async fn test_session_marks() {
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let lime = create_user(&state, "lime").await;
+        let mut session_ids = Vec::new();
+        for name in ["First", "Second"] {
+            let mut session = create_session(&state, [lemon.clone(), lime.clone()]).await;
+            session.name = name.to_string();
+            session.save(&state.storage, &state.tasks).await.unwrap();
+            session_ids.push(session.id.unwrap().to_hex());
+        }
+
+        let headers = [("session-id", session_ids[1].as_str())];
+        let (status, info) = send_with_headers(
+            &state,
+            Method::PATCH,
+            "/session/mark?tags=Study%20later,brilliancy&favorite=true",
+            &lemon,
+            &headers,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(info["tags"][0], "study later");
+        assert_eq!(info["favorite"], true);
+
+        // The opponent has their own marks
+        let (_, info) = send_with_headers(&state, Method::GET, "/session", &lime, &headers).await;
+        assert!(info["tags"].as_array().unwrap().is_empty());
+
+        let (_, list) = send(&state, Method::GET, "/sessions", &lemon).await;
+        assert_eq!(list["pagination"]["total"], 2);
+        let (_, list) = send(&state, Method::GET, "/sessions?tag=Brilliancy", &lemon).await;
+        assert_eq!(list["pagination"]["total"], 1);
+        assert_eq!(list["sessions"][0]["name"], "Second");
+        let (_, list) = send(&state, Method::GET, "/sessions?favorite=true", &lime).await;
+        assert_eq!(list["pagination"]["total"], 0);
+
+        let (_, info) = send_with_headers(
+            &state,
+            Method::PATCH,
+            "/session/mark?tags=&favorite=false",
+            &lemon,
+            &headers,
+        )
+        .await;
+        assert_eq!(info["favorite"], false);
+        assert!(state
+            .storage
+            .find_session_marks_by_key(&lemon)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_session_filters() {
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let lime = create_user(&state, "lime").await;
+        let day = 24 * 60 * 60 * 1_000_000_000;
+
+        let mut won = Session::new(
+            "Won".to_string(),
+            [lemon.clone(), lime.clone()],
+            GameState::new().unwrap(),
+        );
+        won.game_state.winner = Color::WHITE as u8;
+        won.created_stamp = day;
+        won.updated_stamp = 4 * day;
+        let mut drawn = Session::new(
+            "Drawn".to_string(),
+            [lime.clone(), lemon.clone()],
+            GameState::new().unwrap(),
+        );
+        drawn.game_state.termination = Some(Termination::Agreement);
+        drawn.created_stamp = 2 * day;
+        drawn.updated_stamp = 2 * day;
+        let mut running = Session::new_ai(
+            "Running".to_string(),
+            lemon.clone(),
+            GameState::new().unwrap(),
+            &state.rng,
+        );
+        running.created_stamp = 3 * day;
+        running.updated_stamp = 3 * day;
+        for mut session in [won, drawn, running] {
+            session.id = Some(ObjectId::new());
+            session.save(&state.storage, &state.tasks).await.unwrap();
+        }
+
+        let names = |list: &Value| -> Vec<String> {
+            list["sessions"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|session| session["name"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let (_, list) = send(&state, Method::GET, "/sessions?finished=true", &lemon).await;
+        assert_eq!(names(&list), ["Won", "Drawn"]);
+        let (_, list) = send(&state, Method::GET, "/sessions?vs_ai=true", &lemon).await;
+        assert_eq!(names(&list), ["Running"]);
+        let (_, list) = send(&state, Method::GET, "/sessions?result=WIN", &lemon).await;
+        assert_eq!(names(&list), ["Won"]);
+        let (_, list) = send(&state, Method::GET, "/sessions?result=LOSS", &lime).await;
+        assert_eq!(names(&list), ["Won"]);
+        let (_, list) = send(&state, Method::GET, "/sessions?opponent=LIME", &lemon).await;
+        assert_eq!(names(&list), ["Won", "Drawn"]);
+        let uri = "/sessions?from=1970-01-03&to=1970-01-04&sort=NEWEST";
+        let (_, list) = send(&state, Method::GET, uri, &lemon).await;
+        assert_eq!(names(&list), ["Running", "Drawn"]);
+        assert_eq!(list["pagination"]["total"], 2);
+        let uri = "/sessions?sort=RECENT_ACTIVITY";
+        let (_, list) = send(&state, Method::GET, uri, &lemon).await;
+        assert_eq!(names(&list), ["Won", "Running", "Drawn"]);
+
+        let (status, _) = send(&state, Method::GET, "/sessions?from=tomorrow", &lemon).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_abort_session() {
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let lime = create_user(&state, "lime").await;
+        let session = create_session(&state, [lemon.clone(), lime.clone()]).await;
+        let session_id = session.id.unwrap().to_hex();
+        let headers = [("session-id", session_id.as_str())];
+
+        let uri = "/session/move?from=e2&to=e4";
+        let (status, _) = send_with_headers(&state, Method::POST, uri, &lemon, &headers).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, info) =
+            send_with_headers(&state, Method::POST, "/session/abort", &lime, &headers).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(info["aborted"], true);
+        assert_eq!(info["finished"], true);
+        assert_eq!(info["draw"], false);
+
+        let (status, _) =
+            send_with_headers(&state, Method::POST, "/session/abort", &lemon, &headers).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (_, list) = send(&state, Method::GET, "/sessions?result=DRAW", &lemon).await;
+        assert_eq!(list["pagination"]["total"], 0);
+        let (_, list) = send(&state, Method::GET, "/sessions?finished=false", &lemon).await;
+        assert_eq!(list["pagination"]["total"], 0);
+
+        let session = create_session(&state, [lemon.clone(), lime.clone()]).await;
+        let session_id = session.id.unwrap().to_hex();
+        let headers = [("session-id", session_id.as_str())];
+        for (uri, key) in [
+            ("/session/move?from=e2&to=e4", &lemon),
+            ("/session/move?from=e7&to=e5", &lime),
+        ] {
+            let (status, _) = send_with_headers(&state, Method::POST, uri, key, &headers).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        let (status, _) =
+            send_with_headers(&state, Method::POST, "/session/abort", &lemon, &headers).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_illegal_move_reasons() {
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let lime = create_user(&state, "lime").await;
+        let session = create_session(&state, [lemon.clone(), lime.clone()]).await;
+        let session_id = session.id.unwrap().to_hex();
+        let headers = [("session-id", session_id.as_str())];
+
+        for (uri, key, reason) in [
+            ("/session/move?from=e7&to=e5", &lime, "NOT_YOUR_TURN"),
+            ("/session/move?from=e3&to=e4", &lemon, "PIECE_MISSING"),
+            ("/session/move?from=e7&to=e5", &lemon, "OPPONENT_PIECE"),
+            ("/session/move?from=d1&to=d4", &lemon, "BLOCKED_PATH"),
+            (
+                "/session/move?from=e2&to=e4&promotion=q",
+                &lemon,
+                "WRONG_PROMOTION_RANK",
+            ),
+            (
+                "/session/move?castle_kingside=true",
+                &lemon,
+                "CASTLING_BLOCKED",
+            ),
+        ] {
+            let (status, body) = send_with_headers(&state, Method::POST, uri, key, &headers).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+            assert_eq!(body["reason"], reason, "{}", uri);
+            assert!(body["message"].is_string());
+        }
+
+        // Malformed moves stay plain bad requests without a reason
+        let uri = "/session/move?from=e2";
+        let (status, body) = send_with_headers(&state, Method::POST, uri, &lemon, &headers).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, Value::Null);
+    }
+
+    #[tokio::test]
+    async fn test_confirmed_resignation() {
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let lime = create_user(&state, "lime").await;
+        let session = create_session(&state, [lemon.clone(), lime.clone()]).await;
+        let session_id = session.id.unwrap().to_hex();
+        let headers = [("session-id", session_id.as_str())];
+
+        let uri = "/session?confirm=true";
+        let (status, confirmation) =
+            send_with_headers(&state, Method::DELETE, uri, &lemon, &headers).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(confirmation["expires_in"], 30);
+        let token = confirmation["token"].as_str().unwrap();
+        let (_, info) = send_with_headers(&state, Method::GET, "/session", &lemon, &headers).await;
+        assert_eq!(info["finished"], false);
+
+        // The token only works for the player who asked for it
+        let uri = format!("/session?token={}", token);
+        let (status, _) = send_with_headers(&state, Method::DELETE, &uri, &lime, &headers).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = send_with_headers(
+            &state,
+            Method::DELETE,
+            "/session?token=WRONG",
+            &lemon,
+            &headers,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, info) =
+            send_with_headers(&state, Method::DELETE, &uri, &lemon, &headers).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(info["resign"], true);
+        assert_eq!(info["termination"], "RESIGNATION");
+        assert_eq!(info["winner"], "BLACK");
+
+        let session = session_service::find_session(&state, &session_id)
+            .await
+            .unwrap();
+        assert_eq!(session.get_end_reason().as_deref(), Some("resignation"));
+        let pgn = session.to_pgn(&state, None).await.unwrap();
+        assert!(pgn.contains("[Result \"0-1\"]\n[Termination \"normal\"]"));
+    }
+
+    #[tokio::test]
+    async fn test_rename_session() {
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let lime = create_user(&state, "lime").await;
+        let outsider = create_user(&state, "outsider").await;
+        let session = create_session(&state, [lemon.clone(), lime.clone()]).await;
+        let session_id = session.id.unwrap().to_hex();
+        let headers = [("session-id", session_id.as_str())];
+        let etag = session.get_etag(&lime);
+
+        let uri = "/session?name=LEMONS";
+        let (status, info) = send_with_headers(&state, Method::PATCH, uri, &lime, &headers).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(info["name"], "LEMONS");
+        let session = state
+            .storage
+            .find_session_by_id(&session_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_ne!(session.get_etag(&lime), etag);
+
+        let (status, _) =
+            send_with_headers(&state, Method::PATCH, "/session?name=%20", &lemon, &headers).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = send_with_headers(&state, Method::PATCH, uri, &outsider, &headers).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_pause_session() {
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let lime = create_user(&state, "lime").await;
+        let session = create_session(&state, [lemon.clone(), lime.clone()]).await;
+        let session_id = session.id.unwrap().to_hex();
+        let headers = [("session-id", session_id.as_str())];
+        let move_uri = "/session/move?from=e2&to=e4";
+
+        let (status, info) =
+            send_with_headers(&state, Method::POST, "/session/pause", &lemon, &headers).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(info["paused"], false);
+        assert_eq!(info["pause_requested_by"], "WHITE");
+        let (_, notifications) = send(&state, Method::GET, "/notifications", &lime).await;
+        assert_eq!(notifications["notifications"][0]["kind"], "PAUSE_REQUESTED");
+
+        let (_, info) =
+            send_with_headers(&state, Method::POST, "/session/pause", &lime, &headers).await;
+        assert_eq!(info["paused"], true);
+        assert_eq!(info["pause_requested_by"], Value::Null);
+        let (status, _) = send_with_headers(&state, Method::POST, move_uri, &lemon, &headers).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Both have to resume the game, asking twice doesn't count
+        for key in [&lime, &lime] {
+            send_with_headers(&state, Method::POST, "/session/resume", key, &headers).await;
+        }
+        let (status, _) = send_with_headers(&state, Method::POST, move_uri, &lemon, &headers).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (_, info) =
+            send_with_headers(&state, Method::POST, "/session/resume", &lemon, &headers).await;
+        assert_eq!(info["paused"], false);
+        let (status, _) = send_with_headers(&state, Method::POST, move_uri, &lemon, &headers).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_sessions_batch() {
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let lime = create_user(&state, "lime").await;
+        let mut session_ids = Vec::new();
+        for name in ["First", "Second"] {
+            let mut session = create_session(&state, [lemon.clone(), lime.clone()]).await;
+            session.name = name.to_string();
+            session.save(&state.storage, &state.tasks).await.unwrap();
+            session_ids.push(session.id.unwrap().to_hex());
+        }
+
+        let ids = [
+            session_ids[1].clone(),
+            ObjectId::new().to_hex(),
+            session_ids[0].clone(),
+            session_ids[1].clone(),
+        ];
+        let body = serde_json::json!({ "ids": ids });
+        let (status, infos) =
+            send_json(&state, Method::POST, "/sessions/batch", &lemon, body).await;
+        assert_eq!(status, StatusCode::OK);
+        let infos = infos.as_array().unwrap();
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[0]["name"], "Second");
+        assert_eq!(infos[1]["name"], "First");
+        assert_eq!(infos[1]["black_player"], "lime");
+
+        let body = serde_json::json!({ "ids": ["invalid"] });
+        let (status, _) = send_json(&state, Method::POST, "/sessions/batch", &lemon, body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let ids: Vec<String> = (0..101).map(|_| ObjectId::new().to_hex()).collect();
+        let body = serde_json::json!({ "ids": ids });
+        let (status, _) = send_json(&state, Method::POST, "/sessions/batch", &lemon, body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_eval_explain() {
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let lime = create_user(&state, "lime").await;
+        let session = create_session(&state, [lemon.clone(), lime.clone()]).await;
+        let session_id = session.id.unwrap().to_hex();
+        let headers = [("session-id", session_id.as_str())];
+
+        let uri = "/session/move?from=e2&to=e4";
+        send_with_headers(&state, Method::POST, uri, &lemon, &headers).await;
+
+        let uri = "/session/eval/explain";
+        let (status, _) = send_with_headers(&state, Method::GET, uri, &lime, &headers).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let mut session = state
+            .storage
+            .find_session_by_id(&session_id)
+            .await
+            .unwrap()
+            .unwrap();
+        session.resign(Color::BLACK).unwrap();
+        session.save(&state.storage, &state.tasks).await.unwrap();
+
+        let (status, explanation) =
+            send_with_headers(&state, Method::GET, uri, &lime, &headers).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(
+            explanation["white"]["mobility"].as_u64() > explanation["black"]["mobility"].as_u64()
+        );
+        assert_eq!(explanation["summary"][0], "Material is equal");
+
+        let uri = "/session/eval/explain?ply=0";
+        let (_, explanation) = send_with_headers(&state, Method::GET, uri, &lime, &headers).await;
+        assert_eq!(explanation["static_eval"], 0);
+        let uri = "/session/eval/explain?ply=2";
+        let (status, _) = send_with_headers(&state, Method::GET, uri, &lime, &headers).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_review_evals_finished_only() {
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let lime = create_user(&state, "lime").await;
+        let session = create_session(&state, [lemon.clone(), lime.clone()]).await;
+        let session_id = session.id.unwrap().to_hex();
+        let headers = [("session-id", session_id.as_str())];
+
+        let uri = "/session/move?from=e2&to=e4";
+        send_with_headers(&state, Method::POST, uri, &lemon, &headers).await;
+
+        let uri = "/session/review/evals";
+        let (status, _) = send_with_headers(&state, Method::GET, uri, &lime, &headers).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let mut session = state
+            .storage
+            .find_session_by_id(&session_id)
+            .await
+            .unwrap()
+            .unwrap();
+        session.resign(Color::BLACK).unwrap();
+        session.save(&state.storage, &state.tasks).await.unwrap();
+
+        let (status, _) = send_with_headers(&state, Method::GET, uri, &lemon, &headers).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_render_eval_bar_finished_only() {
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let lime = create_user(&state, "lime").await;
+        let mut session = create_session(&state, [lemon.clone(), lime.clone()]).await;
+        let session_id = session.id.unwrap().to_hex();
+        let headers = [("session-id", session_id.as_str())];
+
+        let uri = "/session/render?eval_bar=true";
+        let (status, _) = send_with_headers(&state, Method::GET, uri, &lime, &headers).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        session.resign(Color::BLACK).unwrap();
+        session.save(&state.storage, &state.tasks).await.unwrap();
+        let (status, _) = send_with_headers(&state, Method::GET, uri, &lemon, &headers).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_blunder_alerts() {
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let lime = create_user(&state, "lime").await;
+        let session = create_session(&state, [lemon.clone(), lime.clone()]).await;
+        let session_id = session.id.unwrap().to_hex();
+        let headers = [("session-id", session_id.as_str())];
+
+        let uri = "/session/move?from=e2&to=e4";
+        send_with_headers(&state, Method::POST, uri, &lemon, &headers).await;
+        let uri = "/session/move?from=e7&to=e5";
+        send_with_headers(&state, Method::POST, uri, &lime, &headers).await;
+
+        let uri = "/session?blunder_alerts=true";
+        let (status, info) = send_with_headers(&state, Method::PATCH, uri, &lime, &headers).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(info["blunder_alerts"], true);
+        assert_eq!(info["name"], "Test");
+
+        let uri = "/session/move?from=g1&to=f3";
+        let (_, info) = send_with_headers(&state, Method::POST, uri, &lemon, &headers).await;
+        assert!(info.get("blunder_warning").is_none());
+        let uri = "/session/move?from=d8&to=g5";
+        let (status, info) = send_with_headers(&state, Method::POST, uri, &lime, &headers).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(info["blunder_warning"]["from"], "f3");
+        assert_eq!(info["blunder_warning"]["to"], "g5");
+        assert_eq!(info["blunder_warning"]["material"], 900);
+
+        // The opponent only sees the move
+        let (_, info) = send_with_headers(&state, Method::GET, "/session", &lemon, &headers).await;
+        assert!(info.get("blunder_warning").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ai_difficulty() {
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+
+        let uri = "/session?difficulty=impossible";
+        let (status, _) = send(&state, Method::POST, uri, &lemon).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = send(&state, Method::POST, "/session?difficulty=easy", &lemon).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (_, list) = send(&state, Method::GET, "/sessions", &lemon).await;
+        let info = &list["sessions"][0];
+        assert_eq!(info["ai_difficulty"], "EASY");
+        assert_eq!(info["your_turn"], true);
+
+        let session_id = info["id"].as_str().unwrap();
+        let headers = [("session-id", session_id)];
+        let (_, legal_moves) =
+            send_with_headers(&state, Method::GET, "/session/move", &lemon, &headers).await;
+        let legal_move = &legal_moves["moves"][0];
+        let uri = format!(
+            "/session/move?from={}&to={}",
+            legal_move["from"].as_str().unwrap(),
+            legal_move["to"].as_str().unwrap()
+        );
+        let (status, info) = send_with_headers(&state, Method::POST, &uri, &lemon, &headers).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(info["your_turn"], true);
+    }
 }
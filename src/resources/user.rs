@@ -1,20 +1,57 @@
-use crate::entities::user::{find_user_by_key, User};
+use crate::chess_com::import_games;
+use crate::entities::audit_entry::AuditEntry;
+use crate::entities::user::{get_name_lock_key, User};
+use crate::entities::webhook::Webhook;
 use crate::error::ApiError;
 use crate::extractors::authentication::ExtractUser;
-use crate::models::enums::PermissionLevel;
-use crate::models::query_models::DiscordUserCreation;
-use crate::models::response_models::UserApiKey;
+use crate::models::audit_models::AuditAction;
+use crate::models::enums::{PermissionLevel, Platform};
+use crate::models::query_models::{
+    ActivityQuery, ApiKeyCreation, ApiKeyName, BotCreation, ChessComImportQuery,
+    DiscordUserCreation, PlatformLink, PlatformUnlink, UserUpdate, WebhookRegistration,
+};
+use crate::models::response_models::{MessageResponse, UserApiKey};
+use crate::models::user_models::{ActivityInfo, ApiKeyInfo, UsageInfo, UserInfo};
+use crate::utils::sanitize::{Sanitize, SanitizePolicy};
+use crate::utils::time_operations::day_to_nanos;
 use crate::AppState;
 use axum::extract::{Query, State};
 use axum::response::{IntoResponse, Response};
-use axum::routing::post;
+use axum::routing::{delete, get, patch, post};
 use axum::{Json, Router};
+use chrono::{Days, Utc};
+
+/// Registers the user in the namespace of the negotiator and records who did it
+async fn create_user(
+    state: &AppState,
+    negotiator: &User,
+    query: &DiscordUserCreation,
+) -> Result<User, ApiError> {
+    let lock = state.locks.acquire(&get_name_lock_key(&query.name)).await?;
+    let user = User::new_from_platform(
+        &*state.storage,
+        &negotiator.namespace,
+        Platform::DISCORD,
+        &query.name,
+        &query.display_name,
+        &query.id,
+    )
+    .await?;
+    lock.release().await;
+    AuditEntry::new(AuditAction::UserCreated, negotiator)
+        .target(&user.name)
+        .details(format!("Discord id {}", query.id))
+        .record(&*state.storage)
+        .await;
+    Ok(user)
+}
 
 /// Registers a new discord user.
 ///
 /// NEGOTIATOR ONLY! This endpoint registers a discord user from a given name and discord user id.
 /// If the api key is given, it tries to link the discord id with the given key.
 /// If the key doesn't exist, it will create a new user as usual.
+/// Users are registered in the namespace of the negotiator, the same discord user can exist once per namespace.
 #[utoipa::path(
     post,
     path = "/user/discord",
@@ -23,7 +60,7 @@ use axum::{Json, Router};
         (status = 200, description = "User successfully registered", body = UserApiKey),
         (status = 400, description = "User id already registered"),
         (status = 401, description = "Invalid API Key"),
-        (status = 403, description = "No permission to use this endpoint"),
+        (status = 403, description = "No permission to use this endpoint or user of another namespace"),
         (status = 500, description = "Server error"),
     ),
     security(
@@ -39,38 +76,793 @@ async fn post_user_discord(
     negotiator
         .permission
         .authenticate(PermissionLevel::Negotiator)?;
+    let query = query.sanitize(SanitizePolicy::for_namespace(&negotiator.namespace))?;
 
     let user = match &query.api_key {
-        Some(key) => match find_user_by_key(&state.database.user_collection, key).await? {
+        Some(key) => match state.storage.find_user_by_key(key).await? {
             Some(mut user) => {
-                user.discord_id = query.id.clone();
-                user.save(&state.database.user_collection).await?;
+                if user.namespace != negotiator.namespace {
+                    return Err(ApiError::NoPermission(
+                        "Can't link a user of another namespace.".to_string(),
+                    ));
+                }
+                user.platform_links
+                    .insert(Platform::DISCORD, query.id.clone());
+                user.save(&*state.storage).await?;
+                AuditEntry::new(AuditAction::DiscordLinked, &negotiator)
+                    .target(&user.name)
+                    .details(format!("Discord id {}", query.id))
+                    .record(&*state.storage)
+                    .await;
                 user
             }
-            None => {
-                User::new_from_discord(
-                    &state.database.user_collection,
-                    &query.name,
-                    &query.display_name,
-                    &query.id,
-                )
-                .await?
-            }
+            None => create_user(&state, &negotiator, &query).await?,
         },
-        None => {
-            User::new_from_discord(
-                &state.database.user_collection,
-                &query.name,
-                &query.display_name,
-                &query.id,
-            )
-            .await?
-        }
+        None => create_user(&state, &negotiator, &query).await?,
     };
 
     Ok(Json(UserApiKey { api_key: user.key }).into_response())
 }
 
+/// Register a bot account (60s cooldown).
+///
+/// This endpoint registers an engine account operated by you in your namespace and returns its API key.
+/// Bots only join rooms which allow bots or invited them and have a relaxed rate limit for playing moves.
+#[utoipa::path(
+    post,
+    path = "/user/bot",
+    params(BotCreation),
+    responses(
+        (status = 200, description = "Bot successfully registered", body = UserApiKey),
+        (status = 400, description = "Invalid name or you are a bot yourself"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 429, description = "Rate limited"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn post_user_bot(
+    ExtractUser(operator): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<BotCreation>,
+) -> Result<Response, ApiError> {
+    let query = query.sanitize(SanitizePolicy::for_namespace(&operator.namespace))?;
+    let display_name = query.display_name.unwrap_or(query.name.clone());
+    let lock = state.locks.acquire(&get_name_lock_key(&query.name)).await?;
+    let bot = User::new_bot(&*state.storage, &operator, &query.name, &display_name).await?;
+    lock.release().await;
+    AuditEntry::new(AuditAction::UserCreated, &operator)
+        .target(&bot.name)
+        .details("Bot account")
+        .record(&*state.storage)
+        .await;
+    Ok(Json(UserApiKey { api_key: bot.key }).into_response())
+}
+
+/// Looks up a user of the negotiator's namespace
+async fn find_namespace_user(
+    state: &AppState,
+    negotiator: &User,
+    name: &str,
+) -> Result<User, ApiError> {
+    match state.storage.find_user_by_name(name).await? {
+        Some(user) if user.namespace == negotiator.namespace => Ok(user),
+        _ => Err(ApiError::NotFound("User not found".to_string())),
+    }
+}
+
+/// Link a chat platform id.
+///
+/// NEGOTIATOR ONLY! This endpoint links a Discord, Telegram or Slack user id with a user of your namespace, so bots on that platform can act for them.
+/// Every platform id can only be linked with one user per namespace, linking a platform again replaces the previous id.
+#[utoipa::path(
+    post,
+    path = "/user/link",
+    params(PlatformLink),
+    responses(
+        (status = 200, description = "Platform id linked", body = MessageResponse),
+        (status = 400, description = "Invalid platform id"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "No permission to use this endpoint"),
+        (status = 404, description = "User not found"),
+        (status = 409, description = "The platform id is already linked with another user"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn post_user_link(
+    ExtractUser(negotiator): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<PlatformLink>,
+) -> Result<Response, ApiError> {
+    negotiator
+        .permission
+        .authenticate(PermissionLevel::Negotiator)?;
+    let id = query.id.trim();
+    if id.is_empty() {
+        return Err(ApiError::BadRequest("Invalid platform id.".to_string()));
+    }
+    let mut user = find_namespace_user(&state, &negotiator, &query.name).await?;
+
+    // The same id must not end up linked with two users
+    let lock = state
+        .locks
+        .acquire(&format!(
+            "platform_link:{}:{:?}:{}",
+            negotiator.namespace, query.platform, id
+        ))
+        .await?;
+    match state
+        .storage
+        .find_user_by_platform_id(&negotiator.namespace, query.platform, id)
+        .await?
+    {
+        Some(linked_user) if linked_user.key != user.key => {
+            return Err(ApiError::Conflict(format!(
+                "This {:?} id is already linked with another user.",
+                query.platform
+            )))
+        }
+        _ => {}
+    }
+    user.platform_links.insert(query.platform, id.to_string());
+    user.save(&*state.storage).await?;
+    lock.release().await;
+
+    AuditEntry::new(AuditAction::PlatformLinked, &negotiator)
+        .target(&user.name)
+        .details(format!("{:?} id {}", query.platform, id))
+        .record(&*state.storage)
+        .await;
+
+    Ok(Json(MessageResponse {
+        message: format!("Linked {} with {:?} id {}", user.name, query.platform, id),
+    })
+    .into_response())
+}
+
+/// Unlink a chat platform id.
+///
+/// NEGOTIATOR ONLY! This endpoint removes the Discord, Telegram or Slack id of a user of your namespace.
+#[utoipa::path(
+    delete,
+    path = "/user/link",
+    params(PlatformUnlink),
+    responses(
+        (status = 200, description = "Platform id unlinked", body = MessageResponse),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "No permission to use this endpoint"),
+        (status = 404, description = "User not found or not linked with the platform"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn delete_user_link(
+    ExtractUser(negotiator): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<PlatformUnlink>,
+) -> Result<Response, ApiError> {
+    negotiator
+        .permission
+        .authenticate(PermissionLevel::Negotiator)?;
+    let mut user = find_namespace_user(&state, &negotiator, &query.name).await?;
+
+    let id = user
+        .platform_links
+        .remove(&query.platform)
+        .ok_or(ApiError::NotFound(format!(
+            "The user isn't linked with {:?}",
+            query.platform
+        )))?;
+    user.save(&*state.storage).await?;
+
+    AuditEntry::new(AuditAction::PlatformUnlinked, &negotiator)
+        .target(&user.name)
+        .details(format!("{:?} id {}", query.platform, id))
+        .record(&*state.storage)
+        .await;
+
+    Ok(Json(MessageResponse {
+        message: format!("Unlinked {} from {:?}", user.name, query.platform),
+    })
+    .into_response())
+}
+
+/// Retrieve your profile.
+///
+/// This endpoint returns the profile of the user the API key belongs to.
+#[utoipa::path(
+    get,
+    path = "/user/me",
+    responses(
+        (status = 200, description = "Your profile", body = UserInfo),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn get_user_me(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    let info = UserInfo::from_user(&*state.storage, user).await?;
+    Ok(Json(info).into_response())
+}
+
+/// Retrieve your daily activity.
+///
+/// This endpoint returns how many games you played and won per UTC day together with your streaks of days with games, e.g. for activity heatmaps.
+/// Games count on the day they were started, running games aren't included.
+#[utoipa::path(
+    get,
+    path = "/user/activity",
+    params(ActivityQuery),
+    responses(
+        (status = 200, description = "Your games per day", body = ActivityInfo),
+        (status = 400, description = "Invalid range of days"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn get_user_activity(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<ActivityQuery>,
+) -> Result<Response, ApiError> {
+    let (from, to) = query.retrieve(Utc::now().date_naive())?;
+    let to_stamp = to
+        .checked_add_days(Days::new(1))
+        .map(day_to_nanos)
+        .unwrap_or(u64::MAX);
+    let days = state
+        .storage
+        .find_daily_activity(&user.key, day_to_nanos(from), to_stamp)
+        .await?;
+    Ok(Json(ActivityInfo::new(from, to, days)).into_response())
+}
+
+/// Retrieve your API usage.
+///
+/// This endpoint returns how often you used each endpoint and your current rate limits, which all of your API keys share.
+#[utoipa::path(
+    get,
+    path = "/user/usage",
+    responses(
+        (status = 200, description = "Your API usage", body = UsageInfo),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn get_user_usage(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    let info = UsageInfo::from_user(&state.rate_limiter, user).await?;
+    Ok(Json(info).into_response())
+}
+
+/// Import your chess.com games (60s cooldown).
+///
+/// This endpoint imports the finished games of the latest months of a chess.com account from its public archives as finished sessions, which then count towards your stats and can be reviewed.
+/// Games imported before are skipped. Variants, games from custom positions and promotions to other pieces than a queen are not supported.
+#[utoipa::path(
+    post,
+    path = "/user/import/chesscom",
+    params(ChessComImportQuery),
+    responses(
+        (status = 200, description = "What happened to the found games", body = ImportSummary),
+        (status = 400, description = "Invalid username or months"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "chess.com user not found"),
+        (status = 429, description = "Rate limited"),
+        (status = 500, description = "Server error or chess.com not reachable"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn post_user_import_chesscom(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<ChessComImportQuery>,
+) -> Result<Response, ApiError> {
+    let (username, months) = query.retrieve()?;
+    let summary = import_games(&state, &user, &username, months).await?;
+    Ok(Json(summary).into_response())
+}
+
+/// Rename yourself (60s cooldown).
+///
+/// This endpoint changes your unique name, your display name or both.
+/// Unique names are lowercase and have to be free, the display name is what other people see.
+#[utoipa::path(
+    patch,
+    path = "/user",
+    params(UserUpdate),
+    responses(
+        (status = 200, description = "Your updated profile", body = UserInfo),
+        (status = 400, description = "Invalid name or nothing to change"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "The key used lacks the scope for this endpoint"),
+        (status = 409, description = "The name is already taken"),
+        (status = 429, description = "Rate limited"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn patch_user(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<UserUpdate>,
+) -> Result<Response, ApiError> {
+    let query = query.sanitize(SanitizePolicy::for_namespace(&user.namespace))?;
+    if query.name.is_none() && query.display_name.is_none() {
+        return Err(ApiError::BadRequest("Nothing to change.".to_string()));
+    }
+
+    // Only the names are written, so a ban or key change made in the meantime isn't undone
+    let display_name = query.display_name.as_deref();
+    let Some(name) = query.name.filter(|name| *name != user.name) else {
+        let user = state
+            .storage
+            .rename_user(&user.key, None, display_name)
+            .await?
+            .ok_or(ApiError::NotFound("User not found".to_string()))?;
+        return Ok(Json(UserInfo::from_user(&*state.storage, user).await?).into_response());
+    };
+
+    // Two users taking the same free name at once must not both get it
+    let lock = state.locks.acquire(&get_name_lock_key(&name)).await?;
+    if state.storage.find_user_by_name(&name).await?.is_some() {
+        return Err(ApiError::Conflict(
+            "This name is already taken.".to_string(),
+        ));
+    }
+    let renamed = state
+        .storage
+        .rename_user(&user.key, Some(&name), display_name)
+        .await?
+        .ok_or(ApiError::NotFound("User not found".to_string()))?;
+    lock.release().await;
+
+    AuditEntry::new(AuditAction::UserRenamed, &renamed)
+        .details(format!("{} to {}", user.name, renamed.name))
+        .record(&*state.storage)
+        .await;
+
+    let info = UserInfo::from_user(&*state.storage, renamed).await?;
+    Ok(Json(info).into_response())
+}
+
+/// List your secondary API keys.
+///
+/// This endpoint lists the additional keys of your user, the keys themselves are only shown once when they are created.
+#[utoipa::path(
+    get,
+    path = "/user/keys",
+    responses(
+        (status = 200, description = "Your secondary keys", body = Vec<ApiKeyInfo>),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "The key used lacks the scope for this endpoint"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn get_user_keys(ExtractUser(user): ExtractUser) -> Result<Response, ApiError> {
+    let keys: Vec<ApiKeyInfo> = user.secondary_keys.iter().map(ApiKeyInfo::from).collect();
+    Ok(Json(keys).into_response())
+}
+
+/// Create a secondary API key.
+///
+/// This endpoint creates an additional key for your user with a limited scope, e.g. a PLAY_MOVES key for a bot while your main key stays private.
+/// READ_ONLY keys can only use GET endpoints, PLAY_MOVES keys can additionally move and ADMIN keys can do everything your main key can.
+/// Only keys with the ADMIN scope can manage keys.
+#[utoipa::path(
+    post,
+    path = "/user/keys",
+    params(ApiKeyCreation),
+    responses(
+        (status = 200, description = "The created key", body = ApiKeyInfo),
+        (status = 400, description = "Invalid name or key limit reached"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "The key used lacks the scope for this endpoint"),
+        (status = 409, description = "A key with this name already exists"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn post_user_keys(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<ApiKeyCreation>,
+) -> Result<Response, ApiError> {
+    let query = query.sanitize(SanitizePolicy::for_namespace(&user.namespace))?;
+    let secondary_key = user.new_secondary_key(query.name, query.scope)?;
+    // Added to the stored user only, so a key revoked at the same time isn't written back
+    if !state
+        .storage
+        .push_secondary_key(&user.key, &secondary_key)
+        .await?
+    {
+        return Err(ApiError::NotFound("User not found".to_string()));
+    }
+    AuditEntry::new(AuditAction::KeyCreated, &user)
+        .details(format!(
+            "{} ({:?})",
+            secondary_key.name, secondary_key.scope
+        ))
+        .record(&*state.storage)
+        .await;
+
+    let mut info = ApiKeyInfo::from(&secondary_key);
+    info.api_key = Some(secondary_key.key);
+    Ok(Json(info).into_response())
+}
+
+/// Revoke a secondary API key.
+///
+/// This endpoint deletes one of your additional keys, it can't be used anymore afterwards.
+#[utoipa::path(
+    delete,
+    path = "/user/keys",
+    params(ApiKeyName),
+    responses(
+        (status = 200, description = "Key revoked", body = MessageResponse),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "The key used lacks the scope for this endpoint"),
+        (status = 404, description = "Key not found"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn delete_user_keys(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<ApiKeyName>,
+) -> Result<Response, ApiError> {
+    // Removed from the stored user only, requests running at the same time can't bring the key back
+    let secondary_key = state
+        .storage
+        .remove_secondary_key(&user.key, &query.name)
+        .await?
+        .ok_or(ApiError::NotFound("Key not found".to_string()))?;
+    AuditEntry::new(AuditAction::KeyRevoked, &user)
+        .details(secondary_key.name.clone())
+        .record(&*state.storage)
+        .await;
+
+    Ok(Json(MessageResponse {
+        message: format!("Key {} revoked", secondary_key.name),
+    })
+    .into_response())
+}
+
+/// Register a game result webhook.
+///
+/// NEGOTIATOR ONLY! This endpoint registers a https URL which receives a GameFinishedEvent as POST request whenever a game of a Discord linked user of your namespace finished, so your bot doesn't have to poll for results.
+/// Registering again replaces your previous webhook and its secret. Failed calls are not retried.
+#[utoipa::path(
+    post,
+    path = "/user/webhook",
+    params(WebhookRegistration),
+    responses(
+        (status = 200, description = "Webhook registered, the secret is only included this time", body = WebhookInfo),
+        (status = 400, description = "Invalid URL"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "No permission to use this endpoint"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn post_user_webhook(
+    ExtractUser(negotiator): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<WebhookRegistration>,
+) -> Result<Response, ApiError> {
+    negotiator
+        .permission
+        .authenticate(PermissionLevel::Negotiator)?;
+    let webhook = Webhook::new(&negotiator, query.retrieve()?);
+    state.storage.save_webhook(&webhook).await?;
+    Ok(Json(webhook.to_info(true)).into_response())
+}
+
+/// Retrieve your game result webhook.
+///
+/// NEGOTIATOR ONLY! This endpoint returns the webhook you registered, without its secret.
+#[utoipa::path(
+    get,
+    path = "/user/webhook",
+    responses(
+        (status = 200, description = "Your webhook", body = WebhookInfo),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "No permission to use this endpoint"),
+        (status = 404, description = "No webhook registered"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn get_user_webhook(
+    ExtractUser(negotiator): ExtractUser,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    negotiator
+        .permission
+        .authenticate(PermissionLevel::Negotiator)?;
+    let webhook = state
+        .storage
+        .find_webhook_by_key(&negotiator.key)
+        .await?
+        .ok_or(ApiError::NotFound("No webhook registered".to_string()))?;
+    Ok(Json(webhook.to_info(false)).into_response())
+}
+
+/// Remove your game result webhook.
+///
+/// NEGOTIATOR ONLY! This endpoint stops the calls of your webhook.
+#[utoipa::path(
+    delete,
+    path = "/user/webhook",
+    responses(
+        (status = 200, description = "Webhook removed", body = MessageResponse),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "No permission to use this endpoint"),
+        (status = 404, description = "No webhook registered"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn delete_user_webhook(
+    ExtractUser(negotiator): ExtractUser,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    negotiator
+        .permission
+        .authenticate(PermissionLevel::Negotiator)?;
+    state
+        .storage
+        .find_webhook_by_key(&negotiator.key)
+        .await?
+        .ok_or(ApiError::NotFound("No webhook registered".to_string()))?;
+    state.storage.delete_webhook_by_key(&negotiator.key).await?;
+    Ok(Json(MessageResponse {
+        message: "Webhook removed".to_string(),
+    })
+    .into_response())
+}
+
 pub fn router() -> Router<AppState> {
-    Router::<AppState>::new().route("/user/discord", post(post_user_discord))
+    Router::<AppState>::new()
+        .route("/user/discord", post(post_user_discord))
+        .route("/user/bot", post(post_user_bot))
+        .route("/user/link", post(post_user_link))
+        .route("/user/link", delete(delete_user_link))
+        .route("/user", patch(patch_user))
+        .route("/user/me", get(get_user_me))
+        .route("/user/activity", get(get_user_activity))
+        .route("/user/usage", get(get_user_usage))
+        .route("/user/import/chesscom", post(post_user_import_chesscom))
+        .route("/user/keys", get(get_user_keys))
+        .route("/user/keys", post(post_user_keys))
+        .route("/user/keys", delete(delete_user_keys))
+        .route(
+            "/user/webhook",
+            post(post_user_webhook)
+                .get(get_user_webhook)
+                .delete(delete_user_webhook),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+
+    use super::*;
+    use crate::test_utils::create_user;
+    use crate::test_utils::*;
+
+    #[tokio::test]
+    async fn test_secondary_key_scope() {
+        let state = test_state();
+        let key = create_user(&state, "lemon").await;
+
+        let (status, created) = send(
+            &state,
+            Method::POST,
+            "/user/keys?name=bot&scope=PLAY_MOVES",
+            &key,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let bot_key = created["api_key"].as_str().unwrap();
+
+        let (status, _) = send(&state, Method::GET, "/", bot_key).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&state, Method::POST, "/room", bot_key).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&state, Method::GET, "/user/keys", bot_key).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, _) = send(&state, Method::DELETE, "/user/keys?name=bot", &key).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&state, Method::GET, "/", bot_key).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_rename() {
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let other = create_user(&state, "other").await;
+
+        let (status, info) = send(
+            &state,
+            Method::PATCH,
+            "/user?name=Lime&display_name=Lime",
+            &lemon,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(info["name"], "lime");
+        assert_eq!(info["display_name"], "Lime");
+
+        let (status, _) = send(&state, Method::PATCH, "/user?name=lime", &other).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        // Names which don't fit are rejected instead of being cut or stripped
+        for (index, name) in [&"l".repeat(65), "lime%20green"].iter().enumerate() {
+            let key = create_user(&state, &format!("user{}", index)).await;
+            let uri = format!("/user?name={}", name);
+            let (status, _) = send(&state, Method::PATCH, &uri, &key).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_usage() {
+        let state = test_state();
+        let admin = create_user(&state, "admin").await;
+        let lemon = create_user(&state, "lemon").await;
+        set_permission(&state, &admin, PermissionLevel::Admin).await;
+
+        send(&state, Method::PATCH, "/user?display_name=Lemon", &lemon).await;
+        let (status, usage) = send(&state, Method::GET, "/user/usage", &lemon).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(usage["endpoint_usage"]["PATCH /user"], 1);
+        assert_eq!(usage["total_requests"], 2);
+        let cooldowns = usage["cooldowns"].as_array().unwrap();
+        let rename = cooldowns
+            .iter()
+            .find(|cooldown| cooldown["bucket"] == "rename_user")
+            .unwrap();
+        assert_eq!(rename["remaining"], 0);
+        assert!(rename["retry_after_ms"].as_u64().unwrap() > 0);
+
+        let (status, _) = send(&state, Method::GET, "/admin/usage", &lemon).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, summary) = send(&state, Method::GET, "/admin/usage", &admin).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(summary["users"], 2);
+        // Denied requests are counted as well
+        assert_eq!(summary["endpoint_usage"]["GET /admin/usage"], 2);
+        assert_eq!(summary["total_requests"], 4);
+    }
+
+    #[tokio::test]
+    async fn test_webhook() {
+        let state = test_state();
+        let negotiator = create_user(&state, "negotiator").await;
+        let lemon = create_user(&state, "lemon").await;
+        set_permission(&state, &negotiator, PermissionLevel::Negotiator).await;
+
+        let uri = "/user/webhook?url=https://bot.example.com/results";
+        let (status, _) = send(&state, Method::POST, uri, &lemon).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(
+            &state,
+            Method::POST,
+            "/user/webhook?url=https://127.0.0.1/results",
+            &negotiator,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, info) = send(&state, Method::POST, uri, &negotiator).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(info["secret"].as_str().unwrap().len(), 32);
+
+        let (status, info) = send(&state, Method::GET, "/user/webhook", &negotiator).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(info["url"], "https://bot.example.com/results");
+        assert!(info["secret"].is_null());
+        assert_eq!(
+            state
+                .storage
+                .find_webhooks_by_namespace("")
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let (status, _) = send(&state, Method::DELETE, "/user/webhook", &negotiator).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&state, Method::GET, "/user/webhook", &negotiator).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_platform_link() {
+        let state = test_state();
+        let negotiator = create_user(&state, "negotiator").await;
+        let lemon = create_user(&state, "lemon").await;
+        create_user(&state, "lime").await;
+        set_permission(&state, &negotiator, PermissionLevel::Negotiator).await;
+
+        let uri = "/user/link?name=lemon&platform=TELEGRAM&id=7";
+        let (status, _) = send(&state, Method::POST, uri, &lemon).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&state, Method::POST, uri, &negotiator).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(
+            &state,
+            Method::POST,
+            "/user/link?name=lime&platform=telegram&id=7",
+            &negotiator,
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (_, info) = send(&state, Method::GET, "/user/me", &lemon).await;
+        assert_eq!(info["platform_links"]["TELEGRAM"], "7");
+        assert_eq!(info["platform_links"]["DISCORD"], "lemon");
+
+        let uri = "/user/link?name=lemon&platform=TELEGRAM";
+        let (status, _) = send(&state, Method::DELETE, uri, &negotiator).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&state, Method::DELETE, uri, &negotiator).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
 }
@@ -0,0 +1,42 @@
+use crate::{models::response_models::VersionInfo, AppState};
+use axum::response::{IntoResponse, Response};
+use axum::{routing::get, Json, Router};
+
+/// Retrieve the version of the API.
+///
+/// This endpoint returns the version, git commit, build time and compiled features of the answering server, so you can verify which deployment you're talking to. It doesn't require an API key.
+#[utoipa::path(
+    get,
+    path = "/version",
+    responses(
+        (status = 200, description = "Build information", body = VersionInfo),
+    ),
+    tag = "Misc"
+)]
+async fn get_version() -> Response {
+    Json(VersionInfo::current()).into_response()
+}
+
+pub fn router() -> Router<AppState> {
+    Router::<AppState>::new().route("/version", get(get_version))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+
+    use crate::test_utils::*;
+
+    #[tokio::test]
+    async fn test_version() {
+        let state = test_state();
+        let request = Request::builder().uri("/version").body(Body::empty());
+        let (status, version) = respond(&state, request.unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+        assert!(version["build_stamp"].as_u64().unwrap() > 0);
+    }
+}
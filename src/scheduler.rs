@@ -0,0 +1,403 @@
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::future::join_all;
+use lazy_static::lazy_static;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    entities::{
+        notification::Notification,
+        session::{get_lock_key, Session},
+        webhook::deliver_game_finished,
+    },
+    error::ApiError,
+    models::{
+        notification_models::NotificationKind,
+        scheduler_models::{ScheduledTaskInfo, SchedulerInfo},
+    },
+    services::session_service,
+    utils::time_operations::timestamp_now_nanos,
+    AppState,
+};
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+lazy_static! {
+    /// Finished sessions created more than this many days ago are archived
+    static ref ARCHIVE_AFTER_DAYS: u64 = env::var("SESSION_ARCHIVE_DAYS")
+        .ok()
+        .and_then(|days| days.parse().ok())
+        .unwrap_or(30);
+}
+
+/// Periodic maintenance work, every task runs on its own interval
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScheduledTask {
+    /// Deletes rooms nobody joined in time
+    ExpireRooms,
+    /// Lets the player to move of an abandoned timed game lose on time
+    ForfeitAbandonedGames,
+    /// Moves old finished sessions out of the session collection
+    ArchiveSessions,
+    /// Retries the moves the AI failed to play once their retry is due
+    RetryAiMoves,
+    /// Drops the in-memory rate limiting buckets which are full again
+    CleanupRateLimits,
+}
+
+impl ScheduledTask {
+    pub const ALL: [ScheduledTask; 5] = [
+        ScheduledTask::ExpireRooms,
+        ScheduledTask::ForfeitAbandonedGames,
+        ScheduledTask::ArchiveSessions,
+        ScheduledTask::RetryAiMoves,
+        ScheduledTask::CleanupRateLimits,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScheduledTask::ExpireRooms => "expire_rooms",
+            ScheduledTask::ForfeitAbandonedGames => "forfeit_abandoned_games",
+            ScheduledTask::ArchiveSessions => "archive_sessions",
+            ScheduledTask::RetryAiMoves => "retry_ai_moves",
+            ScheduledTask::CleanupRateLimits => "cleanup_rate_limits",
+        }
+    }
+
+    fn default_interval(&self) -> Duration {
+        match self {
+            ScheduledTask::ExpireRooms => Duration::from_secs(60),
+            ScheduledTask::ForfeitAbandonedGames => Duration::from_secs(60),
+            ScheduledTask::ArchiveSessions => Duration::from_secs(60 * 60),
+            ScheduledTask::RetryAiMoves => Duration::from_secs(10),
+            ScheduledTask::CleanupRateLimits => Duration::from_secs(60),
+        }
+    }
+
+    /// Overridable in seconds with SCHEDULER_<NAME>_INTERVAL, e.g. SCHEDULER_EXPIRE_ROOMS_INTERVAL
+    pub fn get_interval(&self) -> Duration {
+        let variable = format!("SCHEDULER_{}_INTERVAL", self.name().to_uppercase());
+        env::var(variable)
+            .ok()
+            .and_then(|seconds| seconds.parse().ok())
+            .filter(|seconds| *seconds > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(self.default_interval())
+    }
+
+    /// Returns how many rooms, sessions or rate limiting buckets were changed
+    async fn execute(&self, state: &AppState) -> Result<u64, ApiError> {
+        match self {
+            ScheduledTask::ExpireRooms => state.storage.delete_expired_rooms().await,
+            ScheduledTask::ForfeitAbandonedGames => forfeit_abandoned_games(state).await,
+            ScheduledTask::ArchiveSessions => {
+                let created_before =
+                    timestamp_now_nanos().saturating_sub(*ARCHIVE_AFTER_DAYS * NANOS_PER_DAY);
+                state
+                    .storage
+                    .archive_finished_sessions(created_before)
+                    .await
+            }
+            ScheduledTask::RetryAiMoves => retry_ai_moves(state).await,
+            ScheduledTask::CleanupRateLimits => Ok(state.rate_limiter.cleanup()),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+struct TaskMetrics {
+    runs: u64,
+    failures: u64,
+    total_affected: u64,
+    last_affected: u64,
+    last_run_stamp: Option<u64>,
+    last_duration_ms: Option<u64>,
+    last_error: Option<String>,
+}
+
+/// Outcome of the scheduled tasks of this instance, replicas keep their own
+#[derive(Clone, Default)]
+pub struct SchedulerMetrics {
+    tasks: Arc<Mutex<HashMap<&'static str, TaskMetrics>>>,
+}
+
+impl SchedulerMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn record(
+        &self,
+        task: ScheduledTask,
+        started_stamp: u64,
+        duration: Duration,
+        result: &Result<u64, ApiError>,
+    ) {
+        let mut tasks = self.tasks.lock().unwrap();
+        let metrics = tasks.entry(task.name()).or_default();
+        metrics.runs += 1;
+        metrics.last_run_stamp = Some(started_stamp);
+        metrics.last_duration_ms = Some(duration.as_millis() as u64);
+        match result {
+            Ok(affected) => {
+                metrics.total_affected += affected;
+                metrics.last_affected = *affected;
+                metrics.last_error = None;
+            }
+            Err(error) => {
+                metrics.failures += 1;
+                metrics.last_affected = 0;
+                metrics.last_error = Some(error.to_string());
+            }
+        }
+    }
+
+    /// All tasks, including the ones which didn't run yet
+    pub fn get_info(&self) -> SchedulerInfo {
+        let tasks = self.tasks.lock().unwrap();
+        let tasks = ScheduledTask::ALL
+            .iter()
+            .map(|task| {
+                let metrics = tasks.get(task.name()).cloned().unwrap_or_default();
+                ScheduledTaskInfo {
+                    name: task.name().to_string(),
+                    interval_seconds: task.get_interval().as_secs(),
+                    runs: metrics.runs,
+                    failures: metrics.failures,
+                    total_affected: metrics.total_affected,
+                    last_affected: metrics.last_affected,
+                    last_run_stamp: metrics.last_run_stamp,
+                    last_duration_ms: metrics.last_duration_ms,
+                    last_error: metrics.last_error,
+                }
+            })
+            .collect();
+        SchedulerInfo { tasks }
+    }
+}
+
+/// Runs every scheduled task on its interval until shutdown is requested
+pub async fn run(state: AppState, shutdown: CancellationToken) {
+    join_all(
+        ScheduledTask::ALL
+            .into_iter()
+            .map(|task| run_task(state.clone(), task, shutdown.clone())),
+    )
+    .await;
+}
+
+async fn run_task(state: AppState, task: ScheduledTask, shutdown: CancellationToken) {
+    let mut interval = tokio::time::interval(task.get_interval());
+    // A slow run shouldn't be followed by a burst of runs to catch up
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => return,
+            _ = interval.tick() => run_once(&state, task).await,
+        }
+    }
+}
+
+/// Executes the task and records its outcome in the scheduler metrics
+pub async fn run_once(state: &AppState, task: ScheduledTask) {
+    let started_stamp = timestamp_now_nanos();
+    let started = Instant::now();
+    let result = task.execute(state).await;
+    state
+        .scheduler
+        .record(task, started_stamp, started.elapsed(), &result);
+
+    match result {
+        Ok(0) => {}
+        Ok(count) => tracing::info!(task = task.name(), count, "Scheduled task changed entries"),
+        Err(error) => tracing::error!(task = task.name(), %error, "Scheduled task failed"),
+    }
+}
+
+async fn forfeit_abandoned_games(state: &AppState) -> Result<u64, ApiError> {
+    let now = timestamp_now_nanos();
+    let mut count = 0;
+    for session in state.storage.find_running_timed_sessions().await? {
+        if !session.is_abandoned(now) {
+            continue;
+        }
+        let session_id = session.id.map(|id| id.to_hex()).unwrap_or_default();
+        // A busy or broken session shouldn't hold up the others until the next run
+        match forfeit_if_abandoned(state, &session_id).await {
+            Ok(Some(session)) => {
+                notify_timeout(state, &session).await;
+                state
+                    .tasks
+                    .spawn(deliver_game_finished(state.clone(), session));
+                count += 1;
+            }
+            Ok(None) => {}
+            Err(error) => {
+                tracing::warn!(session_id, %error, "Failed to forfeit an abandoned game")
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// Returns in how many sessions the AI moved this time
+async fn retry_ai_moves(state: &AppState) -> Result<u64, ApiError> {
+    let now = timestamp_now_nanos();
+    let mut count = 0;
+    for session in state.storage.find_failed_ai_sessions().await? {
+        if !session.is_ai_move_due(now) {
+            continue;
+        }
+        let session_id = session.id.map(|id| id.to_hex()).unwrap_or_default();
+        match session_service::retry_ai_move(state, &session_id, false).await {
+            Ok(Some(true)) => count += 1,
+            Ok(_) => {}
+            Err(error) => tracing::warn!(session_id, %error, "Failed to retry an AI move"),
+        }
+    }
+    Ok(count)
+}
+
+/// Returns the session if the player to move lost on time
+async fn forfeit_if_abandoned(
+    state: &AppState,
+    session_id: &str,
+) -> Result<Option<Session>, ApiError> {
+    let lock = state.locks.acquire(&get_lock_key(session_id)).await?;
+    // The player could have moved or paused the game in the meantime
+    let session = state.storage.find_session_by_id(session_id).await?;
+    let Some(mut session) = session.filter(|session| session.is_abandoned(timestamp_now_nanos()))
+    else {
+        lock.release().await;
+        return Ok(None);
+    };
+
+    session.time_out()?;
+    session.save(&state.storage, &state.tasks).await?;
+    lock.release().await;
+    Ok(Some(session))
+}
+
+/// Nobody caused the timeout, so both players are told
+async fn notify_timeout(state: &AppState, session: &Session) {
+    for key in &session.keys {
+        Notification::new(
+            key,
+            NotificationKind::GameFinished,
+            format!(
+                "{} ended by timeout ({})",
+                session.name,
+                session.get_result_notation()
+            ),
+        )
+        .session(session)
+        .send(&*state.storage)
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{
+        models::{enums::PermissionLevel, session_models::TimeControl},
+        test_utils::*,
+    };
+
+    #[tokio::test]
+    async fn test_forfeit_abandoned_games() {
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let lime = create_user(&state, "lime").await;
+        let mut session_ids = Vec::new();
+        for (name, idle_seconds) in [("Busy", 400), ("Abandoned", 400), ("Thinking", 100)] {
+            let mut session = create_session(&state, [lemon.clone(), lime.clone()]).await;
+            session.name = name.to_string();
+            session.time_control = Some(TimeControl::new(300, 5).unwrap());
+            session.created_stamp -= idle_seconds * 1_000_000_000;
+            session.updated_stamp = session.created_stamp;
+            session.save(&state.storage, &state.tasks).await.unwrap();
+            session_ids.push(session.id.unwrap().to_hex());
+        }
+
+        // A session which stays locked doesn't hold up the others
+        let lock = state.locks.acquire(&get_lock_key(&session_ids[0])).await;
+        run_once(&state, ScheduledTask::ForfeitAbandonedGames).await;
+        lock.unwrap().release().await;
+        let busy = state.storage.find_session_by_id(&session_ids[0]);
+        assert!(!busy.await.unwrap().unwrap().is_finished());
+        let abandoned = state.storage.find_session_by_id(&session_ids[1]);
+        let abandoned = abandoned.await.unwrap().unwrap();
+        assert_eq!(abandoned.get_end_reason().as_deref(), Some("timeout"));
+        assert_eq!(abandoned.get_result_notation(), "0-1");
+        let thinking = state.storage.find_session_by_id(&session_ids[2]);
+        assert!(!thinking.await.unwrap().unwrap().is_finished());
+
+        let (_, notifications) = send(&state, Method::GET, "/notifications", &lime).await;
+        assert_eq!(notifications["notifications"][0]["kind"], "GAME_FINISHED");
+        let info = state.scheduler.get_info();
+        let task = info
+            .tasks
+            .iter()
+            .find(|task| task.name == "forfeit_abandoned_games")
+            .unwrap();
+        assert_eq!((task.runs, task.last_affected), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_ai_retry() {
+        use crate::models::{enums::AiDifficulty, session_models::AiError};
+
+        let state = test_state();
+        let lemon = create_user(&state, "lemon").await;
+        let admin = create_user(&state, "admin").await;
+        set_permission(&state, &admin, PermissionLevel::Admin).await;
+
+        // The AI failed to play white's first move, once with a retry due and once giving up
+        let mut session_ids = Vec::new();
+        for retry_stamp in [Some(0), None] {
+            let mut session = create_session(&state, ["AI".to_string(), lemon.clone()]).await;
+            session.ai_difficulty = AiDifficulty::BEGINNER;
+            session.ai_error = Some(AiError {
+                message: "Engine crashed".to_string(),
+                attempts: 1,
+                failed_stamp: 0,
+                retry_stamp,
+            });
+            session.save(&state.storage, &state.tasks).await.unwrap();
+            session_ids.push(session.id.unwrap().to_hex());
+        }
+
+        run_once(&state, ScheduledTask::RetryAiMoves).await;
+        let headers = [("session-id", session_ids[0].as_str())];
+        let (_, info) = send_with_headers(&state, Method::GET, "/session", &lemon, &headers).await;
+        assert_eq!(info["ai_error"], Value::Null);
+        assert_eq!(info["your_turn"], true);
+
+        // Sessions the AI gave up on aren't caught up on their own
+        let headers = [("session-id", session_ids[1].as_str())];
+        let (_, info) = send_with_headers(&state, Method::GET, "/session", &lemon, &headers).await;
+        assert_eq!(info["ai_error"]["message"], "Engine crashed");
+        assert_eq!(info["ai_error"]["retry_stamp"], Value::Null);
+        assert_eq!(info["your_turn"], false);
+
+        let (status, _) = send(&state, Method::POST, "/admin/ai/retry", &lemon).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, report) = send(&state, Method::POST, "/admin/ai/retry", &admin).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["retried"], 1);
+        assert_eq!(report["recovered"], 1);
+        let (_, info) = send_with_headers(&state, Method::GET, "/session", &lemon, &headers).await;
+        assert_eq!(info["ai_error"], Value::Null);
+        assert_eq!(info["your_turn"], true);
+    }
+}
@@ -0,0 +1,91 @@
+use std::{panic, path::Path, time::Duration, time::Instant};
+
+use crate::{
+    game::{
+        render::{self, get_required_assets},
+        review,
+        state::GameState,
+    },
+    utils::{sanitize::SanitizePolicy, signing},
+    AppState,
+};
+
+/// A storage backend which doesn't answer by then counts as unreachable
+const STORAGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Verifies everything requests depend on and does the lazy work up front
+/// Returns the problems found, a broken deploy should fail on boot instead of answering with 500s
+pub async fn run(state: &AppState) -> Vec<String> {
+    let start = Instant::now();
+    let mut problems = Vec::new();
+
+    match check_assets() {
+        Ok(count) => println!("Preloaded {} sprites", count),
+        Err(problem) => problems.push(problem),
+    }
+    if let Err(problem) = check_engine() {
+        problems.push(problem);
+    }
+    if let Err(problem) = check_storage(state).await {
+        problems.push(problem);
+    }
+    problems.extend(check_config());
+
+    println!("Self-check finished in {:?}", start.elapsed());
+    problems
+}
+
+/// Also decodes every sprite, so corrupt files are found as well
+fn check_assets() -> Result<usize, String> {
+    let missing: Vec<String> = get_required_assets()
+        .into_iter()
+        .filter(|asset| !Path::new(asset).is_file())
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("Missing render assets: {}", missing.join(", ")));
+    }
+    render::preload_sprites().map_err(|error| format!("Failed to load render assets: {}", error))
+}
+
+/// Runs move generation and a tiny engine search, pleco builds its lookup tables on first use
+fn check_engine() -> Result<(), String> {
+    GameState::new()
+        .and_then(|game_state| review::evaluate(&game_state))
+        .map(|_| ())
+        .map_err(|error| format!("Failed to run the engine: {}", error))
+}
+
+/// The MongoDB driver only connects once the first operation runs
+async fn check_storage(state: &AppState) -> Result<(), String> {
+    match tokio::time::timeout(STORAGE_TIMEOUT, state.storage.ping()).await {
+        Ok(result) => result.map_err(|error| format!("Failed to reach the storage: {}", error)),
+        Err(_) => Err(format!(
+            "The storage didn't respond within {} seconds",
+            STORAGE_TIMEOUT.as_secs()
+        )),
+    }
+}
+
+/// Settings which would otherwise only be read by the first request needing them
+fn check_config() -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Err(error) = signing::check_secret() {
+        problems.push(error.to_string());
+    }
+    // The policies panic with a message naming the invalid variable
+    if panic::catch_unwind(SanitizePolicy::load_all).is_err() {
+        problems.push("Invalid sanitize policy configuration".to_string());
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_dependencies() {
+        assert!(check_assets().unwrap() > 0);
+        assert!(check_engine().is_ok());
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    env,
+    fs::File,
+    io::{self, BufReader},
+    net::SocketAddr,
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::{header, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::{conn::auto, graceful::GracefulShutdown},
+    service::TowerToHyperService,
+};
+use rustls::{Certificate, PrivateKey};
+use rustls_pemfile::Item;
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tokio_util::sync::CancellationToken;
+use tower_http::add_extension::AddExtension;
+
+const DEFAULT_PORT: u16 = 3000;
+
+/// Open connections get this long to finish after shutdown was requested
+const TLS_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Clients get this long to complete the TLS handshake, stalled ones would hold their task forever
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where and how the API is served, PORT defaults to 3000
+pub struct ServerConfig {
+    pub port: u16,
+    /// Without it the API is served over plain HTTP, e.g. behind a reverse proxy
+    pub tls: Option<TlsConfig>,
+    /// GRPC_PORT, the gRPC API is only served if it is set
+    pub grpc_port: Option<u16>,
+}
+
+/// Set through TLS_CERT_PATH and TLS_KEY_PATH, both PEM files
+pub struct TlsConfig {
+    /// Certificate chain, leaf certificate first
+    pub cert_path: String,
+    pub key_path: String,
+    /// HTTP_REDIRECT_PORT, plain HTTP requests to it are redirected to HTTPS
+    pub redirect_port: Option<u16>,
+}
+
+impl ServerConfig {
+    pub fn from_env() -> Self {
+        let tls = match (env::var("TLS_CERT_PATH"), env::var("TLS_KEY_PATH")) {
+            (Ok(cert_path), Ok(key_path)) => Some(TlsConfig {
+                cert_path,
+                key_path,
+                redirect_port: parse_port("HTTP_REDIRECT_PORT"),
+            }),
+            (Err(_), Err(_)) => None,
+            _ => panic!("TLS_CERT_PATH and TLS_KEY_PATH have to be set together."),
+        };
+        Self {
+            port: parse_port("PORT").unwrap_or(DEFAULT_PORT),
+            tls,
+            grpc_port: parse_port("GRPC_PORT"),
+        }
+    }
+}
+
+fn parse_port(name: &str) -> Option<u16> {
+    env::var(name).ok().map(|port| {
+        port.parse()
+            .unwrap_or_else(|_| panic!("{} has to be a port number.", name))
+    })
+}
+
+/// Serves the app until shutdown is requested, then waits for the open requests
+pub async fn serve(
+    app: Router,
+    config: &ServerConfig,
+    shutdown: CancellationToken,
+) -> io::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", config.port)).await?;
+    let Some(tls) = &config.tls else {
+        println!("Listening on http://{}", listener.local_addr()?);
+        return axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await;
+    };
+
+    let acceptor = TlsAcceptor::from(Arc::new(load_tls_config(tls)?));
+    println!("Listening on https://{}", listener.local_addr()?);
+    let redirect = match tls.redirect_port {
+        Some(redirect_port) => {
+            let listener = TcpListener::bind(("0.0.0.0", redirect_port)).await?;
+            println!("Redirecting http://{} to HTTPS", listener.local_addr()?);
+            Some(tokio::spawn(serve_redirect(
+                listener,
+                config.port,
+                shutdown.clone(),
+            )))
+        }
+        None => None,
+    };
+
+    serve_tls(listener, acceptor, app, shutdown).await;
+    if let Some(redirect) = redirect {
+        redirect.await.map_err(io::Error::other)??;
+    }
+    Ok(())
+}
+
+fn load_tls_config(tls: &TlsConfig) -> io::Result<rustls::ServerConfig> {
+    let open = |path: &str| {
+        File::open(path)
+            .map(BufReader::new)
+            .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", path, error)))
+    };
+
+    let certs: Vec<Certificate> = rustls_pemfile::certs(&mut open(&tls.cert_path)?)?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+    if certs.is_empty() {
+        return Err(io::Error::other(format!(
+            "{} contains no certificate",
+            tls.cert_path
+        )));
+    }
+    let key = rustls_pemfile::read_all(&mut open(&tls.key_path)?)?
+        .into_iter()
+        .find_map(|item| match item {
+            Item::PKCS8Key(key) | Item::RSAKey(key) | Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| io::Error::other(format!("{} contains no private key", tls.key_path)))?;
+
+    let mut config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(io::Error::other)?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+async fn serve_tls(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    app: Router,
+    shutdown: CancellationToken,
+) {
+    let graceful = GracefulShutdown::new();
+    loop {
+        let (stream, address) = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(error) => {
+                    tracing::warn!(%error, "Failed to accept a connection");
+                    continue;
+                }
+            },
+        };
+
+        let acceptor = acceptor.clone();
+        // Same as into_make_service_with_connect_info, the rate limiter needs the address
+        let service =
+            TowerToHyperService::new(AddExtension::new(app.clone(), ConnectInfo(address)));
+        let watcher = graceful.watcher();
+        tokio::spawn(async move {
+            // Failed handshakes are common with scanners and outdated clients, they aren't logged
+            let handshake = tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream));
+            let Ok(Ok(stream)) = handshake.await else {
+                return;
+            };
+            let builder = auto::Builder::new(TokioExecutor::new());
+            let connection = builder.serve_connection(TokioIo::new(stream), service);
+            let _ = watcher.watch(connection.into_owned()).await;
+        });
+    }
+
+    if tokio::time::timeout(TLS_SHUTDOWN_TIMEOUT, graceful.shutdown())
+        .await
+        .is_err()
+    {
+        println!("Closed connections which didn't finish in time");
+    }
+}
+
+async fn serve_redirect(
+    listener: TcpListener,
+    https_port: u16,
+    shutdown: CancellationToken,
+) -> io::Result<()> {
+    let app = Router::new()
+        .fallback(move |request: Request| async move { redirect_to_https(&request, https_port) });
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await
+}
+
+/// Keeps host, path and query, only the port is replaced unless it is the default HTTPS port
+fn redirect_to_https(request: &Request, https_port: u16) -> Response {
+    let Some(host) = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .and_then(|host| host.parse::<Uri>().ok())
+        .and_then(|host| host.host().map(str::to_string))
+    else {
+        return (axum::http::StatusCode::BAD_REQUEST, "Missing host header").into_response();
+    };
+    let authority = match https_port {
+        443 => host,
+        port => format!("{}:{}", host, port),
+    };
+    let path = request
+        .uri()
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/");
+    Redirect::permanent(&format!("https://{}{}", authority, path)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    fn redirect_location(host: &str, uri: &str, https_port: u16) -> String {
+        let request = Request::builder()
+            .uri(uri)
+            .header(header::HOST, host)
+            .body(Body::empty())
+            .unwrap();
+        let response = redirect_to_https(&request, https_port);
+        response.headers()[header::LOCATION]
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_redirect_to_https() {
+        assert_eq!(
+            redirect_location("chess.example:80", "/session?id=1", 443),
+            "https://chess.example/session?id=1"
+        );
+        assert_eq!(
+            redirect_location("chess.example", "/", 8443),
+            "https://chess.example:8443/"
+        );
+    }
+}
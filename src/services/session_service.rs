@@ -0,0 +1,185 @@
+use crate::{
+    entities::{
+        notification::notify_opponent,
+        session::{find_session_or_archived_by_id, get_lock_key, Session},
+        user::User,
+        webhook::deliver_game_finished,
+    },
+    error::ApiError,
+    game::state::GameState,
+    locks::Lock,
+    models::{
+        enums::AiDifficulty,
+        move_models::{LegalMoves, MoveQuery},
+        session_models::SessionInfo,
+    },
+    utils::time_operations::timestamp_now_nanos,
+    AppState,
+};
+use mongodb::bson::oid::ObjectId;
+
+/// Archived sessions are finished, they can still be viewed but never changed
+pub async fn find_session(state: &AppState, session_id: &str) -> Result<Session, ApiError> {
+    find_session_or_archived_by_id(&*state.storage, session_id)
+        .await?
+        .ok_or(ApiError::NotFound("Session not found".to_string()))
+}
+
+/// The session together with its lock, for operations which change and save it
+pub async fn lock_session(state: &AppState, session_id: &str) -> Result<(Session, Lock), ApiError> {
+    // The session has to be loaded after locking, otherwise it could be outdated already
+    let lock = state.locks.acquire(&get_lock_key(session_id)).await?;
+    let session = state
+        .storage
+        .find_session_by_id(session_id)
+        .await?
+        .ok_or(ApiError::NotFound("Session not found".to_string()))?;
+    Ok((session, lock))
+}
+
+/// Plays the AI move if possible, previous errors could have lead to AI not playing
+/// Moves which failed before are only retried once their retry is due
+pub async fn catch_up_ai_move(state: &AppState, session: Session) -> Result<Session, ApiError> {
+    let (session, _) = run_ai_move(state, session, false).await?;
+    Ok(session)
+}
+
+/// Retries the failed AI move of the session, forced retries ignore the retry delay and the attempt limit
+/// Returns None if the AI didn't have to move, otherwise if it moved this time
+pub async fn retry_ai_move(
+    state: &AppState,
+    session_id: &str,
+    force: bool,
+) -> Result<Option<bool>, ApiError> {
+    let session = state
+        .storage
+        .find_session_by_id(session_id)
+        .await?
+        .ok_or(ApiError::NotFound("Session not found".to_string()))?;
+    let (_, moved) = run_ai_move(state, session, force).await?;
+    Ok(moved)
+}
+
+/// Searches the AI move on a blocking thread without holding the session lock, since it can take longer than the lock lives
+/// The move is only saved if nobody moved in the meantime, failures are saved as well
+/// Returns the session as it is afterwards and None if the AI didn't have to move, otherwise if it moved
+async fn run_ai_move(
+    state: &AppState,
+    session: Session,
+    force: bool,
+) -> Result<(Session, Option<bool>), ApiError> {
+    let is_due = |session: &Session| match force {
+        true => session.needs_ai_move(),
+        false => session.is_ai_move_due(timestamp_now_nanos()),
+    };
+    if !is_due(&session) {
+        return Ok((session, None));
+    }
+
+    let session_id = session.id.map(|id| id.to_hex()).unwrap_or_default();
+    let move_count = session.game_state.move_log.len();
+    let rng = state.rng.clone();
+    let next_move = tokio::task::spawn_blocking(move || session.find_ai_move(&rng))
+        .await
+        .map_err(|err| ApiError::ServerError(err.to_string()))?;
+
+    let (mut session, lock) = lock_session(state, &session_id).await?;
+    // Another request or replica could have played the move during the search
+    if session.game_state.move_log.len() != move_count || !is_due(&session) {
+        lock.release().await;
+        return Ok((session, None));
+    }
+    let moved = session.apply_ai_move(next_move);
+    session.save(&state.storage, &state.tasks).await?;
+    lock.release().await;
+
+    session.ponder_ai_move(&state.rng);
+    if moved && session.is_finished() {
+        state
+            .tasks
+            .spawn(deliver_game_finished(state.clone(), session.clone()));
+    }
+    Ok((session, Some(moved)))
+}
+
+/// Lets the AI reply right away, a failure doesn't undo the move before and is caught up on later
+async fn reply_as_ai(state: &AppState, session: Session) -> Session {
+    match catch_up_ai_move(state, session.clone()).await {
+        Ok(session) => session,
+        Err(error) => {
+            let session_id = session.id.map(|id| id.to_hex()).unwrap_or_default();
+            tracing::warn!(session_id, %error, "Failed to run the AI move");
+            session
+        }
+    }
+}
+
+pub async fn start_ai_session(
+    state: &AppState,
+    user: &User,
+    difficulty: AiDifficulty,
+) -> Result<Session, ApiError> {
+    // Parallel requests must not start more than one AI session
+    let lock = state
+        .locks
+        .acquire(&format!("ai_session:{}", user.key))
+        .await?;
+    let session = state
+        .storage
+        .find_active_session_by_keys(&[user.key.clone(), "AI".to_string()])
+        .await?;
+
+    if session.is_some() {
+        return Err(ApiError::BadRequest(
+            "You already have an active AI session.".to_string(),
+        ));
+    }
+
+    let game_state = GameState::new()?;
+    let mut new_session = Session::new_ai(
+        "AI Game".to_string(),
+        user.key.clone(),
+        game_state,
+        &state.rng,
+    );
+    // Assigned up front instead of by the storage, so the new session can be returned
+    new_session.id = Some(ObjectId::new());
+    new_session.ai_difficulty = difficulty;
+    new_session.save(&state.storage, &state.tasks).await?;
+    lock.release().await;
+    // Does the AI move if the AI goes first
+    Ok(reply_as_ai(state, new_session).await)
+}
+
+/// The legal moves of the color the user plays
+pub fn get_legal_moves(session: &Session, user: &User) -> Result<LegalMoves, ApiError> {
+    let color = session
+        .get_color_from_key(&user.key)
+        .ok_or(ApiError::BadRequest(
+            "You're not part of this session.".to_string(),
+        ))?;
+    session.get_legal_moves(color)
+}
+
+/// Plays the move of the user in the locked session and lets the opponent know about it
+pub async fn play_move(
+    state: &AppState,
+    user: User,
+    mut session: Session,
+    lock: Lock,
+    chess_move: &MoveQuery,
+) -> Result<SessionInfo, ApiError> {
+    let blunder_warning = session.do_move(&user.key, chess_move)?;
+    session.save(&state.storage, &state.tasks).await?;
+    lock.release().await;
+    notify_opponent(&*state.storage, &session, &user.key, &user.name).await;
+    if session.is_finished() {
+        state
+            .tasks
+            .spawn(deliver_game_finished(state.clone(), session.clone()));
+    }
+    let session = reply_as_ai(state, session).await;
+    let mut info = SessionInfo::from_session(state, session, user.key).await?;
+    info.blunder_warning = blunder_warning;
+    Ok(info)
+}
@@ -0,0 +1,60 @@
+use crate::{entities::user::User, error::ApiError, AppState};
+
+/// The user a primary or secondary API key belongs to
+pub async fn find_user_by_api_key(
+    state: &AppState,
+    api_key: &str,
+) -> Result<Option<User>, ApiError> {
+    match state.storage.find_user_by_key(api_key).await? {
+        Some(user) => Ok(Some(user)),
+        None => state.storage.find_user_by_secondary_key(api_key).await,
+    }
+}
+
+/// Looks up the user of an API key and records the use of the endpoint
+/// Keys with a scope are only accepted for the endpoints their scope allows, `method` and `path` are the ones of the HTTP route
+pub async fn authenticate(
+    state: &AppState,
+    api_key: &str,
+    method: &str,
+    path: &str,
+) -> Result<User, ApiError> {
+    let user = find_user_by_api_key(state, api_key).await?;
+    authorize(state, user, api_key, method, path).await
+}
+
+/// Like authenticate, for a user the rate limiter already looked up with find_user_by_api_key
+pub async fn authorize(
+    state: &AppState,
+    user: Option<User>,
+    api_key: &str,
+    method: &str,
+    path: &str,
+) -> Result<User, ApiError> {
+    let mut user = user.ok_or(ApiError::AuthorizationError(
+        "Invalid API key, check /docs for more information".to_string(),
+    ))?;
+
+    if user.banned {
+        return Err(ApiError::NoPermission(
+            "This API key has been revoked".to_string(),
+        ));
+    }
+
+    if let Some(scope) = user.get_key_scope(api_key) {
+        if !scope.allows(method, path) {
+            return Err(ApiError::NoPermission(format!(
+                "This API key only has the {:?} scope",
+                scope
+            )));
+        }
+    }
+    // Only the usage is written, the user may be banned or deleted while the request runs
+    let endpoint = user.use_endpoint(method, path);
+    state
+        .storage
+        .record_user_usage(&user.key, &endpoint, user.last_access_stamp)
+        .await?;
+
+    Ok(user)
+}
@@ -0,0 +1,383 @@
+use axum::async_trait;
+use futures::{stream::BoxStream, StreamExt};
+use mongodb::bson::oid::ObjectId;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::{
+    entities::{
+        audit_entry::AuditEntry,
+        friendship::Friendship,
+        notification::Notification,
+        render_job::RenderJob,
+        room::Room,
+        session::Session,
+        session_mark::SessionMark,
+        user::{SecondaryKey, User},
+        webhook::Webhook,
+    },
+    error::ApiError,
+    game::review::PlayerAccuracy,
+    models::{
+        enums::{GameOutcome, PermissionLevel, Platform, SessionSort},
+        query_models::{AuditLogQuery, RoomFilterQuery},
+        user_models::{DailyActivity, Title},
+    },
+};
+
+pub mod memory;
+pub mod mongo;
+pub mod sqlite;
+
+/// How many saved sessions a slow watcher can fall behind before it skips some
+const BROADCAST_CAPACITY: usize = 256;
+
+/// Sessions as they are saved
+pub type SessionStream = BoxStream<'static, Result<Session, ApiError>>;
+
+/// Publishes saved sessions within this process, for backends without change streams
+#[derive(Clone)]
+pub struct SessionBroadcast {
+    sender: broadcast::Sender<Session>,
+}
+
+impl SessionBroadcast {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(BROADCAST_CAPACITY);
+        Self { sender }
+    }
+
+    pub fn publish(&self, session: &Session) {
+        // Fails if nobody is watching, which is fine
+        let _ = self.sender.send(session.clone());
+    }
+
+    pub fn watch(&self) -> SessionStream {
+        futures::stream::unfold(self.sender.subscribe(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(session) => return Some((Ok(session), receiver)),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+        .boxed()
+    }
+}
+
+impl Default for SessionBroadcast {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One page of items which were filtered in process, together with the total count
+fn paginate<T>(items: Vec<T>, offset: u64, limit: u64) -> (Vec<T>, u64) {
+    let total = items.len() as u64;
+    let page = items
+        .into_iter()
+        .skip(offset as usize)
+        .take(limit as usize)
+        .collect();
+    (page, total)
+}
+
+/// Which sessions of a user to list, everything is included by default
+#[derive(Clone, Default)]
+pub struct SessionFilter {
+    /// Also sessions which were moved to the archive
+    pub include_archived: bool,
+    pub finished: Option<bool>,
+    /// True for games against the AI only, false for games against people only
+    pub against_ai: Option<bool>,
+    /// How the game ended for the user, running games never match
+    pub outcome: Option<GameOutcome>,
+    pub opponent: Option<OpponentFilter>,
+    /// Range of creation timestamps, the end is exclusive
+    pub created: Option<(u64, u64)>,
+    /// Running sessions before archived ones in insertion order if there is none
+    pub sort: Option<SessionSort>,
+}
+
+/// The opponent of a session, a user or the player of an imported game
+#[derive(Clone)]
+pub struct OpponentFilter {
+    /// Key of the user with the name, None if there is no such user
+    pub key: Option<String>,
+    /// Compared with the opponent of imported games, ignoring case
+    pub name: String,
+}
+
+impl SessionFilter {
+    /// Checks everything except include_archived, for backends which can't query it
+    pub fn matches(&self, key: &str, session: &Session) -> bool {
+        let opponent_matches = |opponent: &OpponentFilter| {
+            opponent
+                .key
+                .as_ref()
+                .is_some_and(|opponent_key| session.keys.contains(opponent_key))
+                || session
+                    .imported
+                    .as_ref()
+                    .is_some_and(|imported| imported.opponent.eq_ignore_ascii_case(&opponent.name))
+        };
+
+        session.keys.iter().any(|own| own == key)
+            && self
+                .finished
+                .is_none_or(|finished| session.is_finished() == finished)
+            && self
+                .against_ai
+                .is_none_or(|against_ai| session.keys.iter().any(|k| k == "AI") == against_ai)
+            && self
+                .outcome
+                .is_none_or(|outcome| session.get_outcome(key) == Some(outcome))
+            && self.opponent.as_ref().is_none_or(opponent_matches)
+            && self
+                .created
+                .is_none_or(|(from, to)| (from..to).contains(&session.created_stamp))
+    }
+
+    /// Sorts sessions which are already in the default order
+    pub fn sort(&self, sessions: &mut [Session]) {
+        match self.sort {
+            None => {}
+            Some(SessionSort::Newest) => {
+                sessions.sort_by_key(|session| std::cmp::Reverse(session.created_stamp))
+            }
+            Some(SessionSort::Oldest) => sessions.sort_by_key(|session| session.created_stamp),
+            Some(SessionSort::RecentActivity) => {
+                sessions.sort_by_key(|session| std::cmp::Reverse(session.get_activity_stamp()))
+            }
+        }
+    }
+}
+
+/// Which rooms to list
+pub enum RoomSelection<'a> {
+    /// Rooms created by the given user
+    Owner(&'a str),
+    /// Rooms reserved for the given user
+    Invited(&'a str),
+    /// Public rooms of the given namespace, of all namespaces if there is none
+    Public {
+        namespace: Option<&'a str>,
+        filter: &'a RoomFilterQuery,
+    },
+}
+
+/// Persistence of all entities, the backend is chosen at startup (see database::setup).
+/// Lists are returned together with the total amount of matching entities for pagination.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn ping(&self) -> Result<(), ApiError>;
+
+    /// Brings data written by older versions up to date, runs in the background after startup
+    async fn migrate(&self) -> Result<(), ApiError> {
+        Ok(())
+    }
+
+    async fn find_user_by_key(&self, key: &str) -> Result<Option<User>, ApiError>;
+    /// The user a secondary key belongs to
+    async fn find_user_by_secondary_key(&self, key: &str) -> Result<Option<User>, ApiError>;
+    async fn find_user_by_name(&self, name: &str) -> Result<Option<User>, ApiError>;
+    /// Keys without a user are skipped, in no particular order
+    async fn find_users_by_keys(&self, keys: &[String]) -> Result<Vec<User>, ApiError>;
+    async fn find_user_by_platform_id(
+        &self,
+        namespace: &str,
+        platform: Platform,
+        id: &str,
+    ) -> Result<Option<User>, ApiError>;
+    /// Stores a newly registered user
+    async fn insert_user(&self, user: &User) -> Result<(), ApiError>;
+    /// Overwrites the whole stored user, users which don't exist (anymore) aren't created
+    async fn save_user(&self, user: &User) -> Result<(), ApiError>;
+    /// Changes the given names only, returns the updated user, None if there is no such user
+    async fn rename_user(
+        &self,
+        key: &str,
+        name: Option<&str>,
+        display_name: Option<&str>,
+    ) -> Result<Option<User>, ApiError>;
+    /// Returns the updated user, None if there is no such user
+    async fn set_user_permission(
+        &self,
+        key: &str,
+        permission: PermissionLevel,
+    ) -> Result<Option<User>, ApiError>;
+    /// Returns the updated user, None if there is no such user
+    async fn set_user_banned(&self, key: &str, banned: bool) -> Result<Option<User>, ApiError>;
+    /// Adds the title to the user, nothing else of the user is written
+    /// Fails if the user reached the title limit or holds the title already, None if there is no such user
+    async fn push_user_title(&self, key: &str, title: &Title) -> Result<Option<User>, ApiError>;
+    /// Removes the title with the given name or all titles if there is none, nothing else of the user is written
+    /// Returns the updated user and how many titles were removed, None if there is no such user
+    async fn pull_user_titles(
+        &self,
+        key: &str,
+        name: Option<&str>,
+    ) -> Result<Option<(User, usize)>, ApiError>;
+    /// Counts a request to the endpoint and sets the last access, nothing else of the user is written
+    /// Unknown users aren't created
+    async fn record_user_usage(
+        &self,
+        key: &str,
+        endpoint: &str,
+        access_stamp: u64,
+    ) -> Result<(), ApiError>;
+    /// Adds the secondary key to the user, nothing else of the user is written
+    /// Fails if the user reached the key limit or has a key with the same name, false if there is no such user
+    async fn push_secondary_key(
+        &self,
+        key: &str,
+        secondary_key: &SecondaryKey,
+    ) -> Result<bool, ApiError>;
+    /// Removes the secondary key with the given name from the user, nothing else of the user is written
+    /// Returns the removed key, None if the user has no such key
+    async fn remove_secondary_key(
+        &self,
+        key: &str,
+        name: &str,
+    ) -> Result<Option<SecondaryKey>, ApiError>;
+    /// Oldest users first, users of all namespaces if there is none
+    async fn find_users(
+        &self,
+        namespace: Option<&str>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<User>, u64), ApiError>;
+    async fn delete_user_by_key(&self, key: &str) -> Result<(), ApiError>;
+
+    /// Archived sessions aren't included
+    async fn find_session_by_id(&self, id: &str) -> Result<Option<Session>, ApiError>;
+    async fn find_archived_session_by_id(&self, id: &str) -> Result<Option<Session>, ApiError>;
+    /// Running and archived sessions, unknown ids are skipped, in no particular order
+    async fn find_sessions_by_ids(&self, ids: &[String]) -> Result<Vec<Session>, ApiError>;
+    /// A running session between exactly the given players
+    async fn find_active_session_by_keys(
+        &self,
+        keys: &[String; 2],
+    ) -> Result<Option<Session>, ApiError>;
+    async fn find_sessions_by_key_and_finished(
+        &self,
+        key: &str,
+        finished: bool,
+    ) -> Result<Vec<Session>, ApiError>;
+    async fn find_sessions_by_key(
+        &self,
+        key: &str,
+        filter: &SessionFilter,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<Session>, u64), ApiError>;
+    /// Inserts the session if it has no id yet
+    async fn save_session(&self, session: &Session) -> Result<(), ApiError>;
+    /// Every session saved from now on, including those saved by other instances if the backend supports it
+    async fn watch_sessions(&self) -> Result<SessionStream, ApiError>;
+    /// Finished games of the user created in the given range of timestamps per UTC day, archived ones included
+    async fn find_daily_activity(
+        &self,
+        key: &str,
+        from_stamp: u64,
+        to_stamp: u64,
+    ) -> Result<Vec<DailyActivity>, ApiError>;
+    /// Stores the accuracy of both players unless the session has it already, nothing else of the session is written
+    async fn set_session_accuracy(
+        &self,
+        id: &str,
+        accuracy: &[PlayerAccuracy; 2],
+    ) -> Result<(), ApiError>;
+    /// Moves finished sessions created before the given timestamp into the archive, returns how many were moved
+    async fn archive_finished_sessions(&self, created_before: u64) -> Result<u64, ApiError>;
+    /// Running sessions with a time control which aren't paused
+    async fn find_running_timed_sessions(&self) -> Result<Vec<Session>, ApiError>;
+    /// Running sessions in which the AI failed to move, see Session::apply_ai_move
+    async fn find_failed_ai_sessions(&self) -> Result<Vec<Session>, ApiError>;
+
+    /// Returns how many rooms were deleted, expired rooms are also dropped whenever rooms are read
+    async fn delete_expired_rooms(&self) -> Result<u64, ApiError>;
+    /// Expired rooms are never returned
+    async fn find_room_by_code(&self, code: &str) -> Result<Option<Room>, ApiError>;
+    async fn find_rooms_by_key(&self, key: &str) -> Result<Vec<Room>, ApiError>;
+    async fn find_rooms(
+        &self,
+        selection: RoomSelection<'_>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<Room>, u64), ApiError>;
+    async fn save_room(&self, room: &Room) -> Result<(), ApiError>;
+    async fn delete_room_by_code(&self, code: &str) -> Result<(), ApiError>;
+    /// Deletes the room and inserts the session started from it, either both happen or neither
+    async fn start_room_session(&self, code: &str, session: &Session) -> Result<(), ApiError>;
+
+    /// Inserts the job and sets its id
+    async fn insert_render_job(&self, job: &mut RenderJob) -> Result<(), ApiError>;
+    async fn find_render_job_by_id(&self, id: &str) -> Result<Option<RenderJob>, ApiError>;
+    /// Marks the oldest queued (or abandoned) job as running, every job is only taken by one worker
+    async fn claim_next_render_job(&self) -> Result<Option<RenderJob>, ApiError>;
+    async fn complete_render_job(
+        &self,
+        job: &RenderJob,
+        result_id: ObjectId,
+    ) -> Result<(), ApiError>;
+    async fn fail_render_job(&self, job: &RenderJob, error: &str) -> Result<(), ApiError>;
+    /// Deletes expired jobs together with their results, returns the amount of deleted jobs
+    async fn delete_expired_render_jobs(&self) -> Result<u64, ApiError>;
+    /// Stores a rendered file, returns its id
+    async fn store_render_result(&self, name: &str, bytes: Vec<u8>) -> Result<ObjectId, ApiError>;
+    async fn load_render_result(&self, id: ObjectId) -> Result<Vec<u8>, ApiError>;
+
+    /// The friendship or request between both users, no matter who sent it
+    async fn find_friendship(
+        &self,
+        first_key: &str,
+        second_key: &str,
+    ) -> Result<Option<Friendship>, ApiError>;
+    /// Friendships and requests the user sent or received
+    async fn find_friendships_by_key(&self, key: &str) -> Result<Vec<Friendship>, ApiError>;
+    /// Inserts the friendship if it has no id yet
+    async fn save_friendship(&self, friendship: &Friendship) -> Result<(), ApiError>;
+    async fn delete_friendship(&self, friendship: &Friendship) -> Result<(), ApiError>;
+
+    async fn insert_audit_entry(&self, entry: &AuditEntry) -> Result<(), ApiError>;
+    /// Newest entries first
+    async fn find_audit_entries(
+        &self,
+        query: &AuditLogQuery,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<AuditEntry>, u64), ApiError>;
+
+    async fn insert_notification(&self, notification: &Notification) -> Result<(), ApiError>;
+    /// Newest notifications of the user first
+    async fn find_notifications(
+        &self,
+        key: &str,
+        unread_only: bool,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<Notification>, u64), ApiError>;
+    async fn count_unread_notifications(&self, key: &str) -> Result<u64, ApiError>;
+    /// Marks the given notification of the user as read or all of them without an id, returns how many changed
+    async fn mark_notifications_read(&self, key: &str, id: Option<&str>) -> Result<u64, ApiError>;
+    async fn delete_notifications_by_key(&self, key: &str) -> Result<(), ApiError>;
+
+    async fn find_session_mark(
+        &self,
+        key: &str,
+        session_id: &str,
+    ) -> Result<Option<SessionMark>, ApiError>;
+    /// Most recently updated marks first
+    async fn find_session_marks_by_key(&self, key: &str) -> Result<Vec<SessionMark>, ApiError>;
+    /// Replaces the mark of the same user and session
+    async fn save_session_mark(&self, mark: &SessionMark) -> Result<(), ApiError>;
+    async fn delete_session_mark(&self, key: &str, session_id: &str) -> Result<(), ApiError>;
+    async fn delete_session_marks_by_key(&self, key: &str) -> Result<(), ApiError>;
+
+    async fn find_webhook_by_key(&self, key: &str) -> Result<Option<Webhook>, ApiError>;
+    async fn find_webhooks_by_namespace(&self, namespace: &str) -> Result<Vec<Webhook>, ApiError>;
+    /// Replaces the webhook of the same negotiator
+    async fn save_webhook(&self, webhook: &Webhook) -> Result<(), ApiError>;
+    async fn delete_webhook_by_key(&self, key: &str) -> Result<(), ApiError>;
+}
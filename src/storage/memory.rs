@@ -0,0 +1,847 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use axum::async_trait;
+use mongodb::bson::{oid::ObjectId, DateTime};
+
+use crate::{
+    entities::{
+        audit_entry::AuditEntry,
+        friendship::Friendship,
+        notification::Notification,
+        render_job::{RenderJob, EXPIRE_AFTER_NANOS, STALE_AFTER_NANOS},
+        room::Room,
+        session::Session,
+        session_mark::SessionMark,
+        user::{SecondaryKey, User},
+        webhook::Webhook,
+    },
+    error::ApiError,
+    game::review::PlayerAccuracy,
+    models::{
+        enums::{PermissionLevel, Platform, RoomSort},
+        query_models::AuditLogQuery,
+        render_job_models::RenderJobStatus,
+        user_models::{DailyActivity, Title},
+    },
+    utils::time_operations::timestamp_now_nanos,
+};
+
+use super::{paginate, RoomSelection, SessionBroadcast, SessionFilter, SessionStream, Storage};
+
+#[derive(Default)]
+struct MemoryData {
+    users: Vec<User>,
+    /// Sessions together with whether they are archived, in insertion order
+    sessions: Vec<(Session, bool)>,
+    rooms: Vec<Room>,
+    render_jobs: Vec<RenderJob>,
+    render_results: HashMap<ObjectId, Vec<u8>>,
+    audit_log: Vec<AuditEntry>,
+    friendships: Vec<Friendship>,
+    notifications: Vec<Notification>,
+    session_marks: Vec<SessionMark>,
+    webhooks: Vec<Webhook>,
+}
+
+impl MemoryData {
+    /// Expired rooms are removed before rooms are read, like the TTL index of MongoDB would
+    fn delete_expired_rooms(&mut self) -> u64 {
+        let now = DateTime::now();
+        let before = self.rooms.len();
+        self.rooms
+            .retain(|room| room.expires_at.is_none_or(|expires_at| expires_at >= now));
+        (before - self.rooms.len()) as u64
+    }
+
+    /// Returns the stored session, new sessions get an id
+    fn write_session(&mut self, session: &Session) -> Session {
+        let mut session = session.clone();
+        let id = *session.id.get_or_insert_with(ObjectId::new);
+        match self
+            .sessions
+            .iter_mut()
+            .find(|(stored, _)| stored.id == Some(id))
+        {
+            Some((stored, _)) => *stored = session.clone(),
+            None => self.sessions.push((session.clone(), false)),
+        }
+        session
+    }
+
+    fn find_render_job_mut(&mut self, job: &RenderJob) -> Option<&mut RenderJob> {
+        self.render_jobs
+            .iter_mut()
+            .find(|stored| stored.id.is_some() && stored.id == job.id)
+    }
+}
+
+/// Keeps everything in process memory, used by tests and for trying out the API without a database
+#[derive(Clone, Default)]
+pub struct MemoryStorage {
+    data: Arc<Mutex<MemoryData>>,
+    broadcast: SessionBroadcast,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn data(&self) -> Result<MutexGuard<'_, MemoryData>, ApiError> {
+        self.data
+            .lock()
+            .map_err(|_| ApiError::ServerError("Memory storage poisoned".to_string()))
+    }
+
+    /// Applies the change to the stored user, None if there is no such user
+    fn update_user<T>(
+        &self,
+        key: &str,
+        change: impl FnOnce(&mut User) -> Result<T, ApiError>,
+    ) -> Result<Option<(User, T)>, ApiError> {
+        let mut data = self.data()?;
+        let Some(user) = data.users.iter_mut().find(|user| user.key == key) else {
+            return Ok(None);
+        };
+        let result = change(user)?;
+        Ok(Some((user.clone(), result)))
+    }
+
+    fn find_session(&self, id: &str, archived: bool) -> Result<Option<Session>, ApiError> {
+        let id = ObjectId::parse_str(id)?;
+        Ok(self
+            .data()?
+            .sessions
+            .iter()
+            .find(|(session, is_archived)| session.id == Some(id) && *is_archived == archived)
+            .map(|(session, _)| session.clone()))
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn ping(&self) -> Result<(), ApiError> {
+        self.data().map(|_| ())
+    }
+
+    async fn find_user_by_key(&self, key: &str) -> Result<Option<User>, ApiError> {
+        Ok(self
+            .data()?
+            .users
+            .iter()
+            .find(|user| user.key == key)
+            .cloned())
+    }
+
+    async fn find_users_by_keys(&self, keys: &[String]) -> Result<Vec<User>, ApiError> {
+        Ok(self
+            .data()?
+            .users
+            .iter()
+            .filter(|user| keys.contains(&user.key))
+            .cloned()
+            .collect())
+    }
+
+    async fn find_user_by_secondary_key(&self, key: &str) -> Result<Option<User>, ApiError> {
+        Ok(self
+            .data()?
+            .users
+            .iter()
+            .find(|user| user.get_key_scope(key).is_some())
+            .cloned())
+    }
+
+    async fn find_user_by_name(&self, name: &str) -> Result<Option<User>, ApiError> {
+        let name = name.to_lowercase();
+        Ok(self
+            .data()?
+            .users
+            .iter()
+            .find(|user| user.name == name)
+            .cloned())
+    }
+
+    async fn find_user_by_platform_id(
+        &self,
+        namespace: &str,
+        platform: Platform,
+        id: &str,
+    ) -> Result<Option<User>, ApiError> {
+        Ok(self
+            .data()?
+            .users
+            .iter()
+            .find(|user| {
+                user.namespace == namespace
+                    && user.platform_links.get(&platform).map(String::as_str) == Some(id)
+            })
+            .cloned())
+    }
+
+    async fn insert_user(&self, user: &User) -> Result<(), ApiError> {
+        let mut data = self.data()?;
+        if data.users.iter().any(|stored| stored.key == user.key) {
+            return Err(ApiError::DatabaseError("Duplicate user key".to_string()));
+        }
+        data.users.push(user.clone());
+        Ok(())
+    }
+
+    async fn save_user(&self, user: &User) -> Result<(), ApiError> {
+        self.update_user(&user.key, |stored| {
+            *stored = user.clone();
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    async fn rename_user(
+        &self,
+        key: &str,
+        name: Option<&str>,
+        display_name: Option<&str>,
+    ) -> Result<Option<User>, ApiError> {
+        let updated = self.update_user(key, |user| {
+            user.rename(name, display_name);
+            Ok(())
+        })?;
+        Ok(updated.map(|(user, _)| user))
+    }
+
+    async fn set_user_permission(
+        &self,
+        key: &str,
+        permission: PermissionLevel,
+    ) -> Result<Option<User>, ApiError> {
+        let updated = self.update_user(key, |user| {
+            user.permission = permission;
+            Ok(())
+        })?;
+        Ok(updated.map(|(user, _)| user))
+    }
+
+    async fn set_user_banned(&self, key: &str, banned: bool) -> Result<Option<User>, ApiError> {
+        let updated = self.update_user(key, |user| {
+            user.banned = banned;
+            Ok(())
+        })?;
+        Ok(updated.map(|(user, _)| user))
+    }
+
+    async fn push_user_title(&self, key: &str, title: &Title) -> Result<Option<User>, ApiError> {
+        let updated = self.update_user(key, |user| user.award_title(title.clone()))?;
+        Ok(updated.map(|(user, _)| user))
+    }
+
+    async fn pull_user_titles(
+        &self,
+        key: &str,
+        name: Option<&str>,
+    ) -> Result<Option<(User, usize)>, ApiError> {
+        self.update_user(key, |user| user.reset_titles(name))
+    }
+
+    async fn record_user_usage(
+        &self,
+        key: &str,
+        endpoint: &str,
+        access_stamp: u64,
+    ) -> Result<(), ApiError> {
+        if let Some(user) = self.data()?.users.iter_mut().find(|user| user.key == key) {
+            user.last_access_stamp = access_stamp;
+            *user.endpoint_usage.entry(endpoint.to_string()).or_insert(0) += 1;
+        }
+        Ok(())
+    }
+
+    async fn push_secondary_key(
+        &self,
+        key: &str,
+        secondary_key: &SecondaryKey,
+    ) -> Result<bool, ApiError> {
+        let updated =
+            self.update_user(key, |user| user.add_secondary_key(secondary_key.clone()))?;
+        Ok(updated.is_some())
+    }
+
+    async fn remove_secondary_key(
+        &self,
+        key: &str,
+        name: &str,
+    ) -> Result<Option<SecondaryKey>, ApiError> {
+        Ok(self
+            .data()?
+            .users
+            .iter_mut()
+            .find(|user| user.key == key)
+            .and_then(|user| user.remove_secondary_key(name).ok()))
+    }
+
+    async fn find_users(
+        &self,
+        namespace: Option<&str>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<User>, u64), ApiError> {
+        let users = self
+            .data()?
+            .users
+            .iter()
+            .filter(|user| namespace.is_none_or(|namespace| user.namespace == namespace))
+            .cloned()
+            .collect();
+        Ok(paginate(users, offset, limit))
+    }
+
+    async fn delete_user_by_key(&self, key: &str) -> Result<(), ApiError> {
+        self.data()?.users.retain(|user| user.key != key);
+        Ok(())
+    }
+
+    async fn find_session_by_id(&self, id: &str) -> Result<Option<Session>, ApiError> {
+        self.find_session(id, false)
+    }
+
+    async fn find_archived_session_by_id(&self, id: &str) -> Result<Option<Session>, ApiError> {
+        self.find_session(id, true)
+    }
+
+    async fn find_sessions_by_ids(&self, ids: &[String]) -> Result<Vec<Session>, ApiError> {
+        let ids = ids
+            .iter()
+            .map(|id| ObjectId::parse_str(id).map(Some))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self
+            .data()?
+            .sessions
+            .iter()
+            .filter(|(session, _)| ids.contains(&session.id))
+            .map(|(session, _)| session.clone())
+            .collect())
+    }
+
+    async fn find_active_session_by_keys(
+        &self,
+        keys: &[String; 2],
+    ) -> Result<Option<Session>, ApiError> {
+        let reversed = [keys[1].clone(), keys[0].clone()];
+        Ok(self
+            .data()?
+            .sessions
+            .iter()
+            .find(|(session, archived)| {
+                !archived
+                    && !session.is_finished()
+                    && (session.keys == *keys || session.keys == reversed)
+            })
+            .map(|(session, _)| session.clone()))
+    }
+
+    async fn find_sessions_by_key_and_finished(
+        &self,
+        key: &str,
+        finished: bool,
+    ) -> Result<Vec<Session>, ApiError> {
+        Ok(self
+            .data()?
+            .sessions
+            .iter()
+            .filter(|(session, archived)| {
+                !archived
+                    && session.keys.iter().any(|k| k == key)
+                    && session.is_finished() == finished
+            })
+            .map(|(session, _)| session.clone())
+            .collect())
+    }
+
+    async fn find_daily_activity(
+        &self,
+        key: &str,
+        from_stamp: u64,
+        to_stamp: u64,
+    ) -> Result<Vec<DailyActivity>, ApiError> {
+        let data = self.data()?;
+        let sessions = data.sessions.iter().filter_map(|(session, _)| {
+            (from_stamp..to_stamp)
+                .contains(&session.created_stamp)
+                .then_some(session)
+        });
+        Ok(DailyActivity::aggregate(key, sessions))
+    }
+
+    async fn find_sessions_by_key(
+        &self,
+        key: &str,
+        filter: &SessionFilter,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<Session>, u64), ApiError> {
+        let data = self.data()?;
+        let mut sessions: Vec<&(Session, bool)> = data
+            .sessions
+            .iter()
+            .filter(|(session, archived)| {
+                (filter.include_archived || !archived) && filter.matches(key, session)
+            })
+            .collect();
+        // Stable, so the insertion order is kept within both groups
+        sessions.sort_by_key(|(_, archived)| *archived);
+        let mut sessions: Vec<Session> = sessions
+            .into_iter()
+            .map(|(session, _)| session.clone())
+            .collect();
+        filter.sort(&mut sessions);
+        Ok(paginate(sessions, offset, limit))
+    }
+
+    async fn save_session(&self, session: &Session) -> Result<(), ApiError> {
+        let session = self.data()?.write_session(session);
+        self.broadcast.publish(&session);
+        Ok(())
+    }
+
+    async fn watch_sessions(&self) -> Result<SessionStream, ApiError> {
+        Ok(self.broadcast.watch())
+    }
+
+    async fn set_session_accuracy(
+        &self,
+        id: &str,
+        accuracy: &[PlayerAccuracy; 2],
+    ) -> Result<(), ApiError> {
+        let id = ObjectId::parse_str(id)?;
+        if let Some((session, _)) = self
+            .data()?
+            .sessions
+            .iter_mut()
+            .find(|(session, _)| session.id == Some(id) && session.accuracy.is_none())
+        {
+            session.accuracy = Some(*accuracy);
+        }
+        Ok(())
+    }
+
+    async fn archive_finished_sessions(&self, created_before: u64) -> Result<u64, ApiError> {
+        let mut count = 0;
+        for (session, archived) in self.data()?.sessions.iter_mut() {
+            if !*archived && session.is_finished() && session.created_stamp < created_before {
+                *archived = true;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    async fn find_running_timed_sessions(&self) -> Result<Vec<Session>, ApiError> {
+        Ok(self
+            .data()?
+            .sessions
+            .iter()
+            .filter(|(session, archived)| {
+                !archived
+                    && !session.is_finished()
+                    && !session.paused
+                    && session.time_control.is_some()
+            })
+            .map(|(session, _)| session.clone())
+            .collect())
+    }
+
+    async fn find_failed_ai_sessions(&self) -> Result<Vec<Session>, ApiError> {
+        Ok(self
+            .data()?
+            .sessions
+            .iter()
+            .filter(|(session, archived)| {
+                !archived && !session.is_finished() && session.ai_error.is_some()
+            })
+            .map(|(session, _)| session.clone())
+            .collect())
+    }
+
+    async fn delete_expired_rooms(&self) -> Result<u64, ApiError> {
+        Ok(self.data()?.delete_expired_rooms())
+    }
+
+    async fn find_room_by_code(&self, code: &str) -> Result<Option<Room>, ApiError> {
+        let code = code.to_uppercase();
+        let mut data = self.data()?;
+        data.delete_expired_rooms();
+        Ok(data.rooms.iter().find(|room| room.code == code).cloned())
+    }
+
+    async fn find_rooms_by_key(&self, key: &str) -> Result<Vec<Room>, ApiError> {
+        let mut data = self.data()?;
+        data.delete_expired_rooms();
+        Ok(data
+            .rooms
+            .iter()
+            .filter(|room| room.key == key)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_rooms(
+        &self,
+        selection: RoomSelection<'_>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<Room>, u64), ApiError> {
+        let mut data = self.data()?;
+        data.delete_expired_rooms();
+        let mut rooms: Vec<Room> = data
+            .rooms
+            .iter()
+            .filter(|room| match &selection {
+                RoomSelection::Owner(key) => room.key == *key,
+                RoomSelection::Invited(key) => room.invited_key.as_deref() == Some(*key),
+                RoomSelection::Public { namespace, filter } => {
+                    room.public
+                        && namespace.is_none_or(|namespace| room.namespace == namespace)
+                        && filter.search.as_ref().is_none_or(|search| {
+                            room.name.to_lowercase().contains(&search.to_lowercase())
+                        })
+                        && filter
+                            .created_after
+                            .is_none_or(|created_after| room.created_stamp > created_after)
+                }
+            })
+            .cloned()
+            .collect();
+        if let RoomSelection::Public { filter, .. } = &selection {
+            match filter.sort.unwrap_or_default() {
+                RoomSort::NEWEST => rooms.sort_by_key(|room| std::cmp::Reverse(room.created_stamp)),
+                RoomSort::OLDEST => rooms.sort_by_key(|room| room.created_stamp),
+            }
+        }
+        Ok(paginate(rooms, offset, limit))
+    }
+
+    async fn save_room(&self, room: &Room) -> Result<(), ApiError> {
+        let mut data = self.data()?;
+        match data
+            .rooms
+            .iter_mut()
+            .find(|stored| stored.code == room.code)
+        {
+            Some(stored) => *stored = room.clone(),
+            None => data.rooms.push(room.clone()),
+        }
+        Ok(())
+    }
+
+    async fn delete_room_by_code(&self, code: &str) -> Result<(), ApiError> {
+        self.data()?.rooms.retain(|room| room.code != code);
+        Ok(())
+    }
+
+    async fn start_room_session(&self, code: &str, session: &Session) -> Result<(), ApiError> {
+        // Both happen under the same lock, so nobody sees one without the other
+        let mut data = self.data()?;
+        data.rooms.retain(|room| room.code != code);
+        data.write_session(session);
+        Ok(())
+    }
+
+    async fn insert_render_job(&self, job: &mut RenderJob) -> Result<(), ApiError> {
+        job.id = Some(ObjectId::new());
+        self.data()?.render_jobs.push(job.clone());
+        Ok(())
+    }
+
+    async fn find_render_job_by_id(&self, id: &str) -> Result<Option<RenderJob>, ApiError> {
+        let id = ObjectId::parse_str(id)?;
+        Ok(self
+            .data()?
+            .render_jobs
+            .iter()
+            .find(|job| job.id == Some(id))
+            .cloned())
+    }
+
+    async fn claim_next_render_job(&self) -> Result<Option<RenderJob>, ApiError> {
+        let now = timestamp_now_nanos();
+        let stale_before = now.saturating_sub(STALE_AFTER_NANOS);
+        let mut data = self.data()?;
+        let job = data
+            .render_jobs
+            .iter_mut()
+            .filter(|job| match job.status {
+                RenderJobStatus::QUEUED => true,
+                RenderJobStatus::RUNNING => job
+                    .started_stamp
+                    .is_some_and(|started_stamp| started_stamp < stale_before),
+                _ => false,
+            })
+            .min_by_key(|job| job.created_stamp);
+        Ok(job.map(|job| {
+            job.status = RenderJobStatus::RUNNING;
+            job.started_stamp = Some(now);
+            job.clone()
+        }))
+    }
+
+    async fn complete_render_job(
+        &self,
+        job: &RenderJob,
+        result_id: ObjectId,
+    ) -> Result<(), ApiError> {
+        let mut data = self.data()?;
+        if let Some(stored) = data.find_render_job_mut(job) {
+            stored.status = RenderJobStatus::DONE;
+            stored.result_id = Some(result_id);
+        }
+        Ok(())
+    }
+
+    async fn fail_render_job(&self, job: &RenderJob, error: &str) -> Result<(), ApiError> {
+        let mut data = self.data()?;
+        if let Some(stored) = data.find_render_job_mut(job) {
+            stored.status = RenderJobStatus::FAILED;
+            stored.error = Some(error.to_string());
+        }
+        Ok(())
+    }
+
+    async fn delete_expired_render_jobs(&self) -> Result<u64, ApiError> {
+        let expired_before = timestamp_now_nanos().saturating_sub(EXPIRE_AFTER_NANOS);
+        let mut data = self.data()?;
+        let (expired, kept) = std::mem::take(&mut data.render_jobs)
+            .into_iter()
+            .partition::<Vec<_>, _>(|job| job.created_stamp < expired_before);
+        data.render_jobs = kept;
+        for result_id in expired.iter().filter_map(|job| job.result_id) {
+            data.render_results.remove(&result_id);
+        }
+        Ok(expired.len() as u64)
+    }
+
+    async fn store_render_result(&self, _name: &str, bytes: Vec<u8>) -> Result<ObjectId, ApiError> {
+        let id = ObjectId::new();
+        self.data()?.render_results.insert(id, bytes);
+        Ok(id)
+    }
+
+    async fn load_render_result(&self, id: ObjectId) -> Result<Vec<u8>, ApiError> {
+        self.data()?
+            .render_results
+            .get(&id)
+            .cloned()
+            .ok_or(ApiError::NotFound("Render result not found".to_string()))
+    }
+
+    async fn find_friendship(
+        &self,
+        first_key: &str,
+        second_key: &str,
+    ) -> Result<Option<Friendship>, ApiError> {
+        Ok(self
+            .data()?
+            .friendships
+            .iter()
+            .find(|friendship| {
+                (friendship.requester == first_key && friendship.addressee == second_key)
+                    || (friendship.requester == second_key && friendship.addressee == first_key)
+            })
+            .cloned())
+    }
+
+    async fn find_friendships_by_key(&self, key: &str) -> Result<Vec<Friendship>, ApiError> {
+        Ok(self
+            .data()?
+            .friendships
+            .iter()
+            .filter(|friendship| friendship.requester == key || friendship.addressee == key)
+            .cloned()
+            .collect())
+    }
+
+    async fn save_friendship(&self, friendship: &Friendship) -> Result<(), ApiError> {
+        let mut friendship = friendship.clone();
+        let id = *friendship.id.get_or_insert_with(ObjectId::new);
+        let mut data = self.data()?;
+        match data
+            .friendships
+            .iter_mut()
+            .find(|stored| stored.id == Some(id))
+        {
+            Some(stored) => *stored = friendship,
+            None => data.friendships.push(friendship),
+        }
+        Ok(())
+    }
+
+    async fn delete_friendship(&self, friendship: &Friendship) -> Result<(), ApiError> {
+        self.data()?
+            .friendships
+            .retain(|stored| stored.id != friendship.id);
+        Ok(())
+    }
+
+    async fn insert_audit_entry(&self, entry: &AuditEntry) -> Result<(), ApiError> {
+        let mut entry = entry.clone();
+        entry.id.get_or_insert_with(ObjectId::new);
+        self.data()?.audit_log.push(entry);
+        Ok(())
+    }
+
+    async fn find_audit_entries(
+        &self,
+        query: &AuditLogQuery,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<AuditEntry>, u64), ApiError> {
+        let user = query.user.as_ref().map(|user| user.to_lowercase());
+        let mut entries: Vec<AuditEntry> = self
+            .data()?
+            .audit_log
+            .iter()
+            .filter(|entry| query.action.is_none_or(|action| entry.action == action))
+            .filter(|entry| {
+                user.as_ref()
+                    .is_none_or(|user| entry.actor == *user || entry.target.as_ref() == Some(user))
+            })
+            .cloned()
+            .collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.created_stamp));
+        Ok(paginate(entries, offset, limit))
+    }
+
+    async fn insert_notification(&self, notification: &Notification) -> Result<(), ApiError> {
+        let mut notification = notification.clone();
+        notification.id.get_or_insert_with(ObjectId::new);
+        self.data()?.notifications.push(notification);
+        Ok(())
+    }
+
+    async fn find_notifications(
+        &self,
+        key: &str,
+        unread_only: bool,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<Notification>, u64), ApiError> {
+        let mut notifications: Vec<Notification> = self
+            .data()?
+            .notifications
+            .iter()
+            .filter(|notification| notification.key == key)
+            .filter(|notification| !unread_only || !notification.read)
+            .cloned()
+            .collect();
+        notifications.sort_by_key(|notification| std::cmp::Reverse(notification.created_stamp));
+        Ok(paginate(notifications, offset, limit))
+    }
+
+    async fn count_unread_notifications(&self, key: &str) -> Result<u64, ApiError> {
+        Ok(self
+            .data()?
+            .notifications
+            .iter()
+            .filter(|notification| notification.key == key && !notification.read)
+            .count() as u64)
+    }
+
+    async fn mark_notifications_read(&self, key: &str, id: Option<&str>) -> Result<u64, ApiError> {
+        let id = id.map(ObjectId::parse_str).transpose()?;
+        let mut count = 0;
+        for notification in self.data()?.notifications.iter_mut() {
+            if notification.key == key
+                && !notification.read
+                && id.is_none_or(|id| notification.id == Some(id))
+            {
+                notification.read = true;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    async fn delete_notifications_by_key(&self, key: &str) -> Result<(), ApiError> {
+        self.data()?
+            .notifications
+            .retain(|notification| notification.key != key);
+        Ok(())
+    }
+
+    async fn find_session_mark(
+        &self,
+        key: &str,
+        session_id: &str,
+    ) -> Result<Option<SessionMark>, ApiError> {
+        Ok(self
+            .data()?
+            .session_marks
+            .iter()
+            .find(|mark| mark.key == key && mark.session_id == session_id)
+            .cloned())
+    }
+
+    async fn find_session_marks_by_key(&self, key: &str) -> Result<Vec<SessionMark>, ApiError> {
+        let mut marks: Vec<SessionMark> = self
+            .data()?
+            .session_marks
+            .iter()
+            .filter(|mark| mark.key == key)
+            .cloned()
+            .collect();
+        marks.sort_by_key(|mark| std::cmp::Reverse(mark.updated_stamp));
+        Ok(marks)
+    }
+
+    async fn save_session_mark(&self, mark: &SessionMark) -> Result<(), ApiError> {
+        let mut data = self.data()?;
+        data.session_marks
+            .retain(|stored| stored.key != mark.key || stored.session_id != mark.session_id);
+        data.session_marks.push(mark.clone());
+        Ok(())
+    }
+
+    async fn delete_session_mark(&self, key: &str, session_id: &str) -> Result<(), ApiError> {
+        self.data()?
+            .session_marks
+            .retain(|mark| mark.key != key || mark.session_id != session_id);
+        Ok(())
+    }
+
+    async fn delete_session_marks_by_key(&self, key: &str) -> Result<(), ApiError> {
+        self.data()?.session_marks.retain(|mark| mark.key != key);
+        Ok(())
+    }
+
+    async fn find_webhook_by_key(&self, key: &str) -> Result<Option<Webhook>, ApiError> {
+        Ok(self
+            .data()?
+            .webhooks
+            .iter()
+            .find(|webhook| webhook.key == key)
+            .cloned())
+    }
+
+    async fn find_webhooks_by_namespace(&self, namespace: &str) -> Result<Vec<Webhook>, ApiError> {
+        Ok(self
+            .data()?
+            .webhooks
+            .iter()
+            .filter(|webhook| webhook.namespace == namespace)
+            .cloned()
+            .collect())
+    }
+
+    async fn save_webhook(&self, webhook: &Webhook) -> Result<(), ApiError> {
+        let mut data = self.data()?;
+        data.webhooks.retain(|stored| stored.key != webhook.key);
+        data.webhooks.push(webhook.clone());
+        Ok(())
+    }
+
+    async fn delete_webhook_by_key(&self, key: &str) -> Result<(), ApiError> {
+        self.data()?.webhooks.retain(|webhook| webhook.key != key);
+        Ok(())
+    }
+}
@@ -0,0 +1,1500 @@
+use std::time::Duration;
+
+use axum::async_trait;
+use futures::{io::Cursor, StreamExt, TryStreamExt};
+use mongodb::{
+    bson::{self, doc, oid::ObjectId, Bson, Document},
+    change_stream::event::ChangeStreamEvent,
+    error::{ErrorKind, TRANSIENT_TRANSACTION_ERROR},
+    gridfs::GridFsBucket,
+    options::{
+        ChangeStreamOptions, ClientOptions, FindOneAndUpdateOptions, FindOptions, FullDocumentType,
+        GridFsBucketOptions, IndexOptions, ReplaceOptions, ReturnDocument, UpdateOptions,
+    },
+    Client, Collection, IndexModel,
+};
+use tracing::instrument;
+
+use crate::{
+    entities::{
+        audit_entry::AuditEntry,
+        friendship::Friendship,
+        notification::Notification,
+        render_job::{RenderJob, EXPIRE_AFTER_NANOS, STALE_AFTER_NANOS},
+        room::{get_room_expiry, Room},
+        session::Session,
+        session_mark::SessionMark,
+        user::{SecondaryKey, User, MAX_SECONDARY_KEYS, MAX_TITLES},
+        webhook::Webhook,
+    },
+    error::ApiError,
+    game::review::PlayerAccuracy,
+    models::{
+        enums::{GameOutcome, PermissionLevel, Platform, RoomSort, SessionSort},
+        query_models::AuditLogQuery,
+        render_job_models::RenderJobStatus,
+        user_models::{DailyActivity, Title},
+    },
+    utils::time_operations::{timestamp_now_nanos, DAY_FORMAT},
+};
+
+use super::{RoomSelection, SessionBroadcast, SessionFilter, SessionStream, Storage};
+
+/// How often a transaction is retried after a transient error (e.g. a write conflict)
+const TRANSACTION_ATTEMPTS: u32 = 3;
+
+/// MongoDB error code when transactions aren't supported (standalone server)
+const ILLEGAL_OPERATION: i32 = 20;
+
+/// MongoDB error code when change streams aren't supported (standalone server)
+const CHANGE_STREAM_UNSUPPORTED: i32 = 40573;
+
+#[derive(Clone)]
+pub struct MongoStorage {
+    pub client: Client,
+    pub session_collection: Collection<Session>,
+    /// Finished sessions moved out of the session collection by the archiver
+    pub session_archive_collection: Collection<Session>,
+    pub user_collection: Collection<User>,
+    pub room_collection: Collection<Room>,
+    pub render_job_collection: Collection<RenderJob>,
+    /// Results of render jobs, GIFs can exceed the document size limit
+    pub render_bucket: GridFsBucket,
+    pub audit_collection: Collection<AuditEntry>,
+    pub friendship_collection: Collection<Friendship>,
+    pub notification_collection: Collection<Notification>,
+    pub session_mark_collection: Collection<SessionMark>,
+    pub webhook_collection: Collection<Webhook>,
+    /// Only watched if change streams aren't available
+    broadcast: SessionBroadcast,
+}
+
+/// Matches the given namespace, documents from before namespaces existed belong to the default one
+pub fn namespace_filter(namespace: &str) -> Bson {
+    if namespace.is_empty() {
+        Bson::Document(doc! { "$in": ["", Bson::Null] })
+    } else {
+        Bson::String(namespace.to_string())
+    }
+}
+
+/// Dotted path of the user id of the platform inside user documents
+fn platform_link_field(platform: Platform) -> Result<String, ApiError> {
+    match bson::to_bson(&platform)? {
+        Bson::String(name) => Ok(format!("platform_links.{}", name)),
+        other => Err(ApiError::ServerError(format!(
+            "Unexpected platform name {}",
+            other
+        ))),
+    }
+}
+
+fn index(keys: Document) -> IndexModel {
+    IndexModel::builder().keys(keys).build()
+}
+
+fn finished_filter(finished: bool) -> Document {
+    if finished {
+        doc! {
+            "$or": [
+                { "game_state.winner": { "$ne": 2 } },
+                { "game_state.draw": true },
+                { "aborted": true },
+            ]
+        }
+    } else {
+        doc! { "game_state.winner": 2, "game_state.draw": false, "aborted": { "$ne": true } }
+    }
+}
+
+impl MongoStorage {
+    pub async fn connect(url: &str) -> Result<Self, ApiError> {
+        let client_options = ClientOptions::parse(url).await?;
+        let client = Client::with_options(client_options)?;
+        let db = client.database("LemonChess");
+
+        let storage = Self {
+            client,
+            session_collection: db.collection("sessions"),
+            session_archive_collection: db.collection("sessions_archive"),
+            user_collection: db.collection("users"),
+            room_collection: db.collection("rooms"),
+            render_job_collection: db.collection("render_jobs"),
+            render_bucket: db.gridfs_bucket(
+                GridFsBucketOptions::builder()
+                    .bucket_name("renders".to_string())
+                    .build(),
+            ),
+            audit_collection: db.collection("audit_log"),
+            friendship_collection: db.collection("friendships"),
+            notification_collection: db.collection("notifications"),
+            session_mark_collection: db.collection("session_marks"),
+            webhook_collection: db.collection("webhooks"),
+            broadcast: SessionBroadcast::new(),
+        };
+        storage.create_indexes().await?;
+        Ok(storage)
+    }
+
+    /// Creates the indexes the API relies on, existing ones are left untouched
+    async fn create_indexes(&self) -> Result<(), ApiError> {
+        self.user_collection
+            .create_indexes(
+                [
+                    index(doc! { "key": 1 }),
+                    index(doc! { "secondary_keys.key": 1 }),
+                    index(doc! { "discord_id": 1, "namespace": 1 }),
+                ],
+                None,
+            )
+            .await?;
+        for platform in Platform::ALL {
+            self.user_collection
+                .create_index(
+                    index(doc! { platform_link_field(platform)?: 1, "namespace": 1 }),
+                    None,
+                )
+                .await?;
+        }
+
+        // Rooms are deleted by MongoDB once their expiry date has passed
+        let room_expiry = IndexModel::builder()
+            .keys(doc! { "expires_at": 1 })
+            .options(IndexOptions::builder().expire_after(Duration::ZERO).build())
+            .build();
+        self.room_collection
+            .create_indexes([index(doc! { "code": 1 }), room_expiry], None)
+            .await?;
+
+        // Also covers lookups by key alone, running and finished games are filtered by winner and draw
+        // The others back the sort orders of session lists
+        for collection in [&self.session_collection, &self.session_archive_collection] {
+            collection
+                .create_indexes(
+                    [
+                        index(doc! { "keys": 1, "game_state.winner": 1, "game_state.draw": 1 }),
+                        index(doc! { "keys": 1, "created_stamp": -1 }),
+                        index(doc! { "keys": 1, "updated_stamp": -1 }),
+                    ],
+                    None,
+                )
+                .await?;
+        }
+
+        self.friendship_collection
+            .create_indexes(
+                [
+                    index(doc! { "requester": 1 }),
+                    index(doc! { "addressee": 1 }),
+                ],
+                None,
+            )
+            .await?;
+
+        self.notification_collection
+            .create_index(index(doc! { "key": 1, "created_stamp": -1 }), None)
+            .await?;
+
+        self.session_mark_collection
+            .create_index(index(doc! { "key": 1, "session_id": 1 }), None)
+            .await?;
+
+        self.webhook_collection
+            .create_indexes(
+                [index(doc! { "key": 1 }), index(doc! { "namespace": 1 })],
+                None,
+            )
+            .await?;
+
+        self.audit_collection
+            .create_indexes(
+                [
+                    index(doc! { "created_stamp": -1 }),
+                    index(doc! { "actor": 1 }),
+                    index(doc! { "target": 1 }),
+                ],
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Moves discord ids of users from before platform links existed into the links, returns how many were moved
+    async fn move_discord_ids_to_links(&self) -> Result<u64, ApiError> {
+        let documents = self.user_collection.clone_with_type::<Document>();
+        let filter = doc! { "discord_id": { "$exists": true } };
+        let mut cursor = documents.find(filter.clone(), None).await?;
+
+        let mut count = 0;
+        while let Some(document) = cursor.try_next().await? {
+            // Reading the document as a user already moves the discord id into the links
+            let user: User = bson::from_document(document)?;
+            let mut filter = filter.clone();
+            filter.insert("key", &user.key);
+            let links = bson::to_bson(&user.platform_links)?;
+            let update =
+                doc! { "$set": { "platform_links": links }, "$unset": { "discord_id": "" } };
+            let result = documents.update_one(filter, update, None).await?;
+            count += result.modified_count;
+        }
+        Ok(count)
+    }
+
+    /// Gives rooms from before rooms expired a lifetime, so the TTL index picks them up
+    async fn set_missing_room_expiry(&self) -> Result<u64, ApiError> {
+        let filter = doc! { "expires_at": { "$in": [null] } };
+        let update = doc! { "$set": { "expires_at": get_room_expiry() } };
+        let result = self
+            .room_collection
+            .update_many(filter, update, None)
+            .await?;
+        Ok(result.modified_count)
+    }
+
+    /// Sessions from before the last activity was tracked count as active since their creation
+    async fn set_missing_updated_stamp(collection: &Collection<Session>) -> Result<u64, ApiError> {
+        let filter = doc! { "updated_stamp": { "$exists": false } };
+        let update = vec![doc! { "$set": { "updated_stamp": "$created_stamp" } }];
+        let result = collection.update_many(filter, update, None).await?;
+        Ok(result.modified_count)
+    }
+
+    /// Rewrites game states still stored field by field in the binary encoding, returns how many were rewritten
+    async fn compact_legacy_game_states(collection: &Collection<Session>) -> Result<u64, ApiError> {
+        let documents = collection.clone_with_type::<Document>();
+        let legacy_filter = doc! { "game_state.data": { "$exists": false } };
+        let mut cursor = documents.find(legacy_filter.clone(), None).await?;
+
+        let mut count = 0;
+        while let Some(document) = cursor.try_next().await? {
+            let session: Session = bson::from_document(document)?;
+            let mut filter = legacy_filter.clone();
+            filter.insert("_id", session.id);
+            // Only replaces the game state if nobody saved the session in the meantime
+            let game_state = bson::to_document(&session)?.remove("game_state");
+            let update = doc! { "$set": { "game_state": game_state } };
+            let result = documents.update_one(filter, update, None).await?;
+            count += result.modified_count;
+        }
+        Ok(count)
+    }
+
+    async fn find_session_in(
+        collection: &Collection<Session>,
+        id: &str,
+    ) -> Result<Option<Session>, ApiError> {
+        let oid = ObjectId::parse_str(id)?;
+        let filter = doc! { "_id": oid };
+        let session = collection.find_one(Some(filter), None).await?;
+        Ok(session)
+    }
+
+    async fn join_in_transaction(
+        &self,
+        code: &str,
+        session: &Document,
+    ) -> Result<(), mongodb::error::Error> {
+        let mut db_session = self.client.start_session(None).await?;
+        db_session.start_transaction(None).await?;
+        // Dropping the session without committing aborts the transaction
+        self.room_collection
+            .delete_one_with_session(doc! { "code": code }, None, &mut db_session)
+            .await?;
+        self.session_collection
+            .clone_with_type::<Document>()
+            .insert_one_with_session(session, None, &mut db_session)
+            .await?;
+        db_session.commit_transaction().await
+    }
+
+    /// Returns the user as it is after the update, None if the filter didn't match
+    async fn update_user(
+        &self,
+        filter: Document,
+        update: Document,
+    ) -> Result<Option<User>, ApiError> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+        let user = self
+            .user_collection
+            .find_one_and_update(filter, update, options)
+            .await?;
+        Ok(user)
+    }
+
+    /// Without transactions the session is inserted first and removed again if the room can't be deleted
+    async fn join_with_compensation(&self, code: &str, session: &Document) -> Result<(), ApiError> {
+        let sessions = self.session_collection.clone_with_type::<Document>();
+        let result = sessions.insert_one(session, None).await?;
+
+        if let Err(error) = self
+            .room_collection
+            .delete_one(doc! { "code": code }, None)
+            .await
+        {
+            sessions
+                .delete_one(doc! { "_id": result.inserted_id }, None)
+                .await?;
+            return Err(ApiError::from(error));
+        }
+        Ok(())
+    }
+}
+
+fn is_transaction_unsupported(error: &mongodb::error::Error) -> bool {
+    matches!(
+        error.kind.as_ref(),
+        ErrorKind::Command(command_error) if command_error.code == ILLEGAL_OPERATION
+    )
+}
+
+fn is_change_stream_unsupported(error: &mongodb::error::Error) -> bool {
+    matches!(
+        error.kind.as_ref(),
+        ErrorKind::Command(command_error) if command_error.code == CHANGE_STREAM_UNSUPPORTED
+    )
+}
+
+/// Makes user input match literally inside a regex
+fn escape_regex(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for character in input.chars() {
+        if "\\^$.|?*+()[]{}".contains(character) {
+            escaped.push('\\');
+        }
+        escaped.push(character);
+    }
+    escaped
+}
+
+/// The query for the sessions of a user, mirrors SessionFilter::matches
+fn session_filter(key: &str, filter: &SessionFilter) -> Document {
+    let mut conditions = vec![doc! { "keys": key }];
+    if let Some(finished) = filter.finished {
+        conditions.push(finished_filter(finished));
+    }
+    match filter.against_ai {
+        Some(true) => conditions.push(doc! { "keys": "AI" }),
+        Some(false) => conditions.push(doc! { "keys": { "$ne": "AI" } }),
+        None => {}
+    }
+    if let Some(outcome) = filter.outcome {
+        let own_color = doc! { "$indexOfArray": ["$keys", key] };
+        conditions.push(match outcome {
+            GameOutcome::Draw => doc! { "game_state.draw": true },
+            GameOutcome::Win => doc! {
+                "game_state.draw": false,
+                "$expr": { "$eq": ["$game_state.winner", own_color] },
+            },
+            GameOutcome::Loss => doc! {
+                "game_state.draw": false,
+                "game_state.winner": { "$ne": 2 },
+                "$expr": { "$ne": ["$game_state.winner", own_color] },
+            },
+        });
+    }
+    if let Some(opponent) = &filter.opponent {
+        let name = doc! {
+            "$regex": format!("^{}$", escape_regex(&opponent.name)),
+            "$options": "i",
+        };
+        let mut alternatives = vec![doc! { "imported.opponent": name }];
+        if let Some(opponent_key) = &opponent.key {
+            alternatives.push(doc! { "keys": opponent_key });
+        }
+        conditions.push(doc! { "$or": alternatives });
+    }
+    if let Some((from, to)) = filter.created {
+        conditions.push(doc! {
+            "created_stamp": { "$gte": from as i64, "$lt": to.min(i64::MAX as u64) as i64 }
+        });
+    }
+    doc! { "$and": conditions }
+}
+
+/// The _id breaks ties, so pages don't overlap
+fn session_sort(sort: SessionSort) -> Document {
+    match sort {
+        SessionSort::Newest => doc! { "created_stamp": -1, "_id": -1 },
+        SessionSort::Oldest => doc! { "created_stamp": 1, "_id": 1 },
+        SessionSort::RecentActivity => doc! { "updated_stamp": -1, "_id": -1 },
+    }
+}
+
+#[async_trait]
+impl Storage for MongoStorage {
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn ping(&self) -> Result<(), ApiError> {
+        self.client
+            .database("admin")
+            .run_command(doc! { "ping": 1 }, None)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn migrate(&self) -> Result<(), ApiError> {
+        match self.set_missing_room_expiry().await? {
+            0 => {}
+            count => println!("Set the expiry of {} rooms", count),
+        }
+        for collection in [&self.session_collection, &self.session_archive_collection] {
+            match Self::compact_legacy_game_states(collection).await? {
+                0 => {}
+                count => println!("Compacted the game state of {} sessions", count),
+            }
+            match Self::set_missing_updated_stamp(collection).await? {
+                0 => {}
+                count => println!("Set the activity stamp of {} sessions", count),
+            }
+        }
+        match self.move_discord_ids_to_links().await? {
+            0 => {}
+            count => println!("Moved the discord id of {} users", count),
+        }
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_user_by_key(&self, key: &str) -> Result<Option<User>, ApiError> {
+        let filter = doc! { "key": key };
+        let user = self.user_collection.find_one(Some(filter), None).await?;
+        Ok(user)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_users_by_keys(&self, keys: &[String]) -> Result<Vec<User>, ApiError> {
+        let filter = doc! { "key": { "$in": keys } };
+        let cursor = self.user_collection.find(filter, None).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_user_by_secondary_key(&self, key: &str) -> Result<Option<User>, ApiError> {
+        let filter = doc! { "secondary_keys.key": key };
+        let user = self.user_collection.find_one(Some(filter), None).await?;
+        Ok(user)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_user_by_name(&self, name: &str) -> Result<Option<User>, ApiError> {
+        let filter = doc! { "name": name.to_lowercase() };
+        let user = self.user_collection.find_one(Some(filter), None).await?;
+        Ok(user)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_user_by_platform_id(
+        &self,
+        namespace: &str,
+        platform: Platform,
+        id: &str,
+    ) -> Result<Option<User>, ApiError> {
+        let mut filter = doc! { "namespace": namespace_filter(namespace) };
+        let link_field = platform_link_field(platform)?;
+        if platform == Platform::DISCORD {
+            // Users which weren't migrated yet only have the old discord id field
+            filter.insert(
+                "$or",
+                vec![doc! { link_field: id }, doc! { "discord_id": id }],
+            );
+        } else {
+            filter.insert(link_field, id);
+        }
+        let user = self.user_collection.find_one(Some(filter), None).await?;
+        Ok(user)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn insert_user(&self, user: &User) -> Result<(), ApiError> {
+        self.user_collection.insert_one(user, None).await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn save_user(&self, user: &User) -> Result<(), ApiError> {
+        let filter = doc! { "key": &user.key };
+        // The discord id of older documents is part of the platform links by now
+        let update = doc! { "$set": bson::to_bson(user)?, "$unset": { "discord_id": "" } };
+
+        // No upsert, a user deleted in the meantime isn't brought back
+        self.user_collection
+            .update_one(filter, update, None)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn rename_user(
+        &self,
+        key: &str,
+        name: Option<&str>,
+        display_name: Option<&str>,
+    ) -> Result<Option<User>, ApiError> {
+        let mut names = Document::new();
+        if let Some(name) = name {
+            names.insert("name", name);
+        }
+        if let Some(display_name) = display_name {
+            names.insert("display_name", display_name);
+        }
+        if names.is_empty() {
+            return self.find_user_by_key(key).await;
+        }
+        self.update_user(doc! { "key": key }, doc! { "$set": names })
+            .await
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn set_user_permission(
+        &self,
+        key: &str,
+        permission: PermissionLevel,
+    ) -> Result<Option<User>, ApiError> {
+        let update = doc! { "$set": { "permission": bson::to_bson(&permission)? } };
+        self.update_user(doc! { "key": key }, update).await
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn set_user_banned(&self, key: &str, banned: bool) -> Result<Option<User>, ApiError> {
+        let update = doc! { "$set": { "banned": banned } };
+        self.update_user(doc! { "key": key }, update).await
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn push_user_title(&self, key: &str, title: &Title) -> Result<Option<User>, ApiError> {
+        // Only matches while the user doesn't hold the title and the limit isn't reached
+        let filter = doc! {
+            "key": key,
+            "titles.name": { "$ne": &title.name },
+            format!("titles.{}", MAX_TITLES - 1): { "$exists": false },
+        };
+        let update = doc! { "$push": { "titles": bson::to_bson(title)? } };
+        if let Some(user) = self.update_user(filter, update).await? {
+            return Ok(Some(user));
+        }
+
+        // Tells apart a missing user from a held title or reached limit
+        match self.find_user_by_key(key).await? {
+            Some(mut user) => {
+                user.award_title(title.clone())?;
+                Err(ApiError::Conflict(
+                    "The titles changed at the same time, try again.".to_string(),
+                ))
+            }
+            None => Ok(None),
+        }
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn pull_user_titles(
+        &self,
+        key: &str,
+        name: Option<&str>,
+    ) -> Result<Option<(User, usize)>, ApiError> {
+        let (filter, update) = match name {
+            Some(name) => (
+                doc! { "key": key, "titles.name": name },
+                doc! { "$pull": { "titles": { "name": name } } },
+            ),
+            None => (doc! { "key": key }, doc! { "$set": { "titles": [] } }),
+        };
+        // The user from before the update tells how many titles were removed
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::Before)
+            .build();
+        let user = self
+            .user_collection
+            .find_one_and_update(filter, update, options)
+            .await?;
+        if let Some(mut user) = user {
+            let removed = user.reset_titles(name)?;
+            return Ok(Some((user, removed)));
+        }
+
+        // Tells apart a missing user from a title the user doesn't hold
+        match self.find_user_by_key(key).await? {
+            Some(mut user) => {
+                user.reset_titles(name)?;
+                Err(ApiError::Conflict(
+                    "The titles changed at the same time, try again.".to_string(),
+                ))
+            }
+            None => Ok(None),
+        }
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn record_user_usage(
+        &self,
+        key: &str,
+        endpoint: &str,
+        access_stamp: u64,
+    ) -> Result<(), ApiError> {
+        let filter = doc! { "key": key };
+        // The endpoint contains the requested path, which may contain dots
+        // So the count is set with $setField instead of $inc on a dotted field path
+        let usage = doc! { "$ifNull": ["$endpoint_usage", {}] };
+        let count =
+            doc! { "$getField": { "field": { "$literal": endpoint }, "input": usage.clone() } };
+        let update = vec![doc! { "$set": {
+            "last_access_stamp": access_stamp as i64,
+            "endpoint_usage": { "$setField": {
+                "field": { "$literal": endpoint },
+                "input": usage,
+                "value": { "$add": [{ "$ifNull": [count, 0_i64] }, 1_i64] },
+            } },
+        } }];
+
+        self.user_collection
+            .update_one(filter, update, None)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn push_secondary_key(
+        &self,
+        key: &str,
+        secondary_key: &SecondaryKey,
+    ) -> Result<bool, ApiError> {
+        // Only matches while the name is free and the limit isn't reached
+        let filter = doc! {
+            "key": key,
+            "secondary_keys.name": { "$ne": &secondary_key.name },
+            format!("secondary_keys.{}", MAX_SECONDARY_KEYS - 1): { "$exists": false },
+        };
+        let update = doc! { "$push": { "secondary_keys": bson::to_bson(secondary_key)? } };
+        let result = self
+            .user_collection
+            .update_one(filter, update, None)
+            .await?;
+        if result.matched_count > 0 {
+            return Ok(true);
+        }
+
+        // Tells apart a missing user from a taken name or reached limit
+        match self.find_user_by_key(key).await? {
+            Some(mut user) => {
+                user.add_secondary_key(secondary_key.clone())?;
+                Err(ApiError::Conflict(
+                    "The keys changed at the same time, try again.".to_string(),
+                ))
+            }
+            None => Ok(false),
+        }
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn remove_secondary_key(
+        &self,
+        key: &str,
+        name: &str,
+    ) -> Result<Option<SecondaryKey>, ApiError> {
+        let filter = doc! { "key": key, "secondary_keys.name": name };
+        let update = doc! { "$pull": { "secondary_keys": { "name": name } } };
+        // The user from before the update still holds the removed key
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::Before)
+            .build();
+        let user = self
+            .user_collection
+            .find_one_and_update(filter, update, options)
+            .await?;
+        Ok(user.and_then(|mut user| user.remove_secondary_key(name).ok()))
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_users(
+        &self,
+        namespace: Option<&str>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<User>, u64), ApiError> {
+        let filter = match namespace {
+            Some(namespace) => doc! { "namespace": namespace_filter(namespace) },
+            None => doc! {},
+        };
+        let find_options = FindOptions::builder()
+            .skip(offset)
+            .limit(limit as i64)
+            .sort(doc! { "created_stamp": 1 })
+            .build();
+
+        let total = self
+            .user_collection
+            .count_documents(filter.clone(), None)
+            .await?;
+        let cursor = self.user_collection.find(filter, find_options).await?;
+        let users: Vec<User> = cursor.try_collect().await?;
+        Ok((users, total))
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn delete_user_by_key(&self, key: &str) -> Result<(), ApiError> {
+        let filter = doc! { "key": key };
+        self.user_collection.delete_one(filter, None).await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_session_by_id(&self, id: &str) -> Result<Option<Session>, ApiError> {
+        Self::find_session_in(&self.session_collection, id).await
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_archived_session_by_id(&self, id: &str) -> Result<Option<Session>, ApiError> {
+        Self::find_session_in(&self.session_archive_collection, id).await
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_sessions_by_ids(&self, ids: &[String]) -> Result<Vec<Session>, ApiError> {
+        let ids = ids
+            .iter()
+            .map(ObjectId::parse_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        let filter = doc! { "_id": { "$in": ids.clone() } };
+        let cursor = self.session_collection.find(filter, None).await?;
+        let mut sessions: Vec<Session> = cursor.try_collect().await?;
+
+        // Only the ids which weren't found are looked up in the archive
+        let missing: Vec<ObjectId> = ids
+            .into_iter()
+            .filter(|id| sessions.iter().all(|session| session.id != Some(*id)))
+            .collect();
+        if !missing.is_empty() {
+            let filter = doc! { "_id": { "$in": missing } };
+            let cursor = self.session_archive_collection.find(filter, None).await?;
+            let archived: Vec<Session> = cursor.try_collect().await?;
+            sessions.extend(archived);
+        }
+        Ok(sessions)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_active_session_by_keys(
+        &self,
+        keys: &[String; 2],
+    ) -> Result<Option<Session>, ApiError> {
+        let mut filter = finished_filter(false);
+        filter.insert("keys", doc! { "$all": keys.to_vec() });
+        let session = self.session_collection.find_one(filter, None).await?;
+        Ok(session)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_sessions_by_key_and_finished(
+        &self,
+        key: &str,
+        finished: bool,
+    ) -> Result<Vec<Session>, ApiError> {
+        let mut filter = finished_filter(finished);
+        filter.insert("keys", key);
+        let cursor = self.session_collection.find(filter, None).await?;
+        let sessions: Vec<Session> = cursor.try_collect().await?;
+        Ok(sessions)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_daily_activity(
+        &self,
+        key: &str,
+        from_stamp: u64,
+        to_stamp: u64,
+    ) -> Result<Vec<DailyActivity>, ApiError> {
+        let mut filter = finished_filter(true);
+        filter.insert("keys", key);
+        filter.insert("aborted", doc! { "$ne": true });
+        filter.insert(
+            "created_stamp",
+            doc! { "$gte": from_stamp as i64, "$lt": to_stamp as i64 },
+        );
+        let day = doc! {
+            "$dateToString": {
+                "format": DAY_FORMAT,
+                "date": { "$toDate": { "$toLong": { "$divide": ["$created_stamp", 1_000_000] } } },
+            }
+        };
+        let won = doc! {
+            "$and": [
+                { "$eq": ["$game_state.winner", { "$indexOfArray": ["$keys", key] }] },
+                { "$eq": ["$game_state.draw", false] },
+            ]
+        };
+        let pipeline = vec![
+            doc! { "$match": filter.clone() },
+            doc! {
+                "$unionWith": {
+                    "coll": self.session_archive_collection.name(),
+                    "pipeline": [{ "$match": filter }],
+                }
+            },
+            doc! {
+                "$group": {
+                    "_id": day,
+                    "games": { "$sum": 1 },
+                    "wins": { "$sum": { "$cond": [won, 1, 0] } },
+                }
+            },
+            doc! { "$sort": { "_id": 1 } },
+            doc! { "$project": { "_id": 0, "date": "$_id", "games": 1, "wins": 1 } },
+        ];
+
+        let cursor = self.session_collection.aggregate(pipeline, None).await?;
+        let documents: Vec<Document> = cursor.try_collect().await?;
+        documents
+            .into_iter()
+            .map(|document| Ok(bson::from_document(document)?))
+            .collect()
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_sessions_by_key(
+        &self,
+        key: &str,
+        filter: &SessionFilter,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<Session>, u64), ApiError> {
+        let collection = &self.session_collection;
+        let archive_collection = &self.session_archive_collection;
+        let include_archived = filter.include_archived;
+        let query = session_filter(key, filter);
+
+        let total = collection.count_documents(query.clone(), None).await?;
+        let archived_total = if include_archived {
+            archive_collection
+                .count_documents(query.clone(), None)
+                .await?
+        } else {
+            0
+        };
+
+        if let Some(sort) = filter.sort {
+            if limit == 0 {
+                return Ok((Vec::new(), total + archived_total));
+            }
+            let mut pipeline = vec![doc! { "$match": query.clone() }];
+            if include_archived {
+                pipeline.push(doc! {
+                    "$unionWith": {
+                        "coll": archive_collection.name(),
+                        "pipeline": [{ "$match": query }],
+                    }
+                });
+            }
+            pipeline.extend([
+                doc! { "$sort": session_sort(sort) },
+                doc! { "$skip": offset as i64 },
+                doc! { "$limit": limit.min(i64::MAX as u64) as i64 },
+            ]);
+            let cursor = collection.aggregate(pipeline, None).await?;
+            let documents: Vec<Document> = cursor.try_collect().await?;
+            let sessions = documents
+                .into_iter()
+                .map(bson::from_document)
+                .collect::<Result<Vec<Session>, _>>()?;
+            return Ok((sessions, total + archived_total));
+        }
+
+        let mut sessions: Vec<Session> = Vec::new();
+        if offset < total {
+            let find_options = FindOptions::builder()
+                .skip(offset)
+                .limit(limit as i64)
+                .build();
+            let cursor = collection.find(query.clone(), find_options).await?;
+            sessions = cursor.try_collect().await?;
+        }
+        let remaining = limit - sessions.len() as u64;
+        if include_archived && remaining > 0 {
+            let find_options = FindOptions::builder()
+                .skip(offset.saturating_sub(total))
+                .limit(remaining as i64)
+                .build();
+            let cursor = archive_collection.find(query, find_options).await?;
+            let archived: Vec<Session> = cursor.try_collect().await?;
+            sessions.extend(archived);
+        }
+
+        Ok((sessions, total + archived_total))
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn save_session(&self, session: &Session) -> Result<(), ApiError> {
+        let collection = self.session_collection.clone_with_type::<Document>();
+        let document = bson::to_document(session)?;
+
+        if let Some(id) = session.id {
+            let filter = doc! { "_id": id };
+            let update = doc! { "$set": document };
+            let options = UpdateOptions::builder().upsert(true).build();
+            collection.update_one(filter, update, Some(options)).await?;
+        } else {
+            collection.insert_one(document, None).await?;
+        }
+        self.broadcast.publish(session);
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn set_session_accuracy(
+        &self,
+        id: &str,
+        accuracy: &[PlayerAccuracy; 2],
+    ) -> Result<(), ApiError> {
+        let filter = doc! { "_id": ObjectId::parse_str(id)?, "accuracy": null };
+        let update = doc! { "$set": { "accuracy": bson::to_bson(accuracy)? } };
+        for collection in [&self.session_collection, &self.session_archive_collection] {
+            let result = collection
+                .update_one(filter.clone(), update.clone(), None)
+                .await?;
+            if result.matched_count > 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn watch_sessions(&self) -> Result<SessionStream, ApiError> {
+        let pipeline = [doc! {
+            "$match": { "operationType": { "$in": ["insert", "update", "replace"] } }
+        }];
+        let options = ChangeStreamOptions::builder()
+            .full_document(Some(FullDocumentType::UpdateLookup))
+            .build();
+
+        match self.session_collection.watch(pipeline, Some(options)).await {
+            Ok(change_stream) => Ok(change_stream
+                .map_err(ApiError::from)
+                .try_filter_map(|event: ChangeStreamEvent<Session>| async move {
+                    Ok(event.full_document)
+                })
+                .boxed()),
+            Err(error) if is_change_stream_unsupported(&error) => {
+                println!("Change streams aren't supported, only sessions saved by this instance are watched");
+                Ok(self.broadcast.watch())
+            }
+            Err(error) => Err(ApiError::from(error)),
+        }
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn archive_finished_sessions(&self, created_before: u64) -> Result<u64, ApiError> {
+        let sessions = self.session_collection.clone_with_type::<Document>();
+        let archive = self
+            .session_archive_collection
+            .clone_with_type::<Document>();
+
+        let mut filter = finished_filter(true);
+        filter.insert("created_stamp", doc! { "$lt": created_before as i64 });
+        let mut cursor = sessions.find(filter, None).await?;
+
+        let mut count = 0;
+        while let Some(document) = cursor.try_next().await? {
+            let id = document.get("_id").cloned().unwrap_or_default();
+            // Copied first, a crash in between leaves the session in both collections instead of none
+            let options = ReplaceOptions::builder().upsert(true).build();
+            archive
+                .replace_one(doc! { "_id": &id }, &document, options)
+                .await?;
+            sessions.delete_one(doc! { "_id": id }, None).await?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_running_timed_sessions(&self) -> Result<Vec<Session>, ApiError> {
+        let mut filter = finished_filter(false);
+        filter.insert("time_control", doc! { "$ne": null });
+        filter.insert("paused", doc! { "$ne": true });
+        let cursor = self.session_collection.find(filter, None).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_failed_ai_sessions(&self) -> Result<Vec<Session>, ApiError> {
+        let mut filter = finished_filter(false);
+        filter.insert("ai_error", doc! { "$ne": null });
+        let cursor = self.session_collection.find(filter, None).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn delete_expired_rooms(&self) -> Result<u64, ApiError> {
+        let filter = doc! { "expires_at": { "$lt": bson::DateTime::now() } };
+        let result = self.room_collection.delete_many(filter, None).await?;
+        Ok(result.deleted_count)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_room_by_code(&self, code: &str) -> Result<Option<Room>, ApiError> {
+        let filter = doc! { "code": code.to_uppercase() };
+        let room = self.room_collection.find_one(Some(filter), None).await?;
+        Ok(room)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_rooms_by_key(&self, key: &str) -> Result<Vec<Room>, ApiError> {
+        let filter = doc! { "key": key };
+        let cursor = self.room_collection.find(filter, None).await?;
+        let rooms: Vec<Room> = cursor.try_collect().await?;
+        Ok(rooms)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_rooms(
+        &self,
+        selection: RoomSelection<'_>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<Room>, u64), ApiError> {
+        let (filter, sort) = match selection {
+            RoomSelection::Owner(key) => (doc! { "key": key }, None),
+            RoomSelection::Invited(key) => (doc! { "invited_key": key }, None),
+            RoomSelection::Public { namespace, filter } => {
+                let mut public_filter = doc! { "public": true };
+                if let Some(namespace) = namespace {
+                    public_filter.insert("namespace", namespace_filter(namespace));
+                }
+                if let Some(search) = &filter.search {
+                    public_filter.insert(
+                        "name",
+                        doc! { "$regex": escape_regex(search), "$options": "i" },
+                    );
+                }
+                if let Some(created_after) = filter.created_after {
+                    public_filter.insert("created_stamp", doc! { "$gt": created_after as i64 });
+                }
+                let sort = match filter.sort.unwrap_or_default() {
+                    RoomSort::NEWEST => doc! { "created_stamp": -1 },
+                    RoomSort::OLDEST => doc! { "created_stamp": 1 },
+                };
+                (public_filter, Some(sort))
+            }
+        };
+
+        let find_options = FindOptions::builder()
+            .skip(offset)
+            .limit(limit as i64)
+            .sort(sort)
+            .build();
+
+        let total = self
+            .room_collection
+            .count_documents(filter.clone(), None)
+            .await?;
+        let cursor = self.room_collection.find(filter, find_options).await?;
+        let rooms: Vec<Room> = cursor.try_collect().await?;
+        Ok((rooms, total))
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn save_room(&self, room: &Room) -> Result<(), ApiError> {
+        if let Some(id) = &room.id {
+            let filter = doc! { "_id": id };
+            let update = doc! { "$set": bson::to_bson(room)? };
+            let options = UpdateOptions::builder().upsert(true).build();
+            self.room_collection
+                .update_one(filter, update, Some(options))
+                .await?;
+        } else {
+            self.room_collection.insert_one(room, None).await?;
+        }
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn delete_room_by_code(&self, code: &str) -> Result<(), ApiError> {
+        let filter = doc! { "code": code };
+        self.room_collection.delete_one(filter, None).await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn start_room_session(&self, code: &str, session: &Session) -> Result<(), ApiError> {
+        let mut document = bson::to_document(session)?;
+        // A fixed id turns a retried insert into a duplicate key error instead of a second game
+        document.insert("_id", session.id.unwrap_or_default());
+
+        let mut attempt = 1;
+        loop {
+            match self.join_in_transaction(code, &document).await {
+                Ok(()) => return Ok(()),
+                Err(error)
+                    if error.contains_label(TRANSIENT_TRANSACTION_ERROR)
+                        && attempt < TRANSACTION_ATTEMPTS =>
+                {
+                    attempt += 1;
+                }
+                Err(error) if is_transaction_unsupported(&error) => {
+                    return self.join_with_compensation(code, &document).await;
+                }
+                Err(error) => return Err(ApiError::from(error)),
+            }
+        }
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn insert_render_job(&self, job: &mut RenderJob) -> Result<(), ApiError> {
+        let result = self.render_job_collection.insert_one(&*job, None).await?;
+        job.id = result.inserted_id.as_object_id();
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_render_job_by_id(&self, id: &str) -> Result<Option<RenderJob>, ApiError> {
+        let oid = ObjectId::parse_str(id)?;
+        let job = self
+            .render_job_collection
+            .find_one(doc! { "_id": oid }, None)
+            .await?;
+        Ok(job)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn claim_next_render_job(&self) -> Result<Option<RenderJob>, ApiError> {
+        let now = timestamp_now_nanos();
+        let filter = doc! { "$or": [
+            { "status": bson::to_bson(&RenderJobStatus::QUEUED)? },
+            {
+                "status": bson::to_bson(&RenderJobStatus::RUNNING)?,
+                "started_stamp": { "$lt": now.saturating_sub(STALE_AFTER_NANOS) as i64 },
+            },
+        ] };
+        let update = doc! { "$set": {
+            "status": bson::to_bson(&RenderJobStatus::RUNNING)?,
+            "started_stamp": now as i64,
+        } };
+        let options = FindOneAndUpdateOptions::builder()
+            .sort(doc! { "created_stamp": 1 })
+            .return_document(ReturnDocument::After)
+            .build();
+        let job = self
+            .render_job_collection
+            .find_one_and_update(filter, update, options)
+            .await?;
+        Ok(job)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn complete_render_job(
+        &self,
+        job: &RenderJob,
+        result_id: ObjectId,
+    ) -> Result<(), ApiError> {
+        let update = doc! { "$set": {
+            "status": bson::to_bson(&RenderJobStatus::DONE)?,
+            "result_id": result_id,
+        } };
+        self.render_job_collection
+            .update_one(doc! { "_id": job.id }, update, None)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn fail_render_job(&self, job: &RenderJob, error: &str) -> Result<(), ApiError> {
+        let update = doc! { "$set": {
+            "status": bson::to_bson(&RenderJobStatus::FAILED)?,
+            "error": error,
+        } };
+        self.render_job_collection
+            .update_one(doc! { "_id": job.id }, update, None)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn delete_expired_render_jobs(&self) -> Result<u64, ApiError> {
+        let filter = doc! { "created_stamp": {
+            "$lt": timestamp_now_nanos().saturating_sub(EXPIRE_AFTER_NANOS) as i64
+        } };
+
+        let mut cursor = self
+            .render_job_collection
+            .find(filter.clone(), None)
+            .await?;
+        while cursor.advance().await? {
+            let job = cursor.deserialize_current()?;
+            if let Some(result_id) = job.result_id {
+                self.render_bucket.delete(Bson::ObjectId(result_id)).await?;
+            }
+        }
+
+        let result = self.render_job_collection.delete_many(filter, None).await?;
+        Ok(result.deleted_count)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn store_render_result(&self, name: &str, bytes: Vec<u8>) -> Result<ObjectId, ApiError> {
+        let result_id = self
+            .render_bucket
+            .upload_from_futures_0_3_reader(name, Cursor::new(bytes), None)
+            .await?;
+        Ok(result_id)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn load_render_result(&self, id: ObjectId) -> Result<Vec<u8>, ApiError> {
+        let mut bytes = Vec::new();
+        self.render_bucket
+            .download_to_futures_0_3_writer(Bson::ObjectId(id), &mut bytes)
+            .await?;
+        Ok(bytes)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_friendship(
+        &self,
+        first_key: &str,
+        second_key: &str,
+    ) -> Result<Option<Friendship>, ApiError> {
+        let filter = doc! { "$or": [
+            { "requester": first_key, "addressee": second_key },
+            { "requester": second_key, "addressee": first_key },
+        ] };
+        let friendship = self.friendship_collection.find_one(filter, None).await?;
+        Ok(friendship)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_friendships_by_key(&self, key: &str) -> Result<Vec<Friendship>, ApiError> {
+        let filter = doc! { "$or": [{ "requester": key }, { "addressee": key }] };
+        let cursor = self.friendship_collection.find(filter, None).await?;
+        let friendships: Vec<Friendship> = cursor.try_collect().await?;
+        Ok(friendships)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn save_friendship(&self, friendship: &Friendship) -> Result<(), ApiError> {
+        if let Some(id) = &friendship.id {
+            let filter = doc! { "_id": id };
+            let update = doc! { "$set": bson::to_bson(friendship)? };
+            self.friendship_collection
+                .update_one(filter, update, None)
+                .await?;
+        } else {
+            self.friendship_collection
+                .insert_one(friendship, None)
+                .await?;
+        }
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn delete_friendship(&self, friendship: &Friendship) -> Result<(), ApiError> {
+        let filter = doc! { "_id": friendship.id };
+        self.friendship_collection.delete_one(filter, None).await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn insert_audit_entry(&self, entry: &AuditEntry) -> Result<(), ApiError> {
+        self.audit_collection.insert_one(entry, None).await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_audit_entries(
+        &self,
+        query: &AuditLogQuery,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<AuditEntry>, u64), ApiError> {
+        let mut filter = doc! {};
+        if let Some(action) = query.action {
+            filter.insert("action", bson::to_bson(&action)?);
+        }
+        if let Some(user) = &query.user {
+            let user = user.to_lowercase();
+            filter.insert(
+                "$or",
+                vec![doc! { "actor": &user }, doc! { "target": &user }],
+            );
+        }
+        let find_options = FindOptions::builder()
+            .skip(offset)
+            .limit(limit as i64)
+            .sort(doc! { "created_stamp": -1 })
+            .build();
+
+        let total = self
+            .audit_collection
+            .count_documents(filter.clone(), None)
+            .await?;
+        let cursor = self.audit_collection.find(filter, find_options).await?;
+        let entries: Vec<AuditEntry> = cursor.try_collect().await?;
+        Ok((entries, total))
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn insert_notification(&self, notification: &Notification) -> Result<(), ApiError> {
+        self.notification_collection
+            .insert_one(notification, None)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_notifications(
+        &self,
+        key: &str,
+        unread_only: bool,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<Notification>, u64), ApiError> {
+        let mut filter = doc! { "key": key };
+        if unread_only {
+            filter.insert("read", false);
+        }
+        let find_options = FindOptions::builder()
+            .skip(offset)
+            .limit(limit as i64)
+            .sort(doc! { "created_stamp": -1 })
+            .build();
+
+        let total = self
+            .notification_collection
+            .count_documents(filter.clone(), None)
+            .await?;
+        let cursor = self
+            .notification_collection
+            .find(filter, find_options)
+            .await?;
+        let notifications: Vec<Notification> = cursor.try_collect().await?;
+        Ok((notifications, total))
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn count_unread_notifications(&self, key: &str) -> Result<u64, ApiError> {
+        let filter = doc! { "key": key, "read": false };
+        let count = self
+            .notification_collection
+            .count_documents(filter, None)
+            .await?;
+        Ok(count)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn mark_notifications_read(&self, key: &str, id: Option<&str>) -> Result<u64, ApiError> {
+        let mut filter = doc! { "key": key, "read": false };
+        if let Some(id) = id {
+            filter.insert("_id", ObjectId::parse_str(id)?);
+        }
+        let update = doc! { "$set": { "read": true } };
+        let result = self
+            .notification_collection
+            .update_many(filter, update, None)
+            .await?;
+        Ok(result.modified_count)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn delete_notifications_by_key(&self, key: &str) -> Result<(), ApiError> {
+        let filter = doc! { "key": key };
+        self.notification_collection
+            .delete_many(filter, None)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_session_mark(
+        &self,
+        key: &str,
+        session_id: &str,
+    ) -> Result<Option<SessionMark>, ApiError> {
+        let filter = doc! { "key": key, "session_id": session_id };
+        let mark = self.session_mark_collection.find_one(filter, None).await?;
+        Ok(mark)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_session_marks_by_key(&self, key: &str) -> Result<Vec<SessionMark>, ApiError> {
+        let filter = doc! { "key": key };
+        let find_options = FindOptions::builder()
+            .sort(doc! { "updated_stamp": -1 })
+            .build();
+        let cursor = self
+            .session_mark_collection
+            .find(filter, find_options)
+            .await?;
+        let marks: Vec<SessionMark> = cursor.try_collect().await?;
+        Ok(marks)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn save_session_mark(&self, mark: &SessionMark) -> Result<(), ApiError> {
+        let filter = doc! { "key": &mark.key, "session_id": &mark.session_id };
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.session_mark_collection
+            .replace_one(filter, mark, Some(options))
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn delete_session_mark(&self, key: &str, session_id: &str) -> Result<(), ApiError> {
+        let filter = doc! { "key": key, "session_id": session_id };
+        self.session_mark_collection
+            .delete_one(filter, None)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn delete_session_marks_by_key(&self, key: &str) -> Result<(), ApiError> {
+        let filter = doc! { "key": key };
+        self.session_mark_collection
+            .delete_many(filter, None)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_webhook_by_key(&self, key: &str) -> Result<Option<Webhook>, ApiError> {
+        let filter = doc! { "key": key };
+        let webhook = self.webhook_collection.find_one(filter, None).await?;
+        Ok(webhook)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn find_webhooks_by_namespace(&self, namespace: &str) -> Result<Vec<Webhook>, ApiError> {
+        let filter = doc! { "namespace": namespace };
+        let cursor = self.webhook_collection.find(filter, None).await?;
+        let webhooks: Vec<Webhook> = cursor.try_collect().await?;
+        Ok(webhooks)
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn save_webhook(&self, webhook: &Webhook) -> Result<(), ApiError> {
+        let filter = doc! { "key": &webhook.key };
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.webhook_collection
+            .replace_one(filter, webhook, Some(options))
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "mongodb"))]
+    async fn delete_webhook_by_key(&self, key: &str) -> Result<(), ApiError> {
+        let filter = doc! { "key": key };
+        self.webhook_collection.delete_one(filter, None).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_regex() {
+        assert_eq!(escape_regex("Lemon's game"), "Lemon's game");
+        assert_eq!(escape_regex("a.b*(c)"), "a\\.b\\*\\(c\\)");
+    }
+}
@@ -0,0 +1,1906 @@
+use std::sync::{Arc, Mutex};
+
+use axum::async_trait;
+use mongodb::bson::{self, oid::ObjectId, Bson, Document};
+use rusqlite::{
+    params, params_from_iter, types::Value, Connection, OptionalExtension, Params,
+    TransactionBehavior,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    entities::{
+        audit_entry::AuditEntry,
+        friendship::Friendship,
+        notification::Notification,
+        render_job::{RenderJob, EXPIRE_AFTER_NANOS, STALE_AFTER_NANOS},
+        room::Room,
+        session::Session,
+        session_mark::SessionMark,
+        user::{SecondaryKey, User},
+        webhook::Webhook,
+    },
+    error::ApiError,
+    game::review::PlayerAccuracy,
+    models::{
+        enums::{PermissionLevel, Platform, RoomSort},
+        query_models::AuditLogQuery,
+        render_job_models::RenderJobStatus,
+        user_models::{DailyActivity, Title},
+    },
+    utils::time_operations::timestamp_now_nanos,
+};
+
+use super::{paginate, RoomSelection, SessionBroadcast, SessionFilter, SessionStream, Storage};
+
+/// Entities are stored as BSON documents, the other columns only exist for lookups
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS users (
+        key TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        discord_id TEXT NOT NULL,
+        namespace TEXT NOT NULL,
+        document BLOB NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS users_name ON users (name);
+    CREATE INDEX IF NOT EXISTS users_discord_id ON users (discord_id, namespace);
+    CREATE TABLE IF NOT EXISTS user_keys (
+        key TEXT PRIMARY KEY,
+        user_key TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS user_keys_user_key ON user_keys (user_key);
+    CREATE TABLE IF NOT EXISTS user_links (
+        platform TEXT NOT NULL,
+        platform_id TEXT NOT NULL,
+        namespace TEXT NOT NULL,
+        user_key TEXT NOT NULL,
+        PRIMARY KEY (platform, platform_id, namespace)
+    );
+    CREATE INDEX IF NOT EXISTS user_links_user_key ON user_links (user_key);
+
+    CREATE TABLE IF NOT EXISTS sessions (
+        id TEXT PRIMARY KEY,
+        white_key TEXT NOT NULL,
+        black_key TEXT NOT NULL,
+        finished INTEGER NOT NULL,
+        archived INTEGER NOT NULL DEFAULT 0,
+        created_stamp INTEGER NOT NULL,
+        document BLOB NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS sessions_white_key ON sessions (white_key);
+    CREATE INDEX IF NOT EXISTS sessions_black_key ON sessions (black_key);
+
+    CREATE TABLE IF NOT EXISTS rooms (
+        code TEXT PRIMARY KEY,
+        key TEXT NOT NULL,
+        invited_key TEXT,
+        namespace TEXT NOT NULL,
+        public INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        created_stamp INTEGER NOT NULL,
+        expires_at INTEGER,
+        document BLOB NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS rooms_key ON rooms (key);
+    CREATE INDEX IF NOT EXISTS rooms_invited_key ON rooms (invited_key);
+
+    CREATE TABLE IF NOT EXISTS render_jobs (
+        id TEXT PRIMARY KEY,
+        status TEXT NOT NULL,
+        created_stamp INTEGER NOT NULL,
+        started_stamp INTEGER,
+        document BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS render_results (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        data BLOB NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS friendships (
+        id TEXT PRIMARY KEY,
+        requester TEXT NOT NULL,
+        addressee TEXT NOT NULL,
+        document BLOB NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS friendships_requester ON friendships (requester);
+    CREATE INDEX IF NOT EXISTS friendships_addressee ON friendships (addressee);
+
+    CREATE TABLE IF NOT EXISTS audit_log (
+        id TEXT PRIMARY KEY,
+        action TEXT NOT NULL,
+        actor TEXT NOT NULL,
+        target TEXT,
+        created_stamp INTEGER NOT NULL,
+        document BLOB NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS audit_log_created_stamp ON audit_log (created_stamp);
+
+    CREATE TABLE IF NOT EXISTS notifications (
+        id TEXT PRIMARY KEY,
+        key TEXT NOT NULL,
+        read INTEGER NOT NULL,
+        created_stamp INTEGER NOT NULL,
+        document BLOB NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS notifications_key ON notifications (key, created_stamp);
+
+    CREATE TABLE IF NOT EXISTS session_marks (
+        key TEXT NOT NULL,
+        session_id TEXT NOT NULL,
+        updated_stamp INTEGER NOT NULL,
+        document BLOB NOT NULL,
+        PRIMARY KEY (key, session_id)
+    );
+
+    CREATE TABLE IF NOT EXISTS webhooks (
+        key TEXT PRIMARY KEY,
+        namespace TEXT NOT NULL,
+        document BLOB NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS webhooks_namespace ON webhooks (namespace);
+";
+
+/// The discord id column is only filled by versions from before platform links, those users get their link here
+const LINK_LEGACY_DISCORD_IDS: &str = "
+    INSERT OR IGNORE INTO user_links (platform, platform_id, namespace, user_key)
+    SELECT 'DISCORD', discord_id, namespace, key FROM users WHERE discord_id != '';
+";
+
+/// Single file storage for small deployments, all access goes through one connection
+#[derive(Clone)]
+pub struct SqliteStorage {
+    connection: Arc<Mutex<Connection>>,
+    broadcast: SessionBroadcast,
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, ApiError> {
+    Ok(bson::to_vec(value)?)
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, ApiError> {
+    Ok(bson::from_slice(bytes)?)
+}
+
+fn find_one<T: DeserializeOwned>(
+    connection: &Connection,
+    sql: &str,
+    params: impl Params,
+) -> Result<Option<T>, ApiError> {
+    let document = connection
+        .query_row(sql, params, |row| row.get::<_, Vec<u8>>(0))
+        .optional()?;
+    document.map(|document| decode(&document)).transpose()
+}
+
+fn find_all<T: DeserializeOwned>(
+    connection: &Connection,
+    sql: &str,
+    params: impl Params,
+) -> Result<Vec<T>, ApiError> {
+    let mut statement = connection.prepare(sql)?;
+    let documents = statement
+        .query_map(params, |row| row.get::<_, Vec<u8>>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    documents.iter().map(|document| decode(document)).collect()
+}
+
+/// Applies the change to the stored document of the user, None if there is no such user
+/// The columns and tables looked up by name, key or platform id are left as they are
+fn update_user<T>(
+    connection: &mut Connection,
+    key: &str,
+    change: impl FnOnce(&mut User) -> Result<T, ApiError>,
+) -> Result<Option<(User, T)>, ApiError> {
+    let transaction = connection.transaction()?;
+    let Some(mut user) = find_one::<User>(
+        &transaction,
+        "SELECT document FROM users WHERE key = ?1",
+        params![key],
+    )?
+    else {
+        return Ok(None);
+    };
+    let result = change(&mut user)?;
+    transaction.execute(
+        "UPDATE users SET document = ?2 WHERE key = ?1",
+        params![key, encode(&user)?],
+    )?;
+    transaction.commit()?;
+    Ok(Some((user, result)))
+}
+
+/// The name a unit enum variant is serialized as
+fn enum_name<T: Serialize>(value: &T) -> Result<String, ApiError> {
+    Ok(bson::to_bson(value)?
+        .as_str()
+        .unwrap_or_default()
+        .to_string())
+}
+
+/// Ids are generated here, since there is no database which assigns them
+fn with_id<T: Serialize>(
+    value: &T,
+    id: Option<ObjectId>,
+) -> Result<(ObjectId, Document), ApiError> {
+    let id = id.unwrap_or_default();
+    let mut document = bson::to_document(value)?;
+    document.insert("_id", id);
+    Ok((id, document))
+}
+
+fn write_session(connection: &Connection, session: &Session) -> Result<(), ApiError> {
+    let (id, document) = with_id(session, session.id)?;
+    connection.execute(
+        "INSERT INTO sessions (id, white_key, black_key, finished, created_stamp, document)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT (id) DO UPDATE SET finished = excluded.finished, document = excluded.document",
+        params![
+            id.to_hex(),
+            session.keys[0],
+            session.keys[1],
+            session.is_finished(),
+            session.created_stamp as i64,
+            encode(&document)?,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Stands in for the TTL index MongoDB uses, expired rooms are removed before rooms are read
+fn delete_expired_rooms(connection: &Connection) -> Result<u64, ApiError> {
+    let count = connection.execute(
+        "DELETE FROM rooms WHERE expires_at < ?1",
+        params![bson::DateTime::now().timestamp_millis()],
+    )?;
+    Ok(count as u64)
+}
+
+/// Makes user input match literally inside a LIKE pattern
+fn escape_like(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for character in input.chars() {
+        if "\\%_".contains(character) {
+            escaped.push('\\');
+        }
+        escaped.push(character);
+    }
+    escaped
+}
+
+impl SqliteStorage {
+    pub fn open(path: &str) -> Result<Self, ApiError> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        connection.execute_batch(LINK_LEGACY_DISCORD_IDS)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            broadcast: SessionBroadcast::new(),
+        })
+    }
+
+    /// Runs the operation on a blocking thread, SQLite calls would otherwise block the async runtime
+    async fn call<T, F>(&self, operation: F) -> Result<T, ApiError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, ApiError> + Send + 'static,
+    {
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection
+                .lock()
+                .map_err(|_| ApiError::ServerError("SQLite connection poisoned".to_string()))?;
+            operation(&mut connection)
+        })
+        .await
+        .map_err(|err| ApiError::ServerError(err.to_string()))?
+    }
+
+    /// Writes the user together with its secondary keys and platform links
+    /// Existing users are only updated, so a user deleted in the meantime isn't brought back
+    async fn write_user(&self, user: &User, new: bool) -> Result<(), ApiError> {
+        let row = (
+            user.key.clone(),
+            user.name.clone(),
+            user.namespace.clone(),
+            encode(user)?,
+        );
+        let secondary_keys: Vec<String> = user
+            .secondary_keys
+            .iter()
+            .map(|secondary_key| secondary_key.key.clone())
+            .collect();
+        let platform_links = user
+            .platform_links
+            .iter()
+            .map(|(platform, id)| Ok((enum_name(platform)?, id.clone())))
+            .collect::<Result<Vec<(String, String)>, ApiError>>()?;
+        self.call(move |connection| {
+            let transaction = connection.transaction()?;
+            if new {
+                transaction.execute(
+                    "INSERT INTO users (key, name, discord_id, namespace, document)
+                     VALUES (?1, ?2, '', ?3, ?4)",
+                    params![row.0, row.1, row.2, row.3],
+                )?;
+            } else if transaction.execute(
+                "UPDATE users SET name = ?2, discord_id = '', namespace = ?3, document = ?4
+                 WHERE key = ?1",
+                params![row.0, row.1, row.2, row.3],
+            )? == 0
+            {
+                return Ok(());
+            }
+            transaction.execute("DELETE FROM user_keys WHERE user_key = ?1", params![row.0])?;
+            for secondary_key in secondary_keys {
+                transaction.execute(
+                    "INSERT INTO user_keys (key, user_key) VALUES (?1, ?2)",
+                    params![secondary_key, row.0],
+                )?;
+            }
+            transaction.execute("DELETE FROM user_links WHERE user_key = ?1", params![row.0])?;
+            for (platform, id) in platform_links {
+                transaction.execute(
+                    "INSERT INTO user_links (platform, platform_id, namespace, user_key)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![platform, id, row.2, row.0],
+                )?;
+            }
+            transaction.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn find_session(&self, id: &str, archived: bool) -> Result<Option<Session>, ApiError> {
+        let id = ObjectId::parse_str(id)?.to_hex();
+        self.call(move |connection| {
+            find_one(
+                connection,
+                "SELECT document FROM sessions WHERE id = ?1 AND archived = ?2",
+                params![id, archived],
+            )
+        })
+        .await
+    }
+
+    async fn update_render_job(
+        &self,
+        job: &RenderJob,
+        status: RenderJobStatus,
+        document: Document,
+    ) -> Result<(), ApiError> {
+        let row = (
+            enum_name(&status)?,
+            encode(&document)?,
+            job.id.map(|id| id.to_hex()).unwrap_or_default(),
+        );
+        self.call(move |connection| {
+            connection.execute(
+                "UPDATE render_jobs SET status = ?1, document = ?2 WHERE id = ?3",
+                params![row.0, row.1, row.2],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn ping(&self) -> Result<(), ApiError> {
+        self.call(|connection| {
+            connection.query_row("SELECT 1", [], |_| Ok(()))?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn find_user_by_key(&self, key: &str) -> Result<Option<User>, ApiError> {
+        let key = key.to_string();
+        self.call(move |connection| {
+            find_one(
+                connection,
+                "SELECT document FROM users WHERE key = ?1",
+                params![key],
+            )
+        })
+        .await
+    }
+
+    async fn find_users_by_keys(&self, keys: &[String]) -> Result<Vec<User>, ApiError> {
+        let keys = serde_json::to_string(keys)
+            .map_err(|err| ApiError::SerializationError(err.to_string()))?;
+        self.call(move |connection| {
+            find_all(
+                connection,
+                "SELECT document FROM users WHERE key IN (SELECT value FROM json_each(?1))",
+                params![keys],
+            )
+        })
+        .await
+    }
+
+    async fn find_user_by_name(&self, name: &str) -> Result<Option<User>, ApiError> {
+        let name = name.to_lowercase();
+        self.call(move |connection| {
+            find_one(
+                connection,
+                "SELECT document FROM users WHERE name = ?1",
+                params![name],
+            )
+        })
+        .await
+    }
+
+    async fn find_user_by_platform_id(
+        &self,
+        namespace: &str,
+        platform: Platform,
+        id: &str,
+    ) -> Result<Option<User>, ApiError> {
+        let namespace = namespace.to_string();
+        let platform = enum_name(&platform)?;
+        let id = id.to_string();
+        self.call(move |connection| {
+            find_one(
+                connection,
+                "SELECT users.document FROM user_links JOIN users ON users.key = user_links.user_key
+                 WHERE user_links.platform = ?1 AND user_links.platform_id = ?2 AND user_links.namespace = ?3",
+                params![platform, id, namespace],
+            )
+        })
+        .await
+    }
+
+    async fn insert_user(&self, user: &User) -> Result<(), ApiError> {
+        self.write_user(user, true).await
+    }
+
+    async fn save_user(&self, user: &User) -> Result<(), ApiError> {
+        self.write_user(user, false).await
+    }
+
+    async fn rename_user(
+        &self,
+        key: &str,
+        name: Option<&str>,
+        display_name: Option<&str>,
+    ) -> Result<Option<User>, ApiError> {
+        let key = key.to_string();
+        let name = name.map(str::to_string);
+        let display_name = display_name.map(str::to_string);
+        self.call(move |connection| {
+            let transaction = connection.transaction()?;
+            let Some(mut user) = find_one::<User>(
+                &transaction,
+                "SELECT document FROM users WHERE key = ?1",
+                params![key],
+            )?
+            else {
+                return Ok(None);
+            };
+            user.rename(name.as_deref(), display_name.as_deref());
+            transaction.execute(
+                "UPDATE users SET name = ?2, document = ?3 WHERE key = ?1",
+                params![key, user.name, encode(&user)?],
+            )?;
+            transaction.commit()?;
+            Ok(Some(user))
+        })
+        .await
+    }
+
+    async fn set_user_permission(
+        &self,
+        key: &str,
+        permission: PermissionLevel,
+    ) -> Result<Option<User>, ApiError> {
+        let key = key.to_string();
+        self.call(move |connection| {
+            let updated = update_user(connection, &key, |user| {
+                user.permission = permission;
+                Ok(())
+            })?;
+            Ok(updated.map(|(user, _)| user))
+        })
+        .await
+    }
+
+    async fn set_user_banned(&self, key: &str, banned: bool) -> Result<Option<User>, ApiError> {
+        let key = key.to_string();
+        self.call(move |connection| {
+            let updated = update_user(connection, &key, |user| {
+                user.banned = banned;
+                Ok(())
+            })?;
+            Ok(updated.map(|(user, _)| user))
+        })
+        .await
+    }
+
+    async fn push_user_title(&self, key: &str, title: &Title) -> Result<Option<User>, ApiError> {
+        let key = key.to_string();
+        let title = title.clone();
+        self.call(move |connection| {
+            let updated = update_user(connection, &key, |user| user.award_title(title))?;
+            Ok(updated.map(|(user, _)| user))
+        })
+        .await
+    }
+
+    async fn pull_user_titles(
+        &self,
+        key: &str,
+        name: Option<&str>,
+    ) -> Result<Option<(User, usize)>, ApiError> {
+        let key = key.to_string();
+        let name = name.map(str::to_string);
+        self.call(move |connection| {
+            update_user(connection, &key, |user| user.reset_titles(name.as_deref()))
+        })
+        .await
+    }
+
+    async fn record_user_usage(
+        &self,
+        key: &str,
+        endpoint: &str,
+        access_stamp: u64,
+    ) -> Result<(), ApiError> {
+        let key = key.to_string();
+        let endpoint = endpoint.to_string();
+        self.call(move |connection| {
+            // Only the usage is changed on the stored document, a stale copy of the user is never written back
+            update_user(connection, &key, |user| {
+                user.last_access_stamp = access_stamp;
+                *user.endpoint_usage.entry(endpoint).or_insert(0) += 1;
+                Ok(())
+            })?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn push_secondary_key(
+        &self,
+        key: &str,
+        secondary_key: &SecondaryKey,
+    ) -> Result<bool, ApiError> {
+        let key = key.to_string();
+        let secondary_key = secondary_key.clone();
+        self.call(move |connection| {
+            let transaction = connection.transaction()?;
+            let Some(mut user) = find_one::<User>(
+                &transaction,
+                "SELECT document FROM users WHERE key = ?1",
+                params![key],
+            )?
+            else {
+                return Ok(false);
+            };
+            user.add_secondary_key(secondary_key.clone())?;
+            transaction.execute(
+                "UPDATE users SET document = ?2 WHERE key = ?1",
+                params![key, encode(&user)?],
+            )?;
+            transaction.execute(
+                "INSERT INTO user_keys (key, user_key) VALUES (?1, ?2)",
+                params![secondary_key.key, key],
+            )?;
+            transaction.commit()?;
+            Ok(true)
+        })
+        .await
+    }
+
+    async fn remove_secondary_key(
+        &self,
+        key: &str,
+        name: &str,
+    ) -> Result<Option<SecondaryKey>, ApiError> {
+        let key = key.to_string();
+        let name = name.to_string();
+        self.call(move |connection| {
+            let transaction = connection.transaction()?;
+            let Some(mut user) = find_one::<User>(
+                &transaction,
+                "SELECT document FROM users WHERE key = ?1",
+                params![key],
+            )?
+            else {
+                return Ok(None);
+            };
+            let Ok(secondary_key) = user.remove_secondary_key(&name) else {
+                return Ok(None);
+            };
+            transaction.execute(
+                "UPDATE users SET document = ?2 WHERE key = ?1",
+                params![key, encode(&user)?],
+            )?;
+            transaction.execute(
+                "DELETE FROM user_keys WHERE key = ?1",
+                params![secondary_key.key],
+            )?;
+            transaction.commit()?;
+            Ok(Some(secondary_key))
+        })
+        .await
+    }
+
+    async fn find_user_by_secondary_key(&self, key: &str) -> Result<Option<User>, ApiError> {
+        let key = key.to_string();
+        self.call(move |connection| {
+            find_one(
+                connection,
+                "SELECT users.document FROM users
+                 JOIN user_keys ON user_keys.user_key = users.key WHERE user_keys.key = ?1",
+                params![key],
+            )
+        })
+        .await
+    }
+
+    async fn find_users(
+        &self,
+        namespace: Option<&str>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<User>, u64), ApiError> {
+        let namespace = namespace.map(|namespace| namespace.to_string());
+        self.call(move |connection| {
+            let condition = "(?1 IS NULL OR namespace = ?1)";
+            let total: u64 = connection.query_row(
+                &format!("SELECT COUNT(*) FROM users WHERE {}", condition),
+                params![namespace],
+                |row| row.get(0),
+            )?;
+            let users = find_all(
+                connection,
+                &format!(
+                    "SELECT document FROM users WHERE {} ORDER BY rowid LIMIT ?2 OFFSET ?3",
+                    condition
+                ),
+                params![namespace, limit as i64, offset as i64],
+            )?;
+            Ok((users, total))
+        })
+        .await
+    }
+
+    async fn delete_user_by_key(&self, key: &str) -> Result<(), ApiError> {
+        let key = key.to_string();
+        self.call(move |connection| {
+            connection.execute("DELETE FROM user_keys WHERE user_key = ?1", params![key])?;
+            connection.execute("DELETE FROM user_links WHERE user_key = ?1", params![key])?;
+            connection.execute("DELETE FROM users WHERE key = ?1", params![key])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn find_session_by_id(&self, id: &str) -> Result<Option<Session>, ApiError> {
+        self.find_session(id, false).await
+    }
+
+    async fn find_archived_session_by_id(&self, id: &str) -> Result<Option<Session>, ApiError> {
+        self.find_session(id, true).await
+    }
+
+    async fn find_sessions_by_ids(&self, ids: &[String]) -> Result<Vec<Session>, ApiError> {
+        let ids = ids
+            .iter()
+            .map(|id| Ok(ObjectId::parse_str(id)?.to_hex()))
+            .collect::<Result<Vec<String>, ApiError>>()?;
+        let ids = serde_json::to_string(&ids)
+            .map_err(|err| ApiError::SerializationError(err.to_string()))?;
+        self.call(move |connection| {
+            find_all(
+                connection,
+                "SELECT document FROM sessions WHERE id IN (SELECT value FROM json_each(?1))",
+                params![ids],
+            )
+        })
+        .await
+    }
+
+    async fn find_active_session_by_keys(
+        &self,
+        keys: &[String; 2],
+    ) -> Result<Option<Session>, ApiError> {
+        let [first, second] = keys.clone();
+        self.call(move |connection| {
+            find_one(
+                connection,
+                "SELECT document FROM sessions
+                 WHERE finished = 0 AND archived = 0
+                 AND ((white_key = ?1 AND black_key = ?2) OR (white_key = ?2 AND black_key = ?1))",
+                params![first, second],
+            )
+        })
+        .await
+    }
+
+    async fn find_sessions_by_key_and_finished(
+        &self,
+        key: &str,
+        finished: bool,
+    ) -> Result<Vec<Session>, ApiError> {
+        let key = key.to_string();
+        self.call(move |connection| {
+            find_all(
+                connection,
+                "SELECT document FROM sessions
+                 WHERE (white_key = ?1 OR black_key = ?1) AND finished = ?2 AND archived = 0",
+                params![key, finished],
+            )
+        })
+        .await
+    }
+
+    async fn find_daily_activity(
+        &self,
+        key: &str,
+        from_stamp: u64,
+        to_stamp: u64,
+    ) -> Result<Vec<DailyActivity>, ApiError> {
+        let key = key.to_string();
+        self.call(move |connection| {
+            let sessions: Vec<Session> = find_all(
+                connection,
+                "SELECT document FROM sessions
+                 WHERE (white_key = ?1 OR black_key = ?1) AND finished = 1
+                 AND created_stamp >= ?2 AND created_stamp < ?3",
+                params![key, from_stamp as i64, to_stamp as i64],
+            )?;
+            Ok(DailyActivity::aggregate(&key, &sessions))
+        })
+        .await
+    }
+
+    async fn find_sessions_by_key(
+        &self,
+        key: &str,
+        filter: &SessionFilter,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<Session>, u64), ApiError> {
+        let key = key.to_string();
+        let filter = filter.clone();
+        self.call(move |connection| {
+            // Narrowed down by the columns, the rest is only in the document
+            let (from, to) = filter.created.unwrap_or((0, i64::MAX as u64));
+            let sessions: Vec<Session> = find_all(
+                connection,
+                "SELECT document FROM sessions
+                 WHERE (white_key = ?1 OR black_key = ?1) AND (archived = 0 OR ?2)
+                 AND (?3 IS NULL OR finished = ?3) AND created_stamp >= ?4 AND created_stamp < ?5
+                 ORDER BY archived, rowid",
+                params![
+                    key,
+                    filter.include_archived,
+                    filter.finished,
+                    from as i64,
+                    to.min(i64::MAX as u64) as i64
+                ],
+            )?;
+            let mut sessions: Vec<Session> = sessions
+                .into_iter()
+                .filter(|session| filter.matches(&key, session))
+                .collect();
+            filter.sort(&mut sessions);
+            Ok(paginate(sessions, offset, limit))
+        })
+        .await
+    }
+
+    async fn save_session(&self, session: &Session) -> Result<(), ApiError> {
+        let stored = session.clone();
+        self.call(move |connection| write_session(connection, &stored))
+            .await?;
+        self.broadcast.publish(session);
+        Ok(())
+    }
+
+    async fn watch_sessions(&self) -> Result<SessionStream, ApiError> {
+        Ok(self.broadcast.watch())
+    }
+
+    async fn set_session_accuracy(
+        &self,
+        id: &str,
+        accuracy: &[PlayerAccuracy; 2],
+    ) -> Result<(), ApiError> {
+        let id = ObjectId::parse_str(id)?.to_hex();
+        let accuracy = bson::to_bson(accuracy)?;
+        self.call(move |connection| {
+            // Only the field is changed on the stored document, a stale copy of the session is never written back
+            let Some(mut document) = find_one::<Document>(
+                connection,
+                "SELECT document FROM sessions WHERE id = ?1",
+                params![id],
+            )?
+            else {
+                return Ok(());
+            };
+            if !matches!(document.get("accuracy"), None | Some(Bson::Null)) {
+                return Ok(());
+            }
+            document.insert("accuracy", accuracy);
+            connection.execute(
+                "UPDATE sessions SET document = ?2 WHERE id = ?1",
+                params![id, encode(&document)?],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn archive_finished_sessions(&self, created_before: u64) -> Result<u64, ApiError> {
+        self.call(move |connection| {
+            let count = connection.execute(
+                "UPDATE sessions SET archived = 1
+                 WHERE archived = 0 AND finished = 1 AND created_stamp < ?1",
+                params![created_before as i64],
+            )?;
+            Ok(count as u64)
+        })
+        .await
+    }
+
+    async fn find_running_timed_sessions(&self) -> Result<Vec<Session>, ApiError> {
+        self.call(move |connection| {
+            let sessions: Vec<Session> = find_all(
+                connection,
+                "SELECT document FROM sessions WHERE finished = 0 AND archived = 0",
+                [],
+            )?;
+            Ok(sessions
+                .into_iter()
+                .filter(|session| !session.paused && session.time_control.is_some())
+                .collect())
+        })
+        .await
+    }
+
+    async fn find_failed_ai_sessions(&self) -> Result<Vec<Session>, ApiError> {
+        self.call(move |connection| {
+            let sessions: Vec<Session> = find_all(
+                connection,
+                "SELECT document FROM sessions WHERE finished = 0 AND archived = 0",
+                [],
+            )?;
+            Ok(sessions
+                .into_iter()
+                .filter(|session| session.ai_error.is_some())
+                .collect())
+        })
+        .await
+    }
+
+    async fn delete_expired_rooms(&self) -> Result<u64, ApiError> {
+        self.call(|connection| delete_expired_rooms(connection))
+            .await
+    }
+
+    async fn find_room_by_code(&self, code: &str) -> Result<Option<Room>, ApiError> {
+        let code = code.to_uppercase();
+        self.call(move |connection| {
+            delete_expired_rooms(connection)?;
+            find_one(
+                connection,
+                "SELECT document FROM rooms WHERE code = ?1",
+                params![code],
+            )
+        })
+        .await
+    }
+
+    async fn find_rooms_by_key(&self, key: &str) -> Result<Vec<Room>, ApiError> {
+        let key = key.to_string();
+        self.call(move |connection| {
+            delete_expired_rooms(connection)?;
+            find_all(
+                connection,
+                "SELECT document FROM rooms WHERE key = ?1",
+                params![key],
+            )
+        })
+        .await
+    }
+
+    async fn find_rooms(
+        &self,
+        selection: RoomSelection<'_>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<Room>, u64), ApiError> {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        let mut order = "rowid";
+        match selection {
+            RoomSelection::Owner(key) => {
+                conditions.push("key = ?");
+                values.push(Value::Text(key.to_string()));
+            }
+            RoomSelection::Invited(key) => {
+                conditions.push("invited_key = ?");
+                values.push(Value::Text(key.to_string()));
+            }
+            RoomSelection::Public { namespace, filter } => {
+                conditions.push("public = 1");
+                if let Some(namespace) = namespace {
+                    conditions.push("namespace = ?");
+                    values.push(Value::Text(namespace.to_string()));
+                }
+                if let Some(search) = &filter.search {
+                    conditions.push("name LIKE ? ESCAPE '\\'");
+                    values.push(Value::Text(format!("%{}%", escape_like(search))));
+                }
+                if let Some(created_after) = filter.created_after {
+                    conditions.push("created_stamp > ?");
+                    values.push(Value::Integer(created_after as i64));
+                }
+                order = match filter.sort.unwrap_or_default() {
+                    RoomSort::NEWEST => "created_stamp DESC",
+                    RoomSort::OLDEST => "created_stamp ASC",
+                };
+            }
+        }
+        let condition = conditions.join(" AND ");
+
+        self.call(move |connection| {
+            delete_expired_rooms(connection)?;
+            let total: u64 = connection.query_row(
+                &format!("SELECT COUNT(*) FROM rooms WHERE {}", condition),
+                params_from_iter(&values),
+                |row| row.get(0),
+            )?;
+            values.push(Value::Integer(limit as i64));
+            values.push(Value::Integer(offset as i64));
+            let rooms = find_all(
+                connection,
+                &format!(
+                    "SELECT document FROM rooms WHERE {} ORDER BY {} LIMIT ? OFFSET ?",
+                    condition, order
+                ),
+                params_from_iter(&values),
+            )?;
+            Ok((rooms, total))
+        })
+        .await
+    }
+
+    async fn save_room(&self, room: &Room) -> Result<(), ApiError> {
+        let row = (
+            room.code.clone(),
+            room.key.clone(),
+            room.invited_key.clone(),
+            room.namespace.clone(),
+            room.public,
+            room.name.clone(),
+            room.created_stamp as i64,
+            room.expires_at
+                .map(|expires_at| expires_at.timestamp_millis()),
+            encode(room)?,
+        );
+        self.call(move |connection| {
+            connection.execute(
+                "INSERT OR REPLACE INTO rooms
+                 (code, key, invited_key, namespace, public, name, created_stamp, expires_at, document)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![row.0, row.1, row.2, row.3, row.4, row.5, row.6, row.7, row.8],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_room_by_code(&self, code: &str) -> Result<(), ApiError> {
+        let code = code.to_string();
+        self.call(move |connection| {
+            connection.execute("DELETE FROM rooms WHERE code = ?1", params![code])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn start_room_session(&self, code: &str, session: &Session) -> Result<(), ApiError> {
+        let code = code.to_string();
+        let session = session.clone();
+        self.call(move |connection| {
+            // Dropping the transaction without committing rolls it back
+            let transaction =
+                connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            transaction.execute("DELETE FROM rooms WHERE code = ?1", params![code])?;
+            write_session(&transaction, &session)?;
+            transaction.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn insert_render_job(&self, job: &mut RenderJob) -> Result<(), ApiError> {
+        let (id, document) = with_id(job, None)?;
+        let row = (
+            id.to_hex(),
+            enum_name(&job.status)?,
+            job.created_stamp as i64,
+            encode(&document)?,
+        );
+        self.call(move |connection| {
+            connection.execute(
+                "INSERT INTO render_jobs (id, status, created_stamp, document) VALUES (?1, ?2, ?3, ?4)",
+                params![row.0, row.1, row.2, row.3],
+            )?;
+            Ok(())
+        })
+        .await?;
+        job.id = Some(id);
+        Ok(())
+    }
+
+    async fn find_render_job_by_id(&self, id: &str) -> Result<Option<RenderJob>, ApiError> {
+        let id = ObjectId::parse_str(id)?.to_hex();
+        self.call(move |connection| {
+            find_one(
+                connection,
+                "SELECT document FROM render_jobs WHERE id = ?1",
+                params![id],
+            )
+        })
+        .await
+    }
+
+    async fn claim_next_render_job(&self) -> Result<Option<RenderJob>, ApiError> {
+        let queued = enum_name(&RenderJobStatus::QUEUED)?;
+        let running = enum_name(&RenderJobStatus::RUNNING)?;
+        self.call(move |connection| {
+            let now = timestamp_now_nanos();
+            let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let job: Option<RenderJob> = find_one(
+                &transaction,
+                "SELECT document FROM render_jobs
+                 WHERE status = ?1 OR (status = ?2 AND started_stamp < ?3)
+                 ORDER BY created_stamp LIMIT 1",
+                params![queued, running, now.saturating_sub(STALE_AFTER_NANOS) as i64],
+            )?;
+            let Some(mut job) = job else {
+                return Ok(None);
+            };
+
+            job.status = RenderJobStatus::RUNNING;
+            job.started_stamp = Some(now);
+            transaction.execute(
+                "UPDATE render_jobs SET status = ?1, started_stamp = ?2, document = ?3 WHERE id = ?4",
+                params![
+                    running,
+                    now as i64,
+                    encode(&job)?,
+                    job.id.map(|id| id.to_hex()).unwrap_or_default()
+                ],
+            )?;
+            transaction.commit()?;
+            Ok(Some(job))
+        })
+        .await
+    }
+
+    async fn complete_render_job(
+        &self,
+        job: &RenderJob,
+        result_id: ObjectId,
+    ) -> Result<(), ApiError> {
+        let mut document = bson::to_document(job)?;
+        document.insert("status", bson::to_bson(&RenderJobStatus::DONE)?);
+        document.insert("result_id", result_id);
+        self.update_render_job(job, RenderJobStatus::DONE, document)
+            .await
+    }
+
+    async fn fail_render_job(&self, job: &RenderJob, error: &str) -> Result<(), ApiError> {
+        let mut document = bson::to_document(job)?;
+        document.insert("status", bson::to_bson(&RenderJobStatus::FAILED)?);
+        document.insert("error", error);
+        self.update_render_job(job, RenderJobStatus::FAILED, document)
+            .await
+    }
+
+    async fn delete_expired_render_jobs(&self) -> Result<u64, ApiError> {
+        let expired_before = timestamp_now_nanos().saturating_sub(EXPIRE_AFTER_NANOS) as i64;
+        self.call(move |connection| {
+            let transaction = connection.transaction()?;
+            let jobs: Vec<RenderJob> = find_all(
+                &transaction,
+                "SELECT document FROM render_jobs WHERE created_stamp < ?1",
+                params![expired_before],
+            )?;
+            for result_id in jobs.iter().filter_map(|job| job.result_id) {
+                transaction.execute(
+                    "DELETE FROM render_results WHERE id = ?1",
+                    params![result_id.to_hex()],
+                )?;
+            }
+            let count = transaction.execute(
+                "DELETE FROM render_jobs WHERE created_stamp < ?1",
+                params![expired_before],
+            )?;
+            transaction.commit()?;
+            Ok(count as u64)
+        })
+        .await
+    }
+
+    async fn store_render_result(&self, name: &str, bytes: Vec<u8>) -> Result<ObjectId, ApiError> {
+        let id = ObjectId::new();
+        let name = name.to_string();
+        self.call(move |connection| {
+            connection.execute(
+                "INSERT INTO render_results (id, name, data) VALUES (?1, ?2, ?3)",
+                params![id.to_hex(), name, bytes],
+            )?;
+            Ok(id)
+        })
+        .await
+    }
+
+    async fn load_render_result(&self, id: ObjectId) -> Result<Vec<u8>, ApiError> {
+        self.call(move |connection| {
+            connection
+                .query_row(
+                    "SELECT data FROM render_results WHERE id = ?1",
+                    params![id.to_hex()],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .ok_or(ApiError::NotFound("Render result not found".to_string()))
+        })
+        .await
+    }
+
+    async fn find_friendship(
+        &self,
+        first_key: &str,
+        second_key: &str,
+    ) -> Result<Option<Friendship>, ApiError> {
+        let first_key = first_key.to_string();
+        let second_key = second_key.to_string();
+        self.call(move |connection| {
+            find_one(
+                connection,
+                "SELECT document FROM friendships
+                 WHERE (requester = ?1 AND addressee = ?2) OR (requester = ?2 AND addressee = ?1)",
+                params![first_key, second_key],
+            )
+        })
+        .await
+    }
+
+    async fn find_friendships_by_key(&self, key: &str) -> Result<Vec<Friendship>, ApiError> {
+        let key = key.to_string();
+        self.call(move |connection| {
+            find_all(
+                connection,
+                "SELECT document FROM friendships WHERE requester = ?1 OR addressee = ?1",
+                params![key],
+            )
+        })
+        .await
+    }
+
+    async fn save_friendship(&self, friendship: &Friendship) -> Result<(), ApiError> {
+        let (id, document) = with_id(friendship, friendship.id)?;
+        let row = (
+            id.to_hex(),
+            friendship.requester.clone(),
+            friendship.addressee.clone(),
+            encode(&document)?,
+        );
+        self.call(move |connection| {
+            connection.execute(
+                "INSERT INTO friendships (id, requester, addressee, document) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (id) DO UPDATE SET document = excluded.document",
+                params![row.0, row.1, row.2, row.3],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_friendship(&self, friendship: &Friendship) -> Result<(), ApiError> {
+        let id = friendship.id.map(|id| id.to_hex()).unwrap_or_default();
+        self.call(move |connection| {
+            connection.execute("DELETE FROM friendships WHERE id = ?1", params![id])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn insert_audit_entry(&self, entry: &AuditEntry) -> Result<(), ApiError> {
+        let (id, document) = with_id(entry, entry.id)?;
+        let row = (
+            id.to_hex(),
+            enum_name(&entry.action)?,
+            entry.actor.clone(),
+            entry.target.clone(),
+            entry.created_stamp as i64,
+            encode(&document)?,
+        );
+        self.call(move |connection| {
+            connection.execute(
+                "INSERT INTO audit_log (id, action, actor, target, created_stamp, document)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![row.0, row.1, row.2, row.3, row.4, row.5],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn find_audit_entries(
+        &self,
+        query: &AuditLogQuery,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<AuditEntry>, u64), ApiError> {
+        let action = query.action.map(|action| enum_name(&action)).transpose()?;
+        let user = query.user.as_ref().map(|user| user.to_lowercase());
+        self.call(move |connection| {
+            let condition = "(?1 IS NULL OR action = ?1) AND (?2 IS NULL OR actor = ?2 OR target = ?2)";
+            let total: u64 = connection.query_row(
+                &format!("SELECT COUNT(*) FROM audit_log WHERE {}", condition),
+                params![action, user],
+                |row| row.get(0),
+            )?;
+            let entries = find_all(
+                connection,
+                &format!(
+                    "SELECT document FROM audit_log WHERE {} ORDER BY created_stamp DESC LIMIT ?3 OFFSET ?4",
+                    condition
+                ),
+                params![action, user, limit as i64, offset as i64],
+            )?;
+            Ok((entries, total))
+        })
+        .await
+    }
+
+    async fn insert_notification(&self, notification: &Notification) -> Result<(), ApiError> {
+        let (id, document) = with_id(notification, notification.id)?;
+        let row = (
+            id.to_hex(),
+            notification.key.clone(),
+            notification.read,
+            notification.created_stamp as i64,
+            encode(&document)?,
+        );
+        self.call(move |connection| {
+            connection.execute(
+                "INSERT INTO notifications (id, key, read, created_stamp, document)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![row.0, row.1, row.2, row.3, row.4],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn find_notifications(
+        &self,
+        key: &str,
+        unread_only: bool,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<Notification>, u64), ApiError> {
+        let key = key.to_string();
+        self.call(move |connection| {
+            let condition = "key = ?1 AND (?2 = 0 OR read = 0)";
+            let total: u64 = connection.query_row(
+                &format!("SELECT COUNT(*) FROM notifications WHERE {}", condition),
+                params![key, unread_only],
+                |row| row.get(0),
+            )?;
+            let notifications = find_all(
+                connection,
+                &format!(
+                    "SELECT document FROM notifications WHERE {} ORDER BY created_stamp DESC LIMIT ?3 OFFSET ?4",
+                    condition
+                ),
+                params![key, unread_only, limit as i64, offset as i64],
+            )?;
+            Ok((notifications, total))
+        })
+        .await
+    }
+
+    async fn count_unread_notifications(&self, key: &str) -> Result<u64, ApiError> {
+        let key = key.to_string();
+        self.call(move |connection| {
+            let count = connection.query_row(
+                "SELECT COUNT(*) FROM notifications WHERE key = ?1 AND read = 0",
+                params![key],
+                |row| row.get(0),
+            )?;
+            Ok(count)
+        })
+        .await
+    }
+
+    async fn mark_notifications_read(&self, key: &str, id: Option<&str>) -> Result<u64, ApiError> {
+        let key = key.to_string();
+        let id = id
+            .map(ObjectId::parse_str)
+            .transpose()?
+            .map(|id| id.to_hex());
+        self.call(move |connection| {
+            let transaction = connection.transaction()?;
+            let notifications: Vec<Notification> = find_all(
+                &transaction,
+                "SELECT document FROM notifications WHERE key = ?1 AND read = 0 AND (?2 IS NULL OR id = ?2)",
+                params![key, id],
+            )?;
+            for mut notification in notifications.iter().cloned() {
+                notification.read = true;
+                let (id, document) = with_id(&notification, notification.id)?;
+                transaction.execute(
+                    "UPDATE notifications SET read = 1, document = ?2 WHERE id = ?1",
+                    params![id.to_hex(), encode(&document)?],
+                )?;
+            }
+            transaction.commit()?;
+            Ok(notifications.len() as u64)
+        })
+        .await
+    }
+
+    async fn delete_notifications_by_key(&self, key: &str) -> Result<(), ApiError> {
+        let key = key.to_string();
+        self.call(move |connection| {
+            connection.execute("DELETE FROM notifications WHERE key = ?1", params![key])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn find_session_mark(
+        &self,
+        key: &str,
+        session_id: &str,
+    ) -> Result<Option<SessionMark>, ApiError> {
+        let (key, session_id) = (key.to_string(), session_id.to_string());
+        self.call(move |connection| {
+            find_one(
+                connection,
+                "SELECT document FROM session_marks WHERE key = ?1 AND session_id = ?2",
+                params![key, session_id],
+            )
+        })
+        .await
+    }
+
+    async fn find_session_marks_by_key(&self, key: &str) -> Result<Vec<SessionMark>, ApiError> {
+        let key = key.to_string();
+        self.call(move |connection| {
+            find_all(
+                connection,
+                "SELECT document FROM session_marks WHERE key = ?1 ORDER BY updated_stamp DESC",
+                params![key],
+            )
+        })
+        .await
+    }
+
+    async fn save_session_mark(&self, mark: &SessionMark) -> Result<(), ApiError> {
+        let row = (
+            mark.key.clone(),
+            mark.session_id.clone(),
+            mark.updated_stamp as i64,
+            encode(mark)?,
+        );
+        self.call(move |connection| {
+            connection.execute(
+                "INSERT INTO session_marks (key, session_id, updated_stamp, document) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (key, session_id) DO UPDATE SET updated_stamp = excluded.updated_stamp, document = excluded.document",
+                params![row.0, row.1, row.2, row.3],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_session_mark(&self, key: &str, session_id: &str) -> Result<(), ApiError> {
+        let (key, session_id) = (key.to_string(), session_id.to_string());
+        self.call(move |connection| {
+            connection.execute(
+                "DELETE FROM session_marks WHERE key = ?1 AND session_id = ?2",
+                params![key, session_id],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_session_marks_by_key(&self, key: &str) -> Result<(), ApiError> {
+        let key = key.to_string();
+        self.call(move |connection| {
+            connection.execute("DELETE FROM session_marks WHERE key = ?1", params![key])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn find_webhook_by_key(&self, key: &str) -> Result<Option<Webhook>, ApiError> {
+        let key = key.to_string();
+        self.call(move |connection| {
+            find_one(
+                connection,
+                "SELECT document FROM webhooks WHERE key = ?1",
+                params![key],
+            )
+        })
+        .await
+    }
+
+    async fn find_webhooks_by_namespace(&self, namespace: &str) -> Result<Vec<Webhook>, ApiError> {
+        let namespace = namespace.to_string();
+        self.call(move |connection| {
+            find_all(
+                connection,
+                "SELECT document FROM webhooks WHERE namespace = ?1",
+                params![namespace],
+            )
+        })
+        .await
+    }
+
+    async fn save_webhook(&self, webhook: &Webhook) -> Result<(), ApiError> {
+        let row = (
+            webhook.key.clone(),
+            webhook.namespace.clone(),
+            encode(webhook)?,
+        );
+        self.call(move |connection| {
+            connection.execute(
+                "INSERT INTO webhooks (key, namespace, document) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (key) DO UPDATE SET namespace = excluded.namespace, document = excluded.document",
+                params![row.0, row.1, row.2],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_webhook_by_key(&self, key: &str) -> Result<(), ApiError> {
+        let key = key.to_string();
+        self.call(move |connection| {
+            connection.execute("DELETE FROM webhooks WHERE key = ?1", params![key])?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game::{color::Color, state::GameState},
+        models::{
+            audit_models::AuditAction,
+            enums::{ColorPreference, KeyScope},
+            notification_models::NotificationKind,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_room_session_roundtrip() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        let room = Room::new(
+            "ABCDEF".to_string(),
+            "owner".to_string(),
+            String::new(),
+            "Room".to_string(),
+            true,
+            ColorPreference::RANDOM,
+            None,
+        );
+        storage.save_room(&room).await.unwrap();
+        let (rooms, total) = storage
+            .find_rooms(RoomSelection::Owner("owner"), 0, 10)
+            .await
+            .unwrap();
+        assert_eq!((rooms.len(), total), (1, 1));
+
+        let keys = ["owner".to_string(), "guest".to_string()];
+        let session = Session::new(room.name.clone(), keys.clone(), GameState::new().unwrap());
+        storage
+            .start_room_session(&room.code, &session)
+            .await
+            .unwrap();
+        assert!(storage
+            .find_room_by_code(&room.code)
+            .await
+            .unwrap()
+            .is_none());
+
+        let active = storage
+            .find_active_session_by_keys(&[keys[1].clone(), keys[0].clone()])
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(active.id.is_some());
+        let (sessions, total) = storage
+            .find_sessions_by_key("guest", &SessionFilter::default(), 0, 10)
+            .await
+            .unwrap();
+        assert_eq!((sessions.len(), total), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_daily_activity() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        let day = 24 * 60 * 60 * 1_000_000_000;
+        let keys = ["lemon".to_string(), "lime".to_string()];
+        for (created_stamp, loser) in [
+            (day, Some(Color::BLACK)),
+            (day + 1, Some(Color::WHITE)),
+            (3 * day, Some(Color::BLACK)),
+            (3 * day, None),
+            (5 * day, Some(Color::BLACK)),
+        ] {
+            let mut session = Session::new(String::new(), keys.clone(), GameState::new().unwrap());
+            session.created_stamp = created_stamp;
+            if let Some(loser) = loser {
+                session.resign(loser).unwrap();
+            }
+            storage.save_session(&session).await.unwrap();
+        }
+
+        let activity = storage
+            .find_daily_activity("lemon", day, 5 * day)
+            .await
+            .unwrap();
+        assert_eq!(
+            activity,
+            [
+                DailyActivity {
+                    date: "1970-01-02".to_string(),
+                    games: 2,
+                    wins: 1,
+                },
+                DailyActivity {
+                    date: "1970-01-04".to_string(),
+                    games: 1,
+                    wins: 1,
+                },
+            ]
+        );
+        let activity = storage
+            .find_daily_activity("lime", 0, 6 * day)
+            .await
+            .unwrap();
+        assert_eq!(activity[0].wins, 1);
+        assert_eq!(activity[2].wins, 0);
+    }
+
+    #[tokio::test]
+    async fn test_batch_lookups() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        let lemon = User::new_from_platform(&storage, "", Platform::DISCORD, "lemon", "Lemon", "1")
+            .await
+            .unwrap();
+        let keys = [lemon.key.clone(), "unknown".to_string()];
+        let users = storage.find_users_by_keys(&keys).await.unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].name, "lemon");
+
+        let mut ids = Vec::new();
+        for resigned in [true, false] {
+            let mut session = Session::new(String::new(), keys.clone(), GameState::new().unwrap());
+            session.id = Some(ObjectId::new());
+            session.created_stamp = 0;
+            if resigned {
+                session.resign(Color::WHITE).unwrap();
+            }
+            storage.save_session(&session).await.unwrap();
+            ids.push(session.id.unwrap().to_hex());
+        }
+        assert_eq!(storage.archive_finished_sessions(1).await.unwrap(), 1);
+
+        ids.push(ObjectId::new().to_hex());
+        let sessions = storage.find_sessions_by_ids(&ids).await.unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert!(storage
+            .find_sessions_by_ids(&["invalid".to_string()])
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_session_accuracy() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        let keys = ["lemon".to_string(), "lime".to_string()];
+        let mut session = Session::new("Old".to_string(), keys, GameState::new().unwrap());
+        session.id = Some(ObjectId::new());
+        storage.save_session(&session).await.unwrap();
+        let id = session.id.unwrap().to_hex();
+
+        // Renamed while the outdated copy was reviewed
+        session.name = "New".to_string();
+        storage.save_session(&session).await.unwrap();
+        let accuracy = |accuracy| PlayerAccuracy { accuracy, acpl: 10 };
+        storage
+            .set_session_accuracy(&id, &[accuracy(90.0), accuracy(80.0)])
+            .await
+            .unwrap();
+        storage
+            .set_session_accuracy(&id, &[accuracy(10.0), accuracy(10.0)])
+            .await
+            .unwrap();
+
+        let stored = storage.find_session_by_id(&id).await.unwrap().unwrap();
+        assert_eq!(stored.name, "New");
+        assert_eq!(stored.accuracy, Some([accuracy(90.0), accuracy(80.0)]));
+    }
+
+    #[tokio::test]
+    async fn test_user_usage() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        let mut lemon =
+            User::new_from_platform(&storage, "", Platform::DISCORD, "lemon", "Lemon", "1")
+                .await
+                .unwrap();
+
+        // Banned while the request of the outdated copy was running
+        let outdated = lemon.clone();
+        lemon.banned = true;
+        storage.save_user(&lemon).await.unwrap();
+        for _ in 0..2 {
+            storage
+                .record_user_usage(&outdated.key, "GET /session/a.b", 42)
+                .await
+                .unwrap();
+        }
+        let stored = storage.find_user_by_key(&lemon.key).await.unwrap().unwrap();
+        assert!(stored.banned);
+        assert_eq!(stored.last_access_stamp, 42);
+        assert_eq!(stored.endpoint_usage["GET /session/a.b"], 2);
+
+        // Deleted users stay deleted
+        storage.delete_user_by_key(&lemon.key).await.unwrap();
+        storage
+            .record_user_usage(&lemon.key, "GET /user", 43)
+            .await
+            .unwrap();
+        assert!(storage
+            .find_user_by_key(&lemon.key)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_user_updates() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        let lemon = User::new_from_platform(&storage, "", Platform::DISCORD, "lemon", "Lemon", "1")
+            .await
+            .unwrap();
+        assert!(storage.insert_user(&lemon).await.is_err());
+
+        // Each update only touches its own field
+        let admin = storage
+            .set_user_permission(&lemon.key, PermissionLevel::Admin)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(admin.permission, PermissionLevel::Admin);
+        let banned = storage.set_user_banned(&lemon.key, true).await.unwrap();
+        assert_eq!(banned.unwrap().permission, PermissionLevel::Admin);
+        let title = lemon.new_title("Champion").unwrap();
+        let awarded = storage.push_user_title(&lemon.key, &title).await.unwrap();
+        assert!(awarded.unwrap().banned);
+        assert!(storage.push_user_title(&lemon.key, &title).await.is_err());
+        let (stored, removed) = storage
+            .pull_user_titles(&lemon.key, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(removed, 1);
+        assert!(stored.titles.is_empty() && stored.banned);
+        assert!(storage
+            .pull_user_titles(&lemon.key, Some("Champion"))
+            .await
+            .is_err());
+
+        let renamed = storage
+            .rename_user(&lemon.key, Some("lime"), None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((renamed.name.as_str(), renamed.banned), ("lime", true));
+        assert_eq!(renamed.display_name, "Lemon");
+        let found = storage.find_user_by_name("lime").await.unwrap();
+        assert_eq!(found.map(|user| user.key), Some(lemon.key.clone()));
+
+        // Deleted users aren't brought back by a late save or update
+        storage.delete_user_by_key(&lemon.key).await.unwrap();
+        storage.save_user(&lemon).await.unwrap();
+        let unbanned = storage.set_user_banned(&lemon.key, false).await.unwrap();
+        assert!(unbanned.is_none());
+        assert!(storage
+            .find_user_by_key(&lemon.key)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_secondary_keys() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        let lemon = User::new_from_platform(&storage, "", Platform::DISCORD, "lemon", "Lemon", "1")
+            .await
+            .unwrap();
+        let bot = lemon
+            .new_secondary_key("bot".to_string(), KeyScope::PlayMoves)
+            .unwrap();
+        assert!(storage.push_secondary_key(&lemon.key, &bot).await.unwrap());
+        let found = storage.find_user_by_secondary_key(&bot.key).await.unwrap();
+        assert_eq!(found.map(|user| user.key), Some(lemon.key.clone()));
+        let duplicate = lemon
+            .new_secondary_key("bot".to_string(), KeyScope::ReadOnly)
+            .unwrap();
+        assert!(storage
+            .push_secondary_key(&lemon.key, &duplicate)
+            .await
+            .is_err());
+        assert!(!storage.push_secondary_key("nobody", &bot).await.unwrap());
+
+        let removed = storage
+            .remove_secondary_key(&lemon.key, "bot")
+            .await
+            .unwrap();
+        assert_eq!(removed.map(|key| key.key), Some(bot.key.clone()));
+        assert!(storage
+            .find_user_by_secondary_key(&bot.key)
+            .await
+            .unwrap()
+            .is_none());
+        let stored = storage.find_user_by_key(&lemon.key).await.unwrap().unwrap();
+        assert!(stored.secondary_keys.is_empty());
+        assert!(storage
+            .remove_secondary_key(&lemon.key, "bot")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_audit_log() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        let admin = User::new_from_platform(&storage, "", Platform::DISCORD, "admin", "Admin", "1")
+            .await
+            .unwrap();
+        for action in [AuditAction::UserBanned, AuditAction::UserUnbanned] {
+            AuditEntry::new(action, &admin)
+                .target("lemon")
+                .record(&storage)
+                .await;
+        }
+
+        let query = AuditLogQuery {
+            action: Some(AuditAction::UserBanned),
+            user: Some("LEMON".to_string()),
+        };
+        let (entries, total) = storage.find_audit_entries(&query, 0, 10).await.unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(entries[0].action, AuditAction::UserBanned);
+    }
+
+    #[tokio::test]
+    async fn test_platform_links() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        let legacy = bson::doc! {
+            "key": "legacy",
+            "name": "legacy",
+            "display_name": "Legacy",
+            "created_stamp": 0_i64,
+            "permission": "User",
+            "discord_id": "42",
+        };
+        {
+            let connection = storage.connection.lock().unwrap();
+            connection
+                .execute(
+                    "INSERT INTO users (key, name, discord_id, namespace, document)
+                     VALUES ('legacy', 'legacy', '42', '', ?1)",
+                    params![encode(&legacy).unwrap()],
+                )
+                .unwrap();
+            connection.execute_batch(LINK_LEGACY_DISCORD_IDS).unwrap();
+        }
+
+        let mut user = storage
+            .find_user_by_platform_id("", Platform::DISCORD, "42")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.platform_links[&Platform::DISCORD], "42");
+
+        user.platform_links.remove(&Platform::DISCORD);
+        user.platform_links
+            .insert(Platform::TELEGRAM, "7".to_string());
+        storage.save_user(&user).await.unwrap();
+        storage
+            .connection
+            .lock()
+            .unwrap()
+            .execute_batch(LINK_LEGACY_DISCORD_IDS)
+            .unwrap();
+
+        let discord = storage.find_user_by_platform_id("", Platform::DISCORD, "42");
+        assert!(discord.await.unwrap().is_none());
+        let telegram = storage.find_user_by_platform_id("", Platform::TELEGRAM, "7");
+        assert_eq!(telegram.await.unwrap().unwrap().key, "legacy");
+        let other_namespace = storage.find_user_by_platform_id("lemon", Platform::TELEGRAM, "7");
+        assert!(other_namespace.await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_notifications() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        for kind in [NotificationKind::YourTurn, NotificationKind::GameFinished] {
+            Notification::new("lemon", kind, "Test")
+                .send(&storage)
+                .await;
+        }
+        Notification::new("lime", NotificationKind::YourTurn, "Test")
+            .send(&storage)
+            .await;
+
+        let (notifications, total) = storage
+            .find_notifications("lemon", true, 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(total, 2);
+        let id = notifications[0].id.unwrap().to_hex();
+
+        assert_eq!(
+            storage
+                .mark_notifications_read("lime", Some(&id))
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            storage
+                .mark_notifications_read("lemon", Some(&id))
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            storage.count_unread_notifications("lemon").await.unwrap(),
+            1
+        );
+        assert_eq!(
+            storage
+                .mark_notifications_read("lemon", None)
+                .await
+                .unwrap(),
+            1
+        );
+
+        let (notifications, total) = storage
+            .find_notifications("lemon", false, 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(total, 2);
+        assert!(notifications.iter().all(|notification| notification.read));
+        assert_eq!(storage.count_unread_notifications("lime").await.unwrap(), 1);
+    }
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("100% lemon_"), "100\\% lemon\\_");
+    }
+}
@@ -0,0 +1,90 @@
+use std::env;
+
+use axum::{extract::Request, http::HeaderMap, middleware::Next, response::Response};
+use opentelemetry::{global, propagation::Extractor, trace::TracerProvider};
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider, Resource};
+use tracing::{field, info_span, Instrument};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{
+    filter::LevelFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt, Layer,
+};
+
+const SERVICE_NAME: &str = "lemon-chess";
+
+/// Prints log events of level info and above to stdout
+/// Also exports traces over OTLP/HTTP if OTEL_EXPORTER_OTLP_ENDPOINT (e.g. http://collector:4318) is set
+/// The other OTEL_* variables like OTEL_SERVICE_NAME are respected as well
+/// The returned provider has to be shut down before exiting, so the last spans are sent
+pub fn setup() -> Option<SdkTracerProvider> {
+    let provider = setup_export();
+    let export_layer = provider
+        .as_ref()
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME)));
+    tracing_subscriber::registry()
+        .with(fmt::layer().with_filter(LevelFilter::INFO))
+        .with(export_layer)
+        .init();
+    provider
+}
+
+fn setup_export() -> Option<SdkTracerProvider> {
+    if env::var("OTEL_EXPORTER_OTLP_ENDPOINT").is_err()
+        && env::var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").is_err()
+    {
+        return None;
+    }
+
+    let exporter = match SpanExporter::builder().with_http().build() {
+        Ok(exporter) => exporter,
+        Err(error) => {
+            println!("Failed to set up the trace export: {}", error);
+            return None;
+        }
+    };
+    let mut resource = Resource::builder();
+    if env::var("OTEL_SERVICE_NAME").is_err() {
+        resource = resource.with_service_name(SERVICE_NAME);
+    }
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource.build())
+        .build();
+
+    // Lets callers like the Discord bot continue their trace with a traceparent header
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    println!("Exporting traces");
+    Some(provider)
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// Runs every request in its own span, a child of the caller's span if it sent one
+/// Without trace export the spans aren't recorded anywhere
+pub async fn trace_request(request: Request, next: Next) -> Response {
+    let span = info_span!(
+        "request",
+        otel.name = format!("{} {}", request.method(), request.uri().path()),
+        http.request.method = %request.method(),
+        url.path = request.uri().path(),
+        http.response.status_code = field::Empty,
+    );
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    let _ = span.set_parent(parent);
+
+    let response = next.run(request).instrument(span.clone()).await;
+    span.record("http.response.status_code", response.status().as_u16());
+    response
+}
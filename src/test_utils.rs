@@ -0,0 +1,111 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+};
+use http_body_util::BodyExt;
+use mongodb::bson::oid::ObjectId;
+use serde_json::Value;
+use tokio_util::task::TaskTracker;
+use tower::ServiceExt;
+
+use crate::{
+    app,
+    entities::{session::Session, user::User},
+    game::state::GameState,
+    locks::LockManager,
+    middleware::{catch_panic::PanicHandler, rate_limit::RateLimiter},
+    models::enums::{PermissionLevel, Platform},
+    notifications::Notifier,
+    presence::PresenceTracker,
+    scheduler::SchedulerMetrics,
+    storage::memory::MemoryStorage,
+    utils::random::GameRng,
+    AppState,
+};
+
+/// In-memory storage and buckets, the randomness is always seeded the same
+pub fn test_state() -> AppState {
+    AppState {
+        storage: Arc::new(MemoryStorage::new()),
+        rate_limiter: RateLimiter::setup(None),
+        locks: LockManager::setup(None),
+        tasks: TaskTracker::new(),
+        notifier: Notifier::new(),
+        presence: PresenceTracker::new_in_memory(),
+        scheduler: SchedulerMetrics::new(),
+        panics: PanicHandler::new(),
+        rng: GameRng::seeded(0),
+    }
+}
+
+/// Returns the API key of the new user, the name doubles as Discord id
+pub async fn create_user(state: &AppState, name: &str) -> String {
+    User::new_from_platform(&*state.storage, "", Platform::DISCORD, name, name, name)
+        .await
+        .unwrap()
+        .key
+}
+
+pub async fn set_permission(state: &AppState, key: &str, permission: PermissionLevel) {
+    let user = state.storage.set_user_permission(key, permission).await;
+    assert!(user.unwrap().is_some());
+}
+
+/// A running game from the start position, already saved with an id
+pub async fn create_session(state: &AppState, keys: [String; 2]) -> Session {
+    let mut session = Session::new("Test".to_string(), keys, GameState::new().unwrap());
+    session.id = Some(ObjectId::new());
+    session.save(&state.storage, &state.tasks).await.unwrap();
+    session
+}
+
+pub async fn send(state: &AppState, method: Method, uri: &str, key: &str) -> (StatusCode, Value) {
+    send_with_headers(state, method, uri, key, &[]).await
+}
+
+pub async fn send_with_headers(
+    state: &AppState,
+    method: Method,
+    uri: &str,
+    key: &str,
+    headers: &[(&str, &str)],
+) -> (StatusCode, Value) {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("x-api-key", key);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    respond(state, request.body(Body::empty()).unwrap()).await
+}
+
+pub async fn send_json(
+    state: &AppState,
+    method: Method,
+    uri: &str,
+    key: &str,
+    body: Value,
+) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("x-api-key", key)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    respond(state, request).await
+}
+
+/// Null for bodies which aren't JSON, e.g. images
+pub async fn respond(state: &AppState, request: Request<Body>) -> (StatusCode, Value) {
+    let response = app(state.clone()).oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
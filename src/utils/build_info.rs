@@ -0,0 +1,2 @@
+// Written by build.rs, describes the build the server is running
+include!(concat!(env!("OUT_DIR"), "/built.rs"));
@@ -0,0 +1,47 @@
+use axum::http::{header, HeaderMap};
+use sha2::{Digest, Sha256};
+
+/// A strong entity tag over all given parts
+pub fn generate(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        // Separator, so ["ab", "c"] and ["a", "bc"] don't collide
+        hasher.update([0]);
+    }
+    format!("\"{}\"", hex::encode(&hasher.finalize()[..16]))
+}
+
+/// If the client already has the representation with the given entity tag
+pub fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == etag || tag == "*")
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn test_if_none_match() {
+        let etag = generate(&["a", "b"]);
+        assert_ne!(etag, generate(&["ab", ""]));
+
+        let mut headers = HeaderMap::new();
+        assert!(!if_none_match(&headers, &etag));
+
+        headers.insert(
+            header::IF_NONE_MATCH,
+            HeaderValue::from_str(&format!("\"other\", W/{}", etag)).unwrap(),
+        );
+        assert!(if_none_match(&headers, &etag));
+        assert!(!if_none_match(&headers, "\"different\""));
+    }
+}
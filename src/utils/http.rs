@@ -0,0 +1,222 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use lazy_static::lazy_static;
+use reqwest::{redirect, Client, ClientBuilder, Response, Url};
+use serde::de::DeserializeOwned;
+use tokio::net::lookup_host;
+
+use crate::error::ApiError;
+
+/// How long a whole request may take, including connecting
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Responses are read into memory, the biggest monthly archives are a few MB
+const MAX_RESPONSE_SIZE: usize = 32 * 1024 * 1024;
+
+const USER_AGENT: &str = concat!("lemon-chess/", env!("CARGO_PKG_VERSION"));
+
+lazy_static! {
+    static ref CLIENT: Client = client_builder()
+        .build()
+        .expect("Failed to build the HTTP client");
+}
+
+fn client_builder() -> ClientBuilder {
+    Client::builder()
+        .https_only(true)
+        .timeout(REQUEST_TIMEOUT)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .user_agent(USER_AGENT)
+        .redirect(redirect::Policy::limited(5))
+}
+
+fn request_error(url: &str, error: impl ToString) -> ApiError {
+    ApiError::ServerError(format!("Request to {} failed: {}", url, error.to_string()))
+}
+
+/// Splits an https URL into host and path, the path without its leading slash
+pub fn split_https_url(url: &str) -> Option<(&str, &str)> {
+    let rest = url.strip_prefix("https://")?;
+    let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+    (!host.is_empty()).then_some((host, path))
+}
+
+/// Addresses a request of a user supplied URL must never reach, like our own loopback or private network
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            // 100.64.0.0/10 is shared address space of carrier-grade NATs
+            let shared = first == 100 && (second & 0b1100_0000) == 64;
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || shared)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+/// Resolves the host of the URL and refuses it if any of its addresses isn't public
+/// The addresses are returned so the request can't be pointed somewhere else by resolving again
+async fn resolve_public_host(url: &Url) -> Result<(String, Vec<SocketAddr>), String> {
+    let host = url.host_str().ok_or("URL has no host")?.to_string();
+    let port = url.port_or_known_default().ok_or("URL has no port")?;
+    let addresses: Vec<SocketAddr> = lookup_host((host.trim_matches(['[', ']']), port))
+        .await
+        .map_err(|err| format!("failed to resolve host: {}", err))?
+        .collect();
+    if addresses.is_empty() {
+        return Err("host has no addresses".to_string());
+    }
+    if addresses.iter().any(|address| !is_public_ip(address.ip())) {
+        return Err("host resolves to a non-public address".to_string());
+    }
+    Ok((host, addresses))
+}
+
+/// Reads the body into memory, bodies above the limit are an error
+async fn read_body(mut response: Response, limit: usize) -> Result<Vec<u8>, String> {
+    if response
+        .content_length()
+        .is_some_and(|length| length > limit as u64)
+    {
+        return Err(format!("response is larger than {} bytes", limit));
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|err| err.to_string())? {
+        if body.len() + chunk.len() > limit {
+            return Err(format!("response is larger than {} bytes", limit));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Fetches an https URL and parses the JSON response
+/// Status 404 results in a NotFound error
+pub async fn get_json<T: DeserializeOwned>(url: &str) -> Result<T, ApiError> {
+    let response = CLIENT
+        .get(url)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .send()
+        .await
+        .map_err(|err| request_error(url, err))?;
+    match response.status().as_u16() {
+        200 => {
+            let body = read_body(response, MAX_RESPONSE_SIZE)
+                .await
+                .map_err(|err| request_error(url, err))?;
+            serde_json::from_slice(&body).map_err(|err| request_error(url, err))
+        }
+        404 => Err(ApiError::NotFound(format!("{} was not found", url))),
+        status => Err(request_error(url, format!("status {}", status))),
+    }
+}
+
+/// Posts a JSON body to a user supplied https URL, every 2xx status counts as success
+/// The host is resolved right before the request and refused if it isn't public, redirects aren't followed
+pub async fn post_json(url: &str, body: &str, headers: &[(&str, &str)]) -> Result<(), ApiError> {
+    let parsed = Url::parse(url).map_err(|err| request_error(url, err))?;
+    let (host, addresses) = resolve_public_host(&parsed)
+        .await
+        .map_err(|err| request_error(url, err))?;
+    let client = client_builder()
+        .redirect(redirect::Policy::none())
+        .resolve_to_addrs(&host, &addresses)
+        .build()
+        .map_err(|err| request_error(url, err))?;
+
+    let mut request = client
+        .post(parsed)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body.to_string());
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|err| request_error(url, err))?;
+    match response.status().as_u16() {
+        200..=299 => Ok(()),
+        status => Err(request_error(url, format!("status {}", status))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_https_url() {
+        assert_eq!(
+            split_https_url("https://example.com"),
+            Some(("example.com", ""))
+        );
+        assert_eq!(
+            split_https_url("https://example.com/hooks/1?a=b"),
+            Some(("example.com", "hooks/1?a=b"))
+        );
+        assert_eq!(split_https_url("http://example.com/hooks"), None);
+        assert_eq!(split_https_url("https:///hooks"), None);
+    }
+
+    #[test]
+    fn test_is_public_ip() {
+        for ip in ["1.1.1.1", "93.184.216.34", "2606:4700::1111"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refuse_private_hosts() {
+        for url in ["https://localhost/hooks", "https://127.0.0.1:8443/hooks"] {
+            let parsed = Url::parse(url).unwrap();
+            assert!(resolve_public_host(&parsed).await.is_err(), "{}", url);
+            assert!(post_json(url, "{}", &[]).await.is_err(), "{}", url);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_body() {
+        let response = || Response::from(axum::http::Response::new("{}"));
+        assert_eq!(read_body(response(), 2).await.unwrap(), b"{}".to_vec());
+        assert!(read_body(response(), 1).await.is_err());
+    }
+}
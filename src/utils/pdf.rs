@@ -0,0 +1,237 @@
+//! A minimal PDF writer, just enough for text, lines and JPEG images on A4 pages
+
+pub const PAGE_WIDTH: f32 = 595.0;
+pub const PAGE_HEIGHT: f32 = 842.0;
+
+/// A JPEG encoded RGB image, PDF viewers can decode those natively
+pub struct JpegImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+/// A single page, coordinates are in points with the origin at the bottom left
+#[derive(Default)]
+pub struct PdfPage {
+    content: String,
+    images: Vec<JpegImage>,
+}
+
+impl PdfPage {
+    pub fn text(&mut self, x: f32, y: f32, size: f32, text: &str) {
+        self.content.push_str(&format!(
+            "BT /F1 {} Tf {} {} Td ({}) Tj ET\n",
+            size,
+            x,
+            y,
+            escape_text(text)
+        ));
+    }
+
+    /// Draws connected line segments through all points
+    pub fn polyline(&mut self, points: &[(f32, f32)], width: f32) {
+        let Some(((start_x, start_y), rest)) = points.split_first() else {
+            return;
+        };
+        self.content
+            .push_str(&format!("{} w {} {} m", width, start_x, start_y));
+        for (x, y) in rest {
+            self.content.push_str(&format!(" {} {} l", x, y));
+        }
+        self.content.push_str(" S\n");
+    }
+
+    pub fn rectangle(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.content
+            .push_str(&format!("0.5 w {} {} {} {} re S\n", x, y, width, height));
+    }
+
+    pub fn image(&mut self, x: f32, y: f32, width: f32, height: f32, image: JpegImage) {
+        self.content.push_str(&format!(
+            "q {} 0 0 {} {} {} cm /Im{} Do Q\n",
+            width,
+            height,
+            x,
+            y,
+            self.images.len()
+        ));
+        self.images.push(image);
+    }
+}
+
+#[derive(Default)]
+pub struct PdfDocument {
+    pages: Vec<PdfPage>,
+}
+
+impl PdfDocument {
+    pub fn add_page(&mut self, page: PdfPage) {
+        self.pages.push(page);
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = ObjectWriter::default();
+
+        // 1: catalog, 2: page tree, 3: font, everything else follows per page
+        let mut next_id = 4;
+        let mut page_ids = Vec::new();
+        for page in &self.pages {
+            page_ids.push(next_id);
+            next_id += 2 + page.images.len();
+        }
+
+        writer.object(1, b"<< /Type /Catalog /Pages 2 0 R >>");
+        let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+        writer.object(
+            2,
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                kids.join(" "),
+                page_ids.len()
+            )
+            .as_bytes(),
+        );
+        writer.object(
+            3,
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>",
+        );
+
+        for (page, page_id) in self.pages.iter().zip(page_ids) {
+            let content_id = page_id + 1;
+            let image_ids: Vec<usize> = (0..page.images.len())
+                .map(|index| content_id + 1 + index)
+                .collect();
+
+            let x_objects: Vec<String> = image_ids
+                .iter()
+                .enumerate()
+                .map(|(index, id)| format!("/Im{} {} 0 R", index, id))
+                .collect();
+            writer.object(
+                page_id,
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Contents {} 0 R /Resources << /Font << /F1 3 0 R >> /XObject << {} >> >> >>",
+                    PAGE_WIDTH,
+                    PAGE_HEIGHT,
+                    content_id,
+                    x_objects.join(" ")
+                )
+                .as_bytes(),
+            );
+            writer.stream(content_id, "", page.content.as_bytes());
+
+            for (image, id) in page.images.iter().zip(image_ids) {
+                writer.stream(
+                    id,
+                    &format!(
+                        "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /DCTDecode",
+                        image.width, image.height
+                    ),
+                    &image.data,
+                );
+            }
+        }
+
+        writer.finish(1)
+    }
+}
+
+#[derive(Default)]
+struct ObjectWriter {
+    buffer: Vec<u8>,
+    /// Byte offsets by object id
+    offsets: Vec<(usize, usize)>,
+}
+
+impl ObjectWriter {
+    fn header(&mut self) {
+        if self.buffer.is_empty() {
+            self.buffer.extend_from_slice(b"%PDF-1.4\n");
+        }
+    }
+
+    fn object(&mut self, id: usize, body: &[u8]) {
+        self.header();
+        self.offsets.push((id, self.buffer.len()));
+        self.buffer
+            .extend_from_slice(format!("{} 0 obj\n", id).as_bytes());
+        self.buffer.extend_from_slice(body);
+        self.buffer.extend_from_slice(b"\nendobj\n");
+    }
+
+    fn stream(&mut self, id: usize, dictionary: &str, data: &[u8]) {
+        let mut body =
+            format!("<< {} /Length {} >>\nstream\n", dictionary, data.len()).into_bytes();
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\nendstream");
+        self.object(id, &body);
+    }
+
+    fn finish(mut self, root_id: usize) -> Vec<u8> {
+        self.header();
+        self.offsets.sort();
+        let size = self.offsets.len() + 1;
+
+        let xref_offset = self.buffer.len();
+        let mut xref = format!("xref\n0 {}\n0000000000 65535 f \n", size);
+        for (_, offset) in &self.offsets {
+            xref.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        xref.push_str(&format!(
+            "trailer\n<< /Size {} /Root {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            size, root_id, xref_offset
+        ));
+        self.buffer.extend_from_slice(xref.as_bytes());
+        self.buffer
+    }
+}
+
+/// Escapes PDF string delimiters, the standard fonts can only show ASCII reliably
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '(' | ')' | '\\' => {
+                escaped.push('\\');
+                escaped.push(character);
+            }
+            ' '..='~' => escaped.push(character),
+            _ => escaped.push('?'),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_structure() {
+        let mut page = PdfPage::default();
+        page.text(50.0, 800.0, 12.0, "1. e4 (best)");
+        page.polyline(&[(0.0, 0.0), (10.0, 10.0)], 1.0);
+        let mut document = PdfDocument::default();
+        document.add_page(page);
+        document.add_page(PdfPage::default());
+
+        let bytes = document.to_bytes();
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.starts_with("%PDF-1.4"));
+        assert!(text.contains("/Count 2"));
+        assert!(text.contains("(1. e4 \\(best\\)) Tj"));
+        assert!(text.ends_with("%%EOF\n"));
+
+        // Every object has to sit exactly at its xref offset
+        let xref_start = text.rfind("xref\n").unwrap();
+        let offsets: Vec<usize> = text[xref_start..]
+            .lines()
+            .skip(3)
+            .take_while(|line| !line.starts_with("trailer"))
+            .map(|line| line[..10].parse().unwrap())
+            .collect();
+        for (index, offset) in offsets.iter().enumerate() {
+            assert!(text[*offset..].starts_with(&format!("{} 0 obj", index + 1)));
+        }
+    }
+}
@@ -1,10 +1,82 @@
-use rand::{distributions::Uniform, Rng};
+use std::{
+    env,
+    sync::{Arc, Mutex},
+};
 
+use rand::{distributions::Uniform, rngs::StdRng, Rng, SeedableRng};
+
+const CODE_CHARS: &str = "ABCDEFGHKLMNPQRSTUVWXYZ0123456789";
+
+/// For secrets like resignation tokens, which must never be predictable
 pub fn generate_user_friendly_code(length: u32) -> String {
-    let chars = "ABCDEFGHKLMNPQRSTUVWXYZ0123456789";
-    let mut rng = rand::thread_rng();
-    let range = Uniform::new(0, chars.len());
+    sample_code(&mut rand::thread_rng(), length)
+}
+
+fn sample_code(rng: &mut impl Rng, length: u32) -> String {
+    let range = Uniform::new(0, CODE_CHARS.len());
     (0..length)
-        .map(|_| chars.as_bytes()[rng.sample(range)] as char)
+        .map(|_| CODE_CHARS.as_bytes()[rng.sample(range)] as char)
         .collect()
 }
+
+/// Randomness of game setup and AI play: room codes, color assignment and AI move sampling
+/// Seeded with RNG_SEED, tests and replays become deterministic. Secrets never come from it.
+#[derive(Clone)]
+pub struct GameRng {
+    seed: Option<u64>,
+    rng: Arc<Mutex<StdRng>>,
+}
+
+impl GameRng {
+    pub fn from_entropy() -> Self {
+        Self {
+            seed: None,
+            rng: Arc::new(Mutex::new(StdRng::from_entropy())),
+        }
+    }
+
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            rng: Arc::new(Mutex::new(StdRng::seed_from_u64(seed))),
+        }
+    }
+
+    /// Seeded if RNG_SEED is set
+    pub fn from_env() -> Self {
+        match env::var("RNG_SEED").ok().and_then(|seed| seed.parse().ok()) {
+            Some(seed) => Self::seeded(seed),
+            None => Self::from_entropy(),
+        }
+    }
+
+    pub fn gen_bool(&self, probability: f64) -> bool {
+        self.lock().gen_bool(probability)
+    }
+
+    pub fn generate_code(&self, length: u32) -> String {
+        sample_code(&mut *self.lock(), length)
+    }
+
+    /// An independent generator for the given context, e.g. the position the AI moves in
+    /// When seeded it only depends on the seed and the context, so work done in the background can't shift the sequence
+    pub fn fork(&self, context: &str) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed ^ fnv1a(context.as_bytes())),
+            None => StdRng::from_entropy(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, StdRng> {
+        self.rng
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Stable across Rust versions unlike the hasher of the standard library
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -1,4 +1,219 @@
+use std::{collections::HashMap, env};
+
+use finl_unicode::grapheme_clusters::Graphemes;
+use lazy_static::lazy_static;
 use rustrict::CensorStr;
+use serde::{de::DeserializeOwned, Deserialize};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::error::ApiError;
+
+lazy_static! {
+    /// Deployment wide policy, configured through the SANITIZE_* environment variables
+    static ref DEFAULT_POLICY: SanitizePolicy = SanitizePolicy::from_env();
+    /// Per namespace overrides, given as JSON object in SANITIZE_POLICIES
+    static ref NAMESPACE_POLICIES: HashMap<String, SanitizePolicy> = env::var("SANITIZE_POLICIES")
+        .ok()
+        .map(|policies| {
+            serde_json::from_str(&policies).expect("SANITIZE_POLICIES is not a valid policy map.")
+        })
+        .unwrap_or_default();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Charset {
+    /// Everything except control characters
+    Unicode,
+    /// Printable ASCII characters
+    Ascii,
+    /// ASCII letters, digits, spaces, dashes and underscores
+    Alphanumeric,
+}
+
+impl Charset {
+    fn allows(&self, character: char) -> bool {
+        match self {
+            Charset::Unicode => !character.is_control(),
+            Charset::Ascii => character.is_ascii() && !character.is_ascii_control(),
+            Charset::Alphanumeric => {
+                character.is_ascii_alphanumeric() || matches!(character, ' ' | '-' | '_')
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfanityAction {
+    Allow,
+    /// Replaces profanity with asterisks
+    Mask,
+    /// Rejects the whole request
+    Reject,
+}
+
+/// Invisible characters which could hide profanity or make names look alike, they are always stripped
+const ZERO_WIDTH_CHARACTERS: [char; 6] = [
+    '\u{00AD}', // Soft hyphen
+    '\u{180E}', // Mongolian vowel separator
+    '\u{200B}', // Zero width space
+    '\u{2060}', // Word joiner
+    '\u{2063}', // Invisible separator
+    '\u{FEFF}', // Zero width no-break space
+];
+
+/// Kept since emoji sequences and some scripts need them, but ignored when looking for blocked words
+const JOINERS: [char; 2] = ['\u{200C}', '\u{200D}'];
+
+/// How strictly user provided text is sanitized
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SanitizePolicy {
+    pub charset: Charset,
+    /// Applies Unicode NFKC normalization, so look-alike forms like fullwidth letters become the regular ones
+    pub normalize: bool,
+    /// Maximum length of user and display names in graphemes
+    pub max_name_length: usize,
+    pub max_room_name_length: usize,
+    /// What happens to profanity in publicly visible text
+    pub profanity: ProfanityAction,
+    /// Words treated as profanity on top of the built-in list, matched case insensitively
+    pub blocked_words: Vec<String>,
+}
+
+impl Default for SanitizePolicy {
+    fn default() -> Self {
+        Self {
+            charset: Charset::Unicode,
+            normalize: true,
+            max_name_length: 64,
+            max_room_name_length: 64,
+            profanity: ProfanityAction::Mask,
+            blocked_words: Vec::new(),
+        }
+    }
+}
+
+impl SanitizePolicy {
+    fn from_env() -> Self {
+        let default = Self::default();
+        fn parse<T: DeserializeOwned>(name: &str) -> Option<T> {
+            env::var(name).ok().map(|value| {
+                serde_json::from_value(serde_json::Value::String(value))
+                    .unwrap_or_else(|_| panic!("{} has an invalid value.", name))
+            })
+        }
+        let parse_length = |name: &str| {
+            env::var(name).ok().map(|value| {
+                value
+                    .parse::<usize>()
+                    .unwrap_or_else(|_| panic!("{} has to be a number.", name))
+            })
+        };
+
+        Self {
+            charset: parse("SANITIZE_CHARSET").unwrap_or(default.charset),
+            normalize: parse("SANITIZE_NORMALIZE").unwrap_or(default.normalize),
+            max_name_length: parse_length("SANITIZE_MAX_NAME_LENGTH")
+                .unwrap_or(default.max_name_length),
+            max_room_name_length: parse_length("SANITIZE_MAX_ROOM_NAME_LENGTH")
+                .unwrap_or(default.max_room_name_length),
+            profanity: parse("SANITIZE_PROFANITY").unwrap_or(default.profanity),
+            // Comma separated, e.g. SANITIZE_BLOCKED_WORDS=lime,sour
+            blocked_words: env::var("SANITIZE_BLOCKED_WORDS")
+                .map(|words| {
+                    words
+                        .split(',')
+                        .map(|word| word.trim().to_lowercase())
+                        .filter(|word| !word.is_empty())
+                        .collect()
+                })
+                .unwrap_or(default.blocked_words),
+        }
+    }
+
+    /// Parses the SANITIZE_* environment variables, panics if they are invalid
+    pub fn load_all() {
+        lazy_static::initialize(&DEFAULT_POLICY);
+        lazy_static::initialize(&NAMESPACE_POLICIES);
+    }
+
+    /// The policy of the given namespace, falling back to the deployment wide one
+    pub fn for_namespace(namespace: &str) -> &'static Self {
+        NAMESPACE_POLICIES.get(namespace).unwrap_or(&DEFAULT_POLICY)
+    }
+
+    /// Normalizes the text, strips zero width and disallowed characters and limits the length
+    pub fn clean(&self, input: &str, max_length: usize) -> String {
+        let normalized: String = match self.normalize {
+            true => input.nfkc().collect(),
+            false => input.to_string(),
+        };
+        let filtered: String = normalized
+            .chars()
+            .filter(|character| !ZERO_WIDTH_CHARACTERS.contains(character))
+            .filter(|character| self.charset.allows(*character))
+            .collect();
+        limit_string(filtered.trim(), max_length)
+    }
+
+    /// Unique names are normalized and lowercased, then only ASCII letters, digits, dashes and underscores are allowed
+    /// Unlike clean, nothing is stripped or cut, so the user gets exactly the name they asked for
+    pub fn unique_name(&self, input: &str) -> Result<String, ApiError> {
+        let name = input.trim().nfkc().collect::<String>().to_lowercase();
+        if name.is_empty() {
+            return Err(ApiError::BadRequest("Invalid user name.".to_string()));
+        }
+        if name.chars().count() > self.max_name_length {
+            return Err(ApiError::BadRequest(format!(
+                "User names can't be longer than {} characters.",
+                self.max_name_length
+            )));
+        }
+        if !name
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || matches!(character, '-' | '_'))
+        {
+            return Err(ApiError::BadRequest(
+                "User names can only contain letters, digits, dashes and underscores.".to_string(),
+            ));
+        }
+        Ok(name)
+    }
+
+    /// Like clean, but also handles profanity since the text is shown to other people
+    pub fn clean_public(&self, input: &str, max_length: usize) -> Result<String, ApiError> {
+        let cleaned = self.clean(input, max_length);
+        match self.profanity {
+            ProfanityAction::Allow => Ok(cleaned),
+            ProfanityAction::Mask => {
+                // The censored text loses diacritics, so clean text is kept as it is
+                let censored = match cleaned.as_str().is_inappropriate() {
+                    true => profanity(&cleaned),
+                    false => cleaned,
+                };
+                Ok(mask_words(&censored, &self.blocked_words))
+            }
+            ProfanityAction::Reject => {
+                if cleaned.as_str().is_inappropriate()
+                    || !find_words(&cleaned, &self.blocked_words).is_empty()
+                {
+                    Err(ApiError::BadRequest(
+                        "Text contains inappropriate language.".to_string(),
+                    ))
+                } else {
+                    Ok(cleaned)
+                }
+            }
+        }
+    }
+}
+
+/// Shared by all query models carrying user provided text
+pub trait Sanitize: Sized {
+    fn sanitize(&self, policy: &SanitizePolicy) -> Result<Self, ApiError>;
+}
 
 pub fn alphanumeric(input: &str) -> String {
     input
@@ -7,14 +222,188 @@ pub fn alphanumeric(input: &str) -> String {
         .collect()
 }
 
+/// Counts graphemes instead of bytes or chars, so accents and emoji are never cut in half
+/// Limits too small to fit the ellipsis cut the text without one
 pub fn limit_string(input: &str, size: usize) -> String {
-    if input.len() > size && size > 3 {
-        format!("{}...", &input[0..size - 3])
-    } else {
+    if Graphemes::new(input).count() <= size {
         input.to_string()
+    } else if size <= 3 {
+        Graphemes::new(input).take(size).collect()
+    } else {
+        format!(
+            "{}...",
+            Graphemes::new(input).take(size - 3).collect::<String>()
+        )
     }
 }
 
+/// Char ranges of all case insensitive occurrences of the words
+fn find_words(input: &str, words: &[String]) -> Vec<(usize, usize)> {
+    let (indices, lowered): (Vec<usize>, Vec<char>) = input
+        .chars()
+        .enumerate()
+        .filter(|(_, character)| !JOINERS.contains(character))
+        .map(|(index, character)| (index, character.to_lowercase().next().unwrap_or(character)))
+        .unzip();
+    let mut ranges = Vec::new();
+    for word in words {
+        let word: Vec<char> = word.to_lowercase().chars().collect();
+        if word.is_empty() || word.len() > lowered.len() {
+            continue;
+        }
+        for start in 0..=lowered.len() - word.len() {
+            let end = start + word.len();
+            if lowered[start..end] == word[..] {
+                ranges.push((indices[start], indices[end - 1] + 1));
+            }
+        }
+    }
+    ranges
+}
+
+/// Replaces the blocked words with asterisks like the built-in profanity filter
+fn mask_words(input: &str, words: &[String]) -> String {
+    let ranges = find_words(input, words);
+    input
+        .chars()
+        .enumerate()
+        .map(|(index, character)| {
+            match ranges
+                .iter()
+                .any(|(start, end)| (*start..*end).contains(&index))
+            {
+                true => '*',
+                false => character,
+            }
+        })
+        .collect()
+}
+
 pub fn profanity(input: &str) -> String {
     input.censor()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_string() {
+        assert_eq!(limit_string("lemonchess", 7), "lemo...");
+        assert_eq!(limit_string("zitrönenjoghurt", 7), "zitr...");
+        assert_eq!(limit_string("lemon", 7), "lemon");
+        // Combining accents and emoji count as one character and are never split
+        assert_eq!(limit_string("zitro\u{308}nenjoghurt", 7), "zitr...");
+        assert_eq!(
+            limit_string("ze\u{301}\u{301}stes", 5),
+            "ze\u{301}\u{301}..."
+        );
+        assert_eq!(limit_string("🍋🍋🍋🍋🍋🍋", 6), "🍋🍋🍋🍋🍋🍋");
+        assert_eq!(limit_string("🍋🍋🍋🍋🍋🍋", 3), "🍋🍋🍋");
+        assert_eq!(limit_string("lemon", 0), "");
+    }
+
+    /// Random strings mixing ASCII, umlauts, CJK, combining accents and emoji sequences
+    #[test]
+    fn test_limit_string_properties() {
+        use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+        let pieces = [
+            "a",
+            "Z",
+            " ",
+            "ö",
+            "ß",
+            "語",
+            "e\u{301}",
+            "🍋",
+            "👍🏽",
+            "👨\u{200D}👩\u{200D}👧",
+            "🇩🇪",
+        ];
+        let mut rng = StdRng::seed_from_u64(1186);
+        for _ in 0..500 {
+            let length = rng.gen_range(0..20);
+            let input: String = (0..length)
+                .map(|_| *pieces.choose(&mut rng).unwrap())
+                .collect();
+            let graphemes: Vec<&str> = Graphemes::new(&input).collect();
+            for size in 0..24 {
+                let limited = limit_string(&input, size);
+                if graphemes.len() <= size {
+                    assert_eq!(limited, input);
+                    continue;
+                }
+                if size <= 3 {
+                    assert_eq!(limited, graphemes[..size].concat());
+                    continue;
+                }
+
+                // Whole graphemes of the input followed by the ellipsis, within the limit
+                let kept = graphemes[..size - 3].concat();
+                assert_eq!(limited, format!("{}...", kept));
+                assert_eq!(Graphemes::new(&limited).count(), size);
+                assert_eq!(limit_string(&limited, size), limited);
+            }
+        }
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = SanitizePolicy {
+            charset: Charset::Alphanumeric,
+            max_name_length: 10,
+            max_room_name_length: 10,
+            profanity: ProfanityAction::Reject,
+            blocked_words: vec!["lime".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(policy.clean(" Lemon\u{0}Chess! ", 64), "LemonChess");
+        assert_eq!(policy.clean_public("Lemon Room", 64).unwrap(), "Lemon Room");
+        assert!(policy.clean_public("fuck", 64).is_err());
+        assert!(policy.clean_public("Sour LIME", 64).is_err());
+        // Fullwidth letters become ASCII instead of being dropped by the charset
+        assert_eq!(policy.clean("Ｌｅｍｏｎ", 64), "Lemon");
+
+        let policy = SanitizePolicy {
+            blocked_words: vec!["lime".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(policy.clean("Zitrönen", 64), "Zitrönen");
+        assert_eq!(
+            policy.clean_public("Zitrönen 🍋", 64).unwrap(),
+            "Zitrönen 🍋"
+        );
+        assert_eq!(policy.clean("Zitro\u{308}nen", 64), "Zitrönen");
+        assert_ne!(policy.clean_public("fuck", 64).unwrap(), "fuck");
+        assert_eq!(
+            policy.clean_public("Li\u{200B}me Room", 64).unwrap(),
+            "**** Room"
+        );
+        assert_eq!(policy.clean("\u{FEFF}Lemon\u{200B}", 64), "Lemon");
+        assert_eq!(policy.clean_public("Li\u{200D}me", 64).unwrap(), "*****");
+    }
+
+    #[test]
+    fn test_unique_name() {
+        let policy = SanitizePolicy {
+            max_name_length: 10,
+            ..Default::default()
+        };
+        assert_eq!(policy.unique_name(" Lemon_Lime ").unwrap(), "lemon_lime");
+        assert_eq!(policy.unique_name("Ｌｅｍｏｎ-1").unwrap(), "lemon-1");
+        assert_eq!(policy.unique_name("lemonchess").unwrap(), "lemonchess");
+        assert!(policy.unique_name("lemonchess1").is_err());
+        // The Cyrillic e looks just like the Latin one
+        for name in [
+            "",
+            "lemon chess",
+            "lemon🍋",
+            "l\u{435}mon",
+            "zitrönen",
+            "li\u{200B}me",
+        ] {
+            assert!(policy.unique_name(name).is_err(), "{}", name);
+        }
+    }
+}
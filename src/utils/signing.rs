@@ -0,0 +1,75 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::env;
+
+use crate::error::ApiError;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Shorter secrets can be guessed, which would allow forging signed links
+const MIN_SECRET_LENGTH: usize = 32;
+
+fn get_mac() -> Result<HmacSha256, ApiError> {
+    let secret = env::var("SIGNING_SECRET")
+        .map_err(|_| ApiError::ServerError("Signing secret not set.".to_string()))?;
+    validate_secret(&secret)?;
+    mac_from_secret(&secret)
+}
+
+fn validate_secret(secret: &str) -> Result<(), ApiError> {
+    if secret.len() < MIN_SECRET_LENGTH {
+        return Err(ApiError::ServerError(format!(
+            "Signing secret has to be at least {} bytes long.",
+            MIN_SECRET_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+fn mac_from_secret(secret: &str) -> Result<HmacSha256, ApiError> {
+    HmacSha256::new_from_slice(secret.as_bytes())
+        .map_err(|err| ApiError::ServerError(err.to_string()))
+}
+
+/// Fails if SIGNING_SECRET isn't set or too short, signed links can't be created or verified without it
+pub fn check_secret() -> Result<(), ApiError> {
+    get_mac().map(|_| ())
+}
+
+/// Returns the hex encoded HMAC-SHA256 signature of the given message
+pub fn sign(message: &str) -> Result<String, ApiError> {
+    let mut mac = get_mac()?;
+    mac.update(message.as_bytes());
+    Ok(hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Like sign, but with a secret shared with someone else instead of our own
+pub fn sign_with(secret: &str, message: &str) -> Result<String, ApiError> {
+    let mut mac = mac_from_secret(secret)?;
+    mac.update(message.as_bytes());
+    Ok(hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Checks a hex encoded signature in constant time
+pub fn verify(message: &str, signature: &str) -> Result<bool, ApiError> {
+    let signature = match hex::decode(signature) {
+        Ok(signature) => signature,
+        Err(_) => return Ok(false),
+    };
+
+    let mut mac = get_mac()?;
+    mac.update(message.as_bytes());
+    Ok(mac.verify_slice(&signature).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_secret() {
+        assert!(validate_secret("").is_err());
+        assert!(validate_secret("lemon").is_err());
+        assert!(validate_secret(&"l".repeat(MIN_SECRET_LENGTH)).is_ok());
+    }
+}
@@ -1,7 +1,9 @@
-use chrono::{offset::LocalResult, TimeZone};
+use chrono::{offset::LocalResult, NaiveDate, NaiveTime, TimeZone, Utc};
 use chrono_tz::Tz;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+pub const DAY_FORMAT: &str = "%Y-%m-%d";
+
 pub fn timestamp_now_nanos() -> u64 {
     let start_time = SystemTime::now();
     let since_unix = start_time
@@ -19,6 +21,20 @@ pub fn nanos_to_date(nanos: u64, tz: &Tz) -> String {
     }
 }
 
+/// The UTC day of a timestamp like 2024-05-31, the format dates are given in queries
+pub fn nanos_to_day(nanos: u64) -> String {
+    let seconds = (nanos / 1_000_000_000) as i64;
+    match Utc.timestamp_opt(seconds, 0) {
+        LocalResult::Single(datetime) => datetime.format(DAY_FORMAT).to_string(),
+        _ => "Invalid timestamp".to_string(),
+    }
+}
+
+/// UNIX timestamp in nanoseconds of the start of a UTC day, days before 1970 start at 0
+pub fn day_to_nanos(day: NaiveDate) -> u64 {
+    (day.and_time(NaiveTime::MIN).and_utc().timestamp().max(0) as u64).saturating_mul(1_000_000_000)
+}
+
 pub fn nanos_to_date_time(nanos: u64, tz: &Tz) -> String {
     let seconds = (nanos / 1_000_000_000) as i64;
     let nanos_remaining = (nanos % 1_000_000_000) as u32;